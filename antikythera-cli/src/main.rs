@@ -1,16 +1,464 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use antikythera::prelude::*;
-use clap::Parser;
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+
+mod scaffold;
+mod tui;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Suppress log output (results and summaries are still written)
+    #[arg(short, long, global = true, default_value_t = false)]
+    quiet: bool,
+
+    /// Format of log lines written to stderr
+    #[arg(long, value_enum, global = true, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Validate two state JSON files and print what changed between them
+    Diff {
+        /// The original state file
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        /// The edited state file
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+    },
+    /// Suggest monster compositions that give a party a target chance of winning
+    Balance(BalanceArgs),
+    /// Fight every actor in a state against every other 1v1 and write a win-probability matrix
+    Matchups(MatchupArgs),
+    /// Scale one group to each level in a range and report how often it wins at each
+    Levels(LevelArgs),
+    /// Run one shard of a simulation split across processes or machines, writing partial results
+    Shard(ShardArgs),
+    /// Combine the partial results written by `shard` into a single results file
+    Merge(MergeArgs),
+    /// Tune one actor's action and target weights to win more often (experimental)
+    Learn(LearnArgs),
+    /// Write a starter scenario with a party of four and a few monsters to edit from, or with
+    /// `--demo`, the demo state
+    NewScenario(NewScenarioArgs),
+    /// Print the probability of a condition like `actor("Hero").alive && round <= 5` in saved results
+    Query(QueryArgs),
+    /// Report the items, rerolls, HP and hit dice a group spends, on average, in the combats it wins
+    Resources(ResourcesArgs),
+    /// Run every scenario in a directory with the same settings and write a CSV comparing them
+    Batch(BatchArgs),
+    /// Estimate how often a group gets through a skill challenge, like a chase or a heist
+    SkillChallenge(SkillChallengeArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct NewScenarioArgs {
+    /// Name of the scenario. It's written to `<NAME>.json` unless it already ends in `.json`, or
+    /// to stdout if it's `-`.
+    #[arg(value_name = "NAME")]
+    name: String,
+
+    /// Write the demo state that `--demo` runs, policies and all, instead of the starter scenario
+    #[arg(long, default_value_t = false)]
+    demo: bool,
+
+    /// Overwrite the file if it already exists
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct BalanceArgs {
+    /// State JSON file containing the party
+    #[arg(long, value_name = "FILE")]
+    party: PathBuf,
+
+    /// State JSON file whose actors are the monster templates to choose from
+    #[arg(long, value_name = "FILE")]
+    bestiary: PathBuf,
+
+    /// Lowest acceptable party win probability
+    #[arg(long, default_value_t = 0.6)]
+    min: f64,
+
+    /// Highest acceptable party win probability
+    #[arg(long, default_value_t = 0.8)]
+    max: f64,
+
+    /// Number of combats simulated per candidate composition
+    #[arg(short, long, default_value_t = 200)]
+    combats: usize,
+
+    /// Most monsters in a single suggestion
+    #[arg(long, default_value_t = 8)]
+    max_monsters: u32,
+
+    /// Most candidate compositions to simulate
+    #[arg(long, default_value_t = 64)]
+    max_evaluations: usize,
+
+    /// Number of suggestions to print
+    #[arg(short = 'n', long, default_value_t = 5)]
+    suggestions: usize,
+
+    /// Random seed shared by every candidate
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Write the best suggestion's state to this file
+    #[arg(long, value_name = "FILE")]
+    save: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct MatchupArgs {
+    /// State JSON file containing the actors to match up
+    #[arg(short, long, default_value = "state.json", value_name = "FILE")]
+    state: PathBuf,
+
+    /// Number of combats simulated per pair
+    #[arg(short, long, default_value_t = 100)]
+    combats: usize,
+
+    /// Random seed shared by every pair
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Write the win-probability matrix as CSV to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Also write the mean rounds-to-kill matrix as CSV to this file
+    #[arg(long, value_name = "FILE")]
+    rounds_output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct LevelArgs {
+    /// Initial state JSON file path
+    #[arg(short, long, default_value = "state.json", value_name = "FILE")]
+    state: PathBuf,
+
+    /// The group whose actors are scaled
+    #[arg(short, long, default_value_t = 0)]
+    group: u32,
+
+    /// First level to simulate
+    #[arg(long, default_value_t = 1)]
+    from: u32,
+
+    /// Last level to simulate
+    #[arg(long, default_value_t = 20)]
+    to: u32,
+
+    /// Number of combats simulated per level
+    #[arg(short, long, default_value_t = 200)]
+    combats: usize,
+
+    /// Random seed shared by every level
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(clap::Args, Debug)]
+struct ShardArgs {
+    /// Initial state JSON file path
+    #[arg(short, long, default_value = "state.json", value_name = "FILE")]
+    state: PathBuf,
+
+    /// Index of this shard. Every shard of a run needs a different index.
+    #[arg(long)]
+    shard: u64,
+
+    /// Number of combats simulated by this shard
+    #[arg(short, long, default_value_t = 1000)]
+    combats: usize,
+
+    /// Base random seed shared by every shard of the run
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Output file path [default: shard-<SHARD>.partial.json]
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Record every actor's HP at the end of each round
+    #[arg(long, default_value_t = false)]
+    hp_time_series: bool,
+
+    /// Tally every die rolled and report how closely each die size matches a fair die
+    #[arg(long, default_value_t = false)]
+    roll_statistics: bool,
+
+    /// Record the round of each combat's first blood, downs, deaths and end, and report how
+    /// they're distributed
+    #[arg(long, default_value_t = false)]
+    event_timeline: bool,
+
+    /// Tally each actor's damage, healing, kills and assists, and add their share of their
+    /// group's to the summary
+    #[arg(long, default_value_t = false)]
+    contributions: bool,
+
+    /// Count how often each actor used each of its actions and spells, and on which rounds
+    #[arg(long, default_value_t = false)]
+    action_usage: bool,
+
+    /// Label how each combat went for the player party (party wins clean, pyrrhic victory, TPK,
+    /// timeout, ...) and report how often each label came up
+    #[arg(long, default_value_t = false)]
+    outcome_labels: bool,
+
+    /// Report damage and survival by initiative position, and how much more often each side wins
+    /// when it acts first, with 95% confidence intervals
+    #[arg(long, default_value_t = false)]
+    turn_order: bool,
+
+    /// Report each weapon's attacks, hit rate, crits and damage, against each target and AC
+    #[arg(long, default_value_t = false)]
+    weapon_stats: bool,
+
+    /// How much of each combat to keep in the results. Keeping less runs faster
+    #[arg(long, value_enum, default_value_t = TreeModeArg::Full)]
+    tree_mode: TreeModeArg,
+
+    /// Record each action as a single step in the state tree, for smaller trees that read like
+    /// a combat log
+    #[arg(long, default_value_t = false)]
+    batch_actions: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct MergeArgs {
+    /// Partial result files written by `shard`
+    #[arg(value_name = "FILE", required = true)]
+    partials: Vec<PathBuf>,
+
+    /// Output file path
+    #[arg(short, long, default_value = "antikythera-statistics.json")]
+    output: PathBuf,
+
+    /// Format of the summary printed to stdout after merging
+    #[arg(short, long, value_enum, default_value_t = SummaryFormat::Text)]
+    format: SummaryFormat,
+}
+
+#[derive(clap::Args, Debug)]
+struct QueryArgs {
+    /// Results file written by a run or by `merge`
+    #[arg(value_name = "RESULTS")]
+    results: PathBuf,
+
+    /// The condition to check, e.g. `actor("Hero").alive && group(1).wiped`
+    #[arg(value_name = "CONDITION")]
+    condition: String,
+
+    /// Check every state reached, not just the ones combats ended in
+    #[arg(long, default_value_t = false)]
+    all_states: bool,
+
+    /// Print the exact count of matching states over the total, like `37/50`
+    #[arg(long, default_value_t = false)]
+    exact: bool,
+
+    /// Value for a `$name` parameter in the condition, as `name=value`
+    #[arg(short, long = "param", value_name = "NAME=VALUE", value_parser = parse_param)]
+    params: Vec<(String, String)>,
+}
+
+fn parse_param(param: &str) -> anyhow::Result<(String, String)> {
+    let (name, value) = param
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Expected NAME=VALUE, got `{}`", param))?;
+    Ok((name.trim_start_matches('$').to_string(), value.to_string()))
+}
+
+#[derive(clap::Args, Debug)]
+struct ResourcesArgs {
+    /// Results file written by a run or by `merge`
+    #[arg(value_name = "RESULTS")]
+    results: PathBuf,
+
+    /// The group whose victories are looked at
+    #[arg(short, long, default_value_t = 0)]
+    group: u32,
+}
+
+#[derive(clap::Args, Debug)]
+struct BatchArgs {
+    /// Directory of state JSON files; every `.json` file in it is run
+    #[arg(value_name = "DIR")]
+    dir: PathBuf,
+
+    /// Number of combats simulated per scenario
+    #[arg(short, long, default_value_t = 1000)]
+    combats: usize,
+
+    /// Random seed shared by every scenario
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Write the comparison as CSV to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct SkillChallengeArgs {
+    /// State JSON file containing the party
+    #[arg(short, long, default_value = "state.json", value_name = "FILE")]
+    state: PathBuf,
+
+    /// The group attempting the challenge
+    #[arg(short, long, default_value_t = 0)]
+    group: u32,
+
+    /// A check as `SKILL:DC`, or `SKILL/SKILL:DC` to allow either skill. Repeat for a series of
+    /// checks, which starts over from the first if the challenge outlasts it
+    #[arg(long = "check", value_name = "SKILLS:DC", value_parser = parse_check, required = true)]
+    checks: Vec<ChallengeCheck>,
+
+    /// Successes needed to complete the challenge
+    #[arg(long, default_value_t = 3)]
+    successes: u32,
+
+    /// Failures that end the challenge in defeat
+    #[arg(long, default_value_t = 3)]
+    failures: u32,
+
+    /// Who attempts each check
+    #[arg(long, value_enum, default_value_t = StrategyArg::Best)]
+    strategy: StrategyArg,
+
+    /// Number of times the challenge is simulated
+    #[arg(short, long, default_value_t = 10000)]
+    trials: usize,
+
+    /// Random seed for the whole run
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+fn parse_check(check: &str) -> anyhow::Result<ChallengeCheck> {
+    let (skills, dc) = check
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Expected SKILLS:DC, got `{}`", check))?;
+    let skills = skills
+        .split('/')
+        .map(|name| {
+            let normalized: String = name
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase();
+            Skill::all()
+                .into_iter()
+                .find(|skill| format!("{:?}", skill).to_lowercase() == normalized)
+                .ok_or_else(|| anyhow::anyhow!("Unknown skill `{}`", name))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let dc = dc
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid DC `{}`", dc))?;
+    Ok(ChallengeCheck { skills, dc })
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum StrategyArg {
+    /// Whoever has the highest modifier
+    Best,
+    /// Whoever has the highest modifier, with advantage from a proficient helper
+    BestWithHelp,
+    /// Everyone takes turns
+    RoundRobin,
+    /// Someone at random
+    Random,
+}
+
+impl From<StrategyArg> for AssignmentStrategy {
+    fn from(value: StrategyArg) -> Self {
+        match value {
+            StrategyArg::Best => AssignmentStrategy::BestModifier,
+            StrategyArg::BestWithHelp => AssignmentStrategy::BestWithHelp,
+            StrategyArg::RoundRobin => AssignmentStrategy::RoundRobin,
+            StrategyArg::Random => AssignmentStrategy::Random,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct LearnArgs {
+    /// Initial state JSON file path
+    #[arg(short, long, default_value = "state.json", value_name = "FILE")]
+    state: PathBuf,
+
+    /// ID of the actor whose policy is tuned
+    #[arg(long)]
+    actor: u32,
+
+    /// Number of optimization iterations
+    #[arg(long, default_value_t = 10)]
+    iterations: usize,
+
+    /// Candidate policies simulated per iteration
+    #[arg(long, default_value_t = 16)]
+    population: usize,
+
+    /// Best candidates kept from each iteration
+    #[arg(long, default_value_t = 4)]
+    elites: usize,
+
+    /// Number of combats simulated per candidate
+    #[arg(short, long, default_value_t = 100)]
+    combats: usize,
+
+    /// Largest weight an action or target can be given
+    #[arg(long, default_value_t = 100)]
+    max_weight: i32,
+
+    /// Random seed for the whole search
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Write the learned policy to this file
+    #[arg(
+        short,
+        long,
+        default_value = "learned-policy.json",
+        value_name = "FILE"
+    )]
+    output: PathBuf,
+
+    /// Also write the state with the learned policy applied to this file
+    #[arg(long, value_name = "FILE")]
+    save_state: Option<PathBuf>,
+}
+
+/// Runs the simulation (the default when no subcommand is given)
+#[derive(clap::Args, Debug)]
+struct RunArgs {
     /// Initial state JSON file path
     #[arg(short, long, default_value = "state.json", value_name = "FILE")]
     state: PathBuf,
 
-    /// Use a demo state instead of loading from file (for testing)
+    /// Use a demo state instead of loading from file (for testing). `new-scenario --demo -` prints
+    /// it as JSON to start from
     #[arg(long, default_value_t = false, conflicts_with = "state")]
     demo: bool,
 
@@ -18,13 +466,334 @@ struct Args {
     #[arg(short, long, default_value_t = 1000)]
     combats: usize,
 
-    /// Random seed for reproducibility
+    /// Run for this many seconds of wall-clock time instead, however many combats that is
+    #[arg(long, value_name = "SECONDS", conflicts_with = "combats")]
+    time_budget: Option<f64>,
+
+    /// Random seed for reproducibility. Without one, a random seed is picked and recorded with
+    /// the results
     #[arg(long, default_value = None)]
     seed: Option<u64>,
 
     /// Output file path
     #[arg(short, long, default_value = "antikythera-statistics.json")]
     output: PathBuf,
+
+    /// Write a timestamped run directory (input state, manifest, results and summary) under this
+    /// directory instead of writing to `--output`
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    out_dir: Option<PathBuf>,
+
+    /// Record every actor's HP at the end of each round
+    #[arg(long, default_value_t = false)]
+    hp_time_series: bool,
+
+    /// Tally every die rolled and report how closely each die size matches a fair die
+    #[arg(long, default_value_t = false)]
+    roll_statistics: bool,
+
+    /// Record the round of each combat's first blood, downs, deaths and end, and report how
+    /// they're distributed
+    #[arg(long, default_value_t = false)]
+    event_timeline: bool,
+
+    /// Tally each actor's damage, healing, kills and assists, and add their share of their
+    /// group's to the summary
+    #[arg(long, default_value_t = false)]
+    contributions: bool,
+
+    /// Count how often each actor used each of its actions and spells, and on which rounds
+    #[arg(long, default_value_t = false)]
+    action_usage: bool,
+
+    /// Label how each combat went for the player party (party wins clean, pyrrhic victory, TPK,
+    /// timeout, ...) and report how often each label came up
+    #[arg(long, default_value_t = false)]
+    outcome_labels: bool,
+
+    /// Report damage and survival by initiative position, and how much more often each side wins
+    /// when it acts first, with 95% confidence intervals
+    #[arg(long, default_value_t = false)]
+    turn_order: bool,
+
+    /// Report each weapon's attacks, hit rate, crits and damage, against each target and AC
+    #[arg(long, default_value_t = false)]
+    weapon_stats: bool,
+
+    /// How much of each combat to keep in the results. Keeping less runs faster
+    #[arg(long, value_enum, default_value_t = TreeModeArg::Full)]
+    tree_mode: TreeModeArg,
+
+    /// Record each action as a single step in the state tree, for smaller trees that read like
+    /// a combat log
+    #[arg(long, default_value_t = false)]
+    batch_actions: bool,
+
+    /// Soft limit on the state tree's memory use, in megabytes, checked every 100 combats
+    #[arg(long, value_name = "MB")]
+    memory_limit: Option<usize>,
+
+    /// What to do once the state tree outgrows --memory-limit
+    #[arg(long, value_enum, default_value_t = MemoryLimitArg::TerminalOnly)]
+    on_memory_limit: MemoryLimitArg,
+
+    /// How much of each simulated combat to log
+    #[arg(short, long, value_enum, default_value_t = VerbosityArg::Off)]
+    verbosity: VerbosityArg,
+
+    /// With `--verbosity full`, also log the actions and targets each policy weighed for every
+    /// action it chose, and why any were ruled out
+    #[arg(long, default_value_t = false)]
+    decisions: bool,
+
+    /// JSON file of message templates to word the log and summary with, keyed by transition
+    /// type under "transitions" and by log line under "logs"
+    #[arg(long, value_name = "FILE")]
+    messages: Option<PathBuf>,
+
+    /// Show a live dashboard while the simulation runs
+    #[arg(long, default_value_t = false)]
+    tui: bool,
+
+    /// Format of the summary printed to stdout after the run
+    #[arg(short, long, value_enum, default_value_t = SummaryFormat::Text)]
+    format: SummaryFormat,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum VerbosityArg {
+    /// Log nothing per combat
+    Off,
+    /// Log one line per combat
+    Summary,
+    /// Log every transition
+    Full,
+}
+
+impl From<VerbosityArg> for Verbosity {
+    fn from(value: VerbosityArg) -> Self {
+        match value {
+            VerbosityArg::Off => Verbosity::Off,
+            VerbosityArg::Summary => Verbosity::Summary,
+            VerbosityArg::Full => Verbosity::Full,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum TreeModeArg {
+    /// Every state and transition
+    Full,
+    /// Only the states combats end in; enough for the summary and outcome queries
+    TerminalOnly,
+    /// No state tree; only hooks and the recorded statistics see the combats
+    None,
+}
+
+impl From<TreeModeArg> for TreeMode {
+    fn from(value: TreeModeArg) -> Self {
+        match value {
+            TreeModeArg::Full => TreeMode::Full,
+            TreeModeArg::TerminalOnly => TreeMode::TerminalOnly,
+            TreeModeArg::None => TreeMode::None,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum MemoryLimitArg {
+    /// Keep only the states combats end in from then on, and stop if even that is too much
+    TerminalOnly,
+    /// Stop and write out the results so far
+    Stop,
+}
+
+impl From<MemoryLimitArg> for MemoryLimitAction {
+    fn from(value: MemoryLimitArg) -> Self {
+        match value {
+            MemoryLimitArg::TerminalOnly => MemoryLimitAction::TerminalOnly,
+            MemoryLimitArg::Stop => MemoryLimitAction::Stop,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line, with timestamp, level, target and message fields
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SummaryFormat {
+    /// Human-readable report
+    Text,
+    /// One JSON object per actor, one per line
+    Json,
+    /// One CSV row per actor, with a header
+    Csv,
+}
+
+impl SummaryFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            SummaryFormat::Text => "txt",
+            SummaryFormat::Json => "jsonl",
+            SummaryFormat::Csv => "csv",
+        }
+    }
+}
+
+/// Everything needed to reproduce a run, written alongside its results.
+#[derive(serde::Serialize, Debug)]
+struct RunManifest {
+    version: &'static str,
+    started_at: String,
+    /// The state file the run was started from, or `None` for the demo state.
+    source: Option<PathBuf>,
+    seed: Option<u64>,
+    combats: usize,
+    /// Seconds the run was given, if it ran for a time budget instead of `combats`.
+    time_budget: Option<f64>,
+    hp_time_series: bool,
+    roll_statistics: bool,
+    event_timeline: bool,
+    contributions: bool,
+    action_usage: bool,
+    outcome_labels: bool,
+    turn_order: bool,
+    weapon_stats: bool,
+    tree_mode: TreeMode,
+    batch_actions: bool,
+    /// The state tree's memory limit in megabytes, if it had one.
+    memory_limit: Option<usize>,
+    /// The message templates file the log and summary were worded with, if any.
+    messages: Option<PathBuf>,
+}
+
+/// The results of one shard, along with what's needed to rerun it.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct PartialResults {
+    version: String,
+    seed: u64,
+    shard: u64,
+    #[serde(deserialize_with = "deserialize_results")]
+    results: IntegrationResults,
+}
+
+fn deserialize_results<'de, D>(deserializer: D) -> Result<IntegrationResults, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = <serde_json::Value as serde::Deserialize>::deserialize(deserializer)?;
+    IntegrationResults::from_json_value(value).map_err(serde::de::Error::custom)
+}
+
+fn format_summary(
+    summary: &RunSummary,
+    messages: &MessageTemplates,
+    format: SummaryFormat,
+) -> anyhow::Result<String> {
+    let mut out = String::new();
+    match format {
+        SummaryFormat::Text => summary.pretty_print_with_messages(&mut out, messages)?,
+        SummaryFormat::Json => {
+            for actor in &summary.actors {
+                out.push_str(&serde_json::to_string(actor)?);
+                out.push('\n');
+            }
+        }
+        SummaryFormat::Csv => {
+            out.push_str(RunSummary::CSV_HEADER);
+            out.push('\n');
+            summary.write_csv_rows(&mut out)?;
+        }
+    }
+    Ok(out)
+}
+
+/// Logs the roll statistics report, if the run recorded one.
+fn log_roll_statistics(results: &IntegrationResults) -> anyhow::Result<()> {
+    if let Some(statistics) = &results.roll_statistics {
+        let mut report = String::new();
+        statistics.pretty_print(&mut report)?;
+        for line in report.lines() {
+            log::info!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Logs when key events happened across combats, if the run recorded an event timeline.
+fn log_event_timeline(results: &IntegrationResults) -> anyhow::Result<()> {
+    if let Some(timeline) = &results.event_timeline {
+        let mut report = String::new();
+        timeline.pretty_print(&mut report, results.state_tree.initial_state())?;
+        for line in report.lines() {
+            log::info!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Logs how often each actor used each action, if the run counted them.
+fn log_action_usage(results: &IntegrationResults) -> anyhow::Result<()> {
+    if let Some(usage) = &results.action_usage {
+        let mut report = String::new();
+        usage.pretty_print(&mut report, results.state_tree.initial_state())?;
+        for line in report.lines() {
+            log::info!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+fn log_outcome_labels(results: &IntegrationResults) -> anyhow::Result<()> {
+    if let Some(labels) = &results.outcome_labels {
+        let mut report = String::new();
+        labels.pretty_print(&mut report)?;
+        for line in report.lines() {
+            log::info!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Logs how much acting early in initiative mattered, if the run tallied it.
+fn log_turn_order(results: &IntegrationResults) -> anyhow::Result<()> {
+    if let Some(turn_order) = &results.turn_order {
+        let mut report = String::new();
+        turn_order.pretty_print(&mut report, results.state_tree.initial_state())?;
+        for line in report.lines() {
+            log::info!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Logs how each weapon fared, if the run tallied it.
+fn log_weapon_stats(results: &IntegrationResults) -> anyhow::Result<()> {
+    if let Some(weapons) = &results.weapon_stats {
+        let mut report = String::new();
+        weapons.pretty_print(&mut report, results.state_tree.initial_state())?;
+        for line in report.lines() {
+            log::info!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+fn write_json(path: &Path, value: &impl serde::Serialize, pretty: bool) -> anyhow::Result<()> {
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let writer = std::io::BufWriter::new(file);
+    if pretty {
+        serde_json::to_writer_pretty(writer, value)?;
+    } else {
+        serde_json::to_writer(writer, value)?;
+    }
+    Ok(())
 }
 
 pub fn demo_state() -> State {
@@ -53,6 +822,14 @@ pub fn demo_state() -> State {
         .max_health(12)
         .level(1) // 10 + 3 (Chain Mail) + 2 (Shield) + 0 (Dex)
         .weapon_proficiency(WeaponType::Longsword, WeaponProficiency::Proficient)
+        .metadata(Metadata {
+            notes: Some(
+                "Group 0 fights group 1. The policy attacks with the longsword ten times as often \
+                 as it punches, and splits its attacks evenly between the goblins."
+                    .to_string(),
+            ),
+            ..Default::default()
+        })
         .build();
 
     hero.give_item(sword, 1);
@@ -69,6 +846,13 @@ pub fn demo_state() -> State {
         .saving_throw_proficiency(SavingThrow::Dexterity, true)
         .max_health(7)
         .level(1)
+        .metadata(Metadata {
+            notes: Some(
+                "Both goblins carry the same longsword item as the hero; items are shared by ID."
+                    .to_string(),
+            ),
+            ..Default::default()
+        })
         .build();
 
     let mut goblin2 = goblin1.clone();
@@ -111,33 +895,632 @@ pub fn demo_state() -> State {
     state
 }
 
+fn load_state(path: &Path) -> anyhow::Result<State> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to open state file {}", path.display()))?;
+    let state = State::from_json(&json)
+        .with_context(|| format!("Failed to parse state file {}", path.display()))?;
+    state
+        .validate()
+        .with_context(|| format!("Invalid state file {}", path.display()))?;
+    let mut header = String::new();
+    state.scenario.pretty_print(&mut header)?;
+    for line in header.lines() {
+        log::info!("{}", line);
+    }
+    for (_, warning) in state.warnings() {
+        log::warn!("{}: {}", path.display(), warning);
+    }
+    Ok(state)
+}
+
+/// Loads a state to simulate. Unlike [`load_state`], actors with no policy to act on are given
+/// one that attacks, so they don't stand around for the whole combat.
+fn load_scenario(path: &Path) -> anyhow::Result<State> {
+    let mut state = load_state(path)?;
+    state.fill_missing_policies();
+    Ok(state)
+}
+
+fn load_messages(path: &Path) -> anyhow::Result<MessageTemplates> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to open message templates {}", path.display()))?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("Failed to parse message templates {}", path.display()))
+}
+
+fn diff(old: &Path, new: &Path) -> anyhow::Result<()> {
+    let old_state = load_state(old)?;
+    let new_state = load_state(new)?;
+
+    let mut out = String::new();
+    old_state
+        .diff(&new_state)?
+        .pretty_print(&mut out, &old_state, &new_state)?;
+    print!("{}", out);
+
+    Ok(())
+}
+
+fn balance(args: &BalanceArgs) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        args.min <= args.max,
+        "--min ({}) must not be greater than --max ({})",
+        args.min,
+        args.max
+    );
+    let party = load_scenario(&args.party)?;
+    let bestiary = load_scenario(&args.bestiary)?;
+    anyhow::ensure!(
+        !bestiary.actors.is_empty(),
+        "Bestiary {} has no actors",
+        args.bestiary.display()
+    );
+
+    let balancer = Balancer::new(party, &bestiary)
+        .target(args.min..=args.max)
+        .combats_per_candidate(args.combats)
+        .max_monsters(args.max_monsters)
+        .max_evaluations(args.max_evaluations)
+        .seed(args.seed);
+    log::info!(
+        "Searching for encounters with a {:.0}-{:.0}% party win rate...",
+        args.min * 100.0,
+        args.max * 100.0
+    );
+    let suggestions = balancer.suggest(args.suggestions)?;
+
+    let mut out = String::new();
+    for suggestion in &suggestions {
+        suggestion.pretty_print(&mut out, &bestiary)?;
+        out.push('\n');
+    }
+    print!("{}", out);
+
+    if let Some(path) = &args.save
+        && let Some(best) = suggestions.first()
+    {
+        write_json(path, &best.state, true)?;
+        log::info!("Best suggestion written to {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn matchups(args: &MatchupArgs) -> anyhow::Result<()> {
+    let state = load_scenario(&args.state)?;
+    anyhow::ensure!(
+        state.actors.len() >= 2,
+        "{} needs at least two actors to match up",
+        args.state.display()
+    );
+
+    let pairs = state.actors.len() * (state.actors.len() - 1) / 2;
+    log::info!(
+        "Running {} combats for each of {} pairs...",
+        args.combats,
+        pairs
+    );
+    let matrix = MatchupAnalysis::new(state)
+        .combats_per_pair(args.combats)
+        .seed(args.seed)
+        .run()?;
+
+    let mut csv = String::new();
+    matrix.write_csv(&mut csv)?;
+    if let Some(path) = &args.output {
+        std::fs::write(path, csv).with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Win-probability matrix written to {}", path.display());
+    } else {
+        print!("{}", csv);
+    }
+
+    if let Some(path) = &args.rounds_output {
+        let mut csv = String::new();
+        matrix.write_rounds_csv(&mut csv)?;
+        std::fs::write(path, csv).with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Rounds-to-kill matrix written to {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn levels(args: &LevelArgs) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        args.from >= 1 && args.from <= args.to,
+        "Level range {}-{} is empty",
+        args.from,
+        args.to
+    );
+    let state = load_scenario(&args.state)?;
+    let scaled: BTreeSet<ActorId> = state
+        .actors
+        .values()
+        .filter(|actor| actor.group == args.group)
+        .map(|actor| actor.id)
+        .collect();
+    anyhow::ensure!(
+        !scaled.is_empty(),
+        "{} has no actors in group {}",
+        args.state.display(),
+        args.group
+    );
+
+    let group = args.group;
+    let group_wins =
+        OutcomeConditionProbability::new(move |state: &State| state.winner() == Some(group));
+
+    println!("level,win_probability");
+    for level in args.from..=args.to {
+        let mut integrator = Integrator::new(
+            args.combats,
+            Roller::from_seed(args.seed),
+            state.scaled_to_level(&scaled, level)?,
+        );
+        let results = integrator.run()?;
+        let wins = group_wins.query(&results.state_tree)?;
+        println!("{},{:.4}", level, wins.to_f64());
+    }
+
+    Ok(())
+}
+
+fn shard(args: &ShardArgs) -> anyhow::Result<()> {
+    let state = load_scenario(&args.state)?;
+    let mut integrator = Integrator::new(
+        args.combats,
+        Roller::for_shard(args.seed, args.shard),
+        state,
+    );
+    integrator.record_hp_time_series(args.hp_time_series);
+    integrator.record_roll_statistics(args.roll_statistics);
+    integrator.record_event_timeline(args.event_timeline);
+    integrator.record_contributions(args.contributions);
+    integrator.record_action_usage(args.action_usage);
+    if args.outcome_labels {
+        integrator.classify_outcomes(PartyOutcomeClassifier);
+    }
+    integrator.record_turn_order(args.turn_order);
+    integrator.record_weapon_stats(args.weapon_stats);
+    integrator.set_tree_mode(args.tree_mode.into());
+    integrator.batch_actions(args.batch_actions);
+
+    log::info!(
+        "Running {} combats for shard {} of seed {}...",
+        args.combats,
+        args.shard,
+        args.seed
+    );
+    let results = integrator.run()?;
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("shard-{}.partial.json", args.shard)));
+    let partial = PartialResults {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        seed: args.seed,
+        shard: args.shard,
+        results,
+    };
+    write_json(&output, &partial, false)?;
+    log::info!("Partial results written to {}", output.display());
+
+    Ok(())
+}
+
+fn merge(args: &MergeArgs) -> anyhow::Result<()> {
+    let mut merged: Option<PartialResults> = None;
+    let mut shards = BTreeSet::new();
+    for path in &args.partials {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let partial: PartialResults = serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("Failed to parse partial results {}", path.display()))?;
+        anyhow::ensure!(
+            shards.insert(partial.shard),
+            "Shard {} appears more than once ({})",
+            partial.shard,
+            path.display()
+        );
+
+        match &mut merged {
+            None => merged = Some(partial),
+            Some(merged) => {
+                anyhow::ensure!(
+                    partial.seed == merged.seed,
+                    "{} was run with seed {}, but earlier shards used seed {}",
+                    path.display(),
+                    partial.seed,
+                    merged.seed
+                );
+                merged
+                    .results
+                    .merge(&partial.results)
+                    .with_context(|| format!("Failed to merge {}", path.display()))?;
+            }
+        }
+    }
+    let results = merged
+        .map(|merged| merged.results)
+        .ok_or_else(|| anyhow::anyhow!("No partial results to merge"))?;
+
+    log::info!(
+        "Merged {} shards: {} combats",
+        shards.len(),
+        results.combats_run
+    );
+    write_json(&args.output, &results, false)?;
+    log::info!("Results written to {}", args.output.display());
+    log_roll_statistics(&results)?;
+    log_event_timeline(&results)?;
+    log_action_usage(&results)?;
+    log_outcome_labels(&results)?;
+    log_turn_order(&results)?;
+    log_weapon_stats(&results)?;
+
+    print!(
+        "{}",
+        format_summary(
+            &RunSummary::new(&results),
+            &MessageTemplates::default(),
+            args.format
+        )?
+    );
+
+    Ok(())
+}
+
+fn learn(args: &LearnArgs) -> anyhow::Result<()> {
+    let mut state = load_scenario(&args.state)?;
+    let actor = ActorId(args.actor);
+
+    log::info!(
+        "Learning a policy for actor {} over {} iterations of {} candidates...",
+        args.actor,
+        args.iterations,
+        args.population
+    );
+    let learned = PolicyLearner::new(state.clone(), actor)
+        .iterations(args.iterations)
+        .population(args.population)
+        .elites(args.elites)
+        .combats_per_evaluation(args.combats)
+        .max_weight(args.max_weight)
+        .seed(args.seed)
+        .learn()?;
+    println!(
+        "Win probability: {:.2}% (was {:.2}%)",
+        learned.wins.to_f64() * 100.0,
+        learned.baseline_wins.to_f64() * 100.0
+    );
+
+    write_json(&args.output, &learned, true)?;
+    log::info!("Learned policy written to {}", args.output.display());
+
+    if let Some(path) = &args.save_state {
+        state.set_actor_policy(actor, learned.policy.clone());
+        write_json(path, &state, true)?;
+        log::info!(
+            "State with the learned policy written to {}",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn new_scenario(args: &NewScenarioArgs) -> anyhow::Result<()> {
+    let mut state = if args.demo {
+        demo_state()
+    } else {
+        scaffold::scenario()
+    };
+    if args.name == "-" {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &state)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+
+    let path = if args.name.ends_with(".json") {
+        PathBuf::from(&args.name)
+    } else {
+        PathBuf::from(format!("{}.json", args.name))
+    };
+    anyhow::ensure!(
+        args.force || !path.exists(),
+        "{} already exists; pass --force to overwrite it",
+        path.display()
+    );
+
+    if let Some(name) = path.file_stem() {
+        state.scenario.title = Some(name.to_string_lossy().into_owned());
+    }
+    write_json(&path, &state, true)?;
+    log::info!("Starter scenario written to {}", path.display());
+    log::info!(
+        "Run it with `antikythera --state {}`, or open it in the GUI to edit",
+        path.display()
+    );
+    Ok(())
+}
+
+fn load_results(path: &Path) -> anyhow::Result<IntegrationResults> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    IntegrationResults::from_json(&json)
+        .with_context(|| format!("Failed to parse results {}", path.display()))
+}
+
+fn query(args: &QueryArgs) -> anyhow::Result<()> {
+    let results = load_results(&args.results)?;
+    let params = args.params.iter().cloned().collect();
+    let condition = StateCondition::parse_with_params(&args.condition, &params)?;
+
+    let mut query = StateConditionProbability::new(condition);
+    if args.all_states {
+        query = query.all_states();
+    }
+    let ratio = query.query(&results.state_tree)?;
+    if args.exact {
+        println!("{}", ratio);
+    } else {
+        println!("{:.4}", ratio.to_f64());
+    }
+    Ok(())
+}
+
+fn resources(args: &ResourcesArgs) -> anyhow::Result<()> {
+    let results = load_results(&args.results)?;
+    let usage = ResourcesSpent { group: args.group }.query(&results.state_tree)?;
+    let mut report = String::new();
+    usage.pretty_print(&mut report, results.state_tree.initial_state())?;
+    print!("{}", report);
+    Ok(())
+}
+
+fn skill_challenge(args: &SkillChallengeArgs) -> anyhow::Result<()> {
+    let state = load_scenario(&args.state)?;
+    let mut challenge = SkillChallenge::new(state.clone())
+        .group(args.group)
+        .successes(args.successes)
+        .failures(args.failures)
+        .strategy(args.strategy.into())
+        .trials(args.trials)
+        .seed(args.seed);
+    for check in &args.checks {
+        challenge = challenge.check(&check.skills, check.dc);
+    }
+
+    log::info!(
+        "Simulating {} attempts at {} successes before {} failures...",
+        args.trials,
+        args.successes,
+        args.failures
+    );
+    let result = challenge.run()?;
+    let mut out = String::new();
+    result.pretty_print(&mut out, &state)?;
+    print!("{}", out);
+    Ok(())
+}
+
+/// How one scenario of a batch turned out.
+struct BatchRow {
+    scenario: String,
+    /// Combats each group ended as the only one left standing.
+    wins: BTreeMap<u32, Ratio>,
+    mean_rounds: Option<f64>,
+}
+
+fn batch(args: &BatchArgs) -> anyhow::Result<()> {
+    let mut paths = std::fs::read_dir(&args.dir)
+        .with_context(|| format!("Failed to read directory {}", args.dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    paths.retain(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+    anyhow::ensure!(
+        !paths.is_empty(),
+        "{} has no scenario files",
+        args.dir.display()
+    );
+
+    let mut rows = Vec::new();
+    let mut failed = 0;
+    for path in &paths {
+        log::info!("Running {} combats for {}...", args.combats, path.display());
+        match batch_scenario(path, args) {
+            Ok(row) => rows.push(row),
+            Err(e) => {
+                log::error!("Skipping {}: {:#}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    let groups: BTreeSet<u32> = rows
+        .iter()
+        .flat_map(|row| row.wins.keys().copied())
+        .collect();
+    let mut csv = String::from("scenario,seed,combats,mean_rounds");
+    for group in &groups {
+        csv.push_str(&format!(",group_{}_win_probability", group));
+    }
+    csv.push('\n');
+    for row in &rows {
+        csv.push_str(&format!(
+            "\"{}\",{},{},",
+            row.scenario.replace('"', "\"\""),
+            args.seed,
+            args.combats
+        ));
+        if let Some(rounds) = row.mean_rounds {
+            csv.push_str(&format!("{:.2}", rounds));
+        }
+        for group in &groups {
+            csv.push(',');
+            if let Some(wins) = row.wins.get(group) {
+                csv.push_str(&format!("{:.4}", wins.to_f64()));
+            }
+        }
+        csv.push('\n');
+    }
+
+    if let Some(path) = &args.output {
+        std::fs::write(path, csv).with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Comparison written to {}", path.display());
+    } else {
+        print!("{}", csv);
+    }
+
+    anyhow::ensure!(
+        failed == 0,
+        "{} of {} scenarios failed to run",
+        failed,
+        paths.len()
+    );
+    Ok(())
+}
+
+fn batch_scenario(path: &Path, args: &BatchArgs) -> anyhow::Result<BatchRow> {
+    let state = load_scenario(path)?;
+    let groups: BTreeSet<u32> = state.actors.values().map(|actor| actor.group).collect();
+
+    let mut integrator = Integrator::new(args.combats, Roller::from_seed(args.seed), state);
+    integrator.set_tree_mode(TreeMode::TerminalOnly);
+    integrator.record_event_timeline(true);
+    let results = integrator.run()?;
+
+    let mut wins = BTreeMap::new();
+    for group in groups {
+        let group_wins =
+            OutcomeConditionProbability::new(move |state: &State| state.winner() == Some(group));
+        wins.insert(group, group_wins.query(&results.state_tree)?);
+    }
+
+    Ok(BatchRow {
+        scenario: path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        wins,
+        mean_rounds: results
+            .event_timeline
+            .as_ref()
+            .and_then(|timeline| timeline.combat_end.mean()),
+    })
+}
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    env_logger::builder()
-        .format_timestamp_secs()
-        .filter_level(log::LevelFilter::Info)
-        .init();
+    let level = if args.quiet {
+        log::LevelFilter::Off
+    } else {
+        log::LevelFilter::Info
+    };
+
+    let dashboard = (args.command.is_none() && args.run.tui).then(tui::Dashboard::new);
+    if let Some(dashboard) = &dashboard {
+        dashboard.init_logger(level)?;
+    } else {
+        let mut builder = env_logger::builder();
+        builder.format_timestamp_secs().filter_level(level);
+        if args.log_format == LogFormat::Json {
+            builder.format(|buf, record| {
+                let line = serde_json::json!({
+                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                    "level": record.level().as_str(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+                writeln!(buf, "{}", line)
+            });
+        }
+        builder.init();
+    }
+
+    match &args.command {
+        Some(Command::Diff { old, new }) => diff(old, new),
+        Some(Command::Balance(balance_args)) => balance(balance_args),
+        Some(Command::Matchups(matchup_args)) => matchups(matchup_args),
+        Some(Command::Levels(level_args)) => levels(level_args),
+        Some(Command::Shard(shard_args)) => shard(shard_args),
+        Some(Command::Merge(merge_args)) => merge(merge_args),
+        Some(Command::Learn(learn_args)) => learn(learn_args),
+        Some(Command::NewScenario(new_scenario_args)) => new_scenario(new_scenario_args),
+        Some(Command::Query(query_args)) => query(query_args),
+        Some(Command::Resources(resources_args)) => resources(resources_args),
+        Some(Command::Batch(batch_args)) => batch(batch_args),
+        Some(Command::SkillChallenge(challenge_args)) => skill_challenge(challenge_args),
+        None => run(args.run, dashboard),
+    }
+}
+
+fn run(args: RunArgs, dashboard: Option<tui::Dashboard>) -> anyhow::Result<()> {
     log::info!("Starting simulation with args: {:?}", args);
+    let started_at = chrono::Utc::now();
 
     let roller = match args.seed {
         Some(seed) => Roller::from_seed(seed),
-        None => Roller::new(),
+        None => Roller::from_random_seed(),
     };
+    let seed = roller.seed();
+    if args.seed.is_none()
+        && let Some(seed) = seed
+    {
+        log::info!("Using random seed {}", seed);
+    }
     let initial_state = if args.demo {
         log::info!("Using demo state");
         demo_state()
     } else {
         log::info!("Loading initial state from {}", args.state.display());
-        let state_file = std::fs::File::open(&args.state)?;
-        let reader = std::io::BufReader::new(state_file);
-        serde_json::from_reader(reader)?
+        load_scenario(&args.state)?
+    };
+    let messages = match &args.messages {
+        Some(path) => load_messages(path)?,
+        None => MessageTemplates::default(),
     };
 
     let mut integrator = Integrator::new(args.combats, roller, initial_state.clone());
+    integrator.record_hp_time_series(args.hp_time_series);
+    integrator.record_roll_statistics(args.roll_statistics);
+    integrator.record_event_timeline(args.event_timeline);
+    integrator.record_contributions(args.contributions);
+    integrator.record_action_usage(args.action_usage);
+    if args.outcome_labels {
+        integrator.classify_outcomes(PartyOutcomeClassifier);
+    }
+    integrator.record_turn_order(args.turn_order);
+    integrator.record_weapon_stats(args.weapon_stats);
+    integrator.set_tree_mode(args.tree_mode.into());
+    integrator.batch_actions(args.batch_actions);
+    integrator.set_verbosity(args.verbosity.into());
+    integrator.set_messages(messages.clone());
+    integrator.record_decisions(args.decisions);
+    if let Some(megabytes) = args.memory_limit {
+        integrator.set_memory_limit(
+            megabytes.saturating_mul(1024 * 1024),
+            args.on_memory_limit.into(),
+        );
+    }
 
-    log::info!("Running {} combats...", args.combats);
+    match args.time_budget {
+        Some(seconds) => {
+            let budget = std::time::Duration::try_from_secs_f64(seconds)
+                .context("Time budget must be a non-negative number of seconds")?;
+            integrator.set_stopping_condition(StoppingCondition::TimeBudget(budget));
+            log::info!("Running combats for {} seconds...", seconds);
+        }
+        None => log::info!("Running {} combats...", args.combats),
+    }
 
-    let results = integrator.run()?;
+    let results = match &dashboard {
+        Some(dashboard) => dashboard.run(integrator)?,
+        None => integrator.run()?,
+    };
 
     log::info!(
         "Simulation complete: {} combats run in {} seconds ({:.2} combats/sec)",
@@ -145,11 +1528,78 @@ fn main() -> anyhow::Result<()> {
         results.elapsed_time.to_std().unwrap().as_secs_f64(),
         results.combats_per_second()
     );
+    match results.memory_limit_reached {
+        Some(MemoryLimitAction::TerminalOnly) => {
+            log::warn!("The memory limit was reached, so only terminal states were kept")
+        }
+        Some(MemoryLimitAction::Stop) => log::warn!(
+            "The memory limit was reached, so the run stopped after {} combats",
+            results.combats_run
+        ),
+        None => {}
+    }
 
-    let stats_file = std::fs::File::create(&args.output)?;
-    let writer = std::io::BufWriter::new(stats_file);
-    serde_json::to_writer(writer, &results)?;
-    log::info!("Results written to {}", args.output.display());
+    // without a state tree there are no outcomes to summarize
+    let summary = (args.tree_mode != TreeModeArg::None)
+        .then(|| {
+            format_summary(
+                &RunSummary::with_messages(&results, &messages),
+                &messages,
+                args.format,
+            )
+        })
+        .transpose()?;
+
+    if let Some(out_dir) = &args.out_dir {
+        let run_dir = out_dir.join(format!("run-{}", started_at.format("%Y%m%d-%H%M%S-%3f")));
+        std::fs::create_dir_all(&run_dir)
+            .with_context(|| format!("Failed to create run directory {}", run_dir.display()))?;
+
+        let manifest = RunManifest {
+            version: env!("CARGO_PKG_VERSION"),
+            started_at: started_at.to_rfc3339(),
+            source: (!args.demo).then(|| args.state.clone()),
+            seed,
+            combats: results.combats_run,
+            time_budget: args.time_budget,
+            hp_time_series: args.hp_time_series,
+            roll_statistics: args.roll_statistics,
+            event_timeline: args.event_timeline,
+            contributions: args.contributions,
+            action_usage: args.action_usage,
+            outcome_labels: args.outcome_labels,
+            turn_order: args.turn_order,
+            weapon_stats: args.weapon_stats,
+            tree_mode: args.tree_mode.into(),
+            batch_actions: args.batch_actions,
+            memory_limit: args.memory_limit,
+            messages: args.messages.clone(),
+        };
+        write_json(&run_dir.join("manifest.json"), &manifest, true)?;
+        write_json(&run_dir.join("state.json"), &initial_state, true)?;
+        write_json(&run_dir.join("results.json"), &results, false)?;
+        if let Some(summary) = &summary {
+            let summary_path = run_dir.join(format!("summary.{}", args.format.extension()));
+            std::fs::write(&summary_path, summary)
+                .with_context(|| format!("Failed to write {}", summary_path.display()))?;
+        }
+
+        log::info!("Run written to {}", run_dir.display());
+    } else {
+        write_json(&args.output, &results, false)?;
+        log::info!("Results written to {}", args.output.display());
+    }
+
+    log_roll_statistics(&results)?;
+    log_event_timeline(&results)?;
+    log_action_usage(&results)?;
+    log_outcome_labels(&results)?;
+    log_turn_order(&results)?;
+    log_weapon_stats(&results)?;
+    match &summary {
+        Some(summary) => print!("{}", summary),
+        None => log::info!("No state tree was kept, so there's no outcome summary"),
+    }
 
     Ok(())
 }
@@ -0,0 +1,221 @@
+//! The starter scenario written by `new-scenario`.
+//!
+//! JSON has no comments, so the explanations live in each actor's metadata notes, where the GUI
+//! shows them too.
+
+use antikythera::prelude::*;
+
+const PARTY: u32 = 0;
+const MONSTERS: u32 = 1;
+
+fn notes(source: &str, notes: &str) -> Metadata {
+    Metadata {
+        source: Some(source.to_string()),
+        notes: Some(notes.to_string()),
+        ..Default::default()
+    }
+}
+
+fn weapon(state: &mut State, name: &str, weapon: WeaponBuilder) -> ItemId {
+    state.add_item(name, ItemInner::Weapon(weapon.build()))
+}
+
+fn add_member(
+    state: &mut State,
+    actor: ActorBuilder,
+    armor_class: u32,
+    weapon: ItemId,
+    weapon_type: WeaponType,
+) {
+    let mut actor = actor
+        .weapon_proficiency(weapon_type, WeaponProficiency::Proficient)
+        .build();
+    actor.armor_class = armor_class;
+    actor.policy = Policy::attacker();
+    actor.give_item(weapon, 1);
+    state.add_actor(actor);
+}
+
+/// A level 3 party of four against an orc and two goblins, an easy fight to start tuning from.
+pub fn scenario() -> State {
+    let mut state = State::new();
+    state
+        .groups
+        .insert(PARTY, GroupInfo::new("Party").player_party());
+    state.groups.insert(MONSTERS, GroupInfo::new("Monsters"));
+    state.scenario = ScenarioMetadata {
+        description: Some("A level 3 party of four against an orc and two goblins.".to_string()),
+        party_level: Some(3),
+        ..Default::default()
+    };
+
+    let longsword = weapon(
+        &mut state,
+        "Longsword",
+        WeaponBuilder::new(WeaponType::Longsword)
+            .damage("1d8+3")
+            .critical_damage("2d8+3"),
+    );
+    let rapier = weapon(
+        &mut state,
+        "Rapier",
+        WeaponBuilder::new(WeaponType::Rapier)
+            .damage("1d8+3")
+            .critical_damage("2d8+3"),
+    );
+    let mace = weapon(
+        &mut state,
+        "Mace",
+        WeaponBuilder::new(WeaponType::Mace)
+            .damage("1d6+2")
+            .critical_damage("2d6+2"),
+    );
+    let crossbow = weapon(
+        &mut state,
+        "Light Crossbow",
+        WeaponBuilder::new(WeaponType::CrossbowLight)
+            .damage("1d8+2")
+            .critical_damage("2d8+2")
+            .range(80),
+    );
+    let greataxe = weapon(
+        &mut state,
+        "Greataxe",
+        WeaponBuilder::new(WeaponType::Greataxe)
+            .damage("1d12+3")
+            .critical_damage("2d12+3"),
+    );
+    let scimitar = weapon(
+        &mut state,
+        "Scimitar",
+        WeaponBuilder::new(WeaponType::Scimitar)
+            .damage("1d6+2")
+            .critical_damage("2d6+2"),
+    );
+
+    add_member(
+        &mut state,
+        ActorBuilder::new("Fighter")
+            .group(PARTY)
+            .level(3)
+            .max_health(28)
+            .stat(Stat::Strength, 16)
+            .stat(Stat::Constitution, 14)
+            .saving_throw_proficiency(SavingThrow::Strength, true)
+            .saving_throw_proficiency(SavingThrow::Constitution, true)
+            .skill_proficiency(Skill::Athletics, SkillProficiency::Proficient)
+            .feature(Feature::Defense)
+            .metadata(notes(
+                "Template",
+                "Group 0 is the party. Change stats, HP and AC to match your character sheet; \
+                 weapons live in the items list and are handed out through inventories.",
+            )),
+        16,
+        longsword,
+        WeaponType::Longsword,
+    );
+    add_member(
+        &mut state,
+        ActorBuilder::new("Rogue")
+            .group(PARTY)
+            .level(3)
+            .max_health(21)
+            .stat(Stat::Dexterity, 16)
+            .stat(Stat::Constitution, 12)
+            .saving_throw_proficiency(SavingThrow::Dexterity, true)
+            .skill_proficiency(Skill::Stealth, SkillProficiency::Expert)
+            .skill_proficiency(Skill::Acrobatics, SkillProficiency::Proficient)
+            .metadata(notes(
+                "Template",
+                "Policies decide what each actor does: raise an action's weight to have them \
+                 pick it more often, or add target weights to focus a specific enemy.",
+            )),
+        14,
+        rapier,
+        WeaponType::Rapier,
+    );
+    add_member(
+        &mut state,
+        ActorBuilder::new("Cleric")
+            .group(PARTY)
+            .level(3)
+            .max_health(24)
+            .stat(Stat::Strength, 14)
+            .stat(Stat::Wisdom, 16)
+            .saving_throw_proficiency(SavingThrow::Wisdom, true)
+            .saving_throw_proficiency(SavingThrow::Charisma, true)
+            .metadata(notes(
+                "Template",
+                "Damage rolls include the ability modifier, e.g. 1d6+2 for a mace with 14 STR.",
+            )),
+        18,
+        mace,
+        WeaponType::Mace,
+    );
+    add_member(
+        &mut state,
+        ActorBuilder::new("Wizard")
+            .group(PARTY)
+            .level(3)
+            .max_health(17)
+            .stat(Stat::Dexterity, 14)
+            .stat(Stat::Intelligence, 16)
+            .saving_throw_proficiency(SavingThrow::Intelligence, true)
+            .saving_throw_proficiency(SavingThrow::Wisdom, true)
+            .metadata(notes(
+                "Template",
+                "Weapons with a range are ranged; leave it out for melee weapons.",
+            )),
+        12,
+        crossbow,
+        WeaponType::CrossbowLight,
+    );
+
+    add_member(
+        &mut state,
+        ActorBuilder::new("Orc")
+            .group(MONSTERS)
+            .npc(true)
+            .max_health(15)
+            .stat(Stat::Strength, 16)
+            .stat(Stat::Dexterity, 12)
+            .stat(Stat::Constitution, 16)
+            .death_behavior(DeathBehavior::InstantDeath)
+            .metadata(Metadata {
+                challenge_rating: Some("1/2".to_string()),
+                ..notes(
+                    "Monster Manual p. 246",
+                    "Group 1 is the monsters. Monsters usually die at 0 HP instead of making \
+                     death saves; set their death behavior accordingly.",
+                )
+            }),
+        13,
+        greataxe,
+        WeaponType::Greataxe,
+    );
+    for _ in 0..2 {
+        add_member(
+            &mut state,
+            ActorBuilder::new("Goblin")
+                .group(MONSTERS)
+                .npc(true)
+                .max_health(7)
+                .stat(Stat::Strength, 8)
+                .stat(Stat::Dexterity, 14)
+                .skill_proficiency(Skill::Stealth, SkillProficiency::Expert)
+                .death_behavior(DeathBehavior::InstantDeath)
+                .metadata(Metadata {
+                    challenge_rating: Some("1/4".to_string()),
+                    ..notes(
+                        "Monster Manual p. 166",
+                        "Copy an actor to add more of the same monster.",
+                    )
+                }),
+            15,
+            scimitar,
+            WeaponType::Scimitar,
+        );
+    }
+
+    state
+}
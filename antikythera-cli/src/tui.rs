@@ -0,0 +1,257 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use antikythera::prelude::*;
+use ratatui::{
+    Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Gauge, List, Paragraph},
+};
+
+const MAX_LOG_LINES: usize = 200;
+
+#[derive(Default)]
+struct Progress {
+    combats: usize,
+    /// Combats won by each group; `None` counts combats that ended without a single group standing.
+    wins: BTreeMap<Option<u32>, usize>,
+    /// The state tree's approximate size in bytes, as last measured.
+    tree_bytes: usize,
+    log: VecDeque<String>,
+    /// Whether the dashboard currently owns the terminal. Log lines are echoed to stderr otherwise.
+    active: bool,
+}
+
+/// A live terminal dashboard showing simulation progress, throughput, rolling win estimates
+/// for each group, and recent log lines.
+#[derive(Clone, Default)]
+pub struct Dashboard {
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Installs a logger that feeds the dashboard's log pane.
+    pub fn init_logger(&self, level: log::LevelFilter) -> anyhow::Result<()> {
+        log::set_boxed_logger(Box::new(DashboardLogger {
+            progress: self.progress.clone(),
+        }))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    /// Runs the integrator on a worker thread while drawing the dashboard, until the run completes
+    /// or the user quits with `q`/`Esc`. Quitting stops the worker after its current combat, and
+    /// waits for it before returning.
+    pub fn run(&self, mut integrator: Integrator) -> anyhow::Result<IntegrationResults> {
+        let total = integrator.min_combats;
+        let budget = match integrator.stopping_condition {
+            StoppingCondition::MinCombats => None,
+            StoppingCondition::TimeBudget(budget) => Some(budget),
+        };
+        let state = &integrator.initial_state;
+        let title = match &state.scenario.title {
+            Some(scenario) => format!(" Antikythera: {} (q to quit) ", scenario),
+            None => " Antikythera (q to quit) ".to_string(),
+        };
+        let groups: BTreeMap<u32, String> = state
+            .actors
+            .values()
+            .map(|actor| (actor.group, state.group_name(actor.group)))
+            .collect();
+        integrator.add_hook(ProgressHook {
+            progress: self.progress.clone(),
+        });
+
+        let started = Instant::now();
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = std::thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let mut state_tree = integrator.begin();
+                while integrator.should_continue() && !stop.load(Ordering::Relaxed) {
+                    integrator.run_combat(&mut state_tree)?;
+                }
+                Ok(integrator.finish(state_tree))
+            }
+        });
+
+        self.progress.lock().unwrap().active = true;
+        let mut terminal = ratatui::init();
+        let outcome = (|| -> anyhow::Result<bool> {
+            loop {
+                terminal.draw(|frame| {
+                    self.draw(frame, &title, total, budget, &groups, started.elapsed())
+                })?;
+                if worker.is_finished() {
+                    return Ok(true);
+                }
+                if event::poll(Duration::from_millis(50))?
+                    && let Event::Key(key) = event::read()?
+                    && key.kind == KeyEventKind::Press
+                    && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                {
+                    return Ok(false);
+                }
+            }
+        })();
+        ratatui::restore();
+        self.progress.lock().unwrap().active = false;
+
+        stop.store(true, Ordering::Relaxed);
+        let results = worker
+            .join()
+            .map_err(|_| anyhow::anyhow!("Simulation thread panicked"))?;
+        if !outcome? {
+            anyhow::bail!("Simulation aborted by user");
+        }
+        results
+    }
+
+    fn draw(
+        &self,
+        frame: &mut Frame,
+        title: &str,
+        total: usize,
+        budget: Option<Duration>,
+        groups: &BTreeMap<u32, String>,
+        elapsed: Duration,
+    ) {
+        let progress = self.progress.lock().unwrap();
+
+        let [progress_area, stats_area, groups_area, log_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(groups.len() as u16 + 3),
+            Constraint::Min(3),
+        ])
+        .areas(frame.area());
+
+        let (ratio, label) = match budget {
+            Some(budget) if !budget.is_zero() => (
+                (elapsed.as_secs_f64() / budget.as_secs_f64()).min(1.0),
+                format!(
+                    "{} combats, {:.0}/{:.0}s",
+                    progress.combats,
+                    elapsed.as_secs_f64(),
+                    budget.as_secs_f64()
+                ),
+            ),
+            Some(_) => (1.0, format!("{} combats", progress.combats)),
+            None if total > 0 => (
+                (progress.combats as f64 / total as f64).min(1.0),
+                format!("{}/{} combats", progress.combats, total),
+            ),
+            None => (1.0, format!("{}/{} combats", progress.combats, total)),
+        };
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(title))
+                .gauge_style(Style::default().fg(Color::Green))
+                .ratio(ratio)
+                .label(label),
+            progress_area,
+        );
+
+        let secs = elapsed.as_secs_f64();
+        let rate = if secs > 0.0 {
+            progress.combats as f64 / secs
+        } else {
+            0.0
+        };
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{:.1}s elapsed, {:.2} combats/sec, state tree {:.1} MB",
+                secs,
+                rate,
+                progress.tree_bytes as f64 / (1024.0 * 1024.0)
+            ))
+            .block(Block::bordered().title(" Throughput ")),
+            stats_area,
+        );
+
+        let win_rate = |group: Option<u32>| {
+            let wins = progress.wins.get(&group).copied().unwrap_or(0);
+            if progress.combats > 0 {
+                wins as f64 / progress.combats as f64 * 100.0
+            } else {
+                0.0
+            }
+        };
+        let mut lines: Vec<Line> = groups
+            .iter()
+            .map(|(group, name)| Line::from(format!("{}: {:6.2}%", name, win_rate(Some(*group)))))
+            .collect();
+        lines.push(Line::from(format!("No winner: {:6.2}%", win_rate(None))));
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Win Probability ")),
+            groups_area,
+        );
+
+        let visible = log_area.height.saturating_sub(2) as usize;
+        let skip = progress.log.len().saturating_sub(visible);
+        frame.render_widget(
+            List::new(progress.log.iter().skip(skip).map(String::as_str))
+                .block(Block::bordered().title(" Log ")),
+            log_area,
+        );
+    }
+}
+
+struct ProgressHook {
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl Hook for ProgressHook {
+    fn on_combat_end(&mut self, state: &State) {
+        let winner = state.winner();
+
+        let mut progress = self.progress.lock().unwrap();
+        progress.combats += 1;
+        *progress.wins.entry(winner).or_default() += 1;
+    }
+
+    fn on_memory_usage(&mut self, usage: &TreeMemoryUsage) {
+        self.progress.lock().unwrap().tree_bytes = usage.total();
+    }
+}
+
+struct DashboardLogger {
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl log::Log for DashboardLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[{} {}] {}", record.level(), record.target(), record.args());
+        let mut progress = self.progress.lock().unwrap();
+        if progress.active {
+            if progress.log.len() == MAX_LOG_LINES {
+                progress.log.pop_front();
+            }
+            progress.log.push_back(line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -2,6 +2,11 @@ use antikythera::prelude::*;
 use eframe::egui;
 
 pub mod analysis;
+pub mod balancer;
+pub mod matchups;
+pub mod queries;
+pub mod replay;
+pub mod report;
 pub mod scripting;
 pub mod simulation;
 pub mod state_editor;
@@ -70,7 +75,7 @@ impl App {
                 self.simulation_app.ui(ui);
             }
             AppMode::Analysis => {
-                self.analysis_app.ui(ui);
+                self.analysis_app.ui(ui, &self.simulation_app.messages);
             }
         });
     }
@@ -147,9 +152,13 @@ impl eframe::App for App {
 }
 
 pub fn unsaved_changes_dialog() -> bool {
+    confirm_dialog("Unsaved Changes", "Discard unsaved changes?")
+}
+
+pub fn confirm_dialog(title: &str, description: &str) -> bool {
     let confirm = rfd::MessageDialog::new()
-        .set_title("Unsaved Changes")
-        .set_description("Discard unsaved changes?")
+        .set_title(title)
+        .set_description(description)
         .set_buttons(rfd::MessageButtons::YesNo)
         .set_level(rfd::MessageLevel::Warning)
         .show();
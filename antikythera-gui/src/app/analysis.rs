@@ -1,7 +1,11 @@
 use antikythera::prelude::*;
 use eframe::egui;
 
-use crate::app::scripting::analysis::AnalysisScriptInterface;
+use crate::app::{
+    queries::QueryLibraryPanel,
+    report::{ReportFormat, ReportSections, render_report},
+    scripting::analysis::AnalysisScriptInterface,
+};
 
 pub struct Metric {
     pub query_name: String,
@@ -13,43 +17,150 @@ pub struct AnalysisApp {
     pub stats: Option<IntegrationResults>,
     metrics: Vec<Metric>,
     script_interface: AnalysisScriptInterface,
+    report_format: ReportFormat,
+    report_sections: ReportSections,
+    /// Name of the metric the running query's result is added under.
+    pending_query_name: Option<String>,
+    query_library: QueryLibraryPanel,
+    /// A quick condition like `actor("Hero").alive && round <= 5`, checked without Lua.
+    condition: String,
+    /// Group whose victories "Compute Resources Spent" looks at.
+    resources_group: u32,
+    /// A second run to compare the loaded one against.
+    compared: Option<ComparedRun>,
+}
+
+/// Results loaded for comparison, and how the main results differ from them.
+struct ComparedRun {
+    results: IntegrationResults,
+    comparison: RunComparison,
 }
 
 impl AnalysisApp {
-    pub fn ui(&mut self, ui: &mut egui::Ui) {
+    /// `messages` word the exported report.
+    pub fn ui(&mut self, ui: &mut egui::Ui, messages: &MessageTemplates) {
         ui.vertical_centered(|ui| {
             ui.heading("Analysis");
         });
 
         ui.separator();
 
-        if ui.button("Load Results").clicked()
-            && let Some(path) = rfd::FileDialog::new()
-                .add_filter("JSON", &["json"])
-                .set_title("Select Results File")
-                .pick_file()
-        {
-            match std::fs::read_to_string(&path).and_then(|data| {
-                serde_json::from_str::<IntegrationResults>(&data).map_err(|e| e.into())
-            }) {
-                Ok(stats) => {
-                    self.stats = Some(stats);
-                }
-                Err(e) => {
-                    eprintln!("Failed to load results: {}", e);
+        ui.horizontal(|ui| {
+            if ui.button("Load Results").clicked()
+                && let Some(stats) = Self::load_results("Select Results File")
+            {
+                self.script_interface.cancel_query();
+                if let Some(compared) = &mut self.compared {
+                    compared.comparison = RunComparison::new(&compared.results, &stats);
                 }
+                self.stats = Some(stats);
             }
-        }
 
-        if self.stats.is_some() && ui.button("Clear Results").clicked() {
-            self.stats = None;
-        }
+            if self.stats.is_some() && ui.button("Clear Results").clicked() {
+                self.script_interface.cancel_query();
+                self.stats = None;
+                self.compared = None;
+            }
+
+            if let Some(stats) = &self.stats {
+                if ui
+                    .button("Compare With...")
+                    .on_hover_text("Load an earlier run to see how these results differ from it")
+                    .clicked()
+                    && let Some(results) = Self::load_results("Select Results to Compare With")
+                {
+                    let comparison = RunComparison::new(&results, stats);
+                    self.compared = Some(ComparedRun {
+                        results,
+                        comparison,
+                    });
+                }
+                if self.compared.is_some() && ui.button("Clear Comparison").clicked() {
+                    self.compared = None;
+                }
+            }
+        });
 
         if let Some(stats) = &self.stats {
-            ui.label(format!(
-                "Loaded state tree with {} nodes",
-                stats.state_tree.node_count()
-            ));
+            ui.label(match stats.state_tree.mode() {
+                TreeMode::Full => format!(
+                    "Loaded state tree with {} nodes",
+                    stats.state_tree.node_count()
+                ),
+                TreeMode::TerminalOnly => format!(
+                    "Loaded {} terminal states (intermediate states weren't kept)",
+                    stats.state_tree.terminal_state_count()
+                ),
+                TreeMode::None => "Loaded results without a state tree".to_string(),
+            });
+            if let Some(metadata) = &stats.metadata {
+                ui.label(format!(
+                    "{} combats run by version {} on {}{}",
+                    metadata.combats,
+                    metadata.version,
+                    metadata.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                    metadata
+                        .hostname
+                        .as_ref()
+                        .map_or(String::new(), |hostname| format!(" ({})", hostname))
+                ))
+                .on_hover_text(format!(
+                    "Seed: {}\nInitial state hash: {:?}",
+                    metadata
+                        .seed
+                        .map_or("unseeded".to_string(), |seed| seed.to_string()),
+                    metadata.initial_state_hash
+                ));
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Export Report").clicked() {
+                    let format = self.report_format;
+                    let metrics: Vec<(String, String)> = self
+                        .metrics
+                        .iter()
+                        .map(|metric| (metric.query_name.clone(), metric.result.clone()))
+                        .chain(
+                            self.script_interface
+                                .metrics
+                                .iter()
+                                .map(|(name, value)| (name.clone(), value.to_string())),
+                        )
+                        .collect();
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter(format!("{:?}", format), &[format.extension()])
+                        .set_title("Export Report")
+                        .set_file_name(format!("report.{}", format.extension()))
+                        .save_file()
+                    {
+                        match render_report(stats, &metrics, messages, self.report_sections, format)
+                            .and_then(|report| Ok(std::fs::write(&path, report)?))
+                        {
+                            Ok(()) => self.script_interface.script_error = None,
+                            Err(e) => {
+                                self.script_interface.script_error =
+                                    Some(format!("Failed to export report: {}", e));
+                            }
+                        }
+                    }
+                }
+                egui::ComboBox::from_id_salt("report_format")
+                    .selected_text(format!("{:?}", self.report_format))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut self.report_format,
+                            ReportFormat::Markdown,
+                            "Markdown",
+                        );
+                        ui.selectable_value(&mut self.report_format, ReportFormat::Html, "Html");
+                    });
+                let sections = &mut self.report_sections;
+                ui.checkbox(&mut sections.summary, "Summary");
+                ui.checkbox(&mut sections.metrics, "Metrics");
+                ui.checkbox(&mut sections.hp_chart, "HP chart")
+                    .on_hover_text("Mean HP by round, as an SVG line chart in HTML reports");
+                ui.checkbox(&mut sections.roll_statistics, "Roll statistics");
+            });
 
             ui.separator();
 
@@ -110,18 +221,13 @@ impl AnalysisApp {
                 self.script_interface.script_error = None;
             }
 
+            let running = self.script_interface.is_running();
+
             // Run query on Ctrl+Enter
-            if text_editor_output.response.has_focus()
-                && ui.input(|i| i.key_pressed(egui::Key::Enter) && i.modifiers.ctrl)
-            {
+            let run_shortcut = text_editor_output.response.has_focus()
+                && ui.input(|i| i.key_pressed(egui::Key::Enter) && i.modifiers.ctrl);
+            if run_shortcut {
                 text_editor_output.response.request_focus(); // keep focus after Ctrl+Enter
-                if let Err(e) = self
-                    .script_interface
-                    .run_outcome_probability_query(&stats.state_tree)
-                {
-                    self.script_interface.script_error =
-                        Some(format!("Error running query: {}", e));
-                }
             }
 
             ui.checkbox(
@@ -129,27 +235,48 @@ impl AnalysisApp {
                 "Run on terminal states only",
             );
 
-            if ui.button("Run Query").clicked()
-                && let Some(results) = self.stats.as_ref()
-            {
-                match self
-                    .script_interface
-                    .run_outcome_probability_query(&results.state_tree)
-                {
+            ui.horizontal(|ui| {
+                let run_clicked = ui
+                    .add_enabled(!running, egui::Button::new("Run Query"))
+                    .clicked();
+                if (run_clicked || run_shortcut) && !running {
+                    self.pending_query_name = Some(if self.script_interface.externals_only {
+                        format!(
+                            "Terminal State Probability of:\n{}",
+                            self.script_interface.query
+                        )
+                    } else {
+                        format!("State Probability of:\n{}", self.script_interface.query)
+                    });
+                    self.script_interface.start_outcome_probability_query(
+                        &stats.state_tree,
+                        self.script_interface.query.clone(),
+                        self.script_interface.externals_only,
+                    );
+                }
+
+                if let Some(progress) = self.script_interface.progress() {
+                    ui.add(
+                        egui::ProgressBar::new(progress as f32)
+                            .desired_width(200.0)
+                            .show_percentage(),
+                    );
+                    if ui.button("Cancel").clicked() {
+                        self.script_interface.cancel_query();
+                        self.pending_query_name = None;
+                        self.query_library.clear_queue();
+                    }
+                }
+            });
+
+            if let Some(result) = self.script_interface.poll_query() {
+                let query_name = self.pending_query_name.take().unwrap_or_default();
+                match result {
                     Ok(probability) => {
                         self.metrics.push(Metric {
-                            query_name: if self.script_interface.externals_only {
-                                format!(
-                                    "Terminal State Probability of:\n{}",
-                                    self.script_interface.query
-                                )
-                            } else {
-                                format!("State Probability of:\n{}", self.script_interface.query)
-                            },
-                            result: format!("{}%", probability * 100.0),
+                            query_name,
+                            result: format!("{}%", probability.to_f64() * 100.0),
                         });
-
-                        self.script_interface.script_error = None;
                     }
                     Err(e) => {
                         self.script_interface.script_error =
@@ -158,6 +285,135 @@ impl AnalysisApp {
                 }
             }
 
+            self.query_library.ui(ui, &mut self.script_interface);
+
+            if !self.script_interface.is_running()
+                && let Some(query) = self.query_library.next_query()
+            {
+                self.pending_query_name = Some(query.metric_name());
+                self.script_interface.start_outcome_probability_query(
+                    &stats.state_tree,
+                    query.script,
+                    query.externals_only,
+                );
+            }
+
+            ui.horizontal(|ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.condition)
+                        .code_editor()
+                        .desired_width(400.0)
+                        .hint_text(r#"actor("Hero").alive && group(1).wiped"#),
+                );
+                let submitted =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if (ui.button("Run Condition").clicked() || submitted)
+                    && !self.condition.trim().is_empty()
+                {
+                    let externals_only = self.script_interface.externals_only;
+                    let result = StateCondition::parse(&self.condition).and_then(|condition| {
+                        StateConditionProbability {
+                            condition,
+                            externals_only,
+                        }
+                        .query(&stats.state_tree)
+                    });
+                    match result {
+                        Ok(probability) => {
+                            self.metrics.push(Metric {
+                                query_name: if externals_only {
+                                    format!("Terminal State Probability of: {}", self.condition)
+                                } else {
+                                    format!("State Probability of: {}", self.condition)
+                                },
+                                result: format!("{}%", probability.to_f64() * 100.0),
+                            });
+                        }
+                        Err(e) => {
+                            self.script_interface.script_error =
+                                Some(format!("Error checking condition: {}", e));
+                        }
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                let compute = ui.button("Compute Resources Spent").clicked();
+                ui.label("by group");
+                ui.add(egui::DragValue::new(&mut self.resources_group));
+                if compute {
+                    match (ResourcesSpent {
+                        group: self.resources_group,
+                    })
+                    .query(&stats.state_tree)
+                    {
+                        Ok(usage) => {
+                            let initial_state = stats.state_tree.initial_state();
+                            let prefix = format!("Group {} per victory: ", self.resources_group);
+                            let mut push = |name: String, result: String| {
+                                self.metrics.push(Metric {
+                                    query_name: format!("{}{}", prefix, name),
+                                    result,
+                                });
+                            };
+                            push(
+                                "Victory rate".to_string(),
+                                format!("{:.2}%", usage.victories.to_f64() * 100.0),
+                            );
+                            push("HP lost".to_string(), format!("{:.2}", usage.health_lost));
+                            push(
+                                "Hit dice to recover".to_string(),
+                                format!("{:.2}", usage.hit_dice),
+                            );
+                            for (item, quantity) in &usage.items {
+                                let mut name = String::new();
+                                item.pretty_print(&mut name, initial_state).ok();
+                                push(format!("{} used", name), format!("{:.2}", quantity));
+                            }
+                            for (resource, count) in &usage.rerolls {
+                                push(format!("{:?} spent", resource), format!("{:.2}", count));
+                            }
+                            for (feature, count) in &usage.features {
+                                push(format!("{:?} used", feature), format!("{:.2}", count));
+                            }
+                        }
+                        Err(e) => {
+                            self.script_interface.script_error =
+                                Some(format!("Error computing resources spent: {}", e));
+                        }
+                    }
+                }
+            });
+
+            if ui.button("Compute Hit Rates").clicked()
+                && let Some(results) = self.stats.as_ref()
+            {
+                match AttackHitRates.query(&results.state_tree) {
+                    Ok(rates) => {
+                        let initial_state = results.state_tree.initial_state();
+                        for ((attacker, target), rate) in rates {
+                            let mut query_name = String::from("Hit Rate: ");
+                            attacker.pretty_print(&mut query_name, initial_state).ok();
+                            query_name.push_str(" -> ");
+                            target.pretty_print(&mut query_name, initial_state).ok();
+                            self.metrics.push(Metric {
+                                query_name,
+                                result: format!(
+                                    "{:.2}% ({:.2}% crit, {} attacks)",
+                                    rate.hit_rate() * 100.0,
+                                    rate.crit_rate() * 100.0,
+                                    rate.attacks
+                                ),
+                            });
+                        }
+                    }
+                    Err(e) => {
+                        self.script_interface.script_error =
+                            Some(format!("Error computing hit rates: {}", e));
+                    }
+                }
+            }
+
             if let Some(error) = &self.script_interface.script_error {
                 ui.colored_label(egui::Color32::RED, error);
             }
@@ -165,6 +421,11 @@ impl AnalysisApp {
             ui.separator();
 
             egui::ScrollArea::vertical().show(ui, |ui| {
+                if let Some(compared) = &self.compared {
+                    Self::comparison_ui(ui, compared);
+                    ui.separator();
+                }
+
                 egui::Grid::new("metrics_grid")
                     .striped(true)
                     .min_col_width(200.0)
@@ -188,7 +449,614 @@ impl AnalysisApp {
                             ui.end_row();
                         }
                     });
+
+                ui.separator();
+                Self::outcomes_ui(ui, &stats.state_tree);
+
+                if let Some(series) = &stats.hp_time_series {
+                    ui.separator();
+                    Self::hp_time_series_ui(ui, stats.state_tree.initial_state(), series);
+                }
+
+                if let Some(statistics) = &stats.roll_statistics {
+                    ui.separator();
+                    Self::roll_statistics_ui(ui, statistics);
+                }
+
+                if stats.contributions.is_some() {
+                    ui.separator();
+                    Self::contributions_ui(ui, &RunSummary::new(stats));
+                }
+
+                if let Some(timeline) = &stats.event_timeline {
+                    ui.separator();
+                    Self::event_timeline_ui(ui, stats.state_tree.initial_state(), timeline);
+                }
+
+                if let Some(usage) = &stats.action_usage {
+                    ui.separator();
+                    Self::action_usage_ui(ui, stats.state_tree.initial_state(), usage);
+                }
+
+                if let Some(labels) = &stats.outcome_labels {
+                    ui.separator();
+                    Self::outcome_labels_ui(ui, labels);
+                }
+
+                if let Some(turn_order) = &stats.turn_order {
+                    ui.separator();
+                    Self::turn_order_ui(ui, stats.state_tree.initial_state(), turn_order);
+                }
+
+                if let Some(weapons) = &stats.weapon_stats {
+                    ui.separator();
+                    Self::weapon_stats_ui(ui, stats.state_tree.initial_state(), weapons);
+                }
             });
         }
     }
+
+    fn load_results(title: &str) -> Option<IntegrationResults> {
+        let path = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title(title)
+            .pick_file()?;
+        match std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| IntegrationResults::from_json(&data))
+        {
+            Ok(results) => Some(results),
+            Err(e) => {
+                eprintln!("Failed to load results: {}", e);
+                None
+            }
+        }
+    }
+
+    /// The loaded results next to an earlier run, with each metric's change. Asterisks mark
+    /// changes too large to put down to chance.
+    fn comparison_ui(ui: &mut egui::Ui, compared: &ComparedRun) {
+        ui.heading("Comparison");
+        ui.label(format!(
+            "Against {} earlier combats. * p < 0.05, ** p < 0.01, *** p < 0.001",
+            compared.results.combats_run
+        ));
+        let percent = |value: f64| format!("{:.2}%", value * 100.0);
+        let comparison = &compared.comparison;
+        egui::Grid::new("comparison_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Metric");
+                ui.label("Before");
+                ui.label("After");
+                ui.label("Change");
+                ui.end_row();
+                let rounds = comparison
+                    .combat_length
+                    .iter()
+                    .map(|metric| (metric, false));
+                let rates = comparison
+                    .win_probabilities
+                    .values()
+                    .chain(comparison.survival.values())
+                    .map(|metric| (metric, true));
+                for (metric, is_rate) in rates.chain(rounds) {
+                    let format = |value: f64| {
+                        if is_rate {
+                            percent(value)
+                        } else {
+                            format!("{:.2}", value)
+                        }
+                    };
+                    let change = if is_rate {
+                        format!("{:+.2} pp", metric.delta() * 100.0)
+                    } else {
+                        format!("{:+.2}", metric.delta())
+                    };
+                    ui.label(&metric.name);
+                    ui.label(format(metric.before));
+                    ui.label(format(metric.after));
+                    let text = format!("{} {}", change, metric.significance().stars());
+                    // whether a change is good depends on whose side the reader is on, so
+                    // significant ones are only made to stand out
+                    if metric.significance() == Significance::NotSignificant {
+                        ui.label(text);
+                    } else {
+                        ui.label(egui::RichText::new(text).strong());
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn outcomes_ui(ui: &mut egui::Ui, state_tree: &StateTree) {
+        ui.heading("Outcomes");
+        let Ok(outcomes) = OutcomeProbabilities.query(state_tree) else {
+            return;
+        };
+        egui::Grid::new("outcomes_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for (outcome, ratio) in outcomes {
+                    let mut description = String::new();
+                    outcome
+                        .pretty_print(&mut description, state_tree.initial_state())
+                        .ok();
+                    ui.label(description);
+                    ui.monospace(format!("{:.2}% ({})", ratio.to_f64() * 100.0, ratio));
+                    ui.end_row();
+                }
+                let initial_state = state_tree.initial_state();
+                for ((actor, phase), ratio) in
+                    PhaseProbabilities.query(state_tree).unwrap_or_default()
+                {
+                    let Some(actor) = initial_state.get_actor(actor) else {
+                        continue;
+                    };
+                    ui.label(format!(
+                        "{} reaches {}",
+                        actor.name,
+                        actor
+                            .phases
+                            .get(phase)
+                            .map_or("", |phase| phase.name.as_str())
+                    ));
+                    ui.monospace(format!("{:.2}% ({})", ratio.to_f64() * 100.0, ratio));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn hp_time_series_ui(ui: &mut egui::Ui, initial_state: &State, series: &HpTimeSeries) {
+        ui.heading("HP Time Series");
+        for actor_id in series.histograms.keys() {
+            let mut name = String::new();
+            actor_id.pretty_print(&mut name, initial_state).ok();
+            let summaries = series.summary(*actor_id);
+            let max_health = initial_state
+                .get_actor(*actor_id)
+                .map_or(0, |actor| actor.max_health);
+            ui.label(&name);
+            hp_curve_plot(ui, &summaries, max_health);
+            egui::CollapsingHeader::new("Per-round detail")
+                .id_salt(("hp_time_series", actor_id.0))
+                .default_open(false)
+                .show(ui, |ui| {
+                    egui::Grid::new(("hp_time_series_grid", actor_id.0))
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in [
+                                "Round", "Samples", "Mean", "Min", "P10", "Median", "P90", "Max",
+                            ] {
+                                ui.strong(heading);
+                            }
+                            ui.end_row();
+                            for summary in &summaries {
+                                ui.monospace(summary.round.to_string());
+                                ui.monospace(summary.samples.to_string());
+                                ui.monospace(format!("{:.2}", summary.mean));
+                                ui.monospace(summary.min.to_string());
+                                ui.monospace(summary.p10.to_string());
+                                ui.monospace(summary.median.to_string());
+                                ui.monospace(summary.p90.to_string());
+                                ui.monospace(summary.max.to_string());
+                                ui.end_row();
+                            }
+                        });
+                });
+        }
+    }
+
+    fn contributions_ui(ui: &mut egui::Ui, summary: &RunSummary) {
+        ui.heading("Contributions");
+        let bar = |ui: &mut egui::Ui, share: f64, text: String| {
+            ui.add(
+                egui::ProgressBar::new(share as f32)
+                    .desired_width(200.0)
+                    .text(text),
+            );
+        };
+        egui::Grid::new("contributions_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for heading in [
+                    "Actor",
+                    "Damage share",
+                    "Healing share",
+                    "Kill participation",
+                ] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for actor in &summary.actors {
+                    let Some(contribution) = &actor.contribution else {
+                        continue;
+                    };
+                    ui.label(format!("{} ({})", actor.name, actor.group_name));
+                    bar(
+                        ui,
+                        contribution.damage_share,
+                        format!(
+                            "{:.1}% ({:.1}/combat)",
+                            contribution.damage_share * 100.0,
+                            contribution.mean_damage_dealt
+                        ),
+                    );
+                    bar(
+                        ui,
+                        contribution.healing_share,
+                        format!(
+                            "{:.1}% ({:.1}/combat)",
+                            contribution.healing_share * 100.0,
+                            contribution.mean_healing_done
+                        ),
+                    );
+                    bar(
+                        ui,
+                        contribution.kill_participation,
+                        format!(
+                            "{:.1}% ({} kills, {} assists)",
+                            contribution.kill_participation * 100.0,
+                            contribution.kills,
+                            contribution.assists
+                        ),
+                    );
+                    ui.end_row();
+                }
+            });
+        ui.label("Shares are of the actor's own group's total.");
+        if let Some(damage) = summary.environment_damage.filter(|damage| *damage > 0.0) {
+            ui.label(format!(
+                "Environmental effects dealt {:.1} damage per combat, credited to nobody.",
+                damage
+            ));
+        }
+
+        let typed: Vec<_> = summary
+            .actors
+            .iter()
+            .filter_map(|actor| Some((actor, actor.contribution.as_ref()?)))
+            .filter(|(_, contribution)| !contribution.damage_by_type.is_empty())
+            .collect();
+        if typed.is_empty() {
+            return;
+        }
+        ui.collapsing("Damage by type", |ui| {
+            egui::Grid::new("damage_by_type_grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    for heading in ["Actor", "Type", "Rolled", "Dealt", "Resisted"] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+                    for (actor, contribution) in typed {
+                        for (damage_type, damage) in &contribution.damage_by_type {
+                            ui.label(&actor.name);
+                            ui.label(format!("{:?}", damage_type));
+                            ui.label(format!("{:.1}", damage.mean_rolled));
+                            ui.label(format!("{:.1}", damage.mean_dealt));
+                            ui.label(format!("{:.1}", damage.mean_resisted()));
+                            ui.end_row();
+                        }
+                    }
+                });
+            ui.label("Per combat, before and after the targets' resistances.");
+        });
+    }
+
+    fn event_timeline_ui(ui: &mut egui::Ui, initial_state: &State, timeline: &EventTimeline) {
+        ui.heading("Event Timeline");
+        let combats = timeline.combats.max(1) as f32;
+        for (name, histogram) in timeline.events(initial_state) {
+            let header = match histogram.mean() {
+                Some(mean) => format!(
+                    "{} (in {:.1}% of combats, mean round {:.2})",
+                    name,
+                    histogram.occurrences() as f32 / combats * 100.0,
+                    mean
+                ),
+                None => format!("{} (never)", name),
+            };
+            egui::CollapsingHeader::new(header)
+                .id_salt(("event_timeline", &name))
+                .default_open(false)
+                .show(ui, |ui| {
+                    egui::Grid::new(("event_timeline_grid", &name))
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Round");
+                            ui.strong("Combats");
+                            ui.end_row();
+                            for (round, count) in &histogram.counts {
+                                let fraction = *count as f32 / combats;
+                                ui.monospace(round.to_string());
+                                ui.add(
+                                    egui::ProgressBar::new(fraction)
+                                        .desired_width(300.0)
+                                        .text(format!("{} ({:.1}%)", count, fraction * 100.0)),
+                                );
+                                ui.end_row();
+                            }
+                        });
+                });
+        }
+    }
+
+    fn outcome_labels_ui(ui: &mut egui::Ui, labels: &OutcomeLabels) {
+        ui.heading("Outcome Labels");
+        egui::Grid::new("outcome_labels_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for (label, count) in labels.sorted() {
+                    let fraction = labels.fraction(label) as f32;
+                    ui.label(label);
+                    ui.add(
+                        egui::ProgressBar::new(fraction)
+                            .desired_width(300.0)
+                            .text(format!("{} ({:.1}%)", count, fraction * 100.0)),
+                    );
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn turn_order_ui(ui: &mut egui::Ui, initial_state: &State, turn_order: &TurnOrderStats) {
+        ui.heading("Turn Order");
+        let interval = |estimate: Estimate, scale: f64| {
+            format!(
+                "{:.1} ({:.1} to {:.1})",
+                estimate.value * scale,
+                estimate.low * scale,
+                estimate.high * scale
+            )
+        };
+        egui::Grid::new("turn_order_groups_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for heading in [
+                    "Group",
+                    "Wins going first",
+                    "Otherwise",
+                    "Difference (95% CI)",
+                ] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for (group, record) in &turn_order.groups {
+                    let advantage = record.advantage();
+                    ui.label(initial_state.group_name(*group));
+                    ui.label(format!(
+                        "{:.1}% ({})",
+                        record.first.to_f64() * 100.0,
+                        record.first
+                    ));
+                    ui.label(format!(
+                        "{:.1}% ({})",
+                        record.later.to_f64() * 100.0,
+                        record.later
+                    ));
+                    ui.label(format!("{} points", interval(advantage, 100.0)))
+                        .on_hover_text(match advantage.excludes_zero() {
+                            true => "Unlikely to be chance",
+                            false => "Could be chance",
+                        });
+                    ui.end_row();
+                }
+            });
+        ui.add_space(8.0);
+        egui::Grid::new("turn_order_positions_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for heading in [
+                    "Position",
+                    "Damage per combat (95% CI)",
+                    "Survival % (95% CI)",
+                ] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for (position, stats) in &turn_order.positions {
+                    ui.label(format!("{}", position + 1));
+                    ui.label(interval(stats.damage(), 1.0));
+                    ui.label(interval(stats.survival(), 100.0));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn weapon_stats_ui(ui: &mut egui::Ui, initial_state: &State, weapons: &WeaponStats) {
+        ui.heading("Weapons");
+        let row = |ui: &mut egui::Ui, against: String, tally: &AttackTally| {
+            let hit_rate = tally.hit_rate();
+            ui.label(against);
+            ui.label(tally.attacks.to_string());
+            ui.label(format!(
+                "{:.1} ({:.1} to {:.1})",
+                hit_rate.value * 100.0,
+                hit_rate.low * 100.0,
+                hit_rate.high * 100.0
+            ));
+            ui.label(format!("{:.1}", tally.crit_rate().value * 100.0));
+            ui.label(format!("{:.2}", tally.damage_per_hit()));
+            ui.label(format!("{:.2}", tally.damage_per_attack()));
+            ui.end_row();
+        };
+        egui::Grid::new("weapon_stats_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for heading in [
+                    "Weapon",
+                    "Against",
+                    "Attacks",
+                    "Hit % (95% CI)",
+                    "Crit %",
+                    "Damage per hit",
+                    "Damage per attack",
+                ] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for (weapon, record) in &weapons.weapons {
+                    let mut name = String::new();
+                    weapon.pretty_print(&mut name, initial_state).ok();
+                    ui.strong(name);
+                    row(ui, "Everyone".to_string(), &record.total);
+                    for (target, tally) in &record.targets {
+                        let target = initial_state
+                            .get_actor(*target)
+                            .map_or_else(|| format!("{:?}", target), |actor| actor.name.clone());
+                        ui.label("");
+                        row(ui, target, tally);
+                    }
+                    for (armor_class, tally) in &record.armor_classes {
+                        ui.label("");
+                        row(ui, format!("AC {}", armor_class), tally);
+                    }
+                }
+            });
+    }
+
+    fn action_usage_ui(ui: &mut egui::Ui, initial_state: &State, usage: &ActionUsage) {
+        ui.heading("Action Usage");
+        for (actor, counts) in &usage.actors {
+            let name = initial_state
+                .get_actor(*actor)
+                .map_or_else(|| format!("{:?}", actor), |actor| actor.name.clone());
+            let total: u64 = counts.iter().map(|count| count.rounds.occurrences()).sum();
+            egui::CollapsingHeader::new(format!("{} ({} actions)", name, total))
+                .id_salt(("action_usage", actor))
+                .default_open(false)
+                .show(ui, |ui| {
+                    egui::Grid::new(("action_usage_grid", actor))
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for heading in ["Action", "Per combat", "Share", "Rounds"] {
+                                ui.strong(heading);
+                            }
+                            ui.end_row();
+                            for count in counts {
+                                let mut action = String::new();
+                                count
+                                    .action
+                                    .pretty_print(&mut action, *actor, initial_state)
+                                    .ok();
+                                let uses = count.rounds.occurrences();
+                                let share = uses as f32 / total.max(1) as f32;
+                                ui.label(action);
+                                ui.monospace(format!(
+                                    "{:.2}",
+                                    usage.per_combat(*actor, count.action)
+                                ));
+                                ui.add(
+                                    egui::ProgressBar::new(share)
+                                        .desired_width(150.0)
+                                        .text(format!("{:.1}%", share * 100.0)),
+                                );
+                                let rounds = count
+                                    .rounds
+                                    .counts
+                                    .iter()
+                                    .map(|(round, uses)| format!("{}: {}", round, uses))
+                                    .collect::<Vec<_>>()
+                                    .join(", ");
+                                ui.label(rounds);
+                                ui.end_row();
+                            }
+                        });
+                });
+        }
+    }
+
+    fn roll_statistics_ui(ui: &mut egui::Ui, statistics: &RollStatistics) {
+        ui.heading("Roll Statistics");
+        egui::Grid::new("roll_statistics_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                for heading in ["Die", "Rolls", "Chi-square", "Critical (0.1%)", "Fair?"] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for die in statistics.dice() {
+                    ui.monospace(format!("d{}", die.die_size));
+                    ui.monospace(die.rolls.to_string());
+                    ui.monospace(format!("{:.2}", die.chi_square));
+                    ui.monospace(format!("{:.2}", die.critical_value));
+                    if die.looks_fair() {
+                        ui.label("Yes");
+                    } else {
+                        ui.colored_label(egui::Color32::RED, "Suspicious");
+                    }
+                    ui.end_row();
+                }
+            });
+        for die in statistics.dice() {
+            egui::CollapsingHeader::new(format!("d{} faces", die.die_size))
+                .id_salt(("roll_statistics", die.die_size))
+                .default_open(false)
+                .show(ui, |ui| {
+                    egui::Grid::new(("roll_statistics_faces", die.die_size))
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Face");
+                            ui.strong("Count");
+                            ui.strong("Frequency");
+                            ui.end_row();
+                            for (face, count) in (1..).zip(&die.counts) {
+                                ui.monospace(face.to_string());
+                                ui.monospace(count.to_string());
+                                ui.monospace(format!("{:.2}%", die.frequency(face) * 100.0));
+                                ui.end_row();
+                            }
+                        });
+                });
+        }
+    }
+}
+
+/// Mean HP per round as a solid line, between thinner P10 and P90 lines, from 0 up to the actor's
+/// max HP or the highest P90, whichever is higher.
+fn hp_curve_plot(ui: &mut egui::Ui, summaries: &[HpRoundSummary], max_health: i32) {
+    let (Some(first), Some(last)) = (summaries.first(), summaries.last()) else {
+        return;
+    };
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width().min(400.0), 120.0),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let rounds = (last.round - first.round).max(1) as f32;
+    let top = summaries
+        .iter()
+        .map(|summary| summary.p90)
+        .fold(max_health, i32::max)
+        .max(1) as f32;
+    let point = |round: u64, hp: f32| {
+        egui::pos2(
+            rect.left() + rect.width() * ((round - first.round) as f32 / rounds),
+            rect.bottom() - rect.height() * (hp / top),
+        )
+    };
+    let curve = |hp: fn(&HpRoundSummary) -> f32| -> Vec<egui::Pos2> {
+        summaries
+            .iter()
+            .map(|summary| point(summary.round, hp(summary)))
+            .collect()
+    };
+    let color = ui.visuals().selection.bg_fill;
+    for band in [curve(|s| s.p10 as f32), curve(|s| s.p90 as f32)] {
+        painter.add(egui::Shape::line(
+            band,
+            egui::Stroke::new(1.0, color.gamma_multiply(0.5)),
+        ));
+    }
+    painter.add(egui::Shape::line(
+        curve(|s| s.mean as f32),
+        egui::Stroke::new(2.0, color),
+    ));
+    response.on_hover_text(format!(
+        "Mean HP with P10 and P90, rounds {} to {}, from 0 to {}",
+        first.round, last.round, top
+    ));
 }
@@ -0,0 +1,186 @@
+use std::sync::mpsc;
+
+use antikythera::prelude::*;
+use eframe::egui;
+
+/// A window that suggests monster compositions for the party in the state editor.
+pub struct BalancerWizard {
+    pub open: bool,
+    bestiary: Option<State>,
+    min_win: f64,
+    max_win: f64,
+    combats_per_candidate: usize,
+    max_monsters: u32,
+    max_evaluations: usize,
+    result_rx: Option<mpsc::Receiver<anyhow::Result<Vec<EncounterSuggestion>>>>,
+    suggestions: Vec<EncounterSuggestion>,
+    error: Option<String>,
+}
+
+impl Default for BalancerWizard {
+    fn default() -> Self {
+        Self {
+            open: false,
+            bestiary: None,
+            min_win: 0.6,
+            max_win: 0.8,
+            combats_per_candidate: 200,
+            max_monsters: 8,
+            max_evaluations: 64,
+            result_rx: None,
+            suggestions: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl BalancerWizard {
+    /// Shows the wizard if it's open. Picking a suggestion replaces `state` with it.
+    pub fn ui(&mut self, ctx: &egui::Context, state: &mut Option<State>) {
+        let mut open = self.open;
+        egui::Window::new("Encounter Balancer")
+            .open(&mut open)
+            .default_width(400.0)
+            .show(ctx, |ui| self.contents(ui, state));
+        self.open = open;
+    }
+
+    fn contents(&mut self, ui: &mut egui::Ui, state: &mut Option<State>) {
+        let Some(party) = state else {
+            ui.label("Create or load a state with the party first.");
+            return;
+        };
+
+        ui.label(format!(
+            "Party: {}",
+            party
+                .actors
+                .values()
+                .map(|actor| actor.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+
+        ui.horizontal(|ui| {
+            if ui.button("Load Bestiary").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .set_title("Select Bestiary State File")
+                    .pick_file()
+            {
+                match std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|source| State::from_json(&source))
+                    .and_then(|mut bestiary| {
+                        bestiary.validate()?;
+                        bestiary.fill_missing_policies();
+                        Ok(bestiary)
+                    }) {
+                    Ok(bestiary) => {
+                        self.bestiary = Some(bestiary);
+                        self.suggestions.clear();
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(format!("Failed to load bestiary: {}", e)),
+                }
+            }
+            if let Some(bestiary) = &self.bestiary {
+                ui.label(format!("{} monster templates", bestiary.actors.len()));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Target party win rate:");
+            ui.add(
+                egui::DragValue::new(&mut self.min_win)
+                    .range(0.0..=self.max_win)
+                    .speed(0.01)
+                    .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+            );
+            ui.label("to");
+            ui.add(
+                egui::DragValue::new(&mut self.max_win)
+                    .range(self.min_win..=1.0)
+                    .speed(0.01)
+                    .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Combats per candidate:");
+            ui.add(egui::DragValue::new(&mut self.combats_per_candidate).range(10..=10000));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Max monsters:");
+            ui.add(egui::DragValue::new(&mut self.max_monsters).range(1..=50));
+            ui.label("Max candidates:");
+            ui.add(egui::DragValue::new(&mut self.max_evaluations).range(1..=1000));
+        });
+
+        let running = self.result_rx.is_some();
+        let can_run = !running && self.bestiary.as_ref().is_some_and(|b| !b.actors.is_empty());
+        if ui
+            .add_enabled(can_run, egui::Button::new("Suggest Encounters"))
+            .clicked()
+            && let Some(bestiary) = &self.bestiary
+        {
+            let mut party = party.clone();
+            party.fill_missing_policies();
+            let balancer = Balancer::new(party, bestiary)
+                .target(self.min_win..=self.max_win)
+                .combats_per_candidate(self.combats_per_candidate)
+                .max_monsters(self.max_monsters)
+                .max_evaluations(self.max_evaluations);
+            let (result_tx, result_rx) = mpsc::channel();
+            std::thread::spawn(move || {
+                let _ = result_tx.send(balancer.suggest(5));
+            });
+            self.result_rx = Some(result_rx);
+            self.error = None;
+        }
+
+        if let Some(result_rx) = &self.result_rx {
+            match result_rx.try_recv() {
+                Ok(Ok(suggestions)) => {
+                    self.suggestions = suggestions;
+                    self.result_rx = None;
+                }
+                Ok(Err(e)) => {
+                    self.error = Some(format!("Balancing failed: {}", e));
+                    self.result_rx = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Simulating candidates...");
+                    });
+                }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.error = Some("Balancing thread stopped unexpectedly".to_string());
+                    self.result_rx = None;
+                }
+            }
+        }
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        let Some(bestiary) = &self.bestiary else {
+            return;
+        };
+        let mut chosen = None;
+        for (i, suggestion) in self.suggestions.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let mut text = String::new();
+                let _ = suggestion.pretty_print(&mut text, bestiary);
+                ui.label(text);
+                if ui.button("Use").clicked() {
+                    chosen = Some(i);
+                }
+            });
+        }
+        if let Some(i) = chosen {
+            *state = Some(self.suggestions[i].state.clone());
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::mpsc;
+
+use antikythera::prelude::*;
+use eframe::egui;
+
+/// Runs every actor in the state against every other 1v1 and shows the results as a heatmap.
+pub struct MatchupsPanel {
+    combats_per_pair: usize,
+    result_rx: Option<mpsc::Receiver<anyhow::Result<MatchupMatrix>>>,
+    matrix: Option<MatchupMatrix>,
+    error: Option<String>,
+}
+
+impl Default for MatchupsPanel {
+    fn default() -> Self {
+        Self {
+            combats_per_pair: 100,
+            result_rx: None,
+            matrix: None,
+            error: None,
+        }
+    }
+}
+
+impl MatchupsPanel {
+    pub fn ui(&mut self, ui: &mut egui::Ui, state: &State) {
+        ui.horizontal(|ui| {
+            ui.label("Combats per pair:");
+            ui.add(egui::DragValue::new(&mut self.combats_per_pair).range(1..=10000));
+
+            let can_run = self.result_rx.is_none() && state.actors.len() >= 2;
+            if ui
+                .add_enabled(can_run, egui::Button::new("Run Matchups"))
+                .clicked()
+            {
+                let mut state = state.clone();
+                state.fill_missing_policies();
+                let analysis = MatchupAnalysis::new(state).combats_per_pair(self.combats_per_pair);
+                let (result_tx, result_rx) = mpsc::channel();
+                std::thread::spawn(move || {
+                    let _ = result_tx.send(analysis.run());
+                });
+                self.result_rx = Some(result_rx);
+                self.error = None;
+            }
+
+            if let Some(matrix) = &self.matrix
+                && ui.button("Save CSV").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CSV", &["csv"])
+                    .set_file_name("matchups.csv")
+                    .save_file()
+            {
+                let mut csv = String::new();
+                if matrix.write_csv(&mut csv).is_ok()
+                    && let Err(e) = std::fs::write(&path, csv)
+                {
+                    log::error!("Failed to write {}: {}", path.display(), e);
+                }
+            }
+        });
+
+        if let Some(result_rx) = &self.result_rx {
+            match result_rx.try_recv() {
+                Ok(Ok(matrix)) => {
+                    self.matrix = Some(matrix);
+                    self.result_rx = None;
+                }
+                Ok(Err(e)) => {
+                    self.error = Some(format!("Matchups failed: {}", e));
+                    self.result_rx = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Simulating matchups...");
+                    });
+                }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.error = Some("Matchup thread stopped unexpectedly".to_string());
+                    self.result_rx = None;
+                }
+            }
+        }
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        if let Some(matrix) = &self.matrix {
+            Self::heatmap_ui(ui, matrix);
+        }
+    }
+
+    fn heatmap_ui(ui: &mut egui::Ui, matrix: &MatchupMatrix) {
+        ui.label("Row actor's chance to beat the column actor (mean rounds to kill in brackets).");
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            egui::Grid::new("matchup_heatmap")
+                .spacing([2.0, 2.0])
+                .show(ui, |ui| {
+                    ui.label("");
+                    for name in &matrix.names {
+                        ui.strong(name);
+                    }
+                    ui.end_row();
+
+                    for (name, row) in matrix.names.iter().zip(&matrix.results) {
+                        ui.strong(name);
+                        for result in row {
+                            let Some(result) = result else {
+                                ui.label("-");
+                                continue;
+                            };
+                            let win_probability = result.wins.to_f64();
+                            let mut text = format!("{:.0}%", win_probability * 100.0);
+                            if let Some(rounds) = result.mean_rounds_to_kill {
+                                text.push_str(&format!(" ({:.1})", rounds));
+                            }
+                            let (rect, response) = ui
+                                .allocate_exact_size(egui::vec2(90.0, 24.0), egui::Sense::hover());
+                            ui.painter()
+                                .rect_filled(rect, 2.0, heat_color(win_probability));
+                            ui.painter().text(
+                                rect.center(),
+                                egui::Align2::CENTER_CENTER,
+                                text,
+                                egui::FontId::monospace(12.0),
+                                egui::Color32::BLACK,
+                            );
+                            response.on_hover_text(format!(
+                                "Won {} combats: {:.2}%",
+                                result.wins,
+                                win_probability * 100.0
+                            ));
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
+
+/// Red for a sure loss through yellow to green for a sure win.
+fn heat_color(win_probability: f64) -> egui::Color32 {
+    let p = win_probability.clamp(0.0, 1.0) as f32;
+    let red = (2.0 * (1.0 - p)).min(1.0);
+    let green = (2.0 * p).min(1.0);
+    egui::Color32::from_rgb((red * 220.0) as u8, (green * 200.0) as u8, 80)
+}
@@ -0,0 +1,257 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::app::scripting::analysis::AnalysisScriptInterface;
+
+const LIBRARY_FILE: &str = "queries.json";
+
+/// The per-user data directory the GUI keeps its files in, following each platform's convention.
+fn data_dir() -> Option<PathBuf> {
+    let env_dir = |var| {
+        std::env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+    };
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")?
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME")?.join("Library").join("Application Support")
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| Some(env_dir("HOME")?.join(".local").join("share")))?
+    };
+    Some(base.join("antikythera"))
+}
+
+fn default_externals_only() -> bool {
+    true
+}
+
+/// An analysis query saved under a name, like "Hero survives" or "TPK".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedQuery {
+    pub name: String,
+    pub script: String,
+    #[serde(default = "default_externals_only")]
+    pub externals_only: bool,
+}
+
+impl NamedQuery {
+    /// The name its results are listed under in the metrics grid.
+    pub fn metric_name(&self) -> String {
+        if self.externals_only {
+            format!("Terminal State Probability of: {}", self.name)
+        } else {
+            format!("State Probability of: {}", self.name)
+        }
+    }
+}
+
+/// The user's saved queries, kept in the app's data directory between sessions.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryLibrary {
+    pub queries: Vec<NamedQuery>,
+}
+
+impl QueryLibrary {
+    /// Where the library is loaded from on startup and saved to when it changes.
+    pub fn default_path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join(LIBRARY_FILE))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// Loads the library from [`Self::default_path`], starting empty if there isn't one yet.
+    pub fn load_default() -> Self {
+        let Some(path) = Self::default_path().filter(|path| path.exists()) else {
+            return Self::default();
+        };
+        Self::load(&path).unwrap_or_else(|e| {
+            log::error!("Failed to load query library {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn save_default(&self) -> anyhow::Result<()> {
+        let path = Self::default_path()
+            .ok_or_else(|| anyhow::anyhow!("No data directory to save the query library in"))?;
+        self.save(&path)
+    }
+
+    /// Adds a query, replacing any saved under the same name.
+    pub fn insert(&mut self, query: NamedQuery) {
+        match self.queries.iter_mut().find(|q| q.name == query.name) {
+            Some(existing) => *existing = query,
+            None => self.queries.push(query),
+        }
+    }
+}
+
+/// Lists the saved queries, and runs them one at a time or all in a row.
+pub struct QueryLibraryPanel {
+    library: QueryLibrary,
+    new_query_name: String,
+    /// Queries waiting for their turn to run.
+    queue: VecDeque<NamedQuery>,
+}
+
+impl Default for QueryLibraryPanel {
+    fn default() -> Self {
+        Self {
+            library: QueryLibrary::load_default(),
+            new_query_name: String::new(),
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl QueryLibraryPanel {
+    /// The next query to run, once the one running now is done.
+    pub fn next_query(&mut self) -> Option<NamedQuery> {
+        self.queue.pop_front()
+    }
+
+    pub fn clear_queue(&mut self) {
+        self.queue.clear();
+    }
+
+    fn save(&self, script: &mut AnalysisScriptInterface) {
+        if let Err(e) = self.library.save_default() {
+            script.script_error = Some(format!("Failed to save query library: {}", e));
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, script: &mut AnalysisScriptInterface) {
+        let busy = script.is_running() || !self.queue.is_empty();
+        egui::CollapsingHeader::new(format!("Query Library ({})", self.library.queries.len()))
+            .id_salt("query_library")
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_query_name)
+                            .hint_text("Query name"),
+                    );
+                    let name = self.new_query_name.trim().to_string();
+                    if ui
+                        .add_enabled(!name.is_empty(), egui::Button::new("Save Current Query"))
+                        .on_hover_text("Saving under an existing name replaces that query")
+                        .clicked()
+                    {
+                        self.library.insert(NamedQuery {
+                            name,
+                            script: script.query.clone(),
+                            externals_only: script.externals_only,
+                        });
+                        self.new_query_name.clear();
+                        self.save(script);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !busy && !self.library.queries.is_empty(),
+                            egui::Button::new("Run All"),
+                        )
+                        .clicked()
+                    {
+                        self.queue = self.library.queries.iter().cloned().collect();
+                    }
+                    if ui.button("Import...").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("JSON", &["json"])
+                            .set_title("Import Query Library")
+                            .pick_file()
+                    {
+                        match QueryLibrary::load(&path) {
+                            Ok(imported) => {
+                                for query in imported.queries {
+                                    self.library.insert(query);
+                                }
+                                self.save(script);
+                            }
+                            Err(e) => {
+                                script.script_error =
+                                    Some(format!("Failed to import query library: {}", e));
+                            }
+                        }
+                    }
+                    if ui.button("Export...").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("JSON", &["json"])
+                            .set_title("Export Query Library")
+                            .set_file_name(LIBRARY_FILE)
+                            .save_file()
+                        && let Err(e) = self.library.save(&path)
+                    {
+                        script.script_error =
+                            Some(format!("Failed to export query library: {}", e));
+                    }
+                });
+
+                let mut open = None;
+                let mut delete = None;
+                egui::Grid::new("query_library_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (index, query) in self.library.queries.iter().enumerate() {
+                            ui.label(&query.name).on_hover_ui(|ui| {
+                                ui.monospace(&query.script);
+                            });
+                            ui.label(if query.externals_only {
+                                "Terminal states"
+                            } else {
+                                "All states"
+                            });
+                            if ui.button("Open").clicked() {
+                                open = Some(index);
+                            }
+                            if ui.add_enabled(!busy, egui::Button::new("Run")).clicked() {
+                                self.queue.push_back(query.clone());
+                            }
+                            if ui.button("Delete").clicked() {
+                                delete = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(index) = open
+                    && (!script.has_unsaved_changes() || crate::app::unsaved_changes_dialog())
+                {
+                    let query = &self.library.queries[index];
+                    script.query = query.script.clone();
+                    script.last_saved_query = Some(query.script.clone());
+                    script.externals_only = query.externals_only;
+                    self.new_query_name = query.name.clone();
+                }
+                if let Some(index) = delete
+                    && crate::app::confirm_dialog(
+                        "Delete Query",
+                        &format!(
+                            "Delete \"{}\" from the query library?",
+                            self.library.queries[index].name
+                        ),
+                    )
+                {
+                    self.library.queries.remove(index);
+                    self.save(script);
+                }
+            });
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::{Arc, Mutex};
+
+use antikythera::prelude::*;
+use eframe::egui;
+
+/// Something that happened in a replayed combat, rendered to text as it happened.
+enum ReplayEvent {
+    Transition {
+        emoji: &'static str,
+        text: String,
+    },
+    Decision {
+        summary: String,
+        targets: Vec<(String, i32)>,
+        candidates: Vec<CandidateAction>,
+    },
+}
+
+/// Records every transition and policy decision of the combats it's attached to.
+struct ReplayRecorder {
+    events: Arc<Mutex<Vec<ReplayEvent>>>,
+    messages: MessageTemplates,
+}
+
+impl Hook for ReplayRecorder {
+    fn on_transition(&mut self, state: &State, transition: &Transition) {
+        let mut text = String::new();
+        if self
+            .messages
+            .transition(&mut text, transition, state)
+            .is_ok()
+        {
+            self.events.lock().unwrap().push(ReplayEvent::Transition {
+                emoji: transition.emoji(),
+                text,
+            });
+        }
+    }
+
+    fn on_decision(&mut self, state: &State, decision: &DecisionAudit) {
+        let name = |actor: ActorId| {
+            let mut name = String::new();
+            actor.pretty_print(&mut name, state).ok();
+            name
+        };
+        let summary = match (decision.chosen, decision.target) {
+            (Some(action_type), Some(target)) => format!(
+                "{} chose {:?} against {}",
+                name(decision.actor),
+                action_type,
+                name(target)
+            ),
+            (Some(action_type), None) => {
+                format!("{} chose {:?}", name(decision.actor), action_type)
+            }
+            (None, _) => format!(
+                "{} had nothing to do ({:?})",
+                name(decision.actor),
+                decision.action_economy_usage
+            ),
+        };
+        self.events.lock().unwrap().push(ReplayEvent::Decision {
+            summary,
+            targets: decision
+                .targets
+                .iter()
+                .map(|(target, weight)| (name(*target), *weight))
+                .collect(),
+            candidates: decision.candidates.clone(),
+        });
+    }
+}
+
+/// Plays out a single combat and lists everything that happened, with each policy decision
+/// expandable to show the actions and targets it weighed.
+#[derive(Default)]
+pub struct ReplayPanel {
+    seed: u64,
+    events: Vec<ReplayEvent>,
+    error: Option<String>,
+}
+
+impl ReplayPanel {
+    pub fn ui(&mut self, ui: &mut egui::Ui, state: &State, messages: &MessageTemplates) {
+        ui.horizontal(|ui| {
+            ui.label("Seed:");
+            ui.add(egui::DragValue::new(&mut self.seed));
+            if ui.button("Replay Combat").clicked() {
+                self.replay(state, messages);
+            }
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        egui::ScrollArea::vertical()
+            .id_salt("combat_replay")
+            .max_height(400.0)
+            .show(ui, |ui| {
+                for (i, event) in self.events.iter().enumerate() {
+                    match event {
+                        ReplayEvent::Transition { emoji, text } => {
+                            ui.label(format!("{} {}", emoji, text));
+                        }
+                        ReplayEvent::Decision {
+                            summary,
+                            targets,
+                            candidates,
+                        } => {
+                            egui::CollapsingHeader::new(format!("🤔 Decision: {}", summary))
+                                .id_salt(("replay_decision", i))
+                                .default_open(false)
+                                .show(ui, |ui| Self::decision_ui(ui, i, targets, candidates));
+                        }
+                    }
+                }
+            });
+    }
+
+    fn decision_ui(
+        ui: &mut egui::Ui,
+        index: usize,
+        targets: &[(String, i32)],
+        candidates: &[CandidateAction],
+    ) {
+        if !targets.is_empty() {
+            ui.strong("Targets");
+            egui::Grid::new(("replay_targets", index))
+                .striped(true)
+                .show(ui, |ui| {
+                    for (name, weight) in targets {
+                        ui.label(name);
+                        ui.monospace(weight.to_string());
+                        ui.end_row();
+                    }
+                });
+        }
+        if !candidates.is_empty() {
+            ui.strong("Actions");
+            egui::Grid::new(("replay_candidates", index))
+                .striped(true)
+                .show(ui, |ui| {
+                    for candidate in candidates {
+                        ui.label(format!("{:?}", candidate.action_type));
+                        ui.monospace(candidate.weight.to_string());
+                        match candidate.rejected {
+                            Some(reason) => {
+                                ui.colored_label(egui::Color32::GRAY, reason.description())
+                            }
+                            None => ui.label("considered"),
+                        };
+                        ui.end_row();
+                    }
+                });
+        }
+    }
+
+    fn replay(&mut self, state: &State, messages: &MessageTemplates) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut state = state.clone();
+        state.fill_missing_policies();
+        let mut integrator = Integrator::new(1, Roller::from_seed(self.seed), state);
+        integrator.record_decisions(true);
+        integrator.add_hook(ReplayRecorder {
+            events: events.clone(),
+            messages: messages.clone(),
+        });
+        self.error = integrator
+            .run()
+            .err()
+            .map(|e| format!("Replay failed: {}", e));
+        self.events = std::mem::take(&mut *events.lock().unwrap());
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt::Write;
+
+use antikythera::prelude::*;
+
+/// File format of an exported analysis report.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+/// Which parts of the analysis go into an exported report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportSections {
+    pub summary: bool,
+    pub metrics: bool,
+    pub hp_chart: bool,
+    pub roll_statistics: bool,
+}
+
+impl Default for ReportSections {
+    fn default() -> Self {
+        Self {
+            summary: true,
+            metrics: true,
+            hp_chart: true,
+            roll_statistics: false,
+        }
+    }
+}
+
+enum Block {
+    Heading(String),
+    Paragraph(String),
+    Table {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    /// One line per series, indexed by round.
+    Chart {
+        y_label: String,
+        series: Vec<(String, Vec<f64>)>,
+    },
+}
+
+/// Renders the loaded results, and any metrics computed in the Analysis tab, into a standalone
+/// document.
+pub fn render_report(
+    results: &IntegrationResults,
+    metrics: &[(String, String)],
+    messages: &MessageTemplates,
+    sections: ReportSections,
+    format: ReportFormat,
+) -> anyhow::Result<String> {
+    let blocks = build_blocks(results, metrics, messages, sections);
+    let out = match format {
+        ReportFormat::Markdown => render_markdown(&blocks)?,
+        ReportFormat::Html => render_html(&blocks)?,
+    };
+    Ok(out)
+}
+
+fn build_blocks(
+    results: &IntegrationResults,
+    metrics: &[(String, String)],
+    messages: &MessageTemplates,
+    sections: ReportSections,
+) -> Vec<Block> {
+    let initial_state = results.state_tree.initial_state();
+    let name_of = |actor: ActorId| {
+        let mut name = String::new();
+        actor.pretty_print(&mut name, initial_state).ok();
+        name
+    };
+
+    let heading = |message| Block::Heading(messages.report(message, &[]));
+
+    let mut blocks = vec![heading(ReportMessage::ReportTitle)];
+    let run = results.metadata.as_ref().map_or(String::new(), |metadata| {
+        let seed = metadata.seed.map_or(String::new(), |seed| {
+            messages.report(ReportMessage::ReportSeed, &[("seed", seed.to_string())])
+        });
+        messages.report(
+            ReportMessage::ReportRun,
+            &[
+                ("version", metadata.version.clone()),
+                (
+                    "started",
+                    metadata
+                        .started_at
+                        .format("%Y-%m-%d %H:%M:%S UTC")
+                        .to_string(),
+                ),
+                ("seed", seed),
+            ],
+        )
+    });
+    blocks.push(Block::Paragraph(messages.report(
+        ReportMessage::ReportAbout,
+        &[("combats", results.combats_run.to_string()), ("run", run)],
+    )));
+
+    if sections.summary {
+        let summary = RunSummary::with_messages(results, messages);
+        blocks.push(heading(ReportMessage::SummaryHeading));
+        blocks.push(Block::Table {
+            headers: ["Actor", "Group", "Survived", "Standing", "Mean final HP"]
+                .map(String::from)
+                .to_vec(),
+            rows: summary
+                .actors
+                .iter()
+                .map(|actor| {
+                    vec![
+                        name_of(actor.actor),
+                        actor.group.to_string(),
+                        format!("{:.1}%", actor.survived.to_f64() * 100.0),
+                        format!("{:.1}%", actor.standing.to_f64() * 100.0),
+                        format!("{:.1} / {}", actor.mean_final_health, actor.max_health),
+                    ]
+                })
+                .collect(),
+        });
+    }
+
+    if sections.metrics {
+        let rows: Vec<Vec<String>> = metrics
+            .iter()
+            .map(|(name, result)| vec![name.clone(), result.clone()])
+            .chain(
+                results
+                    .hook_metrics
+                    .iter()
+                    .map(|(name, value)| vec![name.clone(), value.to_string()]),
+            )
+            .collect();
+        if !rows.is_empty() {
+            blocks.push(heading(ReportMessage::MetricsHeading));
+            blocks.push(Block::Table {
+                headers: vec!["Metric".to_string(), "Result".to_string()],
+                rows,
+            });
+        }
+    }
+
+    if sections.hp_chart
+        && let Some(series) = &results.hp_time_series
+    {
+        blocks.push(heading(ReportMessage::HpChartHeading));
+        blocks.push(Block::Chart {
+            y_label: "HP".to_string(),
+            series: series
+                .histograms
+                .keys()
+                .map(|actor| {
+                    let means = series
+                        .summary(*actor)
+                        .iter()
+                        .map(|round| round.mean)
+                        .collect();
+                    (name_of(*actor), means)
+                })
+                .collect(),
+        });
+    }
+
+    if sections.roll_statistics
+        && let Some(statistics) = &results.roll_statistics
+    {
+        blocks.push(heading(ReportMessage::RollStatisticsHeading));
+        blocks.push(Block::Table {
+            headers: ["Die", "Rolls", "Chi-square", "Critical (0.1%)", "Fair?"]
+                .map(String::from)
+                .to_vec(),
+            rows: statistics
+                .dice()
+                .iter()
+                .map(|die| {
+                    vec![
+                        format!("d{}", die.die_size),
+                        die.rolls.to_string(),
+                        format!("{:.2}", die.chi_square),
+                        format!("{:.2}", die.critical_value),
+                        if die.looks_fair() {
+                            "Yes"
+                        } else {
+                            "Suspicious"
+                        }
+                        .to_string(),
+                    ]
+                })
+                .collect(),
+        });
+    }
+
+    blocks
+}
+
+/// Keeps a cell on one line and stops it from closing the table early.
+fn markdown_cell(text: &str) -> String {
+    text.trim().replace('|', "\\|").replace('\n', "<br>")
+}
+
+fn render_markdown(blocks: &[Block]) -> anyhow::Result<String> {
+    let mut out = String::new();
+    for (index, block) in blocks.iter().enumerate() {
+        match block {
+            Block::Heading(text) if index == 0 => writeln!(out, "# {}", text)?,
+            Block::Heading(text) => writeln!(out, "## {}", text)?,
+            Block::Paragraph(text) => writeln!(out, "{}", text)?,
+            Block::Table { headers, rows } => {
+                writeln!(out, "| {} |", headers.join(" | "))?;
+                writeln!(out, "|{}", " --- |".repeat(headers.len()))?;
+                for row in rows {
+                    let cells: Vec<String> = row.iter().map(|cell| markdown_cell(cell)).collect();
+                    writeln!(out, "| {} |", cells.join(" | "))?;
+                }
+            }
+            // markdown has no charts, so the points go in a table with a column per series
+            Block::Chart { y_label, series } => {
+                let rounds = series.iter().map(|(_, points)| points.len()).max();
+                write!(out, "| Round |")?;
+                for (name, _) in series {
+                    write!(out, " {} ({}) |", markdown_cell(name), y_label)?;
+                }
+                writeln!(out)?;
+                writeln!(out, "| --- |{}", " --- |".repeat(series.len()))?;
+                for round in 0..rounds.unwrap_or(0) {
+                    write!(out, "| {} |", round)?;
+                    for (_, points) in series {
+                        match points.get(round) {
+                            Some(point) => write!(out, " {:.1} |", point)?,
+                            None => write!(out, " |")?,
+                        }
+                    }
+                    writeln!(out)?;
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(out)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const CHART_COLORS: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+];
+
+fn render_html(blocks: &[Block]) -> anyhow::Result<String> {
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    if let Some(Block::Heading(title)) = blocks.first() {
+        writeln!(out, "<title>{}</title>", escape_html(title))?;
+    }
+    writeln!(
+        out,
+        "<style>body{{font-family:sans-serif;max-width:60em;margin:auto}}\
+         table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:4px 8px}}\
+         td{{font-family:monospace;white-space:pre-wrap}}</style>"
+    )?;
+    writeln!(out, "</head><body>")?;
+    for (index, block) in blocks.iter().enumerate() {
+        match block {
+            Block::Heading(text) if index == 0 => writeln!(out, "<h1>{}</h1>", escape_html(text))?,
+            Block::Heading(text) => writeln!(out, "<h2>{}</h2>", escape_html(text))?,
+            Block::Paragraph(text) => writeln!(out, "<p>{}</p>", escape_html(text))?,
+            Block::Table { headers, rows } => {
+                write!(out, "<table><tr>")?;
+                for header in headers {
+                    write!(out, "<th>{}</th>", escape_html(header))?;
+                }
+                writeln!(out, "</tr>")?;
+                for row in rows {
+                    write!(out, "<tr>")?;
+                    for cell in row {
+                        write!(out, "<td>{}</td>", escape_html(cell.trim()))?;
+                    }
+                    writeln!(out, "</tr>")?;
+                }
+                writeln!(out, "</table>")?;
+            }
+            Block::Chart { y_label, series } => render_svg_chart(&mut out, y_label, series)?,
+        }
+    }
+    writeln!(out, "</body></html>")?;
+    Ok(out)
+}
+
+fn render_svg_chart(
+    out: &mut String,
+    y_label: &str,
+    series: &[(String, Vec<f64>)],
+) -> std::fmt::Result {
+    const WIDTH: f64 = 640.0;
+    const HEIGHT: f64 = 320.0;
+    const MARGIN: f64 = 40.0;
+
+    let rounds = series
+        .iter()
+        .map(|(_, points)| points.len())
+        .max()
+        .unwrap_or(0);
+    let max_y = series
+        .iter()
+        .flat_map(|(_, points)| points.iter().copied())
+        .fold(1.0, f64::max);
+    let x =
+        |round: usize| MARGIN + round as f64 / (rounds.max(2) - 1) as f64 * (WIDTH - 2.0 * MARGIN);
+    let y = |value: f64| HEIGHT - MARGIN - value / max_y * (HEIGHT - 2.0 * MARGIN);
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+         font-family=\"sans-serif\" font-size=\"12\">"
+    )?;
+    writeln!(
+        out,
+        "<line x1=\"{MARGIN}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"black\"/>\
+         <line x1=\"{MARGIN}\" y1=\"{MARGIN}\" x2=\"{MARGIN}\" y2=\"{b}\" stroke=\"black\"/>",
+        b = HEIGHT - MARGIN,
+        r = WIDTH - MARGIN,
+    )?;
+    writeln!(
+        out,
+        "<text x=\"4\" y=\"{}\">{:.0}</text><text x=\"4\" y=\"{}\">0</text>\
+         <text x=\"4\" y=\"16\">{}</text><text x=\"{}\" y=\"{}\">Round</text>",
+        MARGIN + 4.0,
+        max_y,
+        HEIGHT - MARGIN,
+        escape_html(y_label),
+        WIDTH / 2.0,
+        HEIGHT - 8.0,
+    )?;
+    for (index, (name, points)) in series.iter().enumerate() {
+        let color = CHART_COLORS[index % CHART_COLORS.len()];
+        let path: Vec<String> = points
+            .iter()
+            .enumerate()
+            .map(|(round, value)| format!("{:.1},{:.1}", x(round), y(*value)))
+            .collect();
+        writeln!(
+            out,
+            "<polyline fill=\"none\" stroke=\"{color}\" stroke-width=\"2\" points=\"{}\"/>",
+            path.join(" ")
+        )?;
+        writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" fill=\"{color}\">{}</text>",
+            WIDTH - MARGIN - 120.0,
+            MARGIN + 14.0 * index as f64,
+            escape_html(name)
+        )?;
+    }
+    writeln!(out, "</svg>")
+}
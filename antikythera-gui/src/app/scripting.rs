@@ -4,9 +4,13 @@ use mlua::prelude::*;
 pub mod analysis;
 pub mod simulation;
 
-pub struct LuaState(pub State);
+/// A borrowed view of a [`State`] for Lua scripts.
+///
+/// Create it inside [`Lua::scope`] so scripts can read the state without copying it; actors and
+/// items are only copied out when a script actually accesses them.
+pub struct LuaState<'a>(pub &'a State);
 
-impl LuaUserData for LuaState {
+impl LuaUserData for LuaState<'_> {
     fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
         fields.add_field_method_get("turn", |_, this| Ok(this.0.turn));
         fields.add_field_method_get("actors", |lua, this| {
@@ -16,9 +20,30 @@ impl LuaUserData for LuaState {
             }
             Ok(table)
         });
+        fields.add_field_method_get("items", |lua, this| {
+            let table = lua.create_table()?;
+            for (id, item) in &this.0.items {
+                table.set(id.0, LuaItem(item.clone()))?;
+            }
+            Ok(table)
+        });
+        fields.add_field_method_get("initiative_order", |lua, this| {
+            lua.create_sequence_from(this.0.initiative_order.iter().map(|id| id.0))
+        });
+        fields.add_field_method_get("current_actor", |_, this| {
+            Ok(this.0.current_actor().map(|id| id.0))
+        });
     }
 
     fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("actor", |_, this, id: u32| {
+            Ok(this.0.get_actor(ActorId(id)).cloned().map(LuaActor))
+        });
+
+        methods.add_method("item", |_, this, id: u32| {
+            Ok(this.0.items.get(&ItemId(id)).cloned().map(LuaItem))
+        });
+
         methods.add_method("actor_id", |_, this, name: String| {
             for actor in this.0.actors.values() {
                 if actor.name == name {
@@ -31,6 +56,18 @@ impl LuaUserData for LuaState {
             )))
         });
 
+        methods.add_method("actors_with_tag", |lua, this, tag: String| {
+            let table = lua.create_table()?;
+            for actor in this.0.actors_with_tag(&tag) {
+                table.set(actor.id.0, LuaActor(actor.clone()))?;
+            }
+            Ok(table)
+        });
+
+        methods.add_method("all_tagged_down", |_, this, tag: String| {
+            Ok(this.0.all_tagged_down(&tag))
+        });
+
         methods.add_method(
             "actor_alive",
             |_, this, actor_id: LuaValue| match actor_id {
@@ -73,11 +110,91 @@ impl LuaUserData for LuaActor {
         fields.add_field_method_get("hp", |_, this| Ok(this.0.health));
         fields.add_field_method_get("max_health", |_, this| Ok(this.0.max_health));
         fields.add_field_method_get("group", |_, this| Ok(this.0.group));
+        fields.add_field_method_get("tags", |lua, this| {
+            lua.create_sequence_from(this.0.tags.iter().cloned())
+        });
+        fields.add_field_method_get("ac", |_, this| Ok(this.0.armor_class));
+        fields.add_field_method_get("initiative", |_, this| Ok(this.0.initiative));
+        // item ID -> quantity
+        fields.add_field_method_get("inventory", |lua, this| {
+            lua.create_table_from(
+                this.0
+                    .inventory
+                    .items
+                    .iter()
+                    .map(|(id, quantity)| (id.0, *quantity)),
+            )
+        });
+        fields.add_field_method_get("equipped", |lua, this| {
+            lua.create_sequence_from(this.0.equipped_items.items.iter().map(|id| id.0))
+        });
+        // sequence of { name = "grappled", by = <actor ID> }, { name = "prone" }
+        // or { name = "helped", by = <actor ID>, against = <actor ID> }
+        fields.add_field_method_get("conditions", |lua, this| {
+            let table = lua.create_table()?;
+            for condition in this.0.conditions.iter() {
+                let entry = lua.create_table()?;
+                entry.set("name", condition.name())?;
+                match condition {
+                    Condition::Grappled { by } => entry.set("by", by.0)?,
+                    Condition::Helped { by, against } => {
+                        entry.set("by", by.0)?;
+                        entry.set("against", against.0)?;
+                    }
+                    Condition::Prone => {}
+                }
+                table.push(entry)?;
+            }
+            Ok(table)
+        });
+        fields.add_field_method_get("action_available", |_, this| {
+            Ok(!this.0.action_economy.action_used)
+        });
+        fields.add_field_method_get("bonus_action_available", |_, this| {
+            Ok(!this.0.action_economy.bonus_action_used)
+        });
+        fields.add_field_method_get("reaction_available", |_, this| {
+            Ok(!this.0.action_economy.reaction_used)
+        });
+        fields.add_field_method_get("movement_remaining", |_, this| {
+            Ok(this
+                .0
+                .movement_speed
+                .saturating_sub(this.0.action_economy.movement_used))
+        });
     }
 
     fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("is_alive", |_, this, ()| Ok(this.0.is_alive()));
         methods.add_method("is_unconscious", |_, this, ()| Ok(this.0.is_unconscious()));
         methods.add_method("is_dead", |_, this, ()| Ok(this.0.is_dead()));
+        methods.add_method("has_tag", |_, this, tag: String| Ok(this.0.has_tag(&tag)));
+        methods.add_method("has_condition", |_, this, name: String| {
+            Ok(this.0.conditions.iter().any(|c| c.name() == name))
+        });
+        methods.add_method("item_count", |_, this, id: u32| {
+            Ok(this
+                .0
+                .inventory
+                .items
+                .get(&ItemId(id))
+                .copied()
+                .unwrap_or(0))
+        });
+        methods.add_method("is_equipped", |_, this, id: u32| {
+            Ok(this.0.equipped_items.is_equipped(ItemId(id)))
+        });
+    }
+}
+
+pub struct LuaItem(pub Item);
+
+impl LuaUserData for LuaItem {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("id", |_, this| Ok(this.0.id.0));
+        fields.add_field_method_get("name", |_, this| Ok(this.0.name.clone()));
+        fields.add_field_method_get("type", |_, this| {
+            Ok(format!("{:?}", this.0.item_type()).to_lowercase())
+        });
     }
 }
@@ -1,3 +1,9 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
+
 use antikythera::prelude::*;
 use mlua::prelude::*;
 
@@ -9,12 +15,20 @@ const DEFAULT_QUERY_SCRIPT: &str = r#"function query(state)
 end"#;
 
 pub struct AnalysisScriptInterface {
-    lua: Lua,
     pub query: String,
     pub last_saved_query: Option<String>,
     pub externals_only: bool,
     pub script_error: Option<String>,
     pub metrics: Vec<(String, f64)>,
+    job: Option<QueryJob>,
+}
+
+/// A query running on a background thread, so long scripts don't freeze the UI.
+struct QueryJob {
+    progress: f64,
+    progress_rx: mpsc::Receiver<f64>,
+    result_rx: mpsc::Receiver<anyhow::Result<Ratio>>,
+    cancel: Arc<AtomicBool>,
 }
 
 impl Default for AnalysisScriptInterface {
@@ -26,12 +40,12 @@ impl Default for AnalysisScriptInterface {
 impl AnalysisScriptInterface {
     pub fn new() -> Self {
         Self {
-            lua: Lua::new(),
             query: String::from(DEFAULT_QUERY_SCRIPT),
             last_saved_query: Some(String::from(DEFAULT_QUERY_SCRIPT)),
             externals_only: true,
             script_error: None,
             metrics: Vec::new(),
+            job: None,
         }
     }
 
@@ -43,29 +57,81 @@ impl AnalysisScriptInterface {
         }
     }
 
-    fn reset_lua(&mut self) {
-        self.lua = Lua::new();
-        self.script_error = None;
+    pub fn is_running(&self) -> bool {
+        self.job.is_some()
+    }
 
-        // insert an empty table for metrics
-        let globals = self.lua.globals();
-        if let Err(e) = globals.set("metrics", self.lua.create_table().unwrap()) {
-            self.script_error = Some(format!("Error creating metrics table: {}", e));
-        }
+    /// Fraction of the states to query that have been visited so far, if a query is running.
+    pub fn progress(&self) -> Option<f64> {
+        self.job.as_ref().map(|job| job.progress)
+    }
 
+    /// Starts running `script` over a copy of `state_tree` on a background thread. Check on it
+    /// with [`Self::poll_query`].
+    pub fn start_outcome_probability_query(
+        &mut self,
+        state_tree: &StateTree,
+        script: String,
+        externals_only: bool,
+    ) {
+        self.cancel_query();
+        self.script_error = None;
         self.metrics.clear();
+
+        let (progress_tx, progress_rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let state_tree = state_tree.clone();
+        let condition = script;
+        std::thread::spawn({
+            let cancel = cancel.clone();
+            move || {
+                let lua = Lua::new();
+                let run = || {
+                    lua.globals().set("metrics", lua.create_table()?)?;
+                    ScriptProbabilityQuery {
+                        lua: &lua,
+                        condition,
+                        externals_only,
+                        progress_tx: Some(progress_tx),
+                        cancel: Some(cancel),
+                    }
+                    .query(&state_tree)
+                };
+                let _ = result_tx.send(run());
+            }
+        });
+
+        self.job = Some(QueryJob {
+            progress: 0.0,
+            progress_rx,
+            result_rx,
+            cancel,
+        });
     }
 
-    pub fn run_outcome_probability_query(&mut self, state_tree: &StateTree) -> anyhow::Result<f64> {
-        self.reset_lua();
+    /// Stops the running query, if any. Its result is thrown away.
+    pub fn cancel_query(&mut self) {
+        if let Some(job) = self.job.take() {
+            job.cancel.store(true, Ordering::Relaxed);
+        }
+    }
 
-        let query = ScriptProbabilityQuery {
-            lua: &self.lua,
-            condition: self.query.clone(),
-            externals_only: self.externals_only,
+    /// Updates the running query's progress, and returns its result once it's done.
+    pub fn poll_query(&mut self) -> Option<anyhow::Result<Ratio>> {
+        let job = self.job.as_mut()?;
+        while let Ok(progress) = job.progress_rx.try_recv() {
+            job.progress = progress;
+        }
+        let result = match job.result_rx.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Err(anyhow::anyhow!("Query thread stopped unexpectedly"))
+            }
         };
-        let result = query.query(state_tree)?;
-        Ok(result)
+        self.job = None;
+        Some(result)
     }
 }
 
@@ -73,12 +139,17 @@ pub struct ScriptProbabilityQuery<'a> {
     lua: &'a Lua,
     pub condition: String,
     pub externals_only: bool,
+    /// Receives the fraction of states visited as the query runs.
+    pub progress_tx: Option<mpsc::Sender<f64>>,
+    /// Stops the query early when set.
+    pub cancel: Option<Arc<AtomicBool>>,
 }
 
 impl Query for ScriptProbabilityQuery<'_> {
-    type Output = f64;
+    type Output = Ratio;
 
     fn query(&self, state_tree: &StateTree) -> anyhow::Result<Self::Output> {
+        state_tree.ensure_states_kept("Queries")?;
         let mut total_states = 0;
         let mut count = 0;
         self.lua.load(&self.condition).exec()?;
@@ -86,16 +157,32 @@ impl Query for ScriptProbabilityQuery<'_> {
         let func: LuaFunction = globals.get("query")?;
 
         let mut error = None;
+        let mut cancelled = false;
+        let to_visit = if self.externals_only || state_tree.mode() != TreeMode::Full {
+            state_tree.terminal_state_count()
+        } else {
+            state_tree.node_count()
+        };
+        let mut visited = 0;
+        let mut last_reported = 0.0;
 
         state_tree.visit_states(self.externals_only, |state, hits| {
-            let lua_state = match self.lua.create_userdata(LuaState(state.clone())) {
-                Ok(ud) => ud,
-                Err(e) => {
-                    error = Some(anyhow::anyhow!("Error creating Lua state: {}", e));
-                    return false;
-                }
-            };
-            let result: bool = match func.call((lua_state,)) {
+            if error.is_some() || cancelled {
+                return false;
+            }
+            if self
+                .cancel
+                .as_ref()
+                .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+            {
+                cancelled = true;
+                return false;
+            }
+            let result = self.lua.scope(|scope| {
+                let lua_state = scope.create_userdata(LuaState(state))?;
+                func.call::<bool>((lua_state,))
+            });
+            let result = match result {
                 Ok(res) => res,
                 Err(e) => {
                     error = Some(anyhow::anyhow!("Error calling Lua function: {}", e));
@@ -110,19 +197,23 @@ impl Query for ScriptProbabilityQuery<'_> {
             self.lua.gc_collect().ok();
             self.lua.gc_collect().ok();
 
+            visited += 1;
+            if let Some(progress_tx) = &self.progress_tx {
+                let progress = visited as f64 / to_visit.max(1) as f64;
+                if progress - last_reported >= 0.01 {
+                    last_reported = progress;
+                    let _ = progress_tx.send(progress);
+                }
+            }
+
             true
         });
 
         if let Some(e) = error {
             return Err(e);
         }
+        anyhow::ensure!(!cancelled, "Query cancelled");
 
-        let result = if total_states > 0 {
-            count as f64 / total_states as f64
-        } else {
-            0.0
-        };
-
-        Ok(result)
+        Ok(Ratio::new(count, total_states))
     }
 }
@@ -1,3 +1,5 @@
+use std::sync::{Arc, Mutex};
+
 use antikythera::prelude::*;
 use mlua::prelude::*;
 
@@ -13,26 +15,36 @@ pub struct LuaHook {
     pub script: String,
     script_rx: crossbeam_channel::Receiver<String>,
     script_error_tx: crossbeam_channel::Sender<String>,
+    /// Metrics the script has passed to `hook.emit` since the integrator last took them.
+    emitted: Arc<Mutex<Vec<(String, f64)>>>,
+    /// The current Lua state, kept in step with `lua` for the [`LuaOutcomeClassifier`].
+    shared_lua: Arc<Mutex<Lua>>,
+    /// Why the script last failed to load, if it did.
+    load_error: Option<String>,
 }
 
 impl LuaHook {
     pub fn new(script: String) -> (Self, LuaHookHandle) {
         let (script_tx, script_rx) = crossbeam_channel::unbounded();
         let (script_error_tx, script_error_rx) = crossbeam_channel::unbounded();
+        let lua = Lua::new();
         let mut this = Self {
-            lua: Lua::new(),
+            shared_lua: Arc::new(Mutex::new(lua.clone())),
+            lua,
             script,
             script_rx,
             script_error_tx,
+            emitted: Arc::default(),
+            load_error: None,
         };
         this.reset_lua();
 
         if !this.script.is_empty()
             && let Err(e) = this.lua.load(&this.script).exec()
         {
-            let _ = this
-                .script_error_tx
-                .send(format!("Error loading Lua script: {}", e));
+            let error = format!("Error loading Lua script: {}", e);
+            let _ = this.script_error_tx.send(error.clone());
+            this.load_error = Some(error);
         }
 
         (
@@ -46,6 +58,7 @@ impl LuaHook {
 
     fn reset_lua(&mut self) {
         self.lua = Lua::new();
+        *self.shared_lua.lock().unwrap() = self.lua.clone();
 
         // insert an empty table for metrics
         let globals = self.lua.globals();
@@ -54,6 +67,24 @@ impl LuaHook {
                 .script_error_tx
                 .send(format!("Error creating metrics table `M`: {}", e));
         }
+
+        // `hook.emit(name, value)` reports a metric partway through the run
+        let emitted = self.emitted.clone();
+        let hook = self.lua.create_table().and_then(|hook| {
+            let emit = self
+                .lua
+                .create_function(move |_, (name, value): (String, f64)| {
+                    emitted.lock().unwrap().push((name, value));
+                    Ok(())
+                })?;
+            hook.set("emit", emit)?;
+            Ok(hook)
+        });
+        if let Err(e) = hook.and_then(|hook| globals.set("hook", hook)) {
+            let _ = self
+                .script_error_tx
+                .send(format!("Error creating `hook` table: {}", e));
+        }
     }
 
     fn reload_script(&mut self) {
@@ -68,29 +99,86 @@ impl LuaHook {
         }
 
         self.reset_lua();
+        self.load_error = None;
 
         if !self.script.is_empty()
             && let Err(e) = self.lua.load(&self.script).exec()
         {
             log::error!("Error loading Lua script: {}", e);
-            let _ = self
-                .script_error_tx
-                .send(format!("Error loading Lua script: {}", e));
+            let error = format!("Error loading Lua script: {}", e);
+            let _ = self.script_error_tx.send(error.clone());
+            self.load_error = Some(error);
         }
     }
+
+    /// A classifier calling the script's `classify_outcome`, or `None` if it doesn't define one.
+    /// Fails if the script didn't load.
+    pub fn outcome_classifier(&self) -> anyhow::Result<Option<LuaOutcomeClassifier>> {
+        if let Some(error) = &self.load_error {
+            anyhow::bail!("{}", error);
+        }
+        if self
+            .lua
+            .globals()
+            .get::<LuaFunction>("classify_outcome")
+            .is_err()
+        {
+            return Ok(None);
+        }
+        Ok(Some(LuaOutcomeClassifier {
+            lua: self.shared_lua.clone(),
+        }))
+    }
+}
+
+/// Labels outcomes with a hook script's `classify_outcome(state)`, called in the hook's own Lua
+/// state so it sees whatever the hook's callbacks have recorded.
+pub struct LuaOutcomeClassifier {
+    lua: Arc<Mutex<Lua>>,
+}
+
+impl OutcomeClassifier for LuaOutcomeClassifier {
+    fn classify(&mut self, state: &State) -> String {
+        let lua = self.lua.lock().unwrap().clone();
+        let label = lua
+            .globals()
+            .get::<LuaFunction>("classify_outcome")
+            .and_then(|func| {
+                lua.scope(|scope| {
+                    let state = scope.create_userdata(LuaState(state))?;
+                    func.call::<String>(state)
+                })
+            });
+        label.unwrap_or_else(|e| {
+            log::error!("Error in classify_outcome: {}", e);
+            "error".to_string()
+        })
+    }
 }
 
 macro_rules! lua_delegate {
-    ($self:expr, $func:ident, $($arg:expr),*) => {
-        if let Ok(globals) = $self.lua.globals().get::<LuaFunction>(stringify!($func))
-        {
-            let res = globals.call::<()>(($($arg),*));
-            if let Err(e) = res {
-                log::error!("Error in {}: {}", stringify!($func), e);
-                let _ = $self
-                    .script_error_tx
-                    .send(format!("Error in {}: {}", stringify!($func), e));
-            }
+    (@report $self:expr, $func:ident, $res:expr) => {
+        if let Err(e) = $res {
+            log::error!("Error in {}: {}", stringify!($func), e);
+            let _ = $self
+                .script_error_tx
+                .send(format!("Error in {}: {}", stringify!($func), e));
+        }
+    };
+    ($self:expr, $func:ident, state: $state:expr $(, $arg:expr)*) => {
+        if let Ok(func) = $self.lua.globals().get::<LuaFunction>(stringify!($func)) {
+            // borrow the state for the duration of the call instead of cloning it
+            let res = $self.lua.scope(|scope| {
+                let state = scope.create_userdata(LuaState($state))?;
+                func.call::<()>((state, $($arg,)*))
+            });
+            lua_delegate!(@report $self, $func, res);
+        }
+    };
+    ($self:expr, $func:ident $(, $arg:expr)*) => {
+        if let Ok(func) = $self.lua.globals().get::<LuaFunction>(stringify!($func)) {
+            let res = func.call::<()>(($($arg,)*));
+            lua_delegate!(@report $self, $func, res);
         }
     };
 }
@@ -98,18 +186,18 @@ macro_rules! lua_delegate {
 impl Hook for LuaHook {
     fn on_integration_start(&mut self, initial_state: &State) {
         self.reload_script();
-        lua_delegate!(self, on_integration_start, LuaState(initial_state.clone()));
+        lua_delegate!(self, on_integration_start, state: initial_state);
     }
 
     fn on_combat_start(&mut self, state: &State) {
-        lua_delegate!(self, on_combat_start, LuaState(state.clone()));
+        lua_delegate!(self, on_combat_start, state: state);
     }
 
     fn on_turn_start(&mut self, state: &State, actor_id: ActorId, turn: u64) {
         lua_delegate!(
             self,
             on_turn_start,
-            LuaState(state.clone()),
+            state: state,
             actor_id.0 as i64,
             turn
         );
@@ -119,32 +207,32 @@ impl Hook for LuaHook {
         lua_delegate!(
             self,
             on_advance_initiative,
-            LuaState(state.clone()),
+            state: state,
             actor_id.0 as i64
         );
     }
 
     fn on_action_executed(&mut self, state: &State, action: &ActionTaken) {
         let action = self.lua.to_value(&action).unwrap_or(LuaValue::Nil);
-        lua_delegate!(self, on_action_executed, LuaState(state.clone()), action);
+        lua_delegate!(self, on_action_executed, state: state, action);
     }
 
     fn on_turn_end(&mut self, state: &State, actor_id: ActorId, turn: u64) {
         lua_delegate!(
             self,
             on_turn_end,
-            LuaState(state.clone()),
+            state: state,
             actor_id.0 as i64,
             turn
         );
     }
 
     fn on_combat_end(&mut self, state: &State) {
-        lua_delegate!(self, on_combat_end, LuaState(state.clone()));
+        lua_delegate!(self, on_combat_end, state: state);
     }
 
     fn on_integration_end(&mut self) {
-        lua_delegate!(self, on_integration_end,);
+        lua_delegate!(self, on_integration_end);
     }
 
     fn metrics(&self) -> Vec<(String, f64)> {
@@ -158,4 +246,8 @@ impl Hook for LuaHook {
         }
         result
     }
+
+    fn take_emitted(&mut self) -> Vec<(String, f64)> {
+        std::mem::take(&mut *self.emitted.lock().unwrap())
+    }
 }
@@ -1,12 +1,17 @@
-use std::sync::mpsc;
+use std::{collections::BTreeMap, sync::mpsc};
 
 use antikythera::prelude::*;
 use eframe::egui;
 
-use crate::app::scripting::simulation::{LuaHook, LuaHookHandle};
+use crate::app::{
+    matchups::MatchupsPanel,
+    replay::ReplayPanel,
+    scripting::simulation::{LuaHook, LuaHookHandle},
+};
 
 const DEFAULT_HOOK_SCRIPT: &str = r#"-- Example Lua Hook Script
--- The global table `metrics` is available to store custom metrics
+-- The global table `M` is available to store custom metrics
+-- Call `hook.emit(name, value)` to report a metric partway through the run and watch it converge
 
 function on_integration_start(initial_state)
     -- Initialize any state or metrics here
@@ -30,24 +35,56 @@ end
 
 function on_combat_end(state)
     -- Called at the end of each combat
+    -- hook.emit("my_metric", value)
 end
 
 function on_integration_end()
     -- Finalize metrics here
 end
 
+-- With "Label outcomes" on, define classify_outcome to label how each combat went instead of
+-- using the built-in labels
+-- function classify_outcome(state)
+--     return "my label"
+-- end
+
 "#;
 
 pub struct SimulationApp {
     pub state: Option<State>,
     pub combats: usize,
+    /// Run for [`Self::time_budget`] seconds instead of a fixed number of combats.
+    pub time_boxed: bool,
+    pub time_budget: f64,
+    pub record_hp_time_series: bool,
+    pub record_roll_statistics: bool,
+    pub record_event_timeline: bool,
+    pub record_contributions: bool,
+    pub record_action_usage: bool,
+    pub record_outcome_labels: bool,
+    pub record_turn_order: bool,
+    pub record_weapon_stats: bool,
+    pub tree_mode: TreeMode,
+    pub batch_actions: bool,
+    /// Soft limit on the state tree's size in megabytes, if any.
+    pub memory_limit: Option<usize>,
+    pub memory_limit_action: MemoryLimitAction,
+    /// Templates the log and the combat replay are worded with.
+    pub messages: MessageTemplates,
+    /// The file [`Self::messages`] was loaded from, if they aren't the built-in ones.
+    messages_source: Option<String>,
     progress: f64,
     progress_rx: Option<mpsc::Receiver<f64>>,
     result_rx: Option<mpsc::Receiver<IntegrationResults>>,
+    metric_rx: Option<mpsc::Receiver<MetricSample>>,
+    /// Metrics emitted by the hook so far, as (combats run, value) points for each name.
+    emitted_metrics: BTreeMap<String, Vec<(usize, f64)>>,
     pub stats: Option<IntegrationResults>,
     pub hook_script: String,
     pub last_saved_hook_script: Option<String>,
     pub hook_handle: Option<LuaHookHandle>,
+    matchups: MatchupsPanel,
+    replay: ReplayPanel,
 }
 
 impl SimulationApp {
@@ -55,13 +92,33 @@ impl SimulationApp {
         Self {
             state: None,
             combats: 1000,
+            time_boxed: false,
+            time_budget: 30.0,
+            record_hp_time_series: false,
+            record_roll_statistics: false,
+            record_event_timeline: false,
+            record_contributions: false,
+            record_action_usage: false,
+            record_outcome_labels: false,
+            record_turn_order: false,
+            record_weapon_stats: false,
+            tree_mode: TreeMode::Full,
+            batch_actions: false,
+            memory_limit: None,
+            memory_limit_action: MemoryLimitAction::TerminalOnly,
+            messages: MessageTemplates::default(),
+            messages_source: None,
             progress: 0.0,
             progress_rx: None,
             result_rx: None,
+            metric_rx: None,
+            emitted_metrics: BTreeMap::new(),
             stats: None,
             hook_handle: None,
             hook_script: String::from(DEFAULT_HOOK_SCRIPT),
             last_saved_hook_script: Some(String::from(DEFAULT_HOOK_SCRIPT)),
+            matchups: MatchupsPanel::default(),
+            replay: ReplayPanel::default(),
         }
     }
 
@@ -77,53 +134,74 @@ impl SimulationApp {
         if let Some(state) = &self.state {
             let roller = Roller::new();
             let (hook, hook_handle) = LuaHook::new(self.hook_script.clone());
+            let classifier = match self.record_outcome_labels {
+                true => match hook.outcome_classifier() {
+                    Ok(classifier) => classifier,
+                    Err(e) => {
+                        log::error!("Failed to load outcome classifier: {}", e);
+                        return;
+                    }
+                },
+                false => None,
+            };
             self.hook_handle = Some(hook_handle);
-            let mut integrator = Integrator::new(self.combats, roller, state.clone());
-            integrator.add_hook(hook);
+            let mut state = state.clone();
+            state.fill_missing_policies();
+            let mut integrator = Integrator::new(self.combats, roller, state);
+            integrator.add_named_hook("lua", hook);
             let (progress_tx, progress_rx) = mpsc::channel();
             let (result_tx, result_rx) = mpsc::channel();
-            let mut state_tree = StateTree::new(state.clone());
-            integrator.start_time = chrono::Utc::now();
+            let (metric_tx, metric_rx) = mpsc::channel();
+            integrator.stream_metrics(metric_tx);
+            integrator.record_hp_time_series(self.record_hp_time_series);
+            integrator.record_roll_statistics(self.record_roll_statistics);
+            integrator.record_event_timeline(self.record_event_timeline);
+            integrator.record_contributions(self.record_contributions);
+            integrator.record_action_usage(self.record_action_usage);
+            if self.record_outcome_labels {
+                match classifier {
+                    Some(classifier) => integrator.classify_outcomes(classifier),
+                    None => integrator.classify_outcomes(PartyOutcomeClassifier),
+                }
+            }
+            integrator.record_turn_order(self.record_turn_order);
+            integrator.record_weapon_stats(self.record_weapon_stats);
+            integrator.set_tree_mode(self.tree_mode);
+            integrator.batch_actions(self.batch_actions);
+            integrator.set_messages(self.messages.clone());
+            if let Some(megabytes) = self.memory_limit {
+                integrator.set_memory_limit(
+                    megabytes.saturating_mul(1024 * 1024),
+                    self.memory_limit_action,
+                );
+            }
+            if self.time_boxed {
+                integrator.set_stopping_condition(StoppingCondition::TimeBudget(
+                    std::time::Duration::from_secs_f64(self.time_budget),
+                ));
+            }
             std::thread::spawn({
                 move || {
-                    let total = integrator.min_combats as f64;
                     let mut last_reported = 0.0;
-                    for hook in &mut integrator.hooks {
-                        hook.on_integration_start(&integrator.initial_state);
-                    }
+                    let mut state_tree = integrator.begin();
                     while integrator.should_continue() {
                         integrator.run_combat(&mut state_tree).ok();
-                        let completed = integrator.combats_run() as f64;
-                        let progress = completed / total;
+                        let progress = integrator.progress();
                         if (progress - last_reported) >= 0.01 || progress == 1.0 {
                             last_reported = progress;
                             let _ = progress_tx.send(progress);
                         }
                     }
 
-                    let elapsed = integrator.elapsed_time();
-
-                    for hook in &mut integrator.hooks {
-                        hook.on_integration_end();
-                    }
-
-                    let mut hook_metrics = Vec::new();
-                    for hook in &integrator.hooks {
-                        hook_metrics.extend(hook.metrics());
-                    }
-
-                    let results = IntegrationResults {
-                        state_tree: state_tree.clone(),
-                        combats_run: integrator.combats_run(),
-                        elapsed_time: elapsed,
-                        hook_metrics,
-                    };
+                    let results = integrator.finish(state_tree);
 
                     let _ = result_tx.send(results);
                 }
             });
             self.progress_rx = Some(progress_rx);
             self.result_rx = Some(result_rx);
+            self.metric_rx = Some(metric_rx);
+            self.emitted_metrics.clear();
         } else {
             self.progress_rx = None;
             self.result_rx = None;
@@ -144,17 +222,162 @@ impl SimulationApp {
 
         ui.horizontal(|ui| {
             ui.label("Combats:");
-            ui.add(
+            ui.add_enabled(
+                !self.time_boxed,
                 egui::DragValue::new(&mut self.combats)
                     .range(1..=100000)
                     .speed(1),
             );
+            ui.checkbox(&mut self.time_boxed, "Time budget instead:");
+            ui.add_enabled(
+                self.time_boxed,
+                egui::DragValue::new(&mut self.time_budget)
+                    .range(1.0..=3600.0)
+                    .speed(1.0)
+                    .suffix(" s"),
+            );
         });
 
+        ui.checkbox(
+            &mut self.record_hp_time_series,
+            "Record HP time series (HP of every actor at the end of each round)",
+        );
+        ui.checkbox(
+            &mut self.record_roll_statistics,
+            "Record roll statistics (how often each face of each die comes up)",
+        );
+        ui.checkbox(
+            &mut self.record_event_timeline,
+            "Record event timeline (the round of first blood, downs, deaths and combat end)",
+        );
+        ui.checkbox(
+            &mut self.record_contributions,
+            "Record contributions (each actor's share of damage, healing and kills)",
+        );
+        ui.checkbox(
+            &mut self.record_action_usage,
+            "Record action usage (how often each actor used each action, and on which rounds)",
+        );
+        ui.checkbox(
+            &mut self.record_outcome_labels,
+            "Label outcomes (party wins clean, pyrrhic victory, TPK, ... or the hook script's \
+             classify_outcome)",
+        );
+        ui.checkbox(
+            &mut self.record_turn_order,
+            "Record turn order (damage and survival by initiative position, and wins when acting \
+             first)",
+        );
+        ui.checkbox(
+            &mut self.record_weapon_stats,
+            "Record weapon statistics (each weapon's hit rate, crits and damage, by target and AC)",
+        );
+
+        ui.horizontal(|ui| {
+            ui.label("Keep:");
+            let label = |mode| match mode {
+                TreeMode::Full => "Full state tree",
+                TreeMode::TerminalOnly => "Terminal states only",
+                TreeMode::None => "No state tree",
+            };
+            egui::ComboBox::from_id_salt("tree_mode")
+                .selected_text(label(self.tree_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [TreeMode::Full, TreeMode::TerminalOnly, TreeMode::None] {
+                        ui.selectable_value(&mut self.tree_mode, mode, label(mode));
+                    }
+                });
+        })
+        .response
+        .on_hover_text(
+            "Keeping less runs faster. Terminal states are enough for outcome queries and the \
+             summary; with no state tree, only hooks and the recorded statistics are available.",
+        );
+        ui.checkbox(
+            &mut self.batch_actions,
+            "Batch actions (record each action as one step in the state tree)",
+        );
+
+        ui.horizontal(|ui| {
+            let mut limited = self.memory_limit.is_some();
+            ui.checkbox(&mut limited, "Memory limit:");
+            let mut megabytes = self.memory_limit.unwrap_or(1024);
+            ui.add_enabled(
+                limited,
+                egui::DragValue::new(&mut megabytes)
+                    .range(16..=1024 * 1024)
+                    .speed(16)
+                    .suffix(" MB"),
+            );
+            self.memory_limit = limited.then_some(megabytes);
+            ui.add_enabled_ui(limited, |ui| {
+                let label = |action| match action {
+                    MemoryLimitAction::TerminalOnly => "then keep terminal states only",
+                    MemoryLimitAction::Stop => "then stop",
+                };
+                egui::ComboBox::from_id_salt("memory_limit_action")
+                    .selected_text(label(self.memory_limit_action))
+                    .show_ui(ui, |ui| {
+                        for action in [MemoryLimitAction::TerminalOnly, MemoryLimitAction::Stop] {
+                            ui.selectable_value(
+                                &mut self.memory_limit_action,
+                                action,
+                                label(action),
+                            );
+                        }
+                    });
+            });
+        })
+        .response
+        .on_hover_text(
+            "Roughly how big the state tree may grow, checked every 100 combats, before the run \
+             keeps less of it or stops early with the results so far.",
+        );
+
+        ui.horizontal(|ui| {
+            ui.label("Message templates:");
+            ui.label(self.messages_source.as_deref().unwrap_or("built-in"));
+            if ui.button("Load").clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .set_title("Select Message Templates")
+                    .pick_file()
+            {
+                let loaded = std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| Ok(serde_json::from_str(&json)?));
+                match loaded {
+                    Ok(messages) => {
+                        self.messages = messages;
+                        self.messages_source = Some(path.display().to_string());
+                    }
+                    Err(e) => {
+                        log::error!("Failed to load message templates: {}", e);
+                    }
+                }
+            }
+            if ui
+                .add_enabled(self.messages_source.is_some(), egui::Button::new("Reset"))
+                .clicked()
+            {
+                self.messages = MessageTemplates::default();
+                self.messages_source = None;
+            }
+        })
+        .response
+        .on_hover_text(
+            "A JSON file rewording the log and the combat replay, with templates keyed by \
+             transition type under \"transitions\" and by log line under \"logs\".",
+        );
+
         ui.separator();
 
         if ui.button("Start Simulation").clicked() && self.progress_rx.is_none() {
-            log::info!("Starting simulation with {} combats", self.combats);
+            if self.time_boxed {
+                log::info!("Starting simulation for {} seconds", self.time_budget);
+            } else {
+                log::info!("Starting simulation with {} combats", self.combats);
+            }
             self.spawn_integrator();
         }
 
@@ -170,26 +393,84 @@ impl SimulationApp {
 
             ui.add(egui::ProgressBar::new(self.progress as f32).show_percentage());
 
+            if let Some(metric_rx) = &self.metric_rx {
+                for sample in metric_rx.try_iter() {
+                    self.emitted_metrics
+                        .entry(sample.name)
+                        .or_default()
+                        .push((sample.combat, sample.value));
+                }
+            }
+
             // check for results
             if let Some(result_rx) = &self.result_rx {
                 if let Ok(state_tree) = result_rx.try_recv() {
                     log::info!("Simulation completed.");
+                    self.emitted_metrics.clear();
+                    for sample in &state_tree.metric_stream {
+                        self.emitted_metrics
+                            .entry(sample.name.clone())
+                            .or_default()
+                            .push((sample.combat, sample.value));
+                    }
                     self.stats = Some(state_tree);
                     self.progress_rx = None;
                     self.result_rx = None;
+                    self.metric_rx = None;
                 } else {
                     ui.label("Simulation running...");
                 }
             }
         }
 
+        if !self.emitted_metrics.is_empty() {
+            egui::CollapsingHeader::new(format!(
+                "Emitted Metrics ({})",
+                self.emitted_metrics.len()
+            ))
+            .default_open(true)
+            .show(ui, |ui| {
+                for (name, points) in &self.emitted_metrics {
+                    metric_plot(ui, name, points);
+                }
+            });
+            if self.progress_rx.is_some() {
+                ui.ctx().request_repaint();
+            }
+        }
+
         if let Some(results) = &self.stats {
             ui.separator();
-            ui.label(format!(
-                "Simulation Results: {} states, {} transitions",
-                results.state_tree.node_count(),
-                results.state_tree.edge_count()
-            ));
+            ui.label(match results.state_tree.mode() {
+                TreeMode::Full => format!(
+                    "Simulation Results: {} states, {} transitions",
+                    results.state_tree.node_count(),
+                    results.state_tree.edge_count()
+                ),
+                TreeMode::TerminalOnly => format!(
+                    "Simulation Results: {} terminal states",
+                    results.state_tree.terminal_state_count()
+                ),
+                TreeMode::None => "Simulation Results: no state tree kept".to_string(),
+            });
+            match results.memory_limit_reached {
+                Some(MemoryLimitAction::TerminalOnly) => {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "The memory limit was reached, so only terminal states were kept.",
+                    );
+                }
+                Some(MemoryLimitAction::Stop) => {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!(
+                            "The memory limit was reached, so the run stopped after {} combats.",
+                            results.combats_run
+                        ),
+                    );
+                }
+                None => {}
+            }
 
             if ui.button("Save Results").clicked()
                 && let Some(path) = rfd::FileDialog::new()
@@ -223,6 +504,20 @@ impl SimulationApp {
 
         ui.separator();
 
+        if let Some(state) = &self.state {
+            egui::CollapsingHeader::new("Matchup Matrix")
+                .default_open(false)
+                .show(ui, |ui| {
+                    self.matchups.ui(ui, state);
+                });
+            egui::CollapsingHeader::new("Combat Replay")
+                .default_open(false)
+                .show(ui, |ui| {
+                    self.replay.ui(ui, state, &self.messages);
+                });
+            ui.separator();
+        }
+
         // Display our hooks script
         egui::ScrollArea::vertical().show(ui, |ui| {
             ui.horizontal(|ui| {
@@ -285,6 +580,52 @@ impl SimulationApp {
     }
 }
 
+/// A line chart of a metric's value against the number of combats run when it was emitted.
+fn metric_plot(ui: &mut egui::Ui, name: &str, points: &[(usize, f64)]) {
+    let Some(&(_, last)) = points.last() else {
+        return;
+    };
+    ui.label(format!("{}: {:.4}", name, last));
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width().min(400.0), 80.0),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let max_combat = points
+        .iter()
+        .map(|(combat, _)| *combat)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f32;
+    let (min, max) = points.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY),
+        |(min, max), (_, value)| (min.min(*value), max.max(*value)),
+    );
+    let range = if max > min { max - min } else { 1.0 };
+    let line: Vec<egui::Pos2> = points
+        .iter()
+        .map(|(combat, value)| {
+            egui::pos2(
+                rect.left() + rect.width() * (*combat as f32 / max_combat),
+                rect.bottom() - rect.height() * ((value - min) / range) as f32,
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        line,
+        egui::Stroke::new(1.5, ui.visuals().selection.bg_fill),
+    ));
+    response.on_hover_text(format!(
+        "{} samples over {} combats, from {:.4} to {:.4}",
+        points.len(),
+        max_combat,
+        min,
+        max
+    ));
+}
+
 impl Default for SimulationApp {
     fn default() -> Self {
         Self::new()
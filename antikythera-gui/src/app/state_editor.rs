@@ -1,13 +1,29 @@
+use std::collections::BTreeSet;
+
 use antikythera::prelude::*;
 use eframe::egui;
 
-use crate::app::unsaved_changes_dialog;
+use crate::app::{balancer::BalancerWizard, confirm_dialog, unsaved_changes_dialog};
 
 #[derive(Default)]
 struct StateEditorUiState {
     inventory_item_to_add: ItemId,
     name_editing: Option<(u32, String)>,
+    tags_editing: Option<(u32, String)>,
     state_json: serde_json::Value,
+    scale_to_level: u32,
+    selected_actors: BTreeSet<ActorId>,
+    bulk_group: u32,
+    bulk_level: u32,
+    bulk_template: Option<ActorId>,
+    /// A roll formula being typed, keyed by item and whether it's the critical damage.
+    formula_editing: Option<((ItemId, bool), String)>,
+    /// Only show actors in this group in the actors list.
+    group_filter: Option<u32>,
+    /// Why the last mount chosen for an actor was refused.
+    mount_error: Option<(ActorId, String)>,
+    /// Why the last state loaded from a file or from the JSON pane was refused.
+    load_error: Option<String>,
 }
 
 #[derive(Default)]
@@ -15,6 +31,13 @@ pub struct StateEditorApp {
     pub state: Option<State>,
     last_saved_state: Option<State>,
     ui_state: StateEditorUiState,
+    balancer: BalancerWizard,
+    /// Goes up whenever the state or the last saved state changes.
+    edit_generation: u64,
+    /// The state as the last frame left it, to tell when it's been edited.
+    last_frame_state: Option<State>,
+    /// The unsaved changes, described as of an edit generation.
+    unsaved_changes: Option<(u64, String)>,
 }
 
 impl StateEditorApp {
@@ -27,6 +50,11 @@ impl StateEditorApp {
     }
 
     pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.state != self.last_frame_state {
+            self.last_frame_state = self.state.clone();
+            self.edit_generation += 1;
+        }
+
         ui.vertical_centered(|ui| {
             ui.heading("State Editor");
         });
@@ -45,6 +73,7 @@ impl StateEditorApp {
                 if should_proceed {
                     self.state = Some(State::new());
                     self.last_saved_state = self.state.clone();
+                    self.edit_generation += 1;
                 }
             }
 
@@ -59,17 +88,19 @@ impl StateEditorApp {
                 if should_proceed {
                     let dialog = rfd::FileDialog::new();
                     if let Some(path) = dialog.pick_file() {
-                        let source = std::fs::read_to_string(&path).unwrap();
-                        let res: Result<serde_json::Value, _> = serde_json::from_str(&source);
-                        if let Ok(state_json) = res
-                            && let Ok(loaded_state) =
-                                serde_json::from_value::<State>(state_json.clone())
-                        {
-                            self.state = Some(loaded_state);
-                            self.last_saved_state = self.state.clone();
-                            self.ui_state.state_json = state_json;
-                        } else {
-                            log::error!("Failed to load state from file: {}", path.display());
+                        match load_state_file(&path) {
+                            Ok((loaded_state, state_json)) => {
+                                self.state = Some(loaded_state);
+                                self.last_saved_state = self.state.clone();
+                                self.edit_generation += 1;
+                                self.ui_state.state_json = state_json;
+                                self.ui_state.load_error = None;
+                            }
+                            Err(e) => {
+                                log::error!("Failed to load {}: {:#}", path.display(), e);
+                                self.ui_state.load_error =
+                                    Some(format!("Failed to load {}: {:#}", path.display(), e));
+                            }
                         }
                     }
                 }
@@ -85,30 +116,114 @@ impl StateEditorApp {
                         log::error!("Failed to save state to file: {}", e);
                     }
                     self.last_saved_state = Some(state.clone());
+                    self.edit_generation += 1;
                 }
             }
+
+            if ui.button("Balance Encounter...").clicked() {
+                self.balancer.open = true;
+            }
         });
 
+        if let Some(error) = &self.ui_state.load_error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        self.balancer.ui(ui.ctx(), &mut self.state);
+
         ui.separator();
 
+        self.unsaved_changes_ui(ui);
+
         self.state_ui(ui);
     }
 
+    fn unsaved_changes_ui(&mut self, ui: &mut egui::Ui) {
+        let (Some(state), Some(last_saved)) = (&self.state, &self.last_saved_state) else {
+            return;
+        };
+        if last_saved == state {
+            return;
+        }
+
+        // diffing is slow for big states, so it's only done while open and once per edit
+        let generation = self.edit_generation;
+        let unsaved_changes = &mut self.unsaved_changes;
+        egui::CollapsingHeader::new("Unsaved Changes")
+            .default_open(false)
+            .show(ui, |ui| {
+                let changes = match unsaved_changes {
+                    Some((described, changes)) if *described == generation => changes,
+                    _ => {
+                        let mut changes = String::new();
+                        let described = last_saved.diff(state).and_then(|diff| {
+                            Ok(diff.pretty_print(&mut changes, last_saved, state)?)
+                        });
+                        if let Err(e) = described {
+                            changes = format!("Couldn't compare with the saved state: {}", e);
+                        }
+                        &mut unsaved_changes.insert((generation, changes)).1
+                    }
+                };
+                egui::ScrollArea::vertical()
+                    .id_salt("unsaved_changes")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        ui.monospace(changes.as_str());
+                    });
+            });
+
+        ui.separator();
+    }
+
     fn actor_ui(
         ui: &mut egui::Ui,
         actor: ActorId,
         state: &mut State,
         ui_state: &mut StateEditorUiState,
-    ) -> (bool, bool) {
+    ) -> (bool, bool, bool) {
+        // an actor carrying a rider can't ride, and mounts already ridden or riding are taken
+        let carrying = state.rider_of(actor).is_some();
+        let mount_candidates: Vec<(ActorId, String, Size)> = state
+            .actors
+            .values()
+            .filter(|other| {
+                !carrying
+                    && other.id != actor
+                    && other.mount.is_none()
+                    && state.rider_of(other.id).is_none_or(|rider| rider == actor)
+            })
+            .map(|other| (other.id, other.name.clone(), other.size))
+            .collect();
+        let mut mount_change = None;
+
+        let targets: Vec<(ActorId, String)> = state
+            .actors
+            .values()
+            .map(|actor| (actor.id, actor.name.clone()))
+            .collect();
+        let group = state.get_actor(actor).map_or(0, |actor| actor.group);
+        let group_name = state.group_name(group);
+        let group_color = state.groups.get(&group).and_then(|info| info.color);
+        let ac_breakdown = state
+            .get_actor(actor)
+            .map(|actor| actor.armor_class_breakdown(state));
+
+        let actor_id = actor;
         let Some(actor) = state.actors.get_mut(&actor) else {
             ui.label(format!("Actor ID {} not found in state.", actor.0));
-            return (false, false);
+            return (false, false, false);
         };
 
         let mut remove = false;
         let mut clone = false;
+        let mut export = false;
 
-        egui::CollapsingHeader::new(format!("{}: {}", actor.id.0, actor.name))
+        let mut header = egui::RichText::new(format!("{}: {}", actor.id.0, actor.name));
+        if let Some([r, g, b]) = group_color {
+            header = header.color(egui::Color32::from_rgb(r, g, b));
+        }
+        egui::CollapsingHeader::new(header)
             .id_salt(actor.id.0)
             .default_open(false)
             .show(ui, |ui| {
@@ -119,6 +234,15 @@ impl StateEditorApp {
                     if ui.button("Clone Actor").clicked() {
                         clone = true;
                     }
+                    if ui
+                        .button("Export Actor...")
+                        .on_hover_text(
+                            "Save this actor and its items to share with other scenarios",
+                        )
+                        .clicked()
+                    {
+                        export = true;
+                    }
                 });
 
                 ui.horizontal(|ui| {
@@ -155,6 +279,112 @@ impl StateEditorApp {
                             .range(0..=100),
                     );
                 });
+                ui.horizontal(|ui| {
+                    ui.label("Tags:");
+
+                    let tags_field = if let Some((id, editing_tags)) = &mut ui_state.tags_editing
+                        && id == &actor.id.0
+                    {
+                        ui.add(egui::TextEdit::singleline(editing_tags).desired_width(200.0))
+                    } else {
+                        let mut tags = Vec::from_iter(actor.tags.iter().cloned()).join(", ");
+                        ui.add(egui::TextEdit::singleline(&mut tags).desired_width(200.0))
+                    }
+                    .on_hover_text("Comma-separated, e.g. \"healer, boss\"");
+
+                    if tags_field.gained_focus() {
+                        let tags = Vec::from_iter(actor.tags.iter().cloned()).join(", ");
+                        ui_state.tags_editing = Some((actor.id.0, tags));
+                    }
+                    if tags_field.lost_focus()
+                        && let Some((id, _)) = &ui_state.tags_editing
+                        && id == &actor.id.0
+                    {
+                        let tags = ui_state.tags_editing.take().unwrap().1;
+                        actor.tags = tags
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|tag| !tag.is_empty())
+                            .map(String::from)
+                            .collect();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Level:");
+                    ui.add(
+                        egui::DragValue::new(&mut actor.level)
+                            .speed(0.1)
+                            .range(1..=30),
+                    );
+                    ui.label("Scale to:");
+                    ui.add(
+                        egui::DragValue::new(&mut ui_state.scale_to_level)
+                            .speed(0.1)
+                            .range(1..=30),
+                    )
+                    .on_hover_text("Target level for the Scale button");
+                    if ui
+                        .button("Scale")
+                        .on_hover_text(
+                            "Change level, recomputing HP and features from the hit die if set",
+                        )
+                        .clicked()
+                    {
+                        match actor.scaled_to_level(ui_state.scale_to_level) {
+                            Ok(scaled) => *actor = scaled,
+                            Err(e) => log::error!("Failed to scale actor: {}", e),
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Hit Die:");
+                    let selected = actor
+                        .scaling
+                        .as_ref()
+                        .map_or("None".to_string(), |scaling| {
+                            format!("d{}", scaling.hit_die)
+                        });
+                    egui::ComboBox::from_id_salt(("hit_die", actor.id.0))
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            if ui
+                                .selectable_label(actor.scaling.is_none(), "None")
+                                .clicked()
+                            {
+                                actor.scaling = None;
+                            }
+                            for die in [6, 8, 10, 12] {
+                                let current = actor.scaling.as_ref().map(|s| s.hit_die);
+                                if ui
+                                    .selectable_label(current == Some(die), format!("d{}", die))
+                                    .clicked()
+                                {
+                                    match &mut actor.scaling {
+                                        Some(scaling) => scaling.hit_die = die,
+                                        None => actor.scaling = Some(LevelScaling::new(die)),
+                                    }
+                                }
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Death:");
+                    egui::ComboBox::from_id_salt(("death_behavior", actor.id.0))
+                        .selected_text(format!("{:?}", actor.death_behavior))
+                        .show_ui(ui, |ui| {
+                            for behavior in [
+                                DeathBehavior::InstantDeath,
+                                DeathBehavior::DeathSaves,
+                                DeathBehavior::NoDeath,
+                            ] {
+                                ui.selectable_value(
+                                    &mut actor.death_behavior,
+                                    behavior,
+                                    format!("{:?}", behavior),
+                                );
+                            }
+                        });
+                });
                 ui.horizontal(|ui| {
                     ui.label("HP:");
                     ui.add(
@@ -177,76 +407,259 @@ impl StateEditorApp {
                         egui::DragValue::new(&mut actor.armor_class)
                             .speed(0.5)
                             .range(1..=30),
+                    )
+                    .on_hover_text("Used as is with a flat AC, or when the formula doesn't apply");
+                    egui::ComboBox::from_id_salt(("ac_formula", actor.id.0))
+                        .selected_text(actor.ac_formula.name())
+                        .show_ui(ui, |ui| {
+                            for formula in AcFormula::all() {
+                                let selected = std::mem::discriminant(&actor.ac_formula)
+                                    == std::mem::discriminant(&formula);
+                                if ui.selectable_label(selected, formula.name()).clicked()
+                                    && !selected
+                                {
+                                    actor.ac_formula = formula;
+                                }
+                            }
+                        });
+                    if let AcFormula::NaturalArmor { base } = &mut actor.ac_formula {
+                        ui.label("Base:");
+                        ui.add(egui::DragValue::new(base).range(10..=25));
+                    }
+                });
+                if let Some(breakdown) = &ac_breakdown {
+                    let mut line = String::new();
+                    breakdown.pretty_print(&mut line).ok();
+                    ui.label(format!("Effective AC: {}", line));
+                    if let Some(unmet) = &breakdown.unmet {
+                        ui.colored_label(ui.visuals().warn_fg_color, unmet);
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Size:");
+                    egui::ComboBox::from_id_salt(("size", actor.id.0))
+                        .selected_text(format!("{:?}", actor.size))
+                        .show_ui(ui, |ui| {
+                            for size in Size::all() {
+                                ui.selectable_value(&mut actor.size, size, format!("{:?}", size));
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Darkvision:");
+                    ui.add(
+                        egui::DragValue::new(&mut actor.vision.darkvision)
+                            .range(0..=300)
+                            .suffix(" ft"),
+                    );
+                    ui.label("Blindsight:");
+                    ui.add(
+                        egui::DragValue::new(&mut actor.vision.blindsight)
+                            .range(0..=300)
+                            .suffix(" ft"),
                     );
                 });
+                ui.horizontal(|ui| {
+                    ui.label("Mount:");
+                    let selected = match actor.mount {
+                        Some(mount) => mount_candidates
+                            .iter()
+                            .find(|(id, _, _)| *id == mount)
+                            .map(|(id, name, _)| format!("{}: {}", id.0, name))
+                            .unwrap_or_else(|| format!("<Actor ID: {}>", mount.0)),
+                        None => "None".to_string(),
+                    };
+                    let mut mount = actor.mount;
+                    egui::ComboBox::from_id_salt(("mount", actor.id.0))
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut mount, None, "None");
+                            for (id, name, size) in &mount_candidates {
+                                if size.can_carry_rider(actor.size) {
+                                    ui.selectable_value(
+                                        &mut mount,
+                                        Some(*id),
+                                        format!("{}: {}", id.0, name),
+                                    );
+                                }
+                            }
+                        });
+                    if mount != actor.mount {
+                        mount_change = Some(mount);
+                    }
+                });
+                if let Some((id, error)) = &ui_state.mount_error
+                    && *id == actor.id
+                {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+
+                Self::metadata_ui(ui, &mut actor.metadata);
+
+                egui::CollapsingHeader::new("Features")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        for feature in Feature::all() {
+                            let mut enabled = actor.has_feature(feature);
+                            if ui
+                                .checkbox(&mut enabled, format!("{:?}", feature))
+                                .changed()
+                            {
+                                if enabled {
+                                    actor.features.insert(feature);
+                                } else {
+                                    actor.features.remove(&feature);
+                                }
+                            }
+                        }
+                        ui.separator();
+                        for &resource in RerollResource::all() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{:?} rerolls:", resource));
+                                let mut count =
+                                    actor.rerolls.get(&resource).copied().unwrap_or_default();
+                                if ui
+                                    .add(egui::DragValue::new(&mut count).range(0..=10))
+                                    .changed()
+                                {
+                                    if count == 0 {
+                                        actor.rerolls.remove(&resource);
+                                    } else {
+                                        actor.rerolls.insert(resource, count);
+                                    }
+                                }
+                            });
+                        }
+                    });
 
                 egui::CollapsingHeader::new("Stats")
                     .default_open(false)
                     .show(ui, |ui| {
                         ui.horizontal(|ui| {
-                            ui.label("Strength:");
-                            ui.add(
-                                egui::DragValue::new(actor.stats.get_mut(Stat::Strength))
-                                    .speed(1)
-                                    .range(1..=20),
-                            );
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Dexterity:");
-                            ui.add(
-                                egui::DragValue::new(actor.stats.get_mut(Stat::Dexterity))
-                                    .speed(1)
-                                    .range(1..=20),
-                            );
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Constitution:");
-                            ui.add(
-                                egui::DragValue::new(actor.stats.get_mut(Stat::Constitution))
-                                    .speed(1)
-                                    .range(1..=20),
-                            );
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Intelligence:");
-                            ui.add(
-                                egui::DragValue::new(actor.stats.get_mut(Stat::Intelligence))
-                                    .speed(1)
-                                    .range(1..=20),
-                            );
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Wisdom:");
-                            ui.add(
-                                egui::DragValue::new(actor.stats.get_mut(Stat::Wisdom))
-                                    .speed(1)
-                                    .range(1..=20),
-                            );
+                            if ui
+                                .button("Standard Array")
+                                .on_hover_text(
+                                    "15, 14, 13, 12, 10, 8, keeping the current order of the stats",
+                                )
+                                .clicked()
+                            {
+                                let mut priority = Stat::all();
+                                priority
+                                    .sort_by_key(|stat| std::cmp::Reverse(actor.stats.base(*stat)));
+                                actor.stats = Stats::standard_array(&priority);
+                            }
+                            if ui
+                                .button("Roll 4d6")
+                                .on_hover_text("4d6 drop lowest, in order")
+                                .clicked()
+                            {
+                                actor.stats = Stats::rolled(
+                                    &mut Roller::new(),
+                                    StatRollMethod::FourD6DropLowest,
+                                );
+                            }
+                            let scores: Vec<(Stat, u32)> = Stat::all()
+                                .into_iter()
+                                .map(|stat| (stat, actor.stats.base(stat)))
+                                .collect();
+                            if let Ok(point_buy) = PointBuy::new(&scores) {
+                                ui.label(format!(
+                                    "Point buy: {}/{}",
+                                    point_buy.points_spent(),
+                                    PointBuy::BUDGET
+                                ));
+                            }
                         });
+                        let bounds = actor.stats.bounds();
+                        for stat in Stat::all() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{:?}:", stat));
+                                ui.add(
+                                    egui::DragValue::new(actor.stats.get_mut(stat))
+                                        .speed(1)
+                                        .range(bounds.min..=bounds.max),
+                                );
+                            });
+                        }
                         ui.horizontal(|ui| {
-                            ui.label("Charisma:");
-                            ui.add(
-                                egui::DragValue::new(actor.stats.get_mut(Stat::Charisma))
-                                    .speed(1)
-                                    .range(1..=20),
-                            );
+                            ui.label("Score bounds:");
+                            let (mut min, mut max) = (bounds.min, bounds.max);
+                            ui.add(egui::DragValue::new(&mut min).speed(1).range(0..=max));
+                            ui.label("to");
+                            ui.add(egui::DragValue::new(&mut max).speed(1).range(min..=99));
+                            if (min, max) != (bounds.min, bounds.max)
+                                && let Ok(bounds) = StatBounds::new(min, max)
+                            {
+                                actor.stats.set_bounds(bounds);
+                            }
                         });
+                        if let Err(e) = actor.stats.validate() {
+                            ui.colored_label(ui.visuals().warn_fg_color, e.to_string());
+                        }
                     }); // end CollapsingHeader for Stats
 
                 egui::CollapsingHeader::new("Saving Throws")
                     .default_open(false)
                     .show(ui, |ui| {
                         for save in SavingThrow::all() {
-                            let mut proficient = actor.saving_throw_proficiencies.get(save);
+                            let proficiencies = &mut actor.saving_throw_proficiencies;
+                            let mut proficient = proficiencies.get(save);
+                            let mut expertise = proficiencies.has_expertise(save);
                             let modifier = actor.saving_throw_modifier(save);
                             ui.horizontal(|ui| {
-                                ui.label(format!("{:?}: {}", save, modifier));
+                                ui.label(format!("{:?}: {}", save, modifier))
+                                    .on_hover_text("Before item and aura bonuses");
                                 ui.checkbox(&mut proficient, "Proficient");
+                                ui.checkbox(&mut expertise, "Expertise");
                             });
-                            actor.saving_throw_proficiencies.set(save, proficient);
+                            // expertise implies proficiency, and losing proficiency loses it
+                            let proficiencies = &mut actor.saving_throw_proficiencies;
+                            if expertise && !proficiencies.has_expertise(save) {
+                                proficient = true;
+                            }
+                            proficiencies.set(save, proficient);
+                            proficiencies.set_expertise(save, proficient && expertise);
                         }
                     }); // end CollapsingHeader for Saving Throws
 
+                egui::CollapsingHeader::new("Resistances")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let responses: Vec<DamageResponse> = DamageResponse::all()
+                            .into_iter()
+                            .filter(|response| *response != DamageResponse::Normal)
+                            .collect();
+                        egui::Grid::new(("resistances", actor.id.0))
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.label("");
+                                for response in &responses {
+                                    ui.strong(format!("{:?}", response));
+                                }
+                                ui.end_row();
+                                for damage_type in DamageType::all() {
+                                    ui.label(format!("{:?}", damage_type));
+                                    let current = actor.resistances.get(damage_type);
+                                    // checking one response unchecks the others; unchecking
+                                    // the current one goes back to normal damage
+                                    for response in &responses {
+                                        let mut checked = current == *response;
+                                        if ui.checkbox(&mut checked, "").changed() {
+                                            actor.resistances.set(
+                                                damage_type,
+                                                if checked {
+                                                    *response
+                                                } else {
+                                                    DamageResponse::Normal
+                                                },
+                                            );
+                                        }
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    }); // end CollapsingHeader for Resistances
+
                 egui::CollapsingHeader::new("Skills")
                     .default_open(false)
                     .show(ui, |ui| {
@@ -292,6 +705,192 @@ impl StateEditorApp {
                         }
                     }); // end CollapsingHeader for Skills
 
+                egui::CollapsingHeader::new("Weapon Proficiencies")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let proficiencies = &mut actor.weapon_proficiencies;
+                        ui.horizontal(|ui| {
+                            for category in WeaponCategory::all() {
+                                if ui
+                                    .button(format!("Proficient with all {:?}", category))
+                                    .clicked()
+                                {
+                                    proficiencies
+                                        .set_category(category, WeaponProficiency::Proficient);
+                                }
+                            }
+                            if ui.button("Clear").clicked() {
+                                *proficiencies = WeaponProficiencies::default();
+                            }
+                        });
+                        egui::Grid::new(("weapon_proficiencies", actor.id.0))
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for weapon_type in WeaponType::all() {
+                                    ui.label(format!(
+                                        "{:?} ({:?})",
+                                        weapon_type,
+                                        weapon_type.category()
+                                    ));
+                                    let mut proficiency = proficiencies.get(*weapon_type);
+                                    egui::ComboBox::from_id_salt((
+                                        "weapon_proficiency",
+                                        actor.id.0,
+                                        *weapon_type,
+                                    ))
+                                    .selected_text(format!("{:?}", proficiency))
+                                    .show_ui(ui, |ui| {
+                                        for option in WeaponProficiency::all() {
+                                            ui.selectable_value(
+                                                &mut proficiency,
+                                                option,
+                                                format!("{:?}", option),
+                                            );
+                                        }
+                                    });
+                                    if proficiency != proficiencies.get(*weapon_type) {
+                                        proficiencies.set(*weapon_type, proficiency);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    }); // end CollapsingHeader for Weapon Proficiencies
+
+                egui::CollapsingHeader::new("Policy")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let group_policy = state.group_policies.get(&actor.group);
+                        if actor.policy.is_empty() {
+                            match group_policy {
+                                Some(_) => {
+                                    ui.label(format!("Follows {}'s default policy.", group_name))
+                                }
+                                None => ui.label("No policy: waits every turn."),
+                            };
+                            if ui
+                                .button("Override")
+                                .on_hover_text("Give this actor a policy of its own")
+                                .clicked()
+                            {
+                                actor.policy = group_policy.cloned().unwrap_or_else(|| {
+                                    PolicyBuilder::new()
+                                        .action_weight(ActionType::Attack, 10)
+                                        .action_weight(ActionType::UnarmedStrike, 1)
+                                        .build()
+                                });
+                            }
+                        } else {
+                            if group_policy.is_some()
+                                && ui
+                                    .button("Use Group Default")
+                                    .on_hover_text(format!(
+                                        "Drop this actor's own policy and follow {}'s",
+                                        group_name
+                                    ))
+                                    .clicked()
+                            {
+                                actor.policy = Policy::default();
+                            }
+                            Self::policy_ui(
+                                ui,
+                                ("actor_policy", actor.id.0),
+                                &mut actor.policy,
+                                &targets,
+                            );
+                        }
+                    }); // end CollapsingHeader for Policy
+
+                egui::CollapsingHeader::new("Phases")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        ui.label("Entered in order as the actor drops below each threshold.");
+                        let abilities: Vec<String> = actor
+                            .abilities
+                            .iter()
+                            .map(|ability| ability.name.clone())
+                            .collect();
+                        // a phase's policy starts out as whatever the actor follows now
+                        let base_policy = if actor.policy.is_empty() {
+                            state
+                                .group_policies
+                                .get(&actor.group)
+                                .cloned()
+                                .unwrap_or_default()
+                        } else {
+                            actor.policy.clone()
+                        };
+                        let mut removed = None;
+                        for (index, phase) in actor.phases.iter_mut().enumerate() {
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{}:", index + 1));
+                                ui.text_edit_singleline(&mut phase.name);
+                                ui.label("below");
+                                ui.add(
+                                    egui::DragValue::new(&mut phase.below_percent)
+                                        .range(1..=100)
+                                        .suffix("% HP"),
+                                );
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+                            if !abilities.is_empty() {
+                                ui.horizontal_wrapped(|ui| {
+                                    ui.label("Unlocks:");
+                                    for (ability, name) in abilities.iter().enumerate() {
+                                        let mut unlocked = phase.unlock.contains(&ability);
+                                        if ui.checkbox(&mut unlocked, name).changed() {
+                                            if unlocked {
+                                                phase.unlock.push(ability);
+                                            } else {
+                                                phase.unlock.retain(|&other| other != ability);
+                                            }
+                                        }
+                                    }
+                                });
+                            }
+                            ui.horizontal_wrapped(|ui| {
+                                ui.label("Spawns:")
+                                    .on_hover_text("These actors sit out the combat until then");
+                                for (id, name) in &targets {
+                                    if *id == actor.id {
+                                        continue;
+                                    }
+                                    let mut spawned = phase.spawn.contains(id);
+                                    if ui.checkbox(&mut spawned, name).changed() {
+                                        if spawned {
+                                            phase.spawn.push(*id);
+                                        } else {
+                                            phase.spawn.retain(|other| other != id);
+                                        }
+                                    }
+                                }
+                            });
+                            let mut changes_policy = phase.policy.is_some();
+                            if ui.checkbox(&mut changes_policy, "Change policy").changed() {
+                                phase.policy = changes_policy.then(|| base_policy.clone());
+                            }
+                            if let Some(policy) = &mut phase.policy {
+                                ui.indent(("phase_policy", actor.id.0, index), |ui| {
+                                    Self::policy_ui(
+                                        ui,
+                                        ("phase_policy", actor.id.0, index),
+                                        policy,
+                                        &targets,
+                                    );
+                                });
+                            }
+                        }
+                        if let Some(index) = removed {
+                            actor.phases.remove(index);
+                        }
+                        if ui.button("Add Phase").clicked() {
+                            let name = format!("Phase {}", actor.phases.len() + 2);
+                            actor.phases.push(Phase::new(&name, 50));
+                        }
+                    }); // end CollapsingHeader for Phases
+
                 egui::CollapsingHeader::new("Inventory")
                     .default_open(false)
                     .show(ui, |ui| {
@@ -304,7 +903,7 @@ impl StateEditorApp {
                             ui.add(
                                 egui::DragValue::new(&mut item_id.0)
                                     .speed(0.5)
-                                    .range(1..=state.next_item_id.saturating_sub(1)),
+                                    .range(1..=state.ids.next_item_id.saturating_sub(1)),
                             );
                             if ui.button("Add Item by ID").clicked() {
                                 items_to_add.push((*item_id, 1));
@@ -356,56 +955,1042 @@ impl StateEditorApp {
                     }); // end CollapsingHeader for Inventory
             }); // end CollapsingHeader for Actor
 
-        (remove, clone)
+        // mounting goes through the state, which checks sizes and riders
+        if let Some(mount) = mount_change {
+            let result = match mount {
+                Some(mount) => state.mount_actor(actor_id, mount),
+                None => {
+                    state.dismount_actor(actor_id);
+                    Ok(())
+                }
+            };
+            ui_state.mount_error = result.err().map(|e| (actor_id, e.to_string()));
+        }
+
+        (remove, clone, export)
     }
 
-    fn actors_list_ui(ui: &mut egui::Ui, state: &mut State, ui_state: &mut StateEditorUiState) {
-        egui::CollapsingHeader::new("Actors")
-            .default_open(false)
-            .show(ui, |ui| {
-                if ui.button("Add Actor").clicked() {
-                    let new_actor = ActorBuilder::new("New Actor").build();
-                    state.add_actor(new_actor);
+    /// Action weights, target weights and recharge priority. A weight of 0 leaves an action out,
+    /// and targets default to a weight of 1.
+    fn policy_ui(
+        ui: &mut egui::Ui,
+        id_salt: impl std::hash::Hash,
+        policy: &mut Policy,
+        targets: &[(ActorId, String)],
+    ) {
+        fn set_weight<K: PartialEq>(
+            weights: &mut Vec<(K, i32)>,
+            key: K,
+            weight: i32,
+            default: i32,
+        ) {
+            match weights.iter().position(|(k, _)| *k == key) {
+                Some(index) if weight == default => {
+                    weights.remove(index);
                 }
+                Some(index) => weights[index].1 = weight,
+                None if weight != default => weights.push((key, weight)),
+                None => {}
+            }
+        }
 
-                let actors: Vec<ActorId> = state.actors.keys().cloned().collect();
-                for actor_id in actors {
-                    let (remove, clone) = Self::actor_ui(ui, actor_id, state, ui_state);
-                    if remove {
-                        state.actors.remove(&actor_id);
+        ui.push_id(id_salt, |ui| {
+            egui::Grid::new("action_weights").show(ui, |ui| {
+                for action_type in ActionType::all() {
+                    let weights = &mut policy.action_weights;
+                    let mut weight = weights
+                        .iter()
+                        .find(|(a, _)| *a == action_type)
+                        .map_or(0, |(_, weight)| *weight);
+                    ui.label(format!("{:?}", action_type));
+                    if ui
+                        .add(egui::DragValue::new(&mut weight).range(0..=100))
+                        .changed()
+                    {
+                        set_weight(weights, action_type, weight, 0);
                     }
-                    if clone && let Some(actor) = state.actors.get(&actor_id) {
-                        let mut cloned_actor = actor.clone();
-                        let new_id = state.next_actor_id;
-                        cloned_actor.id = ActorId(new_id);
-                        state.add_actor(cloned_actor);
+                    ui.end_row();
+                }
+            });
+
+            ui.collapsing("Target Weights", |ui| {
+                egui::Grid::new("target_weights").show(ui, |ui| {
+                    for (id, name) in targets {
+                        let weights = &mut policy.target_weights;
+                        let mut weight = weights
+                            .iter()
+                            .find(|(target, _)| target == id)
+                            .map_or(1, |(_, weight)| *weight);
+                        ui.label(format!("{}: {}", id.0, name));
+                        if ui
+                            .add(egui::DragValue::new(&mut weight).range(0..=100))
+                            .changed()
+                        {
+                            set_weight(weights, *id, weight, 1);
+                        }
+                        ui.end_row();
                     }
+                });
+            });
+
+            ui.horizontal(|ui| {
+                let mut enabled = policy.recharge_priority.is_some();
+                if ui
+                    .checkbox(&mut enabled, "Recharge priority:")
+                    .on_hover_text(
+                        "Weight added to using an ability while a recharge ability is ready",
+                    )
+                    .changed()
+                {
+                    policy.recharge_priority = enabled.then_some(100);
                 }
-            }); // end CollapsingHeader for Actors
+                if let Some(priority) = &mut policy.recharge_priority {
+                    ui.add(egui::DragValue::new(priority).range(0..=1000));
+                }
+            });
+        });
     }
 
-    fn item_ui(
-        ui: &mut egui::Ui,
-        item_id: ItemId,
-        state: &mut State,
-        ui_state: &mut StateEditorUiState,
-    ) {
-        let Some(item) = state.items.get_mut(&item_id) else {
-            ui.label(format!("Item ID {} not found in state.", item_id.0));
-            return;
-        };
-
-        egui::CollapsingHeader::new(format!("{}: {}", item.id.0, item.name))
-            .id_salt(item.id.0)
+    fn groups_ui(ui: &mut egui::Ui, state: &mut State) {
+        egui::CollapsingHeader::new("Groups")
             .default_open(false)
             .show(ui, |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Name:");
+                egui::Grid::new("groups").striped(true).show(ui, |ui| {
+                    for group in state.group_ids() {
+                        let current = state.groups.get(&group).cloned().unwrap_or_default();
+                        let mut info = current.clone();
+                        ui.label(format!("{}:", group));
+                        ui.add(
+                            egui::TextEdit::singleline(&mut info.name)
+                                .hint_text(format!("Group {}", group))
+                                .desired_width(150.0),
+                        );
+                        let mut colored = info.color.is_some();
+                        if ui.checkbox(&mut colored, "Color").changed() {
+                            info.color = colored.then_some([200, 200, 200]);
+                        }
+                        if let Some(color) = &mut info.color {
+                            ui.color_edit_button_srgb(color);
+                        } else {
+                            ui.label("");
+                        }
+                        ui.checkbox(&mut info.player_party, "Player party")
+                            .on_hover_text("The players' side, as opposed to the monsters");
+                        ui.end_row();
 
-                    let name_field = if let Some((id, editing_name)) = &mut ui_state.name_editing
-                        && id == &item.id.0
+                        // only groups with something set get an entry
+                        if info != current {
+                            if info == GroupInfo::default() {
+                                state.groups.remove(&group);
+                            } else {
+                                state.groups.insert(group, info);
+                            }
+                        }
+                    }
+                });
+                if ui.button("Add Group").clicked() {
+                    let group = state.group_ids().last().map_or(0, |group| group + 1);
+                    state
+                        .groups
+                        .insert(group, GroupInfo::new(&format!("Group {}", group)));
+                }
+            });
+    }
+
+    fn group_policies_ui(ui: &mut egui::Ui, state: &mut State) {
+        let targets: Vec<(ActorId, String)> = state
+            .actors
+            .values()
+            .map(|actor| (actor.id, actor.name.clone()))
+            .collect();
+        let groups: BTreeSet<u32> = state.actors.values().map(|actor| actor.group).collect();
+        egui::CollapsingHeader::new("Group Policies")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label("Actors without a policy of their own follow their group's.");
+                for group in groups {
+                    let inheriting = state
+                        .actors
+                        .values()
+                        .filter(|actor| actor.group == group && actor.policy.is_empty())
+                        .count();
+                    let mut enabled = state.group_policies.contains_key(&group);
+                    if ui
+                        .checkbox(
+                            &mut enabled,
+                            format!(
+                                "{} ({} actors following it)",
+                                state.group_name(group),
+                                inheriting
+                            ),
+                        )
+                        .changed()
                     {
-                        ui.add(egui::TextEdit::singleline(editing_name).desired_width(200.0))
+                        if enabled {
+                            state.set_group_policy(
+                                group,
+                                PolicyBuilder::new()
+                                    .action_weight(ActionType::Attack, 10)
+                                    .action_weight(ActionType::UnarmedStrike, 1)
+                                    .build(),
+                            );
+                        } else {
+                            state.group_policies.remove(&group);
+                        }
+                    }
+                    if let Some(policy) = state.group_policies.get_mut(&group) {
+                        ui.indent(("group_policy", group), |ui| {
+                            Self::policy_ui(ui, ("group_policy", group), policy, &targets);
+                        });
+                    }
+                }
+            });
+    }
+
+    /// What the loaded scenario is, from its title, author, party level and description.
+    fn scenario_info_ui(ui: &mut egui::Ui, scenario: &ScenarioMetadata) {
+        if scenario.is_empty() {
+            return;
+        }
+        ui.group(|ui| {
+            ui.strong(scenario.title.as_deref().unwrap_or("Untitled scenario"));
+            if let Some(author) = &scenario.author {
+                ui.label(format!("by {}", author));
+            }
+            if let Some(level) = scenario.party_level {
+                ui.label(format!("For a party of level {}", level));
+            }
+            if let Some(description) = &scenario.description {
+                ui.label(description);
+            }
+        });
+    }
+
+    fn scenario_ui(ui: &mut egui::Ui, scenario: &mut ScenarioMetadata) {
+        egui::CollapsingHeader::new("Scenario")
+            .default_open(false)
+            .show(ui, |ui| {
+                for (label, field) in [
+                    ("Title:", &mut scenario.title),
+                    ("Author:", &mut scenario.author),
+                ] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        let mut text = field.clone().unwrap_or_default();
+                        if ui
+                            .add(egui::TextEdit::singleline(&mut text).desired_width(200.0))
+                            .changed()
+                        {
+                            *field = (!text.is_empty()).then_some(text);
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    let mut has_level = scenario.party_level.is_some();
+                    if ui.checkbox(&mut has_level, "Party level:").changed() {
+                        scenario.party_level = has_level.then_some(1);
+                    }
+                    if let Some(level) = &mut scenario.party_level {
+                        ui.add(egui::DragValue::new(level).range(1..=20));
+                    }
+                });
+                ui.label("Description:");
+                let mut description = scenario.description.clone().unwrap_or_default();
+                if ui
+                    .add(egui::TextEdit::multiline(&mut description).desired_width(300.0))
+                    .changed()
+                {
+                    scenario.description = (!description.is_empty()).then_some(description);
+                }
+            });
+    }
+
+    fn metadata_ui(ui: &mut egui::Ui, metadata: &mut Metadata) {
+        egui::CollapsingHeader::new("Metadata")
+            .default_open(false)
+            .show(ui, |ui| {
+                for (label, field) in [
+                    ("Source:", &mut metadata.source),
+                    ("CR:", &mut metadata.challenge_rating),
+                    ("Portrait:", &mut metadata.portrait),
+                ] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        let mut text = field.clone().unwrap_or_default();
+                        if ui
+                            .add(egui::TextEdit::singleline(&mut text).desired_width(200.0))
+                            .changed()
+                        {
+                            *field = (!text.is_empty()).then_some(text);
+                        }
+                    });
+                }
+                ui.label("Notes:");
+                let mut notes = metadata.notes.clone().unwrap_or_default();
+                if ui
+                    .add(egui::TextEdit::multiline(&mut notes).desired_width(300.0))
+                    .changed()
+                {
+                    metadata.notes = (!notes.is_empty()).then_some(notes);
+                }
+            });
+    }
+
+    fn actors_list_ui(ui: &mut egui::Ui, state: &mut State, ui_state: &mut StateEditorUiState) {
+        egui::CollapsingHeader::new("Actors")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Add Actor").clicked() {
+                        let new_actor = ActorBuilder::new("New Actor").build();
+                        state.add_actor(new_actor);
+                    }
+                    if ui.button("Import Actor...").clicked() {
+                        Self::import_actor(state, ui_state);
+                    }
+                });
+
+                ui_state
+                    .selected_actors
+                    .retain(|id| state.actors.contains_key(id));
+                Self::bulk_actions_ui(ui, state, ui_state);
+
+                ui.horizontal(|ui| {
+                    ui.label("Show:");
+                    let filter = &mut ui_state.group_filter;
+                    let label = |group: Option<u32>| match group {
+                        Some(group) => state.group_name(group),
+                        None => "All groups".to_string(),
+                    };
+                    egui::ComboBox::from_id_salt("group_filter")
+                        .selected_text(label(*filter))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(filter, None, label(None));
+                            for group in state.group_ids() {
+                                ui.selectable_value(filter, Some(group), label(Some(group)));
+                            }
+                        });
+                });
+
+                let actors: Vec<ActorId> = state
+                    .actors
+                    .values()
+                    .filter(|actor| {
+                        ui_state
+                            .group_filter
+                            .is_none_or(|group| actor.group == group)
+                    })
+                    .map(|actor| actor.id)
+                    .collect();
+                for actor_id in actors {
+                    let (remove, clone, export) = ui
+                        .horizontal_top(|ui| {
+                            let mut selected = ui_state.selected_actors.contains(&actor_id);
+                            if ui.checkbox(&mut selected, "").changed() {
+                                if selected {
+                                    ui_state.selected_actors.insert(actor_id);
+                                } else {
+                                    ui_state.selected_actors.remove(&actor_id);
+                                }
+                            }
+                            ui.vertical(|ui| Self::actor_ui(ui, actor_id, state, ui_state))
+                                .inner
+                        })
+                        .inner;
+                    if remove && let Err(e) = state.remove_actor(actor_id) {
+                        log::error!("Failed to remove actor: {}", e);
+                    }
+                    if clone
+                        && let Some(items) = Self::clone_mode_dialog(state, actor_id)
+                        && let Err(e) = state.clone_actor(actor_id, items)
+                    {
+                        log::error!("Failed to clone actor: {}", e);
+                    }
+                    if export {
+                        Self::export_actor(state, actor_id);
+                    }
+                }
+            }); // end CollapsingHeader for Actors
+    }
+
+    /// Asks whether a clone should share the original's items or get copies of them. Actors
+    /// without items are cloned without asking.
+    fn clone_mode_dialog(state: &State, actor_id: ActorId) -> Option<ItemCloning> {
+        let actor = state.get_actor(actor_id)?;
+        if actor.inventory.items.is_empty() && actor.equipped_items.items.is_empty() {
+            return Some(ItemCloning::ShareItems);
+        }
+        const SHARE: &str = "Share Items";
+        const COPY: &str = "Copy Items";
+        let choice = rfd::MessageDialog::new()
+            .set_title("Clone Actor")
+            .set_description(format!(
+                "Should the clone of {} share its items, or get its own copies? Editing a shared \
+                 item changes it for both actors.",
+                actor.name
+            ))
+            .set_buttons(rfd::MessageButtons::YesNoCancelCustom(
+                SHARE.to_string(),
+                COPY.to_string(),
+                "Cancel".to_string(),
+            ))
+            .show();
+        match choice {
+            rfd::MessageDialogResult::Custom(choice) if choice == SHARE => {
+                Some(ItemCloning::ShareItems)
+            }
+            rfd::MessageDialogResult::Custom(choice) if choice == COPY => {
+                Some(ItemCloning::DeepCopyItems)
+            }
+            _ => None,
+        }
+    }
+
+    fn export_actor(state: &State, actor_id: ActorId) {
+        let file = match state.export_actor(actor_id) {
+            Ok(file) => file,
+            Err(e) => {
+                log::error!("Failed to export actor: {}", e);
+                return;
+            }
+        };
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("Export Actor")
+            .set_file_name(format!("{}.json", file.actor.name))
+            .save_file()
+        {
+            let result = serde_json::to_string_pretty(&file)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| Ok(std::fs::write(&path, json)?));
+            if let Err(e) = result {
+                log::error!("Failed to export actor to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Imports an actor file, unless the state wouldn't validate with it, as with homebrew
+    /// effects that don't compile.
+    fn import_actor(state: &mut State, ui_state: &mut StateEditorUiState) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_title("Import Actor")
+            .pick_file()
+        else {
+            return;
+        };
+        let imported = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| ActorFile::from_json(&json))
+            .and_then(|file| {
+                let mut imported = state.clone();
+                imported.import_actor(file);
+                imported.validate()?;
+                Ok(imported)
+            });
+        match imported {
+            Ok(imported) => {
+                *state = imported;
+                ui_state.load_error = None;
+            }
+            Err(e) => {
+                log::error!("Failed to import actor from {}: {:#}", path.display(), e);
+                ui_state.load_error = Some(format!(
+                    "Failed to import actor from {}: {:#}",
+                    path.display(),
+                    e
+                ));
+            }
+        }
+    }
+
+    /// Operations on every actor checked in the actors list.
+    fn bulk_actions_ui(ui: &mut egui::Ui, state: &mut State, ui_state: &mut StateEditorUiState) {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} selected", ui_state.selected_actors.len()));
+            if ui.button("Select All").clicked() {
+                ui_state.selected_actors = state.actors.keys().copied().collect();
+            }
+            if ui.button("Select None").clicked() {
+                ui_state.selected_actors.clear();
+            }
+        });
+        if ui_state.selected_actors.is_empty() {
+            return;
+        }
+        let selected = ui_state.selected_actors.clone();
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut ui_state.bulk_group)
+                    .speed(1)
+                    .range(0..=100),
+            );
+            if ui.button("Assign Group").clicked() {
+                for id in &selected {
+                    if let Some(actor) = state.actors.get_mut(id) {
+                        actor.group = ui_state.bulk_group;
+                    }
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui_state.bulk_level = ui_state.bulk_level.max(1);
+            ui.add(
+                egui::DragValue::new(&mut ui_state.bulk_level)
+                    .speed(0.1)
+                    .range(1..=30),
+            );
+            if ui
+                .button("Set Level")
+                .on_hover_text("Scale every selected actor to this level, at full health")
+                .clicked()
+            {
+                match state.scaled_to_level(&selected, ui_state.bulk_level) {
+                    Ok(scaled) => *state = scaled,
+                    Err(e) => log::error!("Failed to scale actors: {}", e),
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            let name_of = |id: ActorId| {
+                state
+                    .get_actor(id)
+                    .map(|actor| format!("{}: {}", id.0, actor.name))
+                    .unwrap_or_else(|| "Pick a template".to_string())
+            };
+            let mut template = ui_state
+                .bulk_template
+                .filter(|id| state.actors.contains_key(id));
+            egui::ComboBox::from_id_salt("bulk_template")
+                .selected_text(template.map_or("Pick a template".to_string(), name_of))
+                .show_ui(ui, |ui| {
+                    for id in state.actors.keys() {
+                        ui.selectable_value(&mut template, Some(*id), name_of(*id));
+                    }
+                });
+            ui_state.bulk_template = template;
+            if ui
+                .add_enabled(template.is_some(), egui::Button::new("Apply Template"))
+                .on_hover_text(
+                    "Copy everything but name, ID, group, and mount from the template actor",
+                )
+                .clicked()
+                && let Some(template) = template.and_then(|id| state.get_actor(id).cloned())
+                && confirm_dialog(
+                    "Apply Template",
+                    &format!(
+                        "Overwrite {} actors with a copy of {}?",
+                        selected.len(),
+                        template.name
+                    ),
+                )
+            {
+                for id in &selected {
+                    if let Some(actor) = state.actors.get_mut(id) {
+                        *actor = Actor {
+                            id: actor.id,
+                            name: actor.name.clone(),
+                            group: actor.group,
+                            mount: actor.mount,
+                            ..template.clone()
+                        };
+                    }
+                }
+            }
+        });
+        if ui.button("Delete Selected").clicked()
+            && confirm_dialog(
+                "Delete Actors",
+                &format!("Delete {} actors?", selected.len()),
+            )
+        {
+            for id in &selected {
+                if let Err(e) = state.remove_actor(*id) {
+                    log::error!("Failed to remove actor: {}", e);
+                }
+            }
+            ui_state.selected_actors.clear();
+        }
+        ui.separator();
+    }
+
+    fn death_cleanup_ui(ui: &mut egui::Ui, state: &mut State) {
+        ui.horizontal(|ui| {
+            ui.label("When an actor dies:");
+            let cleanup = &mut state.encounter.death_cleanup;
+            let label = |cleanup: DeathCleanup| match cleanup {
+                DeathCleanup::None => "Leave them be",
+                DeathCleanup::RemoveFromTargeting => "Stop targeting them",
+                DeathCleanup::DropLoot => "Drop their loot",
+            };
+            egui::ComboBox::from_id_salt("death_cleanup")
+                .selected_text(label(*cleanup))
+                .show_ui(ui, |ui| {
+                    for option in [
+                        DeathCleanup::None,
+                        DeathCleanup::RemoveFromTargeting,
+                        DeathCleanup::DropLoot,
+                    ] {
+                        ui.selectable_value(cleanup, option, label(option));
+                    }
+                });
+        });
+    }
+
+    fn engagement_distance_ui(ui: &mut egui::Ui, state: &mut State) {
+        ui.horizontal(|ui| {
+            let distance = &mut state.encounter.engagement_distance;
+            let mut enabled = distance.is_some();
+            if ui
+                .checkbox(&mut enabled, "Starting distance:")
+                .on_hover_text("Melee attackers must close the distance before they can strike")
+                .changed()
+            {
+                *distance = enabled.then_some(60);
+            }
+            if let Some(feet) = distance {
+                ui.add(egui::DragValue::new(feet).range(0..=600).suffix(" ft"));
+                ui.checkbox(&mut state.encounter.rules.stealth, "Stealth")
+                    .on_hover_text(
+                        "A side whose passive Stealth beats every enemy's passive Perception \
+                         starts in melee",
+                    );
+            }
+        });
+    }
+
+    fn zones_ui(ui: &mut egui::Ui, state: &mut State) {
+        let actors: Vec<(ActorId, String)> = state
+            .actors
+            .values()
+            .map(|actor| (actor.id, actor.name.clone()))
+            .collect();
+        let zones = &mut state.encounter.zones;
+        let mut enabled = zones.is_some();
+        if ui
+            .checkbox(&mut enabled, "Zones")
+            .on_hover_text(
+                "Theater-of-the-mind positioning: actors stand in named zones instead of a distance apart",
+            )
+            .changed()
+        {
+            *zones = enabled.then(|| {
+                let mut zones = ZoneMap::default();
+                let near = zones.add_zone("Zone 1");
+                let far = zones.add_zone("Zone 2");
+                zones.connect(near, far);
+                zones
+            });
+        }
+        let Some(zones) = zones else {
+            return;
+        };
+
+        egui::CollapsingHeader::new("Zones")
+            .default_open(true)
+            .show(ui, |ui| {
+                for (index, name) in zones.zones.iter_mut().enumerate() {
+                    let zone = ZoneId(index as u32);
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", index));
+                        ui.text_edit_singleline(name);
+                        let mut difficult = zones.difficult.contains(&zone);
+                        if ui
+                            .checkbox(&mut difficult, "Difficult")
+                            .on_hover_text("Takes an extra turn to enter")
+                            .changed()
+                        {
+                            if difficult {
+                                zones.difficult.insert(zone);
+                            } else {
+                                zones.difficult.remove(&zone);
+                            }
+                        }
+                    });
+                }
+                if ui.button("Add Zone").clicked() {
+                    let name = format!("Zone {}", zones.zones.len() + 1);
+                    zones.add_zone(&name);
+                }
+
+                ui.label("Connections:");
+                let ids: Vec<ZoneId> = (0..zones.zones.len() as u32).map(ZoneId).collect();
+                for (i, &a) in ids.iter().enumerate() {
+                    for &b in &ids[i + 1..] {
+                        let mut connected = zones.are_adjacent(a, b);
+                        let label = format!(
+                            "{} ↔ {}",
+                            zones.name(a).unwrap_or_default(),
+                            zones.name(b).unwrap_or_default()
+                        );
+                        if ui.checkbox(&mut connected, label).changed() {
+                            if connected {
+                                zones.connect(a, b);
+                            } else {
+                                zones.connections.remove(&(a, b));
+                            }
+                        }
+                    }
+                }
+
+                ui.label("Starting zones:");
+                for (actor, name) in &actors {
+                    ui.horizontal(|ui| {
+                        ui.label(name);
+                        let mut zone = zones.starting_zone(*actor);
+                        egui::ComboBox::from_id_salt(("starting_zone", actor.0))
+                            .selected_text(zones.name(zone).unwrap_or_default().to_string())
+                            .show_ui(ui, |ui| {
+                                for &id in &ids {
+                                    ui.selectable_value(
+                                        &mut zone,
+                                        id,
+                                        zones.name(id).unwrap_or_default(),
+                                    );
+                                }
+                            });
+                        if zone != zones.starting_zone(*actor) {
+                            zones.starting_zones.insert(*actor, zone);
+                        }
+                    });
+                }
+            });
+    }
+
+    fn objectives_ui(ui: &mut egui::Ui, state: &mut State) {
+        let actors: Vec<(ActorId, String)> = state
+            .actors
+            .values()
+            .map(|actor| (actor.id, actor.name.clone()))
+            .collect();
+        let groups: Vec<(u32, String)> = state
+            .group_ids()
+            .into_iter()
+            .map(|group| (group, state.group_name(group)))
+            .collect();
+        let zones: Vec<(ZoneId, String)> = state
+            .encounter
+            .zones
+            .as_ref()
+            .map(|zones| {
+                (0..zones.zones.len() as u32)
+                    .map(|zone| (ZoneId(zone), zones.zones[zone as usize].clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let first_actor = actors.first().map_or(ActorId(1), |(id, _)| *id);
+        let name_of = |options: &[(ActorId, String)], id: ActorId| {
+            options
+                .iter()
+                .find(|(option, _)| *option == id)
+                .map_or_else(|| format!("Actor {}", id.0), |(_, name)| name.clone())
+        };
+
+        egui::CollapsingHeader::new("Objectives")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label("Combat also ends when one of these is met, checked in order.");
+                let mut removed = None;
+                for (index, objective) in state.encounter.objectives.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        let (kind, group) = match objective {
+                            Objective::Survive { group, .. } => ("Survive", *group),
+                            Objective::Defeat { group, .. } => ("Defeat", *group),
+                            Objective::Protect { group, .. } => ("Protect", *group),
+                            Objective::ReachZone { group, .. } => ("Reach Zone", *group),
+                        };
+                        let mut selected_group = group;
+                        egui::ComboBox::from_id_salt(("objective_group", index))
+                            .selected_text(groups.iter().find(|(id, _)| *id == group).map_or_else(
+                                || format!("Group {}", group),
+                                |(_, name)| name.clone(),
+                            ))
+                            .show_ui(ui, |ui| {
+                                for (id, name) in &groups {
+                                    ui.selectable_value(&mut selected_group, *id, name);
+                                }
+                            });
+                        let mut selected_kind = kind;
+                        egui::ComboBox::from_id_salt(("objective_kind", index))
+                            .selected_text(kind)
+                            .show_ui(ui, |ui| {
+                                for option in ["Survive", "Defeat", "Protect", "Reach Zone"] {
+                                    ui.selectable_value(&mut selected_kind, option, option);
+                                }
+                            });
+                        if selected_kind != kind {
+                            *objective = match selected_kind {
+                                "Survive" => Objective::Survive {
+                                    group: selected_group,
+                                    rounds: 5,
+                                },
+                                "Defeat" => Objective::Defeat {
+                                    group: selected_group,
+                                    target: first_actor,
+                                },
+                                "Protect" => Objective::Protect {
+                                    group: selected_group,
+                                    actor: first_actor,
+                                },
+                                _ => Objective::ReachZone {
+                                    group: selected_group,
+                                    zone: ZoneId(0),
+                                },
+                            };
+                        }
+
+                        match objective {
+                            Objective::Survive { group, rounds } => {
+                                *group = selected_group;
+                                ui.add(
+                                    egui::DragValue::new(rounds)
+                                        .range(1..=100)
+                                        .suffix(" rounds"),
+                                );
+                            }
+                            Objective::Defeat {
+                                group,
+                                target: actor,
+                            }
+                            | Objective::Protect { group, actor } => {
+                                *group = selected_group;
+                                egui::ComboBox::from_id_salt(("objective_actor", index))
+                                    .selected_text(name_of(&actors, *actor))
+                                    .show_ui(ui, |ui| {
+                                        for (id, name) in &actors {
+                                            ui.selectable_value(actor, *id, name);
+                                        }
+                                    });
+                            }
+                            Objective::ReachZone { group, zone } => {
+                                *group = selected_group;
+                                if zones.is_empty() {
+                                    ui.colored_label(
+                                        ui.visuals().warn_fg_color,
+                                        "Only met with zones enabled",
+                                    );
+                                }
+                                let selected = zones.iter().find(|(id, _)| id == zone).map_or_else(
+                                    || format!("Zone {}", zone.0),
+                                    |(_, name)| name.clone(),
+                                );
+                                egui::ComboBox::from_id_salt(("objective_zone", index))
+                                    .selected_text(selected)
+                                    .show_ui(ui, |ui| {
+                                        for (id, name) in &zones {
+                                            ui.selectable_value(zone, *id, name);
+                                        }
+                                    });
+                            }
+                        }
+
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = removed {
+                    state.encounter.objectives.remove(index);
+                }
+                if ui.button("Add Objective").clicked() {
+                    state.encounter.objectives.push(Objective::Survive {
+                        group: groups.first().map_or(0, |(group, _)| *group),
+                        rounds: 5,
+                    });
+                }
+            });
+    }
+
+    fn optional_rules_ui(ui: &mut egui::Ui, state: &mut State) {
+        let rules = &mut state.encounter.rules;
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut rules.pack_tactics, "Pack Tactics")
+                .on_hover_text("Advantage on attacks when an ally is in melee with the target");
+            ui.checkbox(&mut rules.flanking, "Flanking").on_hover_text(
+                "Advantage on melee attacks when an ally is in melee with the target",
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut state.encounter.rules.lighting, "Lighting")
+                .on_hover_text(
+                    "Disadvantage on attacks and Perception against targets that can't be seen, \
+                     and advantage on attacks by them",
+                );
+            if state.encounter.rules.lighting {
+                let lighting = &mut state.encounter.lighting;
+                egui::ComboBox::from_id_salt("lighting")
+                    .selected_text(format!("{:?}", lighting))
+                    .show_ui(ui, |ui| {
+                        for option in Lighting::all() {
+                            ui.selectable_value(lighting, option, format!("{:?}", option));
+                        }
+                    });
+            }
+        });
+        ui.checkbox(&mut state.encounter.rules.encumbrance, "Encumbrance")
+            .on_hover_text("Heavy loads and armor without its Strength requirement reduce speed");
+    }
+
+    fn initiative_ui(ui: &mut egui::Ui, state: &mut State) {
+        egui::CollapsingHeader::new("Initiative")
+            .default_open(false)
+            .show(ui, |ui| {
+                let actors: Vec<(ActorId, String)> = state
+                    .actors
+                    .values()
+                    .filter(|actor| state.rider_of(actor.id).is_none())
+                    .map(|actor| (actor.id, actor.name.clone()))
+                    .collect();
+                let name_of = |id: ActorId| {
+                    actors
+                        .iter()
+                        .find(|(actor, _)| *actor == id)
+                        .map(|(_, name)| format!("{}: {}", id.0, name))
+                        .unwrap_or_else(|| format!("<Actor ID: {}>", id.0))
+                };
+                let mode = &mut state.encounter.initiative;
+
+                let selected = match mode {
+                    InitiativeMode::Rolled => "Rolled",
+                    InitiativeMode::Fixed(_) => "Fixed Values",
+                    InitiativeMode::Order(_) => "Fixed Order",
+                };
+                egui::ComboBox::from_id_salt("initiative_mode")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        if ui
+                            .selectable_label(matches!(mode, InitiativeMode::Rolled), "Rolled")
+                            .clicked()
+                        {
+                            *mode = InitiativeMode::Rolled;
+                        }
+                        if ui
+                            .selectable_label(
+                                matches!(mode, InitiativeMode::Fixed(_)),
+                                "Fixed Values",
+                            )
+                            .clicked()
+                            && !matches!(mode, InitiativeMode::Fixed(_))
+                        {
+                            *mode = InitiativeMode::Fixed(Default::default());
+                        }
+                        if ui
+                            .selectable_label(
+                                matches!(mode, InitiativeMode::Order(_)),
+                                "Fixed Order",
+                            )
+                            .clicked()
+                            && !matches!(mode, InitiativeMode::Order(_))
+                        {
+                            *mode =
+                                InitiativeMode::Order(actors.iter().map(|(id, _)| *id).collect());
+                        }
+                    });
+
+                match mode {
+                    InitiativeMode::Rolled => {}
+                    InitiativeMode::Fixed(values) => {
+                        ui.label("Unchecked actors roll as normal.");
+                        for (id, _) in &actors {
+                            ui.horizontal(|ui| {
+                                let mut fixed = values.contains_key(id);
+                                if ui.checkbox(&mut fixed, name_of(*id)).changed() {
+                                    if fixed {
+                                        values.insert(*id, 10);
+                                    } else {
+                                        values.remove(id);
+                                    }
+                                }
+                                if let Some(value) = values.get_mut(id) {
+                                    ui.add(egui::DragValue::new(value).speed(1).range(-10..=40));
+                                }
+                            });
+                        }
+                    }
+                    InitiativeMode::Order(order) => {
+                        ui.label("Unlisted actors act last.");
+                        let mut swap = None;
+                        let mut remove = None;
+                        for (index, id) in order.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{}.", index + 1));
+                                if ui
+                                    .add_enabled(index > 0, egui::Button::new("^").small())
+                                    .clicked()
+                                {
+                                    swap = Some((index - 1, index));
+                                }
+                                if ui
+                                    .add_enabled(
+                                        index + 1 < order.len(),
+                                        egui::Button::new("v").small(),
+                                    )
+                                    .clicked()
+                                {
+                                    swap = Some((index, index + 1));
+                                }
+                                if ui.small_button("x").clicked() {
+                                    remove = Some(index);
+                                }
+                                ui.label(name_of(*id));
+                            });
+                        }
+                        if let Some((a, b)) = swap {
+                            order.swap(a, b);
+                        }
+                        if let Some(index) = remove {
+                            order.remove(index);
+                        }
+
+                        let unlisted: Vec<ActorId> = actors
+                            .iter()
+                            .map(|(id, _)| *id)
+                            .filter(|id| !order.contains(id))
+                            .collect();
+                        if !unlisted.is_empty() {
+                            egui::ComboBox::from_id_salt("initiative_order_add")
+                                .selected_text("Add Actor")
+                                .show_ui(ui, |ui| {
+                                    for id in unlisted {
+                                        if ui.selectable_label(false, name_of(id)).clicked() {
+                                            order.push(id);
+                                        }
+                                    }
+                                });
+                        }
+                    }
+                }
+            }); // end CollapsingHeader for Initiative
+    }
+
+    fn item_ui(
+        ui: &mut egui::Ui,
+        item_id: ItemId,
+        state: &mut State,
+        ui_state: &mut StateEditorUiState,
+    ) -> bool {
+        let Some(item) = state.items.get_mut(&item_id) else {
+            ui.label(format!("Item ID {} not found in state.", item_id.0));
+            return false;
+        };
+
+        let mut remove = false;
+        egui::CollapsingHeader::new(format!("{}: {}", item.id.0, item.name))
+            .id_salt(item.id.0)
+            .default_open(false)
+            .show(ui, |ui| {
+                if ui.button("Remove Item").clicked() {
+                    remove = true;
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+
+                    let name_field = if let Some((id, editing_name)) = &mut ui_state.name_editing
+                        && id == &item.id.0
+                    {
+                        ui.add(egui::TextEdit::singleline(editing_name).desired_width(200.0))
                     } else {
                         let mut name = item.name.clone();
                         ui.add(egui::TextEdit::singleline(&mut name).desired_width(200.0))
@@ -427,6 +2012,28 @@ impl StateEditorApp {
                     ui.label(format!("{:?}", item.item_type()));
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("Save Bonus:");
+                    ui.add(
+                        egui::DragValue::new(&mut item.save_bonus)
+                            .speed(1)
+                            .range(-10..=10),
+                    )
+                    .on_hover_text("Added to every saving throw while the item is equipped");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Weight:");
+                    ui.add(
+                        egui::DragValue::new(&mut item.weight)
+                            .speed(1)
+                            .range(0..=1000)
+                            .suffix(" lb"),
+                    );
+                });
+
+                Self::metadata_ui(ui, &mut item.metadata);
+
                 match &mut item.inner {
                     ItemInner::Weapon(weapon) => {
                         egui::CollapsingHeader::new("Weapon Details")
@@ -446,40 +2053,62 @@ impl StateEditorApp {
                                             }
                                         });
                                 });
+                                ui.horizontal(|ui| {
+                                    ui.label("Damage Type:");
+                                    let default = format!(
+                                        "Default ({:?})",
+                                        weapon.weapon_type.damage_type()
+                                    );
+                                    let selected = match weapon.damage_type {
+                                        Some(damage_type) => format!("{:?}", damage_type),
+                                        None => default.clone(),
+                                    };
+                                    egui::ComboBox::from_id_salt("weapon_damage_type")
+                                        .selected_text(selected)
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(
+                                                &mut weapon.damage_type,
+                                                None,
+                                                default,
+                                            );
+                                            for damage_type in DamageType::all() {
+                                                ui.selectable_value(
+                                                    &mut weapon.damage_type,
+                                                    Some(damage_type),
+                                                    format!("{:?}", damage_type),
+                                                );
+                                            }
+                                        });
+                                });
                                 ui.horizontal(|ui| {
                                     ui.label("Damage:");
-                                    let mut formula = String::new();
-                                    weapon.damage.pretty_print(&mut formula).unwrap();
-                                    if ui
-                                        .add(
-                                            egui::TextEdit::singleline(&mut formula)
-                                                .desired_width(100.0),
-                                        )
-                                        .changed()
-                                        && let Ok(parsed) =
-                                            antikythera::roll_parser::parse_roll(&formula)
-                                    {
+                                    if let Some(parsed) = Self::roll_formula_ui(
+                                        ui,
+                                        ui_state,
+                                        (item_id, false),
+                                        &weapon.damage,
+                                    ) {
                                         weapon.damage = parsed;
                                     }
+                                    Self::roll_preview_ui(ui, &weapon.damage);
                                 });
 
                                 ui.horizontal(|ui| {
                                     ui.label("Critical Damage:");
-                                    let mut formula = String::new();
                                     let critical_damage =
-                                        weapon.critical_damage.as_ref().unwrap_or(&weapon.damage);
-                                    critical_damage.pretty_print(&mut formula).unwrap();
-                                    if ui
-                                        .add(
-                                            egui::TextEdit::singleline(&mut formula)
-                                                .desired_width(100.0),
-                                        )
-                                        .changed()
-                                        && let Ok(parsed) =
-                                            antikythera::roll_parser::parse_roll(&formula)
-                                    {
+                                        weapon.critical_damage.unwrap_or(weapon.damage);
+                                    if let Some(parsed) = Self::roll_formula_ui(
+                                        ui,
+                                        ui_state,
+                                        (item_id, true),
+                                        &critical_damage,
+                                    ) {
                                         weapon.critical_damage = Some(parsed);
                                     }
+                                    Self::roll_preview_ui(
+                                        ui,
+                                        weapon.critical_damage.as_ref().unwrap_or(&weapon.damage),
+                                    );
                                 });
                                 ui.horizontal(|ui| {
                                     ui.label("Attack Bonus:");
@@ -489,6 +2118,16 @@ impl StateEditorApp {
                                             .range(-10..=10),
                                     );
                                 });
+                                ui.horizontal(|ui| {
+                                    ui.label("Enchantment:");
+                                    ui.add(
+                                        egui::DragValue::new(&mut weapon.enchantment_bonus)
+                                            .speed(1)
+                                            .range(0..=3)
+                                            .prefix("+"),
+                                    )
+                                    .on_hover_text("Added to attack and damage rolls");
+                                });
                                 ui.horizontal(|ui| {
                                     ui.label("Range:");
                                     // melee checkbox
@@ -517,18 +2156,144 @@ impl StateEditorApp {
                                     ui.add(
                                         egui::DragValue::new(&mut armor.ac_bonus)
                                             .speed(1)
-                                            .range(1..=30),
+                                            .range(0..=30),
                                     );
                                 });
+                                ui.horizontal(|ui| {
+                                    ui.label("Enchantment:");
+                                    ui.add(
+                                        egui::DragValue::new(&mut armor.enchantment_bonus)
+                                            .speed(1)
+                                            .range(0..=3)
+                                            .prefix("+"),
+                                    )
+                                    .on_hover_text("Added to the wearer's AC");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Slot:");
+                                    egui::ComboBox::from_id_salt(("armor_slot", item.id.0))
+                                        .selected_text(format!("{:?}", armor.slot))
+                                        .show_ui(ui, |ui| {
+                                            for slot in EquipSlot::all() {
+                                                ui.selectable_value(
+                                                    &mut armor.slot,
+                                                    slot,
+                                                    format!("{:?}", slot),
+                                                );
+                                            }
+                                        });
+                                });
                                 ui.horizontal(|ui| {
                                     ui.label("Stealth Disadvantage:");
                                     ui.checkbox(&mut armor.stealth_disadvantage, "");
                                 });
+                                ui.horizontal(|ui| {
+                                    ui.label("Strength Requirement:");
+                                    ui.add(
+                                        egui::DragValue::new(&mut armor.strength_requirement)
+                                            .speed(1)
+                                            .range(0..=30),
+                                    )
+                                    .on_hover_text(
+                                        "Wearers with less Strength lose 10 ft of speed under the encumbrance rule",
+                                    );
+                                });
                             }); // end CollapsingHeader for Armor Details
                     }
                     _ => {}
                 }
             }); // end CollapsingHeader for item
+        remove
+    }
+
+    /// A text field for a roll formula. What's typed is kept while editing, outlined in red with
+    /// the parse error on hover until it's valid. Returns the roll whenever a valid edit is made.
+    fn roll_formula_ui(
+        ui: &mut egui::Ui,
+        ui_state: &mut StateEditorUiState,
+        key: (ItemId, bool),
+        plan: &RollPlan,
+    ) -> Option<RollPlan> {
+        let mut formula = match &ui_state.formula_editing {
+            Some((editing, formula)) if *editing == key => formula.clone(),
+            _ => {
+                let mut formula = String::new();
+                plan.pretty_print(&mut formula).unwrap();
+                formula
+            }
+        };
+        let error = antikythera::roll_parser::parse_roll(formula.trim()).err();
+
+        let response = ui
+            .scope(|ui| {
+                if error.is_some() {
+                    let stroke = egui::Stroke::new(1.0, egui::Color32::RED);
+                    let widgets = &mut ui.visuals_mut().widgets;
+                    widgets.inactive.bg_stroke = stroke;
+                    widgets.hovered.bg_stroke = stroke;
+                    widgets.active.bg_stroke = stroke;
+                    ui.visuals_mut().selection.stroke = stroke;
+                }
+                ui.add(egui::TextEdit::singleline(&mut formula).desired_width(100.0))
+            })
+            .inner;
+        let response = match &error {
+            Some(error) => response.on_hover_text(error.to_string()),
+            None => response,
+        };
+
+        let mut parsed = None;
+        if response.gained_focus() || response.changed() {
+            if response.changed() {
+                parsed = antikythera::roll_parser::parse_roll(formula.trim()).ok();
+            }
+            ui_state.formula_editing = Some((key, formula));
+        }
+        if response.lost_focus()
+            && matches!(&ui_state.formula_editing, Some((editing, _)) if *editing == key)
+        {
+            ui_state.formula_editing = None;
+        }
+        parsed
+    }
+
+    /// The mean, range, and a small histogram of a roll's totals.
+    fn roll_preview_ui(ui: &mut egui::Ui, plan: &RollPlan) {
+        let distribution = match plan.distribution() {
+            Ok(distribution) => distribution,
+            Err(e) => {
+                ui.colored_label(ui.visuals().warn_fg_color, "no preview")
+                    .on_hover_text(e.to_string());
+                return;
+            }
+        };
+        let peak = distribution
+            .probabilities
+            .values()
+            .copied()
+            .fold(0.0, f64::max);
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(80.0, 18.0), egui::Sense::hover());
+        let bar_width = rect.width() / distribution.probabilities.len().max(1) as f32;
+        let color = ui.visuals().selection.bg_fill;
+        for (i, probability) in distribution.probabilities.values().enumerate() {
+            let height = rect.height() * (*probability / peak) as f32;
+            let left = rect.left() + i as f32 * bar_width;
+            ui.painter().rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(left, rect.bottom() - height),
+                    egui::pos2(left + bar_width, rect.bottom()),
+                ),
+                0.0,
+                color,
+            );
+        }
+        response.on_hover_text("Distribution of totals");
+        ui.label(format!(
+            "mean {:.1}, {} to {}",
+            distribution.mean(),
+            distribution.min(),
+            distribution.max()
+        ));
     }
 
     fn items_list_ui(ui: &mut egui::Ui, state: &mut State, _ui_state: &mut StateEditorUiState) {
@@ -544,17 +2309,25 @@ impl StateEditorApp {
                         state.add_item("New Weapon", ItemInner::Weapon(weapon));
                     }
                     if ui.button("Add Armor").clicked() {
-                        let armor = Armor {
-                            ac_bonus: 1,
-                            stealth_disadvantage: false,
-                        };
-                        state.add_item("New Armor", ItemInner::Armor(armor));
+                        state.add_item("New Armor", ItemInner::Armor(Armor::new(1)));
                     }
+                    ui.menu_button("Add Magic Item", |ui| {
+                        for template in ItemTemplate::magic_items() {
+                            if ui.button(&template.name).clicked() {
+                                state.add_item_template(&template);
+                                ui.close();
+                            }
+                        }
+                    });
                 });
 
                 let items: Vec<ItemId> = state.items.keys().cloned().collect();
                 for item_id in items {
-                    Self::item_ui(ui, item_id, state, _ui_state);
+                    if Self::item_ui(ui, item_id, state, _ui_state)
+                        && let Err(e) = state.remove_item(item_id)
+                    {
+                        log::error!("Failed to remove item: {}", e);
+                    }
                 }
             }); // end CollapsingHeader for Items
     }
@@ -571,8 +2344,12 @@ impl StateEditorApp {
             ui.label("No state loaded. Create or load a state to begin editing.");
             return;
         };
+        Self::scenario_info_ui(ui, &state.scenario);
         ui.label(format!("Actors: {}", state.actors.len()));
         ui.label(format!("Items: {}", state.items.len()));
+        for (_, warning) in state.warnings() {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
         ui.separator();
 
         // Fill all remaining area below the stats/separator with a 2-col strip.
@@ -593,7 +2370,16 @@ impl StateEditorApp {
                                 egui::ScrollArea::vertical().auto_shrink([false; 2]).show(
                                     ui,
                                     |ui| {
+                                        Self::scenario_ui(ui, &mut state.scenario);
                                         Self::actors_list_ui(ui, state, &mut self.ui_state);
+                                        Self::groups_ui(ui, state);
+                                        Self::group_policies_ui(ui, state);
+                                        Self::initiative_ui(ui, state);
+                                        Self::death_cleanup_ui(ui, state);
+                                        Self::engagement_distance_ui(ui, state);
+                                        Self::zones_ui(ui, state);
+                                        Self::objectives_ui(ui, state);
+                                        Self::optional_rules_ui(ui, state);
                                     },
                                 );
                             },
@@ -625,14 +2411,21 @@ impl StateEditorApp {
                                     serde_json::to_value(&state).unwrap_or_default();
                             }
                             if ui.small_button("<").clicked() {
-                                match serde_json::from_value::<State>(
-                                    self.ui_state.state_json.clone(),
-                                ) {
+                                let loaded_state =
+                                    State::from_json_value(self.ui_state.state_json.clone())
+                                        .and_then(|loaded_state| {
+                                            loaded_state.validate()?;
+                                            Ok(loaded_state)
+                                        });
+                                match loaded_state {
                                     Ok(loaded_state) => {
                                         *state = loaded_state;
+                                        self.ui_state.load_error = None;
                                     }
                                     Err(e) => {
-                                        log::error!("Failed to parse state JSON: {}", e);
+                                        log::error!("Failed to apply state JSON: {:#}", e);
+                                        self.ui_state.load_error =
+                                            Some(format!("Failed to apply state JSON: {:#}", e));
                                     }
                                 }
                             }
@@ -659,3 +2452,11 @@ impl StateEditorApp {
         });
     }
 }
+
+/// Reads, migrates and validates a state file, keeping its JSON for the JSON pane.
+fn load_state_file(path: &std::path::Path) -> anyhow::Result<(State, serde_json::Value)> {
+    let state_json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let state = State::from_json_value(state_json.clone())?;
+    state.validate()?;
+    Ok((state, state_json))
+}
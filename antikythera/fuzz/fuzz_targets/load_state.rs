@@ -0,0 +1,36 @@
+//! Scenario files as loaded by the CLI and GUI: parsed, migrated, with formulas applied, then
+//! validated, and every roll plan found in them rolled. Run with `cargo +nightly fuzz run
+//! load_state` from `antikythera/`.
+
+#![no_main]
+
+use antikythera::prelude::{RollPlan, Roller, State};
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+/// Rolls anything in the loaded state that reads back as a roll plan: weapon and effect damage,
+/// healing, random tables and the rest, wherever they sit.
+fn roll_plans(value: &Value, rng: &mut Roller) {
+    match value {
+        Value::Object(fields) => {
+            if fields.contains_key("die_size")
+                && let Ok(plan) = serde_json::from_value::<RollPlan>(value.clone())
+            {
+                let _ = plan.distribution();
+                let _ = plan.roll(rng);
+            }
+            fields.values().for_each(|field| roll_plans(field, rng));
+        }
+        Value::Array(items) => items.iter().for_each(|item| roll_plans(item, rng)),
+        _ => {}
+    }
+}
+
+fuzz_target!(|json: &str| {
+    if let Ok(state) = State::from_json(json) {
+        let _ = state.validate();
+        if let Ok(value) = serde_json::to_value(&state) {
+            roll_plans(&value, &mut Roller::from_seed(0));
+        }
+    }
+});
@@ -0,0 +1,14 @@
+//! Roll formulas as typed into the GUI, which parses them and previews their distribution on every
+//! keystroke. Run with `cargo +nightly fuzz run parse_roll` from `antikythera/`.
+
+#![no_main]
+
+use antikythera::{prelude::Roller, roll_parser::parse_roll};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|formula: &str| {
+    if let Ok(plan) = parse_roll(formula) {
+        let _ = plan.distribution();
+        plan.roll(&mut Roller::from_seed(0)).unwrap();
+    }
+});
@@ -0,0 +1,4 @@
+pub mod balancer;
+pub mod learning;
+pub mod matchups;
+pub mod skill_challenge;
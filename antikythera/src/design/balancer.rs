@@ -0,0 +1,409 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    rules::{
+        actor::{Actor, ActorId},
+        items::{Item, ItemId},
+    },
+    simulation::{
+        integration::Integrator,
+        query::{OutcomeConditionProbability, Query},
+        roller::Roller,
+        state::State,
+    },
+    utils::Ratio,
+};
+
+/// An encounter composition found by the [`Balancer`].
+#[derive(Debug, Clone)]
+pub struct EncounterSuggestion {
+    /// How many of each bestiary template to field, keyed by the template's ID in the bestiary.
+    pub counts: BTreeMap<ActorId, u32>,
+    /// Combats the party won, out of combats run.
+    pub party_wins: Ratio,
+    /// Whether the party's win rate falls inside the balancer's target range.
+    pub within_target: bool,
+    /// The party plus the suggested monsters, ready to simulate or save as a scenario.
+    pub state: State,
+}
+
+impl EncounterSuggestion {
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write, bestiary: &State) -> std::fmt::Result {
+        for (i, (template, count)) in self.counts.iter().filter(|(_, c)| **c > 0).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}x ", count)?;
+            template.pretty_print(f, bestiary)?;
+        }
+        write!(f, ": {:.2}% party win", self.party_wins.to_f64() * 100.0)?;
+        if !self.within_target {
+            write!(f, " (outside target)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Searches for monster compositions that give a party a target chance of winning.
+///
+/// Every actor in the bestiary state is a monster template. Candidates are scored with short
+/// integrations and improved by hill-climbing over the template counts, one monster at a time.
+/// Every candidate is simulated with the same seed, so comparisons between them aren't swamped
+/// by dice noise. Templates need policies that let them act, or their combats never end, and
+/// per-actor target weights in template policies are dropped since those IDs mean nothing in
+/// the party's state (tag weights still apply).
+pub struct Balancer {
+    base: State,
+    party: Vec<ActorId>,
+    templates: BTreeMap<ActorId, Actor>,
+    target: RangeInclusive<f64>,
+    combats_per_candidate: usize,
+    max_monsters: u32,
+    max_evaluations: usize,
+    seed: u64,
+}
+
+impl Balancer {
+    pub fn new(party: State, bestiary: &State) -> Self {
+        let mut base = party;
+        let party: Vec<ActorId> = base.actors.keys().copied().collect();
+        let monster_group = base
+            .actors
+            .values()
+            .map(|actor| actor.group + 1)
+            .max()
+            .unwrap_or(1);
+
+        // bring over only the items the templates actually carry, under fresh IDs
+        let used_items: BTreeSet<ItemId> = bestiary
+            .actors
+            .values()
+            .flat_map(|actor| {
+                actor
+                    .inventory
+                    .items
+                    .keys()
+                    .chain(actor.equipped_items.items.iter())
+                    .copied()
+            })
+            .collect();
+        let mut item_ids = BTreeMap::new();
+        for item_id in used_items {
+            if let Some(item) = bestiary.items.get(&item_id) {
+                let new_id = base.add_item(&item.name, item.inner.clone());
+                base.items.insert(
+                    new_id,
+                    Item {
+                        id: new_id,
+                        ..item.clone()
+                    },
+                );
+                item_ids.insert(item_id, new_id);
+            }
+        }
+        let remap = |id: &ItemId| item_ids.get(id).copied().unwrap_or(*id);
+
+        let templates = bestiary
+            .actors
+            .values()
+            .map(|template| {
+                let mut actor = template.clone();
+                // the template leaves its group behind, so its group's policy comes along
+                actor.policy = bestiary.policy_of(template).clone();
+                actor.group = monster_group;
+                actor.mount = None;
+                actor.initiative = None;
+                actor.features_used.clear();
+                actor.policy.target_weights.clear();
+                actor.inventory.items = template
+                    .inventory
+                    .items
+                    .iter()
+                    .map(|(id, quantity)| (remap(id), *quantity))
+                    .collect();
+                actor.equipped_items.items =
+                    template.equipped_items.items.iter().map(remap).collect();
+                (template.id, actor)
+            })
+            .collect();
+
+        Self {
+            base,
+            party,
+            templates,
+            target: 0.6..=0.8,
+            combats_per_candidate: 200,
+            max_monsters: 8,
+            max_evaluations: 64,
+            seed: 0,
+        }
+    }
+
+    /// The range of party win probabilities to aim for. Defaults to 60-80%.
+    pub fn target(mut self, target: RangeInclusive<f64>) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn combats_per_candidate(mut self, combats: usize) -> Self {
+        self.combats_per_candidate = combats;
+        self
+    }
+
+    /// The most monsters a suggestion may contain in total.
+    pub fn max_monsters(mut self, max_monsters: u32) -> Self {
+        self.max_monsters = max_monsters;
+        self
+    }
+
+    /// Caps the number of candidate compositions simulated by [`Balancer::suggest`].
+    pub fn max_evaluations(mut self, max_evaluations: usize) -> Self {
+        self.max_evaluations = max_evaluations;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The party's state with the given number of each template added.
+    pub fn encounter(&self, counts: &BTreeMap<ActorId, u32>) -> State {
+        let mut state = self.base.clone();
+        for (template_id, count) in counts {
+            let Some(template) = self.templates.get(template_id) else {
+                continue;
+            };
+            for i in 0..*count {
+                let mut monster = template.clone();
+                if *count > 1 {
+                    monster.name = format!("{} {}", template.name, i + 1);
+                }
+                state.add_actor(monster);
+            }
+        }
+        state
+    }
+
+    /// Counts the combats the party wins against the given composition: at least one party
+    /// member is still standing and every monster is down.
+    pub fn evaluate(&self, counts: &BTreeMap<ActorId, u32>) -> anyhow::Result<Ratio> {
+        let state = self.encounter(counts);
+        let mut integrator = Integrator::new(
+            self.combats_per_candidate,
+            Roller::from_seed(self.seed),
+            state,
+        );
+        let results = integrator.run()?;
+
+        let party = self.party.clone();
+        let party_wins = OutcomeConditionProbability::new(move |state: &State| {
+            state.winner().is_some_and(|group| {
+                party.iter().any(|id| {
+                    state
+                        .get_actor(*id)
+                        .is_some_and(|actor| actor.group == group)
+                })
+            })
+        });
+        party_wins.query(&results.state_tree)
+    }
+
+    /// Lower is better; anything inside the target range beats anything outside it.
+    fn score(&self, party_wins: Ratio) -> f64 {
+        let win_probability = party_wins.to_f64();
+        let (low, high) = (*self.target.start(), *self.target.end());
+        let outside = if win_probability < low {
+            low - win_probability
+        } else if win_probability > high {
+            win_probability - high
+        } else {
+            0.0
+        };
+        let midpoint = (low + high) / 2.0;
+        outside * 10.0 + (win_probability - midpoint).abs()
+    }
+
+    /// Hill-climbs from a single monster of each template, returning up to `count` of the best
+    /// compositions found, best first.
+    pub fn suggest(&self, count: usize) -> anyhow::Result<Vec<EncounterSuggestion>> {
+        let mut evaluated: BTreeMap<Vec<u32>, Ratio> = BTreeMap::new();
+        let template_ids: Vec<ActorId> = self.templates.keys().copied().collect();
+        let to_counts = |counts: &[u32]| -> BTreeMap<ActorId, u32> {
+            template_ids
+                .iter()
+                .copied()
+                .zip(counts.iter().copied())
+                .collect()
+        };
+
+        let mut evaluate = |counts: &Vec<u32>| -> anyhow::Result<Option<Ratio>> {
+            if let Some(p) = evaluated.get(counts) {
+                return Ok(Some(*p));
+            }
+            if evaluated.len() >= self.max_evaluations {
+                return Ok(None);
+            }
+            let p = self.evaluate(&to_counts(counts))?;
+            evaluated.insert(counts.clone(), p);
+            Ok(Some(p))
+        };
+
+        'starts: for start in 0..template_ids.len() {
+            let mut current = vec![0; template_ids.len()];
+            current[start] = 1;
+            let Some(mut current_score) = evaluate(&current)?.map(|p| self.score(p)) else {
+                break;
+            };
+
+            loop {
+                let mut best: Option<(Vec<u32>, f64)> = None;
+                for i in 0..current.len() {
+                    for delta in [-1i32, 1] {
+                        let Some(next_count) = current[i].checked_add_signed(delta) else {
+                            continue;
+                        };
+                        let mut neighbor = current.clone();
+                        neighbor[i] = next_count;
+                        let total: u32 = neighbor.iter().sum();
+                        if total == 0 || total > self.max_monsters {
+                            continue;
+                        }
+                        let Some(p) = evaluate(&neighbor)? else {
+                            break 'starts;
+                        };
+                        let score = self.score(p);
+                        if best
+                            .as_ref()
+                            .is_none_or(|(_, best_score)| score < *best_score)
+                        {
+                            best = Some((neighbor, score));
+                        }
+                    }
+                }
+
+                match best {
+                    Some((neighbor, score)) if score < current_score => {
+                        current = neighbor;
+                        current_score = score;
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        let mut ranked: Vec<(Vec<u32>, Ratio)> = evaluated.into_iter().collect();
+        ranked.sort_by(|(_, a), (_, b)| self.score(*a).total_cmp(&self.score(*b)));
+        Ok(ranked
+            .into_iter()
+            .take(count)
+            .map(|(counts, party_wins)| {
+                let counts = to_counts(&counts);
+                EncounterSuggestion {
+                    state: self.encounter(&counts),
+                    counts,
+                    party_wins,
+                    within_target: self.target.contains(&party_wins.to_f64()),
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{
+        ActionType, ActorBuilder, Armor, ItemInner, Policy, PolicyBuilder, Stat, WeaponBuilder,
+        WeaponType,
+    };
+
+    fn fighter_policy() -> Policy {
+        PolicyBuilder::new()
+            .action_weight(ActionType::Attack, 10)
+            .action_weight(ActionType::UnarmedStrike, 1)
+            .build()
+    }
+
+    #[test]
+    fn test_balancer() -> anyhow::Result<()> {
+        let mut party = State::new();
+        let sword = party.add_item(
+            "Longsword",
+            ItemInner::Weapon(
+                WeaponBuilder::new(WeaponType::Longsword)
+                    .attack_bonus(5)
+                    .damage("1d8+3")
+                    .build(),
+            ),
+        );
+        let mut hero = ActorBuilder::new("Hero")
+            .stat(Stat::Strength, 16)
+            .max_health(30)
+            .level(3)
+            .build();
+        hero.give_item(sword, 1);
+        hero.policy = fighter_policy();
+        party.add_actor(hero);
+
+        let mut bestiary = State::new();
+        // take up an item ID in the bestiary so the scimitar has to be remapped
+        bestiary.add_item("Padding", ItemInner::Armor(Armor::test_armor()));
+        let scimitar = bestiary.add_item(
+            "Scimitar",
+            ItemInner::Weapon(
+                WeaponBuilder::new(WeaponType::Scimitar)
+                    .attack_bonus(4)
+                    .damage("1d6+2")
+                    .build(),
+            ),
+        );
+        bestiary.items.get_mut(&scimitar).unwrap().save_bonus = 1;
+        bestiary.items.get_mut(&scimitar).unwrap().weight = 3;
+        let mut goblin = ActorBuilder::new("Goblin").max_health(7).build();
+        goblin.give_item(scimitar, 1);
+        let goblin = bestiary.add_actor(goblin);
+        bestiary.set_group_policy(0, fighter_policy());
+
+        let balancer = Balancer::new(party, &bestiary)
+            .target(0.4..=0.7)
+            .combats_per_candidate(100)
+            .seed(7);
+
+        let encounter = balancer.encounter(&BTreeMap::from([(goblin, 2)]));
+        assert_eq!(encounter.actors.len(), 3);
+        assert_eq!(encounter.items.len(), 2);
+        let goblin_1 = encounter
+            .actors
+            .values()
+            .find(|actor| actor.name == "Goblin 1")
+            .unwrap();
+        assert_eq!(goblin_1.group, 1);
+        assert_eq!(encounter.policy_of(goblin_1), &fighter_policy());
+        let carried = *goblin_1.inventory.items.keys().next().unwrap();
+        assert_eq!(encounter.items[&carried].name, "Scimitar");
+        assert_eq!(encounter.items[&carried].save_bonus, 1);
+        assert_eq!(encounter.items[&carried].weight, 3);
+
+        let one = balancer.evaluate(&BTreeMap::from([(goblin, 1)]))?;
+        let six = balancer.evaluate(&BTreeMap::from([(goblin, 6)]))?;
+        assert!(one.numerator > six.numerator, "{} vs {}", one, six);
+
+        let suggestions = balancer.suggest(3)?;
+        assert!(!suggestions.is_empty());
+        for pair in suggestions.windows(2) {
+            assert!(balancer.score(pair[0].party_wins) <= balancer.score(pair[1].party_wins));
+        }
+        for suggestion in &suggestions {
+            assert_eq!(
+                suggestion.within_target,
+                (0.4..=0.7).contains(&suggestion.party_wins.to_f64())
+            );
+        }
+
+        Ok(())
+    }
+}
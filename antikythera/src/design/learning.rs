@@ -0,0 +1,324 @@
+//! Experimental: tuning an actor's policy by simulation.
+//!
+//! [`PolicyLearner`] runs the cross-entropy method over an actor's action and target weights,
+//! scoring each candidate policy by how often the actor's side wins a batch of short
+//! integrations against everyone else's fixed policies.
+
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{actions::ActionType, actor::ActorId},
+    simulation::{
+        integration::Integrator,
+        policy::Policy,
+        query::{OutcomeConditionProbability, Query},
+        roller::Roller,
+        state::State,
+    },
+    utils::Ratio,
+};
+
+/// A policy found by [`PolicyLearner::learn`], ready to save and load back into an actor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LearnedPolicy {
+    pub actor: ActorId,
+    pub policy: Policy,
+    /// Combats the actor's side wins with the learned policy.
+    pub wins: Ratio,
+    /// Combats the actor's side wins with its original policy, under the same dice.
+    pub baseline_wins: Ratio,
+    /// The best candidate's wins in each iteration. These are measured on the dice the
+    /// candidates were picked with, so they run a little optimistic.
+    pub history: Vec<Ratio>,
+}
+
+/// Tunes one actor's action and target weights to maximize its side's win probability.
+///
+/// Each iteration samples a population of weight vectors from a normal distribution per
+/// weight, scores them and the current means with the same seed, and refits the distributions
+/// to the best few.
+/// The learned policy is the final mean, checked against the original policy on dice no
+/// candidate was scored on. Weights stay between 1 and the maximum weight, so every action
+/// remains possible. Tag weights and the power attack heuristic are kept as they are.
+pub struct PolicyLearner {
+    state: State,
+    actor: ActorId,
+    actions: Vec<ActionType>,
+    iterations: usize,
+    population: usize,
+    elites: usize,
+    combats_per_evaluation: usize,
+    max_weight: i32,
+    seed: u64,
+}
+
+impl PolicyLearner {
+    pub fn new(state: State, actor: ActorId) -> Self {
+        Self {
+            state,
+            actor,
+            actions: vec![
+                ActionType::Attack,
+                ActionType::UnarmedStrike,
+                ActionType::Grapple,
+                ActionType::Shove,
+                ActionType::EscapeGrapple,
+                ActionType::Help,
+                ActionType::UseAbility,
+            ],
+            iterations: 10,
+            population: 16,
+            elites: 4,
+            combats_per_evaluation: 100,
+            max_weight: 100,
+            seed: 0,
+        }
+    }
+
+    /// The action types to tune weights for. Defaults to every action a policy can choose.
+    pub fn actions(mut self, actions: Vec<ActionType>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Candidate policies scored per iteration.
+    pub fn population(mut self, population: usize) -> Self {
+        self.population = population.max(1);
+        self
+    }
+
+    /// How many of the best candidates each iteration's distributions are refit to.
+    pub fn elites(mut self, elites: usize) -> Self {
+        self.elites = elites.max(1);
+        self
+    }
+
+    pub fn combats_per_evaluation(mut self, combats: usize) -> Self {
+        self.combats_per_evaluation = combats;
+        self
+    }
+
+    pub fn max_weight(mut self, max_weight: i32) -> Self {
+        self.max_weight = max_weight.max(1);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The actors the learned policy picks targets among: everyone on another side.
+    fn enemies(&self) -> Vec<ActorId> {
+        let group = self.state.get_actor(self.actor).map(|actor| actor.group);
+        self.state
+            .actors
+            .values()
+            .filter(|actor| Some(actor.group) != group)
+            .map(|actor| actor.id)
+            .collect()
+    }
+
+    /// The actor's original policy with its action weights and target weights for `enemies`
+    /// replaced by `weights`, in that order.
+    fn policy(&self, base: &Policy, enemies: &[ActorId], weights: &[i32]) -> Policy {
+        let (action_weights, target_weights) = weights.split_at(self.actions.len());
+        let mut policy = base.clone();
+        policy.action_weights = self
+            .actions
+            .iter()
+            .copied()
+            .zip(action_weights.iter().copied())
+            .collect();
+        policy
+            .target_weights
+            .retain(|(target, _)| !enemies.contains(target));
+        policy
+            .target_weights
+            .extend(enemies.iter().copied().zip(target_weights.iter().copied()));
+        policy
+    }
+
+    /// Counts the combats the actor's side wins with `policy`: at least one of them is still
+    /// standing and everyone else is down.
+    pub fn evaluate(&self, policy: &Policy, seed: u64) -> anyhow::Result<Ratio> {
+        let mut state = self.state.clone();
+        state.set_actor_policy(self.actor, policy.clone());
+        let mut integrator =
+            Integrator::new(self.combats_per_evaluation, Roller::from_seed(seed), state);
+        let results = integrator.run()?;
+
+        let group = self.state.get_actor(self.actor).map(|actor| actor.group);
+        let side_wins = OutcomeConditionProbability::new(move |state: &State| {
+            group.is_some() && state.winner() == group
+        });
+        side_wins.query(&results.state_tree)
+    }
+
+    pub fn learn(&self) -> anyhow::Result<LearnedPolicy> {
+        let actor = self
+            .state
+            .get_actor(self.actor)
+            .ok_or_else(|| anyhow::anyhow!("Actor {} not found in state", self.actor.0))?;
+        let base = self.state.policy_of(actor).clone();
+        anyhow::ensure!(
+            !self.actions.is_empty(),
+            "No action types to learn weights for"
+        );
+        let enemies = self.enemies();
+        anyhow::ensure!(!enemies.is_empty(), "Actor {} has no enemies", self.actor.0);
+
+        // start from the original policy, with actions it never takes as unlikely as possible
+        let max = self.max_weight as f64;
+        let clamp = |weight: f64| weight.round().clamp(1.0, max) as i32;
+        let mut means: Vec<f64> = self
+            .actions
+            .iter()
+            .map(|action| {
+                base.action_weights
+                    .iter()
+                    .find(|(a, _)| a == action)
+                    .map_or(1, |(_, weight)| *weight)
+            })
+            .chain(
+                enemies
+                    .iter()
+                    .map(|enemy| base.target_weight(*enemy, &self.state)),
+            )
+            .map(|weight| (weight as f64).clamp(1.0, max))
+            .collect();
+        let mut deviations = vec![max / 2.0; means.len()];
+
+        let mut roller = Roller::from_seed(self.seed);
+        let mut history = Vec::with_capacity(self.iterations);
+        for iteration in 0..self.iterations {
+            let seed = self.seed.wrapping_add(iteration as u64 + 1);
+            let mut scored = Vec::with_capacity(self.population);
+            for candidate in 0..self.population {
+                // the current mean always competes, so noise alone can't pull it somewhere worse
+                let weights = if candidate == 0 {
+                    means.iter().map(|mean| clamp(*mean)).collect()
+                } else {
+                    means
+                        .iter()
+                        .zip(&deviations)
+                        .map(|(mean, deviation)| {
+                            let normal = Normal::new(*mean, *deviation)?;
+                            Ok(clamp(normal.sample(roller.rng())))
+                        })
+                        .collect::<anyhow::Result<Vec<i32>>>()?
+                };
+                let wins = self.evaluate(&self.policy(&base, &enemies, &weights), seed)?;
+                scored.push((weights, wins));
+            }
+            scored.sort_by(|(_, a), (_, b)| b.to_f64().total_cmp(&a.to_f64()));
+            scored.truncate(self.elites);
+            history.push(scored[0].1);
+
+            for (i, (mean, deviation)) in means.iter_mut().zip(&mut deviations).enumerate() {
+                let elite_weights = scored.iter().map(|(weights, _)| weights[i] as f64);
+                let elite_mean = elite_weights.clone().sum::<f64>() / scored.len() as f64;
+                let variance = elite_weights
+                    .map(|weight| (weight - elite_mean).powi(2))
+                    .sum::<f64>()
+                    / scored.len() as f64;
+                *mean = elite_mean;
+                // shrink the spread gradually, so one lucky iteration can't freeze a weight
+                *deviation = (*deviation + variance.sqrt()) / 2.0;
+            }
+            log::debug!(
+                "Policy learning iteration {}: best won {}",
+                iteration + 1,
+                scored[0].1
+            );
+        }
+
+        let weights: Vec<i32> = means.iter().map(|mean| clamp(*mean)).collect();
+        let policy = self.policy(&base, &enemies, &weights);
+        let check_seed = self.seed.wrapping_add(self.iterations as u64 + 1);
+        Ok(LearnedPolicy {
+            actor: self.actor,
+            wins: self.evaluate(&policy, check_seed)?,
+            baseline_wins: self.evaluate(&base, check_seed)?,
+            policy,
+            history,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{ActorBuilder, ItemInner, PolicyBuilder, Stat, WeaponBuilder, WeaponType};
+    use crate::test_utils::brawl_policy;
+
+    #[test]
+    fn test_policy_learner() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let greatsword = state.add_item(
+            "Greatsword",
+            ItemInner::Weapon(
+                WeaponBuilder::new(WeaponType::Greatsword)
+                    .damage("2d6+8")
+                    .build(),
+            ),
+        );
+        let mut fighter = ActorBuilder::new("Fighter")
+            .stat(Stat::Strength, 16)
+            .max_health(14)
+            .build();
+        fighter.give_item(greatsword, 1);
+        let fighter = state.add_actor(fighter);
+        let ogre = state.add_actor(
+            ActorBuilder::new("Ogre")
+                .group(1)
+                .stat(Stat::Strength, 16)
+                .max_health(20)
+                .build(),
+        );
+
+        // the fighter mostly punches, even with a greatsword in hand
+        state.set_actor_policy(
+            fighter,
+            PolicyBuilder::new()
+                .action_weight(ActionType::Attack, 1)
+                .action_weight(ActionType::UnarmedStrike, 20)
+                .build(),
+        );
+        state.set_actor_policy(ogre, brawl_policy());
+
+        let learned = PolicyLearner::new(state, fighter)
+            .actions(vec![ActionType::Attack, ActionType::UnarmedStrike])
+            .iterations(5)
+            .population(10)
+            .elites(3)
+            .combats_per_evaluation(100)
+            .max_weight(20)
+            .learn()?;
+        assert_eq!(learned.history.len(), 5);
+        let weight = |action| {
+            learned
+                .policy
+                .action_weights
+                .iter()
+                .find(|(a, _)| *a == action)
+                .map(|(_, weight)| *weight)
+                .unwrap()
+        };
+        assert!(weight(ActionType::Attack) > weight(ActionType::UnarmedStrike));
+        assert!(learned.wins.numerator > learned.baseline_wins.numerator);
+        assert_eq!(learned.policy.target_weights.len(), 1);
+        assert_eq!(learned.policy.target_weights[0].0, ogre);
+
+        let json = serde_json::to_string(&learned)?;
+        assert_eq!(serde_json::from_str::<LearnedPolicy>(&json)?, learned);
+
+        Ok(())
+    }
+}
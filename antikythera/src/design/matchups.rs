@@ -0,0 +1,246 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::actor::ActorId,
+    simulation::{hook::Hook, integration::Integrator, roller::Roller, state::State},
+    utils::Ratio,
+};
+
+/// How one actor fared against another over a batch of 1v1 combats.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MatchupResult {
+    /// Combats the actor won, i.e. ended standing while the opponent didn't, out of combats
+    /// fought.
+    pub wins: Ratio,
+    /// Mean number of rounds the actor needed to drop the opponent, over the combats it won.
+    pub mean_rounds_to_kill: Option<f64>,
+}
+
+/// Win probabilities for every pair of actors in a state, fought 1v1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchupMatrix {
+    pub actors: Vec<ActorId>,
+    pub names: Vec<String>,
+    /// `results[i][j]` is how `actors[i]` fared against `actors[j]`. The diagonal is `None`.
+    pub results: Vec<Vec<Option<MatchupResult>>>,
+}
+
+impl MatchupMatrix {
+    pub fn get(&self, actor: ActorId, opponent: ActorId) -> Option<MatchupResult> {
+        let i = self.actors.iter().position(|id| *id == actor)?;
+        let j = self.actors.iter().position(|id| *id == opponent)?;
+        self.results[i][j]
+    }
+
+    /// Writes the matrix as CSV, one row per actor and one win probability column per opponent.
+    pub fn write_csv(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        self.write_csv_with(f, |result| Some(format!("{:.4}", result.wins.to_f64())))
+    }
+
+    /// Writes the mean rounds-to-kill matrix as CSV, laid out like [`MatchupMatrix::write_csv`].
+    pub fn write_rounds_csv(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        self.write_csv_with(f, |result| {
+            result
+                .mean_rounds_to_kill
+                .map(|rounds| format!("{:.2}", rounds))
+        })
+    }
+
+    fn write_csv_with(
+        &self,
+        f: &mut impl std::fmt::Write,
+        cell: impl Fn(&MatchupResult) -> Option<String>,
+    ) -> std::fmt::Result {
+        write!(f, "actor")?;
+        for name in &self.names {
+            write!(f, ",\"{}\"", name.replace('"', "\"\""))?;
+        }
+        writeln!(f)?;
+        for (name, row) in self.names.iter().zip(&self.results) {
+            write!(f, "\"{}\"", name.replace('"', "\"\""))?;
+            for result in row {
+                write!(
+                    f,
+                    ",{}",
+                    result.as_ref().and_then(&cell).unwrap_or_default()
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs quick 1v1 integrations between every pair of actors in a state.
+///
+/// Each actor keeps its stats, items, and policy but fights alone, without its mount or rider.
+/// Every pair is simulated with the same seed. As with any integration, actors need policies
+/// that let them act, or their combats never end.
+pub struct MatchupAnalysis {
+    state: State,
+    combats_per_pair: usize,
+    seed: u64,
+}
+
+impl MatchupAnalysis {
+    pub fn new(state: State) -> Self {
+        Self {
+            state,
+            combats_per_pair: 100,
+            seed: 0,
+        }
+    }
+
+    pub fn combats_per_pair(mut self, combats: usize) -> Self {
+        self.combats_per_pair = combats;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The state `actor` and `opponent` fight in: just the two of them, on opposing sides.
+    pub fn pair_state(&self, actor: ActorId, opponent: ActorId) -> State {
+        let mut state = self.state.clone();
+        state.actors.retain(|id, _| *id == actor || *id == opponent);
+        for actor_state in state.actors.values_mut() {
+            actor_state.group = if actor_state.id == actor { 0 } else { 1 };
+            actor_state.mount = None;
+        }
+        state
+    }
+
+    /// Simulates `actor` against `opponent`, returning how each of them fared, in that order.
+    pub fn run_pair(
+        &self,
+        actor: ActorId,
+        opponent: ActorId,
+    ) -> anyhow::Result<(MatchupResult, MatchupResult)> {
+        let outcomes = Outcomes::default();
+        let mut integrator = Integrator::new(
+            self.combats_per_pair,
+            Roller::from_seed(self.seed),
+            self.pair_state(actor, opponent),
+        );
+        integrator.add_hook(OutcomeRecorder {
+            rounds: 0,
+            outcomes: outcomes.clone(),
+        });
+        integrator.run()?;
+
+        let outcomes = outcomes.lock().unwrap();
+        let result_for = |id: ActorId| {
+            let won: Vec<u64> = outcomes
+                .iter()
+                .filter(|(winner, _)| *winner == Some(id))
+                .map(|(_, rounds)| *rounds)
+                .collect();
+            MatchupResult {
+                wins: Ratio::new(won.len() as u64, outcomes.len() as u64),
+                mean_rounds_to_kill: (!won.is_empty())
+                    .then(|| won.iter().sum::<u64>() as f64 / won.len() as f64),
+            }
+        };
+        Ok((result_for(actor), result_for(opponent)))
+    }
+
+    pub fn run(&self) -> anyhow::Result<MatchupMatrix> {
+        let actors: Vec<ActorId> = self.state.actors.keys().copied().collect();
+        let names = self
+            .state
+            .actors
+            .values()
+            .map(|actor| actor.name.clone())
+            .collect();
+        let mut results = vec![vec![None; actors.len()]; actors.len()];
+        for i in 0..actors.len() {
+            for j in (i + 1)..actors.len() {
+                let (a, b) = self.run_pair(actors[i], actors[j])?;
+                results[i][j] = Some(a);
+                results[j][i] = Some(b);
+            }
+        }
+        Ok(MatchupMatrix {
+            actors,
+            names,
+            results,
+        })
+    }
+}
+
+/// The winner of each combat, if any, and how many rounds it lasted.
+type Outcomes = Arc<Mutex<Vec<(Option<ActorId>, u64)>>>;
+
+/// Records the winner and length of every combat.
+struct OutcomeRecorder {
+    rounds: u64,
+    outcomes: Outcomes,
+}
+
+impl Hook for OutcomeRecorder {
+    fn on_turn_start(&mut self, _state: &State, _actor_id: ActorId, turn: u64) {
+        self.rounds = turn + 1;
+    }
+
+    fn on_combat_end(&mut self, state: &State) {
+        let standing: Vec<ActorId> = state
+            .actors
+            .values()
+            .filter(|actor| actor.is_alive())
+            .map(|actor| actor.id)
+            .collect();
+        let winner = match standing.as_slice() {
+            [winner] => Some(*winner),
+            _ => None,
+        };
+        self.outcomes.lock().unwrap().push((winner, self.rounds));
+        self.rounds = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{ActorBuilder, Stat};
+    use crate::test_utils::brawl_policy;
+
+    #[test]
+    fn test_matchup_matrix() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let mut add = |name: &str, strength: u32, health: i32| {
+            let mut actor = ActorBuilder::new(name)
+                .stat(Stat::Strength, strength)
+                .max_health(health)
+                .build();
+            actor.policy = policy.clone();
+            state.add_actor(actor)
+        };
+        let ogre = add("Ogre", 20, 40);
+        let rat = add("Rat", 4, 2);
+        let dog = add("Dog", 12, 8);
+
+        let matrix = MatchupAnalysis::new(state).combats_per_pair(50).run()?;
+        assert_eq!(matrix.actors, vec![ogre, rat, dog]);
+        assert_eq!(matrix.get(ogre, ogre), None);
+
+        let ogre_vs_rat = matrix.get(ogre, rat).unwrap();
+        let rat_vs_ogre = matrix.get(rat, ogre).unwrap();
+        assert_eq!(ogre_vs_rat.wins.denominator, 50);
+        assert!(ogre_vs_rat.wins.to_f64() > 0.9);
+        assert!(ogre_vs_rat.wins.numerator + rat_vs_ogre.wins.numerator <= 50);
+        assert!(ogre_vs_rat.mean_rounds_to_kill.unwrap() >= 1.0);
+        assert!(matrix.get(dog, rat).unwrap().wins.to_f64() > 0.5);
+
+        let mut csv = String::new();
+        matrix.write_csv(&mut csv)?;
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.starts_with("actor,\"Ogre\",\"Rat\",\"Dog\"\n\"Ogre\",,"));
+
+        Ok(())
+    }
+}
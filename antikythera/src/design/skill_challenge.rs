@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        actor::{Actor, ActorId},
+        dice::{Advantage, RollSettings},
+        skills::{Skill, SkillProficiency},
+    },
+    simulation::{roller::Roller, state::State},
+};
+
+/// One check in a skill challenge, which the attempting actor can make with any of `skills`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeCheck {
+    pub skills: Vec<Skill>,
+    pub dc: i32,
+}
+
+/// How the party decides who attempts each check.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssignmentStrategy {
+    /// Whoever has the highest modifier for the check.
+    #[default]
+    BestModifier,
+    /// Like [`AssignmentStrategy::BestModifier`], but another party member proficient in the skill
+    /// helps, giving advantage.
+    BestWithHelp,
+    /// Party members take turns, in ID order.
+    RoundRobin,
+    /// A party member chosen at random.
+    Random,
+}
+
+impl AssignmentStrategy {
+    pub fn all() -> Vec<AssignmentStrategy> {
+        vec![
+            AssignmentStrategy::BestModifier,
+            AssignmentStrategy::BestWithHelp,
+            AssignmentStrategy::RoundRobin,
+            AssignmentStrategy::Random,
+        ]
+    }
+}
+
+/// How a party fared over many attempts at a skill challenge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkillChallengeResult {
+    pub trials: usize,
+    pub success_probability: f64,
+    /// Mean failures racked up per attempt, whether it succeeded or not.
+    pub mean_failures: f64,
+    /// Mean checks made per attempt.
+    pub mean_checks: f64,
+    /// How many checks each party member made, over every trial.
+    pub attempts: BTreeMap<ActorId, u64>,
+}
+
+impl SkillChallengeResult {
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write, state: &State) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Success probability: {:.2}%",
+            self.success_probability * 100.0
+        )?;
+        writeln!(f, "Mean failures: {:.2}", self.mean_failures)?;
+        writeln!(f, "Mean checks: {:.2}", self.mean_checks)?;
+        for (id, attempts) in &self.attempts {
+            id.pretty_print(f, state)?;
+            writeln!(
+                f,
+                ": {:.2} checks per attempt",
+                *attempts as f64 / self.trials.max(1) as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Monte Carlo simulation of a non-combat skill challenge: the party makes checks until it
+/// gathers enough successes or too many failures, whichever comes first.
+///
+/// The checks are made in order, starting over from the first if the challenge outlasts them,
+/// so a chase can be a single check repeated and a heist a fixed series of obstacles.
+pub struct SkillChallenge {
+    state: State,
+    group: u32,
+    checks: Vec<ChallengeCheck>,
+    successes: u32,
+    failures: u32,
+    strategy: AssignmentStrategy,
+    trials: usize,
+    seed: u64,
+}
+
+impl SkillChallenge {
+    pub fn new(state: State) -> Self {
+        Self {
+            state,
+            group: 0,
+            checks: Vec::new(),
+            successes: 3,
+            failures: 3,
+            strategy: AssignmentStrategy::default(),
+            trials: 1000,
+            seed: 0,
+        }
+    }
+
+    /// The group whose conscious members take part.
+    pub fn group(mut self, group: u32) -> Self {
+        self.group = group;
+        self
+    }
+
+    pub fn check(mut self, skills: &[Skill], dc: i32) -> Self {
+        self.checks.push(ChallengeCheck {
+            skills: skills.to_vec(),
+            dc,
+        });
+        self
+    }
+
+    /// Successes needed to complete the challenge.
+    pub fn successes(mut self, successes: u32) -> Self {
+        self.successes = successes;
+        self
+    }
+
+    /// Failures that end the challenge in defeat.
+    pub fn failures(mut self, failures: u32) -> Self {
+        self.failures = failures;
+        self
+    }
+
+    pub fn strategy(mut self, strategy: AssignmentStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn trials(mut self, trials: usize) -> Self {
+        self.trials = trials;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn run(&self) -> anyhow::Result<SkillChallengeResult> {
+        anyhow::ensure!(
+            !self.checks.is_empty(),
+            "A skill challenge needs at least one check"
+        );
+        anyhow::ensure!(
+            self.checks.iter().all(|check| !check.skills.is_empty()),
+            "Every check needs at least one skill"
+        );
+        anyhow::ensure!(
+            self.successes > 0 && self.failures > 0,
+            "A skill challenge needs at least one success and one failure to end"
+        );
+        let party: Vec<&Actor> = self
+            .state
+            .actors
+            .values()
+            .filter(|actor| actor.group == self.group && actor.health > 0)
+            .collect();
+        anyhow::ensure!(
+            !party.is_empty(),
+            "Group {} has nobody conscious to attempt the challenge",
+            self.group
+        );
+
+        let mut roller = Roller::from_seed(self.seed);
+        let mut won = 0;
+        let mut total_failures = 0;
+        let mut total_checks = 0;
+        let mut attempts = BTreeMap::new();
+        for _ in 0..self.trials {
+            let (mut successes, mut failures) = (0, 0);
+            let mut turn = 0;
+            while successes < self.successes && failures < self.failures {
+                let check = &self.checks[turn % self.checks.len()];
+                let (actor, advantage) = self.assign(&party, check, turn, &mut roller);
+                let skill = actor.best_skill(&check.skills);
+                let plan = actor.plan_skill_check(
+                    skill,
+                    RollSettings {
+                        advantage,
+                        ..Default::default()
+                    },
+                );
+                if roller.roll(&plan)?.total >= check.dc {
+                    successes += 1;
+                } else {
+                    failures += 1;
+                }
+                *attempts.entry(actor.id).or_insert(0) += 1;
+                turn += 1;
+            }
+            if successes >= self.successes {
+                won += 1;
+            }
+            total_failures += failures;
+            total_checks += turn;
+        }
+
+        let trials = self.trials.max(1) as f64;
+        Ok(SkillChallengeResult {
+            trials: self.trials,
+            success_probability: won as f64 / trials,
+            mean_failures: total_failures as f64 / trials,
+            mean_checks: total_checks as f64 / trials,
+            attempts,
+        })
+    }
+
+    /// Who attempts `check` on the challenge's `turn`th check, and with what advantage.
+    fn assign<'a>(
+        &self,
+        party: &[&'a Actor],
+        check: &ChallengeCheck,
+        turn: usize,
+        roller: &mut Roller,
+    ) -> (&'a Actor, Advantage) {
+        let modifier = |actor: &Actor| actor.skill_modifier(actor.best_skill(&check.skills));
+        let best = || {
+            party
+                .iter()
+                .copied()
+                .max_by_key(|actor| (modifier(actor), std::cmp::Reverse(actor.id)))
+                .expect("the party isn't empty")
+        };
+        match self.strategy {
+            AssignmentStrategy::BestModifier => (best(), Advantage::Normal),
+            AssignmentStrategy::BestWithHelp => {
+                let actor = best();
+                let helped = party.iter().any(|helper| {
+                    helper.id != actor.id
+                        && check.skills.iter().any(|skill| {
+                            matches!(
+                                helper.skill_proficiencies.get(*skill),
+                                SkillProficiency::Proficient | SkillProficiency::Expert
+                            )
+                        })
+                });
+                let advantage = if helped {
+                    Advantage::Advantage
+                } else {
+                    Advantage::Normal
+                };
+                (actor, advantage)
+            }
+            AssignmentStrategy::RoundRobin => (party[turn % party.len()], Advantage::Normal),
+            AssignmentStrategy::Random => (
+                party[roller.range(0, party.len() as u32 - 1) as usize],
+                Advantage::Normal,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{actor::ActorBuilder, stats::Stat};
+
+    #[test]
+    fn test_skill_challenge() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let rogue = state.add_actor(
+            ActorBuilder::new("Rogue")
+                .stat(Stat::Dexterity, 18)
+                .skill_proficiency(Skill::Stealth, SkillProficiency::Expert)
+                .build(),
+        );
+        let fighter = state.add_actor(
+            ActorBuilder::new("Fighter")
+                .stat(Stat::Dexterity, 8)
+                .skill_proficiency(Skill::Stealth, SkillProficiency::Proficient)
+                .build(),
+        );
+        state.add_actor(ActorBuilder::new("Guard").group(1).build());
+
+        let heist = |strategy| {
+            SkillChallenge::new(state.clone())
+                .check(&[Skill::Stealth], 15)
+                .successes(3)
+                .failures(2)
+                .strategy(strategy)
+                .trials(2000)
+                .run()
+        };
+        let best = heist(AssignmentStrategy::BestModifier)?;
+        assert_eq!(best.attempts.keys().collect::<Vec<_>>(), [&rogue]);
+        let helped = heist(AssignmentStrategy::BestWithHelp)?;
+        let round_robin = heist(AssignmentStrategy::RoundRobin)?;
+        assert!(round_robin.attempts.contains_key(&fighter));
+        assert!(helped.success_probability > best.success_probability);
+        assert!(best.success_probability > round_robin.success_probability);
+        assert!(best.mean_checks >= 2.0 && best.mean_checks <= 4.0);
+
+        // the rogue alone, at +8, needs a 7 or better to beat DC 15: a 0.7^3 chance of three
+        // straight successes, plus the three ways of fitting one failure in before the third
+        let p = 0.7f64;
+        let expected = p.powi(3) + 3.0 * p.powi(3) * (1.0 - p);
+        assert!((best.success_probability - expected).abs() < 0.05);
+
+        Ok(())
+    }
+}
@@ -1,33 +1,95 @@
+pub mod design;
 pub mod roll_parser;
 pub mod rules;
 pub mod simulation;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod utils;
 
 pub mod prelude {
     pub use crate::{
+        design::{
+            balancer::{Balancer, EncounterSuggestion},
+            learning::{LearnedPolicy, PolicyLearner},
+            matchups::{MatchupAnalysis, MatchupMatrix, MatchupResult},
+            skill_challenge::{
+                AssignmentStrategy, ChallengeCheck, SkillChallenge, SkillChallengeResult,
+            },
+        },
         rules::{
+            abilities::Ability,
             actions::{Action, ActionEconomyUsage, ActionTaken, ActionType},
             actor::{Actor, ActorBuilder, ActorId},
-            damage::DamageType,
-            dice::{RollPlan, RollResult, RollSettings},
+            armor_class::{AcFormula, ArmorClassBreakdown},
+            conditions::{Condition, Conditions},
+            cover::Cover,
+            damage::{DamageResponse, DamageType, Resistances},
+            death::DeathBehavior,
+            dice::{Advantage, RollDistribution, RollPlan, RollResult, RollSettings},
+            effects::{Effect, EffectTarget, EffectTrigger, RandomEffectEntry, RandomEffectTable},
+            encumbrance::Encumbrance,
+            features::Feature,
+            homebrew::{EffectSpec, HomebrewEffects},
             items::{
-                Armor, Item, ItemId, ItemInner, ItemType, Potion, Scroll, Weapon, WeaponBuilder,
-                WeaponProficiency, WeaponType,
+                Armor, EquipSlot, Item, ItemId, ItemInner, ItemType, Potion, Scroll, Weapon,
+                WeaponBuilder, WeaponCategory, WeaponProficiencies, WeaponProficiency, WeaponType,
             },
+            magic_items::ItemTemplate,
+            metadata::{Metadata, ScenarioMetadata},
+            rerolls::{D20Test, RerollResource},
             saves::SavingThrow,
+            scaling::LevelScaling,
+            size::Size,
             skills::{Skill, SkillProficiency},
             spells::Spell,
-            stats::Stat,
+            stats::{
+                PointBuy, STANDARD_ARRAY, Stat, StatBounds, StatModifier, StatRollMethod, Stats,
+            },
+            vision::{Lighting, Vision},
         },
         simulation::{
-            hook::Hook,
-            integration::{IntegrationResults, Integrator},
-            policy::{Policy, PolicyBuilder},
+            actor_file::ActorFile,
+            classification::{OutcomeClassifier, OutcomeLabels, PartyOutcomeClassifier},
+            comparison::{MetricDelta, RunComparison, Significance},
+            contribution::{ActorContribution, ContributionStats, TypedDamage},
+            diff::{FieldChange, StateDiff},
+            encounter::{DeathCleanup, EncounterSetup, InitiativeMode, OptionalRules},
+            environment::{EffectArea, EnvironmentalEffect, Hazard, Schedule},
+            groups::GroupInfo,
+            hook::{Hook, MetricSample, RegisteredHook},
+            integration::{
+                IntegrationResults, Integrator, MEMORY_CHECK_INTERVAL, MemoryLimit,
+                MemoryLimitAction, RunMetadata, StoppingCondition, Verbosity,
+            },
+            messages::{LogMessage, MessageTemplates, ReportMessage},
+            objectives::{Objective, Outcome},
+            phases::Phase,
+            policy::{
+                CandidateAction, DecisionAudit, Policy, PolicyBuilder, PowerAttackHeuristic,
+                RejectionReason, RerollHeuristic,
+            },
+            positioning::{Positioning, RangeBand, SideDistance, ZoneId, ZoneMap},
             query::*,
+            roll_statistics::{DieStatistics, RollStatistics},
             roller::Roller,
-            state::State,
-            state_tree::StateTree,
-            transition::Transition,
+            schema::SCHEMA_VERSION,
+            state::{ItemCloning, State},
+            state_condition::{StateCondition, StateConditionProbability},
+            state_tree::{StateTree, TerminalState, TreeMemoryUsage, TreeMode},
+            summary::{
+                ActorSummary, ContributionSummary, OutcomeSummary, RunSummary, TypedDamageSummary,
+            },
+            time_series::{HpRoundSummary, HpTimeSeries},
+            timed_effects::{Expiry, TimedEffect, TimedEffects},
+            timeline::{EventTimeline, RoundHistogram},
+            transition::{Transition, TransitionType},
+            turn_order::{FirstMover, PositionStats, TurnOrderStats},
+            usage::{ActionCount, ActionUsage, UsedAction},
+            weapon_stats::{AttackTally, WeaponRecord, WeaponStats},
+        },
+        utils::{
+            Ratio,
+            stats::{Estimate, Moments},
         },
     };
 }
@@ -111,17 +173,26 @@ mod tests {
             state.get_actor(hero).map(|a| a.is_alive()).unwrap()
         });
         let prob = query.query(&results.state_tree)?;
-        println!("Probability that hero is alive: {:.2}%", prob * 100.0);
+        println!(
+            "Probability that hero is alive: {:.2}%",
+            prob.to_f64() * 100.0
+        );
         let query = OutcomeConditionProbability::new(move |state: &State| {
             state.get_actor(goblin).map(|a| a.is_alive()).unwrap()
         });
         let prob = query.query(&results.state_tree)?;
-        println!("Probability that goblin 1 is alive: {:.2}%", prob * 100.0);
+        println!(
+            "Probability that goblin 1 is alive: {:.2}%",
+            prob.to_f64() * 100.0
+        );
         let query = OutcomeConditionProbability::new(move |state: &State| {
             state.get_actor(goblin2).map(|a| a.is_alive()).unwrap()
         });
         let prob = query.query(&results.state_tree)?;
-        println!("Probability that goblin 2 is alive: {:.2}%", prob * 100.0);
+        println!(
+            "Probability that goblin 2 is alive: {:.2}%",
+            prob.to_f64() * 100.0
+        );
 
         Ok(())
     }
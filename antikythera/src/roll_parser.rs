@@ -8,11 +8,16 @@ use nom::{
     sequence::{delimited, pair, preceded},
 };
 
+/// Parses a roll like `2d6+3` or `1d20 [adv]`. Rolls that parse but can't be made, like `1d0`,
+/// are errors too; see [`RollPlan::validate`].
 pub fn parse_roll(input: &str) -> anyhow::Result<RollPlan> {
     let res = all_consuming(roll_plan).parse(input);
 
     match res {
-        Ok((_, roll_plan)) => Ok(roll_plan),
+        Ok((_, roll_plan)) => {
+            roll_plan.validate()?;
+            Ok(roll_plan)
+        }
         Err(_) => Err(anyhow::anyhow!("Failed to parse roll plan")),
     }
 }
@@ -61,12 +66,14 @@ fn roll_settings(input: &str) -> IResult<&str, RollSettings> {
                 opt(preceded(space0, minimum_die_value)),
                 opt(preceded(space0, maximum_die_value)),
                 opt(preceded(space0, reroll_dice_below)),
+                opt(preceded(space0, reroll_once_below)),
             ),
-            |(advantage, min, max, reroll)| RollSettings {
+            |(advantage, min, max, reroll, reroll_once)| RollSettings {
                 advantage: advantage.unwrap_or(Advantage::Normal),
                 minimum_die_value: min,
                 maximum_die_value: max,
                 reroll_dice_below: reroll,
+                reroll_once_below: reroll_once,
             },
         ),
         preceded(space0, char(']')),
@@ -91,6 +98,9 @@ fn maximum_die_value(input: &str) -> IResult<&str, u32> {
 fn reroll_dice_below(input: &str) -> IResult<&str, u32> {
     preceded(tag("rr<"), map_res(digit1, |s: &str| s.parse::<u32>())).parse(input)
 }
+fn reroll_once_below(input: &str) -> IResult<&str, u32> {
+    preceded(tag("ro<"), map_res(digit1, |s: &str| s.parse::<u32>())).parse(input)
+}
 
 #[cfg(test)]
 mod tests {
@@ -109,6 +119,7 @@ mod tests {
                 minimum_die_value: None,
                 maximum_die_value: None,
                 reroll_dice_below: None,
+                reroll_once_below: None,
             },
         };
         let result = parse_roll(input).unwrap();
@@ -117,7 +128,7 @@ mod tests {
 
     #[test]
     fn test_parse_roll_with_settings() {
-        let input = "4d10-2 [adv min=3 max=8 rr<2]";
+        let input = "4d10-2 [adv min=3 max=8 rr<2 ro<3]";
         let expected = RollPlan {
             num_dice: 4,
             die_size: 10,
@@ -127,9 +138,32 @@ mod tests {
                 minimum_die_value: Some(3),
                 maximum_die_value: Some(8),
                 reroll_dice_below: Some(2),
+                reroll_once_below: Some(3),
             },
         };
         let result = parse_roll(input).unwrap();
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_parse_roll_unrollable() {
+        for input in [
+            "1d0",
+            "1d200000",
+            "10001d1",
+            "4294967295d4294967295",
+            "1d6+2000000",
+            "0d200000000",
+            "1d6 [max=0]",
+            "1d6 [min=10]",
+            "1d6 [min=5 max=2]",
+            "1d6 [rr<7]",
+        ] {
+            assert!(parse_roll(input).is_err(), "{:?} should fail", input);
+        }
+        assert!(parse_roll("0d0").is_ok());
+        assert!(parse_roll("20d100").is_ok());
+        assert!(parse_roll("100d100").is_ok());
+        assert!(parse_roll("100d10-1000000 [min=1 max=10 rr<10]").is_ok());
+    }
 }
@@ -1,10 +1,25 @@
+pub mod abilities;
 pub mod actions;
 pub mod actor;
+pub mod armor_class;
+pub mod conditions;
+pub mod cover;
 pub mod damage;
 pub mod death;
 pub mod dice;
+pub mod effects;
+pub mod encumbrance;
+pub mod features;
+pub mod formula;
+pub mod homebrew;
 pub mod items;
+pub mod magic_items;
+pub mod metadata;
+pub mod rerolls;
 pub mod saves;
+pub mod scaling;
+pub mod size;
 pub mod skills;
 pub mod spells;
 pub mod stats;
+pub mod vision;
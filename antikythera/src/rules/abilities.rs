@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rules::effects::Effect;
+
+/// A special action with its own effects, such as a dragon's breath weapon.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Ability {
+    pub name: String,
+    /// The lowest d6 roll that recharges the ability once it's been used, e.g. `Some(5)` for
+    /// "Recharge 5–6". The roll is made at the start of each of the owner's turns.
+    /// `None` means the ability can be used every turn.
+    #[serde(default)]
+    pub recharge: Option<u32>,
+    /// Whether the effects land on every enemy instead of a single target, like a breath weapon's cone.
+    #[serde(default)]
+    pub area: bool,
+    /// Effects applied in order when the ability is used. [`EffectTarget::Target`](crate::rules::effects::EffectTarget::Target)
+    /// is whoever the ability is aimed at.
+    pub effects: Vec<Effect>,
+}
+
+impl Ability {
+    pub fn new(name: &str, effects: Vec<Effect>) -> Self {
+        Self {
+            name: name.to_string(),
+            recharge: None,
+            area: false,
+            effects,
+        }
+    }
+
+    pub fn recharge(mut self, min_roll: u32) -> Self {
+        self.recharge = Some(min_roll);
+        self
+    }
+
+    pub fn area(mut self) -> Self {
+        self.area = true;
+        self
+    }
+
+    /// Whether a d6 roll of `roll` brings the ability back.
+    pub fn recharges_on(&self, roll: u32) -> bool {
+        self.recharge.is_some_and(|min_roll| roll >= min_roll)
+    }
+}
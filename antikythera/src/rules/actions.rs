@@ -11,7 +11,7 @@ use crate::{
     simulation::state::State,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum ActionType {
     Wait,
     UnarmedStrike,
@@ -23,6 +23,31 @@ pub enum ActionType {
     Dodge,
     Help,
     Hide,
+    Grapple,
+    Shove,
+    EscapeGrapple,
+    UseAbility,
+}
+
+impl ActionType {
+    pub fn all() -> Vec<ActionType> {
+        vec![
+            ActionType::Wait,
+            ActionType::UnarmedStrike,
+            ActionType::Attack,
+            ActionType::CastSpell,
+            ActionType::UseItem,
+            ActionType::Dash,
+            ActionType::Disengage,
+            ActionType::Dodge,
+            ActionType::Help,
+            ActionType::Hide,
+            ActionType::Grapple,
+            ActionType::Shove,
+            ActionType::EscapeGrapple,
+            ActionType::UseAbility,
+        ]
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -38,6 +63,10 @@ pub enum Action {
     Dodge,
     Help(HelpAction),
     Hide,
+    Grapple(GrappleAction),
+    Shove(ShoveAction),
+    EscapeGrapple,
+    UseAbility(UseAbilityAction),
     // todo:
     // Ready(ReadyAction),
     // Search(SearchAction),
@@ -57,6 +86,23 @@ impl Action {
             Action::Dodge => ActionType::Dodge,
             Action::Help(_) => ActionType::Help,
             Action::Hide => ActionType::Hide,
+            Action::Grapple(_) => ActionType::Grapple,
+            Action::Shove(_) => ActionType::Shove,
+            Action::EscapeGrapple => ActionType::EscapeGrapple,
+            Action::UseAbility(_) => ActionType::UseAbility,
+        }
+    }
+
+    /// The one actor this action is aimed at, for actions that have one.
+    pub fn target(&self) -> Option<ActorId> {
+        match self {
+            Action::UnarmedStrike(UnarmedStrikeAction { target, .. })
+            | Action::Attack(AttackAction { target, .. })
+            | Action::Help(HelpAction { target, .. })
+            | Action::Grapple(GrappleAction { target })
+            | Action::Shove(ShoveAction { target })
+            | Action::UseAbility(UseAbilityAction { target, .. }) => Some(*target),
+            _ => None,
         }
     }
 
@@ -74,6 +120,9 @@ impl Action {
                 action.target.pretty_print(f, state)?;
                 write!(f, " with their ")?;
                 action.weapon_used.pretty_print(f, state)?;
+                if action.power_attack {
+                    write!(f, " (power attack)")?;
+                }
                 Ok(())
             }
             Action::CastSpell(action) => {
@@ -98,11 +147,32 @@ impl Action {
             Action::Disengage => write!(f, "disengages"),
             Action::Dodge => write!(f, "Dodge"),
             Action::Help(action) => {
-                write!(f, "helps target ")?;
-                action.target.pretty_print(f, state)?;
-                Ok(())
+                write!(f, "helps ")?;
+                action.ally.pretty_print(f, state)?;
+                write!(f, " attack ")?;
+                action.target.pretty_print(f, state)
             }
             Action::Hide => write!(f, "hides"),
+            Action::Grapple(action) => {
+                write!(f, "tries to grapple ")?;
+                action.target.pretty_print(f, state)
+            }
+            Action::Shove(action) => {
+                write!(f, "tries to shove ")?;
+                action.target.pretty_print(f, state)?;
+                write!(f, " prone")
+            }
+            Action::EscapeGrapple => write!(f, "tries to escape a grapple"),
+            Action::UseAbility(action) => {
+                let name = state
+                    .get_actor(action.actor)
+                    .and_then(|actor| actor.abilities.get(action.ability));
+                match name {
+                    Some(ability) => write!(f, "uses {} on ", ability.name)?,
+                    None => write!(f, "uses <Ability {}> on ", action.ability)?,
+                }
+                action.target.pretty_print(f, state)
+            }
         }
     }
 }
@@ -113,11 +183,20 @@ pub struct UnarmedStrikeAction {
     pub attack_roll_settings: RollSettings,
 }
 
+/// Attack roll penalty for a power attack (Great Weapon Master / Sharpshooter).
+pub const POWER_ATTACK_HIT_PENALTY: i32 = 5;
+/// Damage bonus for a power attack (Great Weapon Master / Sharpshooter).
+pub const POWER_ATTACK_DAMAGE_BONUS: i32 = 10;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AttackAction {
     pub weapon_used: ItemId,
     pub target: ActorId,
     pub attack_roll_settings: RollSettings,
+    /// Take -5 to hit for +10 damage on a hit, if the attacker
+    /// [can](crate::rules::actor::Actor::can_power_attack) with this weapon.
+    #[serde(default)]
+    pub power_attack: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -136,6 +215,29 @@ pub struct UseItemAction {
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HelpAction {
+    /// The ally who gains advantage.
+    pub ally: ActorId,
+    /// The enemy the ally's next attack is made against.
+    pub target: ActorId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrappleAction {
+    pub target: ActorId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShoveAction {
+    pub target: ActorId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UseAbilityAction {
+    /// The actor using the ability, whose [`Actor::abilities`](crate::rules::actor::Actor::abilities) it's looked up in.
+    pub actor: ActorId,
+    /// Index into the actor's abilities.
+    pub ability: usize,
+    /// Who the ability is aimed at. Area abilities hit every enemy regardless.
     pub target: ActorId,
 }
 
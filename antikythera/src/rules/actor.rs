@@ -1,20 +1,34 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use derive_more::{From, Into};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     prelude::{ItemId, Policy},
     rules::{
+        abilities::Ability,
         actions::ActionEconomy,
-        death::DeathSaves,
+        armor_class::AcFormula,
+        conditions::Conditions,
+        damage::{DamageResponse, DamageType, Resistances},
+        death::{DeathBehavior, DeathSaves},
         dice::{RollPlan, RollSettings},
+        effects::RandomEffectTable,
+        features::Feature,
+        homebrew::HomebrewEffects,
         items::{
             EquippedItems, Inventory, Weapon, WeaponProficiencies, WeaponProficiency, WeaponType,
         },
-        saves::{SavingThrow, SavingThrowProficiencies},
+        metadata::Metadata,
+        rerolls::RerollResource,
+        saves::{SaveModifier, SaveModifierSource, SavingThrow, SavingThrowProficiencies},
+        scaling::LevelScaling,
+        size::Size,
         skills::{Skill, SkillProficiencies, SkillProficiency},
-        stats::{Stat, Stats},
+        stats::{PointBuy, Stat, StatRollMethod, Stats},
+        vision::Vision,
     },
-    simulation::state::State,
+    simulation::{phases::Phase, roller::Roller, state::State},
 };
 
 #[derive(
@@ -43,22 +57,43 @@ impl ActorBuilder {
                 id: ActorId(0), // Placeholder, will be set when added to SimulationState
                 npc: false,
                 group: 0,
+                tags: BTreeSet::new(),
                 name: name.to_string(),
                 level: 1,
+                size: Size::default(),
+                vision: Vision::default(),
+                mount: None,
                 armor_class: 10,
+                ac_formula: AcFormula::default(),
                 max_health: 10,
                 health: 10,
+                overkill: 0,
                 stats: Stats::default(),
                 movement_speed: 30,
                 skill_proficiencies: SkillProficiencies::default(),
                 saving_throw_proficiencies: SavingThrowProficiencies::default(),
                 death_saves: DeathSaves::default(),
+                death_behavior: DeathBehavior::default(),
                 initiative: None,
+                conditions: Conditions::default(),
                 action_economy: ActionEconomy::default(),
                 equipped_items: EquippedItems::default(),
                 inventory: Inventory::default(),
                 weapon_proficiencies: WeaponProficiencies::default(),
+                features: BTreeSet::new(),
+                features_used: BTreeSet::new(),
+                rerolls: BTreeMap::new(),
                 policy: Policy::default(),
+                metadata: Metadata::default(),
+                scaling: None,
+                random_effects: Vec::new(),
+                abilities: Vec::new(),
+                spent_abilities: BTreeSet::new(),
+                homebrew: HomebrewEffects::default(),
+                resistances: Resistances::default(),
+                phases: Vec::new(),
+                phase: 0,
+                formulas: BTreeMap::new(),
             },
         }
     }
@@ -73,11 +108,31 @@ impl ActorBuilder {
         self
     }
 
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.actor.tags.insert(tag.to_string());
+        self
+    }
+
     pub fn level(mut self, level: u32) -> Self {
         self.actor.level = level;
         self
     }
 
+    pub fn size(mut self, size: Size) -> Self {
+        self.actor.size = size;
+        self
+    }
+
+    pub fn darkvision(mut self, feet: u32) -> Self {
+        self.actor.vision.darkvision = feet;
+        self
+    }
+
+    pub fn blindsight(mut self, feet: u32) -> Self {
+        self.actor.vision.blindsight = feet;
+        self
+    }
+
     pub fn max_health(mut self, max_health: i32) -> Self {
         self.actor.max_health = max_health;
         self.actor.health = max_health; // Start at full health
@@ -94,6 +149,29 @@ impl ActorBuilder {
         self
     }
 
+    /// Assigns the standard array, highest scores first to the stats in `priority`. See
+    /// [`Stats::standard_array`].
+    pub fn standard_array(mut self, priority: &[Stat]) -> Self {
+        self.actor.stats = Stats::standard_array(priority);
+        self
+    }
+
+    pub fn point_buy(mut self, point_buy: &PointBuy) -> Self {
+        self.actor.stats = point_buy.stats().clone();
+        self
+    }
+
+    /// Rolls up every stat, in order. Seeding the roller makes a randomized party reproducible.
+    pub fn rolled_stats(mut self, roller: &mut Roller, method: StatRollMethod) -> Self {
+        self.actor.stats = Stats::rolled(roller, method);
+        self
+    }
+
+    pub fn ac_formula(mut self, formula: AcFormula) -> Self {
+        self.actor.ac_formula = formula;
+        self
+    }
+
     pub fn movement_speed(mut self, speed: u32) -> Self {
         self.actor.movement_speed = speed;
         self
@@ -119,6 +197,13 @@ impl ActorBuilder {
         self
     }
 
+    pub fn saving_throw_expertise(mut self, save: SavingThrow) -> Self {
+        self.actor
+            .saving_throw_proficiencies
+            .set_expertise(save, true);
+        self
+    }
+
     pub fn weapon_proficiencies(mut self, proficiencies: WeaponProficiencies) -> Self {
         self.actor.weapon_proficiencies = proficiencies;
         self
@@ -135,6 +220,63 @@ impl ActorBuilder {
         self
     }
 
+    pub fn feature(mut self, feature: Feature) -> Self {
+        self.actor.features.insert(feature);
+        self
+    }
+
+    pub fn rerolls(mut self, resource: RerollResource, count: u32) -> Self {
+        self.actor.rerolls.insert(resource, count);
+        self
+    }
+
+    pub fn metadata(mut self, metadata: Metadata) -> Self {
+        self.actor.metadata = metadata;
+        self
+    }
+
+    pub fn death_behavior(mut self, death_behavior: DeathBehavior) -> Self {
+        self.actor.death_behavior = death_behavior;
+        self
+    }
+
+    pub fn scaling(mut self, scaling: LevelScaling) -> Self {
+        self.actor.scaling = Some(scaling);
+        self
+    }
+
+    pub fn random_effects(mut self, table: RandomEffectTable) -> Self {
+        self.actor.random_effects.push(table);
+        self
+    }
+
+    pub fn ability(mut self, ability: Ability) -> Self {
+        self.actor.abilities.push(ability);
+        self
+    }
+
+    pub fn homebrew(mut self, homebrew: HomebrewEffects) -> Self {
+        self.actor.homebrew = homebrew;
+        self
+    }
+
+    pub fn resistance(mut self, damage_type: DamageType, response: DamageResponse) -> Self {
+        self.actor.resistances.set(damage_type, response);
+        self
+    }
+
+    pub fn phase(mut self, phase: Phase) -> Self {
+        self.actor.phases.push(phase);
+        self
+    }
+
+    pub fn formula(mut self, name: &str, formula: &str) -> Self {
+        self.actor
+            .formulas
+            .insert(name.to_string(), formula.to_string());
+        self
+    }
+
     pub fn build(self) -> Actor {
         self.actor
     }
@@ -145,25 +287,143 @@ pub struct Actor {
     pub id: ActorId,
     pub npc: bool,
     pub group: u32,
+    /// Freeform labels such as "healer" or "boss", used by policies and queries to pick out actors.
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
     pub name: String,
     pub level: u32,
+    #[serde(default)]
+    pub size: Size,
+    #[serde(default)]
+    pub vision: Vision,
+    /// The actor this actor is riding, if any. Mounts act directly after their rider in initiative.
+    #[serde(default)]
+    pub mount: Option<ActorId>,
     pub armor_class: u32,
+    /// How AC is worked out. Anything but [`AcFormula::Flat`] ignores `armor_class` unless the
+    /// formula can't be used with what the actor is wearing.
+    #[serde(default)]
+    pub ac_formula: AcFormula,
     pub max_health: i32,
+    /// Current HP, between 0 and `max_health`. See [`Actor::modify_health`].
     pub health: i32,
+    /// Damage taken past 0 HP since the actor last dropped to 0. Reaching `max_health` is
+    /// massive damage, which kills an actor that would otherwise make death saves.
+    #[serde(default)]
+    pub overkill: i32,
     pub stats: Stats,
     pub movement_speed: u32,
     pub skill_proficiencies: SkillProficiencies,
     pub saving_throw_proficiencies: SavingThrowProficiencies,
     pub death_saves: DeathSaves,
+    #[serde(default)]
+    pub death_behavior: DeathBehavior,
     pub initiative: Option<i32>,
+    #[serde(default)]
+    pub conditions: Conditions,
     pub action_economy: ActionEconomy,
     pub equipped_items: EquippedItems,
     pub inventory: Inventory,
     pub weapon_proficiencies: WeaponProficiencies,
+    #[serde(default)]
+    pub features: BTreeSet<Feature>,
+    /// Limited-use features that have been spent this combat.
+    #[serde(default)]
+    pub features_used: BTreeSet<Feature>,
+    /// Rerolls left, by resource. See [`RerollResource`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rerolls: BTreeMap<RerollResource, u32>,
+    #[serde(default)]
     pub policy: Policy,
+    #[serde(default)]
+    pub metadata: Metadata,
+    /// How the actor changes with level, used by [`Actor::scaled_to_level`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scaling: Option<LevelScaling>,
+    /// Tables of random effects rolled on when their triggers fire, e.g. wild magic surges.
+    #[serde(default)]
+    pub random_effects: Vec<RandomEffectTable>,
+    /// Special actions like breath weapons, used through [`Action::UseAbility`](crate::rules::actions::Action::UseAbility).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub abilities: Vec<Ability>,
+    /// Indices into `abilities` that have been used and are waiting to recharge.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub spent_abilities: BTreeSet<usize>,
+    /// Homebrew abilities written in the scenario file's effect language.
+    #[serde(default, skip_serializing_if = "HomebrewEffects::is_empty")]
+    pub homebrew: HomebrewEffects,
+    #[serde(default, skip_serializing_if = "Resistances::is_empty")]
+    pub resistances: Resistances,
+    /// Boss phases, entered in order as the actor loses HP. See [`Phase`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<Phase>,
+    /// How many of `phases` the actor has entered this combat.
+    #[serde(default)]
+    pub phase: usize,
+    /// Formulas for derived values like save DCs, applied when the actor is loaded. See
+    /// [`Actor::apply_formulas`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub formulas: BTreeMap<String, String>,
 }
 
 impl Actor {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn has_feature(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// Whether the actor has a feature and hasn't spent it, if it's limited-use.
+    pub fn can_use_feature(&self, feature: Feature) -> bool {
+        self.has_feature(feature)
+            && !(feature.once_per_combat() && self.features_used.contains(&feature))
+    }
+
+    /// Whether the ability at `index` exists, isn't waiting to recharge, and isn't held back for a
+    /// later phase.
+    pub fn ability_ready(&self, index: usize) -> bool {
+        index < self.abilities.len()
+            && !self.spent_abilities.contains(&index)
+            && !self.ability_locked(index)
+    }
+
+    /// Indices of the abilities the actor could use right now.
+    pub fn ready_abilities(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.abilities.len()).filter(|&index| self.ability_ready(index))
+    }
+
+    /// The reroll resource the actor would spend next, if it has any left.
+    pub fn next_reroll(&self) -> Option<RerollResource> {
+        self.rerolls
+            .iter()
+            .find(|(_, count)| **count > 0)
+            .map(|(resource, _)| *resource)
+    }
+
+    /// AC against attacks, including bonuses from features and magic armor. See
+    /// [`Actor::armor_class_breakdown`] for where it comes from.
+    pub fn effective_armor_class(&self, state: &State) -> i32 {
+        self.armor_class_breakdown(state).total()
+    }
+
+    /// Applies damage (a negative `delta`) or healing (a positive one), keeping HP between 0 and
+    /// max HP. Healing past max HP is lost. Damage past 0 HP goes to [`Actor::overkill`] instead,
+    /// which healing clears once the actor is back above 0.
+    pub fn modify_health(&mut self, delta: i32) {
+        let health = self.health.saturating_add(delta);
+        if health < 0 {
+            self.overkill = self.overkill.saturating_add(health.saturating_neg());
+            self.health = 0;
+        } else {
+            self.health = health.min(self.max_health.max(self.health));
+            if self.health > 0 {
+                self.overkill = 0;
+            }
+        }
+    }
+
     pub fn is_alive(&self) -> bool {
         self.health > 0
     }
@@ -173,12 +433,19 @@ impl Actor {
     }
 
     pub fn is_dead(&self) -> bool {
-        self.health <= -self.max_health || self.death_saves.is_dead()
+        match self.death_behavior {
+            DeathBehavior::InstantDeath => self.health <= 0,
+            DeathBehavior::DeathSaves => {
+                self.overkill >= self.max_health || self.death_saves.is_dead()
+            }
+            DeathBehavior::NoDeath => false,
+        }
     }
 
+    /// Level 0, which scenario files can give, counts as level 1.
     pub fn proficiency_bonus(&self) -> u32 {
         match self.level {
-            1..=4 => 2,
+            0..=4 => 2,
             5..=8 => 3,
             9..=12 => 4,
             13..=16 => 5,
@@ -204,16 +471,49 @@ impl Actor {
         stat_mod + proficiency_bonus as i32
     }
 
+    /// Returns whichever of the given skills this actor has the highest modifier in.
+    pub fn best_skill(&self, skills: &[Skill]) -> Skill {
+        skills
+            .iter()
+            .copied()
+            .max_by_key(|skill| self.skill_modifier(*skill))
+            .expect("at least one skill must be given")
+    }
+
     pub fn stat_modifier(&self, stat: Stat) -> i32 {
         self.stats.modifier(stat)
     }
 
+    /// The actor's own saving throw modifier, from its ability score and proficiency. Items and
+    /// auras need the rest of the state; see [`State::saving_throw_modifier`].
     pub fn saving_throw_modifier(&self, save: SavingThrow) -> i32 {
-        let associated_stat = save.to_stat();
-        let stat_mod = self.stats.modifier(associated_stat);
-        let is_proficient = self.saving_throw_proficiencies.get(save);
-        let proficiency_bonus = if is_proficient { self.level } else { 0 };
-        stat_mod + proficiency_bonus as i32
+        self.saving_throw_modifiers(save)
+            .iter()
+            .map(|modifier| modifier.value)
+            .sum()
+    }
+
+    /// The terms of [`Actor::saving_throw_modifier`]. The ability modifier is always included;
+    /// proficiency only if the actor has it.
+    pub fn saving_throw_modifiers(&self, save: SavingThrow) -> Vec<SaveModifier> {
+        let stat = save.to_stat();
+        let mut modifiers = vec![SaveModifier {
+            source: SaveModifierSource::Ability(stat),
+            value: self.stats.modifier(stat),
+        }];
+        let proficiencies = &self.saving_throw_proficiencies;
+        if proficiencies.get(save) {
+            let multiplier = if proficiencies.has_expertise(save) {
+                2
+            } else {
+                1
+            };
+            modifiers.push(SaveModifier {
+                source: SaveModifierSource::Proficiency,
+                value: (self.proficiency_bonus() * multiplier) as i32,
+            });
+        }
+        modifiers
     }
 
     pub fn plan_unarmed_strike_roll(&self, roll_settings: RollSettings) -> RollPlan {
@@ -251,9 +551,12 @@ impl Actor {
         weapon: &Weapon,
         roll_settings: RollSettings,
     ) -> anyhow::Result<RollPlan> {
-        let mut attack_modifier = weapon.attack_bonus;
+        let mut attack_modifier = weapon.attack_bonus + weapon.enchantment_bonus;
         let prof = self.weapon_proficiencies.get(weapon.weapon_type);
         attack_modifier += self.proficiency_bonus_with(prof.into()) as i32;
+        if weapon.is_ranged() && self.has_feature(Feature::Archery) {
+            attack_modifier += 2;
+        }
 
         Ok(RollPlan {
             num_dice: 1,
@@ -263,6 +566,38 @@ impl Actor {
         })
     }
 
+    /// Whether the actor can make a power attack with `weapon`: Great Weapon Master with a
+    /// heavy melee weapon, or Sharpshooter with a ranged weapon.
+    pub fn can_power_attack(&self, weapon: &Weapon) -> bool {
+        if weapon.is_melee() {
+            self.has_feature(Feature::GreatWeaponMaster) && weapon.weapon_type.is_heavy()
+        } else {
+            self.has_feature(Feature::Sharpshooter) && weapon.weapon_type.is_ranged_weapon()
+        }
+    }
+
+    pub fn plan_weapon_damage(&self, weapon: &Weapon, critical: bool) -> RollPlan {
+        let mut damage = if critical {
+            weapon.critical_damage.unwrap_or(weapon.damage)
+        } else {
+            weapon.damage
+        };
+        damage.modifier += weapon.enchantment_bonus;
+
+        if weapon.is_melee() {
+            if self.has_feature(Feature::Dueling) && !weapon.weapon_type.is_two_handed() {
+                damage.modifier += 2;
+            }
+            if self.has_feature(Feature::GreatWeaponFighting)
+                && (weapon.weapon_type.is_two_handed() || weapon.weapon_type.is_versatile())
+            {
+                damage.settings.reroll_once_below = Some(3);
+            }
+        }
+
+        damage
+    }
+
     pub fn plan_skill_check(&self, skill: Skill, roll_settings: RollSettings) -> RollPlan {
         let modifier = self.skill_modifier(skill);
         RollPlan {
@@ -273,6 +608,8 @@ impl Actor {
         }
     }
 
+    /// A saving throw with only the actor's own modifier. The simulation uses
+    /// [`State::plan_saving_throw`], which adds item and aura bonuses.
     pub fn plan_saving_throw(&self, save: SavingThrow, roll_settings: RollSettings) -> RollPlan {
         let modifier = self.saving_throw_modifier(save);
         RollPlan {
@@ -306,28 +643,49 @@ impl Actor {
         self.inventory.add_item(item, quantity);
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-utils"))]
     pub fn test_actor(id: u32, name: &str) -> Self {
         Self {
             id: ActorId(id),
             npc: false,
             group: 0,
+            tags: BTreeSet::new(),
             name: name.to_string(),
             level: 1,
+            size: Size::default(),
+            vision: Vision::default(),
+            mount: None,
             armor_class: 10,
+            ac_formula: AcFormula::default(),
             max_health: 10,
             health: 10,
+            overkill: 0,
             stats: Stats::default(),
             movement_speed: 30,
             skill_proficiencies: SkillProficiencies::default(),
             saving_throw_proficiencies: SavingThrowProficiencies::default(),
             death_saves: DeathSaves::default(),
+            death_behavior: DeathBehavior::default(),
             initiative: None,
+            conditions: Conditions::default(),
             action_economy: ActionEconomy::default(),
             equipped_items: EquippedItems::default(),
             inventory: Inventory::default(),
             weapon_proficiencies: WeaponProficiencies::default(),
+            features: BTreeSet::new(),
+            features_used: BTreeSet::new(),
+            rerolls: BTreeMap::new(),
             policy: Policy::default(),
+            metadata: Metadata::default(),
+            scaling: None,
+            random_effects: Vec::new(),
+            abilities: Vec::new(),
+            spent_abilities: BTreeSet::new(),
+            homebrew: HomebrewEffects::default(),
+            resistances: Resistances::default(),
+            phases: Vec::new(),
+            phase: 0,
+            formulas: BTreeMap::new(),
         }
     }
 }
@@ -335,6 +693,7 @@ impl Actor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rules::items::{Armor, ItemInner, WeaponBuilder};
 
     #[test]
     fn test_actor_is_alive() {
@@ -342,4 +701,190 @@ mod tests {
         assert!(actor.is_alive());
         assert!(!actor.is_dead());
     }
+
+    #[test]
+    fn test_modify_health() {
+        let mut actor = ActorBuilder::new("Test Actor").max_health(10).build();
+
+        actor.modify_health(-4);
+        actor.modify_health(100);
+        assert_eq!(actor.health, 10);
+
+        actor.modify_health(-13);
+        assert_eq!((actor.health, actor.overkill), (0, 3));
+        assert!(actor.is_unconscious());
+        actor.modify_health(-7);
+        assert_eq!((actor.health, actor.overkill), (0, 10));
+        assert!(actor.is_dead());
+
+        // healing from 0 starts fresh
+        actor.overkill = 5;
+        actor.modify_health(2);
+        assert_eq!((actor.health, actor.overkill), (2, 0));
+
+        actor.modify_health(i32::MIN);
+        assert_eq!(actor.health, 0);
+        assert!(actor.is_dead());
+    }
+
+    #[test]
+    fn test_proficiency_bonus() {
+        let at_level = |level| ActorBuilder::new("Test Actor").level(level).build();
+        // level 0 comes up in loaded files and used to underflow
+        assert_eq!(at_level(0).proficiency_bonus(), 2);
+        assert_eq!(at_level(5).proficiency_bonus(), 3);
+        assert_eq!(at_level(20).proficiency_bonus(), 6);
+        assert_eq!(at_level(24).proficiency_bonus(), 7);
+    }
+
+    #[test]
+    fn test_death_behavior() {
+        let at_health = |death_behavior, health| {
+            let mut actor = ActorBuilder::new("Test Actor")
+                .death_behavior(death_behavior)
+                .build();
+            actor.health = health;
+            actor
+        };
+
+        let minion = at_health(DeathBehavior::InstantDeath, 0);
+        assert!(minion.is_dead());
+        assert!(!minion.is_unconscious());
+
+        let hero = at_health(DeathBehavior::DeathSaves, 0);
+        assert!(hero.is_unconscious());
+        let mut hero = at_health(DeathBehavior::DeathSaves, 1);
+        hero.modify_health(-11);
+        assert!(hero.is_dead());
+
+        let mut boss = at_health(DeathBehavior::NoDeath, 1);
+        boss.modify_health(-100);
+        assert!(!boss.is_dead());
+        assert!(boss.is_unconscious());
+        assert!(!boss.is_alive());
+    }
+
+    fn bow() -> Weapon {
+        WeaponBuilder::new(WeaponType::Longbow)
+            .damage("1d8+2")
+            .range(150)
+            .build()
+    }
+
+    fn greatsword() -> Weapon {
+        WeaponBuilder::new(WeaponType::Greatsword)
+            .damage("2d6+3")
+            .build()
+    }
+
+    #[test]
+    fn test_archery() -> anyhow::Result<()> {
+        let plain = ActorBuilder::new("Plain").build();
+        let archer = ActorBuilder::new("Archer")
+            .feature(Feature::Archery)
+            .build();
+
+        let bow = bow();
+        let plain_roll = plain.plan_attack_roll(&bow, RollSettings::default())?;
+        let archer_roll = archer.plan_attack_roll(&bow, RollSettings::default())?;
+        assert_eq!(archer_roll.modifier, plain_roll.modifier + 2);
+
+        // no bonus in melee
+        let sword = Weapon::test_sword();
+        assert_eq!(
+            archer
+                .plan_attack_roll(&sword, RollSettings::default())?
+                .modifier,
+            plain
+                .plan_attack_roll(&sword, RollSettings::default())?
+                .modifier
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dueling() {
+        let duelist = ActorBuilder::new("Duelist")
+            .feature(Feature::Dueling)
+            .build();
+
+        let sword = Weapon::test_sword();
+        assert_eq!(
+            duelist.plan_weapon_damage(&sword, false).modifier,
+            sword.damage.modifier + 2
+        );
+        let greatsword = greatsword();
+        assert_eq!(
+            duelist.plan_weapon_damage(&greatsword, false).modifier,
+            greatsword.damage.modifier
+        );
+        let bow = bow();
+        assert_eq!(
+            duelist.plan_weapon_damage(&bow, false).modifier,
+            bow.damage.modifier
+        );
+    }
+
+    #[test]
+    fn test_great_weapon_fighting() {
+        let fighter = ActorBuilder::new("Fighter")
+            .feature(Feature::GreatWeaponFighting)
+            .build();
+
+        let damage = fighter.plan_weapon_damage(&greatsword(), true);
+        assert_eq!(damage.settings.reroll_once_below, Some(3));
+        // versatile weapons qualify too
+        let damage = fighter.plan_weapon_damage(&Weapon::test_sword(), false);
+        assert_eq!(damage.settings.reroll_once_below, Some(3));
+        let damage = fighter.plan_weapon_damage(&bow(), false);
+        assert_eq!(damage.settings.reroll_once_below, None);
+    }
+
+    #[test]
+    fn test_defense() {
+        let mut state = State::new();
+        let armor = state.add_item("Chain Mail", ItemInner::Armor(Armor::test_armor()));
+        let mut defender = ActorBuilder::new("Defender")
+            .feature(Feature::Defense)
+            .build();
+        assert_eq!(defender.effective_armor_class(&state), 10);
+
+        defender.give_item(armor, 1);
+        defender.equipped_items.equip(armor);
+        assert_eq!(defender.effective_armor_class(&state), 11);
+    }
+
+    #[test]
+    fn test_enchantment_bonus() {
+        let mut state = State::new();
+        let fighter = ActorBuilder::new("Fighter").build();
+        let sword = WeaponBuilder::new(WeaponType::Longsword)
+            .damage("1d8+3")
+            .critical_damage("2d8+3")
+            .enchantment_bonus(2)
+            .build();
+        assert_eq!(
+            fighter
+                .plan_attack_roll(&sword, Default::default())
+                .unwrap()
+                .modifier,
+            2
+        );
+        assert_eq!(fighter.plan_weapon_damage(&sword, false).modifier, 5);
+        assert_eq!(fighter.plan_weapon_damage(&sword, true).modifier, 5);
+
+        let plate = state.add_item(
+            "Plate +1",
+            ItemInner::Armor(Armor::new(8).enchantment_bonus(1)),
+        );
+        let shield = state.add_item(
+            "Shield +2",
+            ItemInner::Armor(Armor::new(2).enchantment_bonus(2)),
+        );
+        let mut knight = ActorBuilder::new("Knight").build();
+        knight.armor_class = 20;
+        knight.equipped_items.equip(plate);
+        knight.equipped_items.equip(shield);
+        assert_eq!(knight.effective_armor_class(&state), 23);
+    }
 }
@@ -0,0 +1,247 @@
+//! How an actor's AC is worked out: either the flat value from its stat block, or one of the
+//! formulas for fighting without armor, like a Barbarian's or Monk's Unarmored Defense.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        actor::Actor,
+        features::Feature,
+        items::{Armor, EquipSlot, Item, ItemInner},
+        stats::Stat,
+    },
+    simulation::state::State,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AcFormula {
+    /// The actor's `armor_class` as written, armor and shield included, like a stat block's AC.
+    #[default]
+    Flat,
+    /// The Barbarian's Unarmored Defense: 10 + Dex + Con without armor. A shield still counts.
+    BarbarianUnarmoredDefense,
+    /// The Monk's Unarmored Defense: 10 + Dex + Wis without armor or a shield.
+    MonkUnarmoredDefense,
+    /// Natural armor: `base` + Dex without armor, like a lizardfolk's 13 + Dex. A shield still
+    /// counts.
+    NaturalArmor { base: u32 },
+}
+
+impl AcFormula {
+    pub fn all() -> Vec<AcFormula> {
+        vec![
+            AcFormula::Flat,
+            AcFormula::BarbarianUnarmoredDefense,
+            AcFormula::MonkUnarmoredDefense,
+            AcFormula::NaturalArmor { base: 13 },
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AcFormula::Flat => "Flat",
+            AcFormula::BarbarianUnarmoredDefense => "Unarmored Defense (Barbarian)",
+            AcFormula::MonkUnarmoredDefense => "Unarmored Defense (Monk)",
+            AcFormula::NaturalArmor { .. } => "Natural Armor",
+        }
+    }
+
+    /// The AC before ability modifiers, or `None` for [`AcFormula::Flat`].
+    fn base(&self) -> Option<u32> {
+        match self {
+            AcFormula::Flat => None,
+            AcFormula::BarbarianUnarmoredDefense | AcFormula::MonkUnarmoredDefense => Some(10),
+            AcFormula::NaturalArmor { base } => Some(*base),
+        }
+    }
+
+    fn stats(&self) -> &'static [Stat] {
+        match self {
+            AcFormula::Flat => &[],
+            AcFormula::BarbarianUnarmoredDefense => &[Stat::Dexterity, Stat::Constitution],
+            AcFormula::MonkUnarmoredDefense => &[Stat::Dexterity, Stat::Wisdom],
+            AcFormula::NaturalArmor { .. } => &[Stat::Dexterity],
+        }
+    }
+
+    fn allows_shield(&self) -> bool {
+        !matches!(self, AcFormula::MonkUnarmoredDefense)
+    }
+}
+
+/// Where an actor's AC comes from, term by term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArmorClassBreakdown {
+    pub formula: AcFormula,
+    /// Why the formula can't be used, e.g. because the actor is wearing armor. When set, `base` is
+    /// the actor's flat `armor_class` instead.
+    pub unmet: Option<String>,
+    pub base: i32,
+    /// Ability modifiers the formula adds.
+    pub modifiers: Vec<(Stat, i32)>,
+    /// A shield's AC, which flat AC already includes but formulas don't.
+    pub shield: i32,
+    /// +1 from the Defense fighting style, while wearing armor.
+    pub defense: i32,
+    /// Enchantment bonuses of everything the actor has equipped.
+    pub enchantment: i32,
+}
+
+impl ArmorClassBreakdown {
+    pub fn total(&self) -> i32 {
+        self.base
+            + self.modifiers.iter().map(|(_, bonus)| bonus).sum::<i32>()
+            + self.shield
+            + self.defense
+            + self.enchantment
+    }
+
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        write!(f, "{} base", self.base)?;
+        for (stat, bonus) in &self.modifiers {
+            write!(f, " {:+} {:?}", bonus, stat)?;
+        }
+        for (bonus, source) in [
+            (self.shield, "shield"),
+            (self.defense, "Defense"),
+            (self.enchantment, "magic"),
+        ] {
+            if bonus != 0 {
+                write!(f, " {:+} {}", bonus, source)?;
+            }
+        }
+        write!(f, " = {}", self.total())
+    }
+}
+
+impl Actor {
+    /// Equipped armor, along with the item itself for naming it.
+    fn equipped_armor<'a>(
+        &'a self,
+        state: &'a State,
+    ) -> impl Iterator<Item = (&'a Item, &'a Armor)> {
+        self.equipped_items
+            .items
+            .iter()
+            .filter_map(|id| state.items.get(id))
+            .filter_map(|item| match &item.inner {
+                ItemInner::Armor(armor) => Some((item, armor)),
+                _ => None,
+            })
+    }
+
+    /// Whether the actor is wearing armor proper, not counting shields or accessories.
+    pub fn is_wearing_armor(&self, state: &State) -> bool {
+        self.equipped_armor(state)
+            .any(|(_, armor)| armor.slot.is_armor())
+    }
+
+    /// Why the actor's [`AcFormula`] can't be used with what it has equipped, if it can't.
+    pub fn ac_formula_unmet(&self, state: &State) -> Option<String> {
+        if self.ac_formula == AcFormula::Flat {
+            return None;
+        }
+        self.equipped_armor(state)
+            .find(|(_, armor)| {
+                armor.slot.is_armor()
+                    || (armor.slot == EquipSlot::Shield && !self.ac_formula.allows_shield())
+            })
+            .map(|(item, _)| {
+                format!(
+                    "{} doesn't apply while wearing {}",
+                    self.ac_formula.name(),
+                    item.name
+                )
+            })
+    }
+
+    /// How the actor's AC against attacks is worked out, including bonuses from features and
+    /// magic armor. See [`Actor::effective_armor_class`] for just the total.
+    pub fn armor_class_breakdown(&self, state: &State) -> ArmorClassBreakdown {
+        let unmet = self.ac_formula_unmet(state);
+        let (base, modifiers, shield) = match self.ac_formula.base() {
+            Some(base) if unmet.is_none() => {
+                let modifiers = self
+                    .ac_formula
+                    .stats()
+                    .iter()
+                    .map(|stat| (*stat, self.stat_modifier(*stat)))
+                    .collect();
+                let shield = self
+                    .equipped_armor(state)
+                    .filter(|(_, armor)| armor.slot == EquipSlot::Shield)
+                    .map(|(_, armor)| armor.ac_bonus as i32)
+                    .sum();
+                (base as i32, modifiers, shield)
+            }
+            _ => (self.armor_class as i32, Vec::new(), 0),
+        };
+        let defense = (self.has_feature(Feature::Defense) && self.is_wearing_armor(state)) as i32;
+        let enchantment = self
+            .equipped_armor(state)
+            .map(|(_, armor)| armor.enchantment_bonus)
+            .sum();
+        ArmorClassBreakdown {
+            formula: self.ac_formula,
+            unmet,
+            base,
+            modifiers,
+            shield,
+            defense,
+            enchantment,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::actor::ActorBuilder;
+
+    #[test]
+    fn test_unarmored_defense() {
+        let mut state = State::new();
+        let shield = state.add_item("Shield", ItemInner::Armor(Armor::shield(2)));
+        let chain_mail = state.add_item("Chain Mail", ItemInner::Armor(Armor::new(6)));
+        let mut barbarian = ActorBuilder::new("Barbarian")
+            .stat(Stat::Dexterity, 14)
+            .stat(Stat::Constitution, 16)
+            .ac_formula(AcFormula::BarbarianUnarmoredDefense)
+            .build();
+        barbarian.armor_class = 16;
+        assert_eq!(barbarian.effective_armor_class(&state), 15);
+
+        barbarian.equipped_items.equip(shield);
+        let breakdown = barbarian.armor_class_breakdown(&state);
+        assert_eq!(breakdown.total(), 17);
+        let mut line = String::new();
+        breakdown.pretty_print(&mut line).unwrap();
+        assert_eq!(line, "10 base +2 Dexterity +3 Constitution +2 shield = 17");
+
+        // armor rules the formula out, so the flat AC applies instead
+        barbarian.equipped_items.equip(chain_mail);
+        let breakdown = barbarian.armor_class_breakdown(&state);
+        assert_eq!(
+            breakdown.unmet.as_deref(),
+            Some("Unarmored Defense (Barbarian) doesn't apply while wearing Chain Mail")
+        );
+        assert_eq!(breakdown.total(), 16);
+
+        // a monk can't use a shield either
+        let mut monk = ActorBuilder::new("Monk")
+            .stat(Stat::Dexterity, 16)
+            .stat(Stat::Wisdom, 14)
+            .ac_formula(AcFormula::MonkUnarmoredDefense)
+            .build();
+        assert_eq!(monk.effective_armor_class(&state), 15);
+        monk.equipped_items.equip(shield);
+        assert!(monk.ac_formula_unmet(&state).is_some());
+        assert_eq!(monk.effective_armor_class(&state), 10);
+
+        let lizardfolk = ActorBuilder::new("Lizardfolk")
+            .stat(Stat::Dexterity, 12)
+            .ac_formula(AcFormula::NaturalArmor { base: 13 })
+            .build();
+        assert_eq!(lizardfolk.effective_armor_class(&state), 14);
+    }
+}
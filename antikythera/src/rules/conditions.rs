@@ -0,0 +1,99 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{rules::actor::ActorId, simulation::state::State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Condition {
+    Grappled {
+        by: ActorId,
+    },
+    Prone,
+    /// Advantage on the next attack roll against `against`, until the helper's next turn starts.
+    Helped {
+        by: ActorId,
+        against: ActorId,
+    },
+}
+
+impl Condition {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Condition::Grappled { .. } => "grappled",
+            Condition::Prone => "prone",
+            Condition::Helped { .. } => "helped",
+        }
+    }
+
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write, state: &State) -> std::fmt::Result {
+        match self {
+            Condition::Grappled { by } => {
+                write!(f, "grappled by ")?;
+                by.pretty_print(f, state)
+            }
+            Condition::Prone => write!(f, "prone"),
+            Condition::Helped { by, against } => {
+                write!(f, "helped by ")?;
+                by.pretty_print(f, state)?;
+                write!(f, " against ")?;
+                against.pretty_print(f, state)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct Conditions {
+    pub conditions: BTreeSet<Condition>,
+}
+
+impl Conditions {
+    pub fn add(&mut self, condition: Condition) {
+        self.conditions.insert(condition);
+    }
+
+    pub fn remove(&mut self, condition: Condition) {
+        self.conditions.remove(&condition);
+    }
+
+    pub fn has(&self, condition: Condition) -> bool {
+        self.conditions.contains(&condition)
+    }
+
+    pub fn is_prone(&self) -> bool {
+        self.has(Condition::Prone)
+    }
+
+    pub fn grappled_by(&self) -> Option<ActorId> {
+        self.conditions
+            .iter()
+            .find_map(|condition| match condition {
+                Condition::Grappled { by } => Some(*by),
+                _ => None,
+            })
+    }
+
+    /// Whether someone is helping this actor attack `target`.
+    pub fn is_helped_against(&self, target: ActorId) -> bool {
+        self.conditions.iter().any(
+            |condition| matches!(condition, Condition::Helped { against, .. } if *against == target),
+        )
+    }
+
+    pub fn is_grappled(&self) -> bool {
+        self.grappled_by().is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Condition> {
+        self.conditions.iter()
+    }
+}
+
+/// The kinds of opposed ability check contests that can be initiated as actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Contest {
+    Grapple,
+    Shove,
+    EscapeGrapple,
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Cover {
+    #[default]
+    None,
+    Half,
+    ThreeQuarters,
+    Total,
+}
+
+impl Cover {
+    pub fn all() -> Vec<Cover> {
+        vec![Cover::None, Cover::Half, Cover::ThreeQuarters, Cover::Total]
+    }
+
+    /// Bonus to AC against attacks. Meaningless for total cover, since such targets can't be attacked.
+    pub fn ac_bonus(&self) -> i32 {
+        match self {
+            Cover::None => 0,
+            Cover::Half => 2,
+            Cover::ThreeQuarters => 5,
+            Cover::Total => 0,
+        }
+    }
+
+    /// Bonus to Dexterity saving throws against effects originating on the other side of the cover.
+    /// The same as [`Cover::ac_bonus`].
+    pub fn dex_save_bonus(&self) -> i32 {
+        self.ac_bonus()
+    }
+
+    pub fn blocks_targeting(&self) -> bool {
+        *self == Cover::Total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cover_bonuses() {
+        assert_eq!(Cover::None.ac_bonus(), 0);
+        assert_eq!(Cover::Half.ac_bonus(), 2);
+        assert_eq!(Cover::ThreeQuarters.ac_bonus(), 5);
+        assert_eq!(Cover::Half.dex_save_bonus(), 2);
+        assert_eq!(Cover::ThreeQuarters.dex_save_bonus(), 5);
+        assert!(Cover::Total.blocks_targeting());
+        assert!(!Cover::ThreeQuarters.blocks_targeting());
+    }
+}
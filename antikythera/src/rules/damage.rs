@@ -1,8 +1,10 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::rules::dice::RollPlan;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum DamageType {
     Bludgeoning,
     Piercing,
@@ -23,3 +25,112 @@ pub struct DamageInstance {
     pub roll: RollPlan,
     pub damage_type: DamageType,
 }
+
+impl DamageType {
+    pub fn all() -> Vec<DamageType> {
+        vec![
+            DamageType::Bludgeoning,
+            DamageType::Piercing,
+            DamageType::Slashing,
+            DamageType::Fire,
+            DamageType::Cold,
+            DamageType::Lightning,
+            DamageType::Acid,
+            DamageType::Poison,
+            DamageType::Psychic,
+            DamageType::Necrotic,
+            DamageType::Radiant,
+            DamageType::Thunder,
+        ]
+    }
+}
+
+/// How an actor takes damage of a given type.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DamageResponse {
+    #[default]
+    Normal,
+    /// Takes half damage, rounded down.
+    Resistant,
+    Immune,
+    /// Takes double damage.
+    Vulnerable,
+}
+
+impl DamageResponse {
+    pub fn all() -> Vec<DamageResponse> {
+        vec![
+            DamageResponse::Normal,
+            DamageResponse::Resistant,
+            DamageResponse::Immune,
+            DamageResponse::Vulnerable,
+        ]
+    }
+
+    pub fn apply(&self, damage: i32) -> i32 {
+        match self {
+            DamageResponse::Normal => damage,
+            DamageResponse::Resistant => damage / 2,
+            DamageResponse::Immune => 0,
+            DamageResponse::Vulnerable => damage * 2,
+        }
+    }
+}
+
+/// An actor's resistances, immunities and vulnerabilities. Types not listed take normal damage.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Resistances {
+    responses: BTreeMap<DamageType, DamageResponse>,
+}
+
+impl Resistances {
+    pub fn get(&self, damage_type: DamageType) -> DamageResponse {
+        self.responses
+            .get(&damage_type)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, damage_type: DamageType, response: DamageResponse) {
+        if response == DamageResponse::Normal {
+            self.responses.remove(&damage_type);
+        } else {
+            self.responses.insert(damage_type, response);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+
+    /// How much of `damage` gets through. Untyped damage always gets through in full.
+    pub fn apply(&self, damage: i32, damage_type: Option<DamageType>) -> i32 {
+        match damage_type {
+            Some(damage_type) => self.get(damage_type).apply(damage),
+            None => damage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resistances() {
+        let mut resistances = Resistances::default();
+        resistances.set(DamageType::Fire, DamageResponse::Resistant);
+        resistances.set(DamageType::Poison, DamageResponse::Immune);
+        resistances.set(DamageType::Radiant, DamageResponse::Vulnerable);
+        assert_eq!(resistances.apply(7, Some(DamageType::Fire)), 3);
+        assert_eq!(resistances.apply(7, Some(DamageType::Poison)), 0);
+        assert_eq!(resistances.apply(7, Some(DamageType::Radiant)), 14);
+        assert_eq!(resistances.apply(7, Some(DamageType::Cold)), 7);
+        assert_eq!(resistances.apply(7, None), 7);
+
+        resistances.set(DamageType::Fire, DamageResponse::Normal);
+        resistances.set(DamageType::Poison, DamageResponse::Normal);
+        resistances.set(DamageType::Radiant, DamageResponse::Normal);
+        assert!(resistances.is_empty());
+    }
+}
@@ -1,5 +1,17 @@
 use serde::{Deserialize, Serialize};
 
+/// What happens to an actor at 0 HP.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeathBehavior {
+    /// Dies on the spot, as most monsters do.
+    InstantDeath,
+    /// Falls unconscious, and dies from massive damage or three failed death saves.
+    #[default]
+    DeathSaves,
+    /// Falls unconscious but never dies.
+    NoDeath,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct DeathSaves {
     pub successes: u8,
@@ -1,7 +1,23 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::simulation::roller::Roller;
 
+/// The most dice a roll can have, so that rolling it stays cheap.
+pub const MAX_ROLL_DICE: u32 = 10_000;
+
+/// The most sides a die can have. Along with [`MAX_ROLL_DICE`] and [`MAX_ROLL_MODIFIER`], this
+/// keeps every total from overflowing.
+pub const MAX_DIE_SIZE: u32 = 100_000;
+
+/// The largest flat modifier a roll can have, small enough that no total overflows.
+pub const MAX_ROLL_MODIFIER: u32 = 1_000_000;
+
+/// The most faces a roll's dice can add up to, e.g. 20d50, for [`RollPlan::distribution`] to
+/// work out. The GUI previews it on every keystroke, so it has to stay quick.
+pub const MAX_DISTRIBUTION_FACES: u64 = 1_000;
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub enum Advantage {
     #[default]
@@ -10,12 +26,35 @@ pub enum Advantage {
     Disadvantage,
 }
 
+impl Advantage {
+    /// Resolves a set of advantage/disadvantage sources: any advantage and any disadvantage cancel out,
+    /// no matter how many of each there are.
+    pub fn from_sources(advantage: bool, disadvantage: bool) -> Self {
+        match (advantage, disadvantage) {
+            (true, false) => Advantage::Advantage,
+            (false, true) => Advantage::Disadvantage,
+            _ => Advantage::Normal,
+        }
+    }
+
+    pub fn is_advantage(&self) -> bool {
+        *self == Advantage::Advantage
+    }
+
+    pub fn is_disadvantage(&self) -> bool {
+        *self == Advantage::Disadvantage
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct RollSettings {
     pub advantage: Advantage,
     pub minimum_die_value: Option<u32>,
     pub maximum_die_value: Option<u32>,
     pub reroll_dice_below: Option<u32>,
+    /// Reroll any die that comes up below this value once, keeping the new roll (e.g. Great Weapon Fighting).
+    #[serde(default)]
+    pub reroll_once_below: Option<u32>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -80,7 +119,68 @@ pub struct RollPlan {
 }
 
 impl RollPlan {
+    /// Checks that the roll can be made: the dice have sides and neither they nor their sides
+    /// are too many, and the settings fit the die. [`RollPlan::roll`] refuses plans that fail
+    /// this.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.modifier.unsigned_abs() <= MAX_ROLL_MODIFIER,
+            "Modifier {} is larger than {}",
+            self.modifier,
+            MAX_ROLL_MODIFIER
+        );
+        anyhow::ensure!(
+            self.num_dice <= MAX_ROLL_DICE,
+            "{} dice is too many, at most {}",
+            self.num_dice,
+            MAX_ROLL_DICE
+        );
+        anyhow::ensure!(
+            self.die_size <= MAX_DIE_SIZE,
+            "A d{} has too many sides, at most {}",
+            self.die_size,
+            MAX_DIE_SIZE
+        );
+        if self.num_dice == 0 {
+            return Ok(());
+        }
+        anyhow::ensure!(self.die_size > 0, "Dice need at least one side");
+        let settings = &self.settings;
+        for (name, value) in [
+            ("Minimum die value", settings.minimum_die_value),
+            ("Maximum die value", settings.maximum_die_value),
+        ] {
+            if let Some(value) = value {
+                anyhow::ensure!(
+                    (1..=self.die_size).contains(&value),
+                    "{} {} is outside 1..={}",
+                    name,
+                    value,
+                    self.die_size
+                );
+            }
+        }
+        if let (Some(min), Some(max)) = (settings.minimum_die_value, settings.maximum_die_value) {
+            anyhow::ensure!(
+                min <= max,
+                "Minimum die value {} is above the maximum {}",
+                min,
+                max
+            );
+        }
+        if let Some(low) = settings.reroll_dice_below {
+            anyhow::ensure!(
+                low <= self.die_size,
+                "Rerolling below {} leaves nothing to roll on a d{}",
+                low,
+                self.die_size
+            );
+        }
+        Ok(())
+    }
+
     pub fn roll(&self, rng: &mut Roller) -> anyhow::Result<RollResult> {
+        self.validate()?;
         match self.settings.advantage {
             Advantage::Normal => self.roll_normal(rng),
             Advantage::Advantage => self.roll_advantage(rng),
@@ -101,7 +201,12 @@ impl RollPlan {
         let mut crit_failure_count = 0;
 
         for _ in 0..self.num_dice {
-            let roll = rng.range(low, self.die_size);
+            let mut roll = rng.die(low, self.die_size);
+            if let Some(threshold) = self.settings.reroll_once_below
+                && roll < threshold
+            {
+                roll = rng.die(low, self.die_size);
+            }
             let clamped_roll = roll.clamp(clamp_min, clamp_max);
             individual_rolls.push(clamped_roll);
             total += clamped_roll as i32;
@@ -168,6 +273,11 @@ impl RollPlan {
         }
     }
 
+    /// The mean total of the roll, ignoring advantage and any die adjustments in the settings.
+    pub fn average(&self) -> f64 {
+        self.num_dice as f64 * (self.die_size as f64 + 1.0) / 2.0 + self.modifier as f64
+    }
+
     pub fn pretty_print(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
         write!(f, "{}d{}", self.num_dice, self.die_size)?;
         if self.modifier > 0 {
@@ -184,9 +294,133 @@ impl RollPlan {
     }
 }
 
+/// The exact probability of every total a [`RollPlan`] can come up with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollDistribution {
+    pub probabilities: BTreeMap<i32, f64>,
+}
+
+impl RollDistribution {
+    pub fn probability(&self, total: i32) -> f64 {
+        self.probabilities.get(&total).copied().unwrap_or(0.0)
+    }
+
+    /// The chance of rolling `total` or higher.
+    pub fn at_least(&self, total: i32) -> f64 {
+        self.probabilities.range(total..).map(|(_, p)| p).sum()
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.probabilities
+            .iter()
+            .map(|(total, p)| *total as f64 * p)
+            .sum()
+    }
+
+    pub fn min(&self) -> i32 {
+        self.probabilities.keys().next().copied().unwrap_or(0)
+    }
+
+    pub fn max(&self) -> i32 {
+        self.probabilities.keys().next_back().copied().unwrap_or(0)
+    }
+
+    fn convolve(&self, other: &Self) -> Self {
+        let mut probabilities = BTreeMap::new();
+        for (a, p) in &self.probabilities {
+            for (b, q) in &other.probabilities {
+                *probabilities.entry(a + b).or_insert(0.0) += p * q;
+            }
+        }
+        Self { probabilities }
+    }
+
+    /// The distribution of the better (or worse) of two independent rolls.
+    fn best_of_two(&self, higher: bool) -> Self {
+        let mut probabilities = BTreeMap::new();
+        let mut below = 0.0;
+        for (total, p) in &self.probabilities {
+            let at_most = below + p;
+            let chance = if higher {
+                at_most * at_most - below * below
+            } else {
+                (1.0 - below).powi(2) - (1.0 - at_most).powi(2)
+            };
+            probabilities.insert(*total, chance);
+            below = at_most;
+        }
+        Self { probabilities }
+    }
+}
+
+impl RollPlan {
+    /// Computes the distribution of totals exactly, honoring every die adjustment in the
+    /// settings. Advantage keeps the higher of two totals and disadvantage the lower, which is
+    /// exact for a single die. Fails for plans that fail [`RollPlan::validate`], and for those
+    /// with more than [`MAX_DISTRIBUTION_FACES`] faces in all.
+    pub fn distribution(&self) -> anyhow::Result<RollDistribution> {
+        self.validate()?;
+        anyhow::ensure!(
+            self.num_dice as u64 * self.die_size as u64 <= MAX_DISTRIBUTION_FACES,
+            "{}d{} has too many faces to work out exactly, at most {} in all",
+            self.num_dice,
+            self.die_size,
+            MAX_DISTRIBUTION_FACES
+        );
+        let mut total = RollDistribution {
+            probabilities: BTreeMap::from([(self.modifier, 1.0)]),
+        };
+        if self.num_dice == 0 {
+            return Ok(total);
+        }
+
+        let low = self
+            .settings
+            .reroll_dice_below
+            .unwrap_or(1)
+            .clamp(1, self.die_size);
+        let face = 1.0 / (self.die_size - low + 1) as f64;
+        let reroll_chance = self
+            .settings
+            .reroll_once_below
+            .map_or(0.0, |threshold| threshold.saturating_sub(low) as f64 * face);
+        let clamp_min = self.settings.minimum_die_value.unwrap_or(1);
+        let clamp_max = self.settings.maximum_die_value.unwrap_or(self.die_size);
+        let mut die = RollDistribution {
+            probabilities: BTreeMap::new(),
+        };
+        for value in low..=self.die_size {
+            let rerolled = self
+                .settings
+                .reroll_once_below
+                .is_some_and(|threshold| value < threshold);
+            let kept = if rerolled { 0.0 } else { face };
+            *die.probabilities
+                .entry(value.clamp(clamp_min, clamp_max) as i32)
+                .or_insert(0.0) += kept + reroll_chance * face;
+        }
+
+        for _ in 0..self.num_dice {
+            total = total.convolve(&die);
+        }
+        Ok(match self.settings.advantage {
+            Advantage::Normal => total,
+            Advantage::Advantage => total.best_of_two(true),
+            Advantage::Disadvantage => total.best_of_two(false),
+        })
+    }
+}
+
+/// For roll literals in code, like `"1d8+3".into()`. Anything typed in or loaded should go
+/// through [`parse_roll`](crate::roll_parser::parse_roll) instead.
+///
+/// # Panics
+///
+/// If `value` isn't a roll [`RollPlan::validate`] accepts.
 impl From<&str> for RollPlan {
     fn from(value: &str) -> Self {
-        crate::roll_parser::parse_roll(value).unwrap()
+        crate::roll_parser::parse_roll(value)
+            .unwrap_or_else(|e| panic!("Invalid roll literal {:?}: {}", value, e))
     }
 }
 
@@ -205,6 +439,7 @@ mod tests {
                 minimum_die_value: None,
                 maximum_die_value: None,
                 reroll_dice_below: None,
+                reroll_once_below: None,
             },
         };
         let mut rng = Roller::test_rng();
@@ -225,6 +460,7 @@ mod tests {
                 minimum_die_value: None,
                 maximum_die_value: None,
                 reroll_dice_below: Some(3),
+                reroll_once_below: None,
             },
         };
         let mut rng = Roller::test_rng();
@@ -234,6 +470,81 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_roll_invalid() {
+        let mut rng = Roller::test_rng();
+        let d6 = RollPlan::from("1d6");
+        for settings in [
+            RollSettings {
+                reroll_dice_below: Some(7),
+                ..Default::default()
+            },
+            RollSettings {
+                minimum_die_value: Some(5),
+                maximum_die_value: Some(2),
+                ..Default::default()
+            },
+        ] {
+            let plan = RollPlan { settings, ..d6 };
+            assert!(plan.roll(&mut rng).is_err(), "{:?}", plan);
+        }
+        let no_sides = RollPlan { die_size: 0, ..d6 };
+        assert!(no_sides.roll(&mut rng).is_err());
+
+        // plans the fuzzer found that used to hang or overflow, from files that skip the parser
+        for (num_dice, die_size, modifier) in [
+            (0, 200_000_000, 0),
+            (u32::MAX, u32::MAX, 0),
+            (MAX_ROLL_DICE + 1, 1, 0),
+            (1, 6, i32::MIN),
+        ] {
+            let plan = RollPlan {
+                num_dice,
+                die_size,
+                modifier,
+                ..d6
+            };
+            assert!(plan.validate().is_err(), "{:?}", plan);
+            assert!(plan.roll(&mut rng).is_err(), "{:?}", plan);
+            assert!(plan.distribution().is_err(), "{:?}", plan);
+        }
+
+        // big rolls are fine to make, just too big to work out exactly
+        for plan in [
+            RollPlan::from("100d100"),
+            RollPlan::from("10000d100000+1000000"),
+        ] {
+            let result = plan.roll(&mut rng).unwrap();
+            assert!(result.total >= plan.num_dice as i32 + plan.modifier);
+            assert!(plan.distribution().is_err(), "{:?}", plan);
+        }
+        assert!(RollPlan::from("20d50").distribution().is_ok());
+    }
+
+    #[test]
+    fn test_roll_reroll_once_below() {
+        let roll = RollPlan {
+            num_dice: 1,
+            die_size: 6,
+            modifier: 0,
+            settings: RollSettings {
+                reroll_once_below: Some(3),
+                ..Default::default()
+            },
+        };
+        let mut rng = Roller::test_rng();
+        let mut low_rolls = 0;
+        for _ in 0..60000 {
+            let result = roll.roll(&mut rng).unwrap();
+            assert!(result.total >= 1 && result.total <= 6);
+            if result.total < 3 {
+                low_rolls += 1;
+            }
+        }
+        // a 1 or 2 is only kept when both rolls are low: (2/6)^2 = 1/9 of the time
+        assert!((5500..7500).contains(&low_rolls), "{}", low_rolls);
+    }
+
     #[test]
     fn test_roll_min_max() {
         let roll = RollPlan {
@@ -245,6 +556,7 @@ mod tests {
                 minimum_die_value: Some(3),
                 maximum_die_value: Some(5),
                 reroll_dice_below: None,
+                reroll_once_below: None,
             },
         };
         let mut rng = Roller::test_rng();
@@ -253,4 +565,53 @@ mod tests {
             assert!(result.total >= 3 && result.total <= 5);
         }
     }
+
+    #[test]
+    fn test_roll_distribution() -> anyhow::Result<()> {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        let two_d6 = RollPlan::from("2d6+1").distribution()?;
+        assert_eq!((two_d6.min(), two_d6.max()), (3, 13));
+        assert!(close(two_d6.mean(), 8.0));
+        assert!(close(two_d6.probability(8), 6.0 / 36.0));
+        assert!(close(two_d6.probabilities.values().sum(), 1.0));
+
+        let advantage = RollPlan {
+            settings: RollSettings {
+                advantage: Advantage::Advantage,
+                ..Default::default()
+            },
+            ..RollPlan::from("1d20")
+        };
+        let advantage = advantage.distribution()?;
+        assert!(close(advantage.mean(), 13.825));
+        assert!(close(advantage.at_least(20), 39.0 / 400.0));
+
+        let great_weapon = RollPlan {
+            settings: RollSettings {
+                reroll_once_below: Some(3),
+                minimum_die_value: Some(2),
+                ..Default::default()
+            },
+            ..RollPlan::from("1d6")
+        };
+        let distribution = great_weapon.distribution()?;
+        assert_eq!(distribution.min(), 2);
+        // 1s and 2s both read as 2, and only stick when both rolls are low
+        assert!(close(distribution.probability(2), 1.0 / 9.0));
+        assert!(close(distribution.probability(6), 1.0 / 6.0 + 1.0 / 18.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_advantage_from_sources() {
+        assert_eq!(Advantage::from_sources(false, false), Advantage::Normal);
+        assert_eq!(Advantage::from_sources(true, false), Advantage::Advantage);
+        assert_eq!(
+            Advantage::from_sources(false, true),
+            Advantage::Disadvantage
+        );
+        assert_eq!(Advantage::from_sources(true, true), Advantage::Normal);
+    }
 }
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        conditions::Condition, damage::DamageType, dice::RollPlan, saves::SavingThrow, stats::Stat,
+    },
+    simulation::roller::Roller,
+};
+
+/// Who an [`Effect`] lands on, relative to whatever triggered it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EffectTarget {
+    /// The actor the effect belongs to.
+    #[default]
+    User,
+    /// The other actor involved in the triggering event, such as the target of an attack.
+    /// Effects aimed at a missing target do nothing.
+    Target,
+}
+
+/// A single change to the state, resolved into transitions when it fires.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Effect {
+    Damage {
+        target: EffectTarget,
+        damage: RollPlan,
+        #[serde(default)]
+        damage_type: Option<DamageType>,
+    },
+    Heal {
+        target: EffectTarget,
+        amount: RollPlan,
+    },
+    ApplyCondition {
+        target: EffectTarget,
+        condition: Condition,
+    },
+    /// Changes a stat for good, or for `duration` rounds if given.
+    ModifyStat {
+        target: EffectTarget,
+        stat: Stat,
+        delta: i32,
+        #[serde(default)]
+        duration: Option<u32>,
+    },
+    /// `target` makes a saving throw against `dc`, and either `on_fail` or `on_success` follows.
+    SavingThrow {
+        target: EffectTarget,
+        save: SavingThrow,
+        dc: i32,
+        #[serde(default)]
+        on_fail: Vec<Effect>,
+        #[serde(default)]
+        on_success: Vec<Effect>,
+        /// On a successful save, the damage effects in `on_fail` still apply at half damage.
+        #[serde(default)]
+        half_damage_on_success: bool,
+    },
+}
+
+/// When an actor's triggered effects fire: its [`RandomEffectTable`]s and homebrew effects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum EffectTrigger {
+    /// At the start of each of the actor's turns.
+    TurnStart,
+    /// After each of the actor's attacks, hit or miss.
+    Attack,
+    /// After each of the actor's attacks that hits, once damage has been dealt.
+    Hit,
+}
+
+/// One possible outcome of a [`RandomEffectTable`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RandomEffectEntry {
+    pub name: String,
+    /// Relative likelihood of this entry; entries with weight 0 never come up.
+    pub weight: u32,
+    /// Effects applied in order when this entry comes up. An empty list is a dud.
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// A table of weighted outcomes rolled on when its trigger fires, such as a wild magic surge
+/// table or a list of random monster mutations.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RandomEffectTable {
+    pub name: String,
+    pub trigger: EffectTrigger,
+    /// Chance out of 100 that the table is rolled on at all when triggered.
+    #[serde(default = "RandomEffectTable::default_chance")]
+    pub chance: u32,
+    pub entries: Vec<RandomEffectEntry>,
+}
+
+impl RandomEffectTable {
+    fn default_chance() -> u32 {
+        100
+    }
+
+    pub fn new(name: &str, trigger: EffectTrigger) -> Self {
+        Self {
+            name: name.to_string(),
+            trigger,
+            chance: Self::default_chance(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn chance(mut self, chance: u32) -> Self {
+        self.chance = chance;
+        self
+    }
+
+    pub fn entry(mut self, name: &str, weight: u32, effects: Vec<Effect>) -> Self {
+        self.entries.push(RandomEffectEntry {
+            name: name.to_string(),
+            weight,
+            effects,
+        });
+        self
+    }
+
+    /// Rolls the table's chance, then picks an entry by weight. Returns the entry's index, or
+    /// `None` if the table didn't go off or has no weighted entries.
+    pub fn roll(&self, roller: &mut Roller) -> Option<usize> {
+        let total: u32 = self.entries.iter().map(|entry| entry.weight).sum();
+        if total == 0 || self.chance == 0 {
+            return None;
+        }
+        if self.chance < 100 && roller.d(100) > self.chance {
+            return None;
+        }
+
+        let mut pick = roller.range(1, total);
+        for (index, entry) in self.entries.iter().enumerate() {
+            if pick <= entry.weight {
+                return Some(index);
+            }
+            pick -= entry.weight;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_effect_table_weights() {
+        let table = RandomEffectTable::new("Surge", EffectTrigger::TurnStart)
+            .entry("Never", 0, Vec::new())
+            .entry("Rare", 1, Vec::new())
+            .entry("Common", 3, Vec::new());
+        let mut roller = Roller::test_rng();
+        let mut counts = [0; 3];
+        for _ in 0..4000 {
+            counts[table.roll(&mut roller).unwrap()] += 1;
+        }
+        assert_eq!(counts[0], 0);
+        assert!(counts[2] > counts[1] * 2, "{:?}", counts);
+
+        let never = table.clone().chance(0);
+        assert_eq!(never.roll(&mut roller), None);
+    }
+}
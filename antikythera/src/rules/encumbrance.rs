@@ -0,0 +1,208 @@
+//! The optional encumbrance rule: what actors carry, and how it slows them down.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        actor::{Actor, ActorId},
+        items::{ItemId, ItemInner},
+        size::Size,
+        stats::Stat,
+    },
+    simulation::state::State,
+};
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Encumbrance {
+    #[default]
+    Unencumbered,
+    /// Carrying more than 5 times Strength in pounds.
+    Encumbered,
+    /// Carrying more than 10 times Strength in pounds.
+    HeavilyEncumbered,
+}
+
+impl Encumbrance {
+    /// How much this slows an actor down, in feet.
+    pub fn speed_penalty(&self) -> u32 {
+        match self {
+            Encumbrance::Unencumbered => 0,
+            Encumbrance::Encumbered => 10,
+            Encumbrance::HeavilyEncumbered => 20,
+        }
+    }
+}
+
+impl Size {
+    /// Larger creatures can carry more; Tiny ones half as much.
+    fn carrying_multiplier(&self) -> f32 {
+        match self {
+            Size::Tiny => 0.5,
+            Size::Small | Size::Medium => 1.0,
+            Size::Large => 2.0,
+            Size::Huge => 4.0,
+            Size::Gargantuan => 8.0,
+        }
+    }
+}
+
+impl State {
+    /// Total weight of everything in an actor's inventory and everything it has equipped, in pounds.
+    pub fn carried_weight(&self, actor: &Actor) -> u32 {
+        let weight = |id: &ItemId| self.items.get(id).map_or(0, |item| item.weight);
+        let carried: u32 = actor
+            .inventory
+            .items
+            .iter()
+            .map(|(id, quantity)| weight(id).saturating_mul(*quantity))
+            .fold(0, u32::saturating_add);
+        let worn: u32 = actor
+            .equipped_items
+            .items
+            .iter()
+            .filter(|id| !actor.inventory.items.contains_key(id))
+            .map(weight)
+            .fold(0, u32::saturating_add);
+        carried.saturating_add(worn)
+    }
+
+    pub fn encumbrance(&self, actor: &Actor) -> Encumbrance {
+        let strength = actor.stats.get(Stat::Strength) as f32 * actor.size.carrying_multiplier();
+        let weight = self.carried_weight(actor) as f32;
+        if weight > strength * 10.0 {
+            Encumbrance::HeavilyEncumbered
+        } else if weight > strength * 5.0 {
+            Encumbrance::Encumbered
+        } else {
+            Encumbrance::Unencumbered
+        }
+    }
+
+    /// Armor the actor has equipped but lacks the Strength to wear unhindered.
+    pub fn unmet_strength_requirements(&self, actor: &Actor) -> Vec<ItemId> {
+        let strength = actor.stats.get(Stat::Strength);
+        actor
+            .equipped_items
+            .items
+            .iter()
+            .copied()
+            .filter(|id| {
+                matches!(
+                    self.items.get(id).map(|item| &item.inner),
+                    Some(ItemInner::Armor(armor)) if armor.strength_requirement > strength
+                )
+            })
+            .collect()
+    }
+
+    /// An actor's walking speed after the optional encumbrance rule, if enabled.
+    pub fn effective_speed(&self, actor: &Actor) -> u32 {
+        if !self.encounter.rules.encumbrance {
+            return actor.movement_speed;
+        }
+        let mut penalty = self.encumbrance(actor).speed_penalty();
+        if !self.unmet_strength_requirements(actor).is_empty() {
+            penalty += 10;
+        }
+        actor.movement_speed.saturating_sub(penalty)
+    }
+
+    /// Actors slowed down by the optional encumbrance rule, worded for the person building the
+    /// scenario. Empty when the rule is off. Part of [`State::warnings`].
+    pub(crate) fn encumbrance_warnings(&self) -> Vec<(ActorId, String)> {
+        if !self.encounter.rules.encumbrance {
+            return Vec::new();
+        }
+        let mut warnings = Vec::new();
+        for actor in self.actors.values() {
+            let encumbrance = self.encumbrance(actor);
+            if encumbrance != Encumbrance::Unencumbered {
+                warnings.push((
+                    actor.id,
+                    format!(
+                        "{} is {:?} carrying {} lb, losing {} ft of speed",
+                        actor.name,
+                        encumbrance,
+                        self.carried_weight(actor),
+                        encumbrance.speed_penalty()
+                    ),
+                ));
+            }
+            for id in self.unmet_strength_requirements(actor) {
+                let item = &self.items[&id];
+                let ItemInner::Armor(armor) = &item.inner else {
+                    continue;
+                };
+                warnings.push((
+                    actor.id,
+                    format!(
+                        "{} lacks the Strength {} to wear {}, losing 10 ft of speed",
+                        actor.name, armor.strength_requirement, item.name
+                    ),
+                ));
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{actor::ActorBuilder, items::Armor};
+
+    #[test]
+    fn test_encumbrance() {
+        let mut state = State::new();
+        let plate = state.add_item(
+            "Plate",
+            ItemInner::Armor(Armor::new(8).strength_requirement(15)),
+        );
+        state.items.get_mut(&plate).unwrap().weight = 65;
+        let rock = state.add_item("Rock", ItemInner::Armor(Armor::new(0)));
+        state.items.get_mut(&rock).unwrap().weight = 10;
+
+        let mut wizard = ActorBuilder::new("Wizard")
+            .stat(Stat::Strength, 8)
+            .movement_speed(30)
+            .build();
+        wizard.equipped_items.equip(plate);
+        let wizard = state.add_actor(wizard);
+
+        // off by default, so nothing changes
+        let actor = state.get_actor(wizard).unwrap();
+        assert_eq!(state.effective_speed(actor), 30);
+        assert!(state.encumbrance_warnings().is_empty());
+
+        state.encounter.rules.encumbrance = true;
+        let actor = state.get_actor(wizard).unwrap();
+        assert_eq!(state.carried_weight(actor), 65);
+        assert_eq!(state.encumbrance(actor), Encumbrance::Encumbered);
+        assert_eq!(state.effective_speed(actor), 10);
+        assert_eq!(state.encumbrance_warnings().len(), 2);
+
+        state.actors.get_mut(&wizard).unwrap().give_item(rock, 2);
+        let actor = state.get_actor(wizard).unwrap();
+        assert_eq!(state.carried_weight(actor), 85);
+        assert_eq!(state.encumbrance(actor), Encumbrance::HeavilyEncumbered);
+        assert_eq!(state.effective_speed(actor), 0);
+
+        state.actors.get_mut(&wizard).unwrap().size = Size::Large;
+        let actor = state.get_actor(wizard).unwrap();
+        assert_eq!(state.encumbrance(actor), Encumbrance::Encumbered);
+
+        // a huge pile of rocks weighs as much as anything can, rather than overflowing
+        state
+            .actors
+            .get_mut(&wizard)
+            .unwrap()
+            .inventory
+            .items
+            .insert(rock, u32::MAX);
+        let actor = state.get_actor(wizard).unwrap();
+        assert_eq!(state.carried_weight(actor), u32::MAX);
+        assert_eq!(state.encumbrance(actor), Encumbrance::HeavilyEncumbered);
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// Class, race, and feat features that modify combat rolls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Feature {
+    /// +2 to attack rolls with ranged weapons.
+    Archery,
+    /// +2 damage with melee weapons that aren't two-handed.
+    Dueling,
+    /// +1 AC while wearing armor.
+    Defense,
+    /// Reroll 1s and 2s on damage dice (once) with two-handed or versatile melee weapons.
+    GreatWeaponFighting,
+    /// When reduced to 0 HP but not killed outright, drop to 1 HP instead. Once per combat.
+    RelentlessEndurance,
+    /// When reduced to 0 HP, make a CON save (DC 5 + damage taken) to drop to 1 HP instead.
+    /// Fails automatically against critical hits.
+    UndeadFortitude,
+    /// Reroll natural 1s on attack rolls, ability checks and saving throws, keeping the new roll.
+    HalflingLuck,
+    /// While conscious, the actor and its allies add its CHA modifier (minimum +1) to saving
+    /// throws. Positions aren't tracked, so every ally counts as within range.
+    AuraOfProtection,
+    /// Can take -5 to hit for +10 damage with heavy melee weapons.
+    GreatWeaponMaster,
+    /// Can take -5 to hit for +10 damage with ranged weapons.
+    Sharpshooter,
+}
+
+impl Feature {
+    pub fn all() -> Vec<Feature> {
+        vec![
+            Feature::Archery,
+            Feature::Dueling,
+            Feature::Defense,
+            Feature::GreatWeaponFighting,
+            Feature::RelentlessEndurance,
+            Feature::UndeadFortitude,
+            Feature::HalflingLuck,
+            Feature::AuraOfProtection,
+            Feature::GreatWeaponMaster,
+            Feature::Sharpshooter,
+        ]
+    }
+
+    /// Whether this feature triggers when its owner would drop to 0 HP.
+    pub fn is_on_drop_trigger(&self) -> bool {
+        matches!(
+            self,
+            Feature::RelentlessEndurance | Feature::UndeadFortitude
+        )
+    }
+
+    /// Whether this feature can only be used once per combat.
+    pub fn once_per_combat(&self) -> bool {
+        matches!(self, Feature::RelentlessEndurance)
+    }
+}
@@ -0,0 +1,498 @@
+//! Small integer formulas for values derived from an actor, like `8 + prof + cha_mod` for a save
+//! DC, so a scenario's stat blocks stay right when levels or ability scores change.
+//!
+//! Formulas support integers, names, `+ - * /`, parentheses and `min(...)`/`max(...)`. Division
+//! rounds down, as it does everywhere else in the rules. Arithmetic is checked, and formulas are
+//! limited in length and nesting, so a bad scenario file gives an error rather than a panic.
+//!
+//! Formulas can use each other by name. Each is evaluated once however many others use it, and
+//! chains of them are limited in depth like nesting is, so scenario files can't make loading
+//! them take exponential time or overflow the stack.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::{
+    rules::{actor::Actor, stats::Stat},
+    simulation::state::State,
+};
+
+const MAX_LENGTH: usize = 256;
+const MAX_DEPTH: usize = 32;
+/// How long a chain of formulas using each other can be.
+const MAX_REFERENCE_DEPTH: usize = 32;
+
+/// Evaluates `formula`, looking up each name it uses with `variable`.
+pub fn evaluate(
+    formula: &str,
+    variable: &mut dyn FnMut(&str) -> anyhow::Result<i64>,
+) -> anyhow::Result<i64> {
+    anyhow::ensure!(
+        formula.len() <= MAX_LENGTH,
+        "Formula is longer than {} characters",
+        MAX_LENGTH
+    );
+    let mut parser = Parser {
+        tokens: tokenize(formula)?,
+        position: 0,
+        depth: 0,
+        variable,
+    };
+    let value = parser.expression()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(token) => Err(anyhow::anyhow!("Unexpected {} in formula", token)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+fn tokenize(formula: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = formula.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut digits = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(c);
+                chars.next();
+            }
+            let number = digits
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Number {} is too large", digits))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while let Some(&c) = chars
+                .peek()
+                .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+            {
+                name.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Name(name));
+        } else if "+-*/(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            anyhow::bail!("Unexpected '{}' in formula", c);
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+    variable: &'a mut dyn FnMut(&str) -> anyhow::Result<i64>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> anyhow::Result<()> {
+        anyhow::ensure!(self.eat(symbol), "Expected '{}' in formula", symbol);
+        Ok(())
+    }
+
+    fn expression(&mut self) -> anyhow::Result<i64> {
+        self.depth += 1;
+        anyhow::ensure!(
+            self.depth <= MAX_DEPTH,
+            "Formula is nested more than {} deep",
+            MAX_DEPTH
+        );
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value = value.checked_add(self.term()?).ok_or_else(overflow)?;
+            } else if self.eat('-') {
+                value = value.checked_sub(self.term()?).ok_or_else(overflow)?;
+            } else {
+                break;
+            }
+        }
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn term(&mut self) -> anyhow::Result<i64> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value = value.checked_mul(self.unary()?).ok_or_else(overflow)?;
+            } else if self.eat('/') {
+                value = floor_div(value, self.unary()?)?;
+            } else {
+                break;
+            }
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<i64> {
+        if self.eat('-') {
+            self.depth += 1;
+            anyhow::ensure!(
+                self.depth <= MAX_DEPTH,
+                "Formula is nested more than {} deep",
+                MAX_DEPTH
+            );
+            let value = self.unary()?.checked_neg().ok_or_else(overflow)?;
+            self.depth -= 1;
+            Ok(value)
+        } else {
+            self.atom()
+        }
+    }
+
+    fn atom(&mut self) -> anyhow::Result<i64> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(number),
+            Some(Token::Symbol('(')) => {
+                let value = self.expression()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Some(Token::Name(name)) if self.eat('(') => {
+                let mut arguments = vec![self.expression()?];
+                while self.eat(',') {
+                    arguments.push(self.expression()?);
+                }
+                self.expect(')')?;
+                match name.as_str() {
+                    "min" => Ok(arguments.into_iter().min().unwrap()),
+                    "max" => Ok(arguments.into_iter().max().unwrap()),
+                    _ => Err(anyhow::anyhow!("Unknown function '{}'", name)),
+                }
+            }
+            Some(Token::Name(name)) => (self.variable)(&name),
+            Some(token) => Err(anyhow::anyhow!("Unexpected {} in formula", token)),
+            None => Err(anyhow::anyhow!("Formula ended unexpectedly")),
+        }
+    }
+}
+
+fn overflow() -> anyhow::Error {
+    anyhow::anyhow!("Formula overflowed")
+}
+
+fn floor_div(numerator: i64, denominator: i64) -> anyhow::Result<i64> {
+    anyhow::ensure!(denominator != 0, "Formula divides by zero");
+    let quotient = numerator.checked_div(denominator).ok_or_else(overflow)?;
+    if numerator % denominator != 0 && (numerator < 0) != (denominator < 0) {
+        Ok(quotient - 1)
+    } else {
+        Ok(quotient)
+    }
+}
+
+fn stat_name(stat: Stat) -> &'static str {
+    match stat {
+        Stat::Strength => "str",
+        Stat::Dexterity => "dex",
+        Stat::Constitution => "con",
+        Stat::Intelligence => "int",
+        Stat::Wisdom => "wis",
+        Stat::Charisma => "cha",
+    }
+}
+
+/// Evaluates one actor's formulas, each at most once, however many others use it.
+struct Evaluation<'a> {
+    actor: &'a Actor,
+    fields: Value,
+    values: BTreeMap<String, i64>,
+    /// The formulas being evaluated, innermost last, to catch cycles.
+    evaluating: Vec<String>,
+}
+
+impl Evaluation<'_> {
+    fn formula(&mut self, name: &str) -> anyhow::Result<i64> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(*value);
+        }
+        anyhow::ensure!(
+            !self.evaluating.iter().any(|evaluating| evaluating == name),
+            "Formula for {} refers to itself",
+            name
+        );
+        anyhow::ensure!(
+            self.evaluating.len() < MAX_REFERENCE_DEPTH,
+            "Formulas refer to each other more than {} deep",
+            MAX_REFERENCE_DEPTH
+        );
+        self.evaluating.push(name.to_string());
+        let formula = &self.actor.formulas[name];
+        let value = evaluate(formula, &mut |variable| self.variable(variable))
+            .map_err(|e| anyhow::anyhow!("Formula for {}: {}", name, e))?;
+        self.evaluating.pop();
+        self.values.insert(name.to_string(), value);
+        Ok(value)
+    }
+
+    fn variable(&mut self, variable: &str) -> anyhow::Result<i64> {
+        let actor = self.actor;
+        if actor.formulas.contains_key(variable) {
+            return self.formula(variable);
+        }
+        if variable == "prof" {
+            return Ok(actor.proficiency_bonus() as i64);
+        }
+        for stat in Stat::all() {
+            if variable == stat_name(stat) {
+                return Ok(actor.stats.get(stat) as i64);
+            }
+            if variable.strip_suffix("_mod") == Some(stat_name(stat)) {
+                return Ok(actor.stat_modifier(stat) as i64);
+            }
+        }
+        self.fields
+            .get(variable)
+            .and_then(Value::as_i64)
+            .ok_or_else(|| anyhow::anyhow!("Unknown name '{}'", variable))
+    }
+}
+
+impl Actor {
+    /// Evaluates every formula in [`Actor::formulas`] against the actor as it is now.
+    ///
+    /// Formulas can use `prof`, the ability scores `str` to `cha`, their modifiers `str_mod` to
+    /// `cha_mod`, the names of other formulas, and any top-level numeric field such as `level`
+    /// or `armor_class`.
+    pub fn evaluate_formulas(&self) -> anyhow::Result<Vec<(String, i64)>> {
+        let mut evaluation = Evaluation {
+            actor: self,
+            fields: serde_json::to_value(self)?,
+            values: BTreeMap::new(),
+            evaluating: Vec::new(),
+        };
+        self.formulas
+            .keys()
+            .map(|name| Ok((name.clone(), evaluation.formula(name)?)))
+            .collect()
+    }
+
+    /// Evaluates the actor's formulas and writes each result to the field it's named after.
+    ///
+    /// A formula's name is a path into the actor's JSON, like `armor_class` or
+    /// `abilities/0/effects/0/SavingThrow/dc`. Names that aren't a field are only there for other
+    /// formulas to use. An actor at full health stays at full health if `max_health` changes.
+    pub fn apply_formulas(&mut self) -> anyhow::Result<()> {
+        if self.formulas.is_empty() {
+            return Ok(());
+        }
+        let values = self.evaluate_formulas()?;
+        let mut fields = serde_json::to_value(&*self)?;
+        for (name, value) in values {
+            let Some(field) = fields.pointer_mut(&format!("/{}", name)) else {
+                continue;
+            };
+            anyhow::ensure!(
+                field.is_number(),
+                "Formula for {} doesn't name a numeric field",
+                name
+            );
+            *field = value.into();
+        }
+        let full_health = self.health == self.max_health;
+        let mut actor: Actor = serde_json::from_value(fields)
+            .map_err(|e| anyhow::anyhow!("Formula result doesn't fit: {}", e))?;
+        if full_health {
+            actor.health = actor.max_health;
+        }
+        *self = actor;
+        Ok(())
+    }
+}
+
+impl State {
+    /// Applies every actor's formulas. See [`Actor::apply_formulas`].
+    pub fn apply_formulas(&mut self) -> anyhow::Result<()> {
+        for actor in self.actors.values_mut() {
+            actor
+                .apply_formulas()
+                .map_err(|e| anyhow::anyhow!("Invalid formula on {}: {}", actor.name, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::ActorBuilder,
+        rules::{
+            abilities::Ability,
+            dice::RollPlan,
+            effects::{Effect, EffectTarget},
+            saves::SavingThrow,
+        },
+    };
+
+    fn constant(formula: &str) -> anyhow::Result<i64> {
+        evaluate(formula, &mut |name| match name {
+            "x" => Ok(7),
+            _ => Err(anyhow::anyhow!("Unknown name '{}'", name)),
+        })
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(constant("8 + 2 * 3").unwrap(), 14);
+        assert_eq!(constant("(8 + 2) * 3").unwrap(), 30);
+        assert_eq!(constant("x / 2").unwrap(), 3);
+        assert_eq!(constant("-x / 2").unwrap(), -4);
+        assert_eq!(constant("--x").unwrap(), 7);
+        assert_eq!(constant("max(1, x - 10, 2) + min(x)").unwrap(), 9);
+
+        for bad in [
+            "",
+            "1 +",
+            "(1",
+            "1)",
+            "x / 0",
+            "y",
+            "abs(1)",
+            "1 $ 2",
+            "99999999999999999999",
+            "9223372036854775807 + 1",
+        ] {
+            assert!(constant(bad).is_err(), "{:?} should fail", bad);
+        }
+        assert!(constant(&"(".repeat(100)).is_err());
+        assert!(constant(&"-".repeat(100)).is_err());
+        assert!(constant(&"1+".repeat(200)).is_err());
+    }
+
+    #[test]
+    fn test_apply_formulas() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let caster = state.add_actor(
+            ActorBuilder::new("Caster")
+                .level(5)
+                .max_health(10)
+                .stat(Stat::Charisma, 16)
+                .stat(Stat::Dexterity, 14)
+                .formula("save_dc", "8 + prof + cha_mod")
+                .formula("armor_class", "10 + dex_mod")
+                .formula("max_health", "level * 6")
+                .ability(Ability::new(
+                    "Hold",
+                    vec![Effect::SavingThrow {
+                        target: EffectTarget::Target,
+                        save: SavingThrow::Wisdom,
+                        dc: 0,
+                        on_fail: vec![Effect::Damage {
+                            target: EffectTarget::Target,
+                            damage: RollPlan::from("1d6"),
+                            damage_type: None,
+                        }],
+                        on_success: Vec::new(),
+                        half_damage_on_success: false,
+                    }],
+                ))
+                .formula("abilities/0/effects/0/SavingThrow/dc", "save_dc")
+                .build(),
+        );
+        state.apply_formulas()?;
+        let actor = state.get_actor(caster).unwrap();
+        assert_eq!(actor.armor_class, 12);
+        assert_eq!(actor.max_health, 30);
+        assert_eq!(actor.health, 30);
+        assert!(matches!(
+            actor.abilities[0].effects[..],
+            [Effect::SavingThrow { dc: 14, .. }]
+        ));
+        assert_eq!(
+            actor.evaluate_formulas()?,
+            vec![
+                ("abilities/0/effects/0/SavingThrow/dc".to_string(), 14),
+                ("armor_class".to_string(), 12),
+                ("max_health".to_string(), 30),
+                ("save_dc".to_string(), 14),
+            ]
+        );
+
+        let mut looping = ActorBuilder::new("Looping")
+            .formula("a", "b + 1")
+            .formula("b", "a")
+            .build();
+        assert!(looping.apply_formulas().is_err());
+        let mut negative = ActorBuilder::new("Negative")
+            .formula("armor_class", "0 - 1")
+            .build();
+        assert!(negative.apply_formulas().is_err());
+        let mut not_a_number = ActorBuilder::new("Named").formula("name", "1").build();
+        assert!(not_a_number.apply_formulas().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_formulas_using_formulas() -> anyhow::Result<()> {
+        // each formula doubles the next, which would take 2^31 steps without remembering them
+        let mut doubling = ActorBuilder::new("Doubling").formula("f30", "1");
+        for i in 0..30 {
+            doubling = doubling.formula(&format!("f{}", i), &format!("f{} + f{}", i + 1, i + 1));
+        }
+        let values = doubling.build().evaluate_formulas()?;
+        assert_eq!(values[0], ("f0".to_string(), 1 << 30));
+        assert_eq!(values.len(), 31);
+
+        let chain = |length: usize| {
+            let mut chain = ActorBuilder::new("Chain").formula(&format!("f{}", length), "1");
+            for i in 0..length {
+                chain = chain.formula(&format!("f{}", i), &format!("f{}", i + 1));
+            }
+            chain.build().evaluate_formulas()
+        };
+        assert!(chain(MAX_REFERENCE_DEPTH - 1).is_ok());
+        assert!(chain(MAX_REFERENCE_DEPTH).is_err());
+        assert!(chain(10_000).is_err());
+
+        // a level 0 actor, as loaded scenarios can have, is proficient like a level 1 one
+        let novice = ActorBuilder::new("Novice")
+            .level(0)
+            .formula("bonus", "prof")
+            .build();
+        assert_eq!(novice.evaluate_formulas()?, vec![("bonus".to_string(), 2)]);
+        Ok(())
+    }
+}
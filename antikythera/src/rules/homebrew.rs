@@ -0,0 +1,355 @@
+//! A small, forgiving effect language for homebrew abilities in scenario files.
+//!
+//! Abilities are written as plain JSON objects and compiled into [`Effect`]s when they fire:
+//!
+//! ```json
+//! "homebrew": {
+//!     "on_hit": {
+//!         "save": "DEX",
+//!         "dc": 13,
+//!         "fail": { "damage": "2d6 fire" },
+//!         "success": { "damage_half": true }
+//!     }
+//! }
+//! ```
+//!
+//! Each trigger takes either a single effect or a list of them. An effect can have any of
+//! `damage`, `heal`, `condition`, and `stats`, all applied in that order, or a `save` with
+//! `dc`, `fail`, and `success` blocks. Stat changes are permanent unless given a `duration`
+//! in rounds. `target` picks who it lands on: `"self"` or `"target"`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::rules::{
+    conditions::Condition,
+    damage::DamageType,
+    dice::RollPlan,
+    effects::{Effect, EffectTarget, EffectTrigger},
+    saves::SavingThrow,
+    stats::Stat,
+};
+
+/// Homebrew effects attached to an actor, keyed by when they fire.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HomebrewEffects {
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub on_turn_start: Vec<EffectSpec>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub on_attack: Vec<EffectSpec>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub on_hit: Vec<EffectSpec>,
+}
+
+impl HomebrewEffects {
+    pub fn is_empty(&self) -> bool {
+        self.on_turn_start.is_empty() && self.on_attack.is_empty() && self.on_hit.is_empty()
+    }
+
+    pub fn specs(&self, trigger: EffectTrigger) -> &[EffectSpec] {
+        match trigger {
+            EffectTrigger::TurnStart => &self.on_turn_start,
+            EffectTrigger::Attack => &self.on_attack,
+            EffectTrigger::Hit => &self.on_hit,
+        }
+    }
+
+    /// Compiles every effect for a trigger. Effects on attacks land on the attack's target
+    /// unless they say otherwise; effects at the start of a turn land on the actor itself.
+    pub fn compile(&self, trigger: EffectTrigger) -> anyhow::Result<Vec<Effect>> {
+        let default_target = match trigger {
+            EffectTrigger::TurnStart => EffectTarget::User,
+            EffectTrigger::Attack | EffectTrigger::Hit => EffectTarget::Target,
+        };
+        let mut effects = Vec::new();
+        for spec in self.specs(trigger) {
+            effects.extend(spec.compile(default_target)?);
+        }
+        Ok(effects)
+    }
+
+    /// Checks that every effect compiles.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for trigger in [
+            EffectTrigger::TurnStart,
+            EffectTrigger::Attack,
+            EffectTrigger::Hit,
+        ] {
+            self.compile(trigger)?;
+        }
+        Ok(())
+    }
+}
+
+/// One homebrew effect as written in a scenario file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EffectSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// A roll, optionally followed by a damage type: `"2d6 fire"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub damage: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// Stat changes, e.g. `{ "STR": -2 }`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stats: BTreeMap<String, i32>,
+    /// How many rounds the stat changes last. Without it, they're permanent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub save: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dc: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fail: Option<Box<EffectSpec>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<Box<EffectSpec>>,
+    /// In a `success` block: the `fail` block's damage still applies, halved.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub damage_half: bool,
+}
+
+impl EffectSpec {
+    pub fn compile(&self, default_target: EffectTarget) -> anyhow::Result<Vec<Effect>> {
+        let target = match self.target.as_deref() {
+            None => default_target,
+            Some(target) => parse_target(target)?,
+        };
+
+        if let Some(save) = &self.save {
+            anyhow::ensure!(
+                self.damage.is_none()
+                    && self.heal.is_none()
+                    && self.condition.is_none()
+                    && self.stats.is_empty()
+                    && self.duration.is_none(),
+                "Effects with a save go in its `fail` and `success` blocks"
+            );
+            let dc = self
+                .dc
+                .ok_or_else(|| anyhow::anyhow!("Save `{}` is missing a `dc`", save))?;
+            let compile_block = |block: &Option<Box<EffectSpec>>| match block {
+                Some(block) => block.compile(default_target),
+                None => Ok(Vec::new()),
+            };
+            return Ok(vec![Effect::SavingThrow {
+                target,
+                save: parse_save(save)?,
+                dc,
+                on_fail: compile_block(&self.fail)?,
+                on_success: compile_block(&self.success)?,
+                half_damage_on_success: self.success.as_ref().is_some_and(|s| s.damage_half),
+            }]);
+        }
+        anyhow::ensure!(
+            self.dc.is_none() && self.fail.is_none() && self.success.is_none(),
+            "`dc`, `fail`, and `success` need a `save`"
+        );
+        anyhow::ensure!(
+            self.duration.is_none() || !self.stats.is_empty(),
+            "`duration` only applies to `stats`"
+        );
+
+        let mut effects = Vec::new();
+        if let Some(damage) = &self.damage {
+            let (damage, damage_type) = parse_damage(damage)?;
+            effects.push(Effect::Damage {
+                target,
+                damage,
+                damage_type,
+            });
+        }
+        if let Some(heal) = &self.heal {
+            effects.push(Effect::Heal {
+                target,
+                amount: parse_amount(heal)?,
+            });
+        }
+        if let Some(condition) = &self.condition {
+            effects.push(Effect::ApplyCondition {
+                target,
+                condition: parse_condition(condition)?,
+            });
+        }
+        for (stat, delta) in &self.stats {
+            effects.push(Effect::ModifyStat {
+                target,
+                stat: parse_stat(stat)?,
+                delta: *delta,
+                duration: self.duration,
+            });
+        }
+        Ok(effects)
+    }
+}
+
+pub(crate) fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<EffectSpec>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(EffectSpec),
+        Many(Vec<EffectSpec>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(spec) => vec![spec],
+        OneOrMany::Many(specs) => specs,
+    })
+}
+
+fn parse_target(target: &str) -> anyhow::Result<EffectTarget> {
+    match target.to_ascii_lowercase().as_str() {
+        "self" | "user" => Ok(EffectTarget::User),
+        "target" => Ok(EffectTarget::Target),
+        _ => anyhow::bail!("Unknown effect target `{}`", target),
+    }
+}
+
+fn parse_stat(stat: &str) -> anyhow::Result<Stat> {
+    match stat.to_ascii_lowercase().as_str() {
+        "str" | "strength" => Ok(Stat::Strength),
+        "dex" | "dexterity" => Ok(Stat::Dexterity),
+        "con" | "constitution" => Ok(Stat::Constitution),
+        "int" | "intelligence" => Ok(Stat::Intelligence),
+        "wis" | "wisdom" => Ok(Stat::Wisdom),
+        "cha" | "charisma" => Ok(Stat::Charisma),
+        _ => anyhow::bail!("Unknown stat `{}`", stat),
+    }
+}
+
+fn parse_save(save: &str) -> anyhow::Result<SavingThrow> {
+    parse_stat(save).map(SavingThrow::from_stat)
+}
+
+fn parse_condition(condition: &str) -> anyhow::Result<Condition> {
+    match condition.to_ascii_lowercase().as_str() {
+        "prone" => Ok(Condition::Prone),
+        _ => anyhow::bail!("Unsupported condition `{}`", condition),
+    }
+}
+
+/// A roll like `"1d6+2"`, or a flat number.
+fn parse_amount(amount: &str) -> anyhow::Result<RollPlan> {
+    let amount = amount.trim();
+    if let Ok(flat) = amount.parse::<i32>() {
+        return Ok(RollPlan {
+            num_dice: 0,
+            die_size: 1,
+            modifier: flat,
+            settings: Default::default(),
+        });
+    }
+    crate::roll_parser::parse_roll(amount)
+        .map_err(|_| anyhow::anyhow!("Couldn't parse roll `{}`", amount))
+}
+
+fn parse_damage(damage: &str) -> anyhow::Result<(RollPlan, Option<DamageType>)> {
+    let damage = damage.trim();
+    let (roll, damage_type) = match damage.rsplit_once(char::is_whitespace) {
+        Some((roll, word)) if word.chars().all(char::is_alphabetic) => (roll, Some(word)),
+        _ => (damage, None),
+    };
+    let damage_type = match damage_type {
+        Some(word) => Some(parse_damage_type(word)?),
+        None => None,
+    };
+    Ok((parse_amount(roll)?, damage_type))
+}
+
+fn parse_damage_type(damage_type: &str) -> anyhow::Result<DamageType> {
+    Ok(match damage_type.to_ascii_lowercase().as_str() {
+        "bludgeoning" => DamageType::Bludgeoning,
+        "piercing" => DamageType::Piercing,
+        "slashing" => DamageType::Slashing,
+        "fire" => DamageType::Fire,
+        "cold" => DamageType::Cold,
+        "lightning" => DamageType::Lightning,
+        "acid" => DamageType::Acid,
+        "poison" => DamageType::Poison,
+        "psychic" => DamageType::Psychic,
+        "necrotic" => DamageType::Necrotic,
+        "radiant" => DamageType::Radiant,
+        "thunder" => DamageType::Thunder,
+        _ => anyhow::bail!("Unknown damage type `{}`", damage_type),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_homebrew_effects() -> anyhow::Result<()> {
+        let homebrew: HomebrewEffects = serde_json::from_str(
+            r#"{
+                "on_hit": {
+                    "save": "DEX",
+                    "dc": 13,
+                    "fail": { "damage": "2d6 fire" },
+                    "success": { "damage_half": true }
+                },
+                "on_turn_start": [
+                    { "heal": "1d4" },
+                    { "target": "self", "stats": { "str": 1 }, "duration": 2 }
+                ]
+            }"#,
+        )?;
+        homebrew.validate()?;
+
+        let on_hit = homebrew.compile(EffectTrigger::Hit)?;
+        assert_eq!(
+            on_hit,
+            vec![Effect::SavingThrow {
+                target: EffectTarget::Target,
+                save: SavingThrow::Dexterity,
+                dc: 13,
+                on_fail: vec![Effect::Damage {
+                    target: EffectTarget::Target,
+                    damage: RollPlan::from("2d6"),
+                    damage_type: Some(DamageType::Fire),
+                }],
+                on_success: Vec::new(),
+                half_damage_on_success: true,
+            }]
+        );
+
+        let on_turn_start = homebrew.compile(EffectTrigger::TurnStart)?;
+        assert_eq!(on_turn_start.len(), 2);
+        assert_eq!(
+            on_turn_start[1],
+            Effect::ModifyStat {
+                target: EffectTarget::User,
+                stat: Stat::Strength,
+                delta: 1,
+                duration: Some(2),
+            }
+        );
+        assert!(homebrew.compile(EffectTrigger::Attack)?.is_empty());
+
+        let bad: EffectSpec = serde_json::from_str(r#"{ "save": "LUCK", "dc": 10 }"#)?;
+        assert!(bad.compile(EffectTarget::Target).is_err());
+        let bad: EffectSpec = serde_json::from_str(r#"{ "damage": "lots" }"#)?;
+        assert!(bad.compile(EffectTarget::Target).is_err());
+
+        Ok(())
+    }
+}
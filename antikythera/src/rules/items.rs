@@ -3,7 +3,10 @@ use std::collections::{BTreeMap, BTreeSet};
 use derive_more::{Deref, From, Into};
 use serde::{Deserialize, Serialize};
 
-use crate::rules::{dice::RollPlan, skills::SkillProficiency, spells::SpellId};
+use crate::rules::{
+    damage::DamageType, dice::RollPlan, metadata::Metadata, skills::SkillProficiency,
+    spells::SpellId,
+};
 
 #[derive(
     Debug, Clone, Copy, PartialEq, PartialOrd, Ord, Eq, Hash, Serialize, Deserialize, From, Into,
@@ -53,6 +56,14 @@ pub struct Item {
     pub id: ItemId,
     pub name: String,
     pub inner: ItemInner,
+    /// Bonus to every saving throw while equipped, like a cloak of protection's.
+    #[serde(default)]
+    pub save_bonus: i32,
+    /// In pounds, for the optional encumbrance rule.
+    #[serde(default)]
+    pub weight: u32,
+    #[serde(default)]
+    pub metadata: Metadata,
 }
 
 impl Item {
@@ -65,12 +76,15 @@ impl Item {
         }
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-utils"))]
     pub fn test_sword() -> Self {
         Self {
             id: ItemId(1),
             name: "Test Sword".to_string(),
             inner: ItemInner::Weapon(Weapon::test_sword()),
+            save_bonus: 0,
+            weight: 3,
+            metadata: Metadata::default(),
         }
     }
 }
@@ -81,7 +95,7 @@ pub struct Potion {
 }
 
 impl Potion {
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-utils"))]
     pub fn test_potion() -> Self {
         use crate::rules::dice::RollSettings;
         Self {
@@ -100,6 +114,25 @@ pub struct Scroll {
     pub spell_id: SpellId,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WeaponCategory {
+    Simple,
+    Martial,
+}
+
+impl WeaponCategory {
+    pub fn all() -> Vec<WeaponCategory> {
+        vec![WeaponCategory::Simple, WeaponCategory::Martial]
+    }
+
+    pub fn weapon_types(self) -> impl Iterator<Item = WeaponType> {
+        WeaponType::all()
+            .iter()
+            .copied()
+            .filter(move |weapon_type| weapon_type.category() == self)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Ord, Eq, Hash, Serialize, Deserialize)]
 pub enum WeaponType {
     Battleaxe,
@@ -141,6 +174,80 @@ pub enum WeaponType {
 }
 
 impl WeaponType {
+    pub fn is_two_handed(&self) -> bool {
+        use WeaponType::*;
+        matches!(
+            self,
+            CrossbowHeavy
+                | Glaive
+                | Greataxe
+                | Greatclub
+                | Greatsword
+                | Halberd
+                | Longbow
+                | Maul
+                | Pike
+                | Shortbow
+        )
+    }
+
+    /// Heavy weapons, which Great Weapon Master's power attack needs.
+    pub fn is_heavy(&self) -> bool {
+        use WeaponType::*;
+        matches!(
+            self,
+            CrossbowHeavy | Glaive | Greataxe | Greatsword | Halberd | Longbow | Maul | Pike
+        )
+    }
+
+    /// Ranged weapons, as opposed to melee weapons that can be thrown.
+    pub fn is_ranged_weapon(&self) -> bool {
+        use WeaponType::*;
+        matches!(
+            self,
+            Blowgun | CrossbowHeavy | CrossbowLight | Dart | Longbow | Net | Shortbow | Sling
+        )
+    }
+
+    /// Versatile weapons can be wielded in one or two hands.
+    pub fn is_versatile(&self) -> bool {
+        use WeaponType::*;
+        matches!(
+            self,
+            Battleaxe | Longsword | Quarterstaff | Spear | Trident | Warhammer
+        )
+    }
+
+    /// The type of damage this kind of weapon usually deals.
+    pub fn damage_type(&self) -> DamageType {
+        use WeaponType::*;
+        match self {
+            Club | Flail | Greatclub | LightHammer | Mace | Maul | Net | Quarterstaff | Sling
+            | Warhammer => DamageType::Bludgeoning,
+            Blowgun | CrossbowHeavy | CrossbowLight | Dagger | Dart | Javelin | Lance | Longbow
+            | Morningstar | Pike | Rapier | Shortbow | Shortsword | Spear | Trident | WarPick => {
+                DamageType::Piercing
+            }
+            Battleaxe | Glaive | Greataxe | Greatsword | Halberd | Handaxe | Longsword
+            | Scimitar | Sickle | Whip => DamageType::Slashing,
+        }
+    }
+
+    pub fn category(&self) -> WeaponCategory {
+        use WeaponType::*;
+        match self {
+            Club | CrossbowLight | Dagger | Dart | Greatclub | Handaxe | Javelin | LightHammer
+            | Mace | Quarterstaff | Shortbow | Sickle | Sling | Spear => WeaponCategory::Simple,
+            _ => WeaponCategory::Martial,
+        }
+    }
+
+    /// Reach weapons can strike targets up to 10 feet away.
+    pub fn has_reach(&self) -> bool {
+        use WeaponType::*;
+        matches!(self, Glaive | Halberd | Lance | Pike | Whip)
+    }
+
     pub fn all() -> &'static [WeaponType] {
         use WeaponType::*;
         &[
@@ -191,9 +298,20 @@ pub struct Weapon {
     pub damage: RollPlan,
     pub critical_damage: Option<RollPlan>,
     pub range: Option<u32>, // in feet, None for melee
+    /// A magic weapon's +1, +2, or +3, added to both attack and damage rolls.
+    #[serde(default)]
+    pub enchantment_bonus: i32,
+    /// Overrides the weapon type's usual damage type, for weapons like a flame tongue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub damage_type: Option<DamageType>,
 }
 
 impl Weapon {
+    pub fn damage_type_dealt(&self) -> DamageType {
+        self.damage_type
+            .unwrap_or_else(|| self.weapon_type.damage_type())
+    }
+
     pub fn is_melee(&self) -> bool {
         self.range.is_none()
     }
@@ -202,7 +320,16 @@ impl Weapon {
         self.range.is_some()
     }
 
-    #[cfg(test)]
+    /// The farthest a target can be for this weapon to attack it, in feet.
+    pub fn reach(&self) -> u32 {
+        match self.range {
+            Some(range) => range,
+            None if self.weapon_type.has_reach() => 10,
+            None => 5,
+        }
+    }
+
+    #[cfg(any(test, feature = "test-utils"))]
     pub fn test_sword() -> Self {
         use crate::rules::dice::RollSettings;
         Self {
@@ -216,6 +343,8 @@ impl Weapon {
             },
             critical_damage: None,
             range: None,
+            enchantment_bonus: 0,
+            damage_type: None,
         }
     }
 }
@@ -239,6 +368,8 @@ impl WeaponBuilder {
                 },
                 critical_damage: None,
                 range: None,
+                enchantment_bonus: 0,
+                damage_type: None,
             },
         }
     }
@@ -263,6 +394,16 @@ impl WeaponBuilder {
         self
     }
 
+    pub fn enchantment_bonus(mut self, bonus: i32) -> Self {
+        self.weapon.enchantment_bonus = bonus;
+        self
+    }
+
+    pub fn damage_type(mut self, damage_type: DamageType) -> Self {
+        self.weapon.damage_type = Some(damage_type);
+        self
+    }
+
     pub fn build(self) -> Weapon {
         self.weapon
     }
@@ -275,6 +416,16 @@ pub enum WeaponProficiency {
     Proficient,
 }
 
+impl WeaponProficiency {
+    pub fn all() -> Vec<WeaponProficiency> {
+        vec![
+            WeaponProficiency::None,
+            WeaponProficiency::HalfProficient,
+            WeaponProficiency::Proficient,
+        ]
+    }
+}
+
 impl From<WeaponProficiency> for SkillProficiency {
     fn from(prof: WeaponProficiency) -> Self {
         match prof {
@@ -310,27 +461,80 @@ impl WeaponProficiencies {
     pub fn set(&mut self, weapon_type: WeaponType, proficiency: WeaponProficiency) {
         self.proficiencies.insert(weapon_type, proficiency);
     }
+
+    /// Sets the proficiency for every weapon in `category`, like a class's "simple weapons".
+    pub fn set_category(&mut self, category: WeaponCategory, proficiency: WeaponProficiency) {
+        for weapon_type in category.weapon_types() {
+            self.set(weapon_type, proficiency);
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct Armor {
     pub ac_bonus: u32,
     pub stealth_disadvantage: bool,
+    /// Magic armor's +1, +2, or +3, added to the wearer's AC on top of their base armor class.
+    #[serde(default)]
+    pub enchantment_bonus: i32,
+    /// Heavy armor's minimum Strength score. Under the optional encumbrance rule, a wearer below
+    /// it has their speed reduced by 10 feet.
+    #[serde(default)]
+    pub strength_requirement: u32,
+    /// Where it's worn. Only armor in the body slots counts as wearing armor for features like
+    /// Unarmored Defense; see [`EquipSlot::is_armor`].
+    #[serde(default)]
+    pub slot: EquipSlot,
 }
 
 impl Armor {
-    #[cfg(test)]
-    pub fn test_armor() -> Self {
+    pub fn new(ac_bonus: u32) -> Self {
         Self {
-            ac_bonus: 2,
+            ac_bonus,
             stealth_disadvantage: false,
+            enchantment_bonus: 0,
+            strength_requirement: 0,
+            slot: EquipSlot::default(),
         }
     }
+
+    /// A shield, adding `ac_bonus` on top of whatever the wearer's AC is worked out from.
+    pub fn shield(ac_bonus: u32) -> Self {
+        Self::new(ac_bonus).slot(EquipSlot::Shield)
+    }
+
+    pub fn stealth_disadvantage(mut self) -> Self {
+        self.stealth_disadvantage = true;
+        self
+    }
+
+    pub fn enchantment_bonus(mut self, bonus: i32) -> Self {
+        self.enchantment_bonus = bonus;
+        self
+    }
+
+    pub fn strength_requirement(mut self, strength: u32) -> Self {
+        self.strength_requirement = strength;
+        self
+    }
+
+    pub fn slot(mut self, slot: EquipSlot) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn test_armor() -> Self {
+        Self::new(2)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug, Default, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
 pub enum EquipSlot {
     Head,
+    #[default]
     Chest,
     Legs,
     Feet,
@@ -339,6 +543,25 @@ pub enum EquipSlot {
     Accessory,
 }
 
+impl EquipSlot {
+    pub fn all() -> Vec<EquipSlot> {
+        vec![
+            EquipSlot::Head,
+            EquipSlot::Chest,
+            EquipSlot::Legs,
+            EquipSlot::Feet,
+            EquipSlot::Hands,
+            EquipSlot::Shield,
+            EquipSlot::Accessory,
+        ]
+    }
+
+    /// Whether something worn here is armor, as opposed to a shield or an accessory like a cloak.
+    pub fn is_armor(&self) -> bool {
+        !matches!(self, EquipSlot::Shield | EquipSlot::Accessory)
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct EquippedItems {
     pub items: BTreeSet<ItemId>,
@@ -0,0 +1,147 @@
+//! Ready-made magic items, so a party can be geared up without building every item by hand.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        dice::RollPlan,
+        items::{Armor, EquipSlot, Item, ItemId, ItemInner, WeaponBuilder, WeaponType},
+    },
+    simulation::state::State,
+};
+
+/// An item that can be added to any state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub inner: ItemInner,
+    #[serde(default)]
+    pub save_bonus: i32,
+}
+
+impl ItemTemplate {
+    /// +1, +2, and +3 versions of common weapons, armor, and shields, plus a cloak of protection.
+    ///
+    /// Weapon damage covers only the weapon's dice and enchantment, so add the wielder's ability
+    /// modifier after adding one to a state. Magic armor only adds its enchantment to the wearer's
+    /// AC, since actors' base armor class already accounts for what they wear.
+    pub fn magic_items() -> Vec<ItemTemplate> {
+        let weapons = [
+            (WeaponType::Dagger, "1d4", None),
+            (WeaponType::Shortsword, "1d6", None),
+            (WeaponType::Rapier, "1d8", None),
+            (WeaponType::Longsword, "1d8", None),
+            (WeaponType::Warhammer, "1d8", None),
+            (WeaponType::Greatsword, "2d6", None),
+            (WeaponType::Greataxe, "1d12", None),
+            (WeaponType::Shortbow, "1d6", Some(80)),
+            (WeaponType::Longbow, "1d8", Some(150)),
+            (WeaponType::CrossbowLight, "1d8", Some(80)),
+        ];
+        let armor = [("Armor", Armor::new(0)), ("Shield", Armor::shield(2))];
+
+        let mut templates = Vec::new();
+        for bonus in 1..=3 {
+            for (weapon_type, dice, range) in weapons {
+                let damage: RollPlan = dice.into();
+                let mut critical_damage = damage;
+                critical_damage.num_dice *= 2;
+                let mut weapon = WeaponBuilder::new(weapon_type)
+                    .damage(damage)
+                    .critical_damage(critical_damage)
+                    .enchantment_bonus(bonus);
+                if let Some(range) = range {
+                    weapon = weapon.range(range);
+                }
+                templates.push(ItemTemplate {
+                    name: format!("{weapon_type:?} +{bonus}"),
+                    inner: ItemInner::Weapon(weapon.build()),
+                    save_bonus: 0,
+                });
+            }
+            for (name, armor) in &armor {
+                templates.push(ItemTemplate {
+                    name: format!("{name} +{bonus}"),
+                    inner: ItemInner::Armor(armor.clone().enchantment_bonus(bonus)),
+                    save_bonus: 0,
+                });
+            }
+        }
+        templates.push(ItemTemplate {
+            name: "Cloak of Protection".to_string(),
+            inner: ItemInner::Armor(
+                Armor::new(0)
+                    .enchantment_bonus(1)
+                    .slot(EquipSlot::Accessory),
+            ),
+            save_bonus: 1,
+        });
+        templates
+    }
+}
+
+impl State {
+    pub fn add_item_template(&mut self, template: &ItemTemplate) -> ItemId {
+        let id = self.ids.allocate_item_id();
+        self.items.insert(
+            id,
+            Item {
+                id,
+                name: template.name.clone(),
+                inner: template.inner.clone(),
+                save_bonus: template.save_bonus,
+                weight: 0,
+                metadata: Default::default(),
+            },
+        );
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{actor::ActorBuilder, saves::SavingThrow, stats::Stat};
+
+    #[test]
+    fn test_magic_item_templates() -> anyhow::Result<()> {
+        let templates = ItemTemplate::magic_items();
+        let find = |name: &str| {
+            templates
+                .iter()
+                .find(|template| template.name == name)
+                .ok_or_else(|| anyhow::anyhow!("No template named {name}"))
+        };
+
+        let mut state = State::new();
+        let sword = state.add_item_template(find("Longsword +2")?);
+        let cloak = state.add_item_template(find("Cloak of Protection")?);
+        let mut paladin = ActorBuilder::new("Paladin").stat(Stat::Wisdom, 10).build();
+        paladin.give_item(sword, 1);
+        paladin.equipped_items.equip(cloak);
+        let paladin = state.add_actor(paladin);
+        let paladin = state.get_actor(paladin).unwrap();
+
+        let ItemInner::Weapon(weapon) = &state.items[&sword].inner else {
+            anyhow::bail!("Longsword +2 isn't a weapon");
+        };
+        assert_eq!(
+            paladin
+                .plan_attack_roll(weapon, Default::default())?
+                .modifier,
+            2
+        );
+        let critical = paladin.plan_weapon_damage(weapon, true);
+        assert_eq!((critical.num_dice, critical.die_size), (2, 8));
+        assert_eq!(critical.modifier, 2);
+        assert_eq!(paladin.effective_armor_class(&state), 11);
+        assert_eq!(state.saving_throw_modifier(paladin, SavingThrow::Wisdom), 1);
+
+        let ItemInner::Weapon(bow) = &find("Longbow +3")?.inner else {
+            anyhow::bail!("Longbow +3 isn't a weapon");
+        };
+        assert!(bow.is_ranged());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+/// Descriptive information about an actor or item that has no effect on the simulation.
+///
+/// Metadata is skipped when hashing, so it never causes otherwise identical states to be
+/// treated as distinct nodes in the state tree.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Where the stat block comes from, e.g. "Monster Manual p. 166".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Challenge rating, kept as written (e.g. "1/4").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge_rating: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Path to a portrait image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub portrait: Option<String>,
+}
+
+impl std::hash::Hash for Metadata {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+/// What a scenario file is, for whoever opens it next. Shown when the scenario is loaded, and
+/// like [`Metadata`], never part of the combat state.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScenarioMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// What the encounter is, how to run it, and anything else worth knowing before simulating it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The character level the encounter is meant for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub party_level: Option<u32>,
+}
+
+impl ScenarioMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// A header like "Goblin Ambush, by Sam (party level 3)", followed by the description on
+    /// the lines after it. Writes nothing if there's nothing to show.
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+        write!(
+            f,
+            "{}",
+            self.title.as_deref().unwrap_or("Untitled scenario")
+        )?;
+        if let Some(author) = &self.author {
+            write!(f, ", by {}", author)?;
+        }
+        if let Some(level) = self.party_level {
+            write!(f, " (party level {})", level)?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, "\n{}", description.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::hash::Hash for ScenarioMetadata {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScenarioMetadata;
+    use crate::{
+        rules::actor::ActorBuilder,
+        simulation::{state::State, state_tree::StateHash},
+    };
+
+    #[test]
+    fn test_metadata_ignored_by_hash() {
+        let mut state = State::new();
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").build());
+        let before = StateHash::hash_state(&state);
+
+        let metadata = &mut state.get_actor_mut(goblin).unwrap().metadata;
+        metadata.challenge_rating = Some("1/4".to_string());
+        metadata.notes = Some("Ambushes from the treeline".to_string());
+        assert_eq!(StateHash::hash_state(&state), before);
+
+        // but it survives a round trip
+        let json = serde_json::to_string(&state).unwrap();
+        let loaded: State = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, state);
+    }
+
+    #[test]
+    fn test_scenario_metadata() {
+        let mut state = State::new();
+        let before = StateHash::hash_state(&state);
+        let mut header = String::new();
+        state.scenario.pretty_print(&mut header).unwrap();
+        assert!(header.is_empty());
+
+        state.scenario = ScenarioMetadata {
+            title: Some("Goblin Ambush".to_string()),
+            author: Some("Sam".to_string()),
+            description: Some("Four goblins in the trees.\n".to_string()),
+            party_level: Some(3),
+        };
+        assert_eq!(StateHash::hash_state(&state), before);
+        state.scenario.pretty_print(&mut header).unwrap();
+        assert_eq!(
+            header,
+            "Goblin Ambush, by Sam (party level 3)\nFour goblins in the trees."
+        );
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(State::from_json(&json).unwrap(), state);
+    }
+}
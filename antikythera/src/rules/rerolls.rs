@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rules::actor::ActorId;
+
+/// A limited supply of d20 rerolls an actor can spend on a failed roll, keeping the new result.
+///
+/// Actors spend them according to their policy's
+/// [`RerollHeuristic`](crate::simulation::policy::RerollHeuristic), cheapest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum RerollResource {
+    /// Inspiration awarded by the DM.
+    Inspiration,
+    /// A luck point from the Lucky feat.
+    LuckPoint,
+}
+
+impl RerollResource {
+    pub fn all() -> &'static [RerollResource] {
+        &[RerollResource::Inspiration, RerollResource::LuckPoint]
+    }
+}
+
+/// What a d20 roll is for, which decides whether it's worth a reroll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum D20Test {
+    Attack { target: ActorId },
+    SavingThrow,
+    AbilityCheck,
+}
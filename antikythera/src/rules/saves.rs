@@ -1,8 +1,18 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use serde::{Deserialize, Serialize};
 
-use crate::rules::stats::Stat;
+use crate::{
+    rules::{
+        actor::{Actor, ActorId},
+        cover::Cover,
+        dice::{RollPlan, RollSettings},
+        features::Feature,
+        items::ItemId,
+        stats::Stat,
+    },
+    simulation::state::State,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum SavingThrow {
@@ -52,6 +62,9 @@ impl SavingThrow {
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct SavingThrowProficiencies {
     save_proficiencies: BTreeMap<SavingThrow, bool>,
+    /// Saves that add double the proficiency bonus. Expertise implies proficiency.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    save_expertise: BTreeSet<SavingThrow>,
 }
 
 impl SavingThrowProficiencies {
@@ -65,6 +78,214 @@ impl SavingThrowProficiencies {
     }
 
     pub fn get(&self, save: SavingThrow) -> bool {
-        *self.save_proficiencies.get(&save).unwrap_or(&false)
+        *self.save_proficiencies.get(&save).unwrap_or(&false) || self.has_expertise(save)
+    }
+
+    pub fn set_expertise(&mut self, save: SavingThrow, expertise: bool) {
+        if expertise {
+            self.save_expertise.insert(save);
+        } else {
+            self.save_expertise.remove(&save);
+        }
+    }
+
+    pub fn has_expertise(&self, save: SavingThrow) -> bool {
+        self.save_expertise.contains(&save)
+    }
+}
+
+/// Where part of a saving throw modifier comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SaveModifierSource {
+    /// The save's ability modifier.
+    Ability(Stat),
+    /// The proficiency bonus, doubled with expertise.
+    Proficiency,
+    /// An equipped item's [`Item::save_bonus`](crate::rules::items::Item::save_bonus).
+    Item(ItemId),
+    /// An ally's aura, such as [`Feature::AuraOfProtection`].
+    Aura(ActorId),
+    /// Cover between the actor and whoever the effect comes from, for Dexterity saves.
+    Cover(Cover),
+}
+
+/// One term of a saving throw modifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SaveModifier {
+    pub source: SaveModifierSource,
+    pub value: i32,
+}
+
+impl State {
+    /// Every bonus and penalty to `actor`'s saving throw, itemized: its own ability and
+    /// proficiency (see [`Actor::saving_throw_modifiers`]), then its equipped items, then the
+    /// strongest aura it stands in. The ability term is always there, even at 0; other zero terms
+    /// are left out.
+    pub fn saving_throw_modifiers(&self, actor: &Actor, save: SavingThrow) -> Vec<SaveModifier> {
+        let mut modifiers = actor.saving_throw_modifiers(save);
+        for item_id in &actor.equipped_items.items {
+            if let Some(item) = self.items.get(item_id)
+                && item.save_bonus != 0
+            {
+                modifiers.push(SaveModifier {
+                    source: SaveModifierSource::Item(*item_id),
+                    value: item.save_bonus,
+                });
+            }
+        }
+        // auras of the same kind don't stack, so only the strongest one counts
+        let aura = self
+            .actors
+            .values()
+            .filter(|ally| {
+                ally.group == actor.group
+                    && ally.health > 0
+                    && ally.has_feature(Feature::AuraOfProtection)
+            })
+            .map(|ally| (ally.stat_modifier(Stat::Charisma).max(1), ally.id))
+            .max_by_key(|(value, id)| (*value, std::cmp::Reverse(*id)))
+            .map(|(value, id)| SaveModifier {
+                source: SaveModifierSource::Aura(id),
+                value,
+            });
+        modifiers.extend(aura);
+        modifiers
+    }
+
+    /// [`State::saving_throw_modifiers`] against an effect that comes from `from`, which adds the
+    /// cover between them to Dexterity saves.
+    pub fn saving_throw_modifiers_from(
+        &self,
+        actor: &Actor,
+        save: SavingThrow,
+        from: Option<ActorId>,
+    ) -> Vec<SaveModifier> {
+        let mut modifiers = self.saving_throw_modifiers(actor, save);
+        if save == SavingThrow::Dexterity
+            && let Some(from) = from
+        {
+            let cover = self.encounter.cover_between(from, actor.id);
+            if cover.dex_save_bonus() != 0 {
+                modifiers.push(SaveModifier {
+                    source: SaveModifierSource::Cover(cover),
+                    value: cover.dex_save_bonus(),
+                });
+            }
+        }
+        modifiers
+    }
+
+    /// The total of [`State::saving_throw_modifiers`].
+    pub fn saving_throw_modifier(&self, actor: &Actor, save: SavingThrow) -> i32 {
+        self.saving_throw_modifier_from(actor, save, None)
+    }
+
+    /// The total of [`State::saving_throw_modifiers_from`].
+    pub fn saving_throw_modifier_from(
+        &self,
+        actor: &Actor,
+        save: SavingThrow,
+        from: Option<ActorId>,
+    ) -> i32 {
+        self.saving_throw_modifiers_from(actor, save, from)
+            .iter()
+            .map(|modifier| modifier.value)
+            .sum()
+    }
+
+    /// The d20 roll for `actor`'s saving throw against an effect from `from`, if it comes from
+    /// anyone in particular.
+    pub fn plan_saving_throw(
+        &self,
+        actor: &Actor,
+        save: SavingThrow,
+        from: Option<ActorId>,
+        roll_settings: RollSettings,
+    ) -> RollPlan {
+        RollPlan {
+            num_dice: 1,
+            die_size: 20,
+            modifier: self.saving_throw_modifier_from(actor, save, from),
+            settings: roll_settings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{
+        actor::ActorBuilder,
+        items::{Armor, ItemInner},
+    };
+
+    #[test]
+    fn test_save_modifier_sources() {
+        let mut state = State::new();
+        let cloak = state.add_item("Cloak of Protection", ItemInner::Armor(Armor::new(1)));
+        state.items.get_mut(&cloak).unwrap().save_bonus = 1;
+
+        let mut rogue = ActorBuilder::new("Rogue")
+            .stat(Stat::Dexterity, 16)
+            .level(5)
+            .saving_throw_expertise(SavingThrow::Dexterity)
+            .build();
+        rogue.give_item(cloak, 1);
+        let rogue = state.add_actor(rogue);
+        let paladin = state.add_actor(
+            ActorBuilder::new("Paladin")
+                .stat(Stat::Charisma, 16)
+                .feature(Feature::AuraOfProtection)
+                .build(),
+        );
+        let modifier = |state: &State| {
+            let rogue = state.get_actor(rogue).unwrap();
+            state.saving_throw_modifier(rogue, SavingThrow::Dexterity)
+        };
+
+        // +3 DEX, +6 for double proficiency at level 5, +3 from the paladin's aura
+        let actor = state.get_actor(rogue).unwrap();
+        assert_eq!(actor.saving_throw_modifier(SavingThrow::Dexterity), 9);
+        assert_eq!(modifier(&state), 12);
+
+        // the cloak only counts while worn
+        state
+            .actors
+            .get_mut(&rogue)
+            .unwrap()
+            .equipped_items
+            .equip(cloak);
+        assert_eq!(modifier(&state), 13);
+
+        // and the aura only while the paladin is conscious
+        state.actors.get_mut(&paladin).unwrap().modify_health(-100);
+        assert_eq!(modifier(&state), 10);
+        let rogue_actor = state.get_actor(rogue).unwrap();
+        assert!(
+            state
+                .saving_throw_modifiers(rogue_actor, SavingThrow::Dexterity)
+                .iter()
+                .all(|modifier| modifier.source != SaveModifierSource::Aura(paladin))
+        );
+
+        // cover against the paladin helps against the paladin's effects, and only Dexterity saves
+        state.encounter.set_cover(paladin, rogue, Cover::Half);
+        let rogue = state.get_actor(rogue).unwrap();
+        let dex_from = |from| state.saving_throw_modifier_from(rogue, SavingThrow::Dexterity, from);
+        assert_eq!(dex_from(Some(paladin)), 12);
+        assert_eq!(dex_from(None), 10);
+        assert_eq!(dex_from(Some(rogue.id)), 10);
+        assert!(
+            state
+                .saving_throw_modifiers_from(rogue, SavingThrow::Dexterity, Some(paladin))
+                .contains(&SaveModifier {
+                    source: SaveModifierSource::Cover(Cover::Half),
+                    value: 2,
+                })
+        );
+        assert_eq!(
+            state.saving_throw_modifier_from(rogue, SavingThrow::Wisdom, Some(paladin)),
+            state.saving_throw_modifier(rogue, SavingThrow::Wisdom)
+        );
     }
 }
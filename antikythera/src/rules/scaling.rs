@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        actor::{Actor, ActorId},
+        features::Feature,
+        stats::Stat,
+    },
+    simulation::state::State,
+};
+
+/// How an actor's definition changes with level, for simulating the same character at
+/// different points in a campaign.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LevelScaling {
+    /// Size of the actor's hit die. HP is the maximum roll at level 1 plus the fixed average
+    /// (half the die, plus one) at every level after, with the CON modifier added each level.
+    pub hit_die: u32,
+    /// The level each feature is gained at. Features not listed here are always available.
+    #[serde(default)]
+    pub features: BTreeMap<Feature, u32>,
+}
+
+impl LevelScaling {
+    pub fn new(hit_die: u32) -> Self {
+        Self {
+            hit_die,
+            features: BTreeMap::new(),
+        }
+    }
+
+    pub fn feature_at(mut self, feature: Feature, level: u32) -> Self {
+        self.features.insert(feature, level);
+        self
+    }
+
+    /// Max HP at `level` for a creature with the given CON modifier. Every level adds at least 1.
+    pub fn max_health(&self, level: u32, con_modifier: i32) -> i32 {
+        let first = (self.hit_die as i32 + con_modifier).max(1);
+        let per_level = (self.hit_die as i32 / 2 + 1 + con_modifier).max(1);
+        first + per_level * level.saturating_sub(1) as i32
+    }
+}
+
+impl Actor {
+    /// A copy of this actor at a different level, at full health.
+    ///
+    /// Proficiency follows the level automatically. With [`Actor::scaling`] set, max HP is
+    /// recomputed from the hit die and features are granted or removed according to the
+    /// levels they're gained at. Without it, max HP scales in proportion to the level and
+    /// features are left alone. Fails if one of the actor's formulas doesn't hold at the new level.
+    pub fn scaled_to_level(&self, level: u32) -> anyhow::Result<Actor> {
+        let level = level.max(1);
+        let mut actor = self.clone();
+        actor.level = level;
+
+        if let Some(scaling) = &self.scaling {
+            actor.max_health = scaling.max_health(level, self.stat_modifier(Stat::Constitution));
+            for (feature, gained_at) in &scaling.features {
+                if level >= *gained_at {
+                    actor.features.insert(*feature);
+                } else {
+                    actor.features.remove(feature);
+                }
+            }
+        } else {
+            let per_level = self.max_health as f64 / self.level.max(1) as f64;
+            actor.max_health = ((per_level * level as f64).round() as i32).max(1);
+        }
+
+        let features = actor.features.clone();
+        actor
+            .features_used
+            .retain(|feature| features.contains(feature));
+        actor
+            .apply_formulas()
+            .map_err(|e| anyhow::anyhow!("Invalid formula on {}: {}", actor.name, e))?;
+        actor.health = actor.max_health;
+        actor.overkill = 0;
+        Ok(actor)
+    }
+}
+
+impl State {
+    /// A copy of this state with the given actors scaled to `level`. See [`Actor::scaled_to_level`].
+    pub fn scaled_to_level(&self, actors: &BTreeSet<ActorId>, level: u32) -> anyhow::Result<State> {
+        let mut state = self.clone();
+        for actor in state.actors.values_mut() {
+            if actors.contains(&actor.id) {
+                *actor = actor.scaled_to_level(level)?;
+            }
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{actor::ActorBuilder, saves::SavingThrow};
+
+    #[test]
+    fn test_scaled_to_level() -> anyhow::Result<()> {
+        let fighter = ActorBuilder::new("Fighter")
+            .stat(Stat::Constitution, 14)
+            .saving_throw_proficiency(SavingThrow::Constitution, true)
+            .scaling(
+                LevelScaling::new(10)
+                    .feature_at(Feature::Defense, 1)
+                    .feature_at(Feature::RelentlessEndurance, 5),
+            )
+            .build();
+
+        let level_1 = fighter.scaled_to_level(1)?;
+        assert_eq!(level_1.max_health, 12);
+        assert_eq!(level_1.health, 12);
+        assert!(level_1.has_feature(Feature::Defense));
+        assert!(!level_1.has_feature(Feature::RelentlessEndurance));
+
+        let level_5 = level_1.scaled_to_level(5)?;
+        assert_eq!(level_5.max_health, 12 + 4 * 8);
+        assert_eq!(level_5.proficiency_bonus(), 3);
+        assert_eq!(level_5.saving_throw_modifier(SavingThrow::Constitution), 5);
+        assert!(level_5.has_feature(Feature::RelentlessEndurance));
+
+        // without scaling info, HP scales with level and features stay put
+        let goblin = ActorBuilder::new("Goblin")
+            .level(2)
+            .max_health(14)
+            .feature(Feature::Archery)
+            .build();
+        let big_goblin = goblin.scaled_to_level(6)?;
+        assert_eq!(big_goblin.max_health, 42);
+        assert!(big_goblin.has_feature(Feature::Archery));
+
+        // a formula that only breaks at the new level fails the scaling, rather than leaving the
+        // old value in place
+        let mut state = State::new();
+        let dodger = state.add_actor(
+            ActorBuilder::new("Dodger")
+                .formula("armor_class", "12 - level")
+                .build(),
+        );
+        assert_eq!(
+            state.scaled_to_level(&BTreeSet::from([dodger]), 5)?.actors[&dodger].armor_class,
+            7
+        );
+        let error = state
+            .scaled_to_level(&BTreeSet::from([dodger]), 20)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Invalid formula on Dodger"));
+        Ok(())
+    }
+}
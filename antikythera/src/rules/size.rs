@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Size {
+    Tiny,
+    Small,
+    #[default]
+    Medium,
+    Large,
+    Huge,
+    Gargantuan,
+}
+
+impl Size {
+    pub fn all() -> Vec<Size> {
+        vec![
+            Size::Tiny,
+            Size::Small,
+            Size::Medium,
+            Size::Large,
+            Size::Huge,
+            Size::Gargantuan,
+        ]
+    }
+
+    /// How many size categories larger `self` is than `other` (negative if smaller).
+    pub fn steps_larger_than(&self, other: Size) -> i32 {
+        *self as i32 - other as i32
+    }
+
+    /// Grapple and shove targets can be at most one size larger than the attacker.
+    pub fn can_grapple_or_shove(&self, target: Size) -> bool {
+        target.steps_larger_than(*self) <= 1
+    }
+
+    /// A mount must be at least one size larger than its rider.
+    pub fn can_carry_rider(&self, rider: Size) -> bool {
+        self.steps_larger_than(rider) >= 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_restrictions() {
+        assert!(Size::Medium.can_grapple_or_shove(Size::Large));
+        assert!(!Size::Medium.can_grapple_or_shove(Size::Huge));
+        assert!(Size::Small.can_grapple_or_shove(Size::Tiny));
+
+        assert!(Size::Large.can_carry_rider(Size::Medium));
+        assert!(!Size::Medium.can_carry_rider(Size::Medium));
+    }
+}
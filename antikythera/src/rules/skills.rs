@@ -157,6 +157,7 @@ mod tests {
                 minimum_die_value: None,
                 maximum_die_value: None,
                 reroll_dice_below: None,
+                reroll_once_below: None,
             },
         };
         assert_eq!(check.total_modifier(), 5);
@@ -174,6 +175,7 @@ mod tests {
                 minimum_die_value: None,
                 maximum_die_value: None,
                 reroll_dice_below: None,
+                reroll_once_below: None,
             },
         };
         let roll = check.roll();
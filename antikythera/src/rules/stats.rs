@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+use crate::simulation::roller::Roller;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Stat {
     Strength,
     Dexterity,
@@ -23,6 +25,54 @@ impl Stat {
     }
 }
 
+/// A temporary change to one stat, such as a Ray of Enfeeblement-style strength penalty. It
+/// lasts until removed; ones that run out after a few rounds are applied as
+/// [`TimedEffect`](crate::simulation::timed_effects::TimedEffect)s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash)]
+pub struct StatModifier {
+    pub stat: Stat,
+    pub delta: i32,
+}
+
+/// The lowest and highest an ability score can be, 1 to 30 unless set otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StatBounds {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl Default for StatBounds {
+    fn default() -> Self {
+        Self { min: 1, max: 30 }
+    }
+}
+
+impl StatBounds {
+    pub fn new(min: u32, max: u32) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            min <= max,
+            "Minimum ability score {} is above the maximum {}",
+            min,
+            max
+        );
+        Ok(Self { min, max })
+    }
+
+    pub fn contains(&self, value: u32) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+
+    pub fn clamp(&self, value: u32) -> u32 {
+        value.clamp(self.min, self.max.max(self.min))
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// An actor's ability scores: a base value per stat, plus a stack of temporary modifiers.
+/// Scores stay within the stats' [`StatBounds`], both as set and with modifiers applied.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct Stats {
     strength: u32,
@@ -31,6 +81,10 @@ pub struct Stats {
     intelligence: u32,
     wisdom: u32,
     charisma: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    modifiers: Vec<StatModifier>,
+    #[serde(default, skip_serializing_if = "StatBounds::is_default")]
+    bounds: StatBounds,
 }
 
 impl Default for Stats {
@@ -42,6 +96,8 @@ impl Default for Stats {
             intelligence: 10,
             wisdom: 10,
             charisma: 10,
+            modifiers: Vec::new(),
+            bounds: StatBounds::default(),
         }
     }
 }
@@ -52,7 +108,19 @@ impl Stats {
         self
     }
 
+    /// The stat's current value: its base value with every modifier applied, kept within bounds.
     pub fn get(&self, stat: Stat) -> u32 {
+        let delta = self
+            .modifiers
+            .iter()
+            .filter(|modifier| modifier.stat == stat)
+            .fold(0i32, |total, modifier| total.saturating_add(modifier.delta));
+        self.bounds
+            .clamp(self.base(stat).saturating_add_signed(delta))
+    }
+
+    /// The stat's value without any modifiers.
+    pub fn base(&self, stat: Stat) -> u32 {
         match stat {
             Stat::Strength => self.strength,
             Stat::Dexterity => self.dexterity,
@@ -63,6 +131,8 @@ impl Stats {
         }
     }
 
+    /// The stat's base value, for permanent changes. Unlike [`Stats::set`], this doesn't keep it
+    /// within bounds; [`Stats::validate`] catches anything left out of them.
     pub fn get_mut(&mut self, stat: Stat) -> &mut u32 {
         match stat {
             Stat::Strength => &mut self.strength,
@@ -74,13 +144,168 @@ impl Stats {
         }
     }
 
+    /// Sets the stat's base value, clamped to the bounds.
     pub fn set(&mut self, stat: Stat, value: u32) {
-        *self.get_mut(stat) = value;
+        *self.get_mut(stat) = self.bounds.clamp(value);
+    }
+
+    pub fn bounds(&self) -> StatBounds {
+        self.bounds
+    }
+
+    /// Changes the bounds, clamping every base value to the new ones.
+    pub fn set_bounds(&mut self, bounds: StatBounds) {
+        self.bounds = bounds;
+        for stat in Stat::all() {
+            self.set(stat, self.base(stat));
+        }
+    }
+
+    /// Checks that every base value is within bounds, as it may not be in a hand-edited file.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        StatBounds::new(self.bounds.min, self.bounds.max)?;
+        for stat in Stat::all() {
+            let value = self.base(stat);
+            anyhow::ensure!(
+                self.bounds.contains(value),
+                "{:?} {} is outside {}..={}",
+                stat,
+                value,
+                self.bounds.min,
+                self.bounds.max
+            );
+        }
+        Ok(())
     }
 
     pub fn modifier(&self, stat: Stat) -> i32 {
         self.get(stat) as i32 / 2 - 5
     }
+
+    pub fn modifiers(&self) -> &[StatModifier] {
+        &self.modifiers
+    }
+
+    pub fn add_modifier(&mut self, modifier: StatModifier) {
+        self.modifiers.push(modifier);
+    }
+
+    /// Removes one copy of `modifier`, leaving any others like it in place.
+    pub fn remove_modifier(&mut self, modifier: &StatModifier) {
+        if let Some(index) = self.modifiers.iter().position(|ours| ours == modifier) {
+            self.modifiers.remove(index);
+        }
+    }
+}
+
+/// The standard array of ability scores, highest first.
+pub const STANDARD_ARRAY: [u32; 6] = [15, 14, 13, 12, 10, 8];
+
+impl Stats {
+    /// The standard array, with the highest scores going to the stats in `priority`, in order.
+    /// Stats left out of `priority` take what's left over in the usual order, Strength first.
+    pub fn standard_array(priority: &[Stat]) -> Stats {
+        let mut order: Vec<Stat> = Vec::with_capacity(6);
+        for stat in priority.iter().copied().chain(Stat::all()) {
+            if !order.contains(&stat) {
+                order.push(stat);
+            }
+        }
+        let mut stats = Stats::default();
+        for (stat, score) in order.into_iter().zip(STANDARD_ARRAY) {
+            stats.set(stat, score);
+        }
+        stats
+    }
+
+    /// Rolls a score for each stat with `method`, in order from Strength to Charisma.
+    pub fn rolled(roller: &mut Roller, method: StatRollMethod) -> Stats {
+        let mut stats = Stats::default();
+        for stat in Stat::all() {
+            stats.set(stat, method.roll(roller));
+        }
+        stats
+    }
+}
+
+/// How to roll up a single ability score.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatRollMethod {
+    /// Roll 4d6 and add up the highest three.
+    #[default]
+    FourD6DropLowest,
+    /// Roll 3d6 and take what you get.
+    ThreeD6,
+}
+
+impl StatRollMethod {
+    pub fn roll(&self, roller: &mut Roller) -> u32 {
+        match self {
+            StatRollMethod::FourD6DropLowest => {
+                let rolls = [roller.d(6), roller.d(6), roller.d(6), roller.d(6)];
+                rolls.iter().sum::<u32>() - rolls.iter().min().unwrap()
+            }
+            StatRollMethod::ThreeD6 => roller.d(6) + roller.d(6) + roller.d(6),
+        }
+    }
+}
+
+/// Ability scores bought with points: every stat starts at 8, and can be raised as high as 15
+/// for a total of at most 27 points.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PointBuy {
+    stats: Stats,
+}
+
+impl PointBuy {
+    pub const BUDGET: u32 = 27;
+
+    /// Buys the listed scores, leaving every other stat at 8. Fails if a score is outside 8–15 or
+    /// the total goes over budget.
+    pub fn new(scores: &[(Stat, u32)]) -> anyhow::Result<Self> {
+        let mut stats = Stats::default();
+        for stat in Stat::all() {
+            stats.set(stat, 8);
+        }
+        for &(stat, score) in scores {
+            anyhow::ensure!(
+                Self::cost(score).is_some(),
+                "{:?} of {} can't be bought; scores go from 8 to 15",
+                stat,
+                score
+            );
+            stats.set(stat, score);
+        }
+        let buy = Self { stats };
+        anyhow::ensure!(
+            buy.points_spent() <= Self::BUDGET,
+            "These scores cost {} points, over the budget of {}",
+            buy.points_spent(),
+            Self::BUDGET
+        );
+        Ok(buy)
+    }
+
+    /// What raising a stat from 8 to `score` costs, or `None` if it can't be bought.
+    pub fn cost(score: u32) -> Option<u32> {
+        match score {
+            8..=13 => Some(score - 8),
+            14 => Some(7),
+            15 => Some(9),
+            _ => None,
+        }
+    }
+
+    pub fn points_spent(&self) -> u32 {
+        Stat::all()
+            .into_iter()
+            .filter_map(|stat| Self::cost(self.stats.base(stat)))
+            .sum()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
 }
 
 #[cfg(test)]
@@ -111,6 +336,33 @@ mod tests {
         assert_eq!(stats.modifier(Stat::Dexterity), 2);
     }
 
+    #[test]
+    fn test_stat_modifiers() {
+        let mut stats = Stats::default().with_stat(Stat::Strength, 16);
+        let weaken = StatModifier {
+            stat: Stat::Strength,
+            delta: -4,
+        };
+        stats.add_modifier(weaken);
+        stats.add_modifier(weaken);
+        stats.add_modifier(StatModifier {
+            stat: Stat::Strength,
+            delta: -100,
+        });
+        assert_eq!(stats.get(Stat::Strength), 1);
+        assert_eq!(stats.base(Stat::Strength), 16);
+        assert_eq!(stats.get(Stat::Dexterity), 10);
+
+        stats.modifiers.pop();
+        assert_eq!(stats.get(Stat::Strength), 8);
+        // removing one of two identical modifiers leaves the other
+        stats.remove_modifier(&weaken);
+        assert_eq!(stats.get(Stat::Strength), 12);
+        stats.remove_modifier(&weaken);
+        assert_eq!(stats.get(Stat::Strength), 16);
+        assert!(stats.modifiers().is_empty());
+    }
+
     #[test]
     fn test_stat_block_modifier() {
         let stats = Stats::default()
@@ -119,4 +371,104 @@ mod tests {
         assert_eq!(stats.modifier(Stat::Constitution), -1);
         assert_eq!(stats.modifier(Stat::Intelligence), 4);
     }
+
+    #[test]
+    fn test_modifier_table() {
+        let table = [
+            (1, -5),
+            (2, -4),
+            (3, -4),
+            (8, -1),
+            (9, -1),
+            (10, 0),
+            (11, 0),
+            (12, 1),
+            (19, 4),
+            (20, 5),
+            (29, 9),
+            (30, 10),
+        ];
+        for (score, modifier) in table {
+            let stats = Stats::default().with_stat(Stat::Wisdom, score);
+            assert_eq!(stats.modifier(Stat::Wisdom), modifier, "score {}", score);
+        }
+        for score in 1..=30 {
+            let stats = Stats::default().with_stat(Stat::Wisdom, score);
+            assert_eq!(
+                stats.modifier(Stat::Wisdom),
+                (score as i32 - 10).div_euclid(2),
+                "score {}",
+                score
+            );
+        }
+    }
+
+    #[test]
+    fn test_stat_bounds() -> anyhow::Result<()> {
+        let mut stats = Stats::default()
+            .with_stat(Stat::Strength, 40)
+            .with_stat(Stat::Dexterity, 0);
+        assert_eq!(stats.base(Stat::Strength), 30);
+        assert_eq!(stats.base(Stat::Dexterity), 1);
+        stats.add_modifier(StatModifier {
+            stat: Stat::Strength,
+            delta: 4,
+        });
+        assert_eq!(stats.get(Stat::Strength), 30);
+        stats.validate()?;
+
+        // tighter bounds pull existing scores in
+        stats.set_bounds(StatBounds::new(3, 18)?);
+        assert_eq!(stats.base(Stat::Strength), 18);
+        assert_eq!(stats.base(Stat::Dexterity), 3);
+        assert!(StatBounds::new(18, 3).is_err());
+
+        *stats.get_mut(Stat::Wisdom) = 25;
+        let error = stats.validate().unwrap_err().to_string();
+        assert_eq!(error, "Wisdom 25 is outside 3..=18");
+
+        let json = serde_json::to_string(&Stats::default())?;
+        assert!(!json.contains("bounds"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stat_generators() -> anyhow::Result<()> {
+        let wizard = Stats::standard_array(&[Stat::Intelligence, Stat::Constitution]);
+        assert_eq!(wizard.base(Stat::Intelligence), 15);
+        assert_eq!(wizard.base(Stat::Constitution), 14);
+        assert_eq!(wizard.base(Stat::Strength), 13);
+        assert_eq!(wizard.base(Stat::Charisma), 8);
+
+        let fighter = PointBuy::new(&[
+            (Stat::Strength, 15),
+            (Stat::Constitution, 15),
+            (Stat::Dexterity, 13),
+            (Stat::Wisdom, 10),
+        ])?;
+        assert_eq!(fighter.points_spent(), 25);
+        assert_eq!(fighter.stats().base(Stat::Intelligence), 8);
+        assert!(PointBuy::new(&[(Stat::Strength, 16)]).is_err());
+        assert!(
+            PointBuy::new(&[
+                (Stat::Strength, 15),
+                (Stat::Dexterity, 15),
+                (Stat::Wisdom, 15),
+                (Stat::Charisma, 9)
+            ])
+            .is_err()
+        );
+
+        let mut roller = Roller::scripted([6, 6, 6, 1, 2, 3, 4]);
+        assert_eq!(StatRollMethod::FourD6DropLowest.roll(&mut roller), 18);
+        assert_eq!(StatRollMethod::ThreeD6.roll(&mut roller), 9);
+        let rolled = Stats::rolled(&mut Roller::from_seed(5), StatRollMethod::default());
+        assert!(
+            Stat::all()
+                .into_iter()
+                .all(|stat| (3..=18).contains(&rolled.base(stat)))
+        );
+
+        Ok(())
+    }
 }
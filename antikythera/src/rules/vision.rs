@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Lighting {
+    #[default]
+    Bright,
+    Dim,
+    Darkness,
+}
+
+impl Lighting {
+    pub fn all() -> Vec<Lighting> {
+        vec![Lighting::Bright, Lighting::Dim, Lighting::Darkness]
+    }
+
+    /// Dim light lightly obscures an area, which imposes disadvantage on Perception checks that rely on sight.
+    pub fn lightly_obscured(&self) -> bool {
+        *self == Lighting::Dim
+    }
+
+    /// Darkness heavily obscures an area, which effectively blinds anyone trying to see into it.
+    pub fn heavily_obscured(&self) -> bool {
+        *self == Lighting::Darkness
+    }
+}
+
+/// Special senses, with the range of each in feet. A range of 0 means the actor lacks the sense.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Vision {
+    /// Within range, dim light counts as bright light and darkness as dim light.
+    #[serde(default)]
+    pub darkvision: u32,
+    /// Within range, the actor perceives its surroundings without relying on sight at all.
+    #[serde(default)]
+    pub blindsight: u32,
+}
+
+impl Vision {
+    /// The lighting as this actor perceives it at a target `distance` feet away.
+    /// `None` means distance isn't tracked, so every sense reaches the target.
+    pub fn perceived_lighting(&self, lighting: Lighting, distance: Option<u32>) -> Lighting {
+        let in_range = |range: u32| range > 0 && distance.is_none_or(|distance| distance <= range);
+        if in_range(self.blindsight) {
+            return Lighting::Bright;
+        }
+        match lighting {
+            Lighting::Dim if in_range(self.darkvision) => Lighting::Bright,
+            Lighting::Darkness if in_range(self.darkvision) => Lighting::Dim,
+            lighting => lighting,
+        }
+    }
+
+    /// Whether this actor can see something standing in `lighting` at `distance` feet.
+    pub fn can_see(&self, lighting: Lighting, distance: Option<u32>) -> bool {
+        !self
+            .perceived_lighting(lighting, distance)
+            .heavily_obscured()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vision_ranges() {
+        let human = Vision::default();
+        assert!(human.can_see(Lighting::Dim, Some(30)));
+        assert!(!human.can_see(Lighting::Darkness, Some(5)));
+
+        let dwarf = Vision {
+            darkvision: 60,
+            ..Default::default()
+        };
+        assert_eq!(
+            dwarf.perceived_lighting(Lighting::Darkness, Some(60)),
+            Lighting::Dim
+        );
+        assert_eq!(
+            dwarf.perceived_lighting(Lighting::Dim, None),
+            Lighting::Bright
+        );
+        assert!(!dwarf.can_see(Lighting::Darkness, Some(65)));
+
+        let ooze = Vision {
+            blindsight: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            ooze.perceived_lighting(Lighting::Darkness, Some(10)),
+            Lighting::Bright
+        );
+        assert!(!ooze.can_see(Lighting::Darkness, Some(15)));
+    }
+}
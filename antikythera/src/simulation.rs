@@ -1,8 +1,30 @@
+pub mod actor_file;
+pub mod classification;
+pub mod comparison;
+pub mod contribution;
+pub mod diff;
+pub mod encounter;
+pub mod environment;
+pub mod groups;
 pub mod hook;
 pub mod integration;
+pub mod messages;
+pub mod objectives;
+pub mod phases;
 pub mod policy;
+pub mod positioning;
 pub mod query;
+pub mod roll_statistics;
 pub mod roller;
+pub mod schema;
 pub mod state;
+pub mod state_condition;
 pub mod state_tree;
+pub mod summary;
+pub mod time_series;
+pub mod timed_effects;
+pub mod timeline;
 pub mod transition;
+pub mod turn_order;
+pub mod usage;
+pub mod weapon_stats;
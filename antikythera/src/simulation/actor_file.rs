@@ -0,0 +1,257 @@
+//! Single actors saved on their own, so a build can be shared between scenarios.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        actor::{Actor, ActorId},
+        conditions::Conditions,
+        items::{Item, ItemId},
+    },
+    simulation::{
+        schema::{SCHEMA_VERSION, migrate_items, schema_version},
+        state::State,
+    },
+};
+
+/// An actor along with every item it carries, independent of the state it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorFile {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub actor: Actor,
+    /// The items in the actor's inventory or equipped, keyed by their ID in the original state.
+    #[serde(default)]
+    pub items: BTreeMap<ItemId, Item>,
+}
+
+impl ActorFile {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        let version = schema_version(&value, "actor")?;
+        if let Some(items) = value.get_mut("items") {
+            migrate_items(items, version);
+        }
+        let mut file: ActorFile = serde_json::from_value(value)?;
+        file.schema_version = SCHEMA_VERSION;
+        Ok(file)
+    }
+}
+
+impl State {
+    /// Packs up an actor and its items. Anything that refers to other actors in this state, like
+    /// its mount, conditions, and per-actor target weights, is left behind. An actor following its
+    /// group's policy takes a copy of it along.
+    pub fn export_actor(&self, actor: ActorId) -> anyhow::Result<ActorFile> {
+        let actor = self
+            .get_actor(actor)
+            .ok_or_else(|| anyhow::anyhow!("Actor {} not found", actor.0))?;
+        let policy = self.policy_of(actor).clone();
+        let mut actor = actor.clone();
+        actor.policy = policy;
+        actor.mount = None;
+        actor.conditions = Conditions::default();
+        actor.policy.target_weights.clear();
+
+        let items = actor
+            .inventory
+            .items
+            .keys()
+            .chain(&actor.equipped_items.items)
+            .map(|id| {
+                self.items
+                    .get(id)
+                    .cloned()
+                    .map(|item| (*id, item))
+                    .ok_or_else(|| anyhow::anyhow!("Item {} not found", id.0))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(ActorFile {
+            schema_version: SCHEMA_VERSION,
+            actor,
+            items,
+        })
+    }
+
+    /// Adds an exported actor to this state. Items already in the state with the same name and
+    /// stats are reused; the rest are added under new IDs. An actor without a policy keeps it
+    /// empty and follows its new group's.
+    pub fn import_actor(&mut self, file: ActorFile) -> ActorId {
+        let ActorFile {
+            mut actor, items, ..
+        } = file;
+
+        let mut ids = BTreeMap::new();
+        for (old_id, mut item) in items {
+            let existing = self.items.values().find(|other| {
+                other.name == item.name
+                    && other.inner == item.inner
+                    && other.save_bonus == item.save_bonus
+                    && other.weight == item.weight
+            });
+            let new_id = match existing {
+                Some(other) => other.id,
+                None => {
+                    item.id = self.ids.allocate_item_id();
+                    let id = item.id;
+                    self.items.insert(id, item);
+                    id
+                }
+            };
+            ids.insert(old_id, new_id);
+        }
+
+        let remap = |id: ItemId| ids.get(&id).copied().unwrap_or(id);
+        actor.inventory.items = actor
+            .inventory
+            .items
+            .into_iter()
+            .map(|(id, quantity)| (remap(id), quantity))
+            .collect();
+        actor.equipped_items.items = actor.equipped_items.items.into_iter().map(remap).collect();
+
+        self.add_actor(actor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::{
+            actor::ActorBuilder,
+            items::{ItemInner, WeaponBuilder, WeaponType},
+        },
+        simulation::policy::Policy,
+        test_utils::brawl_policy,
+    };
+
+    #[test]
+    fn test_actor_round_trip() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let sword = WeaponBuilder::new(WeaponType::Longsword)
+            .damage("1d8+3")
+            .build();
+        let sword = state.add_item("Longsword", ItemInner::Weapon(sword));
+        let mut hero = ActorBuilder::new("Hero").max_health(30).build();
+        hero.give_item(sword, 1);
+        let hero = state.add_actor(hero);
+        let horse = state.add_actor(ActorBuilder::new("Horse").build());
+        state.actors.get_mut(&hero).unwrap().mount = Some(horse);
+
+        let json = serde_json::to_string(&state.export_actor(hero)?)?;
+        let file = ActorFile::from_json(&json)?;
+        assert_eq!(file.actor.mount, None);
+        assert_eq!(file.items.len(), 1);
+
+        // the other scenario already has a dagger, so the sword gets a new ID
+        let mut other = State::new();
+        other.add_item(
+            "Dagger",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Dagger).build()),
+        );
+        let imported = other.import_actor(file.clone());
+        let imported = other.get_actor(imported).unwrap();
+        assert_eq!(imported.name, "Hero");
+        assert_eq!(imported.max_health, 30);
+        let (&new_sword, &quantity) = imported.inventory.items.iter().next().unwrap();
+        assert_ne!(new_sword, sword);
+        assert_eq!(quantity, 1);
+        assert_eq!(other.items[&new_sword].name, "Longsword");
+
+        // importing again reuses the sword instead of adding another copy
+        other.import_actor(file.clone());
+        assert_eq!(other.items.len(), 2);
+        assert_eq!(other.actors.len(), 2);
+
+        // but a sword that differs only in its save bonus or weight is a different item
+        let mut heavy = file.clone();
+        heavy.items.values_mut().next().unwrap().weight = 30;
+        let imported = other.import_actor(heavy);
+        let heavy_sword = *other.actors[&imported]
+            .inventory
+            .items
+            .keys()
+            .next()
+            .unwrap();
+        assert_ne!(heavy_sword, new_sword);
+        assert_eq!(other.items[&heavy_sword].weight, 30);
+        let mut magic = file;
+        magic.items.values_mut().next().unwrap().save_bonus = 1;
+        let imported = other.import_actor(magic);
+        let magic_sword = *other.actors[&imported]
+            .inventory
+            .items
+            .keys()
+            .next()
+            .unwrap();
+        assert_ne!(magic_sword, new_sword);
+        assert_eq!(other.items[&magic_sword].save_bonus, 1);
+        assert_eq!(other.items.len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_policy() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        let mut json = serde_json::to_value(state.export_actor(hero)?)?;
+        json["actor"].as_object_mut().unwrap().remove("policy");
+        let file = ActorFile::from_json(&json.to_string())?;
+        assert!(file.actor.policy.is_empty());
+
+        // the empty policy is kept as it is, until the front end asks for an attacker
+        let mut other = State::new();
+        let imported = other.import_actor(file.clone());
+        assert!(other.actors[&imported].policy.is_empty());
+        other.fill_missing_policies();
+        assert_eq!(other.actors[&imported].policy, Policy::attacker());
+
+        // a group policy applies instead
+        let brawl = brawl_policy();
+        other.set_group_policy(0, brawl.clone());
+        let imported = other.import_actor(file);
+        other.fill_missing_policies();
+        assert_eq!(other.policy_of(&other.actors[&imported]), &brawl);
+
+        // the same goes for scenarios
+        let mut scenario = serde_json::to_value(&state)?;
+        scenario["actors"][hero.0.to_string()]
+            .as_object_mut()
+            .unwrap()
+            .remove("policy");
+        let loaded = State::from_json(&scenario.to_string())?;
+        assert!(loaded.actors[&hero].policy.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_file() -> anyhow::Result<()> {
+        for json in ["[]", "null", "3", "\"actor\""] {
+            assert!(ActorFile::from_json(json).is_err(), "{}", json);
+        }
+
+        // a weapon whose damage can't be rolled imports, but the scenario no longer validates
+        let mut state = State::new();
+        let sword = WeaponBuilder::new(WeaponType::Longsword)
+            .damage("1d8+3")
+            .build();
+        let sword = state.add_item("Sword", ItemInner::Weapon(sword));
+        let mut hero = ActorBuilder::new("Hero").build();
+        hero.give_item(sword, 1);
+        let hero = state.add_actor(hero);
+        let mut json = serde_json::to_value(state.export_actor(hero)?)?;
+        json["items"][sword.0.to_string()]["inner"]["Weapon"]["damage"]["die_size"] = 0.into();
+        let file = ActorFile::from_json(&json.to_string())?;
+
+        let mut other = State::new();
+        other.import_actor(file);
+        let error = other.validate().unwrap_err().to_string();
+        assert!(error.contains("Invalid damage roll on Sword"), "{}", error);
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! Labels for how each combat went, like "pyrrhic victory" or "TPK", counted across a run. A
+//! [`OutcomeClassifier`] picks the label from the state the combat ended in.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{objectives::Objective, state::State};
+
+/// Labels the state a combat ended in with a category, like "party wins clean". Any
+/// `FnMut(&State) -> String` closure is one.
+pub trait OutcomeClassifier: Send + Sync {
+    fn classify(&mut self, state: &State) -> String;
+}
+
+impl<F> OutcomeClassifier for F
+where
+    F: FnMut(&State) -> String + Send + Sync,
+{
+    fn classify(&mut self, state: &State) -> String {
+        self(state)
+    }
+}
+
+/// The built-in labels, from the player party's point of view. The party is every group marked
+/// as the [`player_party`](crate::simulation::groups::GroupInfo::player_party), or the
+/// lowest-numbered group with anyone in it if none is.
+///
+/// - "timeout": a [`Objective::Survive`] objective decided the combat, whoever held out.
+/// - "party wins clean": the party won with everyone standing.
+/// - "party wins": the party won, but someone ended the combat down.
+/// - "pyrrhic victory": the party won, but someone died.
+/// - "TPK": the party lost with nobody left standing.
+/// - "party loses": the party lost some other way, like an objective.
+/// - "undecided": nobody won, and it wasn't a TPK. Also what a state without any party members
+///   gets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PartyOutcomeClassifier;
+
+impl OutcomeClassifier for PartyOutcomeClassifier {
+    fn classify(&mut self, state: &State) -> String {
+        let mut marked: Vec<u32> = state
+            .group_ids()
+            .into_iter()
+            .filter(|group| state.is_player_party(*group))
+            .collect();
+        if marked.is_empty() {
+            marked.extend(state.actors.values().map(|actor| actor.group).min());
+        }
+        let party: Vec<_> = state
+            .actors
+            .values()
+            .filter(|actor| marked.contains(&actor.group))
+            .collect();
+        if party.is_empty() {
+            return "undecided".to_string();
+        }
+
+        let outcome = state.outcome;
+        let timeout = outcome
+            .and_then(|outcome| outcome.objective)
+            .and_then(|index| state.encounter.objectives.get(index))
+            .is_some_and(|objective| matches!(objective, Objective::Survive { .. }));
+        let party_won = outcome
+            .and_then(|outcome| outcome.winner)
+            .is_some_and(|winner| party.iter().any(|actor| actor.group == winner));
+        let label = if timeout {
+            "timeout"
+        } else if party_won {
+            if party.iter().any(|actor| actor.is_dead()) {
+                "pyrrhic victory"
+            } else if party.iter().any(|actor| !actor.is_alive()) {
+                "party wins"
+            } else {
+                "party wins clean"
+            }
+        } else if party.iter().all(|actor| !actor.is_alive()) {
+            "TPK"
+        } else if outcome.is_some_and(|outcome| outcome.winner.is_some()) {
+            "party loses"
+        } else {
+            "undecided"
+        };
+        label.to_string()
+    }
+}
+
+/// How many combats an [`OutcomeClassifier`] gave each label.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutcomeLabels {
+    pub combats: u64,
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl OutcomeLabels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, label: String) {
+        self.combats += 1;
+        *self.counts.entry(label).or_default() += 1;
+    }
+
+    /// Adds another run's counts into these.
+    pub fn merge(&mut self, other: &OutcomeLabels) {
+        self.combats += other.combats;
+        for (label, count) in &other.counts {
+            *self.counts.entry(label.clone()).or_default() += count;
+        }
+    }
+
+    /// Fraction of combats given `label`.
+    pub fn fraction(&self, label: &str) -> f64 {
+        self.counts.get(label).copied().unwrap_or_default() as f64 / self.combats.max(1) as f64
+    }
+
+    /// Every label with its count, most common first.
+    pub fn sorted(&self) -> Vec<(&str, u64)> {
+        let mut labels: Vec<(&str, u64)> = self
+            .counts
+            .iter()
+            .map(|(label, count)| (label.as_str(), *count))
+            .collect();
+        labels.sort_by_key(|(label, count)| (std::cmp::Reverse(*count), *label));
+        labels
+    }
+
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        for (label, count) in self.sorted() {
+            writeln!(
+                f,
+                "{}: {:.2}% ({}/{})",
+                label,
+                self.fraction(label) * 100.0,
+                count,
+                self.combats
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::{actor::ActorBuilder, death::DeathBehavior},
+        simulation::{groups::GroupInfo, integration::Integrator, roller::Roller},
+        test_utils::duel,
+    };
+
+    #[test]
+    fn test_party_outcome_classifier() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").group(1).max_health(10).build());
+        let squire = state.add_actor(
+            ActorBuilder::new("Squire")
+                .group(1)
+                .max_health(10)
+                .death_behavior(DeathBehavior::InstantDeath)
+                .build(),
+        );
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").max_health(10).build());
+        state
+            .groups
+            .insert(1, GroupInfo::new("Party").player_party());
+
+        let classify = |edit: &dyn Fn(&mut State)| {
+            let mut state = state.clone();
+            edit(&mut state);
+            state.outcome = state.combat_outcome();
+            PartyOutcomeClassifier.classify(&state)
+        };
+        // exactly max HP of damage, so only the squire dies outright
+        let down =
+            |actor| move |state: &mut State| state.get_actor_mut(actor).unwrap().modify_health(-10);
+        assert_eq!(classify(&down(goblin)), "party wins clean");
+        assert_eq!(
+            classify(&|state| {
+                down(goblin)(state);
+                down(hero)(state);
+            }),
+            "party wins"
+        );
+        assert_eq!(
+            classify(&|state| {
+                down(goblin)(state);
+                down(squire)(state);
+            }),
+            "pyrrhic victory"
+        );
+        assert_eq!(
+            classify(&|state| {
+                down(hero)(state);
+                down(squire)(state);
+            }),
+            "TPK"
+        );
+        assert_eq!(classify(&|_| {}), "undecided");
+        assert_eq!(
+            classify(&|state| {
+                state.current_turn_index = Some(0);
+                state.encounter.objectives.push(Objective::Survive {
+                    group: 0,
+                    rounds: 0,
+                });
+            }),
+            "timeout"
+        );
+
+        // unmarked, the party is the lowest group there is, here the goblin's
+        let mut unmarked = state.clone();
+        unmarked.groups.clear();
+        for actor in unmarked.actors.values_mut() {
+            actor.group += 1;
+        }
+        unmarked.get_actor_mut(hero).unwrap().modify_health(-10);
+        unmarked.get_actor_mut(squire).unwrap().modify_health(-10);
+        unmarked.outcome = unmarked.combat_outcome();
+        assert_eq!(
+            PartyOutcomeClassifier.classify(&unmarked),
+            "party wins clean"
+        );
+        // nobody in the party at all isn't a TPK
+        let mut empty = state.clone();
+        empty.groups.clear();
+        empty.actors.clear();
+        assert_eq!(PartyOutcomeClassifier.classify(&empty), "undecided");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_outcome_labels() -> anyhow::Result<()> {
+        let (state, _, _) = duel(12, "Goblin", 7);
+
+        let mut integrator = Integrator::new(40, Roller::test_rng(), state);
+        integrator.classify_outcomes(|state: &State| match state.outcome {
+            Some(outcome) => format!("{:?}", outcome.winner),
+            None => "none".to_string(),
+        });
+        let results = integrator.run()?;
+        let labels = results.outcome_labels.as_ref().unwrap();
+        assert_eq!(labels.combats, 40);
+        assert_eq!(labels.counts.values().sum::<u64>(), 40);
+        let hero_wins = labels.fraction("Some(0)");
+        assert!(hero_wins > 0.0 && hero_wins < 1.0, "{:?}", labels);
+        assert_eq!(labels.sorted()[0].1, *labels.counts.values().max().unwrap());
+
+        let mut merged = results.clone();
+        merged.merge(&results)?;
+        let merged_labels = merged.outcome_labels.as_ref().unwrap();
+        assert_eq!(merged_labels.combats, 80);
+        assert_eq!(merged_labels.fraction("Some(0)"), hero_wins);
+
+        let mut report = String::new();
+        labels.pretty_print(&mut report)?;
+        assert!(report.contains("Some(1): "), "{}", report);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! Two runs side by side, such as before and after a build or encounter change, with how far each
+//! headline number moved and whether it moved by more than chance would explain.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::actor::ActorId,
+    simulation::{integration::IntegrationResults, summary::RunSummary},
+    utils::{Ratio, stats::Moments},
+};
+
+/// How unlikely a difference is to be down to chance alone, by a two-sided z-test.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Significance {
+    #[default]
+    NotSignificant,
+    /// p < 0.05
+    Likely,
+    /// p < 0.01
+    Strong,
+    /// p < 0.001
+    VeryStrong,
+}
+
+impl Significance {
+    pub fn from_z_score(z: f64) -> Self {
+        match z.abs() {
+            z if z >= 3.291 => Significance::VeryStrong,
+            z if z >= 2.576 => Significance::Strong,
+            z if z >= 1.960 => Significance::Likely,
+            _ => Significance::NotSignificant,
+        }
+    }
+
+    /// The usual asterisks: `*` for p < 0.05, up to `***` for p < 0.001.
+    pub fn stars(&self) -> &'static str {
+        match self {
+            Significance::NotSignificant => "",
+            Significance::Likely => "*",
+            Significance::Strong => "**",
+            Significance::VeryStrong => "***",
+        }
+    }
+}
+
+/// One number measured in both runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricDelta {
+    pub name: String,
+    pub before: f64,
+    pub after: f64,
+    /// Standard deviations the change is from no change at all. Zero when neither run varied.
+    pub z_score: f64,
+}
+
+impl MetricDelta {
+    /// Compares the proportion of combats something happened in, pooling the two runs for the
+    /// variance.
+    pub fn proportions(name: &str, before: Ratio, after: Ratio) -> Self {
+        let pooled = Ratio::new(
+            before.numerator + after.numerator,
+            before.denominator + after.denominator,
+        )
+        .to_f64();
+        let variance = pooled
+            * (1.0 - pooled)
+            * (1.0 / before.denominator.max(1) as f64 + 1.0 / after.denominator.max(1) as f64);
+        Self::new(name, before.to_f64(), after.to_f64(), variance)
+    }
+
+    /// Compares two means by Welch's method, from each run's mean and the variance of its mean.
+    fn means(name: &str, before: &Moments, after: &Moments) -> Self {
+        Self::new(
+            name,
+            before.mean(),
+            after.mean(),
+            before.variance_of_mean() + after.variance_of_mean(),
+        )
+    }
+
+    fn new(name: &str, before: f64, after: f64, variance: f64) -> Self {
+        let z_score = if variance > 0.0 {
+            (after - before) / variance.sqrt()
+        } else {
+            0.0
+        };
+        Self {
+            name: name.to_string(),
+            before,
+            after,
+            z_score,
+        }
+    }
+
+    pub fn delta(&self) -> f64 {
+        self.after - self.before
+    }
+
+    pub fn significance(&self) -> Significance {
+        Significance::from_z_score(self.z_score)
+    }
+}
+
+/// The headline numbers of two runs, and how each changed from the first to the second.
+///
+/// Actors and groups are matched by ID, so both runs should come from versions of the same
+/// scenario. Ones only in one of the runs are left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunComparison {
+    /// The chance each group is the one that wins.
+    pub win_probabilities: BTreeMap<u32, MetricDelta>,
+    /// Mean rounds per combat. Only present when both runs kept their terminal states.
+    pub combat_length: Option<MetricDelta>,
+    /// Each actor's chance of ending combat not dead.
+    pub survival: BTreeMap<ActorId, MetricDelta>,
+}
+
+impl RunComparison {
+    pub fn new(before: &IntegrationResults, after: &IntegrationResults) -> Self {
+        let before_summary = RunSummary::new(before);
+        let after_summary = RunSummary::new(after);
+
+        // combats each group won, out of every combat that ended
+        let wins = |summary: &RunSummary| {
+            let mut wins: BTreeMap<u32, u64> = BTreeMap::new();
+            for outcome in &summary.outcomes {
+                if let Some(group) = outcome.outcome.winner {
+                    *wins.entry(group).or_default() += outcome.ratio.numerator;
+                }
+            }
+            let total: u64 = summary.outcomes.iter().map(|o| o.ratio.numerator).sum();
+            move |group: u32| Ratio::new(wins.get(&group).copied().unwrap_or(0), total)
+        };
+        let (before_wins, after_wins) = (wins(&before_summary), wins(&after_summary));
+        let after_state = after.state_tree.initial_state();
+        let win_probabilities = before
+            .state_tree
+            .initial_state()
+            .group_ids()
+            .intersection(&after_state.group_ids())
+            .map(|&group| {
+                let name = format!("{} wins", after_state.group_name(group));
+                let delta = MetricDelta::proportions(&name, before_wins(group), after_wins(group));
+                (group, delta)
+            })
+            .collect();
+
+        let rounds = |results: &IntegrationResults| {
+            let mut moments = Moments::default();
+            results.state_tree.visit_states(true, |state, hits| {
+                moments.add(state.final_round.unwrap_or(0) as f64, hits);
+                true
+            });
+            (moments.count > 0).then_some(moments)
+        };
+        let combat_length = rounds(before)
+            .zip(rounds(after))
+            .map(|(before, after)| MetricDelta::means("Rounds per combat", &before, &after));
+
+        let survival = after_summary
+            .actors
+            .iter()
+            .filter_map(|actor| {
+                let previous = before_summary
+                    .actors
+                    .iter()
+                    .find(|other| other.actor == actor.actor)?;
+                let name = format!("{} survives", actor.name);
+                let delta = MetricDelta::proportions(&name, previous.survived, actor.survived);
+                Some((actor.actor, delta))
+            })
+            .collect();
+
+        Self {
+            win_probabilities,
+            combat_length,
+            survival,
+        }
+    }
+
+    /// Every metric, in a fixed order: win probabilities, combat length, then survival.
+    pub fn metrics(&self) -> impl Iterator<Item = &MetricDelta> {
+        self.win_probabilities
+            .values()
+            .chain(&self.combat_length)
+            .chain(self.survival.values())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        simulation::{integration::Integrator, roller::Roller, state::State},
+        test_utils::duel,
+    };
+
+    #[test]
+    fn test_run_comparison() -> anyhow::Result<()> {
+        let (mut state, hero, ogre) = duel(12, "Ogre", 12);
+
+        let run = |state: &State| Integrator::new(200, Roller::test_rng(), state.clone()).run();
+        let before = run(&state)?;
+
+        // the same fight again changes nothing
+        let same = RunComparison::new(&before, &before);
+        assert!(same.metrics().all(|metric| metric.delta() == 0.0));
+        assert!(
+            same.metrics()
+                .all(|metric| metric.significance() == Significance::NotSignificant)
+        );
+
+        // a much tougher hero wins far more often, and that's no fluke
+        state.get_actor_mut(hero).unwrap().max_health = 40;
+        state.get_actor_mut(hero).unwrap().health = 40;
+        let after = run(&state)?;
+        let comparison = RunComparison::new(&before, &after);
+        let hero_wins = &comparison.win_probabilities[&0];
+        assert_eq!(hero_wins.name, "Group 0 wins");
+        assert!(hero_wins.delta() > 0.2, "{:?}", hero_wins);
+        assert_eq!(hero_wins.significance(), Significance::VeryStrong);
+        assert!(comparison.survival[&ogre].delta() < 0.0);
+        let combat_length = comparison.combat_length.as_ref().unwrap();
+        assert!(combat_length.before >= 1.0, "{:?}", combat_length);
+        assert!(
+            combat_length.after > combat_length.before,
+            "{:?}",
+            combat_length
+        );
+        assert_eq!(comparison.metrics().count(), 5);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{actor::ActorId, damage::DamageType},
+    simulation::{environment::EnvironmentFiring, state::State, transition::Transition},
+};
+
+/// What one actor did across every recorded combat.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorContribution {
+    /// HP taken from other actors, including damage past 0 HP.
+    pub damage_dealt: u64,
+    pub healing_done: u64,
+    /// Enemies that died to this actor's damage.
+    pub kills: u64,
+    /// Enemies this actor damaged that died to someone else, or to failed death saves.
+    pub assists: u64,
+    /// Times this actor died.
+    pub deaths: u64,
+    /// This actor's typed damage, before and after its targets' resistances.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub damage_by_type: BTreeMap<DamageType, TypedDamage>,
+}
+
+/// Damage of one type, as rolled and as it landed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypedDamage {
+    pub rolled: u64,
+    /// What got through after resistances, immunities and vulnerabilities. Can exceed `rolled`.
+    pub dealt: u64,
+}
+
+impl TypedDamage {
+    /// Damage that resistances and immunities stopped.
+    pub fn resisted(&self) -> u64 {
+        self.rolled.saturating_sub(self.dealt)
+    }
+}
+
+impl ActorContribution {
+    fn merge(&mut self, other: &ActorContribution) {
+        self.damage_dealt += other.damage_dealt;
+        self.healing_done += other.healing_done;
+        self.kills += other.kills;
+        self.assists += other.assists;
+        self.deaths += other.deaths;
+        for (damage_type, other) in &other.damage_by_type {
+            let typed = self.damage_by_type.entry(*damage_type).or_default();
+            typed.rolled += other.rolled;
+            typed.dealt += other.dealt;
+        }
+    }
+}
+
+/// Which actors damaged whom in the combat in progress.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct CombatContributions {
+    damagers: BTreeMap<ActorId, BTreeSet<ActorId>>,
+    dead: BTreeSet<ActorId>,
+    environment: EnvironmentFiring,
+}
+
+/// Tallies each actor's damage, healing, kills and assists across combats.
+///
+/// Health transitions don't say who caused them, so damage and healing are credited to the actor
+/// whose turn it is. Damage an actor takes on its own turn, like a lingering effect, isn't
+/// credited to anyone, and what environmental effects do at the top of a round goes to
+/// [`Self::environment`].
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContributionStats {
+    pub combats: u64,
+    pub actors: BTreeMap<ActorId, ActorContribution>,
+    /// Damage, healing and kills from the encounter's environmental effects. Its `deaths` and
+    /// `assists` stay 0.
+    #[serde(default)]
+    pub environment: ActorContribution,
+    #[serde(skip)]
+    current: CombatContributions,
+}
+
+impl ContributionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin_combat(&mut self) {
+        self.combats += 1;
+        self.current = CombatContributions::default();
+    }
+
+    /// Credits whoever's turn it is for `transition`, which has just been applied to `state`.
+    pub fn observe(&mut self, state: &State, transition: &Transition) {
+        self.current.environment.observe(transition);
+        if self.current.environment.is_firing() {
+            self.observe_environment(state, transition);
+            return;
+        }
+        let source = state
+            .current_turn_index
+            .and_then(|index| state.initiative_order.get(index))
+            .copied();
+
+        if let (
+            Transition::DamageTaken {
+                target,
+                damage_type,
+                rolled,
+                dealt,
+            },
+            Some(source),
+        ) = (transition, source)
+            && *target != source
+        {
+            let typed = self
+                .actors
+                .entry(source)
+                .or_default()
+                .damage_by_type
+                .entry(*damage_type)
+                .or_default();
+            typed.rolled += (*rolled).max(0) as u64;
+            typed.dealt += (*dealt).max(0) as u64;
+        }
+
+        if let (Transition::HealthModification { target, delta }, Some(source)) =
+            (transition, source)
+        {
+            if *delta < 0 && *target != source {
+                self.actors.entry(source).or_default().damage_dealt += delta.unsigned_abs() as u64;
+                self.current
+                    .damagers
+                    .entry(*target)
+                    .or_default()
+                    .insert(source);
+            } else if *delta > 0 {
+                self.actors.entry(source).or_default().healing_done += *delta as u64;
+            }
+        }
+
+        for (id, actor) in &state.actors {
+            if !actor.is_dead() || !self.current.dead.insert(*id) {
+                continue;
+            }
+            self.actors.entry(*id).or_default().deaths += 1;
+            let killer = match transition {
+                Transition::HealthModification { target, .. } if target == id => source,
+                _ => None,
+            };
+            let damagers = self.current.damagers.get(id).into_iter().flatten();
+            for damager in damagers {
+                // friendly fire doesn't count towards kills
+                if state
+                    .get_actor(*damager)
+                    .is_none_or(|damager| damager.group == actor.group)
+                {
+                    continue;
+                }
+                let contribution = self.actors.entry(*damager).or_default();
+                if Some(*damager) == killer {
+                    contribution.kills += 1;
+                } else {
+                    contribution.assists += 1;
+                }
+            }
+        }
+    }
+
+    /// Credits the environment for `transition`, which landed while environmental effects were
+    /// firing. Anyone it kills still counts as an assist for the actors who'd damaged them.
+    fn observe_environment(&mut self, state: &State, transition: &Transition) {
+        match transition {
+            Transition::DamageTaken {
+                damage_type,
+                rolled,
+                dealt,
+                ..
+            } => {
+                let typed = self
+                    .environment
+                    .damage_by_type
+                    .entry(*damage_type)
+                    .or_default();
+                typed.rolled += (*rolled).max(0) as u64;
+                typed.dealt += (*dealt).max(0) as u64;
+            }
+            Transition::HealthModification { delta, .. } if *delta < 0 => {
+                self.environment.damage_dealt += delta.unsigned_abs() as u64;
+            }
+            Transition::HealthModification { delta, .. } => {
+                self.environment.healing_done += *delta as u64;
+            }
+            _ => {}
+        }
+
+        for (id, actor) in &state.actors {
+            if !actor.is_dead() || !self.current.dead.insert(*id) {
+                continue;
+            }
+            self.actors.entry(*id).or_default().deaths += 1;
+            if matches!(transition, Transition::HealthModification { target, .. } if target == id) {
+                self.environment.kills += 1;
+            }
+            for damager in self.current.damagers.get(id).into_iter().flatten() {
+                if state
+                    .get_actor(*damager)
+                    .is_some_and(|damager| damager.group != actor.group)
+                {
+                    self.actors.entry(*damager).or_default().assists += 1;
+                }
+            }
+        }
+    }
+
+    /// Adds another tally's combats into this one.
+    pub fn merge(&mut self, other: &ContributionStats) {
+        self.combats += other.combats;
+        for (id, other) in &other.actors {
+            self.actors.entry(*id).or_default().merge(other);
+        }
+        self.environment.merge(&other.environment);
+    }
+
+    fn group_total(
+        &self,
+        initial_state: &State,
+        group: u32,
+        value: impl Fn(&ActorContribution) -> u64,
+    ) -> u64 {
+        self.actors
+            .iter()
+            .filter(|(id, _)| {
+                initial_state
+                    .get_actor(**id)
+                    .is_some_and(|actor| actor.group == group)
+            })
+            .map(|(_, contribution)| value(contribution))
+            .sum()
+    }
+
+    /// Fraction of `actor`'s group's damage that `actor` dealt.
+    pub fn damage_share(&self, initial_state: &State, actor: ActorId) -> f64 {
+        self.share(initial_state, actor, |c| c.damage_dealt)
+    }
+
+    /// Fraction of `actor`'s group's healing that `actor` did.
+    pub fn healing_share(&self, initial_state: &State, actor: ActorId) -> f64 {
+        self.share(initial_state, actor, |c| c.healing_done)
+    }
+
+    fn share(
+        &self,
+        initial_state: &State,
+        actor: ActorId,
+        value: impl Fn(&ActorContribution) -> u64,
+    ) -> f64 {
+        let Some(group) = initial_state.get_actor(actor).map(|actor| actor.group) else {
+            return 0.0;
+        };
+        let total = self.group_total(initial_state, group, &value);
+        let own = self.actors.get(&actor).map_or(0, value);
+        if total > 0 {
+            own as f64 / total as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Fraction of enemy deaths that `actor` got the kill or an assist on.
+    pub fn kill_participation(&self, initial_state: &State, actor: ActorId) -> f64 {
+        let Some(group) = initial_state.get_actor(actor).map(|actor| actor.group) else {
+            return 0.0;
+        };
+        let enemy_deaths: u64 = initial_state
+            .actors
+            .values()
+            .filter(|enemy| enemy.group != group)
+            .filter_map(|enemy| self.actors.get(&enemy.id))
+            .map(|enemy| enemy.deaths)
+            .sum();
+        let participated = self
+            .actors
+            .get(&actor)
+            .map_or(0, |actor| actor.kills + actor.assists);
+        if enemy_deaths > 0 {
+            participated as f64 / enemy_deaths as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{actor::Actor, death::DeathBehavior};
+
+    #[test]
+    fn test_contribution_stats() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let fighter = state.add_actor(Actor::test_actor(0, "Fighter"));
+        let cleric = state.add_actor(Actor::test_actor(1, "Cleric"));
+        let mut goblin = Actor::test_actor(2, "Goblin");
+        goblin.group = 1;
+        goblin.death_behavior = DeathBehavior::InstantDeath;
+        let goblin = state.add_actor(goblin);
+        state.initiative_order = vec![fighter, cleric, goblin];
+        let initial_state = state.clone();
+
+        let mut stats = ContributionStats::new();
+        stats.begin_combat();
+        let mut turn = |state: &mut State, actor: usize, transition: Transition| {
+            state.current_turn_index = Some(actor);
+            transition.apply(state)?;
+            stats.observe(state, &transition);
+            anyhow::Ok(())
+        };
+        let hit = |target, delta| Transition::HealthModification { target, delta };
+        turn(&mut state, 2, hit(fighter, -4))?;
+        turn(&mut state, 1, hit(fighter, 3))?;
+        turn(&mut state, 1, hit(goblin, -2))?;
+        turn(&mut state, 0, hit(goblin, -8))?;
+        // nobody gets credit for hurting themselves
+        turn(&mut state, 0, hit(fighter, -1))?;
+        let slash = Transition::DamageTaken {
+            target: goblin,
+            damage_type: DamageType::Slashing,
+            rolled: 8,
+            dealt: 4,
+        };
+        turn(&mut state, 0, slash)?;
+
+        assert_eq!(stats.actors[&fighter].damage_dealt, 8);
+        assert_eq!(stats.actors[&fighter].kills, 1);
+        assert_eq!(stats.actors[&cleric].assists, 1);
+        assert_eq!(stats.actors[&goblin].deaths, 1);
+        assert_eq!(stats.actors[&goblin].damage_dealt, 4);
+        assert!((stats.damage_share(&initial_state, fighter) - 0.8).abs() < 1e-9);
+        assert_eq!(stats.healing_share(&initial_state, cleric), 1.0);
+        assert_eq!(stats.kill_participation(&initial_state, cleric), 1.0);
+        assert_eq!(stats.kill_participation(&initial_state, goblin), 0.0);
+        let slashing = stats.actors[&fighter].damage_by_type[&DamageType::Slashing];
+        assert_eq!((slashing.dealt, slashing.resisted()), (4, 4));
+
+        let mut merged = stats.clone();
+        merged.merge(&stats);
+        assert_eq!(merged.combats, 2);
+        assert_eq!(merged.actors[&fighter].damage_dealt, 16);
+        assert_eq!(
+            merged.actors[&fighter].damage_by_type[&DamageType::Slashing].rolled,
+            16
+        );
+        assert!((merged.damage_share(&initial_state, fighter) - 0.8).abs() < 1e-9);
+        Ok(())
+    }
+}
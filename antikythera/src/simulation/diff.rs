@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    rules::{actor::ActorId, items::ItemId},
+    simulation::state::State,
+};
+
+/// A single field that differs between two states, addressed by a dotted path into its
+/// serialized form (e.g. `stats.Strength` or `inventory.items.3`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub path: String,
+    /// `None` if the field is absent in the old state.
+    pub old: Option<Value>,
+    /// `None` if the field is absent in the new state.
+    pub new: Option<Value>,
+}
+
+impl FieldChange {
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match &self.old {
+            Some(old) => write!(f, "{}", old)?,
+            None => write!(f, "<none>")?,
+        }
+        write!(f, " -> ")?;
+        match &self.new {
+            Some(new) => write!(f, "{}", new),
+            None => write!(f, "<none>"),
+        }
+    }
+}
+
+/// Everything that differs between two [`State`]s.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateDiff {
+    pub added_actors: Vec<ActorId>,
+    pub removed_actors: Vec<ActorId>,
+    pub changed_actors: BTreeMap<ActorId, Vec<FieldChange>>,
+    pub added_items: Vec<ItemId>,
+    pub removed_items: Vec<ItemId>,
+    pub changed_items: BTreeMap<ItemId, Vec<FieldChange>>,
+    /// Changes to everything outside the actor and item maps (turn, initiative, encounter setup, ...).
+    pub changed_fields: Vec<FieldChange>,
+}
+
+impl StateDiff {
+    pub fn between(old: &State, new: &State) -> anyhow::Result<Self> {
+        let mut diff = StateDiff::default();
+
+        for (id, old_actor) in &old.actors {
+            match new.actors.get(id) {
+                Some(new_actor) => {
+                    let changes = diff_serialized(old_actor, new_actor)?;
+                    if !changes.is_empty() {
+                        diff.changed_actors.insert(*id, changes);
+                    }
+                }
+                None => diff.removed_actors.push(*id),
+            }
+        }
+        diff.added_actors = new
+            .actors
+            .keys()
+            .filter(|id| !old.actors.contains_key(id))
+            .copied()
+            .collect();
+
+        for (id, old_item) in &old.items {
+            match new.items.get(id) {
+                Some(new_item) => {
+                    let changes = diff_serialized(old_item, new_item)?;
+                    if !changes.is_empty() {
+                        diff.changed_items.insert(*id, changes);
+                    }
+                }
+                None => diff.removed_items.push(*id),
+            }
+        }
+        diff.added_items = new
+            .items
+            .keys()
+            .filter(|id| !old.items.contains_key(id))
+            .copied()
+            .collect();
+
+        let mut old_rest = serde_json::to_value(old)?;
+        let mut new_rest = serde_json::to_value(new)?;
+        for value in [&mut old_rest, &mut new_rest] {
+            if let Value::Object(map) = value {
+                map.remove("actors");
+                map.remove("items");
+            }
+        }
+        diff_values("", &old_rest, &new_rest, &mut diff.changed_fields);
+
+        Ok(diff)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_actors.is_empty()
+            && self.removed_actors.is_empty()
+            && self.changed_actors.is_empty()
+            && self.added_items.is_empty()
+            && self.removed_items.is_empty()
+            && self.changed_items.is_empty()
+            && self.changed_fields.is_empty()
+    }
+
+    /// Prints one line per added/removed entity, and an indented line per changed field.
+    /// Names are looked up in whichever state still contains the entity.
+    pub fn pretty_print(
+        &self,
+        f: &mut impl std::fmt::Write,
+        old: &State,
+        new: &State,
+    ) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+
+        for id in &self.added_actors {
+            write!(f, "+ Actor ")?;
+            id.pretty_print(f, new)?;
+            writeln!(f)?;
+        }
+        for id in &self.removed_actors {
+            write!(f, "- Actor ")?;
+            id.pretty_print(f, old)?;
+            writeln!(f)?;
+        }
+        for (id, changes) in &self.changed_actors {
+            write!(f, "~ Actor ")?;
+            id.pretty_print(f, new)?;
+            writeln!(f)?;
+            for change in changes {
+                write!(f, "    ")?;
+                change.pretty_print(f)?;
+                writeln!(f)?;
+            }
+        }
+
+        for id in &self.added_items {
+            write!(f, "+ Item ")?;
+            id.pretty_print(f, new)?;
+            writeln!(f)?;
+        }
+        for id in &self.removed_items {
+            write!(f, "- Item ")?;
+            id.pretty_print(f, old)?;
+            writeln!(f)?;
+        }
+        for (id, changes) in &self.changed_items {
+            write!(f, "~ Item ")?;
+            id.pretty_print(f, new)?;
+            writeln!(f)?;
+            for change in changes {
+                write!(f, "    ")?;
+                change.pretty_print(f)?;
+                writeln!(f)?;
+            }
+        }
+
+        for change in &self.changed_fields {
+            write!(f, "~ ")?;
+            change.pretty_print(f)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn diff_serialized<T: Serialize>(old: &T, new: &T) -> anyhow::Result<Vec<FieldChange>> {
+    let mut changes = Vec::new();
+    diff_values(
+        "",
+        &serde_json::to_value(old)?,
+        &serde_json::to_value(new)?,
+        &mut changes,
+    );
+    Ok(changes)
+}
+
+/// Recurses into objects so changes are reported at the leaf; arrays and scalars are compared whole.
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<FieldChange>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                match new_map.get(key) {
+                    Some(new_value) => diff_values(&join(key), old_value, new_value, changes),
+                    None => changes.push(FieldChange {
+                        path: join(key),
+                        old: Some(old_value.clone()),
+                        new: None,
+                    }),
+                }
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    changes.push(FieldChange {
+                        path: join(key),
+                        old: None,
+                        new: Some(new_value.clone()),
+                    });
+                }
+            }
+        }
+        _ if old != new => changes.push(FieldChange {
+            path: path.to_string(),
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        rules::{
+            actor::ActorBuilder,
+            items::{Armor, ItemInner},
+        },
+        simulation::state::State,
+    };
+
+    #[test]
+    fn test_state_diff() -> anyhow::Result<()> {
+        let mut old = State::new();
+        let hero = old.add_actor(ActorBuilder::new("Hero").max_health(20).build());
+        let goblin = old.add_actor(ActorBuilder::new("Goblin").max_health(7).build());
+        let shield = old.add_item("Shield", ItemInner::Armor(Armor::new(2)));
+
+        assert!(old.diff(&old)?.is_empty());
+
+        let mut new = old.clone();
+        new.actors.remove(&goblin);
+        let orc = new.add_actor(ActorBuilder::new("Orc").max_health(15).build());
+        new.get_actor_mut(hero).unwrap().name = "Champion".to_string();
+        new.turn = 3;
+
+        let diff = old.diff(&new)?;
+        assert_eq!(diff.added_actors, vec![orc]);
+        assert_eq!(diff.removed_actors, vec![goblin]);
+        assert!(diff.added_items.is_empty() && diff.removed_items.is_empty());
+        assert!(!diff.changed_items.contains_key(&shield));
+
+        let hero_changes = &diff.changed_actors[&hero];
+        assert_eq!(hero_changes.len(), 1);
+        assert_eq!(hero_changes[0].path, "name");
+
+        let fields: Vec<_> = diff.changed_fields.iter().map(|c| &c.path).collect();
+        assert!(fields.contains(&&"turn".to_string()));
+        assert!(fields.contains(&&"next_actor_id".to_string()));
+
+        let mut printed = String::new();
+        diff.pretty_print(&mut printed, &old, &new)?;
+        assert!(printed.contains("+ Actor Orc"));
+        assert!(printed.contains("- Actor Goblin"));
+        assert!(printed.contains("~ Actor Champion"));
+        assert!(printed.contains("    name: \"Hero\" -> \"Champion\""));
+        assert!(printed.contains("~ turn: 0 -> 3"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{actor::ActorId, cover::Cover, dice::Advantage, vision::Lighting},
+    simulation::{environment::EnvironmentalEffect, objectives::Objective, positioning::ZoneMap},
+};
+
+/// How initiative is determined at the start of each combat.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InitiativeMode {
+    /// Every actor rolls initiative as normal.
+    #[default]
+    Rolled,
+    /// Listed actors use the given initiative instead of rolling; everyone else rolls.
+    Fixed(BTreeMap<ActorId, i32>),
+    /// Actors act in exactly this order, first to last. Unlisted actors act after everyone listed.
+    Order(Vec<ActorId>),
+}
+
+impl InitiativeMode {
+    /// The initiative `actor` starts combat with, or `None` if it should be rolled.
+    pub fn forced_initiative(&self, actor: ActorId) -> Option<i32> {
+        match self {
+            InitiativeMode::Rolled => None,
+            InitiativeMode::Fixed(values) => values.get(&actor).copied(),
+            InitiativeMode::Order(order) => Some(
+                order
+                    .iter()
+                    .position(|id| *id == actor)
+                    .map_or(0, |index| (order.len() - index) as i32),
+            ),
+        }
+    }
+}
+
+/// What happens to an actor once it dies.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeathCleanup {
+    /// The dead stay where they fell, still targetable and still holding their gear.
+    #[default]
+    None,
+    /// The dead are removed from targeting.
+    RemoveFromTargeting,
+    /// The dead are removed from targeting, and their inventory goes to the loot pool.
+    DropLoot,
+}
+
+/// Optional and variant rules, all off by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OptionalRules {
+    /// Attacks have advantage when an ally of the attacker is in melee with the target, as if every
+    /// actor had the Pack Tactics trait. Meant for swarms of kobolds, wolves and the like.
+    #[serde(default)]
+    pub pack_tactics: bool,
+    /// The DMG flanking variant: melee attacks have advantage when an ally of the attacker is also
+    /// in melee with the target.
+    #[serde(default)]
+    pub flanking: bool,
+    /// Attackers that can't see their target have disadvantage, and targets that can't see their
+    /// attacker are attacked with advantage. See [`EncounterSetup::lighting`].
+    #[serde(default)]
+    pub lighting: bool,
+    /// The PHB variant encumbrance rule: carrying more than 5 times Strength in pounds costs 10
+    /// feet of speed, and more than 10 times costs 20. Wearing armor without its Strength
+    /// requirement costs another 10. See
+    /// [`State::effective_speed`](crate::simulation::state::State::effective_speed).
+    #[serde(default)]
+    pub encumbrance: bool,
+    /// A group whose every member's passive Stealth beats the passive Perception of every enemy
+    /// gets into melee unnoticed, instead of starting at
+    /// [`EncounterSetup::engagement_distance`]. See
+    /// [`State::opening_distance`](crate::simulation::state::State::opening_distance).
+    #[serde(default)]
+    pub stealth: bool,
+}
+
+impl OptionalRules {
+    /// Whether any enabled rule cares who is in melee with whom.
+    pub fn tracks_engagements(&self) -> bool {
+        self.pack_tactics || self.flanking
+    }
+}
+
+/// Encounter-level configuration that stays fixed for the duration of a combat.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EncounterSetup {
+    /// Cover that a target has against attacks from a given attacker, keyed by attacker then target.
+    #[serde(default)]
+    pub cover: BTreeMap<ActorId, BTreeMap<ActorId, Cover>>,
+    /// Forced advantage for attacks from an attacker against a specific target, keyed by attacker then target.
+    /// Overrides every other source of advantage or disadvantage, which makes it useful for what-if analysis.
+    #[serde(default)]
+    pub advantage_overrides: BTreeMap<ActorId, BTreeMap<ActorId, Advantage>>,
+    /// Forced advantage for every attack made by an actor. Per-target overrides take precedence.
+    #[serde(default)]
+    pub attacker_advantage_overrides: BTreeMap<ActorId, Advantage>,
+    /// Forced initiative values or turn order, for reproducing a specific table situation.
+    #[serde(default)]
+    pub initiative: InitiativeMode,
+    #[serde(default)]
+    pub death_cleanup: DeathCleanup,
+    /// Distance between the two sides when combat begins, in feet. Each round, an actor that needs to
+    /// be closer to act moves up to its speed; ranged attackers can act right away.
+    /// `None` disables the model, so every attack reaches every target.
+    #[serde(default)]
+    pub engagement_distance: Option<u32>,
+    /// Zones for theater-of-the-mind positioning. When set, they replace
+    /// [`EncounterSetup::engagement_distance`].
+    #[serde(default)]
+    pub zones: Option<ZoneMap>,
+    /// Effects the battlefield itself applies on a schedule, at the start of a round.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub environment: Vec<EnvironmentalEffect>,
+    #[serde(default)]
+    pub rules: OptionalRules,
+    /// Light level across the battlefield. Only matters with [`OptionalRules::lighting`] enabled.
+    #[serde(default)]
+    pub lighting: Lighting,
+    /// Light level where particular actors stand, for encounters with lit and unlit areas.
+    #[serde(default)]
+    pub actor_lighting: BTreeMap<ActorId, Lighting>,
+    /// Ways to win besides being the last group standing, checked in order. See [`Objective`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub objectives: Vec<Objective>,
+}
+
+impl EncounterSetup {
+    /// The light level where `actor` stands.
+    pub fn lighting_at(&self, actor: ActorId) -> Lighting {
+        self.actor_lighting
+            .get(&actor)
+            .copied()
+            .unwrap_or(self.lighting)
+    }
+
+    pub fn cover_between(&self, attacker: ActorId, target: ActorId) -> Cover {
+        self.cover
+            .get(&attacker)
+            .and_then(|targets| targets.get(&target))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_cover(&mut self, attacker: ActorId, target: ActorId, cover: Cover) {
+        if cover == Cover::None {
+            if let Some(targets) = self.cover.get_mut(&attacker) {
+                targets.remove(&target);
+                if targets.is_empty() {
+                    self.cover.remove(&attacker);
+                }
+            }
+        } else {
+            self.cover
+                .entry(attacker)
+                .or_default()
+                .insert(target, cover);
+        }
+    }
+
+    /// Sets the same cover for attacks in both directions between two actors.
+    pub fn set_mutual_cover(&mut self, a: ActorId, b: ActorId, cover: Cover) {
+        self.set_cover(a, b, cover);
+        self.set_cover(b, a, cover);
+    }
+
+    pub fn advantage_override(&self, attacker: ActorId, target: ActorId) -> Option<Advantage> {
+        self.advantage_overrides
+            .get(&attacker)
+            .and_then(|targets| targets.get(&target))
+            .or_else(|| self.attacker_advantage_overrides.get(&attacker))
+            .copied()
+    }
+
+    /// Forces advantage for attacks from `attacker` against `target`, or clears the override if `None`.
+    pub fn set_advantage_override(
+        &mut self,
+        attacker: ActorId,
+        target: ActorId,
+        advantage: Option<Advantage>,
+    ) {
+        if let Some(advantage) = advantage {
+            self.advantage_overrides
+                .entry(attacker)
+                .or_default()
+                .insert(target, advantage);
+        } else if let Some(targets) = self.advantage_overrides.get_mut(&attacker) {
+            targets.remove(&target);
+            if targets.is_empty() {
+                self.advantage_overrides.remove(&attacker);
+            }
+        }
+    }
+
+    /// Forces advantage for all attacks by `attacker`, or clears the override if `None`.
+    pub fn set_attacker_advantage_override(
+        &mut self,
+        attacker: ActorId,
+        advantage: Option<Advantage>,
+    ) {
+        if let Some(advantage) = advantage {
+            self.attacker_advantage_overrides
+                .insert(attacker, advantage);
+        } else {
+            self.attacker_advantage_overrides.remove(&attacker);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::{actor::ActorBuilder, dice::RollSettings},
+        simulation::{state::State, transition::Transition},
+    };
+
+    #[test]
+    fn test_cover_between() {
+        let mut setup = EncounterSetup::default();
+        setup.set_cover(ActorId(1), ActorId(2), Cover::Half);
+        assert_eq!(setup.cover_between(ActorId(1), ActorId(2)), Cover::Half);
+        assert_eq!(setup.cover_between(ActorId(2), ActorId(1)), Cover::None);
+
+        setup.set_mutual_cover(ActorId(1), ActorId(2), Cover::ThreeQuarters);
+        assert_eq!(
+            setup.cover_between(ActorId(2), ActorId(1)),
+            Cover::ThreeQuarters
+        );
+
+        setup.set_mutual_cover(ActorId(1), ActorId(2), Cover::None);
+        assert!(setup.cover.is_empty());
+    }
+
+    #[test]
+    fn test_advantage_overrides() {
+        let mut setup = EncounterSetup::default();
+        assert_eq!(setup.advantage_override(ActorId(1), ActorId(2)), None);
+
+        setup.set_attacker_advantage_override(ActorId(1), Some(Advantage::Advantage));
+        setup.set_advantage_override(ActorId(1), ActorId(3), Some(Advantage::Normal));
+        assert_eq!(
+            setup.advantage_override(ActorId(1), ActorId(2)),
+            Some(Advantage::Advantage)
+        );
+        assert_eq!(
+            setup.advantage_override(ActorId(1), ActorId(3)),
+            Some(Advantage::Normal)
+        );
+        assert_eq!(setup.advantage_override(ActorId(2), ActorId(1)), None);
+
+        setup.set_advantage_override(ActorId(1), ActorId(3), None);
+        setup.set_attacker_advantage_override(ActorId(1), None);
+        assert_eq!(setup, EncounterSetup::default());
+    }
+
+    #[test]
+    fn test_total_cover_blocks_targeting() {
+        let mut state = State::new();
+        let archer = state.add_actor(ActorBuilder::new("Archer").group(0).build());
+        let exposed = state.add_actor(ActorBuilder::new("Exposed").group(1).build());
+        let hidden = state.add_actor(ActorBuilder::new("Hidden").group(1).build());
+
+        state.encounter.set_cover(archer, hidden, Cover::Total);
+        assert_eq!(state.possible_targets(archer), vec![exposed]);
+    }
+
+    #[test]
+    fn test_pack_tactics_and_flanking() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let kobold = state.add_actor(ActorBuilder::new("Kobold").group(1).build());
+        let packmate = state.add_actor(ActorBuilder::new("Kobold").group(1).build());
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        Transition::Engaged {
+            actor: packmate,
+            target: hero,
+        }
+        .apply(&mut state)?;
+
+        let advantage = |state: &State, melee: bool| {
+            let (kobold, hero) = (
+                state.get_actor(kobold).unwrap(),
+                state.get_actor(hero).unwrap(),
+            );
+            state
+                .attack_roll_settings(kobold, hero, melee, RollSettings::default())
+                .advantage
+        };
+        assert_eq!(advantage(&state, true), Advantage::Normal);
+
+        state.encounter.rules.flanking = true;
+        assert_eq!(advantage(&state, true), Advantage::Advantage);
+        assert_eq!(advantage(&state, false), Advantage::Normal);
+
+        state.encounter.rules.pack_tactics = true;
+        assert_eq!(advantage(&state, false), Advantage::Advantage);
+
+        // a downed packmate doesn't help
+        Transition::HealthModification {
+            target: packmate,
+            delta: -20,
+        }
+        .apply(&mut state)?;
+        assert_eq!(advantage(&state, true), Advantage::Normal);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lighting() {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        let drow = state.add_actor(ActorBuilder::new("Drow").group(1).darkvision(120).build());
+        state.encounter.lighting = Lighting::Darkness;
+        state.distance = Some(30);
+
+        let roll = |state: &State, attacker: ActorId, target: ActorId| {
+            let (attacker, target) = (
+                state.get_actor(attacker).unwrap(),
+                state.get_actor(target).unwrap(),
+            );
+            (
+                state
+                    .attack_roll_settings(attacker, target, false, RollSettings::default())
+                    .advantage,
+                state.passive_perception(attacker, target),
+            )
+        };
+        // off by default, so darkness changes nothing
+        assert_eq!(roll(&state, hero, drow), (Advantage::Normal, 10));
+
+        state.encounter.rules.lighting = true;
+        assert_eq!(roll(&state, hero, drow), (Advantage::Disadvantage, 5));
+        // darkvision sees darkness as dim light, which is enough to attack by but still hinders
+        // Perception
+        assert_eq!(roll(&state, drow, hero), (Advantage::Advantage, 5));
+
+        // a torch lights up the hero but not the drow
+        state
+            .encounter
+            .actor_lighting
+            .insert(hero, Lighting::Bright);
+        assert_eq!(roll(&state, drow, hero), (Advantage::Advantage, 10));
+        assert_eq!(roll(&state, hero, drow), (Advantage::Disadvantage, 5));
+    }
+}
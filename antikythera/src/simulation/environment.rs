@@ -0,0 +1,219 @@
+//! Environmental effects that fire on a schedule, for set-piece battles: a ceiling that collapses
+//! in round 3, a lava floor that burns whoever stands on it every round.
+//!
+//! They're written in the same effect language as homebrew abilities:
+//!
+//! ```json
+//! "environment": [{
+//!     "name": "Ceiling collapse",
+//!     "schedule": { "AtRound": 3 },
+//!     "area": { "Zone": 1 },
+//!     "effects": { "save": "DEX", "dc": 15, "fail": { "damage": "2d10 bludgeoning" } }
+//! }]
+//! ```
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        actor::{Actor, ActorId},
+        effects::{Effect, EffectTarget},
+        homebrew::{EffectSpec, one_or_many},
+    },
+    simulation::{positioning::ZoneId, state::State, transition::Transition},
+};
+
+/// When an environmental effect fires, by round number counting from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Schedule {
+    /// Once, at the start of the given round.
+    AtRound(u64),
+    /// At the start of every round from `start` through `end`, or until combat ends if there's no
+    /// `end`.
+    EveryRound {
+        #[serde(default = "first_round")]
+        start: u64,
+        #[serde(default)]
+        end: Option<u64>,
+    },
+}
+
+fn first_round() -> u64 {
+    1
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::EveryRound {
+            start: first_round(),
+            end: None,
+        }
+    }
+}
+
+impl Schedule {
+    pub fn fires_in(&self, round: u64) -> bool {
+        match *self {
+            Schedule::AtRound(at) => round == at,
+            Schedule::EveryRound { start, end } => {
+                round >= start && end.is_none_or(|end| round <= end)
+            }
+        }
+    }
+}
+
+/// Who an environmental effect lands on.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EffectArea {
+    #[default]
+    Everyone,
+    /// Everyone standing in a zone, when the encounter uses
+    /// [`EncounterSetup::zones`](crate::simulation::encounter::EncounterSetup::zones).
+    Zone(ZoneId),
+    Group(u32),
+    Actors(BTreeSet<ActorId>),
+}
+
+impl EffectArea {
+    pub fn contains(&self, state: &State, actor: &Actor) -> bool {
+        match self {
+            EffectArea::Everyone => true,
+            EffectArea::Zone(zone) => state.zone_of(actor.id) == Some(*zone),
+            EffectArea::Group(group) => actor.group == *group,
+            EffectArea::Actors(actors) => actors.contains(&actor.id),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EnvironmentalEffect {
+    pub name: String,
+    #[serde(default)]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub area: EffectArea,
+    /// What happens to each actor in the area. Both `"self"` and `"target"` mean that actor.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub effects: Vec<EffectSpec>,
+}
+
+impl EnvironmentalEffect {
+    pub fn compile(&self) -> anyhow::Result<Vec<Effect>> {
+        let mut effects = Vec::new();
+        for spec in &self.effects {
+            effects.extend(spec.compile(EffectTarget::Target)?);
+        }
+        Ok(effects)
+    }
+
+    /// The living actors this effect lands on right now.
+    pub fn targets(&self, state: &State) -> Vec<ActorId> {
+        state
+            .actors
+            .values()
+            .filter(|actor| {
+                !actor.is_dead()
+                    && !state.fallen.contains(&actor.id)
+                    && !state.reserves.contains(&actor.id)
+                    && self.area.contains(state, actor)
+            })
+            .map(|actor| actor.id)
+            .collect()
+    }
+}
+
+/// Something in a zone that hurts whoever moves into it, like a lava floor or a patch of caltrops.
+/// See [`ZoneMap::hazards`](crate::simulation::positioning::ZoneMap::hazards).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Hazard {
+    pub name: String,
+    /// What happens to each actor that enters. Both `"self"` and `"target"` mean that actor.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub effects: Vec<EffectSpec>,
+}
+
+impl Hazard {
+    pub fn compile(&self) -> anyhow::Result<Vec<Effect>> {
+        let mut effects = Vec::new();
+        for spec in &self.effects {
+            effects.extend(spec.compile(EffectTarget::Target)?);
+        }
+        Ok(effects)
+    }
+}
+
+/// Follows whether environmental effects are firing, from each
+/// [`Transition::EnvironmentalEffect`] until the next turn starts and from each
+/// [`Transition::HazardEntered`] until the move into the zone finishes, so what they do isn't
+/// credited to whoever's turn it happens to be.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EnvironmentFiring(bool);
+
+impl EnvironmentFiring {
+    pub fn observe(&mut self, transition: &Transition) {
+        match transition {
+            Transition::EnvironmentalEffect { .. } | Transition::HazardEntered { .. } => {
+                self.0 = true
+            }
+            Transition::BeginCombat
+            | Transition::ZoneEntered { .. }
+            | Transition::AdvanceInitiative
+            | Transition::BeginTurn { .. } => self.0 = false,
+            _ => {}
+        }
+    }
+
+    pub fn is_firing(&self) -> bool {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_from_json() -> anyhow::Result<()> {
+        let json = r#"[
+            {
+                "name": "Ceiling collapse",
+                "schedule": { "AtRound": 3 },
+                "area": { "Zone": 1 },
+                "effects": { "save": "DEX", "dc": 15, "fail": { "damage": "2d10" } }
+            },
+            {
+                "name": "Lava",
+                "area": { "Group": 1 },
+                "effects": [{ "damage": "1d6 fire" }]
+            }
+        ]"#;
+        let environment: Vec<EnvironmentalEffect> = serde_json::from_str(json)?;
+        let [collapse, lava] = &environment[..] else {
+            anyhow::bail!("Expected two environmental effects");
+        };
+        assert!(!collapse.schedule.fires_in(2));
+        assert!(collapse.schedule.fires_in(3));
+        assert!(!collapse.schedule.fires_in(4));
+        assert!(lava.schedule.fires_in(1) && lava.schedule.fires_in(100));
+        assert!(matches!(
+            collapse.compile()?[..],
+            [Effect::SavingThrow { .. }]
+        ));
+        assert_eq!(lava.compile()?.len(), 1);
+
+        let window = Schedule::EveryRound {
+            start: 2,
+            end: Some(3),
+        };
+        assert_eq!(
+            (1..=4)
+                .map(|round| window.fires_in(round))
+                .collect::<Vec<_>>(),
+            [false, true, true, false]
+        );
+
+        Ok(())
+    }
+}
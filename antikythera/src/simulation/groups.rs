@@ -0,0 +1,113 @@
+//! Names and display settings for the groups actors fight in, which are otherwise bare numbers.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::state::State;
+
+/// What a group is called and how it's shown. None of it affects combat.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GroupInfo {
+    #[serde(default)]
+    pub name: String,
+    /// An sRGB color for the group's actors in the GUI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[u8; 3]>,
+    /// Whether this group is the players' side, as opposed to the monsters.
+    #[serde(default)]
+    pub player_party: bool,
+}
+
+impl GroupInfo {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn color(mut self, color: [u8; 3]) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn player_party(mut self) -> Self {
+        self.player_party = true;
+        self
+    }
+}
+
+impl State {
+    /// A group's name, or "Group N" if it hasn't been given one.
+    pub fn group_name(&self, group: u32) -> String {
+        match self.groups.get(&group) {
+            Some(info) if !info.name.is_empty() => info.name.clone(),
+            _ => format!("Group {}", group),
+        }
+    }
+
+    /// The group with the given name, ignoring case.
+    pub fn group_by_name(&self, name: &str) -> Option<u32> {
+        self.groups
+            .iter()
+            .find(|(_, info)| info.name.eq_ignore_ascii_case(name))
+            .map(|(group, _)| *group)
+    }
+
+    /// Every group with actors in it or a [`GroupInfo`] entry.
+    pub fn group_ids(&self) -> BTreeSet<u32> {
+        self.actors
+            .values()
+            .map(|actor| actor.group)
+            .chain(self.groups.keys().copied())
+            .collect()
+    }
+
+    /// Whether `group` is marked as the players' side.
+    pub fn is_player_party(&self, group: u32) -> bool {
+        self.groups
+            .get(&group)
+            .is_some_and(|info| info.player_party)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::actor::ActorBuilder;
+
+    #[test]
+    fn test_group_names() -> anyhow::Result<()> {
+        let mut state = State::new();
+        state.add_actor(ActorBuilder::new("Hero").build());
+        state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        state.groups.insert(
+            0,
+            GroupInfo::new("Party").color([40, 120, 220]).player_party(),
+        );
+
+        assert_eq!(state.group_name(0), "Party");
+        assert_eq!(state.group_name(1), "Group 1");
+        assert_eq!(state.group_by_name("party"), Some(0));
+        assert_eq!(state.group_by_name("Monsters"), None);
+        assert!(state.is_player_party(0) && !state.is_player_party(1));
+        assert_eq!(state.group_ids(), BTreeSet::from([0, 1]));
+
+        // names are cosmetic, so they don't split states in the tree
+        let renamed = {
+            let mut state = state.clone();
+            state.groups.get_mut(&0).unwrap().name = "Heroes".to_string();
+            state
+        };
+        assert_eq!(
+            crate::simulation::state_tree::StateHash::hash_state(&state),
+            crate::simulation::state_tree::StateHash::hash_state(&renamed)
+        );
+
+        let loaded = State::from_json(&serde_json::to_string(&state)?)?;
+        assert_eq!(loaded.groups, state.groups);
+
+        Ok(())
+    }
+}
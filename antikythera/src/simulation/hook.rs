@@ -1,17 +1,80 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{prelude::*, rules::actions::ActionTaken};
 
 #[allow(unused)]
 pub trait Hook: Send + Sync {
     fn on_integration_start(&mut self, initial_state: &State) {}
     fn on_combat_start(&mut self, state: &State) {}
+    /// Called after every transition is applied, with the resulting state.
+    fn on_transition(&mut self, state: &State, transition: &Transition) {}
     fn on_turn_start(&mut self, state: &State, actor_id: ActorId, turn: u64) {}
     fn on_advance_initiative(&mut self, state: &State, actor_id: ActorId) {}
+    /// Called before each chosen action is executed, with what the policy weighed to choose it.
+    /// Only called when the integrator records decisions.
+    fn on_decision(&mut self, state: &State, decision: &DecisionAudit) {}
     fn on_action_executed(&mut self, state: &State, action: &ActionTaken) {}
     fn on_turn_end(&mut self, state: &State, actor_id: ActorId, turn: u64) {}
     fn on_combat_end(&mut self, state: &State) {}
+    /// Transitions to apply at the end of every turn, like a
+    /// [`Transition::PhaseEntered`] for a phase change a [`Phase`] threshold can't express.
+    /// They're applied as they are, so they should leave the state consistent.
+    fn inject_transitions(&mut self, state: &State) -> Vec<Transition> {
+        vec![]
+    }
+    /// Called every [`MEMORY_CHECK_INTERVAL`] combats with roughly how much memory the state
+    /// tree takes up.
+    fn on_memory_usage(&mut self, usage: &TreeMemoryUsage) {}
     fn on_integration_end(&mut self) {}
 
     fn metrics(&self) -> Vec<(String, f64)> {
         vec![]
     }
+
+    /// Intermediate metrics emitted since the last call, like a running average. The integrator
+    /// takes them after every combat and at the end of the run, and adds them to
+    /// [`IntegrationResults::metric_stream`].
+    fn take_emitted(&mut self) -> Vec<(String, f64)> {
+        vec![]
+    }
+}
+
+/// A metric emitted by a hook partway through a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricSample {
+    /// How many combats had finished when the metric was collected.
+    pub combat: usize,
+    pub name: String,
+    pub value: f64,
+}
+
+/// A hook registered with an [`Integrator`], with the name it was registered under, if any.
+pub struct RegisteredHook {
+    pub name: Option<String>,
+    /// Disabled hooks aren't called and report no metrics.
+    pub enabled: bool,
+    pub hook: Box<dyn Hook>,
+}
+
+impl RegisteredHook {
+    /// The hook's metrics, prefixed with its name (`"lua.kills"`) if it has one.
+    pub fn metrics(&self) -> Vec<(String, f64)> {
+        self.prefixed(self.hook.metrics())
+    }
+
+    /// The hook's newly emitted metrics, prefixed the same way as [`Self::metrics`].
+    pub fn take_emitted(&mut self) -> Vec<(String, f64)> {
+        let emitted = self.hook.take_emitted();
+        self.prefixed(emitted)
+    }
+
+    fn prefixed(&self, metrics: Vec<(String, f64)>) -> Vec<(String, f64)> {
+        match &self.name {
+            Some(name) => metrics
+                .into_iter()
+                .map(|(metric, value)| (format!("{}.{}", name, metric), value))
+                .collect(),
+            None => metrics,
+        }
+    }
 }
@@ -3,6 +3,7 @@ use std::{
     sync::{
         Arc,
         atomic::{AtomicUsize, Ordering},
+        mpsc,
     },
 };
 
@@ -10,26 +11,163 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     prelude::{
-        Action, ActionEconomyUsage, ActionTaken, ActorId, ItemInner, RollSettings, Transition,
+        Action, ActionEconomyUsage, ActionTaken, ActorId, ItemInner, RollSettings, Skill,
+        Transition,
+    },
+    rules::{
+        abilities::Ability,
+        actions::{
+            AttackAction, GrappleAction, HelpAction, POWER_ATTACK_DAMAGE_BONUS,
+            POWER_ATTACK_HIT_PENALTY, ShoveAction, UnarmedStrikeAction, UseAbilityAction,
+        },
+        conditions::{Condition, Contest},
+        damage::DamageType,
+        dice::{RollPlan, RollResult},
+        effects::{Effect, EffectTarget, EffectTrigger},
+        features::Feature,
+        rerolls::D20Test,
+        saves::SavingThrow,
     },
-    rules::actions::{AttackAction, UnarmedStrikeAction},
     simulation::{
-        hook::Hook,
+        classification::{OutcomeClassifier, OutcomeLabels},
+        contribution::ContributionStats,
+        encounter::DeathCleanup,
+        environment::EnvironmentalEffect,
+        hook::{Hook, MetricSample, RegisteredHook},
+        messages::{LogMessage, MessageTemplates},
+        policy::DecisionAudit,
+        positioning::ZoneId,
+        roll_statistics::RollStatistics,
         roller::Roller,
+        schema::SCHEMA_VERSION,
         state::State,
-        state_tree::{NodeIndex, StateTree},
+        state_tree::{NodeIndex, StateHash, StateTree, TreeMemoryUsage, TreeMode},
+        time_series::HpTimeSeries,
+        timed_effects::{Expiry, TimedEffect},
+        timeline::EventTimeline,
+        turn_order::TurnOrderStats,
+        usage::ActionUsage,
+        weapon_stats::WeaponStats,
     },
     utils::ProtectedCell,
 };
 
 pub type Timestamp = chrono::DateTime<chrono::Utc>;
 
+/// Where a set of results came from, so that saved results describe themselves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    /// The version of this library that produced the results.
+    pub version: String,
+    pub started_at: Timestamp,
+    /// The seed the roller was created from, if it was created from one.
+    pub seed: Option<u64>,
+    pub combats: usize,
+    /// Hash of everything in the initial state that affects combat. Results from the same
+    /// version with the same hash ran the same scenario under the same rules.
+    pub initial_state_hash: StateHash,
+    /// The machine the run happened on, if the integrator was asked to record it.
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// The wall-clock time the run was given, if it ran under [`StoppingCondition::TimeBudget`]
+    /// rather than to a number of combats.
+    #[serde(default)]
+    pub time_budget: Option<std::time::Duration>,
+}
+
+impl RunMetadata {
+    pub fn new(initial_state: &State, seed: Option<u64>, combats: usize) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: chrono::Utc::now(),
+            seed,
+            combats,
+            initial_state_hash: StateHash::hash_state(initial_state),
+            hostname: None,
+            time_budget: None,
+        }
+    }
+
+    /// Combines the metadata of two runs over the same initial state. The seed is kept only if
+    /// both runs share it. Time budgets are added up, like elapsed times, and dropped unless both
+    /// runs had one.
+    pub fn merge(&mut self, other: &RunMetadata) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.version == other.version,
+            "Cannot merge results from versions {} and {}",
+            self.version,
+            other.version
+        );
+        anyhow::ensure!(
+            self.initial_state_hash == other.initial_state_hash,
+            "Cannot merge results from different initial states"
+        );
+        self.started_at = self.started_at.min(other.started_at);
+        if self.seed != other.seed {
+            self.seed = None;
+        }
+        self.combats += other.combats;
+        if self.hostname != other.hostname {
+            self.hostname = None;
+        }
+        self.time_budget = self
+            .time_budget
+            .zip(other.time_budget)
+            .map(|(budget, other_budget)| budget + other_budget);
+        Ok(())
+    }
+}
+
+fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IntegrationResults {
+    /// See [`SCHEMA_VERSION`]. Results saved before versioning load as version 0.
+    #[serde(default)]
+    pub schema_version: u32,
     pub state_tree: StateTree,
     pub combats_run: usize,
     pub elapsed_time: chrono::Duration,
+    /// What each hook reported at the end of the run. Merged results average each metric over the
+    /// combats of the sides that reported it; see [`IntegrationResults::hook_metric_combats`].
     pub hook_metrics: Vec<(String, f64)>,
+    /// How many combats each hook metric was averaged over, for metrics that only some of the
+    /// merged runs reported. Metrics missing here cover all [`IntegrationResults::combats_run`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hook_metric_combats: BTreeMap<String, usize>,
+    /// Every metric hooks emitted during the run, in the order they were collected.
+    /// See [`Hook::take_emitted`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metric_stream: Vec<MetricSample>,
+    #[serde(default)]
+    pub hp_time_series: Option<HpTimeSeries>,
+    #[serde(default)]
+    pub roll_statistics: Option<RollStatistics>,
+    #[serde(default)]
+    pub event_timeline: Option<EventTimeline>,
+    #[serde(default)]
+    pub contributions: Option<ContributionStats>,
+    #[serde(default)]
+    pub action_usage: Option<ActionUsage>,
+    #[serde(default)]
+    pub outcome_labels: Option<OutcomeLabels>,
+    #[serde(default)]
+    pub turn_order: Option<TurnOrderStats>,
+    #[serde(default)]
+    pub weapon_stats: Option<WeaponStats>,
+    /// What the integrator did when the state tree outgrew its [`MemoryLimit`], if it did.
+    #[serde(default)]
+    pub memory_limit_reached: Option<MemoryLimitAction>,
+    /// Missing from results saved before it was recorded.
+    #[serde(default)]
+    pub metadata: Option<RunMetadata>,
 }
 
 impl IntegrationResults {
@@ -41,31 +179,489 @@ impl IntegrationResults {
             0.0
         }
     }
+
+    /// A hook metric's value and how many combats it was averaged over, if it was reported.
+    pub fn hook_metric(&self, name: &str) -> Option<(f64, usize)> {
+        let (_, value) = self
+            .hook_metrics
+            .iter()
+            .find(|(metric, _)| metric == name)?;
+        let combats = self
+            .hook_metric_combats
+            .get(name)
+            .copied()
+            .unwrap_or(self.combats_run);
+        Some((*value, combats))
+    }
+
+    /// Combines the results of another run over the same initial state into these, such as
+    /// another shard of a distributed run.
+    ///
+    /// State trees, HP time series, roll statistics, event timelines, contributions, action
+    /// usage, outcome labels, turn order statistics and weapon statistics are merged exactly, as
+    /// integer counts, so probabilities computed from them come out the same whatever order the
+    /// shards are merged in. Elapsed times are added up, so the
+    /// combats per second are per worker. Hook metrics are averaged, weighted by the combats of the
+    /// sides that reported them.
+    /// The other run's metric stream is appended, counting its combats after these.
+    pub fn merge(&mut self, other: &IntegrationResults) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.hp_time_series.is_some() == other.hp_time_series.is_some(),
+            "Cannot merge results with and without HP time series"
+        );
+        anyhow::ensure!(
+            self.roll_statistics.is_some() == other.roll_statistics.is_some(),
+            "Cannot merge results with and without roll statistics"
+        );
+        anyhow::ensure!(
+            self.event_timeline.is_some() == other.event_timeline.is_some(),
+            "Cannot merge results with and without event timelines"
+        );
+        anyhow::ensure!(
+            self.contributions.is_some() == other.contributions.is_some(),
+            "Cannot merge results with and without contributions"
+        );
+        anyhow::ensure!(
+            self.action_usage.is_some() == other.action_usage.is_some(),
+            "Cannot merge results with and without action usage"
+        );
+        anyhow::ensure!(
+            self.outcome_labels.is_some() == other.outcome_labels.is_some(),
+            "Cannot merge results with and without outcome labels"
+        );
+        anyhow::ensure!(
+            self.turn_order.is_some() == other.turn_order.is_some(),
+            "Cannot merge results with and without turn order statistics"
+        );
+        anyhow::ensure!(
+            self.weapon_stats.is_some() == other.weapon_stats.is_some(),
+            "Cannot merge results with and without weapon statistics"
+        );
+        match (&mut self.metadata, &other.metadata) {
+            (Some(metadata), Some(other_metadata)) => metadata.merge(other_metadata)?,
+            _ => self.metadata = None,
+        }
+        self.state_tree.merge(&other.state_tree)?;
+        if let (Some(series), Some(other_series)) =
+            (&mut self.hp_time_series, &other.hp_time_series)
+        {
+            series.merge(other_series);
+        }
+        if let (Some(statistics), Some(other_statistics)) =
+            (&mut self.roll_statistics, &other.roll_statistics)
+        {
+            statistics.merge(other_statistics);
+        }
+        if let (Some(timeline), Some(other_timeline)) =
+            (&mut self.event_timeline, &other.event_timeline)
+        {
+            timeline.merge(other_timeline);
+        }
+        if let (Some(contributions), Some(other_contributions)) =
+            (&mut self.contributions, &other.contributions)
+        {
+            contributions.merge(other_contributions);
+        }
+        if let (Some(usage), Some(other_usage)) = (&mut self.action_usage, &other.action_usage) {
+            usage.merge(other_usage);
+        }
+        if let (Some(labels), Some(other_labels)) =
+            (&mut self.outcome_labels, &other.outcome_labels)
+        {
+            labels.merge(other_labels);
+        }
+        if let (Some(turn_order), Some(other_turn_order)) =
+            (&mut self.turn_order, &other.turn_order)
+        {
+            turn_order.merge(other_turn_order);
+        }
+        if let (Some(weapons), Some(other_weapons)) = (&mut self.weapon_stats, &other.weapon_stats)
+        {
+            weapons.merge(other_weapons);
+        }
+        self.memory_limit_reached = self.memory_limit_reached.max(other.memory_limit_reached);
+
+        self.metric_stream
+            .extend(other.metric_stream.iter().map(|sample| MetricSample {
+                combat: sample.combat + self.combats_run,
+                ..sample.clone()
+            }));
+
+        // each metric is averaged over only the combats of the sides that reported it, so a hook
+        // disabled on one side doesn't drag the other side's numbers down
+        let combats = self.combats_run + other.combats_run;
+        let mut names: Vec<String> = self.hook_metrics.iter().map(|(n, _)| n.clone()).collect();
+        for (name, _) in &other.hook_metrics {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        let mut hook_metrics = Vec::with_capacity(names.len());
+        let mut hook_metric_combats = BTreeMap::new();
+        for name in names {
+            let (value, runs_reported) = match (self.hook_metric(&name), other.hook_metric(&name)) {
+                (Some((value, runs)), Some((other_value, other_runs))) if runs + other_runs > 0 => {
+                    let runs_reported = runs + other_runs;
+                    let total = value * runs as f64 + other_value * other_runs as f64;
+                    (total / runs_reported as f64, runs_reported)
+                }
+                (Some(reported), _) | (None, Some(reported)) => reported,
+                (None, None) => unreachable!("{} was reported by one side", name),
+            };
+            if runs_reported != combats {
+                hook_metric_combats.insert(name.clone(), runs_reported);
+            }
+            hook_metrics.push((name, value));
+        }
+        self.hook_metrics = hook_metrics;
+        self.hook_metric_combats = hook_metric_combats;
+
+        self.combats_run = combats;
+        self.elapsed_time += other.elapsed_time;
+        Ok(())
+    }
+}
+
+/// How much the integrator logs while it runs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Verbosity {
+    /// Nothing is logged.
+    #[default]
+    Off,
+    /// One line per combat, describing how it ended.
+    Summary,
+    /// Every transition is logged as it is applied, in addition to the per-combat summaries.
+    Full,
+}
+
+/// When an [`Integrator`] stops running combats.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StoppingCondition {
+    /// Once [`Integrator::min_combats`] combats have run.
+    #[default]
+    MinCombats,
+    /// Once this much wall-clock time has passed since the run began, however many combats that
+    /// is. The combat running when time runs out is finished, so the run overshoots slightly.
+    TimeBudget(std::time::Duration),
+}
+
+/// What the integrator does when its state tree grows past a [`MemoryLimit`].
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum MemoryLimitAction {
+    /// Keep only the states combats end in from then on, converting the tree so far. If even
+    /// that outgrows the limit, stop.
+    #[default]
+    TerminalOnly,
+    /// Stop running combats, keeping the results so far.
+    Stop,
+}
+
+/// A soft cap on how much memory the state tree may take up, as measured by
+/// [`StateTree::memory_usage`] every [`MEMORY_CHECK_INTERVAL`] combats. It's checked between
+/// combats, so the tree can overshoot it a little.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryLimit {
+    pub bytes: usize,
+    pub action: MemoryLimitAction,
+}
+
+/// How many combats run between measurements of the state tree's memory usage.
+pub const MEMORY_CHECK_INTERVAL: usize = 100;
+
+/// Effects compiled from the initial state's effect specs once a run, rather than every time they
+/// fire. Specs that don't compile are left out, to fail with their error when they fire.
+#[derive(Debug, Default, Clone)]
+struct CompiledEffects {
+    /// By index into the encounter's environmental effects.
+    environment: BTreeMap<usize, Arc<[Effect]>>,
+    /// By the zone the hazard is in.
+    hazards: BTreeMap<ZoneId, Arc<[Effect]>>,
+    /// Each actor's homebrew effects, by trigger. Triggers with no effects are left out too.
+    homebrew: BTreeMap<(ActorId, EffectTrigger), Arc<[Effect]>>,
+}
+
+impl CompiledEffects {
+    fn new(state: &State) -> Self {
+        let environment = state
+            .encounter
+            .environment
+            .iter()
+            .enumerate()
+            .filter_map(|(index, environmental)| {
+                Some((index, environmental.compile().ok()?.into()))
+            })
+            .collect();
+        let hazards = state
+            .encounter
+            .zones
+            .iter()
+            .flat_map(|zones| &zones.hazards)
+            .filter_map(|(&zone, hazard)| Some((zone, hazard.compile().ok()?.into())))
+            .collect();
+        let homebrew = state
+            .actors
+            .values()
+            .flat_map(|actor| {
+                [
+                    EffectTrigger::TurnStart,
+                    EffectTrigger::Attack,
+                    EffectTrigger::Hit,
+                ]
+                .into_iter()
+                .filter(|trigger| !actor.homebrew.specs(*trigger).is_empty())
+                .filter_map(|trigger| {
+                    let effects = actor.homebrew.compile(trigger).ok()?;
+                    Some(((actor.id, trigger), effects.into()))
+                })
+            })
+            .collect();
+        Self {
+            environment,
+            hazards,
+            homebrew,
+        }
+    }
 }
 
 pub struct Integrator {
     pub min_combats: usize,
+    pub stopping_condition: StoppingCondition,
+    pub memory_limit: Option<MemoryLimit>,
+    /// The state tree's memory usage as last measured.
+    pub memory_usage: TreeMemoryUsage,
+    memory_limit_reached: Option<MemoryLimitAction>,
     pub combats_run: Arc<AtomicUsize>,
     pub start_time: Timestamp,
     pub roller: Roller,
     pub initial_state: State,
-    pub hooks: Vec<Box<dyn Hook>>,
+    /// Hooks in the order they're called: the order they were added in.
+    pub hooks: Vec<RegisteredHook>,
+    pub hp_time_series: Option<HpTimeSeries>,
+    pub event_timeline: Option<EventTimeline>,
+    pub contributions: Option<ContributionStats>,
+    pub action_usage: Option<ActionUsage>,
+    pub outcome_classifier: Option<Box<dyn OutcomeClassifier>>,
+    pub outcome_labels: Option<OutcomeLabels>,
+    pub turn_order: Option<TurnOrderStats>,
+    pub weapon_stats: Option<WeaponStats>,
+    pub record_decisions: bool,
+    pub record_hostname: bool,
+    pub batch_actions: bool,
+    pub verbosity: Verbosity,
+    /// How transitions and decisions are worded in the log.
+    pub messages: MessageTemplates,
+    pub tree_mode: TreeMode,
+    pub metric_stream: Vec<MetricSample>,
+    metric_tx: Option<mpsc::Sender<MetricSample>>,
+    metadata: Option<RunMetadata>,
+    compiled: CompiledEffects,
 }
 
 impl Integrator {
     pub fn new(min_combats: usize, roller: Roller, initial_state: State) -> Self {
         Self {
             min_combats,
+            stopping_condition: StoppingCondition::default(),
+            memory_limit: None,
+            memory_usage: TreeMemoryUsage::default(),
+            memory_limit_reached: None,
             combats_run: Arc::new(AtomicUsize::new(0)),
             start_time: chrono::Utc::now(),
             roller,
             initial_state,
             hooks: Vec::new(),
+            hp_time_series: None,
+            event_timeline: None,
+            contributions: None,
+            action_usage: None,
+            outcome_classifier: None,
+            outcome_labels: None,
+            turn_order: None,
+            weapon_stats: None,
+            record_decisions: false,
+            record_hostname: false,
+            batch_actions: false,
+            verbosity: Verbosity::default(),
+            messages: MessageTemplates::default(),
+            tree_mode: TreeMode::default(),
+            metric_stream: Vec::new(),
+            metric_tx: None,
+            metadata: None,
+            compiled: CompiledEffects::default(),
+        }
+    }
+
+    /// Runs for a wall-clock budget instead of to [`Self::min_combats`], for the best estimate
+    /// that can be had in that time. The results report how many combats fit in it.
+    pub fn set_stopping_condition(&mut self, stopping_condition: StoppingCondition) {
+        self.stopping_condition = stopping_condition;
+    }
+
+    /// Keeps the state tree under roughly `bytes`, doing `action` once it grows past them,
+    /// instead of running out of memory. The results record if that happened.
+    pub fn set_memory_limit(&mut self, bytes: usize, action: MemoryLimitAction) {
+        self.memory_limit = Some(MemoryLimit { bytes, action });
+    }
+
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+        self.verbosity = verbosity;
+    }
+
+    /// Rewords the log with `messages` instead of the built-in English.
+    pub fn set_messages(&mut self, messages: MessageTemplates) {
+        self.messages = messages;
+    }
+
+    /// How much of each combat the state tree keeps. Anything short of [`TreeMode::Full`] skips
+    /// hashing and storing the states in between, for faster runs that only need summaries.
+    pub fn set_tree_mode(&mut self, tree_mode: TreeMode) {
+        self.tree_mode = tree_mode;
+    }
+
+    /// Records every actor's HP at the end of each round into [`IntegrationResults::hp_time_series`].
+    pub fn record_hp_time_series(&mut self, enabled: bool) {
+        self.hp_time_series = enabled.then(HpTimeSeries::new);
+    }
+
+    /// Records the round of each combat's first blood, downs, deaths and end into
+    /// [`IntegrationResults::event_timeline`].
+    pub fn record_event_timeline(&mut self, enabled: bool) {
+        self.event_timeline = enabled.then(EventTimeline::new);
+    }
+
+    /// Tallies each actor's damage, healing, kills and assists into
+    /// [`IntegrationResults::contributions`].
+    pub fn record_contributions(&mut self, enabled: bool) {
+        self.contributions = enabled.then(ContributionStats::new);
+    }
+
+    /// Counts which actions each actor took, and on which rounds, into
+    /// [`IntegrationResults::action_usage`].
+    pub fn record_action_usage(&mut self, enabled: bool) {
+        self.action_usage = enabled.then(ActionUsage::new);
+    }
+
+    /// Labels the state each combat ends in with `classifier`, counting the labels into
+    /// [`IntegrationResults::outcome_labels`].
+    pub fn classify_outcomes(&mut self, classifier: impl OutcomeClassifier + 'static) {
+        self.outcome_classifier = Some(Box::new(classifier));
+        self.outcome_labels = Some(OutcomeLabels::new());
+    }
+
+    /// Tallies damage and survival by initiative position, and each group's wins with and without
+    /// the first turn, into [`IntegrationResults::turn_order`].
+    pub fn record_turn_order(&mut self, enabled: bool) {
+        self.turn_order = enabled.then(TurnOrderStats::new);
+    }
+
+    /// Tallies each weapon's attacks, hits, crits and damage, by target and by target AC, into
+    /// [`IntegrationResults::weapon_stats`].
+    pub fn record_weapon_stats(&mut self, enabled: bool) {
+        self.weapon_stats = enabled.then(WeaponStats::new);
+    }
+
+    /// Records what every policy weighed for each action it chose, passing it to
+    /// [`Hook::on_decision`] and logging it at [`Verbosity::Full`].
+    pub fn record_decisions(&mut self, enabled: bool) {
+        self.record_decisions = enabled;
+    }
+
+    /// Records everything an action does as one [`Transition::Composite`] edge in the state
+    /// tree, instead of an edge per transition, for smaller trees that read like a combat log.
+    /// Hooks still see every transition on its own.
+    pub fn batch_actions(&mut self, enabled: bool) {
+        self.batch_actions = enabled;
+    }
+
+    /// Includes the machine's hostname in [`RunMetadata::hostname`].
+    pub fn record_hostname(&mut self, enabled: bool) {
+        self.record_hostname = enabled;
+    }
+
+    /// Tallies every die rolled into [`IntegrationResults::roll_statistics`].
+    pub fn record_roll_statistics(&mut self, enabled: bool) {
+        self.roller.record_roll_statistics(enabled);
+    }
+
+    /// Also sends every metric hooks emit to `tx` as soon as it's collected, so it can be watched
+    /// while the run is still going.
+    pub fn stream_metrics(&mut self, tx: mpsc::Sender<MetricSample>) {
+        self.metric_tx = Some(tx);
+    }
+
+    /// Takes the metrics hooks have emitted into [`Self::metric_stream`].
+    fn collect_emitted(&mut self) {
+        let combat = self.combats_run();
+        for hook in self.hooks.iter_mut().filter(|hook| hook.enabled) {
+            for (name, value) in hook.take_emitted() {
+                let sample = MetricSample {
+                    combat,
+                    name,
+                    value,
+                };
+                if let Some(tx) = &self.metric_tx
+                    && tx.send(sample.clone()).is_err()
+                {
+                    // nobody's watching anymore
+                    self.metric_tx = None;
+                }
+                self.metric_stream.push(sample);
+            }
         }
     }
 
+    /// Adds an unnamed hook, called after every hook added before it. Its metrics are reported
+    /// as-is.
     pub fn add_hook<H: Hook + 'static>(&mut self, hook: H) {
-        self.hooks.push(Box::new(hook));
+        self.hooks.push(RegisteredHook {
+            name: None,
+            enabled: true,
+            hook: Box::new(hook),
+        });
+    }
+
+    /// Adds a hook under a name, which prefixes its metrics and lets it be enabled, disabled, or
+    /// removed later. Adding a hook under a name already in use replaces that hook in place.
+    pub fn add_named_hook<H: Hook + 'static>(&mut self, name: &str, hook: H) {
+        let registered = RegisteredHook {
+            name: Some(name.to_string()),
+            enabled: true,
+            hook: Box::new(hook),
+        };
+        match self.hook_index(name) {
+            Some(index) => self.hooks[index] = registered,
+            None => self.hooks.push(registered),
+        }
+    }
+
+    fn hook_index(&self, name: &str) -> Option<usize> {
+        self.hooks
+            .iter()
+            .position(|hook| hook.name.as_deref() == Some(name))
+    }
+
+    /// Turns a named hook on or off for the next run.
+    pub fn set_hook_enabled(&mut self, name: &str, enabled: bool) -> anyhow::Result<()> {
+        let index = self
+            .hook_index(name)
+            .ok_or_else(|| anyhow::anyhow!("No hook named `{}`", name))?;
+        self.hooks[index].enabled = enabled;
+        Ok(())
+    }
+
+    pub fn remove_hook(&mut self, name: &str) -> Option<Box<dyn Hook>> {
+        let index = self.hook_index(name)?;
+        Some(self.hooks.remove(index).hook)
+    }
+
+    /// Enabled hooks, in the order they're called.
+    fn enabled_hooks(&mut self) -> impl Iterator<Item = &mut Box<dyn Hook>> {
+        self.hooks
+            .iter_mut()
+            .filter(|hook| hook.enabled)
+            .map(|hook| &mut hook.hook)
     }
 
     pub fn combats_run(&self) -> usize {
@@ -77,45 +673,201 @@ impl Integrator {
     }
 
     pub fn should_continue(&self) -> bool {
-        self.combats_run() < self.min_combats
+        if self.memory_limit_reached == Some(MemoryLimitAction::Stop) {
+            return false;
+        }
+        match self.stopping_condition {
+            StoppingCondition::MinCombats => self.combats_run() < self.min_combats,
+            StoppingCondition::TimeBudget(budget) => {
+                self.elapsed_time().to_std().unwrap_or_default() < budget
+            }
+        }
+    }
+
+    /// How far through the run this is, from 0 to 1, by combats or by time spent depending on
+    /// the stopping condition.
+    pub fn progress(&self) -> f64 {
+        let progress = match self.stopping_condition {
+            StoppingCondition::MinCombats if self.min_combats == 0 => 1.0,
+            StoppingCondition::MinCombats => self.combats_run() as f64 / self.min_combats as f64,
+            StoppingCondition::TimeBudget(budget) if budget.is_zero() => 1.0,
+            StoppingCondition::TimeBudget(budget) => {
+                self.elapsed_time()
+                    .to_std()
+                    .unwrap_or_default()
+                    .as_secs_f64()
+                    / budget.as_secs_f64()
+            }
+        };
+        progress.min(1.0)
     }
 
     pub fn elapsed_time(&self) -> chrono::Duration {
         chrono::Utc::now() - self.start_time
     }
 
-    pub fn run(&mut self) -> anyhow::Result<IntegrationResults> {
-        for hook in &mut self.hooks {
-            hook.on_integration_start(&self.initial_state);
+    /// Notifies hooks that integration is starting and returns a fresh state tree to run combats into.
+    pub fn begin(&mut self) -> StateTree {
+        for hook in self.hooks.iter_mut().filter(|hook| hook.enabled) {
+            hook.hook.on_integration_start(&self.initial_state);
+        }
+        if let Some(series) = &mut self.hp_time_series {
+            *series = HpTimeSeries::new();
+        }
+        if let Some(timeline) = &mut self.event_timeline {
+            *timeline = EventTimeline::new();
+        }
+        if let Some(contributions) = &mut self.contributions {
+            *contributions = ContributionStats::new();
+        }
+        if let Some(usage) = &mut self.action_usage {
+            *usage = ActionUsage::new();
+        }
+        if let Some(labels) = &mut self.outcome_labels {
+            *labels = OutcomeLabels::new();
+        }
+        if let Some(turn_order) = &mut self.turn_order {
+            *turn_order = TurnOrderStats::new();
+        }
+        if let Some(weapons) = &mut self.weapon_stats {
+            *weapons = WeaponStats::new();
         }
-        let mut state_tree = StateTree::new(self.initial_state.clone());
+        if self.roller.roll_statistics().is_some() {
+            self.roller.record_roll_statistics(true);
+        }
+        self.memory_usage = TreeMemoryUsage::default();
+        self.memory_limit_reached = None;
+        self.compiled = CompiledEffects::new(&self.initial_state);
         self.start_time = chrono::Utc::now();
-        while self.should_continue() {
-            self.run_combat(&mut state_tree)?;
+        let mut metadata =
+            RunMetadata::new(&self.initial_state, self.roller.seed(), self.min_combats);
+        metadata.started_at = self.start_time;
+        if self.record_hostname {
+            metadata.hostname = hostname();
+        }
+        if let StoppingCondition::TimeBudget(budget) = self.stopping_condition {
+            metadata.time_budget = Some(budget);
         }
+        self.metadata = Some(metadata);
+        StateTree::with_mode(self.initial_state.clone(), self.tree_mode)
+    }
+
+    /// Notifies hooks that integration has ended and collects everything into an [`IntegrationResults`].
+    pub fn finish(&mut self, state_tree: StateTree) -> IntegrationResults {
         let elapsed_time = self.elapsed_time();
 
-        for hook in &mut self.hooks {
+        for hook in self.enabled_hooks() {
             hook.on_integration_end();
         }
+        self.collect_emitted();
         let hook_metrics = self
             .hooks
             .iter()
-            .flat_map(|hook| hook.metrics().into_iter())
+            .filter(|hook| hook.enabled)
+            .flat_map(|hook| hook.metrics())
             .collect();
-        let results = IntegrationResults {
+        IntegrationResults {
+            schema_version: SCHEMA_VERSION,
             state_tree,
             combats_run: self.combats_run(),
             elapsed_time,
             hook_metrics,
-        };
-        Ok(results)
+            hook_metric_combats: BTreeMap::new(),
+            metric_stream: self.metric_stream.clone(),
+            hp_time_series: self.hp_time_series.clone(),
+            roll_statistics: self.roller.roll_statistics().cloned(),
+            event_timeline: self.event_timeline.clone(),
+            contributions: self.contributions.clone(),
+            action_usage: self.action_usage.clone().map(|mut usage| {
+                usage.sort();
+                usage
+            }),
+            outcome_labels: self.outcome_labels.clone(),
+            turn_order: self.turn_order.clone(),
+            weapon_stats: self.weapon_stats.clone(),
+            memory_limit_reached: self.memory_limit_reached,
+            metadata: self.metadata.take().map(|metadata| RunMetadata {
+                combats: self.combats_run(),
+                ..metadata
+            }),
+        }
     }
 
+    pub fn run(&mut self) -> anyhow::Result<IntegrationResults> {
+        let mut state_tree = self.begin();
+        while self.should_continue() {
+            self.run_combat(&mut state_tree)?;
+        }
+        Ok(self.finish(state_tree))
+    }
+
+    /// Runs the next combat, with its own substream of the roller keyed by how many combats have
+    /// run so far.
     pub fn run_combat(&mut self, state_tree: &mut StateTree) -> anyhow::Result<()> {
-        CombatContext::new(self, state_tree).run_combat()?;
+        self.replay_combat(self.combats_run(), state_tree)?;
+        if self.combats_run().is_multiple_of(MEMORY_CHECK_INTERVAL) {
+            self.check_memory(state_tree)?;
+        }
+        Ok(())
+    }
+
+    /// Measures the state tree, tells hooks how big it is, and reins it in if it's outgrown the
+    /// memory limit.
+    fn check_memory(&mut self, state_tree: &mut StateTree) -> anyhow::Result<()> {
+        self.memory_usage = state_tree.memory_usage();
+        let usage = self.memory_usage;
+        for hook in self.enabled_hooks() {
+            hook.on_memory_usage(&usage);
+        }
+        let Some(limit) = self.memory_limit else {
+            return Ok(());
+        };
+        if usage.total() <= limit.bytes {
+            return Ok(());
+        }
+
+        let action = match (limit.action, state_tree.mode()) {
+            (MemoryLimitAction::TerminalOnly, TreeMode::Full) => MemoryLimitAction::TerminalOnly,
+            _ => MemoryLimitAction::Stop,
+        };
+        let mut report = String::new();
+        usage.pretty_print(&mut report)?;
+        match action {
+            MemoryLimitAction::TerminalOnly => {
+                log::warn!(
+                    "State tree reached {} after {} combats; keeping only terminal states from now on",
+                    report,
+                    self.combats_run()
+                );
+                state_tree.reduce_to(TreeMode::TerminalOnly)?;
+                self.memory_usage = state_tree.memory_usage();
+            }
+            MemoryLimitAction::Stop => {
+                log::warn!(
+                    "State tree reached {} after {} combats; stopping early",
+                    report,
+                    self.combats_run()
+                );
+            }
+        }
+        self.memory_limit_reached = Some(action);
         Ok(())
     }
+
+    /// Runs combat `index` of the run on its own. With the same seed and initial state, it makes
+    /// exactly the same transitions as it did in the full run.
+    pub fn replay_combat(
+        &mut self,
+        index: usize,
+        state_tree: &mut StateTree,
+    ) -> anyhow::Result<()> {
+        let substream = self.roller.for_combat(index as u64);
+        let roller = std::mem::replace(&mut self.roller, substream);
+        let result = CombatContext::new(self, state_tree).run_combat();
+        let substream = std::mem::replace(&mut self.roller, roller);
+        self.roller.rejoin(substream);
+        result
+    }
 }
 
 pub struct CombatContext<'a, 'b> {
@@ -123,6 +875,8 @@ pub struct CombatContext<'a, 'b> {
     pub state_tree: &'b mut StateTree,
     pub state: ProtectedCell<State>,
     pub current_node: NodeIndex,
+    /// The transitions of the action being taken, while actions are batched.
+    batch: Option<Vec<Transition>>,
 }
 
 impl<'a, 'b> CombatContext<'a, 'b> {
@@ -132,68 +886,198 @@ impl<'a, 'b> CombatContext<'a, 'b> {
             current_node: state_tree.root(),
             state_tree,
             integrator,
+            batch: None,
         }
     }
 
     pub fn run_combat(mut self) -> anyhow::Result<()> {
         self.transition(Transition::BeginCombat)?;
 
-        let mut initiative_rolls = BTreeMap::new();
-        for actor in self.state.actors.values() {
-            let roll = actor.plan_initiative_roll(RollSettings::default());
-            let result = self.integrator.roller.roll(&roll)?;
-            initiative_rolls.insert(actor.id, result.total);
-        }
-
-        for (actor_id, roll) in &initiative_rolls {
-            self.transition(Transition::InitiativeRoll {
-                actor: *actor_id,
-                roll: *roll,
-            })?;
+        // actors who start the fight prone stand up on their first turn, like anyone knocked down
+        let prone: Vec<ActorId> = self
+            .state
+            .actors
+            .values()
+            .filter(|actor| {
+                actor.conditions.is_prone()
+                    && !self.state.timed_effects.gives(actor.id, Condition::Prone)
+            })
+            .map(|actor| actor.id)
+            .collect();
+        for actor in prone {
+            self.apply_condition(actor, Condition::Prone)?;
         }
 
+        self.roll_initiative()?;
+        self.trigger_environment()?;
         while self.advance_turn()? {
             // continue advancing turns until combat is over
         }
 
+        // the final (possibly partial) round ends with the combat
+        if let Some(series) = &mut self.integrator.hp_time_series {
+            series.record_round(&self.state, self.state.turn);
+        }
+
+        let rounds = self.state.turn + 1;
+        if let Some(outcome) = self.state.combat_outcome() {
+            self.transition(Transition::CombatDecided { outcome })?;
+        }
         self.transition(Transition::EndCombat)?;
+        if self.state_tree.mode() == TreeMode::TerminalOnly {
+            self.state_tree.add_terminal_state(&self.state);
+        }
+        if let Some(classifier) = &mut self.integrator.outcome_classifier {
+            let label = classifier.classify(&self.state);
+            if let Some(labels) = &mut self.integrator.outcome_labels {
+                labels.record(label);
+            }
+        }
+
+        if self.integrator.verbosity >= Verbosity::Summary {
+            let standing: Vec<&str> = self
+                .state
+                .actors
+                .values()
+                .filter(|actor| actor.is_alive())
+                .map(|actor| actor.name.as_str())
+                .collect();
+            log::info!(
+                "Combat {} ended after {} rounds; standing: {}",
+                self.integrator.combats_run() + 1,
+                rounds,
+                if standing.is_empty() {
+                    "nobody".to_string()
+                } else {
+                    standing.join(", ")
+                }
+            );
+        }
 
         self.integrator.record_combat();
+        self.integrator.collect_emitted();
+        Ok(())
+    }
+
+    /// Rolls initiative for everyone but mounts, who act on their rider's, unless the encounter
+    /// forces it.
+    fn roll_initiative(&mut self) -> anyhow::Result<()> {
+        let rolling: Vec<(ActorId, RollPlan)> = self
+            .state
+            .actors
+            .values()
+            .filter(|actor| self.state.rider_of(actor.id).is_none())
+            .map(|actor| {
+                (
+                    actor.id,
+                    actor.plan_initiative_roll(RollSettings::default()),
+                )
+            })
+            .collect();
+        let mut initiative_rolls = BTreeMap::new();
+        for (actor, roll) in rolling {
+            let initiative = match self.state.encounter.initiative.forced_initiative(actor) {
+                Some(initiative) => initiative,
+                None => self.roll_d20(actor, &roll)?.total,
+            };
+            initiative_rolls.insert(actor, initiative);
+        }
+
+        for (actor_id, roll) in &initiative_rolls {
+            self.transition(Transition::InitiativeRoll {
+                actor: *actor_id,
+                roll: *roll,
+            })?;
+        }
         Ok(())
     }
 
     pub fn transition(&mut self, transition: Transition) -> anyhow::Result<()> {
+        let round = self.state.turn;
         transition.apply(ProtectedCell::get_mut(&mut self.state))?;
-        let new_node = self
-            .state_tree
-            .add_transition(self.current_node, &self.state, transition);
-        self.current_node = new_node;
+        if self.state_tree.mode() == TreeMode::Full {
+            match &mut self.batch {
+                Some(batch) => batch.push(transition.clone()),
+                None => self.add_to_tree(transition.clone()),
+            }
+        }
+
+        if self.integrator.verbosity == Verbosity::Full {
+            let mut line = String::new();
+            self.integrator
+                .messages
+                .transition(&mut line, &transition, &self.state)?;
+            let combat = self.integrator.combats_run() + 1;
+            log::info!(
+                "{}",
+                self.integrator
+                    .messages
+                    .log(LogMessage::Transition, combat, &line)
+            );
+        }
+
+        for hook in self.integrator.enabled_hooks() {
+            hook.on_transition(&self.state, &transition);
+        }
+
+        if let Some(timeline) = &mut self.integrator.event_timeline {
+            match transition {
+                Transition::BeginCombat => timeline.begin_combat(&self.state),
+                // ending combat resets the turn counter
+                Transition::EndCombat => timeline.end_combat(round + 1),
+                _ => timeline.observe(&self.state, self.state.turn + 1),
+            }
+        }
+        if let Some(contributions) = &mut self.integrator.contributions {
+            match transition {
+                Transition::BeginCombat => contributions.begin_combat(),
+                _ => contributions.observe(&self.state, &transition),
+            }
+        }
+        if let Some(turn_order) = &mut self.integrator.turn_order {
+            match transition {
+                Transition::BeginCombat => turn_order.begin_combat(),
+                Transition::EndCombat => turn_order.end_combat(&self.state),
+                _ => turn_order.observe(&self.state, &transition),
+            }
+        }
+        if let Some(usage) = &mut self.integrator.action_usage
+            && transition == Transition::BeginCombat
+        {
+            usage.begin_combat();
+        }
 
         match transition {
             Transition::BeginCombat => {
-                for hook in &mut self.integrator.hooks {
+                for hook in self.integrator.enabled_hooks() {
                     hook.on_combat_start(&self.state);
                 }
             }
             Transition::BeginTurn { actor } => {
-                for hook in &mut self.integrator.hooks {
+                for hook in self.integrator.enabled_hooks() {
                     hook.on_turn_start(&self.state, actor, self.state.turn);
                 }
             }
             Transition::AdvanceInitiative => {
+                if self.state.turn != round
+                    && let Some(series) = &mut self.integrator.hp_time_series
+                {
+                    series.record_round(&self.state, round);
+                }
+
                 let current_actor_id =
                     self.state.initiative_order[self.state.current_turn_index.unwrap()];
-                for hook in &mut self.integrator.hooks {
+                for hook in self.integrator.enabled_hooks() {
                     hook.on_advance_initiative(&self.state, current_actor_id);
                 }
             }
             Transition::EndTurn { actor } => {
-                for hook in &mut self.integrator.hooks {
+                for hook in self.integrator.enabled_hooks() {
                     hook.on_turn_end(&self.state, actor, self.state.turn);
                 }
             }
             Transition::EndCombat => {
-                for hook in &mut self.integrator.hooks {
+                for hook in self.integrator.enabled_hooks() {
                     hook.on_combat_end(&self.state);
                 }
             }
@@ -213,16 +1097,26 @@ impl<'a, 'b> CombatContext<'a, 'b> {
         }
 
         // advance to next actor in initiative order
+        let round = self.state.turn;
         self.transition(Transition::AdvanceInitiative)?;
+        if self.state.turn != round {
+            self.trigger_environment()?;
+            if self.state.is_combat_over() {
+                return Ok(false);
+            }
+        }
 
         let current_actor_id = self.state.initiative_order[self.state.current_turn_index.unwrap()];
 
+        self.expire_timed_effects(Expiry::StartOfTurn(current_actor_id))?;
+
         let Some(current_actor) = self.state.get_actor(current_actor_id) else {
             anyhow::bail!("Current actor not found in simulation state");
         };
 
         // dead actors skip their turn
         if current_actor.is_unconscious() || current_actor.is_dead() {
+            self.expire_timed_effects(Expiry::EndOfTurn(current_actor_id))?;
             return Ok(true);
         }
 
@@ -230,98 +1124,894 @@ impl<'a, 'b> CombatContext<'a, 'b> {
             actor: current_actor_id,
         })?;
 
+        self.update_conditions_at_turn_start(current_actor_id)?;
+        self.roll_recharges(current_actor_id)?;
+        self.trigger_effects(current_actor_id, EffectTrigger::TurnStart, None)?;
+
         for action_type in [ActionEconomyUsage::Action, ActionEconomyUsage::BonusAction] {
             let actor = self
                 .state
                 .get_actor(current_actor_id)
                 .ok_or_else(|| anyhow::anyhow!("Actor not found in simulation state"))?;
-            let action_taken = actor.policy.take_action(
+            let mut decision = self
+                .integrator
+                .record_decisions
+                .then(|| DecisionAudit::new(current_actor_id, action_type));
+            let action_taken = self.state.policy_of(actor).take_action_audited(
                 action_type,
                 current_actor_id,
                 &self.state,
                 &mut self.integrator.roller,
+                decision.as_mut(),
             )?;
+            if let Some(decision) = &decision {
+                if self.integrator.verbosity == Verbosity::Full {
+                    let mut line = String::new();
+                    decision.pretty_print(&mut line, &self.state)?;
+                    let combat = self.integrator.combats_run() + 1;
+                    log::info!(
+                        "{}",
+                        self.integrator
+                            .messages
+                            .log(LogMessage::Decision, combat, &line)
+                    );
+                }
+                for hook in self.integrator.enabled_hooks() {
+                    hook.on_decision(&self.state, decision);
+                }
+            }
             self.evaluate_action(current_actor_id, &action_taken)?;
+            self.enter_phases()?;
 
-            for hook in &mut self.integrator.hooks {
+            for hook in self.integrator.enabled_hooks() {
                 hook.on_action_executed(&self.state, &action_taken);
             }
         }
 
+        self.expire_timed_effects(Expiry::EndOfTurn(current_actor_id))?;
         self.transition(Transition::EndTurn {
             actor: current_actor_id,
         })?;
 
+        let injected: Vec<Transition> = self
+            .integrator
+            .enabled_hooks()
+            .flat_map(|hook| hook.inject_transitions(&self.state))
+            .collect();
+        for transition in injected {
+            self.transition(transition)?;
+        }
+
         Ok(true)
     }
 
-    pub fn evaluate_action(
-        &mut self,
-        actor_id: ActorId,
-        action: &ActionTaken,
-    ) -> anyhow::Result<()> {
-        if let Some(actor) = self.state.get_actor(actor_id) {
-            if actor.is_unconscious() || actor.is_dead() {
-                return Ok(());
+    /// Releases grapples held by creatures that can no longer act. Conditions and stat modifiers that
+    /// run out on a schedule wear off through [`Self::expire_timed_effects`] instead.
+    fn update_conditions_at_turn_start(&mut self, actor_id: ActorId) -> anyhow::Result<()> {
+        let Some(actor) = self.state.get_actor(actor_id) else {
+            anyhow::bail!("Actor not found in simulation state");
+        };
+
+        let mut expired = Vec::new();
+        for condition in actor.conditions.iter() {
+            match condition {
+                Condition::Grappled { by } => {
+                    let grappler_can_act = self
+                        .state
+                        .get_actor(*by)
+                        .is_some_and(|grappler| grappler.is_alive());
+                    if !grappler_can_act {
+                        expired.push(*condition);
+                    }
+                }
+                Condition::Prone | Condition::Helped { .. } => {}
             }
+        }
 
-            if !actor
-                .action_economy
-                .can_take_action(action.action_economy_usage)
-            {
-                return Ok(());
+        for condition in expired {
+            self.transition(Transition::ConditionRemoved {
+                target: actor_id,
+                condition,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Puts a condition on `target`. Prone lasts until the target stands up at the start of their
+    /// next turn; other conditions last until something ends them.
+    fn apply_condition(&mut self, target: ActorId, condition: Condition) -> anyhow::Result<()> {
+        match condition {
+            Condition::Prone => {
+                let effect =
+                    TimedEffect::new(target, condition, Expiry::StartOfTurn(target), &self.state);
+                self.transition(Transition::TimedEffectApplied { effect })
             }
-        } else {
-            anyhow::bail!("Actor not found in simulation state");
+            _ => self.transition(Transition::ConditionApplied { target, condition }),
         }
+    }
 
-        self.transition(Transition::ActionEconomyUsed {
-            target: actor_id,
-            action_type: action.action_economy_usage,
-        })?;
+    /// Ends every [`TimedEffect`] that wears off at `boundary`.
+    fn expire_timed_effects(&mut self, boundary: Expiry) -> anyhow::Result<()> {
+        for effect in self.state.timed_effects.expiring_at(boundary, &self.state) {
+            self.transition(Transition::TimedEffectExpired { effect })?;
+        }
+        Ok(())
+    }
 
+    /// Rolls a d6 for each of the actor's spent abilities, recharging those that roll high enough.
+    fn roll_recharges(&mut self, actor_id: ActorId) -> anyhow::Result<()> {
         let Some(actor) = self.state.get_actor(actor_id) else {
             anyhow::bail!("Actor not found in simulation state");
         };
+        let spent: Vec<(usize, Ability)> = actor
+            .spent_abilities
+            .iter()
+            .filter_map(|&index| Some((index, actor.abilities.get(index)?.clone())))
+            .collect();
 
-        match &action.action {
-            Action::Wait => {}
-            Action::UnarmedStrike(UnarmedStrikeAction {
-                target,
-                attack_roll_settings,
-            }) => {
-                let target = self
-                    .state
+        for (index, ability) in spent {
+            if ability.recharges_on(self.integrator.roller.d(6)) {
+                self.transition(Transition::AbilityRecharged {
+                    actor: actor_id,
+                    ability: index,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Help only applies to a single attack, so it's used up once `attacker` has attacked `target`.
+    fn consume_help(&mut self, attacker: ActorId, target: ActorId) -> anyhow::Result<()> {
+        let Some(actor) = self.state.get_actor(attacker) else {
+            anyhow::bail!("Actor not found in simulation state");
+        };
+        let used: Vec<Condition> = actor
+            .conditions
+            .iter()
+            .filter(|condition| {
+                matches!(condition, Condition::Helped { against, .. } if *against == target)
+            })
+            .copied()
+            .collect();
+        for condition in used {
+            self.transition(Transition::ConditionRemoved {
+                target: attacker,
+                condition,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn trigger_attack_effects(
+        &mut self,
+        attacker: ActorId,
+        target: ActorId,
+        hit: bool,
+    ) -> anyhow::Result<()> {
+        self.trigger_effects(attacker, EffectTrigger::Attack, Some(target))?;
+        if hit {
+            self.trigger_effects(attacker, EffectTrigger::Hit, Some(target))?;
+        }
+        Ok(())
+    }
+
+    /// Fires everything the actor has tied to `trigger`: random effect tables first, then homebrew effects.
+    pub fn trigger_effects(
+        &mut self,
+        actor_id: ActorId,
+        trigger: EffectTrigger,
+        target: Option<ActorId>,
+    ) -> anyhow::Result<()> {
+        self.trigger_random_effects(actor_id, trigger, target)?;
+
+        let Some(actor) = self.state.get_actor(actor_id) else {
+            anyhow::bail!("Actor not found in simulation state");
+        };
+        if actor.homebrew.specs(trigger).is_empty() {
+            return Ok(());
+        }
+        let effects = match self.integrator.compiled.homebrew.get(&(actor_id, trigger)) {
+            Some(effects) => effects.clone(),
+            None => actor
+                .homebrew
+                .compile(trigger)
+                .map_err(|e| anyhow::anyhow!("Invalid homebrew effect on {}: {}", actor.name, e))?
+                .into(),
+        };
+        for effect in effects.iter() {
+            self.apply_effect(actor_id, target, effect)?;
+        }
+        Ok(())
+    }
+
+    /// Fires the encounter's environmental effects scheduled for the round that's starting, on
+    /// everyone in each effect's area.
+    pub fn trigger_environment(&mut self) -> anyhow::Result<()> {
+        let round = self.state.turn + 1;
+        let scheduled: Vec<(usize, EnvironmentalEffect)> = self
+            .state
+            .encounter
+            .environment
+            .iter()
+            .enumerate()
+            .filter(|(_, effect)| effect.schedule.fires_in(round))
+            .map(|(index, effect)| (index, effect.clone()))
+            .collect();
+
+        for (index, environmental) in scheduled {
+            let effects = match self.integrator.compiled.environment.get(&index) {
+                Some(effects) => effects.clone(),
+                None => environmental
+                    .compile()
+                    .map_err(|e| {
+                        anyhow::anyhow!(
+                            "Invalid environmental effect {}: {}",
+                            environmental.name,
+                            e
+                        )
+                    })?
+                    .into(),
+            };
+            self.transition(Transition::EnvironmentalEffect { effect: index })?;
+            for target in environmental.targets(&self.state) {
+                for effect in effects.iter() {
+                    self.apply_effect(target, Some(target), effect)?;
+                }
+            }
+            self.enter_phases()?;
+        }
+        Ok(())
+    }
+
+    /// Moves every actor that's due into its next boss phase.
+    pub fn enter_phases(&mut self) -> anyhow::Result<()> {
+        let due: Vec<(ActorId, usize)> = self
+            .state
+            .actors
+            .values()
+            .filter_map(|actor| Some((actor.id, actor.due_phase()?)))
+            .collect();
+        for (actor, phase) in due {
+            self.transition(Transition::PhaseEntered { actor, phase })?;
+        }
+        Ok(())
+    }
+
+    /// Rolls on each of the actor's random effect tables with the given trigger, applying the
+    /// effects of whichever entries come up.
+    pub fn trigger_random_effects(
+        &mut self,
+        actor_id: ActorId,
+        trigger: EffectTrigger,
+        target: Option<ActorId>,
+    ) -> anyhow::Result<()> {
+        let Some(actor) = self.state.get_actor(actor_id) else {
+            anyhow::bail!("Actor not found in simulation state");
+        };
+        let tables: Vec<usize> = actor
+            .random_effects
+            .iter()
+            .enumerate()
+            .filter(|(_, table)| table.trigger == trigger)
+            .map(|(index, _)| index)
+            .collect();
+
+        for table in tables {
+            let Some(effects_table) = self
+                .state
+                .get_actor(actor_id)
+                .and_then(|actor| actor.random_effects.get(table))
+                .cloned()
+            else {
+                continue;
+            };
+            let Some(entry) = effects_table.roll(&mut self.integrator.roller) else {
+                continue;
+            };
+            self.transition(Transition::RandomEffectRolled {
+                actor: actor_id,
+                table,
+                entry,
+            })?;
+            for effect in &effects_table.entries[entry].effects {
+                self.apply_effect(actor_id, target, effect)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolves a single effect owned by `user` into transitions.
+    pub fn apply_effect(
+        &mut self,
+        user: ActorId,
+        target: Option<ActorId>,
+        effect: &Effect,
+    ) -> anyhow::Result<()> {
+        self.apply_effect_inner(user, target, effect, false)
+    }
+
+    fn apply_effect_inner(
+        &mut self,
+        user: ActorId,
+        target: Option<ActorId>,
+        effect: &Effect,
+        half_damage: bool,
+    ) -> anyhow::Result<()> {
+        let resolve = |effect_target: &EffectTarget| match effect_target {
+            EffectTarget::User => Some(user),
+            EffectTarget::Target => target,
+        };
+
+        match effect {
+            Effect::Damage {
+                target,
+                damage,
+                damage_type,
+            } => {
+                let Some(target) = resolve(target) else {
+                    return Ok(());
+                };
+                let mut damage = self.integrator.roller.roll(damage)?.total.max(0);
+                if half_damage {
+                    damage /= 2;
+                }
+                if damage > 0 {
+                    self.apply_damage(target, damage, *damage_type, false)?;
+                }
+            }
+            Effect::Heal { target, amount } => {
+                let Some(target) = resolve(target) else {
+                    return Ok(());
+                };
+                let amount = self.integrator.roller.roll(amount)?.total.max(0);
+                let Some(actor) = self.state.get_actor(target) else {
+                    anyhow::bail!("Target actor not found");
+                };
+                if actor.is_dead() {
+                    return Ok(());
+                }
+                // healing can't go over max
+                let delta = amount.min(actor.max_health - actor.health).max(0);
+                if delta != 0 {
+                    self.transition(Transition::HealthModification { target, delta })?;
+                }
+            }
+            Effect::ApplyCondition { target, condition } => {
+                if let Some(target) = resolve(target) {
+                    self.apply_condition(target, *condition)?;
+                }
+            }
+            Effect::ModifyStat {
+                target,
+                stat,
+                delta,
+                duration,
+            } => {
+                if let Some(target) = resolve(target) {
+                    self.transition(Transition::StatModification {
+                        target,
+                        stat: *stat,
+                        delta: *delta,
+                        duration: *duration,
+                    })?;
+                }
+            }
+            Effect::SavingThrow {
+                target: save_target,
+                save,
+                dc,
+                on_fail,
+                on_success,
+                half_damage_on_success,
+            } => {
+                let Some(saver) = resolve(save_target) else {
+                    return Ok(());
+                };
+                let Some(actor) = self.state.get_actor(saver) else {
+                    anyhow::bail!("Target actor not found");
+                };
+                let roll =
+                    self.state
+                        .plan_saving_throw(actor, *save, Some(user), Default::default());
+                let dc = *dc;
+                let success = self
+                    .roll_d20_test(saver, &roll, D20Test::SavingThrow, |result| {
+                        result.total >= dc
+                    })?
+                    .total
+                    >= dc;
+                self.transition(Transition::SavingThrowResolved {
+                    actor: saver,
+                    save: *save,
+                    dc,
+                    success,
+                })?;
+
+                if success {
+                    for effect in on_success {
+                        self.apply_effect_inner(user, target, effect, half_damage)?;
+                    }
+                    if *half_damage_on_success {
+                        for effect in on_fail {
+                            if matches!(effect, Effect::Damage { .. }) {
+                                self.apply_effect_inner(user, target, effect, true)?;
+                            }
+                        }
+                    }
+                } else {
+                    for effect in on_fail {
+                        self.apply_effect_inner(user, target, effect, half_damage)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deals damage to an actor after its resistances, immunities and vulnerabilities, resolving
+    /// any features that trigger when it would drop to 0 HP. Returns the hit points the actor
+    /// actually lost: damage past 0 HP, or absorbed by hanging on at 1 HP, doesn't count.
+    // todo: temporary hit points
+    pub fn apply_damage(
+        &mut self,
+        target: ActorId,
+        damage: i32,
+        damage_type: Option<DamageType>,
+        critical: bool,
+    ) -> anyhow::Result<i32> {
+        let actor = self
+            .state
+            .get_actor(target)
+            .ok_or_else(|| anyhow::anyhow!("Target actor not found"))?;
+        let rolled = damage;
+        let damage = actor.resistances.apply(damage, damage_type);
+        if let Some(damage_type) = damage_type {
+            self.transition(Transition::DamageTaken {
+                target,
+                damage_type,
+                rolled,
+                dealt: damage,
+            })?;
+        }
+        let actor = self
+            .state
+            .get_actor(target)
+            .ok_or_else(|| anyhow::anyhow!("Target actor not found"))?;
+        let health = actor.health;
+        let remaining = health - damage;
+
+        // massive damage kills outright, with no chance to hang on
+        if health > 0
+            && remaining <= 0
+            && remaining > -actor.max_health
+            && let Some(feature) = self.resolve_on_drop_triggers(target, damage, critical)?
+        {
+            self.transition(Transition::FeatureUsed {
+                actor: target,
+                feature,
+            })?;
+            if health != 1 {
+                self.transition(Transition::HealthModification {
+                    target,
+                    delta: 1 - health,
+                })?;
+            }
+            return Ok(health - 1);
+        }
+
+        self.transition(Transition::HealthModification {
+            target,
+            delta: -damage,
+        })?;
+        self.clean_up_if_dead(target)?;
+        Ok(damage.min(health.max(0)))
+    }
+
+    /// Takes a freshly killed actor out of the fight, as configured by
+    /// [`EncounterSetup::death_cleanup`](crate::simulation::encounter::EncounterSetup::death_cleanup).
+    fn clean_up_if_dead(&mut self, target: ActorId) -> anyhow::Result<()> {
+        let drop_loot = match self.state.encounter.death_cleanup {
+            DeathCleanup::None => return Ok(()),
+            DeathCleanup::RemoveFromTargeting => false,
+            DeathCleanup::DropLoot => true,
+        };
+        let dead = self
+            .state
+            .get_actor(target)
+            .is_some_and(|actor| actor.is_dead());
+        if dead && !self.state.fallen.contains(&target) {
+            self.transition(Transition::ActorKilled {
+                actor: target,
+                drop_loot,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the first available feature that keeps the actor at 1 HP instead of dropping to 0, if any.
+    fn resolve_on_drop_triggers(
+        &mut self,
+        target: ActorId,
+        damage: i32,
+        critical: bool,
+    ) -> anyhow::Result<Option<Feature>> {
+        let actor = self
+            .state
+            .get_actor(target)
+            .ok_or_else(|| anyhow::anyhow!("Target actor not found"))?;
+        let triggers: Vec<Feature> = actor
+            .features
+            .iter()
+            .copied()
+            .filter(|feature| feature.is_on_drop_trigger() && actor.can_use_feature(*feature))
+            .collect();
+        let con_save = self.state.plan_saving_throw(
+            actor,
+            SavingThrow::Constitution,
+            None,
+            Default::default(),
+        );
+
+        for feature in triggers {
+            let triggered = match feature {
+                Feature::RelentlessEndurance => true,
+                Feature::UndeadFortitude => {
+                    !critical && self.roll_d20(target, &con_save)?.total >= 5 + damage
+                }
+                _ => false,
+            };
+            if triggered {
+                return Ok(Some(feature));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Rolls an opposed ability check and records its outcome. Ties keep the status quo,
+    /// so the initiator has to beat the defender outright.
+    fn resolve_contest(
+        &mut self,
+        initiator: ActorId,
+        target: ActorId,
+        contest: Contest,
+    ) -> anyhow::Result<bool> {
+        let initiator_actor = self
+            .state
+            .get_actor(initiator)
+            .ok_or_else(|| anyhow::anyhow!("Actor not found in simulation state"))?;
+        let target_actor = self
+            .state
+            .get_actor(target)
+            .ok_or_else(|| anyhow::anyhow!("Target actor not found"))?;
+
+        let (initiator_skill, target_skill) = match contest {
+            Contest::Grapple | Contest::Shove => (
+                Skill::Athletics,
+                target_actor.best_skill(&[Skill::Athletics, Skill::Acrobatics]),
+            ),
+            Contest::EscapeGrapple => (
+                initiator_actor.best_skill(&[Skill::Athletics, Skill::Acrobatics]),
+                Skill::Athletics,
+            ),
+        };
+
+        let initiator_roll = initiator_actor.plan_skill_check(initiator_skill, Default::default());
+        let target_roll = target_actor.plan_skill_check(target_skill, Default::default());
+        let initiator_result = self.roll_d20(initiator, &initiator_roll)?;
+        let target_result = self.roll_d20(target, &target_roll)?;
+        // the target holds on ties, so it only needs to match the initiator
+        let target_result = self.reroll_if_failed(
+            target,
+            &target_roll,
+            D20Test::AbilityCheck,
+            target_result,
+            |result| result.total >= initiator_result.total,
+        )?;
+        let initiator_result = self.reroll_if_failed(
+            initiator,
+            &initiator_roll,
+            D20Test::AbilityCheck,
+            initiator_result,
+            |result| result.total > target_result.total,
+        )?;
+
+        let success = initiator_result.total > target_result.total;
+        self.transition(Transition::ContestResolved {
+            initiator,
+            target,
+            contest,
+            success,
+        })?;
+
+        Ok(success)
+    }
+
+    /// Rolls a d20 test for `actor_id`. With Halfling Luck, a natural 1 is rerolled.
+    fn roll_d20(&mut self, actor_id: ActorId, roll: &RollPlan) -> anyhow::Result<RollResult> {
+        let result = self.integrator.roller.roll(roll)?;
+        let lucky = self
+            .state
+            .get_actor(actor_id)
+            .is_some_and(|actor| actor.has_feature(Feature::HalflingLuck));
+        if lucky && result.is_critical_failure() {
+            return self.integrator.roller.roll(roll);
+        }
+        Ok(result)
+    }
+
+    /// Spends one of the actor's reroll resources on `result` if it failed and the actor's policy
+    /// calls for it, returning the roll that stands.
+    fn reroll_if_failed(
+        &mut self,
+        actor_id: ActorId,
+        roll: &RollPlan,
+        test: D20Test,
+        result: RollResult,
+        succeeds: impl Fn(&RollResult) -> bool,
+    ) -> anyhow::Result<RollResult> {
+        if succeeds(&result) {
+            return Ok(result);
+        }
+        let Some(actor) = self.state.get_actor(actor_id) else {
+            anyhow::bail!("Actor not found in simulation state");
+        };
+        let Some(resource) = actor.next_reroll() else {
+            return Ok(result);
+        };
+        if !self.state.policy_of(actor).should_reroll(test, &self.state) {
+            return Ok(result);
+        }
+
+        self.transition(Transition::RerollUsed {
+            actor: actor_id,
+            resource,
+        })?;
+        self.roll_d20(actor_id, roll)
+    }
+
+    /// Rolls a d20 test, spending a reroll on a failure if the actor's policy calls for it.
+    fn roll_d20_test(
+        &mut self,
+        actor_id: ActorId,
+        roll: &RollPlan,
+        test: D20Test,
+        succeeds: impl Fn(&RollResult) -> bool,
+    ) -> anyhow::Result<RollResult> {
+        let result = self.roll_d20(actor_id, roll)?;
+        self.reroll_if_failed(actor_id, roll, test, result, succeeds)
+    }
+
+    /// Moves `actor_id` toward the target of `action` if it's out of reach, however the encounter
+    /// models positioning. Only actions taken with the main action move, so an actor covers at
+    /// most one move's worth each turn.
+    ///
+    /// Moving into a zone with a hazard sets it off on the mover and whoever it carries along.
+    ///
+    /// Returns whether the action can go ahead afterward.
+    fn close_distance(&mut self, actor_id: ActorId, action: &ActionTaken) -> anyhow::Result<bool> {
+        let (Some(target), Some(reach)) = (
+            action.action.target(),
+            self.state.action_reach(&action.action),
+        ) else {
+            return Ok(true);
+        };
+        if self.state.get_actor(actor_id).is_none() {
+            anyhow::bail!("Actor not found in simulation state");
+        }
+
+        let movement = self
+            .state
+            .positioning()
+            .approach(&self.state, actor_id, target, reach)?;
+        if let Some(Transition::ZoneEntered { actor, zone }) = movement {
+            self.trigger_hazard(actor, zone)?;
+        }
+        if let Some(movement) = movement {
+            self.transition(movement)?;
+        }
+        if self
+            .state
+            .get_actor(actor_id)
+            .is_none_or(|actor| actor.is_dead())
+        {
+            return Ok(false);
+        }
+        Ok(self.state.in_reach(actor_id, &action.action))
+    }
+
+    /// Sets off the hazard in `zone`, if there is one, on `actor_id` and its rider or mount as they
+    /// move into it.
+    fn trigger_hazard(&mut self, actor_id: ActorId, zone: ZoneId) -> anyhow::Result<()> {
+        let Some(hazard) = self
+            .state
+            .encounter
+            .zones
+            .as_ref()
+            .and_then(|zones| zones.hazards.get(&zone))
+        else {
+            return Ok(());
+        };
+        let effects = match self.integrator.compiled.hazards.get(&zone) {
+            Some(effects) => effects.clone(),
+            None => hazard
+                .compile()
+                .map_err(|e| anyhow::anyhow!("Invalid hazard {}: {}", hazard.name, e))?
+                .into(),
+        };
+        let Some(actor) = self.state.get_actor(actor_id) else {
+            anyhow::bail!("Actor not found in simulation state");
+        };
+        let movers: Vec<ActorId> = [Some(actor_id), actor.mount, self.state.rider_of(actor_id)]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        self.transition(Transition::HazardEntered {
+            actor: actor_id,
+            zone,
+        })?;
+        for target in movers {
+            for effect in effects.iter() {
+                self.apply_effect(target, Some(target), effect)?;
+            }
+        }
+        self.enter_phases()
+    }
+
+    /// Records that `actor_id` is in melee with the target of `action`, for the optional rules that
+    /// care about adjacency. Striking from farther than 5 feet with a reach weapon doesn't count.
+    fn engage(&mut self, actor_id: ActorId, action: &Action) -> anyhow::Result<()> {
+        if !self.state.encounter.rules.tracks_engagements() {
+            return Ok(());
+        }
+        let target = match action {
+            Action::UnarmedStrike(UnarmedStrikeAction { target, .. })
+            | Action::Grapple(GrappleAction { target })
+            | Action::Shove(ShoveAction { target }) => *target,
+            Action::Attack(AttackAction {
+                weapon_used,
+                target,
+                ..
+            }) if matches!(
+                self.state.items.get(weapon_used).map(|item| &item.inner),
+                Some(ItemInner::Weapon(weapon)) if weapon.is_melee()
+            ) =>
+            {
+                *target
+            }
+            _ => return Ok(()),
+        };
+        if self
+            .state
+            .distance_between(actor_id, target)
+            .is_some_and(|distance| distance > 5)
+        {
+            return Ok(());
+        }
+        if self.state.engagements.get(&actor_id) != Some(&target) {
+            self.transition(Transition::Engaged {
+                actor: actor_id,
+                target,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn add_to_tree(&mut self, transition: Transition) {
+        self.current_node =
+            self.state_tree
+                .add_transition(self.current_node, &self.state, transition);
+    }
+
+    /// Carries out an action, as one step in the state tree if the integrator batches actions.
+    pub fn evaluate_action(
+        &mut self,
+        actor_id: ActorId,
+        action: &ActionTaken,
+    ) -> anyhow::Result<()> {
+        if !self.integrator.batch_actions {
+            return self.execute_action(actor_id, action);
+        }
+        self.batch = Some(Vec::new());
+        let result = self.execute_action(actor_id, action);
+        let batch = self.batch.take().unwrap_or_default();
+        if let Some(transition) = Transition::composite(batch) {
+            self.add_to_tree(transition);
+        }
+        result
+    }
+
+    fn execute_action(&mut self, actor_id: ActorId, action: &ActionTaken) -> anyhow::Result<()> {
+        if self.state.get_actor(actor_id).is_none() {
+            anyhow::bail!("Actor not found in simulation state");
+        }
+        // illegal actions, like attacking out of reach with a bonus action, do nothing
+        if !self
+            .state
+            .is_legal(actor_id, &action.action, action.action_economy_usage)
+        {
+            return Ok(());
+        }
+
+        self.transition(Transition::ActionEconomyUsed {
+            target: actor_id,
+            action_type: action.action_economy_usage,
+        })?;
+
+        if !self.close_distance(actor_id, action)? {
+            return Ok(());
+        }
+        self.engage(actor_id, &action.action)?;
+        if let Some(usage) = &mut self.integrator.action_usage {
+            usage.record(actor_id, &action.action, self.state.turn + 1);
+        }
+
+        let Some(actor) = self.state.get_actor(actor_id) else {
+            anyhow::bail!("Actor not found in simulation state");
+        };
+
+        match &action.action {
+            Action::Wait => {}
+            Action::UnarmedStrike(UnarmedStrikeAction {
+                target,
+                attack_roll_settings,
+            }) => {
+                let target = self
+                    .state
                     .actors
                     .get(target)
                     .ok_or_else(|| anyhow::anyhow!("Target actor not found"))?;
 
-                let attack_roll = actor.plan_unarmed_strike_roll(*attack_roll_settings);
-                let attack_result = self.integrator.roller.roll(&attack_roll)?;
+                let cover = self.state.encounter.cover_between(actor_id, target.id);
+
+                let attack_roll_settings =
+                    self.state
+                        .attack_roll_settings(actor, target, true, *attack_roll_settings);
+                let attack_roll = actor.plan_unarmed_strike_roll(attack_roll_settings);
+                let armor_class = target.effective_armor_class(&self.state) + cover.ac_bonus();
+                let damage = actor.plan_unarmed_strike_damage();
+                let crit_damage = actor.plan_unarmed_strike_crit_damage();
+                let target = target.id;
 
-                let attack_hits = attack_result.meets_dc(target.armor_class as i32);
-                let attack_crits = attack_result.is_critical_success();
+                let attack_result = self.roll_d20_test(
+                    actor_id,
+                    &attack_roll,
+                    D20Test::Attack { target },
+                    |result| result.meets_dc(armor_class),
+                )?;
+                let attack_hits = attack_result.meets_dc(armor_class);
+                let attack_crits = attack_hits && attack_result.is_critical_success();
+                let damage_roll = if attack_crits { crit_damage } else { damage };
+
+                self.transition(Transition::AttackResolved {
+                    attacker: actor_id,
+                    target,
+                    hit: attack_hits,
+                    crit: attack_crits,
+                })?;
+                self.consume_help(actor_id, target)?;
 
                 if attack_hits {
-                    let damage_roll = if attack_crits {
-                        actor.plan_unarmed_strike_crit_damage()
-                    } else {
-                        actor.plan_unarmed_strike_damage()
-                    };
                     let damage_result = self.integrator.roller.roll(&damage_roll)?;
 
-                    // apply damage to target
-                    // todo: calculate resistances, vulnerabilities, temporary hit points, etc.
-                    self.transition(Transition::HealthModification {
-                        target: target.id,
-                        delta: -damage_result.total,
-                    })?;
+                    self.apply_damage(
+                        target,
+                        damage_result.total,
+                        Some(DamageType::Bludgeoning),
+                        attack_crits,
+                    )?;
                 }
+                self.trigger_attack_effects(actor_id, target, attack_hits)?;
             }
             Action::Attack(AttackAction {
                 weapon_used: weapon_used_id,
                 target,
                 attack_roll_settings,
+                power_attack,
             }) => {
                 let target = self
                     .state
@@ -338,35 +2028,1861 @@ impl<'a, 'b> CombatContext<'a, 'b> {
                 let ItemInner::Weapon(weapon_used) = &weapon_used.inner else {
                     return Err(anyhow::anyhow!("Item used for attack is not a weapon"));
                 };
+                anyhow::ensure!(
+                    !*power_attack || actor.can_power_attack(weapon_used),
+                    "{} can't power attack with a {:?}",
+                    actor.name,
+                    weapon_used.weapon_type
+                );
+
+                let cover = self.state.encounter.cover_between(actor_id, target.id);
+
+                let attack_roll_settings = self.state.attack_roll_settings(
+                    actor,
+                    target,
+                    weapon_used.is_melee(),
+                    *attack_roll_settings,
+                );
+                let mut attack_roll = actor.plan_attack_roll(weapon_used, attack_roll_settings)?;
+                if *power_attack {
+                    attack_roll.modifier -= POWER_ATTACK_HIT_PENALTY;
+                }
+                let armor_class = target.effective_armor_class(&self.state) + cover.ac_bonus();
+                let damage_type = weapon_used.damage_type_dealt();
+                let [mut damage, mut crit_damage] =
+                    [false, true].map(|crit| actor.plan_weapon_damage(weapon_used, crit));
+                if *power_attack {
+                    damage.modifier += POWER_ATTACK_DAMAGE_BONUS;
+                    crit_damage.modifier += POWER_ATTACK_DAMAGE_BONUS;
+                }
+                let target = target.id;
 
-                let attack_roll = actor.plan_attack_roll(weapon_used, *attack_roll_settings)?;
-                let attack_result = self.integrator.roller.roll(&attack_roll)?;
+                let attack_result = self.roll_d20_test(
+                    actor_id,
+                    &attack_roll,
+                    D20Test::Attack { target },
+                    |result| result.meets_dc(armor_class),
+                )?;
+                let attack_hits = attack_result.meets_dc(armor_class);
+                let attack_crits = attack_hits && attack_result.is_critical_success();
+                let damage_roll = if attack_crits { crit_damage } else { damage };
 
-                let attack_hits = attack_result.meets_dc(target.armor_class as i32);
+                self.transition(Transition::AttackResolved {
+                    attacker: actor_id,
+                    target,
+                    hit: attack_hits,
+                    crit: attack_crits,
+                })?;
+                self.consume_help(actor_id, target)?;
 
+                let mut dealt = 0;
                 if attack_hits {
-                    let damage_roll = if attack_result.is_critical_success() {
-                        weapon_used
-                            .critical_damage
-                            .as_ref()
-                            .unwrap_or(&weapon_used.damage)
-                    } else {
-                        &weapon_used.damage
-                    };
-
-                    let damage_result = self.integrator.roller.roll(damage_roll)?;
-
-                    // apply damage to target
-                    // todo: calculate resistances, vulnerabilities, temporary hit points, etc.
-                    self.transition(Transition::HealthModification {
-                        target: target.id,
-                        delta: -damage_result.total,
+                    let damage_result = self.integrator.roller.roll(&damage_roll)?;
+                    dealt = self.apply_damage(
+                        target,
+                        damage_result.total,
+                        Some(damage_type),
+                        attack_crits,
+                    )?;
+                }
+                if let Some(weapons) = &mut self.integrator.weapon_stats {
+                    weapons.record(
+                        *weapon_used_id,
+                        target,
+                        armor_class,
+                        attack_hits,
+                        attack_crits,
+                        dealt.max(0) as u64,
+                    );
+                }
+                self.trigger_attack_effects(actor_id, target, attack_hits)?;
+            }
+            Action::Grapple(GrappleAction { target }) => {
+                if self.resolve_contest(actor_id, *target, Contest::Grapple)? {
+                    self.transition(Transition::ConditionApplied {
+                        target: *target,
+                        condition: Condition::Grappled { by: actor_id },
+                    })?;
+                }
+            }
+            Action::Shove(ShoveAction { target }) => {
+                if self.resolve_contest(actor_id, *target, Contest::Shove)? {
+                    self.apply_condition(*target, Condition::Prone)?;
+                }
+            }
+            Action::Help(HelpAction { ally, target }) => {
+                let effect = TimedEffect::new(
+                    *ally,
+                    Condition::Helped {
+                        by: actor_id,
+                        against: *target,
+                    },
+                    Expiry::StartOfTurn(actor_id),
+                    &self.state,
+                );
+                self.transition(Transition::TimedEffectApplied { effect })?;
+            }
+            Action::EscapeGrapple => {
+                let Some(grappler) = actor.conditions.grappled_by() else {
+                    return Ok(());
+                };
+
+                if self.resolve_contest(actor_id, grappler, Contest::EscapeGrapple)? {
+                    self.transition(Transition::ConditionRemoved {
+                        target: actor_id,
+                        condition: Condition::Grappled { by: grappler },
                     })?;
                 }
             }
+            Action::UseAbility(UseAbilityAction {
+                ability: index,
+                target,
+                ..
+            }) => {
+                let ability = actor.abilities[*index].clone();
+                let targets = if ability.area {
+                    self.state.possible_targets(actor_id)
+                } else {
+                    vec![*target]
+                };
+
+                self.transition(Transition::AbilityUsed {
+                    actor: actor_id,
+                    ability: *index,
+                })?;
+                for target in targets {
+                    for effect in &ability.effects {
+                        self.apply_effect(actor_id, Some(target), effect)?;
+                    }
+                }
+            }
             action => todo!("Handle {:?} action", action),
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{
+        Ability, ActionType, ActorBuilder, Advantage, AttackHitRates, Cover, DamageResponse,
+        DeathBehavior, EffectArea, EffectSpec, Feature, Hazard, InitiativeMode, ItemType,
+        ItemsRecovered, LevelScaling, Lighting, OutcomeConditionProbability, OutcomeProbabilities,
+        PolicyBuilder, Potion, Query, RandomEffectTable, RerollHeuristic, RerollResource,
+        ResourcesSpent, RollPlan, RunSummary, Schedule, SkillProficiency, Stat, UsedAction,
+        WeaponBuilder, WeaponType, ZoneMap,
+    };
+    use crate::test_utils::{brawl, brawl_policy};
+
+    #[test]
+    fn test_grapple_and_escape() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let wrestler = state.add_actor(
+            ActorBuilder::new("Wrestler")
+                .stat(Stat::Strength, 30)
+                .skill_proficiency(Skill::Athletics, SkillProficiency::Expert)
+                .build(),
+        );
+        // at best the goblin's 15 only ties the wrestler's worst roll, and ties hold
+        let goblin = state.add_actor(
+            ActorBuilder::new("Goblin")
+                .group(1)
+                .stat(Stat::Strength, 1)
+                .stat(Stat::Dexterity, 1)
+                .build(),
+        );
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+
+        let grapple = ActionTaken {
+            actor: wrestler,
+            action: Action::Grapple(GrappleAction { target: goblin }),
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        for _ in 0..20 {
+            ctx.transition(Transition::BeginTurn { actor: wrestler })?;
+            ctx.evaluate_action(wrestler, &grapple)?;
+        }
+        assert_eq!(
+            ctx.state
+                .get_actor(goblin)
+                .unwrap()
+                .conditions
+                .grappled_by(),
+            Some(wrestler)
+        );
+
+        // the goblin can't escape while the wrestler is strong, but is released once they drop
+        let escape = ActionTaken {
+            actor: goblin,
+            action: Action::EscapeGrapple,
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        for _ in 0..20 {
+            ctx.transition(Transition::BeginTurn { actor: goblin })?;
+            ctx.evaluate_action(goblin, &escape)?;
+        }
+        assert_eq!(
+            ctx.state
+                .get_actor(goblin)
+                .unwrap()
+                .conditions
+                .grappled_by(),
+            Some(wrestler)
+        );
+        ctx.transition(Transition::HealthModification {
+            target: wrestler,
+            delta: -100,
+        })?;
+        ctx.transition(Transition::BeginTurn { actor: goblin })?;
+        ctx.update_conditions_at_turn_start(goblin)?;
+        assert!(
+            !ctx.state
+                .get_actor(goblin)
+                .unwrap()
+                .conditions
+                .is_grappled()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_help_grants_advantage_once() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let helper = state.add_actor(ActorBuilder::new("Helper").build());
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(100).build());
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+
+        let help = ActionTaken {
+            actor: helper,
+            action: Action::Help(HelpAction {
+                ally: fighter,
+                target: goblin,
+            }),
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        ctx.transition(Transition::BeginTurn { actor: helper })?;
+        ctx.evaluate_action(helper, &help)?;
+
+        let advantage = |ctx: &CombatContext| {
+            let fighter = ctx.state.get_actor(fighter).unwrap();
+            let goblin = ctx.state.get_actor(goblin).unwrap();
+            ctx.state
+                .attack_roll_settings(fighter, goblin, true, Default::default())
+                .advantage
+        };
+        assert_eq!(advantage(&ctx), Advantage::Advantage);
+
+        let strike = ActionTaken {
+            actor: fighter,
+            action: Action::UnarmedStrike(UnarmedStrikeAction {
+                target: goblin,
+                attack_roll_settings: Default::default(),
+            }),
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        ctx.transition(Transition::BeginTurn { actor: fighter })?;
+        ctx.evaluate_action(fighter, &strike)?;
+        assert_eq!(advantage(&ctx), Advantage::Normal);
+
+        // unused help runs out when the helper's next turn starts
+        ctx.transition(Transition::BeginTurn { actor: helper })?;
+        ctx.evaluate_action(helper, &help)?;
+        assert_eq!(advantage(&ctx), Advantage::Advantage);
+        ctx.expire_timed_effects(Expiry::StartOfTurn(helper))?;
+        assert_eq!(advantage(&ctx), Advantage::Normal);
+        assert!(ctx.state.timed_effects.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_forced_initiative() -> anyhow::Result<()> {
+        struct OrderRecorder(std::sync::Arc<std::sync::Mutex<Vec<Vec<ActorId>>>>);
+        impl Hook for OrderRecorder {
+            fn on_combat_start(&mut self, _state: &State) {
+                self.0.lock().unwrap().push(Vec::new());
+            }
+            fn on_transition(&mut self, state: &State, transition: &Transition) {
+                if let Transition::InitiativeRoll { .. } = transition {
+                    *self.0.lock().unwrap().last_mut().unwrap() = state.initiative_order.clone();
+                }
+            }
+        }
+
+        let mut state = State::new();
+        let knight = state.add_actor(ActorBuilder::new("Knight").build());
+        let rogue = state.add_actor(ActorBuilder::new("Rogue").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        let policy = brawl_policy();
+        for actor in [knight, rogue, goblin] {
+            state.set_actor_policy(actor, policy.clone());
+        }
+
+        let run = |mode: InitiativeMode| -> anyhow::Result<Vec<Vec<ActorId>>> {
+            let mut state = state.clone();
+            state.encounter.initiative = mode;
+            let orders = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let mut integrator = Integrator::new(5, Roller::test_rng(), state);
+            integrator.add_hook(OrderRecorder(orders.clone()));
+            integrator.run()?;
+            Ok(orders.lock().unwrap().clone())
+        };
+
+        let orders = run(InitiativeMode::Order(vec![goblin, knight]))?;
+        assert_eq!(orders.len(), 5);
+        assert!(orders.iter().all(|order| *order == [goblin, knight, rogue]));
+
+        let fixed = InitiativeMode::Fixed(BTreeMap::from([(rogue, 30), (knight, -10)]));
+        let orders = run(fixed)?;
+        assert!(orders.iter().all(|order| *order == [rogue, goblin, knight]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_death_cleanup() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let dagger = state.add_item(
+            "Dagger",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Dagger).build()),
+        );
+        let hero = state.add_actor(
+            ActorBuilder::new("Hero")
+                .stat(Stat::Strength, 18)
+                .max_health(30)
+                .build(),
+        );
+        let mut goblin = ActorBuilder::new("Goblin")
+            .group(1)
+            .max_health(4)
+            .death_behavior(DeathBehavior::InstantDeath)
+            .build();
+        goblin.give_item(dagger, 2);
+        goblin.equipped_items.equip(dagger);
+        let goblin = state.add_actor(goblin);
+        let boss = state.add_actor(ActorBuilder::new("Boss").group(1).max_health(8).build());
+        let policy = brawl_policy();
+        for actor in [hero, goblin, boss] {
+            state.set_actor_policy(actor, policy.clone());
+        }
+        state.encounter.death_cleanup = DeathCleanup::DropLoot;
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state.clone());
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.apply_damage(goblin, 10, None, false)?;
+        let dead = ctx.state.get_actor(goblin).unwrap();
+        assert!(dead.inventory.items.is_empty());
+        assert!(!dead.equipped_items.is_equipped(dagger));
+        assert_eq!(ctx.state.loot.items.get(&dagger), Some(&2));
+        assert_eq!(ctx.state.possible_targets(hero), vec![boss]);
+
+        let results = Integrator::new(50, Roller::test_rng(), state).run()?;
+        let recovered = ItemsRecovered.query(&results.state_tree)?;
+        assert!(recovered[&dagger] > 0.0 && recovered[&dagger] <= 2.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engagement_distance() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let bow = WeaponBuilder::new(WeaponType::Shortbow).range(80).build();
+        let hero = state.add_actor(ActorBuilder::new("Hero").movement_speed(30).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        state.encounter.engagement_distance = Some(60);
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.transition(Transition::BeginCombat)?;
+        assert_eq!(ctx.state.distance, Some(60));
+
+        let strike = |action_economy_usage| ActionTaken {
+            actor: hero,
+            action: Action::UnarmedStrike(UnarmedStrikeAction {
+                target: goblin,
+                attack_roll_settings: RollSettings::default(),
+            }),
+            action_economy_usage,
+        };
+        ctx.transition(Transition::BeginTurn { actor: hero })?;
+        ctx.evaluate_action(hero, &strike(ActionEconomyUsage::Action))?;
+        ctx.evaluate_action(hero, &strike(ActionEconomyUsage::BonusAction))?;
+        // one turn's movement isn't enough to close 55 feet
+        assert_eq!(ctx.state.distance, Some(30));
+
+        let (hero, goblin) = (
+            ctx.state.get_actor(hero).unwrap(),
+            ctx.state.get_actor(goblin).unwrap(),
+        );
+        let far =
+            ctx.state
+                .attack_roll_settings(hero, goblin, bow.is_melee(), RollSettings::default());
+        assert_eq!(far.advantage, Advantage::Normal);
+        let mut engaged = State::clone(&ctx.state);
+        engaged.distance = Some(5);
+        let close =
+            engaged.attack_roll_settings(hero, goblin, bow.is_melee(), RollSettings::default());
+        assert_eq!(close.advantage, Advantage::Disadvantage);
+
+        assert_eq!(bow.reach(), 80);
+        assert_eq!(WeaponBuilder::new(WeaponType::Pike).build().reach(), 10);
+        assert_eq!(WeaponBuilder::new(WeaponType::Dagger).build().reach(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_environmental_effects() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").max_health(100).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(100).build());
+        let mut zones = ZoneMap::default();
+        let hall = zones.add_zone("Hall");
+        let lava = zones.add_zone("Lava");
+        zones.connect(hall, lava);
+        zones.starting_zones.insert(goblin, lava);
+        state.encounter.zones = Some(zones);
+        state.encounter.environment = vec![
+            EnvironmentalEffect {
+                name: "Lava".to_string(),
+                area: EffectArea::Zone(lava),
+                effects: vec![EffectSpec {
+                    damage: Some("1d6 fire".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            EnvironmentalEffect {
+                name: "Ceiling collapse".to_string(),
+                schedule: Schedule::AtRound(2),
+                effects: vec![EffectSpec {
+                    damage: Some("10".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ];
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        integrator.record_contributions(true);
+        integrator.record_turn_order(true);
+        let mut state_tree = integrator.begin();
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.transition(Transition::BeginCombat)?;
+        for (actor, roll) in [(hero, 20), (goblin, 10)] {
+            ctx.transition(Transition::InitiativeRoll { actor, roll })?;
+        }
+        let health = |ctx: &CombatContext, actor| ctx.state.get_actor(actor).unwrap().health;
+
+        ctx.trigger_environment()?;
+        assert_eq!(health(&ctx, hero), 100);
+        let burned = 100 - health(&ctx, goblin);
+        assert!((1..=6).contains(&burned));
+
+        // neither side attacks, so only the top of round 2 does anything
+        while ctx.state.turn == 0 {
+            assert!(ctx.advance_turn()?);
+        }
+        assert_eq!(health(&ctx, hero), 90);
+        let goblin_health = health(&ctx, goblin);
+        assert!(goblin_health <= 100 - burned - 11);
+
+        // none of it is credited to whoever's turn it was
+        let contributions = ctx.integrator.contributions.as_ref().unwrap();
+        assert!(contributions.actors.values().all(|a| a.damage_dealt == 0));
+        let environment_damage = (100 - 90) + (100 - goblin_health) as u64;
+        assert_eq!(contributions.environment.damage_dealt, environment_damage);
+        ctx.transition(Transition::EndCombat)?;
+        let turn_order = ctx.integrator.turn_order.as_ref().unwrap();
+        assert_eq!(turn_order.positions.len(), 2);
+        assert!(turn_order.positions.values().all(|p| p.damage_dealt == 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zone_movement() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let dagger = state.add_item(
+            "Dagger",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Dagger).build()),
+        );
+        let mut hero = ActorBuilder::new("Hero").max_health(50).build();
+        hero.give_item(dagger, 1);
+        let hero = state.add_actor(hero);
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(50).build());
+        let mut zones = ZoneMap::default();
+        let road = zones.add_zone("Road");
+        let bridge = zones.add_zone("Bridge");
+        let camp = zones.add_zone("Camp");
+        zones.connect(road, bridge);
+        zones.connect(bridge, camp);
+        zones.starting_zones.insert(goblin, camp);
+        state.encounter.zones = Some(zones);
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.transition(Transition::BeginCombat)?;
+        let stab = ActionTaken {
+            actor: hero,
+            action: Action::Attack(AttackAction {
+                weapon_used: dagger,
+                target: goblin,
+                attack_roll_settings: RollSettings::default(),
+                power_attack: false,
+            }),
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        // two zones away, so the first turn is spent crossing the bridge
+        ctx.transition(Transition::BeginTurn { actor: hero })?;
+        ctx.evaluate_action(hero, &stab)?;
+        assert_eq!(ctx.state.zone_of(hero), Some(bridge));
+        assert_eq!(ctx.state.get_actor(goblin).unwrap().health, 50);
+        ctx.transition(Transition::EndTurn { actor: hero })?;
+
+        ctx.transition(Transition::BeginTurn { actor: hero })?;
+        ctx.evaluate_action(hero, &stab)?;
+        assert_eq!(ctx.state.zone_of(hero), Some(camp));
+        assert_eq!(ctx.state.distance_between(hero, goblin), Some(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zone_terrain() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let dagger = state.add_item(
+            "Dagger",
+            ItemInner::Weapon(
+                WeaponBuilder::new(WeaponType::Dagger)
+                    .damage("1d4+2")
+                    .build(),
+            ),
+        );
+        let mut hero = ActorBuilder::new("Hero").max_health(50).build();
+        hero.give_item(dagger, 1);
+        let hero = state.add_actor(hero);
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(50).build());
+        let mut zones = ZoneMap::default();
+        let road = zones.add_zone("Road");
+        let bog = zones.add_zone("Bog");
+        let camp = zones.add_zone("Camp");
+        zones.connect(road, bog);
+        zones.connect(bog, camp);
+        zones.difficult.insert(bog);
+        zones.hazards.insert(
+            camp,
+            Hazard {
+                name: "Caltrops".to_string(),
+                effects: vec![EffectSpec {
+                    damage: Some("3 piercing".to_string()),
+                    ..Default::default()
+                }],
+            },
+        );
+        zones.starting_zones.insert(goblin, camp);
+        state.encounter.zones = Some(zones);
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        integrator.record_contributions(true);
+        let mut state_tree = integrator.begin();
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.transition(Transition::BeginCombat)?;
+        for (actor, roll) in [(hero, 20), (goblin, 10)] {
+            ctx.transition(Transition::InitiativeRoll { actor, roll })?;
+        }
+        let stab = ActionTaken {
+            actor: hero,
+            action: Action::Attack(AttackAction {
+                weapon_used: dagger,
+                target: goblin,
+                attack_roll_settings: RollSettings::default(),
+                power_attack: false,
+            }),
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        let turn = |ctx: &mut CombatContext| -> anyhow::Result<()> {
+            ctx.transition(Transition::BeginTurn { actor: hero })?;
+            ctx.evaluate_action(hero, &stab)?;
+            ctx.transition(Transition::EndTurn { actor: hero })
+        };
+
+        // the bog takes two turns to wade into
+        turn(&mut ctx)?;
+        assert_eq!(ctx.state.zone_of(hero), Some(road));
+        assert_eq!(ctx.state.zone_progress.get(&hero), Some(&bog));
+        turn(&mut ctx)?;
+        assert_eq!(ctx.state.zone_of(hero), Some(bog));
+        assert!(ctx.state.zone_progress.is_empty());
+
+        // the camp is strewn with caltrops, which the hero steps on before stabbing
+        turn(&mut ctx)?;
+        assert_eq!(ctx.state.zone_of(hero), Some(camp));
+        assert_eq!(ctx.state.get_actor(hero).unwrap().health, 47);
+
+        // staying put doesn't set them off again, and the stabbing is the hero's own
+        while ctx.state.get_actor(goblin).unwrap().health == 50 {
+            turn(&mut ctx)?;
+        }
+        assert_eq!(ctx.state.get_actor(hero).unwrap().health, 47);
+        let stabbed = 50 - ctx.state.get_actor(goblin).unwrap().health;
+        let contributions = ctx.integrator.contributions.as_ref().unwrap();
+        assert_eq!(contributions.environment.damage_dealt, 3);
+        assert_eq!(contributions.actors[&hero].damage_dealt, stabbed as u64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stealthy_approach() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        state.add_actor(
+            ActorBuilder::new("Rogue")
+                .group(1)
+                .stat(Stat::Dexterity, 18)
+                .skill_proficiency(Skill::Stealth, SkillProficiency::Expert)
+                .build(),
+        );
+        state.encounter.engagement_distance = Some(60);
+        let opening = |state: &State| -> anyhow::Result<Option<u32>> {
+            let mut state = state.clone();
+            Transition::BeginCombat.apply(&mut state)?;
+            Ok(state.distance)
+        };
+        assert_eq!(opening(&state)?, Some(60));
+
+        // the rogue's passive Stealth of 18 beats the hero's passive Perception of 10
+        state.encounter.rules.stealth = true;
+        assert_eq!(opening(&state)?, Some(5));
+
+        let hero_actor = state.actors.get_mut(&hero).unwrap();
+        hero_actor.stats.set(Stat::Wisdom, 20);
+        hero_actor
+            .skill_proficiencies
+            .set(Skill::Perception, SkillProficiency::Expert);
+        assert_eq!(opening(&state)?, Some(60));
+
+        // darkness knocks 5 off the hero's passive Perception of 19
+        state.encounter.rules.lighting = true;
+        state.encounter.lighting = Lighting::Darkness;
+        assert_eq!(opening(&state)?, Some(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_power_attack_needs_feat_and_weapon() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let rapier = state.add_item(
+            "Rapier",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Rapier).damage("1d8").build()),
+        );
+        let maul = state.add_item(
+            "Maul",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Maul).damage("2d6").build()),
+        );
+        let mut hero = ActorBuilder::new("Hero")
+            .feature(Feature::GreatWeaponMaster)
+            .build();
+        hero.give_item(rapier, 1);
+        hero.give_item(maul, 1);
+        let hero = state.add_actor(hero);
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(50).build());
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.transition(Transition::BeginCombat)?;
+        let power_attack = |weapon_used| ActionTaken {
+            actor: hero,
+            action: Action::Attack(AttackAction {
+                weapon_used,
+                target: goblin,
+                attack_roll_settings: RollSettings::default(),
+                power_attack: true,
+            }),
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        ctx.transition(Transition::BeginTurn { actor: hero })?;
+        assert!(ctx.evaluate_action(hero, &power_attack(rapier)).is_err());
+        ctx.evaluate_action(hero, &power_attack(maul))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reach_weapons() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let dagger = state.add_item(
+            "Dagger",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Dagger).build()),
+        );
+        let glaive = state.add_item(
+            "Glaive",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Glaive).build()),
+        );
+        let whip = state.add_item(
+            "Whip",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Whip).build()),
+        );
+        let mut hero = ActorBuilder::new("Hero").max_health(50).build();
+        hero.give_item(dagger, 1);
+        hero.give_item(glaive, 1);
+        let hero = state.add_actor(hero);
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(50).build());
+        state.encounter.engagement_distance = Some(10);
+        state.encounter.rules.flanking = true;
+
+        let attack = |weapon_used, action_economy_usage| ActionTaken {
+            actor: hero,
+            action: Action::Attack(AttackAction {
+                weapon_used,
+                target: goblin,
+                attack_roll_settings: RollSettings::default(),
+                power_attack: false,
+            }),
+            action_economy_usage,
+        };
+        // reach weapons strike from 10 feet without closing in, and without engaging
+        for weapon in [glaive, whip] {
+            let mut integrator = Integrator::new(1, Roller::test_rng(), state.clone());
+            let mut state_tree = StateTree::new(integrator.initial_state.clone());
+            let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+            ctx.transition(Transition::BeginCombat)?;
+            ctx.transition(Transition::BeginTurn { actor: hero })?;
+            ctx.evaluate_action(hero, &attack(weapon, ActionEconomyUsage::Action))?;
+            assert_eq!(ctx.state.distance, Some(10));
+            assert!(ctx.state.engagements.is_empty());
+        }
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state.clone());
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.transition(Transition::BeginCombat)?;
+        ctx.transition(Transition::BeginTurn { actor: hero })?;
+        // out of reach without the main action to move with, so nothing is spent
+        ctx.evaluate_action(hero, &attack(dagger, ActionEconomyUsage::BonusAction))?;
+        let actor = ctx.state.get_actor(hero).unwrap();
+        assert!(
+            actor
+                .action_economy
+                .can_take_action(ActionEconomyUsage::BonusAction)
+        );
+        // the policy reaches for the glaive from here, even though the dagger comes first
+        let chosen = PolicyBuilder::new()
+            .action_weight(ActionType::Attack, 1)
+            .build()
+            .take_action(
+                ActionEconomyUsage::Action,
+                hero,
+                &ctx.state,
+                &mut Roller::test_rng(),
+            )?;
+        assert!(matches!(
+            chosen.action,
+            Action::Attack(AttackAction { weapon_used, .. }) if weapon_used == glaive
+        ));
+        // a dagger has to close to 5 feet first, which puts the hero in melee
+        ctx.evaluate_action(hero, &attack(dagger, ActionEconomyUsage::Action))?;
+        assert_eq!(ctx.state.distance, Some(5));
+        assert_eq!(ctx.state.engagements.get(&hero), Some(&goblin));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_named_hooks() -> anyhow::Result<()> {
+        struct Counter {
+            label: &'static str,
+            order: Arc<std::sync::Mutex<Vec<&'static str>>>,
+            combats: usize,
+        }
+
+        impl Hook for Counter {
+            fn on_combat_start(&mut self, _state: &State) {
+                self.combats += 1;
+                self.order.lock().unwrap().push(self.label);
+            }
+
+            fn metrics(&self) -> Vec<(String, f64)> {
+                vec![("combats".to_string(), self.combats as f64)]
+            }
+        }
+
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        state.set_actor_policy(fighter, policy.clone());
+        state.set_actor_policy(goblin, policy);
+
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let counter = |label| Counter {
+            label,
+            order: order.clone(),
+            combats: 0,
+        };
+        let mut integrator = Integrator::new(2, Roller::test_rng(), state);
+        integrator.add_named_hook("first", counter("old first"));
+        integrator.add_hook(counter("unnamed"));
+        integrator.add_named_hook("last", counter("last"));
+        integrator.add_named_hook("first", counter("first"));
+        integrator.set_hook_enabled("last", false)?;
+        assert!(integrator.set_hook_enabled("missing", true).is_err());
+
+        let results = integrator.run()?;
+        assert_eq!(
+            results.hook_metrics,
+            vec![
+                ("first.combats".to_string(), 2.0),
+                ("combats".to_string(), 2.0)
+            ]
+        );
+        assert_eq!(
+            *order.lock().unwrap(),
+            ["first", "unnamed", "first", "unnamed"]
+        );
+        assert!(integrator.remove_hook("last").is_some());
+        assert_eq!(integrator.hooks.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_emitted_metrics() -> anyhow::Result<()> {
+        #[derive(Default)]
+        struct RunningCount {
+            combats: usize,
+            emitted: Vec<(String, f64)>,
+        }
+
+        impl Hook for RunningCount {
+            fn on_combat_end(&mut self, _state: &State) {
+                self.combats += 1;
+                self.emitted
+                    .push(("combats".to_string(), self.combats as f64));
+            }
+
+            fn take_emitted(&mut self) -> Vec<(String, f64)> {
+                std::mem::take(&mut self.emitted)
+            }
+        }
+
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        state.set_actor_policy(fighter, policy.clone());
+        state.set_actor_policy(goblin, policy);
+
+        let (tx, rx) = mpsc::channel();
+        let mut integrator = Integrator::new(3, Roller::test_rng(), state);
+        integrator.add_named_hook("count", RunningCount::default());
+        integrator.stream_metrics(tx);
+        let results = integrator.run()?;
+
+        let streamed: Vec<MetricSample> = rx.try_iter().collect();
+        assert_eq!(streamed, results.metric_stream);
+        let points: Vec<(usize, &str, f64)> = streamed
+            .iter()
+            .map(|sample| (sample.combat, sample.name.as_str(), sample.value))
+            .collect();
+        assert_eq!(
+            points,
+            [
+                (1, "count.combats", 1.0),
+                (2, "count.combats", 2.0),
+                (3, "count.combats", 3.0)
+            ]
+        );
+
+        let mut merged = results.clone();
+        merged.merge(&results)?;
+        assert_eq!(merged.metric_stream.len(), 6);
+        assert_eq!(merged.metric_stream[3].combat, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stat_modifier_expiry() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").stat(Stat::Strength, 16).build());
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        let strength =
+            |ctx: &CombatContext| ctx.state.get_actor(hero).unwrap().stats.get(Stat::Strength);
+        ctx.transition(Transition::BeginCombat)?;
+        ctx.transition(Transition::InitiativeRoll {
+            actor: hero,
+            roll: 10,
+        })?;
+
+        ctx.transition(Transition::StatModification {
+            target: hero,
+            stat: Stat::Strength,
+            delta: -20,
+            duration: Some(1),
+        })?;
+        assert_eq!(strength(&ctx), 1);
+
+        ctx.expire_timed_effects(Expiry::StartOfTurn(hero))?;
+        assert_eq!(strength(&ctx), 1);
+
+        // knocked down, the hero stands up when their next turn starts
+        ctx.apply_condition(hero, Condition::Prone)?;
+        assert!(ctx.state.get_actor(hero).unwrap().conditions.is_prone());
+
+        // alone in the initiative order, the hero's next turn starts the next round
+        ctx.transition(Transition::AdvanceInitiative)?;
+        assert_eq!(ctx.state.turn, 1);
+        ctx.expire_timed_effects(Expiry::StartOfTurn(hero))?;
+        assert_eq!(strength(&ctx), 16);
+        assert!(!ctx.state.get_actor(hero).unwrap().conditions.is_prone());
+        assert!(ctx.state.timed_effects.effects.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_effect_tables() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let sorcerer = state.add_actor(
+            ActorBuilder::new("Sorcerer")
+                .random_effects(
+                    RandomEffectTable::new("Wild Magic", EffectTrigger::TurnStart)
+                        .entry("Dud", 0, Vec::new())
+                        .entry(
+                            "Bulk Up",
+                            1,
+                            vec![Effect::ModifyStat {
+                                target: EffectTarget::User,
+                                stat: Stat::Strength,
+                                delta: 2,
+                                duration: None,
+                            }],
+                        ),
+                )
+                .random_effects(
+                    RandomEffectTable::new("Searing Touch", EffectTrigger::Attack).entry(
+                        "Burn",
+                        1,
+                        vec![Effect::Damage {
+                            target: EffectTarget::Target,
+                            damage: RollPlan::from("1d1+2"),
+                            damage_type: None,
+                        }],
+                    ),
+                )
+                .build(),
+        );
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(20).build());
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+
+        ctx.transition(Transition::BeginTurn { actor: sorcerer })?;
+        ctx.trigger_random_effects(sorcerer, EffectTrigger::TurnStart, None)?;
+        let stats = &ctx.state.get_actor(sorcerer).unwrap().stats;
+        assert_eq!(stats.get(Stat::Strength), 12);
+
+        let mut rolled = String::new();
+        Transition::RandomEffectRolled {
+            actor: sorcerer,
+            table: 0,
+            entry: 1,
+        }
+        .pretty_print(&mut rolled, &ctx.state)?;
+        assert_eq!(rolled, "Sorcerer rolls on Wild Magic: Bulk Up");
+
+        // the attack trigger fires whether or not the attack lands
+        let strike = ActionTaken {
+            actor: sorcerer,
+            action: Action::UnarmedStrike(UnarmedStrikeAction {
+                target: goblin,
+                attack_roll_settings: Default::default(),
+            }),
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        let health_before = ctx.state.get_actor(goblin).unwrap().health;
+        ctx.evaluate_action(sorcerer, &strike)?;
+        let health_after = ctx.state.get_actor(goblin).unwrap().health;
+        assert!(health_before - health_after >= 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recharge_abilities() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let breath = Ability::new(
+            "Fire Breath",
+            vec![Effect::Damage {
+                target: EffectTarget::Target,
+                damage: RollPlan::from("1d1+4"),
+                damage_type: None,
+            }],
+        )
+        .recharge(5)
+        .area();
+        let dragon = state.add_actor(ActorBuilder::new("Dragon").ability(breath).build());
+        let goblins = [
+            state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(20).build()),
+            state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(20).build()),
+        ];
+        let policy = PolicyBuilder::new().recharge_priority(100).build();
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.transition(Transition::BeginTurn { actor: dragon })?;
+
+        let action = policy.take_action(
+            ActionEconomyUsage::Action,
+            dragon,
+            &ctx.state,
+            &mut ctx.integrator.roller,
+        )?;
+        assert_eq!(action.action.action_type(), ActionType::UseAbility);
+
+        // a negative priority is refused, and leaves the ability out rather than panicking
+        let reluctant = PolicyBuilder::new().recharge_priority(-5).build();
+        assert_eq!(
+            reluctant.validate().unwrap_err().to_string(),
+            "Recharge priority -5 is negative"
+        );
+        let waits = reluctant.take_action(
+            ActionEconomyUsage::Action,
+            dragon,
+            &ctx.state,
+            &mut Roller::test_rng(),
+        )?;
+        assert_eq!(waits.action, Action::Wait);
+
+        ctx.evaluate_action(dragon, &action)?;
+        for goblin in goblins {
+            assert_eq!(ctx.state.get_actor(goblin).unwrap().health, 15);
+        }
+
+        let mut used = String::new();
+        Transition::AbilityUsed {
+            actor: dragon,
+            ability: 0,
+        }
+        .pretty_print(&mut used, &ctx.state)?;
+        assert_eq!(used, "Dragon uses Fire Breath");
+
+        // spent until a recharge roll comes up 5 or 6
+        assert!(!ctx.state.get_actor(dragon).unwrap().ability_ready(0));
+        assert!(
+            !ctx.state
+                .possible_actions(dragon, ActionEconomyUsage::Action)
+                .iter()
+                .any(|action| action.action_type() == ActionType::UseAbility)
+        );
+        let mut turns = 0;
+        while !ctx.state.get_actor(dragon).unwrap().ability_ready(0) {
+            turns += 1;
+            assert!(turns < 100, "breath weapon never recharged");
+            ctx.transition(Transition::BeginTurn { actor: dragon })?;
+            ctx.roll_recharges(dragon)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_huge_recharge_priority() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let breath = Ability::new(
+            "Fire Breath",
+            vec![Effect::Damage {
+                target: EffectTarget::Target,
+                damage: RollPlan::from("1d1+4"),
+                damage_type: None,
+            }],
+        )
+        .recharge(5)
+        .area();
+        let dragon = state.add_actor(ActorBuilder::new("Dragon").ability(breath).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(20).build());
+        // the boost saturates instead of overflowing, and the weights still add up
+        let policy = PolicyBuilder::new()
+            .action_weight(ActionType::UseAbility, i32::MAX)
+            .action_weight(ActionType::UnarmedStrike, i32::MAX)
+            .recharge_priority(i32::MAX)
+            .build();
+        assert!(policy.validate().is_ok());
+        state.set_actor_policy(dragon, policy);
+        state.set_actor_policy(goblin, brawl_policy());
+
+        let mut integrator = Integrator::new(20, Roller::test_rng(), state);
+        integrator.record_action_usage(true);
+        let results = integrator.run()?;
+        assert_eq!(results.combats_run, 20);
+        let usage = results.action_usage.unwrap();
+        assert!(usage.uses(dragon, UsedAction::Ability { index: 0 }) > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rerolls() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let halfling = state.add_actor(
+            ActorBuilder::new("Halfling")
+                .feature(Feature::HalflingLuck)
+                .rerolls(RerollResource::LuckPoint, 1)
+                .build(),
+        );
+        state.set_actor_policy(
+            halfling,
+            PolicyBuilder::new()
+                .rerolls(RerollHeuristic {
+                    attack_target_max_health: Some(20),
+                    ..Default::default()
+                })
+                .build(),
+        );
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(20).build());
+
+        // a natural 1 then a hit for 3; a miss, a luck point and a hit for 2; a miss with no luck left
+        let roller = Roller::scripted([1, 15, 3, 2, 18, 2, 2]);
+        let mut integrator = Integrator::new(1, roller, state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        let strike = ActionTaken {
+            actor: halfling,
+            action: Action::UnarmedStrike(UnarmedStrikeAction {
+                target: goblin,
+                attack_roll_settings: Default::default(),
+            }),
+            action_economy_usage: ActionEconomyUsage::Action,
+        };
+        for expected_health in [17, 15, 15] {
+            ctx.transition(Transition::BeginTurn { actor: halfling })?;
+            ctx.evaluate_action(halfling, &strike)?;
+            assert_eq!(ctx.state.get_actor(goblin).unwrap().health, expected_health);
+        }
+        let halfling_actor = ctx.state.get_actor(halfling).unwrap();
+        assert_eq!(halfling_actor.next_reroll(), None);
+
+        let mut spent = String::new();
+        Transition::RerollUsed {
+            actor: halfling,
+            resource: RerollResource::LuckPoint,
+        }
+        .pretty_print(&mut spent, &ctx.state)?;
+        assert_eq!(spent, "Halfling spends a luck point to reroll");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_halfling_luck_beyond_attacks() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let halfling = state.add_actor(
+            ActorBuilder::new("Halfling")
+                .feature(Feature::HalflingLuck)
+                .feature(Feature::UndeadFortitude)
+                .build(),
+        );
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+
+        // natural 1s on initiative and on the fortitude save are both rerolled
+        let roller = Roller::scripted([1, 12, 7, 1, 10]);
+        let mut integrator = Integrator::new(1, roller, state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.roll_initiative()?;
+        assert_eq!(ctx.state.get_actor(halfling).unwrap().initiative, Some(12));
+        assert_eq!(ctx.state.get_actor(goblin).unwrap().initiative, Some(7));
+        assert_eq!(
+            ctx.resolve_on_drop_triggers(halfling, 5, false)?,
+            Some(Feature::UndeadFortitude)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resources_spent() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let potion = state.add_item("Healing Potion", ItemInner::Potion(Potion::test_potion()));
+        let mut halfling = ActorBuilder::new("Halfling")
+            .level(3)
+            .max_health(20)
+            .scaling(LevelScaling::new(8))
+            .feature(Feature::HalflingLuck)
+            .rerolls(RerollResource::LuckPoint, 3)
+            .build();
+        halfling.give_item(potion, 2);
+        let halfling = state.add_actor(halfling);
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(10).build());
+        let policy = PolicyBuilder::new()
+            .action_weight(ActionType::UnarmedStrike, 1)
+            .rerolls(RerollHeuristic {
+                attack_target_max_health: Some(20),
+                ..Default::default()
+            })
+            .build();
+        for actor in [halfling, goblin] {
+            state.set_actor_policy(actor, policy.clone());
+        }
+
+        let results = Integrator::new(200, Roller::test_rng(), state).run()?;
+        let usage = ResourcesSpent { group: 0 }.query(&results.state_tree)?;
+        let initial_state = results.state_tree.initial_state();
+        assert!(
+            usage.victories.numerator > 0
+                && usage.victories.numerator < usage.victories.denominator
+        );
+        let luck = usage.rerolls[&RerollResource::LuckPoint];
+        assert!(luck > 0.0 && luck <= 3.0, "{luck}");
+        assert!(usage.health_lost > 0.0 && usage.health_lost < 20.0);
+        // a d8 heals 4.5 on average, and no one spends more hit dice than their level
+        assert!(usage.hit_dice > 0.0 && usage.hit_dice <= 3.0);
+        // nobody drinks potions yet
+        assert_eq!(usage.items_of_type(initial_state, ItemType::Potion), 0.0);
+
+        assert!(
+            ResourcesSpent { group: 2 }
+                .query(&results.state_tree)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resources_spent_on_potions() -> anyhow::Result<()> {
+        let mut state = State::new();
+        state.encounter.death_cleanup = DeathCleanup::DropLoot;
+        let potion = state.add_item("Healing Potion", ItemInner::Potion(Potion::test_potion()));
+        let mut hero = ActorBuilder::new("Hero").build();
+        hero.give_item(potion, 2);
+        let hero = state.add_actor(hero);
+        let mut squire = ActorBuilder::new("Squire").build();
+        squire.give_item(potion, 1);
+        let squire = state.add_actor(squire);
+        let mut goblin = ActorBuilder::new("Goblin").group(1).build();
+        goblin.give_item(potion, 3);
+        let goblin = state.add_actor(goblin);
+
+        // the hero drinks a potion, and the squire and goblin fall and drop theirs
+        let mut won = state.clone();
+        won.actors
+            .get_mut(&hero)
+            .unwrap()
+            .inventory
+            .remove_item(potion, 1);
+        for dead in [squire, goblin] {
+            won.actors.get_mut(&dead).unwrap().modify_health(-100);
+            Transition::ActorKilled {
+                actor: dead,
+                drop_loot: true,
+            }
+            .apply(&mut won)?;
+        }
+        assert_eq!(won.loot.items[&potion], 4);
+        assert_eq!(won.winner(), Some(0));
+
+        let mut tree = StateTree::with_mode(state, TreeMode::TerminalOnly);
+        tree.add_terminal_state(&won);
+        let usage = ResourcesSpent { group: 0 }.query(&tree)?;
+        assert_eq!(usage.victories.numerator, usage.victories.denominator);
+        // the squire's potion was dropped, not drunk, and the goblin's were never the party's
+        assert_eq!(usage.items, BTreeMap::from([(potion, 1.0)]));
+        assert_eq!(
+            usage.items_of_type(tree.initial_state(), ItemType::Potion),
+            1.0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_saving_throw_effects() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let dragon = state.add_actor(ActorBuilder::new("Dragon").build());
+        let knight = state.add_actor(ActorBuilder::new("Knight").group(1).max_health(100).build());
+        state
+            .encounter
+            .set_cover(dragon, knight, Cover::ThreeQuarters);
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+
+        let breath = |dc: i32| Effect::SavingThrow {
+            target: EffectTarget::Target,
+            save: SavingThrow::Dexterity,
+            dc,
+            on_fail: vec![Effect::Damage {
+                target: EffectTarget::Target,
+                damage: RollPlan::from("1d1+19"),
+                damage_type: None,
+            }],
+            on_success: Vec::new(),
+            half_damage_on_success: true,
+        };
+        let health = |ctx: &CombatContext| ctx.state.get_actor(knight).unwrap().health;
+
+        ctx.apply_effect(dragon, Some(knight), &breath(100))?;
+        assert_eq!(health(&ctx), 80);
+        ctx.apply_effect(dragon, Some(knight), &breath(-100))?;
+        assert_eq!(health(&ctx), 70);
+        // no target, no save
+        ctx.apply_effect(dragon, None, &breath(100))?;
+        assert_eq!(health(&ctx), 70);
+        // +5 from the knight's cover turns even a 1 into a 6
+        for _ in 0..5 {
+            ctx.apply_effect(dragon, Some(knight), &breath(6))?;
+        }
+        assert_eq!(health(&ctx), 20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_homebrew_effects_compiled_once() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let homebrew = serde_json::from_str(r#"{ "on_hit": { "damage": "1d1+4 fire" } }"#)?;
+        let hero = state.add_actor(ActorBuilder::new("Hero").homebrew(homebrew).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(50).build());
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = integrator.begin();
+        assert_eq!(
+            integrator
+                .compiled
+                .homebrew
+                .keys()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![(hero, EffectTrigger::Hit)]
+        );
+        // what fires is the compiled copy, not the spec compiled again
+        integrator.compiled.homebrew.insert(
+            (hero, EffectTrigger::Hit),
+            vec![Effect::Damage {
+                target: EffectTarget::Target,
+                damage: RollPlan::from("1d1+9"),
+                damage_type: None,
+            }]
+            .into(),
+        );
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.trigger_effects(hero, EffectTrigger::Hit, Some(goblin))?;
+        assert_eq!(ctx.state.get_actor(goblin).unwrap().health, 40);
+        // nothing is compiled for triggers with no effects
+        ctx.trigger_effects(hero, EffectTrigger::Attack, Some(goblin))?;
+        assert_eq!(ctx.state.get_actor(goblin).unwrap().health, 40);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_attacks_are_resolved_in_tree() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").stat(Stat::Strength, 16).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        for actor in [hero, goblin] {
+            state.set_actor_policy(actor, brawl_policy());
+        }
+
+        let mut integrator = Integrator::new(50, Roller::test_rng(), state);
+        let results = integrator.run()?;
+
+        let rates = AttackHitRates.query(&results.state_tree)?;
+        let hero_rate = rates[&(hero, goblin)];
+        assert!(hero_rate.attacks > 0);
+        assert!(hero_rate.hits > 0 && hero_rate.hits < hero_rate.attacks);
+        assert!(hero_rate.crits <= hero_rate.hits);
+        assert!(rates.contains_key(&(goblin, hero)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_on_drop_triggers() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let orc = state.add_actor(
+            ActorBuilder::new("Orc")
+                .max_health(15)
+                .feature(Feature::RelentlessEndurance)
+                .build(),
+        );
+        let brute = state.add_actor(
+            ActorBuilder::new("Brute")
+                .max_health(15)
+                .feature(Feature::RelentlessEndurance)
+                .build(),
+        );
+        let zombie = state.add_actor(
+            ActorBuilder::new("Zombie")
+                .group(1)
+                .max_health(22)
+                .feature(Feature::UndeadFortitude)
+                .build(),
+        );
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        let health = |ctx: &CombatContext, id| ctx.state.get_actor(id).unwrap().health;
+        let overkill = |ctx: &CombatContext, id| ctx.state.get_actor(id).unwrap().overkill;
+
+        // massive damage isn't survivable, and only what the brute had left counts as dealt
+        assert_eq!(ctx.apply_damage(brute, 30, None, false)?, 15);
+        assert_eq!((health(&ctx, brute), overkill(&ctx, brute)), (0, 15));
+        assert!(ctx.state.get_actor(brute).unwrap().is_dead());
+
+        assert_eq!(ctx.apply_damage(orc, 20, None, false)?, 14);
+        assert_eq!(health(&ctx, orc), 1);
+        assert!(
+            !ctx.state
+                .get_actor(orc)
+                .unwrap()
+                .can_use_feature(Feature::RelentlessEndurance)
+        );
+
+        // only once per combat
+        assert_eq!(ctx.apply_damage(orc, 5, None, false)?, 1);
+        assert_eq!((health(&ctx, orc), overkill(&ctx, orc)), (0, 4));
+
+        // undead fortitude never saves against a crit
+        ctx.apply_damage(zombie, 25, None, true)?;
+        assert_eq!((health(&ctx, zombie), overkill(&ctx, zombie)), (0, 3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resistances() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let ghoul = state.add_actor(
+            ActorBuilder::new("Ghoul")
+                .max_health(50)
+                .resistance(DamageType::Slashing, DamageResponse::Resistant)
+                .resistance(DamageType::Poison, DamageResponse::Immune)
+                .resistance(DamageType::Radiant, DamageResponse::Vulnerable)
+                .build(),
+        );
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        let health = |ctx: &CombatContext| ctx.state.get_actor(ghoul).unwrap().health;
+
+        assert_eq!(
+            ctx.apply_damage(ghoul, 9, Some(DamageType::Slashing), false)?,
+            4
+        );
+        assert_eq!(health(&ctx), 46);
+        assert_eq!(
+            ctx.apply_damage(ghoul, 9, Some(DamageType::Poison), false)?,
+            0
+        );
+        assert_eq!(health(&ctx), 46);
+        assert_eq!(
+            ctx.apply_damage(ghoul, 9, Some(DamageType::Radiant), false)?,
+            18
+        );
+        assert_eq!(health(&ctx), 28);
+        ctx.apply_damage(ghoul, 9, None, false)?;
+        assert_eq!(health(&ctx), 19);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_shard_results() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").max_health(6).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(6).build());
+        state.set_actor_policy(fighter, policy.clone());
+        state.set_actor_policy(goblin, policy);
+
+        let run_shard = |shard| {
+            let mut integrator = Integrator::new(10, Roller::for_shard(7, shard), state.clone());
+            integrator.record_hp_time_series(true);
+            integrator.record_event_timeline(true);
+            integrator.run()
+        };
+        let first = run_shard(0)?;
+        let second = run_shard(1)?;
+        assert_eq!(
+            run_shard(0)?.state_tree.total_node_hits(),
+            first.state_tree.total_node_hits()
+        );
+
+        let mut merged = first.clone();
+        merged.merge(&second)?;
+        assert_eq!(merged.combats_run, 20);
+        assert_eq!(
+            merged.state_tree.total_node_hits(),
+            first.state_tree.total_node_hits() + second.state_tree.total_node_hits()
+        );
+        let first_round_samples = |results: &IntegrationResults| {
+            results
+                .hp_time_series
+                .as_ref()
+                .and_then(|series| series.round_summary(fighter, 0))
+                .map_or(0, |summary| summary.samples)
+        };
+        assert_eq!(first_round_samples(&merged), 20);
+        let timeline = merged.event_timeline.as_ref().unwrap();
+        assert_eq!(timeline.combats, 20);
+        assert_eq!(timeline.combat_end.occurrences(), 20);
+        // every combat ends with someone down, after blood was first drawn
+        let downs = timeline
+            .first_down
+            .values()
+            .map(|h| h.occurrences())
+            .sum::<u64>();
+        assert!(downs >= 20);
+        assert!(timeline.first_blood.mean() <= timeline.combat_end.mean());
+
+        let metadata = first.metadata.as_ref().unwrap();
+        assert_eq!(metadata.combats, 10);
+        assert!(metadata.seed.is_some());
+        assert_eq!(metadata.initial_state_hash, StateHash::hash_state(&state));
+        let merged_metadata = merged.metadata.as_ref().unwrap();
+        assert_eq!(merged_metadata.combats, 20);
+        assert_eq!(merged_metadata.seed, None);
+
+        // win counts merge exactly, in either order
+        let fighter_wins = OutcomeConditionProbability::new(move |state: &State| {
+            state
+                .get_actor(fighter)
+                .is_some_and(|actor| actor.is_alive())
+        });
+        let mut expected = fighter_wins.query(&first.state_tree)?;
+        expected.merge(&fighter_wins.query(&second.state_tree)?);
+        assert_eq!(fighter_wins.query(&merged.state_tree)?, expected);
+        let mut reversed = second.clone();
+        reversed.merge(&first)?;
+        assert_eq!(fighter_wins.query(&reversed.state_tree)?, expected);
+
+        let mut without_series = Integrator::new(1, Roller::test_rng(), state.clone());
+        assert!(merged.merge(&without_series.run()?).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_hook_metrics() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").max_health(6).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(6).build());
+        state.set_actor_policy(fighter, policy.clone());
+        state.set_actor_policy(goblin, policy);
+
+        let run = |combats, metrics: &[(&str, f64)]| -> anyhow::Result<IntegrationResults> {
+            let mut results = Integrator::new(combats, Roller::test_rng(), state.clone()).run()?;
+            results.hook_metrics = metrics
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect();
+            Ok(results)
+        };
+        let first = run(1, &[("kills", 4.0), ("rounds", 2.0)])?;
+        let second = run(3, &[("kills", 8.0), ("heals", 4.0)])?;
+
+        // a metric only one side reported is averaged over that side's combats alone, whichever
+        // side it is
+        let mut merged = first.clone();
+        merged.merge(&second)?;
+        assert_eq!(
+            merged.hook_metrics,
+            [
+                ("kills".to_string(), 7.0),
+                ("rounds".to_string(), 2.0),
+                ("heals".to_string(), 4.0),
+            ]
+        );
+        assert_eq!(merged.hook_metric("rounds"), Some((2.0, 1)));
+        assert_eq!(merged.hook_metric("heals"), Some((4.0, 3)));
+        assert_eq!(merged.hook_metric("kills"), Some((7.0, 4)));
+        let mut reversed = second.clone();
+        reversed.merge(&first)?;
+        reversed.hook_metrics.sort_by(|(a, _), (b, _)| a.cmp(b));
+        merged.hook_metrics.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(reversed.hook_metrics, merged.hook_metrics);
+        assert_eq!(reversed.hook_metric_combats, merged.hook_metric_combats);
+
+        // the counts carry through further merges, and survive a save
+        let third = run(4, &[("rounds", 5.0)])?;
+        merged.merge(&third)?;
+        let merged = IntegrationResults::from_json(&serde_json::to_string(&merged)?)?;
+        assert_eq!(merged.hook_metric("rounds"), Some((4.4, 5)));
+        assert_eq!(merged.hook_metric("heals"), Some((4.0, 3)));
+        assert_eq!(merged.hook_metric("kills"), Some((7.0, 4)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_modes() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").max_health(8).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(6).build());
+        state.set_actor_policy(fighter, policy.clone());
+        state.set_actor_policy(goblin, policy);
+
+        let run = |mode| {
+            let mut integrator = Integrator::new(50, Roller::from_seed(3), state.clone());
+            integrator.set_tree_mode(mode);
+            integrator.record_hp_time_series(true);
+            integrator.run()
+        };
+        let full = run(TreeMode::Full)?;
+        let terminal = run(TreeMode::TerminalOnly)?;
+        let none = run(TreeMode::None)?;
+
+        // the same combats are fought whatever is kept of them
+        let fighter_wins = |results: &IntegrationResults| {
+            OutcomeConditionProbability::new(move |state: &State| {
+                state.get_actor(fighter).is_some_and(|a| a.is_alive())
+            })
+            .query(&results.state_tree)
+        };
+        assert_eq!(fighter_wins(&terminal)?, fighter_wins(&full)?);
+        assert_eq!(
+            RunSummary::new(&terminal).actors,
+            RunSummary::new(&full).actors
+        );
+        assert_eq!(terminal.state_tree.node_count(), 1);
+        assert_eq!(
+            terminal
+                .state_tree
+                .terminal_states()
+                .map(|t| t.hits.get())
+                .sum::<u64>(),
+            50
+        );
+        assert_eq!(none.state_tree.node_count(), 1);
+        // a tree that kept nothing can't answer, rather than answering 0
+        let error = fighter_wins(&none).unwrap_err().to_string();
+        assert!(error.contains("only kept None"), "{}", error);
+        assert!(OutcomeProbabilities.query(&none.state_tree).is_err());
+        assert!(ItemsRecovered.query(&none.state_tree).is_err());
+        assert_eq!(none.hp_time_series, full.hp_time_series);
+
+        let reloaded = IntegrationResults::from_json(&serde_json::to_string(&terminal)?)?;
+        let mut merged = terminal.clone();
+        merged.merge(&reloaded)?;
+        // the same combats twice over, so twice the counts
+        assert_eq!(
+            fighter_wins(&merged)?.reduced(),
+            fighter_wins(&full)?.reduced()
+        );
+        assert_eq!(
+            merged.state_tree.total_node_hits(),
+            2 * terminal.state_tree.total_node_hits()
+        );
+        assert!(merged.merge(&full).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_combat() -> anyhow::Result<()> {
+        struct TraceRecorder(std::sync::Arc<std::sync::Mutex<Vec<Vec<Transition>>>>);
+        impl Hook for TraceRecorder {
+            fn on_transition(&mut self, _state: &State, transition: &Transition) {
+                let mut trace = self.0.lock().unwrap();
+                if matches!(transition, Transition::BeginCombat) {
+                    trace.push(Vec::new());
+                }
+                trace.last_mut().unwrap().push(transition.clone());
+            }
+        }
+
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").max_health(12).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(12).build());
+        state.set_actor_policy(fighter, policy.clone());
+        state.set_actor_policy(goblin, policy);
+
+        let recorded = |run: &dyn Fn(&mut Integrator) -> anyhow::Result<()>| {
+            let trace = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let mut integrator = Integrator::new(6, Roller::from_seed(11), state.clone());
+            integrator.add_hook(TraceRecorder(trace.clone()));
+            run(&mut integrator)?;
+            let trace = trace.lock().unwrap().clone();
+            anyhow::Ok(trace)
+        };
+        let full = recorded(&|integrator| integrator.run().map(drop))?;
+        assert_eq!(full.len(), 6);
+        assert_ne!(full[3], full[4]);
+
+        // each combat has its own substream, so it replays alone, in any order
+        let replayed = recorded(&|integrator| {
+            let mut state_tree = integrator.begin();
+            for index in [4, 3] {
+                integrator.replay_combat(index, &mut state_tree)?;
+            }
+            Ok(())
+        })?;
+        assert_eq!(replayed, [full[4].clone(), full[3].clone()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_budget() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let _policy = brawl_policy();
+        state.add_actor(ActorBuilder::new("Fighter").max_health(6).build());
+        state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(6).build());
+        brawl(&mut state);
+
+        // the combat count is ignored, and the run goes on until time is up
+        let budget = std::time::Duration::from_millis(200);
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        integrator.set_stopping_condition(StoppingCondition::TimeBudget(budget));
+        let results = integrator.run()?;
+        assert!(results.combats_run > 1);
+        assert!(results.elapsed_time.to_std()? >= budget);
+        assert_eq!(integrator.progress(), 1.0);
+        let metadata = results.metadata.as_ref().unwrap();
+        assert_eq!(metadata.combats, results.combats_run);
+        assert_eq!(metadata.time_budget, Some(budget));
+
+        let mut merged = results.clone();
+        merged.merge(&results)?;
+        assert_eq!(merged.metadata.unwrap().time_budget, Some(budget * 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_limit() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let _policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").max_health(8).build());
+        state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(6).build());
+        brawl(&mut state);
+        let fighter_wins = |tree: &StateTree| {
+            OutcomeConditionProbability::new(move |state: &State| {
+                state.get_actor(fighter).is_some_and(|a| a.is_alive())
+            })
+            .query(tree)
+        };
+
+        // plenty of room changes nothing, but the tree is still measured
+        let mut integrator = Integrator::new(300, Roller::from_seed(3), state.clone());
+        integrator.set_memory_limit(usize::MAX, MemoryLimitAction::Stop);
+        let full = integrator.run()?;
+        assert_eq!(full.combats_run, 300);
+        assert_eq!(full.memory_limit_reached, None);
+        assert!(integrator.memory_usage.edges > 0);
+        assert_eq!(integrator.memory_usage.terminal_states, 0);
+
+        // a full tree reduced afterwards keeps the same outcomes
+        let mut reduced = full.state_tree.clone();
+        reduced.reduce_to(TreeMode::TerminalOnly)?;
+        assert_eq!(reduced.mode(), TreeMode::TerminalOnly);
+        assert_eq!(reduced.node_count(), 1);
+        assert_eq!(fighter_wins(&reduced)?, fighter_wins(&full.state_tree)?);
+        assert!(reduced.memory_usage().total() < full.state_tree.memory_usage().total());
+        assert!(reduced.reduce_to(TreeMode::Full).is_err());
+
+        // no room at all: the first check drops to terminal states, and the next one stops
+        let mut integrator = Integrator::new(1000, Roller::from_seed(3), state.clone());
+        integrator.set_memory_limit(1, MemoryLimitAction::TerminalOnly);
+        let limited = integrator.run()?;
+        assert_eq!(limited.combats_run, 2 * MEMORY_CHECK_INTERVAL);
+        assert_eq!(limited.memory_limit_reached, Some(MemoryLimitAction::Stop));
+        assert_eq!(limited.state_tree.mode(), TreeMode::TerminalOnly);
+        assert_eq!(
+            limited
+                .state_tree
+                .terminal_states()
+                .map(|t| t.hits.get())
+                .sum::<u64>(),
+            limited.combats_run as u64
+        );
+
+        let mut integrator = Integrator::new(1000, Roller::from_seed(3), state);
+        integrator.set_memory_limit(1, MemoryLimitAction::Stop);
+        let stopped = integrator.run()?;
+        assert_eq!(stopped.combats_run, MEMORY_CHECK_INTERVAL);
+        assert_eq!(stopped.state_tree.mode(), TreeMode::Full);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_actions() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let _policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").max_health(8).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(6).build());
+        brawl(&mut state);
+
+        let run = |batch| {
+            let mut integrator = Integrator::new(50, Roller::from_seed(3), state.clone());
+            integrator.batch_actions(batch);
+            integrator.run()
+        };
+        let unbatched = run(false)?;
+        let batched = run(true)?;
+
+        // the same combats, in a smaller tree
+        let fighter_wins = |results: &IntegrationResults| {
+            OutcomeConditionProbability::new(move |state: &State| {
+                state.get_actor(fighter).is_some_and(|a| a.is_alive())
+            })
+            .query(&results.state_tree)
+        };
+        assert_eq!(fighter_wins(&batched)?, fighter_wins(&unbatched)?);
+        assert_eq!(
+            AttackHitRates.query(&batched.state_tree)?,
+            AttackHitRates.query(&unbatched.state_tree)?
+        );
+        assert!(batched.state_tree.node_count() < unbatched.state_tree.node_count());
+
+        // a strike that lands is one edge, read as one line
+        let (_, _, strike) = batched
+            .state_tree
+            .edges()
+            .find(|(_, _, edge)| {
+                edge.transition.parts().iter().any(|part| {
+                    matches!(part, Transition::AttackResolved { attacker, hit: true, .. } if *attacker == goblin)
+                })
+            })
+            .unwrap();
+        assert!(matches!(strike.transition, Transition::Composite(_)));
+        let mut line = String::new();
+        strike
+            .transition
+            .pretty_print(&mut line, batched.state_tree.initial_state())?;
+        assert!(line.starts_with("Goblin"), "{}", line);
+        assert!(line.contains("; "), "{}", line);
+        assert!(!strike.transition.is_quiet());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,536 @@
+//! Template strings for the text the simulation writes about itself, so log output, combat
+//! replays and exported reports can be reworded or localized without code changes.
+//!
+//! A template is plain text with `{name}` placeholders, filled in from the transition or log
+//! line it describes. `{{` and `}}` stand for literal braces. Every transition template can use
+//! `{text}`, the built-in English description, and placeholders a transition doesn't have are
+//! left as they are, so a typo shows up in the output instead of disappearing.
+//!
+//! ```
+//! # use antikythera::prelude::*;
+//! let mut messages = MessageTemplates::default();
+//! messages.set_transition(TransitionType::BeginCombat, "Roll for initiative!");
+//! let mut line = String::new();
+//! messages.transition(&mut line, &Transition::BeginCombat, &State::new())?;
+//! assert_eq!(line, "Roll for initiative!");
+//! # Ok::<(), std::fmt::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::actor::ActorId,
+    simulation::{
+        state::State,
+        transition::{Transition, TransitionType},
+    },
+};
+
+/// A kind of line the integrator logs, other than the transitions themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LogMessage {
+    /// A transition, with `{combat}` (counting from 1) and `{text}`, the transition's message.
+    Transition,
+    /// A policy decision, with `{combat}` and `{text}`, the decision's audit.
+    Decision,
+}
+
+impl LogMessage {
+    pub fn default_template(self) -> &'static str {
+        match self {
+            LogMessage::Transition => "[combat {combat}] {text}",
+            LogMessage::Decision => "[combat {combat}] decision: {text}",
+        }
+    }
+}
+
+/// A line or heading of a run summary or exported report. Numbers come already formatted, so
+/// `{survived}` is like `62.50`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ReportMessage {
+    /// With `{combats}`, `{seconds}` and `{rate}`, in combats per second.
+    RunTotals,
+    /// One way combats ended, with `{description}` and `{percent}`.
+    Outcome,
+    /// With `{id}`, `{name}`, `{group}`, `{survived}` and `{standing}` (as percentages),
+    /// `{health}` (the mean final HP) and `{max_health}`.
+    Actor,
+    /// With `{damage}`, `{damage_share}`, `{healing}`, `{healing_share}`, `{kills}`, `{assists}`
+    /// and `{participation}`.
+    Contribution,
+    /// With `{damage_type}`, `{rolled}`, `{dealt}` and `{resisted}`.
+    TypedDamage,
+    /// With `{damage}`, the mean environmental damage per combat.
+    Environment,
+    /// The exported report's title.
+    ReportTitle,
+    /// The report's opening line, with `{combats}` and `{run}`, which is [`Self::ReportRun`] if
+    /// the results know how they were made and empty otherwise.
+    ReportAbout,
+    /// With `{version}`, `{started}` and `{seed}`, which is [`Self::ReportSeed`] or empty.
+    ReportRun,
+    /// With `{seed}`.
+    ReportSeed,
+    SummaryHeading,
+    MetricsHeading,
+    HpChartHeading,
+    RollStatisticsHeading,
+}
+
+impl ReportMessage {
+    pub fn default_template(self) -> &'static str {
+        match self {
+            ReportMessage::RunTotals => "{combats} combats in {seconds}s ({rate} combats/sec)",
+            ReportMessage::Outcome => "{description}: {percent}%",
+            ReportMessage::Actor => {
+                "[{id}] {name} ({group}): survived {survived}%, standing {standing}%, mean final \
+                 HP {health}/{max_health}"
+            }
+            ReportMessage::Contribution => {
+                "damage {damage}/combat ({damage_share}% of group), healing {healing}/combat \
+                 ({healing_share}% of group), {kills} kills, {assists} assists ({participation}% \
+                 kill participation)"
+            }
+            ReportMessage::TypedDamage => {
+                "{damage_type}: {rolled}/combat rolled, {dealt} dealt, {resisted} resisted"
+            }
+            ReportMessage::Environment => "Environment: damage {damage}/combat",
+            ReportMessage::ReportTitle => "Antikythera Report",
+            ReportMessage::ReportAbout => "{combats} combats simulated{run}.",
+            ReportMessage::ReportRun => " by version {version} on {started}{seed}",
+            ReportMessage::ReportSeed => " with seed {seed}",
+            ReportMessage::SummaryHeading => "Summary",
+            ReportMessage::MetricsHeading => "Metrics",
+            ReportMessage::HpChartHeading => "Mean HP by Round",
+            ReportMessage::RollStatisticsHeading => "Roll Statistics",
+        }
+    }
+}
+
+/// User overrides for the built-in messages, keyed by what they describe. Anything without an
+/// override is described the built-in way.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageTemplates {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub transitions: BTreeMap<TransitionType, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub logs: BTreeMap<LogMessage, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub reports: BTreeMap<ReportMessage, String>,
+}
+
+impl MessageTemplates {
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty() && self.logs.is_empty() && self.reports.is_empty()
+    }
+
+    pub fn set_transition(&mut self, transition_type: TransitionType, template: &str) {
+        self.transitions
+            .insert(transition_type, template.to_string());
+    }
+
+    pub fn set_log(&mut self, message: LogMessage, template: &str) {
+        self.logs.insert(message, template.to_string());
+    }
+
+    pub fn set_report(&mut self, message: ReportMessage, template: &str) {
+        self.reports.insert(message, template.to_string());
+    }
+
+    /// Describes `transition` like [`Transition::pretty_print`] does, unless its type has a
+    /// template. The parts of a composite transition are each described this way.
+    pub fn transition(
+        &self,
+        f: &mut impl std::fmt::Write,
+        transition: &Transition,
+        state: &State,
+    ) -> std::fmt::Result {
+        let mut text = String::new();
+        match transition {
+            Transition::Composite(parts) if parts.iter().any(|part| !part.is_quiet()) => {
+                for (i, part) in parts.iter().filter(|part| !part.is_quiet()).enumerate() {
+                    if i > 0 {
+                        text.push_str("; ");
+                    }
+                    self.transition(&mut text, part, state)?;
+                }
+            }
+            transition => transition.pretty_print(&mut text, state)?,
+        }
+        match self.transitions.get(&transition.transition_type()) {
+            Some(template) => {
+                let mut args = transition_args(transition, state);
+                args.push(("text", text));
+                write!(f, "{}", fill(template, &args))
+            }
+            None => write!(f, "{}", text),
+        }
+    }
+
+    /// A log line for `text`, which was already described with these templates.
+    pub fn log(&self, message: LogMessage, combat: usize, text: &str) -> String {
+        let template = self
+            .logs
+            .get(&message)
+            .map_or(message.default_template(), String::as_str);
+        fill(
+            template,
+            &[("combat", combat.to_string()), ("text", text.to_string())],
+        )
+    }
+
+    /// A report line or heading, with the placeholders `message` documents filled from `args`.
+    pub fn report(&self, message: ReportMessage, args: &[(&str, String)]) -> String {
+        let template = self
+            .reports
+            .get(&message)
+            .map_or(message.default_template(), String::as_str);
+        fill(template, args)
+    }
+}
+
+/// Replaces each `{name}` in `template` with its value in `args`.
+fn fill(template: &str, args: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            out.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let value = rest.starts_with('{').then(|| {
+            let end = rest.find('}')?;
+            let name = &rest[1..end];
+            let (_, value) = args.iter().find(|(arg, _)| *arg == name)?;
+            Some((end, value))
+        });
+        match value.flatten() {
+            Some((end, value)) => {
+                out.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The placeholders a transition's template can use, besides `{text}`. Names are the
+/// transition's field names, with actors, items and the like given by name.
+fn transition_args(transition: &Transition, state: &State) -> Vec<(&'static str, String)> {
+    let name = |actor: &ActorId| {
+        let mut name = String::new();
+        actor.pretty_print(&mut name, state).ok();
+        name
+    };
+    let debug = |value: &dyn std::fmt::Debug| format!("{:?}", value);
+    match transition {
+        Transition::Root
+        | Transition::BeginCombat
+        | Transition::EndCombat
+        | Transition::AdvanceInitiative
+        | Transition::Composite(_) => vec![],
+        Transition::InitiativeRoll { actor, roll } => {
+            vec![("actor", name(actor)), ("roll", roll.to_string())]
+        }
+        Transition::BeginTurn { actor } | Transition::EndTurn { actor } => {
+            vec![("actor", name(actor))]
+        }
+        Transition::HealthModification { target, delta } => vec![
+            ("target", name(target)),
+            ("delta", delta.to_string()),
+            ("amount", delta.abs().to_string()),
+        ],
+        Transition::StatModification {
+            target,
+            stat,
+            delta,
+            duration,
+        } => vec![
+            ("target", name(target)),
+            ("stat", debug(stat)),
+            ("delta", delta.to_string()),
+            ("amount", delta.abs().to_string()),
+            (
+                "duration",
+                duration.map_or_else(String::new, |rounds| rounds.to_string()),
+            ),
+        ],
+        Transition::ActionEconomyUsed {
+            target,
+            action_type,
+        } => vec![
+            ("target", name(target)),
+            ("action_type", debug(action_type)),
+        ],
+        Transition::ConditionApplied { target, condition }
+        | Transition::ConditionRemoved { target, condition } => {
+            let mut described = String::new();
+            condition.pretty_print(&mut described, state).ok();
+            vec![("target", name(target)), ("condition", described)]
+        }
+        Transition::ContestResolved {
+            initiator,
+            target,
+            contest,
+            success,
+        } => vec![
+            ("initiator", name(initiator)),
+            ("target", name(target)),
+            ("contest", debug(contest)),
+            ("success", success.to_string()),
+        ],
+        Transition::AttackResolved {
+            attacker,
+            target,
+            hit,
+            crit,
+        } => vec![
+            ("attacker", name(attacker)),
+            ("target", name(target)),
+            ("hit", hit.to_string()),
+            ("crit", crit.to_string()),
+        ],
+        Transition::FeatureUsed { actor, feature } => {
+            vec![("actor", name(actor)), ("feature", debug(feature))]
+        }
+        Transition::RandomEffectRolled {
+            actor,
+            table,
+            entry,
+        } => {
+            let rolled = state
+                .get_actor(*actor)
+                .and_then(|actor| actor.random_effects.get(*table));
+            vec![
+                ("actor", name(actor)),
+                (
+                    "table",
+                    rolled.map_or_else(|| table.to_string(), |table| table.name.clone()),
+                ),
+                (
+                    "entry",
+                    rolled
+                        .and_then(|table| table.entries.get(*entry))
+                        .map_or_else(|| entry.to_string(), |entry| entry.name.clone()),
+                ),
+            ]
+        }
+        Transition::SavingThrowResolved {
+            actor,
+            save,
+            dc,
+            success,
+        } => vec![
+            ("actor", name(actor)),
+            ("save", debug(save)),
+            ("dc", dc.to_string()),
+            ("success", success.to_string()),
+        ],
+        Transition::StatModifiersExpired { target } => vec![("target", name(target))],
+        Transition::ActorKilled { actor, drop_loot } => {
+            vec![("actor", name(actor)), ("drop_loot", drop_loot.to_string())]
+        }
+        Transition::DistanceClosed { actor, feet } => {
+            vec![("actor", name(actor)), ("feet", feet.to_string())]
+        }
+        Transition::Engaged { actor, target } => {
+            vec![("actor", name(actor)), ("target", name(target))]
+        }
+        Transition::ZoneEntered { actor, zone } => vec![
+            ("actor", name(actor)),
+            (
+                "zone",
+                state
+                    .encounter
+                    .zones
+                    .as_ref()
+                    .and_then(|zones| zones.name(*zone))
+                    .map_or_else(|| zone.0.to_string(), str::to_string),
+            ),
+        ],
+        Transition::ZoneApproached { actor, zone } => vec![
+            ("actor", name(actor)),
+            (
+                "zone",
+                state
+                    .encounter
+                    .zones
+                    .as_ref()
+                    .and_then(|zones| zones.name(*zone))
+                    .map_or_else(|| zone.0.to_string(), str::to_string),
+            ),
+        ],
+        Transition::HazardEntered { actor, zone } => vec![
+            ("actor", name(actor)),
+            (
+                "hazard",
+                state
+                    .encounter
+                    .zones
+                    .as_ref()
+                    .and_then(|zones| zones.hazards.get(zone))
+                    .map_or_else(|| zone.0.to_string(), |hazard| hazard.name.clone()),
+            ),
+        ],
+        Transition::DamageTaken {
+            target,
+            damage_type,
+            rolled,
+            dealt,
+        } => vec![
+            ("target", name(target)),
+            ("damage_type", debug(damage_type).to_lowercase()),
+            ("rolled", rolled.to_string()),
+            ("dealt", dealt.to_string()),
+            ("resisted", rolled.saturating_sub(*dealt).to_string()),
+        ],
+        Transition::CombatDecided { outcome } => {
+            let mut described = String::new();
+            outcome.pretty_print(&mut described, state).ok();
+            vec![("outcome", described)]
+        }
+        Transition::PhaseEntered { actor, phase } => vec![
+            ("actor", name(actor)),
+            (
+                "phase",
+                state
+                    .get_actor(*actor)
+                    .and_then(|actor| actor.phases.get(*phase))
+                    .map_or_else(|| (phase + 1).to_string(), |phase| phase.name.clone()),
+            ),
+        ],
+        Transition::EnvironmentalEffect { effect } => vec![(
+            "effect",
+            state
+                .encounter
+                .environment
+                .get(*effect)
+                .map_or_else(|| effect.to_string(), |effect| effect.name.clone()),
+        )],
+        Transition::RerollUsed { actor, resource } => {
+            vec![("actor", name(actor)), ("resource", debug(resource))]
+        }
+        Transition::TimedEffectApplied { effect } | Transition::TimedEffectExpired { effect } => {
+            let mut condition = String::new();
+            effect.change.pretty_print(&mut condition, state).ok();
+            let mut expiry = String::new();
+            effect.expiry.pretty_print(&mut expiry, state).ok();
+            vec![
+                ("target", name(&effect.target)),
+                ("condition", condition),
+                ("expiry", expiry),
+            ]
+        }
+        Transition::AbilityUsed { actor, ability }
+        | Transition::AbilityRecharged { actor, ability } => vec![
+            ("actor", name(actor)),
+            (
+                "ability",
+                state
+                    .get_actor(*actor)
+                    .and_then(|actor| actor.abilities.get(*ability))
+                    .map_or_else(|| ability.to_string(), |ability| ability.name.clone()),
+            ),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{actor::ActorBuilder, damage::DamageType};
+
+    #[test]
+    fn test_message_templates() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").build());
+        let hit = Transition::AttackResolved {
+            attacker: hero,
+            target: goblin,
+            hit: true,
+            crit: false,
+        };
+        let damage = Transition::DamageTaken {
+            target: goblin,
+            damage_type: DamageType::Fire,
+            rolled: 8,
+            dealt: 4,
+        };
+        let describe = |messages: &MessageTemplates, transition: &Transition| {
+            let mut line = String::new();
+            messages.transition(&mut line, transition, &state).unwrap();
+            line
+        };
+
+        let mut messages = MessageTemplates::default();
+        assert_eq!(describe(&messages, &hit), "Hero hits Goblin");
+
+        messages.set_transition(TransitionType::AttackResolved, "{attacker} ⚔ {target}");
+        messages.set_transition(
+            TransitionType::DamageTaken,
+            "{target}: -{dealt} {damage_type} ({resisted} resisted) {missing} {{braces}}",
+        );
+        assert_eq!(describe(&messages, &hit), "Hero ⚔ Goblin");
+        assert_eq!(
+            describe(&messages, &damage),
+            "Goblin: -4 fire (4 resisted) {missing} {braces}"
+        );
+
+        // composites describe each part with its own template
+        let composite = Transition::composite(vec![
+            Transition::ActionEconomyUsed {
+                target: hero,
+                action_type: crate::rules::actions::ActionEconomyUsage::Action,
+            },
+            hit,
+            damage,
+        ])
+        .unwrap();
+        assert_eq!(
+            describe(&messages, &composite),
+            "Hero ⚔ Goblin; Goblin: -4 fire (4 resisted) {missing} {braces}"
+        );
+        messages.set_transition(TransitionType::Composite, "<{text}>");
+        assert!(describe(&messages, &composite).starts_with("<Hero ⚔ Goblin; "));
+
+        assert_eq!(
+            messages.log(LogMessage::Transition, 3, "text"),
+            "[combat 3] text"
+        );
+        messages.set_log(LogMessage::Transition, "Kampf {combat}: {text}");
+        assert_eq!(
+            messages.log(LogMessage::Transition, 3, "text"),
+            "Kampf 3: text"
+        );
+
+        assert_eq!(
+            messages.report(ReportMessage::SummaryHeading, &[]),
+            "Summary"
+        );
+        messages.set_report(ReportMessage::Environment, "Umgebung: {damage}");
+        assert_eq!(
+            messages.report(ReportMessage::Environment, &[("damage", "2.5".to_string())]),
+            "Umgebung: 2.5"
+        );
+
+        let json = serde_json::to_string(&messages)?;
+        assert_eq!(serde_json::from_str::<MessageTemplates>(&json)?, messages);
+        assert_eq!(
+            serde_json::from_str::<MessageTemplates>("{}")?,
+            MessageTemplates::default()
+        );
+
+        Ok(())
+    }
+}
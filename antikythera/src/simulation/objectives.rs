@@ -0,0 +1,309 @@
+//! Victory conditions besides being the last group standing: holding out for a number of rounds,
+//! taking down a particular enemy, keeping someone alive, or getting to a zone.
+//!
+//! ```json
+//! "objectives": [
+//!     { "Survive": { "group": 0, "rounds": 5 } },
+//!     { "Protect": { "group": 0, "actor": 2 } }
+//! ]
+//! ```
+//!
+//! Objectives are checked in order whenever the usual last-group-standing check is, so the first
+//! one met decides the combat. Which one it was is recorded in the final state's
+//! [`State::outcome`].
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::actor::{Actor, ActorId},
+    simulation::{positioning::ZoneId, state::State},
+};
+
+/// A condition that ends combat with a winner as soon as it's met.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Objective {
+    /// `group` wins if anyone in it is still standing once `rounds` rounds are over.
+    Survive { group: u32, rounds: u64 },
+    /// `group` wins as soon as `target` drops to 0 HP.
+    Defeat { group: u32, target: ActorId },
+    /// `group` loses as soon as `actor` drops to 0 HP, and whoever's left standing wins.
+    Protect { group: u32, actor: ActorId },
+    /// `group` wins as soon as one of its conscious members stands in `zone`. Only met in
+    /// encounters with [`EncounterSetup::zones`](crate::simulation::encounter::EncounterSetup::zones).
+    ReachZone { group: u32, zone: ZoneId },
+}
+
+impl Objective {
+    /// Whether the objective has been met, and if so, who won: `Some(None)` when it ended the
+    /// combat without leaving exactly one group standing to claim it.
+    fn check(&self, state: &State) -> Option<Option<u32>> {
+        let standing = |group: u32| {
+            state
+                .actors
+                .values()
+                .any(|actor| actor.group == group && state.is_fighting(actor))
+        };
+        let down = |actor: ActorId| state.get_actor(actor).is_none_or(|actor| !actor.is_alive());
+        match *self {
+            Objective::Survive { group, rounds } => {
+                (state.current_turn_index.is_some() && state.turn >= rounds && standing(group))
+                    .then_some(Some(group))
+            }
+            Objective::Defeat { group, target } => down(target).then_some(Some(group)),
+            Objective::Protect { group, actor } => {
+                if !down(actor) {
+                    return None;
+                }
+                let others: BTreeSet<u32> = state
+                    .actors
+                    .values()
+                    .filter(|other| other.group != group && state.is_fighting(other))
+                    .map(|other| other.group)
+                    .collect();
+                let mut others = others.into_iter();
+                Some(match (others.next(), others.next()) {
+                    (Some(winner), None) => Some(winner),
+                    _ => None,
+                })
+            }
+            Objective::ReachZone { group, zone } => state
+                .actors
+                .values()
+                .any(|actor| {
+                    actor.group == group
+                        && state.is_fighting(actor)
+                        && state.zone_of(actor.id) == Some(zone)
+                })
+                .then_some(Some(group)),
+        }
+    }
+
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write, state: &State) -> std::fmt::Result {
+        let name = |actor: ActorId| {
+            state
+                .get_actor(actor)
+                .map_or_else(|| format!("actor {}", actor.0), |actor| actor.name.clone())
+        };
+        match *self {
+            Objective::Survive { group, rounds } => {
+                write!(f, "{} survives {} rounds", state.group_name(group), rounds)
+            }
+            Objective::Defeat { group, target } => {
+                write!(f, "{} defeats {}", state.group_name(group), name(target))
+            }
+            Objective::Protect { group, actor } => {
+                write!(f, "{} loses {}", state.group_name(group), name(actor))
+            }
+            Objective::ReachZone { group, zone } => {
+                let zone = state
+                    .encounter
+                    .zones
+                    .as_ref()
+                    .and_then(|zones| zones.name(zone))
+                    .map_or_else(|| format!("zone {}", zone.0), str::to_string);
+                write!(f, "{} reaches {}", state.group_name(group), zone)
+            }
+        }
+    }
+}
+
+/// How a combat ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Outcome {
+    /// The winning group, or `None` if nobody was left to win.
+    pub winner: Option<u32>,
+    /// The index of the [`Objective`] that decided the combat, or `None` if it was the last
+    /// group standing.
+    pub objective: Option<usize>,
+}
+
+impl Outcome {
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write, state: &State) -> std::fmt::Result {
+        match self.winner {
+            Some(group) => write!(f, "{} wins", state.group_name(group))?,
+            None => write!(f, "Nobody wins")?,
+        }
+        match self
+            .objective
+            .and_then(|index| state.encounter.objectives.get(index))
+        {
+            Some(objective) => {
+                write!(f, " (")?;
+                objective.pretty_print(f, state)?;
+                write!(f, ")")
+            }
+            None => write!(f, " (last standing)"),
+        }
+    }
+}
+
+impl State {
+    /// Whether an actor is standing and in the fight, rather than held in
+    /// [`State::reserves`].
+    pub fn is_fighting(&self, actor: &Actor) -> bool {
+        actor.is_alive() && !self.reserves.contains(&actor.id)
+    }
+
+    /// How the combat in progress has been decided, or `None` if it hasn't been yet. Objectives
+    /// come first, in order; failing those, combat is over once at most one group has anyone
+    /// standing.
+    pub fn combat_outcome(&self) -> Option<Outcome> {
+        for (index, objective) in self.encounter.objectives.iter().enumerate() {
+            if let Some(winner) = objective.check(self) {
+                return Some(Outcome {
+                    winner,
+                    objective: Some(index),
+                });
+            }
+        }
+
+        let living_groups: BTreeSet<u32> = self
+            .actors
+            .values()
+            .filter(|actor| self.is_fighting(actor))
+            .map(|actor| actor.group)
+            .collect();
+        (living_groups.len() <= 1).then(|| Outcome {
+            winner: living_groups.first().copied(),
+            objective: None,
+        })
+    }
+
+    /// The group that won the combat this state ended: the one recorded in [`State::outcome`],
+    /// or for a state without one, the only group with anyone still alive.
+    pub fn winner(&self) -> Option<u32> {
+        if let Some(outcome) = &self.outcome {
+            return outcome.winner;
+        }
+        let standing: BTreeSet<u32> = self
+            .actors
+            .values()
+            .filter(|actor| actor.is_alive())
+            .map(|actor| actor.group)
+            .collect();
+        match standing.len() {
+            1 => standing.first().copied(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::actor::ActorBuilder,
+        simulation::{
+            integration::Integrator,
+            query::{OutcomeProbabilities, Query},
+            roller::Roller,
+            transition::Transition,
+        },
+        test_utils::brawl,
+    };
+
+    #[test]
+    fn test_objectives() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").max_health(40).build());
+        let merchant = state.add_actor(ActorBuilder::new("Merchant").max_health(1).build());
+        let ogre = state.add_actor(ActorBuilder::new("Ogre").group(1).max_health(60).build());
+        brawl(&mut state);
+
+        let outcomes = |state: &State| -> anyhow::Result<_> {
+            let results = Integrator::new(50, Roller::test_rng(), state.clone()).run()?;
+            OutcomeProbabilities.query(&results.state_tree)
+        };
+
+        // holding out for a single round against an ogre that can't drop the hero that fast
+        state.encounter.objectives = vec![Objective::Survive {
+            group: 0,
+            rounds: 1,
+        }];
+        let survived = outcomes(&state)?;
+        let by_survival = Outcome {
+            winner: Some(0),
+            objective: Some(0),
+        };
+        assert!(survived[&by_survival].to_f64() > 0.99, "{:?}", survived);
+
+        // escorting a merchant with 1 HP goes badly whenever the ogre punches the merchant
+        state.encounter.objectives = vec![Objective::Protect {
+            group: 0,
+            actor: merchant,
+        }];
+        let escorted = outcomes(&state)?;
+        let merchant_lost = Outcome {
+            winner: Some(1),
+            objective: Some(0),
+        };
+        assert!(escorted[&merchant_lost].to_f64() > 0.5, "{:?}", escorted);
+
+        // with no objective met, it falls back to the last group standing
+        state.encounter.objectives = vec![Objective::Defeat {
+            group: 1,
+            target: hero,
+        }];
+        state.get_actor_mut(ogre).unwrap().max_health = 1;
+        state.get_actor_mut(ogre).unwrap().health = 1;
+        let routed = outcomes(&state)?;
+        assert!(
+            routed
+                .keys()
+                .all(|outcome| outcome.objective.is_none() || outcome.winner == Some(1))
+        );
+        let mut line = String::new();
+        Outcome {
+            winner: Some(0),
+            objective: None,
+        }
+        .pretty_print(&mut line, &state)?;
+        assert_eq!(line, "Group 0 wins (last standing)");
+        line.clear();
+        merchant_lost.pretty_print(&mut line, &state)?;
+        assert_eq!(line, "Group 1 wins (Group 1 defeats Hero)");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_winner() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").max_health(40).build());
+        for name in ["Ogre", "Troll"] {
+            state.add_actor(ActorBuilder::new(name).group(1).max_health(60).build());
+        }
+        brawl(&mut state);
+        // the hero only has to hold out for a round, and wins with both monsters still up
+        state.encounter.objectives = vec![Objective::Survive {
+            group: 0,
+            rounds: 1,
+        }];
+
+        let results = Integrator::new(20, Roller::test_rng(), state.clone()).run()?;
+        for terminal in results.state_tree.terminal_states() {
+            let survivors = |group| {
+                terminal
+                    .state
+                    .actors
+                    .values()
+                    .filter(|actor| actor.group == group && actor.is_alive())
+                    .count()
+            };
+            assert!(survivors(0) < survivors(1));
+            assert_eq!(terminal.state.winner(), Some(0));
+        }
+
+        // a state from before outcomes were recorded falls back to the last group standing
+        let mut routed = state;
+        Transition::HealthModification {
+            target: hero,
+            delta: -40,
+        }
+        .apply(&mut routed)?;
+        assert_eq!(routed.winner(), Some(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Boss phases: a fight that changes once its boss is bloodied, with a new policy, abilities held
+//! back until then, and reinforcements that only join when it happens.
+//!
+//! ```json
+//! "phases": [{
+//!     "name": "Enraged",
+//!     "below_percent": 50,
+//!     "policy": { "action_weights": [["UseAbility", 10]] },
+//!     "unlock": [0],
+//!     "spawn": [4, 5]
+//! }]
+//! ```
+//!
+//! Phases are entered in order, each once the actor is below its threshold at the end of an
+//! action, and each entry is recorded as a [`Transition::PhaseEntered`](crate::simulation::transition::Transition::PhaseEntered).
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::actor::{Actor, ActorId},
+    simulation::{policy::Policy, state::State},
+};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Phase {
+    pub name: String,
+    /// Entered once the actor is below this percentage of its max HP, but still standing.
+    pub below_percent: u32,
+    /// Replaces the actor's policy for the rest of the combat, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Policy>,
+    /// Indices into the actor's abilities that can't be used until this phase.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unlock: Vec<usize>,
+    /// Actors held in reserve until this phase, then added to the initiative order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spawn: Vec<ActorId>,
+}
+
+impl Phase {
+    pub fn new(name: &str, below_percent: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            below_percent,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    pub fn unlock(mut self, ability: usize) -> Self {
+        self.unlock.push(ability);
+        self
+    }
+
+    pub fn spawn(mut self, actor: ActorId) -> Self {
+        self.spawn.push(actor);
+        self
+    }
+
+    /// Whether an actor at `health` out of `max_health` is below this phase's threshold.
+    pub fn triggered_at(&self, health: i32, max_health: i32) -> bool {
+        health > 0 && (health as i64) * 100 < (max_health as i64) * self.below_percent as i64
+    }
+}
+
+impl Actor {
+    /// The phase the actor is in, if it has entered any.
+    pub fn current_phase(&self) -> Option<&Phase> {
+        self.phase
+            .checked_sub(1)
+            .and_then(|index| self.phases.get(index))
+    }
+
+    /// The index of the next phase the actor should enter, if it's due one.
+    pub fn due_phase(&self) -> Option<usize> {
+        let next = self.phases.get(self.phase)?;
+        next.triggered_at(self.health, self.max_health)
+            .then_some(self.phase)
+    }
+
+    /// Whether the ability at `index` is held back for a phase the actor hasn't entered yet.
+    pub fn ability_locked(&self, index: usize) -> bool {
+        self.phases
+            .iter()
+            .skip(self.phase)
+            .any(|phase| phase.unlock.contains(&index))
+    }
+}
+
+impl State {
+    /// Actors waiting to be spawned by a phase, who sit out combat until then.
+    pub fn phase_reserves(&self) -> BTreeSet<ActorId> {
+        self.actors
+            .values()
+            .flat_map(|actor| actor.phases.iter().skip(actor.phase))
+            .flat_map(|phase| phase.spawn.iter().copied())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::{
+            abilities::Ability,
+            actions::ActionType,
+            actor::ActorBuilder,
+            dice::RollPlan,
+            effects::{Effect, EffectTarget},
+        },
+        simulation::{
+            integration::Integrator,
+            policy::PolicyBuilder,
+            query::{PhaseProbabilities, Query},
+            roller::Roller,
+            transition::Transition,
+        },
+        test_utils::brawl_policy,
+    };
+
+    #[test]
+    fn test_boss_phases() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let brawl = brawl_policy();
+        let knight = state.add_actor(ActorBuilder::new("Knight").max_health(60).build());
+        let imp = state.add_actor(ActorBuilder::new("Imp").group(1).max_health(5).build());
+        let roar = Ability::new(
+            "Roar",
+            vec![Effect::Damage {
+                target: EffectTarget::Target,
+                damage: RollPlan::from("1d4"),
+                damage_type: None,
+            }],
+        );
+        let boss = state.add_actor(
+            ActorBuilder::new("Demon")
+                .group(1)
+                .max_health(20)
+                .ability(roar)
+                .phase(
+                    Phase::new("Enraged", 50)
+                        .policy(
+                            PolicyBuilder::new()
+                                .action_weight(ActionType::UseAbility, 1)
+                                .build(),
+                        )
+                        .unlock(0)
+                        .spawn(imp),
+                )
+                .build(),
+        );
+        for actor in [knight, imp, boss] {
+            state.set_actor_policy(actor, brawl.clone());
+        }
+
+        // before the phase, the roar is held back and the imp sits out
+        let demon = state.get_actor(boss).unwrap();
+        assert!(demon.ability_locked(0));
+        assert_eq!(demon.ready_abilities().count(), 0);
+        assert_eq!(state.phase_reserves(), BTreeSet::from([imp]));
+
+        let mut bloodied = state.clone();
+        Transition::BeginCombat.apply(&mut bloodied)?;
+        assert!(!bloodied.possible_targets(knight).contains(&imp));
+        Transition::HealthModification {
+            target: boss,
+            delta: -11,
+        }
+        .apply(&mut bloodied)?;
+        assert_eq!(bloodied.get_actor(boss).unwrap().health, 9);
+        assert_eq!(bloodied.get_actor(boss).unwrap().due_phase(), Some(0));
+        Transition::PhaseEntered {
+            actor: boss,
+            phase: 0,
+        }
+        .apply(&mut bloodied)?;
+        let demon = bloodied.get_actor(boss).unwrap();
+        assert_eq!(demon.current_phase().unwrap().name, "Enraged");
+        // the demon only has the one phase to enter
+        let error = Transition::PhaseEntered {
+            actor: boss,
+            phase: 1,
+        }
+        .apply(&mut bloodied.clone())
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Demon has no phase 1");
+        assert!(demon.ability_ready(0));
+        assert_eq!(
+            bloodied.policy_of(demon).action_weights,
+            [(ActionType::UseAbility, 1)]
+        );
+        assert!(bloodied.reserves.is_empty());
+        assert!(bloodied.possible_targets(knight).contains(&imp));
+
+        // a knight that hits hard enough gets the demon to its second phase more often than not
+        let results = Integrator::new(100, Roller::test_rng(), state).run()?;
+        let reached = PhaseProbabilities.query(&results.state_tree)?;
+        assert!(reached[&(boss, 0)].to_f64() > 0.5, "{:?}", reached);
+
+        Ok(())
+    }
+}
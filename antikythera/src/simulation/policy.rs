@@ -1,9 +1,17 @@
+use std::collections::BTreeSet;
+
 use crate::{
     prelude::ActionType,
     rules::{
-        actions::{Action, ActionEconomyUsage, ActionTaken, AttackAction, UnarmedStrikeAction},
-        actor::ActorId,
-        items::ItemInner,
+        actions::{
+            Action, ActionEconomyUsage, ActionTaken, AttackAction, GrappleAction, HelpAction,
+            ShoveAction, UnarmedStrikeAction, UseAbilityAction,
+        },
+        actor::{Actor, ActorId},
+        conditions::Condition,
+        dice::Advantage,
+        items::{ItemId, ItemInner},
+        rerolls::D20Test,
     },
     simulation::{roller::Roller, state::State},
 };
@@ -19,9 +27,21 @@ pub struct WeightedProbability<T> {
 }
 
 impl<T: Clone> WeightedProbability<T> {
-    pub fn new(items: Vec<(T, i32)>) -> Self {
-        let distr = WeightedIndex::new(items.iter().map(|&(_, weight)| weight)).unwrap();
-        Self { items, distr }
+    /// A table to sample `items` from, or `None` if none of them has a positive weight. Negative
+    /// weights count as 0, which leaves their items out. Weights that add up past `i32::MAX` are
+    /// scaled down in proportion to fit.
+    pub fn new(items: Vec<(T, i32)>) -> Option<Self> {
+        let weights: Vec<i64> = items
+            .iter()
+            .map(|&(_, weight)| i64::from(weight.max(0)))
+            .collect();
+        let total: i64 = weights.iter().sum();
+        let scale = |weight: i64| match total > i64::from(i32::MAX) {
+            true => (weight * i64::from(i32::MAX) / total) as i32,
+            false => weight as i32,
+        };
+        let distr = WeightedIndex::new(weights.into_iter().map(scale)).ok()?;
+        Some(Self { items, distr })
     }
 
     pub fn sample(&self, rng: &mut impl Rng) -> &T {
@@ -70,24 +90,379 @@ impl PolicyBuilder {
         self
     }
 
+    /// Weight for targeting actors with the given tag, used when no per-actor weight is set.
+    pub fn tag_weight(mut self, tag: &str, weight: i32) -> Self {
+        if let Some((_, existing_weight)) =
+            self.policy.tag_weights.iter_mut().find(|(t, _)| t == tag)
+        {
+            *existing_weight = weight;
+        } else {
+            self.policy.tag_weights.push((tag.to_string(), weight));
+        }
+        self
+    }
+
+    pub fn power_attack(mut self, heuristic: PowerAttackHeuristic) -> Self {
+        self.policy.power_attack = Some(heuristic);
+        self
+    }
+
+    /// Weight added to [`ActionType::UseAbility`] whenever one of the actor's recharge abilities is
+    /// ready. Like the other weights it can't be negative. Set it well above them to have monsters
+    /// use breath weapons as soon as they're back.
+    pub fn recharge_priority(mut self, weight: i32) -> Self {
+        self.policy.recharge_priority = Some(weight);
+        self
+    }
+
+    pub fn rerolls(mut self, heuristic: RerollHeuristic) -> Self {
+        self.policy.rerolls = Some(heuristic);
+        self
+    }
+
     pub fn build(self) -> Policy {
         self.policy
     }
 }
 
+/// When to take a power attack (-5 to hit, +10 damage) with weapon attacks.
+/// The attack is a power attack if any enabled condition holds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerAttackHeuristic {
+    /// Power attack targets whose AC (including cover) is at most this value.
+    pub max_target_ac: Option<i32>,
+    /// Power attack whenever the attack roll has advantage.
+    pub with_advantage: bool,
+}
+
+impl PowerAttackHeuristic {
+    pub fn always() -> Self {
+        Self {
+            max_target_ac: Some(i32::MAX),
+            with_advantage: true,
+        }
+    }
+
+    pub fn should_power_attack(&self, target_ac: i32, advantage: Advantage) -> bool {
+        self.max_target_ac.is_some_and(|max| target_ac <= max)
+            || (self.with_advantage && advantage.is_advantage())
+    }
+}
+
+/// When to spend a [`RerollResource`](crate::rules::rerolls::RerollResource) on a failed d20 roll.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RerollHeuristic {
+    /// Reroll missed attacks against targets with at most this much HP left, to finish them off.
+    pub attack_target_max_health: Option<i32>,
+    /// Reroll failed saving throws.
+    pub saving_throws: bool,
+    /// Reroll lost ability checks, such as grapple contests.
+    pub ability_checks: bool,
+}
+
+impl RerollHeuristic {
+    pub fn should_reroll(&self, test: D20Test, state: &State) -> bool {
+        match test {
+            D20Test::Attack { target } => self.attack_target_max_health.is_some_and(|max| {
+                state
+                    .get_actor(target)
+                    .is_some_and(|target| target.health <= max)
+            }),
+            D20Test::SavingThrow => self.saving_throws,
+            D20Test::AbilityCheck => self.ability_checks,
+        }
+    }
+}
+
+/// Why a policy left an action out of a decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RejectionReason {
+    /// The actor has no weapon to attack with.
+    NoWeapon,
+    /// The target is too large to grapple or shove.
+    TargetTooLarge,
+    /// The actor is already grappling the target.
+    AlreadyGrappling,
+    /// The target is already prone.
+    TargetProne,
+    /// The actor isn't grappled, so there's nothing to escape.
+    NotGrappled,
+    /// No ally would hit harder than the actor, so helping isn't worth it.
+    NoAllyToHelp,
+    /// None of the actor's abilities are ready to use.
+    NoAbilityReady,
+    /// The target is out of reach, and the actor can't close the distance with this action.
+    OutOfReach,
+    /// Policies can't choose this kind of action yet.
+    Unsupported,
+    /// The actor's action economy doesn't allow it right now.
+    ActionEconomySpent,
+}
+
+impl RejectionReason {
+    pub fn description(&self) -> &'static str {
+        match self {
+            RejectionReason::NoWeapon => "no weapon",
+            RejectionReason::TargetTooLarge => "target too large",
+            RejectionReason::AlreadyGrappling => "already grappling target",
+            RejectionReason::TargetProne => "target already prone",
+            RejectionReason::NotGrappled => "not grappled",
+            RejectionReason::NoAllyToHelp => "no ally worth helping",
+            RejectionReason::NoAbilityReady => "no ability ready",
+            RejectionReason::OutOfReach => "target out of reach",
+            RejectionReason::Unsupported => "not supported by policies",
+            RejectionReason::ActionEconomySpent => "action economy spent",
+        }
+    }
+}
+
+/// One action a policy weighed, and why it was ruled out if it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CandidateAction {
+    pub action_type: ActionType,
+    pub weight: i32,
+    pub rejected: Option<RejectionReason>,
+}
+
+/// Everything a policy weighed when choosing a single action, for debugging policies.
+/// Filled in by [`Policy::take_action_audited`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecisionAudit {
+    pub actor: ActorId,
+    pub action_economy_usage: ActionEconomyUsage,
+    /// Every enemy the policy could target, with its weight.
+    pub targets: Vec<(ActorId, i32)>,
+    /// The target picked, before the action was chosen.
+    pub target: Option<ActorId>,
+    /// Every action in the policy, in order, including those ruled out.
+    pub candidates: Vec<CandidateAction>,
+    /// The action chosen, or `None` if the actor waited because nothing was possible.
+    pub chosen: Option<ActionType>,
+}
+
+impl DecisionAudit {
+    pub fn new(actor: ActorId, action_economy_usage: ActionEconomyUsage) -> Self {
+        Self {
+            actor,
+            action_economy_usage,
+            targets: Vec::new(),
+            target: None,
+            candidates: Vec::new(),
+            chosen: None,
+        }
+    }
+
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write, state: &State) -> std::fmt::Result {
+        self.actor.pretty_print(f, state)?;
+        write!(f, " ({:?}): ", self.action_economy_usage)?;
+        match self.chosen {
+            Some(action_type) => write!(f, "chose {:?}", action_type)?,
+            None => write!(f, "nothing to do")?,
+        }
+        if let Some(target) = self.target {
+            write!(f, " targeting ")?;
+            target.pretty_print(f, state)?;
+        }
+        if !self.targets.is_empty() {
+            write!(f, "; targets [")?;
+            for (i, (target, weight)) in self.targets.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                target.pretty_print(f, state)?;
+                write!(f, ": {}", weight)?;
+            }
+            write!(f, "]")?;
+        }
+        if !self.candidates.is_empty() {
+            write!(f, "; actions [")?;
+            for (i, candidate) in self.candidates.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{:?}: {}", candidate.action_type, candidate.weight)?;
+                if let Some(reason) = candidate.rejected {
+                    write!(f, " ({})", reason.description())?;
+                }
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+/// How an actor picks its actions and targets, saved as part of the actor in scenario files.
+///
+/// Every field is optional, so external tools only need to write what they use. Weights are
+/// lists of `[key, weight]` pairs, with targets keyed by actor ID:
+///
+/// ```json
+/// "policy": {
+///     "action_weights": [["Attack", 3], ["Dodge", 1]],
+///     "target_weights": [[2, 5]],
+///     "tag_weights": [["caster", 4]],
+///     "power_attack": { "max_target_ac": 15, "with_advantage": true },
+///     "recharge_priority": 10,
+///     "rerolls": { "attack_target_max_health": 8, "saving_throws": true }
+/// }
+/// ```
+///
+/// An actor with no action weights only waits. An actor whose policy is left entirely empty
+/// follows its group's default policy instead, if the state has one; see
+/// [`State::policy_of`]. If neither sets anything, front ends that call
+/// [`State::fill_missing_policies`] before simulating give it [`Policy::attacker`] instead of
+/// leaving it idle. Loading and saving keep the empty policy as it is.
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Policy {
+    #[serde(default)]
     pub action_weights: Vec<(ActionType, i32)>,
+    #[serde(default)]
     pub target_weights: Vec<(ActorId, i32)>,
+    #[serde(default)]
+    pub tag_weights: Vec<(String, i32)>,
+    #[serde(default)]
+    pub power_attack: Option<PowerAttackHeuristic>,
+    /// Weight added to using an ability while a recharge ability is ready. See
+    /// [`PolicyBuilder::recharge_priority`].
+    #[serde(default)]
+    pub recharge_priority: Option<i32>,
+    #[serde(default)]
+    pub rerolls: Option<RerollHeuristic>,
 }
 
 impl Policy {
+    /// Attacks with a weapon when the actor has one, and unarmed otherwise. What
+    /// [`State::fill_missing_policy`] gives an actor with no policy to act on.
+    pub fn attacker() -> Self {
+        PolicyBuilder::new()
+            .action_weight(ActionType::Attack, 10)
+            .action_weight(ActionType::UnarmedStrike, 1)
+            .build()
+    }
+
+    /// Checks that no weight, including the recharge priority, is negative. A weight of 0 leaves its
+    /// action or target out.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (action_type, weight) in &self.action_weights {
+            if *weight < 0 {
+                anyhow::bail!("{:?} has a negative weight of {}", action_type, weight);
+            }
+        }
+        for (target, weight) in &self.target_weights {
+            if *weight < 0 {
+                anyhow::bail!(
+                    "Actor {} has a negative target weight of {}",
+                    target.0,
+                    weight
+                );
+            }
+        }
+        for (tag, weight) in &self.tag_weights {
+            if *weight < 0 {
+                anyhow::bail!("Tag {} has a negative target weight of {}", tag, weight);
+            }
+        }
+        if let Some(priority) = self.recharge_priority
+            && priority < 0
+        {
+            anyhow::bail!("Recharge priority {} is negative", priority);
+        }
+        Ok(())
+    }
+
+    /// Whether nothing has been set, in which case an actor defers to its group's policy.
+    pub fn is_empty(&self) -> bool {
+        *self == Policy::default()
+    }
+
+    /// The weight for targeting `target`: its own weight if set, otherwise the highest weight
+    /// among its tags, otherwise 1.
+    pub fn target_weight(&self, target: ActorId, state: &State) -> i32 {
+        if let Some((_, weight)) = self.target_weights.iter().find(|(id, _)| *id == target) {
+            return *weight;
+        }
+        state
+            .get_actor(target)
+            .and_then(|actor| {
+                self.tag_weights
+                    .iter()
+                    .filter(|(tag, _)| actor.has_tag(tag))
+                    .map(|(_, weight)| *weight)
+                    .max()
+            })
+            .unwrap_or(1)
+    }
+
+    /// Whether to spend a reroll on a failed `test`. Never, without a [`RerollHeuristic`].
+    pub fn should_reroll(&self, test: D20Test, state: &State) -> bool {
+        self.rerolls
+            .is_some_and(|heuristic| heuristic.should_reroll(test, state))
+    }
+
+    fn should_power_attack(
+        &self,
+        actor: &Actor,
+        target: &Actor,
+        weapon_used: ItemId,
+        state: &State,
+    ) -> bool {
+        let Some(heuristic) = self.power_attack else {
+            return false;
+        };
+        let Some(ItemInner::Weapon(weapon)) = state.items.get(&weapon_used).map(|item| &item.inner)
+        else {
+            return false;
+        };
+        if !actor.can_power_attack(weapon) {
+            return false;
+        }
+
+        let target_ac = target.effective_armor_class(state)
+            + state
+                .encounter
+                .cover_between(actor.id, target.id)
+                .ac_bonus();
+        let settings =
+            state.attack_roll_settings(actor, target, weapon.is_melee(), Default::default());
+        heuristic.should_power_attack(target_ac, settings.advantage)
+    }
+
+    /// The ally who would get the most out of being helped against `target`, if helping them is
+    /// worth more than attacking: their best hit has to outdamage the actor's own.
+    fn help_candidate(&self, actor: &Actor, target: ActorId, state: &State) -> Option<ActorId> {
+        let own_damage = expected_attack_damage(actor, state);
+        state
+            .allies_of(actor.id)?
+            .into_iter()
+            .filter_map(|ally| state.get_actor(ally))
+            .filter(|ally| ally.is_alive() && !ally.conditions.is_helped_against(target))
+            .map(|ally| (ally.id, expected_attack_damage(ally, state)))
+            .filter(|(_, damage)| *damage > own_damage)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(ally, _)| ally)
+    }
+
     pub fn take_action(
         &self,
         action_economy_usage: ActionEconomyUsage,
         actor: ActorId,
         state: &State,
         rng: &mut Roller,
+    ) -> anyhow::Result<ActionTaken> {
+        self.take_action_audited(action_economy_usage, actor, state, rng, None)
+    }
+
+    /// Chooses an action like [`Policy::take_action`], recording the targets and actions
+    /// considered into `audit`. Rolls the same dice either way.
+    pub fn take_action_audited(
+        &self,
+        action_economy_usage: ActionEconomyUsage,
+        actor: ActorId,
+        state: &State,
+        rng: &mut Roller,
+        mut audit: Option<&mut DecisionAudit>,
     ) -> anyhow::Result<ActionTaken> {
         if action_economy_usage != ActionEconomyUsage::Action {
             return Ok(ActionTaken {
@@ -108,58 +483,181 @@ impl Policy {
 
         let mut target_weights = vec![];
         for enemy in enemies {
-            let weight = self
-                .target_weights
-                .iter()
-                .find(|(id, _)| *id == enemy)
-                .map(|(_, weight)| *weight)
-                .unwrap_or(1);
+            let weight = self.target_weight(enemy, state);
             target_weights.push((enemy, weight));
         }
-        let target_table = WeightedProbability::new(target_weights);
+        if let Some(audit) = audit.as_deref_mut() {
+            audit.targets = target_weights.clone();
+        }
+        // every target weighted at 0 means there's no one the actor is willing to go after
+        let Some(target_table) = WeightedProbability::new(target_weights) else {
+            return Ok(ActionTaken {
+                actor,
+                action: Action::Wait,
+                action_economy_usage,
+            });
+        };
         let target = *target_table.sample(rng.rng());
+        if let Some(audit) = audit.as_deref_mut() {
+            audit.target = Some(target);
+        }
 
         let actor = state.get_actor(actor).unwrap();
+        let target_actor = state.get_actor(target).unwrap();
+        let can_grapple_or_shove = actor.size.can_grapple_or_shove(target_actor.size);
+        let legal: Vec<Action> = state
+            .possible_actions(actor.id, action_economy_usage)
+            .into_iter()
+            .filter(|action| action.target().is_none_or(|aimed_at| aimed_at == target))
+            .collect();
+        let legal_types: BTreeSet<ActionType> = legal.iter().map(Action::action_type).collect();
 
-        let mut weapon_used = None;
-        for item_id in actor.inventory.items.keys() {
-            if let Some(item) = state.items.get(item_id)
-                && let ItemInner::Weapon(_) = &item.inner
+        // the first weapon that reaches the target from here, or else the first one that can once
+        // the actor moves
+        let has_weapon = actor.inventory.items.keys().any(|item_id| {
+            matches!(
+                state.items.get(item_id).map(|item| &item.inner),
+                Some(ItemInner::Weapon(_))
+            )
+        });
+        let attacks: Vec<&Action> = legal
+            .iter()
+            .filter(|action| matches!(action, Action::Attack(_)))
+            .collect();
+        let weapon_used = attacks
+            .iter()
+            .find(|action| state.in_reach(actor.id, action))
+            .or(attacks.first())
+            .and_then(|action| match action {
+                Action::Attack(attack) => Some(attack.weapon_used),
+                _ => None,
+            });
+
+        let help_ally = self
+            .action_weights
+            .iter()
+            .any(|(action_type, _)| *action_type == ActionType::Help)
+            .then(|| self.help_candidate(actor, target, state))
+            .flatten()
+            .filter(|&ally| legal.contains(&Action::Help(HelpAction { ally, target })));
+
+        // recharge abilities come first, since they're usually the strongest thing on the stat block
+        let abilities: Vec<usize> = legal
+            .iter()
+            .filter_map(|action| match action {
+                Action::UseAbility(use_ability) => Some(use_ability.ability),
+                _ => None,
+            })
+            .collect();
+        let ability = abilities
+            .iter()
+            .find(|&&index| actor.abilities[index].recharge.is_some())
+            .or(abilities.first())
+            .copied();
+        let recharge_ready = ability.is_some_and(|index| actor.abilities[index].recharge.is_some());
+
+        let economy_spent = !actor.action_economy.can_take_action(action_economy_usage);
+        let rejection = |action_type: ActionType| {
+            let reason = match action_type {
+                ActionType::Attack if !has_weapon => Some(RejectionReason::NoWeapon),
+                ActionType::Attack | ActionType::UnarmedStrike => None,
+                ActionType::Grapple | ActionType::Shove if !can_grapple_or_shove => {
+                    Some(RejectionReason::TargetTooLarge)
+                }
+                ActionType::Grapple
+                    if target_actor
+                        .conditions
+                        .has(Condition::Grappled { by: actor.id }) =>
+                {
+                    Some(RejectionReason::AlreadyGrappling)
+                }
+                ActionType::Shove if target_actor.conditions.is_prone() => {
+                    Some(RejectionReason::TargetProne)
+                }
+                ActionType::Grapple | ActionType::Shove => None,
+                ActionType::EscapeGrapple if !actor.conditions.is_grappled() => {
+                    Some(RejectionReason::NotGrappled)
+                }
+                ActionType::EscapeGrapple => None,
+                ActionType::Help if help_ally.is_none() => Some(RejectionReason::NoAllyToHelp),
+                ActionType::Help => None,
+                ActionType::UseAbility if ability.is_none() => {
+                    Some(RejectionReason::NoAbilityReady)
+                }
+                ActionType::UseAbility => None,
+                _ => Some(RejectionReason::Unsupported),
+            };
+            reason.or_else(|| {
+                (!legal_types.contains(&action_type)).then_some(match economy_spent {
+                    true => RejectionReason::ActionEconomySpent,
+                    false => RejectionReason::OutOfReach,
+                })
+            })
+        };
+        let mut weights = self.action_weights.clone();
+        if let Some(priority) = self.recharge_priority
+            && recharge_ready
+        {
+            match weights
+                .iter_mut()
+                .find(|(action_type, _)| *action_type == ActionType::UseAbility)
             {
-                weapon_used = Some(*item_id);
-                break;
+                Some((_, weight)) => *weight = weight.saturating_add(priority),
+                None => weights.push((ActionType::UseAbility, priority)),
             }
         }
-
-        let mut action_weights = self.action_weights.clone();
-        let possible_actions = state.possible_actions(actor.id);
-        action_weights.retain(|(action_type_candidate, _)| match action_type_candidate {
-            ActionType::Attack => weapon_used.is_some(),
-            ActionType::UnarmedStrike => true,
-            _ => false,
-        });
-        action_weights
-            .retain(|(action_type_candidate, _)| possible_actions.contains(action_type_candidate));
-        if action_weights.is_empty() {
+        let mut action_weights = Vec::with_capacity(weights.len());
+        for (action_type, weight) in weights {
+            let rejected = rejection(action_type);
+            if let Some(audit) = audit.as_deref_mut() {
+                audit.candidates.push(CandidateAction {
+                    action_type,
+                    weight,
+                    rejected,
+                });
+            }
+            if rejected.is_none() {
+                action_weights.push((action_type, weight));
+            }
+        }
+        let Some(action_table) = WeightedProbability::new(action_weights) else {
             return Ok(ActionTaken {
                 actor: actor.id,
                 action: Action::Wait,
                 action_economy_usage,
             });
-        }
-        let action_table = WeightedProbability::new(action_weights);
+        };
         let action_type = action_table.sample(rng.rng());
+        if let Some(audit) = audit {
+            audit.chosen = Some(*action_type);
+        }
 
         let action = match action_type {
             ActionType::Wait => Action::Wait,
-            ActionType::Attack => Action::Attack(AttackAction {
-                weapon_used: weapon_used.unwrap(),
+            ActionType::Attack => {
+                let weapon_used = weapon_used.unwrap();
+                Action::Attack(AttackAction {
+                    weapon_used,
+                    target,
+                    attack_roll_settings: Default::default(),
+                    power_attack: self.should_power_attack(actor, target_actor, weapon_used, state),
+                })
+            }
+            ActionType::UnarmedStrike => Action::UnarmedStrike(UnarmedStrikeAction {
                 target,
                 attack_roll_settings: Default::default(),
             }),
-            ActionType::UnarmedStrike => Action::UnarmedStrike(UnarmedStrikeAction {
+            ActionType::Grapple => Action::Grapple(GrappleAction { target }),
+            ActionType::Shove => Action::Shove(ShoveAction { target }),
+            ActionType::EscapeGrapple => Action::EscapeGrapple,
+            ActionType::Help => Action::Help(HelpAction {
+                ally: help_ally.unwrap(),
+                target,
+            }),
+            ActionType::UseAbility => Action::UseAbility(UseAbilityAction {
+                actor: actor.id,
+                ability: ability.unwrap(),
                 target,
-                attack_roll_settings: Default::default(),
             }),
             _ => Action::Wait, // placeholder for other actions
         };
@@ -171,3 +669,362 @@ impl Policy {
         })
     }
 }
+
+/// Average damage of the actor's hardest-hitting attack, on a hit.
+fn expected_attack_damage(actor: &Actor, state: &State) -> f64 {
+    actor
+        .inventory
+        .items
+        .keys()
+        .filter_map(
+            |item_id| match state.items.get(item_id).map(|item| &item.inner) {
+                Some(ItemInner::Weapon(weapon)) => {
+                    Some(actor.plan_weapon_damage(weapon, false).average())
+                }
+                _ => None,
+            },
+        )
+        .fold(actor.plan_unarmed_strike_damage().average(), f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{
+        actor::ActorBuilder,
+        cover::Cover,
+        features::Feature,
+        items::{WeaponBuilder, WeaponType},
+    };
+
+    fn takes_power_attack(policy: &Policy, actor: ActorId, state: &State) -> bool {
+        let mut roller = Roller::test_rng();
+        match policy
+            .take_action(ActionEconomyUsage::Action, actor, state, &mut roller)
+            .unwrap()
+            .action
+        {
+            Action::Attack(attack) => attack.power_attack,
+            action => panic!("expected an attack, got {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_power_attack_heuristic() {
+        let mut state = State::new();
+        let greatsword = state.add_item(
+            "Greatsword",
+            ItemInner::Weapon(WeaponBuilder::new(WeaponType::Greatsword).build()),
+        );
+        let mut fighter = ActorBuilder::new("Fighter")
+            .feature(Feature::GreatWeaponMaster)
+            .build();
+        fighter.give_item(greatsword, 1);
+        let fighter = state.add_actor(fighter);
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+
+        let low_ac_only = PolicyBuilder::new()
+            .action_weight(ActionType::Attack, 1)
+            .power_attack(PowerAttackHeuristic {
+                max_target_ac: Some(12),
+                with_advantage: false,
+            })
+            .build();
+        let advantage_only = PolicyBuilder::new()
+            .action_weight(ActionType::Attack, 1)
+            .power_attack(PowerAttackHeuristic {
+                max_target_ac: None,
+                with_advantage: true,
+            })
+            .build();
+
+        assert!(takes_power_attack(&low_ac_only, fighter, &state));
+        assert!(!takes_power_attack(&advantage_only, fighter, &state));
+
+        // three-quarters cover raises the goblin's AC from 10 to 15
+        state
+            .encounter
+            .set_cover(fighter, goblin, Cover::ThreeQuarters);
+        assert!(!takes_power_attack(&low_ac_only, fighter, &state));
+
+        state
+            .encounter
+            .set_attacker_advantage_override(fighter, Some(Advantage::Advantage));
+        assert!(takes_power_attack(&advantage_only, fighter, &state));
+    }
+
+    #[test]
+    fn test_power_attack_needs_feat_and_weapon() {
+        let always = PolicyBuilder::new()
+            .action_weight(ActionType::Attack, 1)
+            .power_attack(PowerAttackHeuristic::always())
+            .build();
+        let takes_with = |features: &[Feature], weapon: WeaponBuilder| {
+            let mut state = State::new();
+            let weapon = state.add_item("Weapon", ItemInner::Weapon(weapon.build()));
+            let mut fighter = ActorBuilder::new("Fighter");
+            for &feature in features {
+                fighter = fighter.feature(feature);
+            }
+            let mut fighter = fighter.build();
+            fighter.give_item(weapon, 1);
+            let fighter = state.add_actor(fighter);
+            state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+            takes_power_attack(&always, fighter, &state)
+        };
+        let both = [Feature::GreatWeaponMaster, Feature::Sharpshooter];
+
+        assert!(takes_with(&both, WeaponBuilder::new(WeaponType::Maul)));
+        assert!(takes_with(
+            &both,
+            WeaponBuilder::new(WeaponType::Longbow).range(150)
+        ));
+        // light and finesse weapons never qualify, thrown or not
+        for weapon_type in [
+            WeaponType::Dagger,
+            WeaponType::Rapier,
+            WeaponType::Scimitar,
+            WeaponType::Shortsword,
+            WeaponType::Handaxe,
+        ] {
+            assert!(!takes_with(&both, WeaponBuilder::new(weapon_type)));
+            assert!(!takes_with(
+                &both,
+                WeaponBuilder::new(weapon_type).range(20)
+            ));
+        }
+        // nor does a heavy weapon without Great Weapon Master, or a bow without Sharpshooter
+        assert!(!takes_with(
+            &[Feature::Sharpshooter],
+            WeaponBuilder::new(WeaponType::Maul)
+        ));
+        assert!(!takes_with(
+            &[Feature::GreatWeaponMaster],
+            WeaponBuilder::new(WeaponType::Longbow).range(150)
+        ));
+    }
+
+    #[test]
+    fn test_help_ally_with_bigger_hits() {
+        let mut state = State::new();
+        let greatsword = state.add_item(
+            "Greatsword",
+            ItemInner::Weapon(
+                WeaponBuilder::new(WeaponType::Greatsword)
+                    .damage("2d6+4")
+                    .build(),
+            ),
+        );
+        let bard = state.add_actor(ActorBuilder::new("Bard").build());
+        let mut barbarian = ActorBuilder::new("Barbarian").build();
+        barbarian.give_item(greatsword, 1);
+        let barbarian = state.add_actor(barbarian);
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+
+        let policy = PolicyBuilder::new()
+            .action_weight(ActionType::Help, 1)
+            .build();
+        let mut roller = Roller::test_rng();
+
+        let action = policy
+            .take_action(ActionEconomyUsage::Action, bard, &state, &mut roller)
+            .unwrap()
+            .action;
+        assert_eq!(
+            action,
+            Action::Help(HelpAction {
+                ally: barbarian,
+                target: goblin,
+            })
+        );
+
+        // the barbarian has nobody worth helping
+        let action = policy
+            .take_action(ActionEconomyUsage::Action, barbarian, &state, &mut roller)
+            .unwrap()
+            .action;
+        assert_eq!(action, Action::Wait);
+    }
+
+    #[test]
+    fn test_zero_and_negative_weights() {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).tag("minion").build());
+        let take_action = |policy: &Policy| {
+            policy
+                .take_action(
+                    ActionEconomyUsage::Action,
+                    hero,
+                    &state,
+                    &mut Roller::test_rng(),
+                )
+                .unwrap()
+                .action
+        };
+
+        // nothing worth targeting or doing means waiting, not a panic
+        let shunned = PolicyBuilder::new()
+            .action_weight(ActionType::UnarmedStrike, 1)
+            .target_weight(goblin, 0)
+            .build();
+        assert_eq!(take_action(&shunned), Action::Wait);
+        let idle = PolicyBuilder::new()
+            .action_weight(ActionType::UnarmedStrike, 0)
+            .build();
+        assert_eq!(take_action(&idle), Action::Wait);
+
+        // negative weights are refused up front, and count as 0 if they slip through
+        let negative = PolicyBuilder::new()
+            .action_weight(ActionType::UnarmedStrike, 1)
+            .tag_weight("minion", -3)
+            .build();
+        assert_eq!(
+            negative.validate().unwrap_err().to_string(),
+            "Tag minion has a negative target weight of -3"
+        );
+        assert_eq!(take_action(&negative), Action::Wait);
+    }
+
+    #[test]
+    fn test_decision_audit() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let monk = state.add_actor(ActorBuilder::new("Monk").build());
+        let giant = state.add_actor(
+            ActorBuilder::new("Giant")
+                .group(1)
+                .size(crate::rules::size::Size::Huge)
+                .build(),
+        );
+        let policy = PolicyBuilder::new()
+            .action_weight(ActionType::Attack, 10)
+            .action_weight(ActionType::Grapple, 5)
+            .action_weight(ActionType::EscapeGrapple, 5)
+            .action_weight(ActionType::UnarmedStrike, 1)
+            .target_weight(giant, 3)
+            .build();
+
+        let mut audit = DecisionAudit::new(monk, ActionEconomyUsage::Action);
+        let audited = policy.take_action_audited(
+            ActionEconomyUsage::Action,
+            monk,
+            &state,
+            &mut Roller::test_rng(),
+            Some(&mut audit),
+        )?;
+        let plain = policy.take_action(
+            ActionEconomyUsage::Action,
+            monk,
+            &state,
+            &mut Roller::test_rng(),
+        )?;
+        assert_eq!(audited, plain);
+
+        assert_eq!(audit.targets, vec![(giant, 3)]);
+        assert_eq!(audit.target, Some(giant));
+        assert_eq!(audit.chosen, Some(ActionType::UnarmedStrike));
+        let rejections: Vec<_> = audit
+            .candidates
+            .iter()
+            .map(|candidate| (candidate.action_type, candidate.rejected))
+            .collect();
+        assert_eq!(
+            rejections,
+            vec![
+                (ActionType::Attack, Some(RejectionReason::NoWeapon)),
+                (ActionType::Grapple, Some(RejectionReason::TargetTooLarge)),
+                (
+                    ActionType::EscapeGrapple,
+                    Some(RejectionReason::NotGrappled)
+                ),
+                (ActionType::UnarmedStrike, None),
+            ]
+        );
+
+        let mut line = String::new();
+        audit.pretty_print(&mut line, &state)?;
+        assert!(line.contains("Attack: 10 (no weapon)"), "{}", line);
+
+        // with the action spent, everything is ruled out
+        state
+            .get_actor_mut(monk)
+            .unwrap()
+            .action_economy
+            .use_action(ActionEconomyUsage::Action)?;
+        let mut audit = DecisionAudit::new(monk, ActionEconomyUsage::Action);
+        policy.take_action_audited(
+            ActionEconomyUsage::Action,
+            monk,
+            &state,
+            &mut Roller::test_rng(),
+            Some(&mut audit),
+        )?;
+        assert_eq!(audit.chosen, None);
+        assert_eq!(
+            audit.candidates[3].rejected,
+            Some(RejectionReason::ActionEconomySpent)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_round_trip() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        let policy = PolicyBuilder::new()
+            .action_weight(ActionType::Attack, 3)
+            .action_weight(ActionType::Dodge, 1)
+            .target_weight(goblin, 5)
+            .tag_weight("caster", 4)
+            .power_attack(PowerAttackHeuristic::always())
+            .recharge_priority(10)
+            .rerolls(RerollHeuristic {
+                attack_target_max_health: Some(8),
+                saving_throws: true,
+                ability_checks: false,
+            })
+            .build();
+        state.set_actor_policy(hero, policy.clone());
+
+        let loaded = State::from_json(&serde_json::to_string(&state)?)?;
+        assert_eq!(loaded.get_actor(hero).unwrap().policy, policy);
+        // the goblin has no policy to act on at all, and is loaded without one all the same
+        assert!(loaded.get_actor(goblin).unwrap().policy.is_empty());
+        assert_eq!(loaded, state);
+
+        // the shape documented on `Policy`, which external tools write by hand
+        let documented: Policy = serde_json::from_str(&format!(
+            r#"{{
+                "action_weights": [["Attack", 3], ["Dodge", 1]],
+                "target_weights": [[{}, 5]],
+                "tag_weights": [["caster", 4]],
+                "power_attack": {{ "max_target_ac": 2147483647, "with_advantage": true }},
+                "recharge_priority": 10,
+                "rerolls": {{ "attack_target_max_health": 8, "saving_throws": true }}
+            }}"#,
+            goblin.0
+        ))?;
+        assert_eq!(documented, policy);
+        assert_eq!(serde_json::from_str::<Policy>("{}")?, Policy::default());
+
+        // an actor saved without a policy is loaded with an empty one, and only filled in as an
+        // attacker on request, when its group has no policy for it either
+        let mut json = serde_json::to_value(&state)?;
+        json["actors"][hero.0.to_string()]
+            .as_object_mut()
+            .unwrap()
+            .remove("policy");
+        let mut loaded = State::from_json_value(json.clone())?;
+        assert_eq!(loaded.get_actor(hero).unwrap().policy, Policy::default());
+        loaded.fill_missing_policies();
+        assert_eq!(loaded.get_actor(hero).unwrap().policy, Policy::attacker());
+        json["group_policies"] = serde_json::json!({ "0": policy });
+        let mut loaded = State::from_json_value(json)?;
+        loaded.fill_missing_policies();
+        assert_eq!(loaded.get_actor(hero).unwrap().policy, Policy::default());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,459 @@
+//! Where actors stand relative to each other, and how they close the gap.
+//!
+//! The rest of the simulation asks a [`Positioning`] backend rather than reading positions
+//! directly, so every backend shares the same reach, targeting, and movement logic. There are two:
+//!
+//! - [`SideDistance`], the default: a single distance between the two sides, starting at
+//!   [`EncounterSetup::engagement_distance`](crate::simulation::encounter::EncounterSetup::engagement_distance).
+//! - [`ZoneMap`], theater-of-the-mind style: a handful of named zones, each actor standing in one,
+//!   with weapons reaching a [`RangeBand`] rather than a distance in feet.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use derive_more::{From, Into};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::actor::ActorId,
+    simulation::{
+        environment::{EffectArea, Hazard},
+        objectives::Objective,
+        state::State,
+        transition::Transition,
+    },
+};
+
+pub trait Positioning {
+    /// How far apart two actors are in feet, or `None` if it isn't tracked.
+    fn distance(&self, state: &State, from: ActorId, to: ActorId) -> Option<u32>;
+
+    /// Whether `actor` can reach `target` from where it stands with something that reaches
+    /// `reach` feet.
+    fn in_reach(&self, state: &State, actor: ActorId, target: ActorId, reach: u32) -> bool;
+
+    /// The move `actor` makes this turn toward `target`, or `None` if it's already within `reach`
+    /// or can't move. Fails if there's no way to get there at all.
+    fn approach(
+        &self,
+        state: &State,
+        actor: ActorId,
+        target: ActorId,
+        reach: u32,
+    ) -> anyhow::Result<Option<Transition>>;
+}
+
+/// The two sides start some distance apart, and whoever moves closes it for everyone.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SideDistance;
+
+impl Positioning for SideDistance {
+    fn distance(&self, state: &State, _from: ActorId, _to: ActorId) -> Option<u32> {
+        state.distance
+    }
+
+    fn in_reach(&self, state: &State, _actor: ActorId, _target: ActorId, reach: u32) -> bool {
+        state.distance.is_none_or(|distance| distance <= reach)
+    }
+
+    fn approach(
+        &self,
+        state: &State,
+        actor: ActorId,
+        _target: ActorId,
+        reach: u32,
+    ) -> anyhow::Result<Option<Transition>> {
+        let Some(distance) = state.distance else {
+            return Ok(None);
+        };
+        let speed = state.effective_speed(
+            state
+                .get_actor(actor)
+                .ok_or_else(|| anyhow::anyhow!("Actor not found in simulation state"))?,
+        );
+        let feet = distance.saturating_sub(reach).min(speed);
+        Ok((feet > 0).then_some(Transition::DistanceClosed { actor, feet }))
+    }
+}
+
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    From,
+    Into,
+)]
+pub struct ZoneId(pub u32);
+
+/// How far something reaches, or how far apart two actors are, in zones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum RangeBand {
+    /// The same zone.
+    Engaged,
+    /// An adjacent zone.
+    Near,
+    /// Two or more zones away.
+    Far,
+}
+
+impl RangeBand {
+    /// The band a weapon or action reaching `reach` feet can strike into. Melee and reach weapons
+    /// stay in their own zone, thrown weapons and short-range shots reach the next one over, and
+    /// anything longer reaches every zone.
+    pub fn of_reach(reach: u32) -> RangeBand {
+        match reach {
+            0..=10 => RangeBand::Engaged,
+            11..=60 => RangeBand::Near,
+            _ => RangeBand::Far,
+        }
+    }
+
+    pub fn of_steps(steps: u32) -> RangeBand {
+        match steps {
+            0 => RangeBand::Engaged,
+            1 => RangeBand::Near,
+            _ => RangeBand::Far,
+        }
+    }
+}
+
+/// A battlefield split into a few named zones. Actors in the same zone are in melee, and moving
+/// takes an actor one zone along the way to its target per turn, or two turns if the zone is
+/// difficult terrain.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ZoneMap {
+    /// Zone names, indexed by [`ZoneId`].
+    pub zones: Vec<String>,
+    /// Pairs of zones an actor can move directly between, in either direction. Zones that aren't
+    /// connected are walled off from each other.
+    #[serde(default)]
+    pub connections: BTreeSet<(ZoneId, ZoneId)>,
+    /// Where each actor starts. Anyone not listed starts in the first zone.
+    #[serde(default)]
+    pub starting_zones: BTreeMap<ActorId, ZoneId>,
+    /// Zones of difficult terrain, which take an extra turn to enter.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub difficult: BTreeSet<ZoneId>,
+    /// What hurts whoever moves into a zone.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hazards: BTreeMap<ZoneId, Hazard>,
+}
+
+impl ZoneMap {
+    /// Rough width of a zone in feet, for senses like darkvision that need a distance.
+    pub const ZONE_WIDTH: u32 = 30;
+
+    pub fn add_zone(&mut self, name: &str) -> ZoneId {
+        self.zones.push(name.to_string());
+        ZoneId(self.zones.len() as u32 - 1)
+    }
+
+    pub fn connect(&mut self, a: ZoneId, b: ZoneId) {
+        if a != b {
+            self.connections.insert((a.min(b), a.max(b)));
+        }
+    }
+
+    pub fn are_adjacent(&self, a: ZoneId, b: ZoneId) -> bool {
+        self.connections.contains(&(a.min(b), a.max(b)))
+    }
+
+    pub fn name(&self, zone: ZoneId) -> Option<&str> {
+        self.zones.get(zone.0 as usize).map(String::as_str)
+    }
+
+    pub fn starting_zone(&self, actor: ActorId) -> ZoneId {
+        self.starting_zones.get(&actor).copied().unwrap_or_default()
+    }
+
+    fn neighbors(&self, zone: ZoneId) -> impl Iterator<Item = ZoneId> + '_ {
+        self.connections.iter().filter_map(move |&(a, b)| {
+            if a == zone {
+                Some(b)
+            } else if b == zone {
+                Some(a)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The shortest route from `from` to `to`, not counting `from` itself, or `None` if there's
+    /// no way through.
+    pub fn path(&self, from: ZoneId, to: ZoneId) -> Option<Vec<ZoneId>> {
+        let mut came_from = BTreeMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(zone) = queue.pop_front() {
+            if zone == to {
+                let mut path = Vec::new();
+                let mut zone = to;
+                while zone != from {
+                    path.push(zone);
+                    zone = came_from[&zone];
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.neighbors(zone) {
+                if next != from && !came_from.contains_key(&next) {
+                    came_from.insert(next, zone);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Checks that every zone id refers to a zone, that every hazard's effects compile, and that
+    /// every pair of hostile actors has a way between their starting zones, so no one is stuck out
+    /// of reach of the fight.
+    pub fn validate(&self, state: &State) -> anyhow::Result<()> {
+        let check = |zone: ZoneId, what: &dyn std::fmt::Display| {
+            anyhow::ensure!(
+                self.name(zone).is_some(),
+                "{} refers to unknown zone {}",
+                what,
+                zone.0
+            );
+            Ok(())
+        };
+        for &(a, b) in &self.connections {
+            check(a, &"A zone connection")?;
+            check(b, &"A zone connection")?;
+        }
+        for &zone in &self.difficult {
+            check(zone, &"A difficult terrain zone")?;
+        }
+        for (&zone, hazard) in &self.hazards {
+            check(zone, &hazard.name)?;
+            hazard
+                .compile()
+                .map_err(|e| anyhow::anyhow!("Invalid hazard {}: {}", hazard.name, e))?;
+        }
+        for actor in state.actors.values() {
+            check(self.starting_zone(actor.id), &actor.name)?;
+        }
+        for actor in state.actors.values() {
+            for enemy in state.actors.values() {
+                if actor.group >= enemy.group {
+                    continue;
+                }
+                let (from, to) = (self.starting_zone(actor.id), self.starting_zone(enemy.id));
+                anyhow::ensure!(
+                    self.path(from, to).is_some(),
+                    "No path between {} in {} and {} in {}",
+                    actor.name,
+                    self.zones[from.0 as usize],
+                    enemy.name,
+                    self.zones[to.0 as usize]
+                );
+            }
+        }
+        for effect in &state.encounter.environment {
+            if let EffectArea::Zone(zone) = effect.area {
+                check(zone, &effect.name)?;
+            }
+        }
+        for objective in &state.encounter.objectives {
+            if let Objective::ReachZone { zone, .. } = *objective {
+                check(zone, &"A ReachZone objective")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// How far apart two actors are, or `None` if either isn't in a zone or there's no way
+    /// between their zones.
+    pub fn band_between(&self, state: &State, a: ActorId, b: ActorId) -> Option<RangeBand> {
+        let (a, b) = (state.zone_of(a)?, state.zone_of(b)?);
+        let steps = self.path(a, b)?.len() as u32;
+        Some(RangeBand::of_steps(steps))
+    }
+}
+
+impl Positioning for ZoneMap {
+    fn distance(&self, state: &State, from: ActorId, to: ActorId) -> Option<u32> {
+        let (a, b) = (state.zone_of(from)?, state.zone_of(to)?);
+        match self.path(a, b)?.len() as u32 {
+            0 => Some(5),
+            steps => Some(steps * Self::ZONE_WIDTH),
+        }
+    }
+
+    fn in_reach(&self, state: &State, actor: ActorId, target: ActorId, reach: u32) -> bool {
+        if state.zone_of(actor).is_none() || state.zone_of(target).is_none() {
+            return true;
+        }
+        self.band_between(state, actor, target)
+            .is_some_and(|band| band <= RangeBand::of_reach(reach))
+    }
+
+    fn approach(
+        &self,
+        state: &State,
+        actor: ActorId,
+        target: ActorId,
+        reach: u32,
+    ) -> anyhow::Result<Option<Transition>> {
+        let mover = state
+            .get_actor(actor)
+            .ok_or_else(|| anyhow::anyhow!("Actor not found in simulation state"))?;
+        if self.in_reach(state, actor, target, reach) || state.effective_speed(mover) == 0 {
+            return Ok(None);
+        }
+        let (Some(from), Some(to)) = (state.zone_of(actor), state.zone_of(target)) else {
+            return Ok(None);
+        };
+        let path = self.path(from, to).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No path from zone {} to zone {} for {}",
+                from.0,
+                to.0,
+                mover.name
+            )
+        })?;
+        let Some(&zone) = path.first() else {
+            return Ok(None);
+        };
+        if self.difficult.contains(&zone) && state.zone_progress.get(&actor) != Some(&zone) {
+            return Ok(Some(Transition::ZoneApproached { actor, zone }));
+        }
+        Ok(Some(Transition::ZoneEntered { actor, zone }))
+    }
+}
+
+impl State {
+    /// The positioning backend this encounter uses.
+    pub fn positioning(&self) -> &dyn Positioning {
+        match &self.encounter.zones {
+            Some(zones) => zones,
+            None => &SideDistance,
+        }
+    }
+
+    pub fn zone_of(&self, actor: ActorId) -> Option<ZoneId> {
+        self.actor_zones.get(&actor).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::actor::ActorBuilder;
+
+    #[test]
+    fn test_zone_paths() {
+        let mut map = ZoneMap::default();
+        let gate = map.add_zone("Gate");
+        let courtyard = map.add_zone("Courtyard");
+        let keep = map.add_zone("Keep");
+        let tower = map.add_zone("Tower");
+        map.connect(gate, courtyard);
+        map.connect(keep, courtyard);
+        assert!(map.are_adjacent(courtyard, gate));
+        assert_eq!(map.path(gate, keep), Some(vec![courtyard, keep]));
+        assert_eq!(map.path(keep, keep), Some(vec![]));
+        assert_eq!(map.path(gate, tower), None);
+
+        let mut state = State::new();
+        let knight = state.add_actor(ActorBuilder::new("Knight").build());
+        let archer = state.add_actor(ActorBuilder::new("Archer").group(1).build());
+        map.starting_zones.insert(archer, keep);
+        state.encounter.zones = Some(map);
+        Transition::BeginCombat.apply(&mut state).unwrap();
+        assert_eq!(state.zone_of(knight), Some(gate));
+
+        let positioning = state.positioning();
+        assert_eq!(positioning.distance(&state, knight, archer), Some(60));
+        assert!(!positioning.in_reach(&state, knight, archer, 5));
+        assert!(!positioning.in_reach(&state, knight, archer, 30));
+        assert!(positioning.in_reach(&state, archer, knight, 150));
+        assert_eq!(
+            positioning.approach(&state, knight, archer, 5).unwrap(),
+            Some(Transition::ZoneEntered {
+                actor: knight,
+                zone: courtyard
+            })
+        );
+        state.validate().unwrap();
+
+        // cut off in the tower, the archer can't be reached, and loading says so
+        let zones = state.encounter.zones.as_mut().unwrap();
+        zones.starting_zones.insert(archer, tower);
+        assert!(state.validate().is_err());
+        Transition::BeginCombat.apply(&mut state).unwrap();
+        assert!(
+            state
+                .positioning()
+                .approach(&state, knight, archer, 5)
+                .is_err()
+        );
+
+        let zones = state.encounter.zones.as_mut().unwrap();
+        zones.starting_zones.insert(archer, ZoneId(7));
+        assert!(state.validate().is_err());
+    }
+
+    #[test]
+    fn test_zone_terrain_from_json() -> anyhow::Result<()> {
+        let json = r#"{
+            "zones": ["Gate", "Moat", "Keep"],
+            "connections": [[0, 1], [1, 2]],
+            "difficult": [1],
+            "hazards": { "2": { "name": "Burning oil", "effects": { "damage": "2d6 fire" } } }
+        }"#;
+        let map: ZoneMap = serde_json::from_str(json)?;
+        assert!(map.difficult.contains(&ZoneId(1)));
+        assert_eq!(map.hazards[&ZoneId(2)].compile()?.len(), 1);
+        assert_eq!(
+            serde_json::from_value::<ZoneMap>(serde_json::to_value(&map)?)?,
+            map
+        );
+
+        let mut state = State::new();
+        let knight = state.add_actor(ActorBuilder::new("Knight").build());
+        let archer = state.add_actor(ActorBuilder::new("Archer").group(1).build());
+        let mut map = map;
+        map.starting_zones.insert(archer, ZoneId(2));
+        state.encounter.zones = Some(map);
+        state.validate()?;
+        Transition::BeginCombat.apply(&mut state)?;
+
+        // wading into the moat takes a turn before getting across
+        let approach = |state: &State| state.positioning().approach(state, knight, archer, 5);
+        let wading = approach(&state)?.unwrap();
+        assert_eq!(
+            wading,
+            Transition::ZoneApproached {
+                actor: knight,
+                zone: ZoneId(1)
+            }
+        );
+        wading.apply(&mut state)?;
+        assert_eq!(state.zone_of(knight), Some(ZoneId(0)));
+        assert_eq!(
+            approach(&state)?,
+            Some(Transition::ZoneEntered {
+                actor: knight,
+                zone: ZoneId(1)
+            })
+        );
+
+        let zones = state.encounter.zones.as_mut().unwrap();
+        zones.difficult.insert(ZoneId(7));
+        assert!(state.validate().is_err());
+        let zones = state.encounter.zones.as_mut().unwrap();
+        zones.difficult.remove(&ZoneId(7));
+        zones.hazards.get_mut(&ZoneId(2)).unwrap().effects[0].damage = Some("2d".to_string());
+        assert!(state.validate().is_err());
+
+        Ok(())
+    }
+}
@@ -1,4 +1,21 @@
-use crate::simulation::{state::State, state_tree::StateTree};
+use std::collections::BTreeMap;
+
+use crate::{
+    rules::{
+        actor::{Actor, ActorId},
+        features::Feature,
+        items::{ItemId, ItemType},
+        rerolls::RerollResource,
+        stats::Stat,
+    },
+    simulation::{
+        objectives::Outcome,
+        state::State,
+        state_tree::{StateTree, TreeMode},
+        transition::Transition,
+    },
+    utils::Ratio,
+};
 
 pub trait Query {
     type Output;
@@ -33,24 +50,341 @@ impl OutcomeConditionProbability {
 }
 
 impl Query for OutcomeConditionProbability {
-    type Output = f64;
+    type Output = Ratio;
 
+    /// The exact number of ending states satisfying the condition, out of every ending state.
     fn query(&self, state_tree: &StateTree) -> anyhow::Result<Self::Output> {
-        let mut condition_hits = 0u64;
-        let mut total_outgoing_hits = 0u64;
-
+        state_tree.ensure_states_kept("Outcome probabilities")?;
+        let mut ratio = Ratio::default();
         state_tree.visit_states(true, |state, hits| {
             if (self.condition)(state) {
-                condition_hits += hits;
+                ratio.numerator += hits;
+            }
+            ratio.denominator += hits;
+            true
+        });
+        Ok(ratio)
+    }
+}
+
+/// A query for how combats ended: the share of them decided each way, by winner and by the
+/// [`Objective`](crate::simulation::objectives::Objective) that decided them.
+pub struct OutcomeProbabilities;
+
+impl Query for OutcomeProbabilities {
+    type Output = BTreeMap<Outcome, Ratio>;
+
+    fn query(&self, state_tree: &StateTree) -> anyhow::Result<Self::Output> {
+        state_tree.ensure_states_kept("Outcome probabilities")?;
+        let mut outcomes: BTreeMap<Outcome, u64> = BTreeMap::new();
+        let mut total_hits = 0u64;
+
+        state_tree.visit_states(true, |state, hits| {
+            if let Some(outcome) = state.outcome {
+                *outcomes.entry(outcome).or_default() += hits;
+            }
+            total_hits += hits;
+            true
+        });
+
+        Ok(outcomes
+            .into_iter()
+            .map(|(outcome, hits)| (outcome, Ratio::new(hits, total_hits)))
+            .collect())
+    }
+}
+
+/// A query for how far boss fights get: the share of combats in which each actor reached each of
+/// its [`Phase`](crate::simulation::phases::Phase)s, keyed by actor and phase index.
+pub struct PhaseProbabilities;
+
+impl Query for PhaseProbabilities {
+    type Output = BTreeMap<(ActorId, usize), Ratio>;
+
+    fn query(&self, state_tree: &StateTree) -> anyhow::Result<Self::Output> {
+        state_tree.ensure_states_kept("Phase probabilities")?;
+        let mut reached: BTreeMap<(ActorId, usize), u64> = BTreeMap::new();
+        let mut total_hits = 0u64;
+
+        for actor in state_tree.initial_state().actors.values() {
+            for phase in 0..actor.phases.len() {
+                reached.insert((actor.id, phase), 0);
+            }
+        }
+        state_tree.visit_states(true, |state, hits| {
+            for actor in state.actors.values() {
+                for phase in 0..actor.phase {
+                    *reached.entry((actor.id, phase)).or_default() += hits;
+                }
             }
-            total_outgoing_hits += hits;
+            total_hits += hits;
             true
         });
 
-        if total_outgoing_hits > 0 {
-            Ok(condition_hits as f64 / total_outgoing_hits as f64)
+        Ok(reached
+            .into_iter()
+            .map(|(key, hits)| (key, Ratio::new(hits, total_hits)))
+            .collect())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HitRate {
+    pub attacks: u64,
+    pub hits: u64,
+    pub crits: u64,
+}
+
+impl HitRate {
+    pub fn hit_rate(&self) -> f64 {
+        if self.attacks > 0 {
+            self.hits as f64 / self.attacks as f64
         } else {
-            Ok(0.0)
+            0.0
+        }
+    }
+
+    pub fn crit_rate(&self) -> f64 {
+        if self.attacks > 0 {
+            self.crits as f64 / self.attacks as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A query that tallies resolved attacks for every attacker/target pair across all simulated combats.
+pub struct AttackHitRates;
+
+impl Query for AttackHitRates {
+    type Output = BTreeMap<(ActorId, ActorId), HitRate>;
+
+    fn query(&self, state_tree: &StateTree) -> anyhow::Result<Self::Output> {
+        anyhow::ensure!(
+            state_tree.mode() == TreeMode::Full,
+            "Hit rates need the full state tree, but this run only kept {:?}",
+            state_tree.mode()
+        );
+        let mut rates: Self::Output = BTreeMap::new();
+
+        for (_, _, edge) in state_tree.edges() {
+            for part in edge.transition.parts() {
+                let Transition::AttackResolved {
+                    attacker,
+                    target,
+                    hit,
+                    crit,
+                } = *part
+                else {
+                    continue;
+                };
+                let hits = edge.hits.get();
+                let rate = rates.entry((attacker, target)).or_default();
+                rate.attacks += hits;
+                if hit {
+                    rate.hits += hits;
+                }
+                if crit {
+                    rate.crits += hits;
+                }
+            }
+        }
+
+        Ok(rates)
+    }
+}
+
+/// A query for the treasure left behind: the average quantity of each item in the loot pool at
+/// the end of a combat. Only actors killed with
+/// [`DeathCleanup::DropLoot`](crate::simulation::encounter::DeathCleanup::DropLoot) drop anything.
+pub struct ItemsRecovered;
+
+impl Query for ItemsRecovered {
+    type Output = BTreeMap<ItemId, f64>;
+
+    fn query(&self, state_tree: &StateTree) -> anyhow::Result<Self::Output> {
+        state_tree.ensure_states_kept("Items recovered")?;
+        let mut recovered: BTreeMap<ItemId, u64> = BTreeMap::new();
+        let mut total_hits = 0u64;
+
+        state_tree.visit_states(true, |state, hits| {
+            for (item, quantity) in state.loot.iter() {
+                *recovered.entry(*item).or_default() += *quantity as u64 * hits;
+            }
+            total_hits += hits;
+            true
+        });
+
+        if total_hits == 0 {
+            return Ok(BTreeMap::new());
+        }
+        Ok(recovered
+            .into_iter()
+            .map(|(item, quantity)| (item, quantity as f64 / total_hits as f64))
+            .collect())
+    }
+}
+
+/// What a group spends, on average, in the combats it wins.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ResourceUsage {
+    /// Combats the group won, out of every combat.
+    pub victories: Ratio,
+    /// Items used up, by item. Items a fallen member dropped count as recovered, not spent.
+    pub items: BTreeMap<ItemId, f64>,
+    pub rerolls: BTreeMap<RerollResource, f64>,
+    /// Once-per-combat features spent, by how many members spent them.
+    pub features: BTreeMap<Feature, f64>,
+    /// HP lost across the group, not counting damage past 0 HP.
+    pub health_lost: f64,
+    /// Hit dice the survivors would spend on a short rest to heal back to full, at the average
+    /// roll. Only members with [`LevelScaling`](crate::rules::scaling::LevelScaling) have hit
+    /// dice, and no one spends more than their level.
+    pub hit_dice: f64,
+}
+
+impl ResourceUsage {
+    pub fn pretty_print(
+        &self,
+        f: &mut impl std::fmt::Write,
+        initial_state: &State,
+    ) -> std::fmt::Result {
+        writeln!(f, "Won {:.1}% of combats", self.victories.to_f64() * 100.0)?;
+        if self.victories.numerator == 0 {
+            return Ok(());
+        }
+        writeln!(f, "Per victory, on average:")?;
+        writeln!(f, "  HP lost: {:.1}", self.health_lost)?;
+        writeln!(f, "  Hit dice to recover: {:.2}", self.hit_dice)?;
+        for (item, quantity) in &self.items {
+            write!(f, "  ")?;
+            item.pretty_print(f, initial_state)?;
+            writeln!(f, " used: {:.2}", quantity)?;
+        }
+        for (resource, count) in &self.rerolls {
+            writeln!(f, "  {:?} spent: {:.2}", resource, count)?;
+        }
+        for (feature, count) in &self.features {
+            writeln!(f, "  {:?} used: {:.2}", feature, count)?;
+        }
+        Ok(())
+    }
+
+    /// Expected items used up of one type, e.g. potions, summed over every item of that type.
+    pub fn items_of_type(&self, initial_state: &State, item_type: ItemType) -> f64 {
+        self.items
+            .iter()
+            .filter(|(item, _)| {
+                initial_state
+                    .items
+                    .get(item)
+                    .is_some_and(|item| item.item_type() == item_type)
+            })
+            .map(|(_, quantity)| quantity)
+            .sum()
+    }
+}
+
+/// A query for the resources a group spends to win, for planning an adventuring day: the
+/// expected items, rerolls, features, HP and hit dice used up, conditioned on the group winning
+/// (someone in the group is standing and no one else is).
+pub struct ResourcesSpent {
+    pub group: u32,
+}
+
+impl ResourcesSpent {
+    fn hit_dice_to_recover(actor: &Actor, health_lost: i32) -> u32 {
+        let Some(scaling) = &actor.scaling else {
+            return 0;
+        };
+        let per_die = ((scaling.hit_die as f64 + 1.0) / 2.0
+            + actor.stat_modifier(Stat::Constitution) as f64)
+            .max(1.0);
+        ((health_lost as f64 / per_die).ceil() as u32).min(actor.level)
+    }
+}
+
+impl Query for ResourcesSpent {
+    type Output = ResourceUsage;
+
+    fn query(&self, state_tree: &StateTree) -> anyhow::Result<Self::Output> {
+        state_tree.ensure_states_kept("Resources spent")?;
+        let initial_state = state_tree.initial_state();
+        let members: Vec<&Actor> = initial_state
+            .actors
+            .values()
+            .filter(|actor| actor.group == self.group)
+            .collect();
+        anyhow::ensure!(!members.is_empty(), "No actors in group {}", self.group);
+
+        let mut usage = ResourceUsage::default();
+        let mut items: BTreeMap<ItemId, u64> = BTreeMap::new();
+        let mut rerolls: BTreeMap<RerollResource, u64> = BTreeMap::new();
+        let mut features: BTreeMap<Feature, u64> = BTreeMap::new();
+        let mut health_lost = 0u64;
+        let mut hit_dice = 0u64;
+        let mut victories = 0u64;
+        let mut total_hits = 0u64;
+
+        state_tree.visit_states(true, |state, hits| {
+            total_hits += hits;
+            if state.winner() != Some(self.group) {
+                return true;
+            }
+            victories += hits;
+
+            let mut spent: BTreeMap<ItemId, u32> = BTreeMap::new();
+            for initial in &members {
+                for (item, quantity) in &initial.inventory.items {
+                    *spent.entry(*item).or_default() += quantity;
+                }
+            }
+            for initial in &members {
+                let Some(actor) = state.get_actor(initial.id) else {
+                    continue;
+                };
+                // what a fallen member dropped wasn't spent, but other actors' loot isn't theirs
+                let dropped = state.dropped.get(&initial.id).into_iter();
+                for (item, quantity) in dropped
+                    .flat_map(|dropped| &dropped.items)
+                    .chain(&actor.inventory.items)
+                {
+                    if let Some(spent) = spent.get_mut(item) {
+                        *spent = spent.saturating_sub(*quantity);
+                    }
+                }
+                for (resource, count) in &initial.rerolls {
+                    let left = actor.rerolls.get(resource).copied().unwrap_or(0);
+                    *rerolls.entry(*resource).or_default() +=
+                        count.saturating_sub(left) as u64 * hits;
+                }
+                for feature in &actor.features_used {
+                    *features.entry(*feature).or_default() += hits;
+                }
+                let lost = (initial.health - actor.health.max(0)).max(0);
+                health_lost += lost as u64 * hits;
+                if !actor.is_dead() {
+                    hit_dice += Self::hit_dice_to_recover(actor, lost) as u64 * hits;
+                }
+            }
+            for (item, spent) in spent {
+                if spent > 0 {
+                    *items.entry(item).or_default() += spent as u64 * hits;
+                }
+            }
+            true
+        });
+
+        usage.victories = Ratio::new(victories, total_hits);
+        if victories == 0 {
+            return Ok(usage);
         }
+        let mean = |total: u64| total as f64 / victories as f64;
+        usage.items = items.into_iter().map(|(k, v)| (k, mean(v))).collect();
+        usage.rerolls = rerolls.into_iter().map(|(k, v)| (k, mean(v))).collect();
+        usage.features = features.into_iter().map(|(k, v)| (k, mean(v))).collect();
+        usage.health_lost = mean(health_lost);
+        usage.hit_dice = mean(hit_dice);
+        Ok(usage)
     }
 }
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Standard normal quantile for the 99.9th percentile, used to flag suspicious dice.
+const Z_999: f64 = 3.090;
+
+/// A tally of every die the [`Roller`](crate::simulation::roller::Roller) rolled, by die size
+/// and face, for checking that the dice are fair.
+///
+/// Faces are counted as rolled, before any minimum or maximum from the roll's settings is
+/// applied. Rerolls that can only land in part of the die's range (see
+/// [`RollSettings::reroll_dice_below`](crate::rules::dice::RollSettings::reroll_dice_below))
+/// aren't fair dice and are left out.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollStatistics {
+    /// die size -> number of times each face came up, starting from 1
+    pub tallies: BTreeMap<u32, Vec<u64>>,
+}
+
+/// The empirical distribution of one die size, with a chi-square test against a fair die.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DieStatistics {
+    pub die_size: u32,
+    pub rolls: u64,
+    /// Number of times each face came up, starting from 1.
+    pub counts: Vec<u64>,
+    /// Pearson's chi-square statistic against a uniform distribution over the faces.
+    pub chi_square: f64,
+    pub degrees_of_freedom: u32,
+    /// The statistic a fair die exceeds only about 0.1% of the time.
+    pub critical_value: f64,
+}
+
+impl DieStatistics {
+    /// Whether the tally is consistent with a fair die at the 0.1% significance level.
+    pub fn looks_fair(&self) -> bool {
+        self.chi_square <= self.critical_value
+    }
+
+    /// Fraction of rolls that came up `face`.
+    pub fn frequency(&self, face: u32) -> f64 {
+        let count = face
+            .checked_sub(1)
+            .and_then(|index| self.counts.get(index as usize))
+            .copied()
+            .unwrap_or(0);
+        if self.rolls > 0 {
+            count as f64 / self.rolls as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+impl RollStatistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, die_size: u32, face: u32) {
+        // a d1 has nothing to be unfair about
+        if die_size < 2 || face == 0 || face > die_size {
+            return;
+        }
+        let counts = self
+            .tallies
+            .entry(die_size)
+            .or_insert_with(|| vec![0; die_size as usize]);
+        counts[face as usize - 1] += 1;
+    }
+
+    /// Adds another tally's rolls into this one.
+    pub fn merge(&mut self, other: &RollStatistics) {
+        for (die_size, other_counts) in &other.tallies {
+            let counts = self
+                .tallies
+                .entry(*die_size)
+                .or_insert_with(|| vec![0; *die_size as usize]);
+            for (count, other_count) in counts.iter_mut().zip(other_counts) {
+                *count += other_count;
+            }
+        }
+    }
+
+    pub fn die(&self, die_size: u32) -> Option<DieStatistics> {
+        let counts = self.tallies.get(&die_size)?;
+        let rolls: u64 = counts.iter().sum();
+        if rolls == 0 {
+            return None;
+        }
+
+        let expected = rolls as f64 / die_size as f64;
+        let chi_square = counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum();
+        let degrees_of_freedom = die_size - 1;
+
+        // Wilson-Hilferty approximation of the chi-square quantile
+        let k = degrees_of_freedom as f64;
+        let h = 2.0 / (9.0 * k);
+        let critical_value = k * (1.0 - h + Z_999 * h.sqrt()).powi(3);
+
+        Some(DieStatistics {
+            die_size,
+            rolls,
+            counts: counts.clone(),
+            chi_square,
+            degrees_of_freedom,
+            critical_value,
+        })
+    }
+
+    /// Statistics for every die size rolled, smallest first.
+    pub fn dice(&self) -> Vec<DieStatistics> {
+        self.tallies
+            .keys()
+            .filter_map(|die_size| self.die(*die_size))
+            .collect()
+    }
+
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        for die in self.dice() {
+            writeln!(
+                f,
+                "d{}: {} rolls, chi-square {:.2} ({} df, critical {:.2}){}",
+                die.die_size,
+                die.rolls,
+                die.chi_square,
+                die.degrees_of_freedom,
+                die.critical_value,
+                if die.looks_fair() { "" } else { " SUSPICIOUS" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{ActorBuilder, Integrator, Roller, State};
+    use crate::test_utils::brawl_policy;
+
+    #[test]
+    fn test_roll_statistics() -> anyhow::Result<()> {
+        let mut roller = Roller::test_rng();
+        roller.record_roll_statistics(true);
+        for _ in 0..6000 {
+            roller.d(6);
+        }
+        roller.d(1);
+        let stats = roller.roll_statistics().unwrap().clone();
+        assert_eq!(stats.tallies.keys().copied().collect::<Vec<_>>(), vec![6]);
+        let d6 = stats.die(6).unwrap();
+        assert_eq!(d6.rolls, 6000);
+        assert_eq!(d6.degrees_of_freedom, 5);
+        // the tabulated 99.9% quantile for 5 degrees of freedom is 20.52
+        assert!((d6.critical_value - 20.52).abs() < 0.5);
+        assert!(d6.looks_fair(), "{:?}", d6);
+
+        let mut loaded = RollStatistics::new();
+        for face in [1, 2, 3, 4, 5, 6, 6, 6] {
+            for _ in 0..100 {
+                loaded.record(6, face);
+            }
+        }
+        assert!(!loaded.die(6).unwrap().looks_fair());
+        loaded.merge(&stats);
+        assert_eq!(loaded.die(6).unwrap().rolls, 6800);
+
+        // integration records every die it rolls
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        state.set_actor_policy(fighter, policy.clone());
+        state.set_actor_policy(goblin, policy);
+        let mut integrator = Integrator::new(10, Roller::test_rng(), state);
+        integrator.record_roll_statistics(true);
+        let results = integrator.run()?;
+        let d20 = results.roll_statistics.unwrap().die(20).unwrap();
+        assert!(d20.rolls >= 20);
+
+        Ok(())
+    }
+}
@@ -1,17 +1,37 @@
+#[cfg(any(test, feature = "test-utils"))]
+use std::collections::VecDeque;
+
 use rand::{Rng, SeedableRng, rngs::StdRng};
 
-use crate::{prelude::RollPlan, rules::dice::RollResult};
+use crate::{
+    prelude::RollPlan, rules::dice::RollResult, simulation::roll_statistics::RollStatistics,
+};
 
 #[derive(Debug)]
 pub struct Roller {
     rng: StdRng,
+    seed: Option<u64>,
+    /// What [`Roller::for_combat`] derives substreams from: the seed, or a random key without one.
+    stream_key: u64,
+    statistics: Option<RollStatistics>,
+    /// Faces handed out before falling back to `rng`. Only ever filled by [`Roller::scripted`], so
+    /// it isn't compiled in outside tests.
+    #[cfg(any(test, feature = "test-utils"))]
+    script: VecDeque<u32>,
 }
 
 impl Roller {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let rng = StdRng::from_os_rng();
-        Roller { rng }
+        let mut rng = StdRng::from_os_rng();
+        Roller {
+            stream_key: rng.random(),
+            rng,
+            seed: None,
+            statistics: None,
+            #[cfg(any(test, feature = "test-utils"))]
+            script: VecDeque::new(),
+        }
     }
 
     /// Creates a new `Roller` instance with a different random seed.
@@ -19,33 +39,161 @@ impl Roller {
     pub fn fork(&mut self) -> Self {
         let mut seed = [0u8; 32];
         self.rng.fill(&mut seed);
-        let rng = StdRng::from_seed(seed);
-        Roller { rng }
+        let mut rng = StdRng::from_seed(seed);
+        Roller {
+            stream_key: rng.random(),
+            rng,
+            seed: None,
+            statistics: None,
+            #[cfg(any(test, feature = "test-utils"))]
+            script: VecDeque::new(),
+        }
     }
 
     pub fn from_seed(seed: u64) -> Self {
         let rng = StdRng::seed_from_u64(seed);
-        Roller { rng }
+        Roller {
+            rng,
+            seed: Some(seed),
+            stream_key: seed,
+            statistics: None,
+            #[cfg(any(test, feature = "test-utils"))]
+            script: VecDeque::new(),
+        }
+    }
+
+    /// A roller from a fresh random seed, which is kept so the run can be repeated.
+    pub fn from_random_seed() -> Self {
+        Self::from_seed(rand::random())
+    }
+
+    /// The seed this roller was created from, if it was created from one.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Creates the roller for one shard of a run split across several processes or machines.
+    /// Each shard of a base seed gets its own stream, and rerunning a shard reproduces it exactly.
+    pub fn for_shard(base_seed: u64, shard: u64) -> Self {
+        Self::from_seed(base_seed ^ shard.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    /// Forks off the substream for combat `index` of a run. It depends only on this roller's seed
+    /// and the index, so rerunning a combat reproduces it exactly whatever ran before it, and
+    /// combats could be run in any order or in parallel.
+    ///
+    /// Roll statistics and scripted faces move over to the substream until it's handed back with
+    /// [`Roller::rejoin`].
+    pub fn for_combat(&mut self, index: u64) -> Self {
+        let seed = splitmix64(self.stream_key ^ splitmix64(index));
+        Roller {
+            statistics: self.statistics.take(),
+            #[cfg(any(test, feature = "test-utils"))]
+            script: std::mem::take(&mut self.script),
+            ..Self::from_seed(seed)
+        }
+    }
+
+    /// Takes back what [`Roller::for_combat`] lent a substream once its combat is over.
+    pub fn rejoin(&mut self, substream: Roller) {
+        self.statistics = substream.statistics;
+        #[cfg(any(test, feature = "test-utils"))]
+        {
+            self.script = substream.script;
+        }
+    }
+
+    /// The next face handed out by [`Roller::scripted`], if any are left.
+    #[cfg(any(test, feature = "test-utils"))]
+    fn next_scripted(&mut self) -> Option<u32> {
+        self.script.pop_front()
+    }
+
+    #[cfg(not(any(test, feature = "test-utils")))]
+    #[inline(always)]
+    fn next_scripted(&mut self) -> Option<u32> {
+        None
     }
 
     pub fn d(&mut self, die_size: u32) -> u32 {
-        self.rng.random_range(1..=die_size)
+        let face = match self.next_scripted() {
+            Some(face) => {
+                assert!(
+                    (1..=die_size).contains(&face),
+                    "scripted roll {face} doesn't fit a d{die_size}"
+                );
+                face
+            }
+            None => self.rng.random_range(1..=die_size),
+        };
+        if let Some(statistics) = &mut self.statistics {
+            statistics.record(die_size, face);
+        }
+        face
+    }
+
+    /// Rolls a die, rerolling anything below `low`. Only full-range rolls are tallied in the
+    /// roll statistics.
+    pub fn die(&mut self, low: u32, die_size: u32) -> u32 {
+        if low <= 1 {
+            self.d(die_size)
+        } else {
+            self.range(low, die_size)
+        }
     }
 
     pub fn range(&mut self, min: u32, max: u32) -> u32 {
-        self.rng.random_range(min..=max)
+        match self.next_scripted() {
+            Some(value) => {
+                assert!(
+                    (min..=max).contains(&value),
+                    "scripted roll {value} isn't between {min} and {max}"
+                );
+                value
+            }
+            None => self.rng.random_range(min..=max),
+        }
     }
 
     pub fn roll(&mut self, roll: &RollPlan) -> anyhow::Result<RollResult> {
         roll.roll(self)
     }
 
+    /// Starts or stops tallying every die rolled. Starting clears any earlier tally.
+    pub fn record_roll_statistics(&mut self, enabled: bool) {
+        self.statistics = enabled.then(RollStatistics::new);
+    }
+
+    pub fn roll_statistics(&self) -> Option<&RollStatistics> {
+        self.statistics.as_ref()
+    }
+
     pub fn rng(&mut self) -> &mut StdRng {
         &mut self.rng
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-utils"))]
     pub fn test_rng() -> Self {
         Self::from_seed(42)
     }
+
+    /// A roller that returns `faces` in order for every die rolled, whatever its size, then
+    /// carries on like [`Roller::test_rng`]. Panics if a face doesn't fit the die it's used for.
+    ///
+    /// Policies pick actions and targets with [`Roller::rng`] directly, which is never scripted.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn scripted(faces: impl IntoIterator<Item = u32>) -> Self {
+        Self {
+            script: faces.into_iter().collect(),
+            ..Self::test_rng()
+        }
+    }
+}
+
+/// Scrambles a `u64`, so that nearby combat indices and seeds give unrelated substreams.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
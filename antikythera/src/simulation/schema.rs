@@ -0,0 +1,286 @@
+//! Versioning for saved states and results.
+//!
+//! States and results are written with the current [`SCHEMA_VERSION`]. Files from before
+//! versioning count as version 0. Loading goes through [`State::from_json`] and
+//! [`IntegrationResults::from_json`], which bring older files up to date one version at a time
+//! and refuse files written by a newer version.
+
+use serde_json::Value;
+
+use crate::simulation::{integration::IntegrationResults, state::State};
+
+/// The schema version of states and results written by this build.
+///
+/// 1. HP no longer goes below 0; damage past 0 is tracked as overkill.
+/// 2. Stat modifiers that run out are timed effects.
+/// 3. Armor has an equipment slot. Shields saved before then are told apart by name.
+pub const SCHEMA_VERSION: u32 = 3;
+
+pub(crate) fn schema_version(value: &Value, what: &str) -> anyhow::Result<u32> {
+    anyhow::ensure!(value.is_object(), "The {} isn't a JSON object", what);
+    let version = match value.get("schema_version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid {} schema version: {}", what, version))?,
+    };
+    anyhow::ensure!(
+        version <= SCHEMA_VERSION,
+        "The {} was saved with schema version {}, but this version of antikythera only reads up \
+         to version {}",
+        what,
+        version,
+        SCHEMA_VERSION
+    );
+    Ok(version)
+}
+
+/// Brings a state's JSON from `version` up to [`SCHEMA_VERSION`].
+fn migrate_state(state: &mut Value, version: u32) {
+    if version < 1
+        && let Some(actors) = state.get_mut("actors").and_then(Value::as_object_mut)
+    {
+        for actor in actors.values_mut() {
+            let health = actor.get("health").and_then(Value::as_i64).unwrap_or(0);
+            if health < 0 {
+                actor["health"] = 0.into();
+                actor["overkill"] = (-health).into();
+            }
+        }
+    }
+    if version < 2 {
+        let turn = state.get("turn").cloned().unwrap_or(Value::from(0));
+        let current_turn_index = state.get("current_turn_index").cloned().unwrap_or_default();
+        let mut timed = Vec::new();
+        if let Some(actors) = state.get_mut("actors").and_then(Value::as_object_mut) {
+            for (id, actor) in actors.iter_mut() {
+                let Some(modifiers) = actor
+                    .pointer_mut("/stats/modifiers")
+                    .and_then(Value::as_array_mut)
+                else {
+                    continue;
+                };
+                for modifier in modifiers.iter_mut().filter_map(Value::as_object_mut) {
+                    let Some(until) = modifier
+                        .remove("until_turn")
+                        .filter(|until| !until.is_null())
+                    else {
+                        continue;
+                    };
+                    let id: Value = id.parse::<u64>().map(Value::from).unwrap_or_default();
+                    timed.push(serde_json::json!({
+                        "target": id,
+                        "change": { "Stat": modifier },
+                        "expiry": { "StartOfTurnInRound": [id, until] },
+                        "applied_round": turn,
+                        "applied_turn": current_turn_index,
+                    }));
+                }
+            }
+        }
+        if !timed.is_empty() {
+            let effects = &mut state["timed_effects"]["effects"];
+            if !effects.is_array() {
+                *effects = Value::Array(Vec::new());
+            }
+            effects.as_array_mut().unwrap().extend(timed);
+        }
+    }
+    if let Some(items) = state.get_mut("items") {
+        migrate_items(items, version);
+    }
+    state["schema_version"] = SCHEMA_VERSION.into();
+}
+
+/// Brings the JSON of a map of items, as kept by states and actor files, from `version` up to
+/// [`SCHEMA_VERSION`].
+pub(crate) fn migrate_items(items: &mut Value, version: u32) {
+    if version >= 3 {
+        return;
+    }
+    let Some(items) = items.as_object_mut() else {
+        return;
+    };
+    for item in items.values_mut() {
+        let is_shield = item
+            .get("name")
+            .and_then(Value::as_str)
+            .is_some_and(|name| name.to_lowercase().contains("shield"));
+        if let Some(armor) = item
+            .pointer_mut("/inner/Armor")
+            .and_then(Value::as_object_mut)
+            && !armor.contains_key("slot")
+        {
+            let slot = if is_shield { "Shield" } else { "Chest" };
+            armor.insert("slot".to_string(), slot.into());
+        }
+    }
+}
+
+impl State {
+    /// Loads a state from JSON, migrating it from older schema versions.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Self::from_json_value(serde_json::from_str(json)?)
+    }
+
+    pub fn from_json_value(mut value: Value) -> anyhow::Result<Self> {
+        let version = schema_version(&value, "state")?;
+        migrate_state(&mut value, version);
+        let mut state: State = serde_json::from_value(value)?;
+        state.apply_formulas()?;
+        Ok(state)
+    }
+}
+
+impl IntegrationResults {
+    /// Loads results from JSON, migrating them from older schema versions.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Self::from_json_value(serde_json::from_str(json)?)
+    }
+
+    pub fn from_json_value(mut value: Value) -> anyhow::Result<Self> {
+        let version = schema_version(&value, "results")?;
+        if let Some(state) = value.pointer_mut("/state_tree/initial_state") {
+            migrate_state(state, version);
+        }
+        value["schema_version"] = SCHEMA_VERSION.into();
+        let mut results: IntegrationResults = serde_json::from_value(value)?;
+        if version < 1 {
+            // the state hash changed along with HP accounting
+            results.state_tree.rebuild_state_cache();
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::{ActorBuilder, Integrator, Roller},
+        rules::{
+            conditions::Condition,
+            items::{Armor, EquipSlot, Item, ItemInner},
+            stats::{Stat, StatModifier},
+        },
+        simulation::{
+            actor_file::ActorFile,
+            timed_effects::{Expiry, TimedChange},
+        },
+        test_utils::brawl_policy,
+    };
+
+    #[test]
+    fn test_schema_migration() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let policy = brawl_policy();
+        let fighter = state.add_actor(ActorBuilder::new("Fighter").max_health(6).build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).max_health(6).build());
+        state.set_actor_policy(fighter, policy.clone());
+        state.set_actor_policy(goblin, policy);
+
+        // a state from before versioning, with a goblin below 0 HP
+        let mut old = serde_json::to_value(&state)?;
+        old.as_object_mut().unwrap().remove("schema_version");
+        old["actors"][goblin.0.to_string()]["health"] = (-4).into();
+        let migrated = State::from_json_value(old)?;
+        assert_eq!(migrated.schema_version, SCHEMA_VERSION);
+        let migrated_goblin = migrated.get_actor(goblin).unwrap();
+        assert_eq!((migrated_goblin.health, migrated_goblin.overkill), (0, 4));
+
+        // a version 1 state with a strength penalty that runs out, and the Help action's advantage
+        // stored as a bare condition
+        let mut old = serde_json::to_value(&state)?;
+        old["schema_version"] = 1.into();
+        old["actors"][fighter.0.to_string()]["stats"]["modifiers"] = serde_json::json!([
+            { "stat": "Strength", "delta": -2, "until_turn": 3 },
+            { "stat": "Dexterity", "delta": 1, "until_turn": null },
+        ]);
+        old["timed_effects"] = serde_json::json!({ "effects": [{
+            "target": goblin.0,
+            "condition": { "Helped": { "by": fighter.0, "against": fighter.0 } },
+            "expiry": { "StartOfTurn": fighter.0 },
+            "applied_round": 0,
+            "applied_turn": null,
+        }]});
+        let migrated = State::from_json_value(old)?;
+        let penalty = StatModifier {
+            stat: Stat::Strength,
+            delta: -2,
+        };
+        let effects = &migrated.timed_effects.effects;
+        assert_eq!(effects.len(), 2);
+        assert_eq!(
+            effects[0].change,
+            TimedChange::Condition(Condition::Helped {
+                by: fighter,
+                against: fighter
+            })
+        );
+        assert_eq!(effects[1].change, TimedChange::Stat(penalty));
+        assert_eq!(effects[1].expiry, Expiry::StartOfTurnInRound(fighter, 3));
+        assert_eq!(
+            migrated
+                .get_actor(fighter)
+                .unwrap()
+                .stats
+                .get(Stat::Dexterity),
+            11
+        );
+
+        // version 2 armor had no slot, so a shield only shows in its name
+        let mut armored = state.clone();
+        let shield = armored.add_item("Shield +1", ItemInner::Armor(Armor::shield(3)));
+        let mail = armored.add_item("Chain Mail", ItemInner::Armor(Armor::new(6)));
+        armored
+            .actors
+            .get_mut(&fighter)
+            .unwrap()
+            .give_item(shield, 1);
+        let strip_slots = |items: &mut Value| {
+            for item in items.as_object_mut().unwrap().values_mut() {
+                item["inner"]["Armor"]
+                    .as_object_mut()
+                    .unwrap()
+                    .remove("slot");
+            }
+        };
+        let mut old = serde_json::to_value(&armored)?;
+        old["schema_version"] = 2.into();
+        strip_slots(&mut old["items"]);
+        let migrated = State::from_json_value(old)?;
+        let slot = |item: &Item| match &item.inner {
+            ItemInner::Armor(armor) => armor.slot,
+            _ => unreachable!(),
+        };
+        assert_eq!(slot(&migrated.items[&shield]), EquipSlot::Shield);
+        assert_eq!(slot(&migrated.items[&mail]), EquipSlot::Chest);
+
+        let mut old = serde_json::to_value(armored.export_actor(fighter)?)?;
+        old["schema_version"] = 2.into();
+        strip_slots(&mut old["items"]);
+        let file = ActorFile::from_json(&old.to_string())?;
+        assert_eq!(slot(&file.items[&shield]), EquipSlot::Shield);
+
+        let results = Integrator::new(5, Roller::test_rng(), state.clone()).run()?;
+        let mut old = serde_json::to_value(&results)?;
+        old.as_object_mut().unwrap().remove("schema_version");
+        let mut loaded = IntegrationResults::from_json_value(old)?;
+        assert_eq!(loaded.schema_version, SCHEMA_VERSION);
+        loaded.merge(&results)?;
+        assert_eq!(
+            loaded.state_tree.node_count(),
+            results.state_tree.node_count()
+        );
+
+        let mut future = serde_json::to_value(&state)?;
+        future["schema_version"] = (SCHEMA_VERSION + 1).into();
+        let error = State::from_json_value(future).unwrap_err().to_string();
+        assert!(error.contains(&format!("schema version {}", SCHEMA_VERSION + 1)));
+        assert!(State::from_json("[]").is_err());
+        assert!(IntegrationResults::from_json("null").is_err());
+
+        Ok(())
+    }
+}
@@ -1,24 +1,301 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    hash::{Hash, Hasher},
+};
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    prelude::{ActionEconomyUsage, ActionType, Policy},
+    prelude::{ActionEconomyUsage, Policy},
     rules::{
+        actions::{
+            Action, AttackAction, GrappleAction, HelpAction, ShoveAction, UnarmedStrikeAction,
+            UseAbilityAction,
+        },
         actor::{Actor, ActorId},
-        items::{Item, ItemId, ItemInner},
+        conditions::Condition,
+        dice::{Advantage, RollSettings},
+        items::{Inventory, Item, ItemId, ItemInner},
+        metadata::ScenarioMetadata,
+        skills::Skill,
+        vision::Lighting,
+    },
+    simulation::{
+        diff::StateDiff,
+        encounter::{EncounterSetup, InitiativeMode},
+        environment::EffectArea,
+        groups::GroupInfo,
+        objectives::{Objective, Outcome},
+        positioning::ZoneId,
+        schema::SCHEMA_VERSION,
+        timed_effects::TimedEffects,
     },
 };
 
+/// What [`State::clone_actor`] does with the items the actor carries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ItemCloning {
+    /// The clone carries the same items, so editing one of them changes it for both actors.
+    #[default]
+    ShareItems,
+    /// The clone gets its own copy of every item, under new IDs.
+    DeepCopyItems,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub struct State {
+    /// See [`SCHEMA_VERSION`](crate::simulation::schema::SCHEMA_VERSION). States saved before
+    /// versioning load as version 0.
+    #[serde(default)]
+    pub schema_version: u32,
     pub turn: u64,
     pub actors: BTreeMap<ActorId, Actor>,
-    pub next_actor_id: u32,
     pub items: BTreeMap<ItemId, Item>,
-    pub next_item_id: u32,
+    #[serde(flatten)]
+    pub ids: IdAllocator,
     pub initiative_order: Vec<ActorId>,
     pub current_turn_index: Option<usize>,
+    #[serde(default)]
+    pub encounter: EncounterSetup,
+    /// Actors that have been killed and taken out of targeting. See
+    /// [`DeathCleanup`](crate::simulation::encounter::DeathCleanup).
+    #[serde(default)]
+    pub fallen: BTreeSet<ActorId>,
+    /// Items dropped by the dead.
+    #[serde(default)]
+    pub loot: Inventory,
+    /// What each actor dropped into [`State::loot`] when it died, to tell whose loot is whose.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dropped: BTreeMap<ActorId, Inventory>,
+    /// How far apart the two sides are, in feet, or `None` if distance isn't being modeled. See
+    /// [`EncounterSetup::engagement_distance`].
+    #[serde(default)]
+    pub distance: Option<u32>,
+    /// The enemy each actor last closed to melee with, standing in for positioning when deciding
+    /// who is adjacent to whom.
+    #[serde(default)]
+    pub engagements: BTreeMap<ActorId, ActorId>,
+    /// Which zone each actor stands in, when the encounter uses
+    /// [`EncounterSetup::zones`].
+    #[serde(default)]
+    pub actor_zones: BTreeMap<ActorId, ZoneId>,
+    /// The difficult-terrain zone each actor is partway into. See
+    /// [`ZoneMap::difficult`](crate::simulation::positioning::ZoneMap::difficult).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub zone_progress: BTreeMap<ActorId, ZoneId>,
+    /// Default policies by group, for actors without a policy of their own. See
+    /// [`State::policy_of`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub group_policies: BTreeMap<u32, Policy>,
+    /// Names and colors for groups. See [`GroupInfo`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<u32, GroupInfo>,
+    /// How the last combat was decided, once it has been. See [`State::combat_outcome`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Outcome>,
+    /// The round the last combat ended in, counting from 1, once it has ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_round: Option<u64>,
+    /// Actors sitting out the combat until a boss [`Phase`](crate::simulation::phases::Phase)
+    /// spawns them. See [`State::phase_reserves`].
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub reserves: BTreeSet<ActorId>,
+    /// Conditions that wear off at a turn boundary. See [`TimedEffects`].
+    #[serde(default, skip_serializing_if = "TimedEffects::is_empty")]
+    pub timed_effects: TimedEffects,
+    /// Title, author and notes for the scenario. See [`ScenarioMetadata`].
+    #[serde(default, skip_serializing_if = "ScenarioMetadata::is_empty")]
+    pub scenario: ScenarioMetadata,
+}
+
+/// Hands out fresh actor and item IDs.
+///
+/// This is bookkeeping rather than combat state, so it's left out of [`HashableState`]: states
+/// that only differ in which IDs would be handed out next are the same node in the state tree.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IdAllocator {
+    pub next_actor_id: u32,
+    pub next_item_id: u32,
+}
+
+impl Default for IdAllocator {
+    fn default() -> Self {
+        Self {
+            next_actor_id: 1,
+            next_item_id: 1,
+        }
+    }
+}
+
+impl IdAllocator {
+    pub fn allocate_actor_id(&mut self) -> ActorId {
+        let id = ActorId(self.next_actor_id);
+        self.next_actor_id += 1;
+        id
+    }
+
+    pub fn allocate_item_id(&mut self) -> ItemId {
+        let id = ItemId(self.next_item_id);
+        self.next_item_id += 1;
+        id
+    }
+}
+
+/// A view of a [`State`] that hashes only the fields that affect how combat plays out.
+///
+/// Names and metadata are left out, so states that differ only cosmetically share a node in the
+/// state tree. Every field is destructured explicitly so that new fields must be classified here.
+pub struct HashableState<'a>(pub &'a State);
+
+impl Hash for HashableState<'_> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        let State {
+            // bookkeeping for loading saved states
+            schema_version: _,
+            turn,
+            actors,
+            items,
+            // ID bookkeeping isn't combat state
+            ids: _,
+            initiative_order,
+            current_turn_index,
+            encounter,
+            fallen,
+            loot,
+            dropped,
+            distance,
+            engagements,
+            actor_zones,
+            zone_progress,
+            group_policies,
+            // names and colors are cosmetic
+            groups: _,
+            outcome,
+            // counted per terminal state by the tree instead, so combats ending alike share one
+            final_round: _,
+            reserves,
+            timed_effects,
+            // describes the scenario, doesn't change it
+            scenario: _,
+        } = self.0;
+
+        turn.hash(hasher);
+        actors.len().hash(hasher);
+        for actor in actors.values() {
+            hash_actor(actor, hasher);
+        }
+        items.len().hash(hasher);
+        for item in items.values() {
+            let Item {
+                id,
+                inner,
+                save_bonus,
+                weight,
+                name: _,
+                metadata: _,
+            } = item;
+            id.hash(hasher);
+            inner.hash(hasher);
+            save_bonus.hash(hasher);
+            weight.hash(hasher);
+        }
+        initiative_order.hash(hasher);
+        current_turn_index.hash(hasher);
+        encounter.hash(hasher);
+        fallen.hash(hasher);
+        loot.hash(hasher);
+        dropped.hash(hasher);
+        distance.hash(hasher);
+        engagements.hash(hasher);
+        actor_zones.hash(hasher);
+        zone_progress.hash(hasher);
+        group_policies.hash(hasher);
+        outcome.hash(hasher);
+        reserves.hash(hasher);
+        timed_effects.hash(hasher);
+    }
+}
+
+fn hash_actor<H: Hasher>(actor: &Actor, hasher: &mut H) {
+    let Actor {
+        id,
+        npc,
+        group,
+        tags,
+        level,
+        size,
+        vision,
+        mount,
+        armor_class,
+        ac_formula,
+        max_health,
+        health,
+        overkill,
+        stats,
+        movement_speed,
+        skill_proficiencies,
+        saving_throw_proficiencies,
+        death_saves,
+        death_behavior,
+        initiative,
+        conditions,
+        action_economy,
+        equipped_items,
+        inventory,
+        weapon_proficiencies,
+        features,
+        features_used,
+        rerolls,
+        policy,
+        random_effects,
+        abilities,
+        spent_abilities,
+        homebrew,
+        resistances,
+        phases,
+        phase,
+        name: _,
+        metadata: _,
+        scaling: _,
+        formulas: _,
+    } = actor;
+
+    id.hash(hasher);
+    npc.hash(hasher);
+    group.hash(hasher);
+    tags.hash(hasher);
+    level.hash(hasher);
+    size.hash(hasher);
+    vision.hash(hasher);
+    mount.hash(hasher);
+    armor_class.hash(hasher);
+    ac_formula.hash(hasher);
+    max_health.hash(hasher);
+    health.hash(hasher);
+    overkill.hash(hasher);
+    stats.hash(hasher);
+    movement_speed.hash(hasher);
+    skill_proficiencies.hash(hasher);
+    saving_throw_proficiencies.hash(hasher);
+    death_saves.hash(hasher);
+    death_behavior.hash(hasher);
+    initiative.hash(hasher);
+    conditions.hash(hasher);
+    action_economy.hash(hasher);
+    equipped_items.hash(hasher);
+    inventory.hash(hasher);
+    weapon_proficiencies.hash(hasher);
+    features.hash(hasher);
+    features_used.hash(hasher);
+    rerolls.hash(hasher);
+    policy.hash(hasher);
+    random_effects.hash(hasher);
+    abilities.hash(hasher);
+    spent_abilities.hash(hasher);
+    homebrew.hash(hasher);
+    resistances.hash(hasher);
+    phases.hash(hasher);
+    phase.hash(hasher);
 }
 
 impl Default for State {
@@ -30,42 +307,391 @@ impl Default for State {
 impl State {
     pub fn new() -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             turn: 0,
             actors: BTreeMap::new(),
-            next_actor_id: 1,
             items: BTreeMap::new(),
-            next_item_id: 1,
+            ids: IdAllocator::default(),
             initiative_order: Vec::new(),
             current_turn_index: None,
+            encounter: EncounterSetup::default(),
+            fallen: BTreeSet::new(),
+            loot: Inventory::default(),
+            dropped: BTreeMap::new(),
+            distance: None,
+            engagements: BTreeMap::new(),
+            actor_zones: BTreeMap::new(),
+            zone_progress: BTreeMap::new(),
+            group_policies: BTreeMap::new(),
+            groups: BTreeMap::new(),
+            outcome: None,
+            final_round: None,
+            reserves: BTreeSet::new(),
+            timed_effects: TimedEffects::default(),
+            scenario: ScenarioMetadata::default(),
         }
     }
 
+    /// Lists what changed going from `self` to `other`.
+    pub fn diff(&self, other: &State) -> anyhow::Result<StateDiff> {
+        StateDiff::between(self, other)
+    }
+
     pub fn add_actor(&mut self, mut actor: Actor) -> ActorId {
-        let actor_id = ActorId(self.next_actor_id);
-        self.next_actor_id += 1;
+        let actor_id = self.ids.allocate_actor_id();
         actor.id = actor_id;
         self.actors.insert(actor_id, actor);
         actor_id
     }
 
+    /// Adds a copy of an actor to the state, returning the copy's ID. The copy starts on foot,
+    /// since a mount only carries one rider.
+    pub fn clone_actor(&mut self, actor: ActorId, items: ItemCloning) -> anyhow::Result<ActorId> {
+        let mut clone = self
+            .get_actor(actor)
+            .ok_or_else(|| anyhow::anyhow!("Actor {} not found", actor.0))?
+            .clone();
+        clone.mount = None;
+
+        if items == ItemCloning::DeepCopyItems {
+            let carried: BTreeSet<ItemId> = clone
+                .inventory
+                .items
+                .keys()
+                .chain(&clone.equipped_items.items)
+                .copied()
+                .collect();
+            let mut copies = BTreeMap::new();
+            for id in carried {
+                let mut item = self
+                    .items
+                    .get(&id)
+                    .ok_or_else(|| anyhow::anyhow!("Item {} not found", id.0))?
+                    .clone();
+                item.id = self.ids.allocate_item_id();
+                copies.insert(id, item.id);
+                self.items.insert(item.id, item);
+            }
+            clone.inventory.items = clone
+                .inventory
+                .items
+                .into_iter()
+                .map(|(id, quantity)| (copies[&id], quantity))
+                .collect();
+            clone.equipped_items.items = clone
+                .equipped_items
+                .items
+                .into_iter()
+                .map(|id| copies[&id])
+                .collect();
+        }
+
+        Ok(self.add_actor(clone))
+    }
+
+    /// Removes an actor from the state, along with every reference to it: its place in the
+    /// initiative order, riders mounted on it, conditions it imposes, timed effects on it or waiting
+    /// on its turns, target weights aimed at it, phases that would spawn it, and its entries in the
+    /// encounter setup. Objectives about the actor are dropped too, as is the record of what it
+    /// dropped when it died. The items it carries, or left in [`State::loot`], stay in the state.
+    pub fn remove_actor(&mut self, actor: ActorId) -> anyhow::Result<Actor> {
+        let removed = self
+            .actors
+            .remove(&actor)
+            .ok_or_else(|| anyhow::anyhow!("Actor {} not found", actor.0))?;
+
+        if let Some(index) = self.initiative_order.iter().position(|id| *id == actor) {
+            self.initiative_order.remove(index);
+            self.current_turn_index = match self.current_turn_index {
+                _ if self.initiative_order.is_empty() => None,
+                Some(current) if current > index => Some(current - 1),
+                Some(current) if current >= self.initiative_order.len() => Some(0),
+                current => current,
+            };
+        }
+
+        let forget_targets = |policy: &mut Policy| {
+            policy.target_weights.retain(|(target, _)| *target != actor);
+        };
+        for other in self.actors.values_mut() {
+            if other.mount == Some(actor) {
+                other.mount = None;
+            }
+            other
+                .conditions
+                .conditions
+                .retain(|condition| match *condition {
+                    Condition::Grappled { by } => by != actor,
+                    Condition::Helped { by, against } => by != actor && against != actor,
+                    Condition::Prone => true,
+                });
+            forget_targets(&mut other.policy);
+            for phase in &mut other.phases {
+                phase.spawn.retain(|id| *id != actor);
+                if let Some(policy) = &mut phase.policy {
+                    forget_targets(policy);
+                }
+            }
+        }
+        self.group_policies.values_mut().for_each(forget_targets);
+        self.timed_effects.forget_actor(actor);
+
+        let encounter = &mut self.encounter;
+        encounter.cover.remove(&actor);
+        encounter.advantage_overrides.remove(&actor);
+        for targets in encounter.cover.values_mut() {
+            targets.remove(&actor);
+        }
+        for targets in encounter.advantage_overrides.values_mut() {
+            targets.remove(&actor);
+        }
+        encounter.attacker_advantage_overrides.remove(&actor);
+        match &mut encounter.initiative {
+            InitiativeMode::Rolled => {}
+            InitiativeMode::Fixed(values) => {
+                values.remove(&actor);
+            }
+            InitiativeMode::Order(order) => order.retain(|id| *id != actor),
+        }
+        if let Some(zones) = &mut encounter.zones {
+            zones.starting_zones.remove(&actor);
+        }
+        for effect in &mut encounter.environment {
+            if let EffectArea::Actors(actors) = &mut effect.area {
+                actors.remove(&actor);
+            }
+        }
+        encounter.actor_lighting.remove(&actor);
+        encounter.objectives.retain(|objective| match *objective {
+            Objective::Defeat { target: id, .. } | Objective::Protect { actor: id, .. } => {
+                id != actor
+            }
+            Objective::Survive { .. } | Objective::ReachZone { .. } => true,
+        });
+
+        self.fallen.remove(&actor);
+        self.dropped.remove(&actor);
+        self.engagements
+            .retain(|engaged, target| *engaged != actor && *target != actor);
+        self.actor_zones.remove(&actor);
+        self.zone_progress.remove(&actor);
+        self.reserves.remove(&actor);
+
+        Ok(removed)
+    }
+
     pub fn add_item(&mut self, name: &str, item: ItemInner) -> ItemId {
-        let item_id = ItemId(self.next_item_id);
-        self.next_item_id += 1;
+        let item_id = self.ids.allocate_item_id();
         let item = Item {
             id: item_id,
             name: name.to_string(),
             inner: item,
+            save_bonus: 0,
+            weight: 0,
+            metadata: Default::default(),
         };
         self.items.insert(item_id, item);
         item_id
     }
 
+    /// Removes an item from the state, taking it out of every inventory, every actor's equipped
+    /// items, and the loot pool.
+    pub fn remove_item(&mut self, item: ItemId) -> anyhow::Result<Item> {
+        let removed = self
+            .items
+            .remove(&item)
+            .ok_or_else(|| anyhow::anyhow!("Item {} not found", item.0))?;
+        for actor in self.actors.values_mut() {
+            actor.inventory.items.remove(&item);
+            actor.equipped_items.items.remove(&item);
+        }
+        self.loot.items.remove(&item);
+        for dropped in self.dropped.values_mut() {
+            dropped.items.remove(&item);
+        }
+        Ok(removed)
+    }
+
+    /// Things a scenario's author probably didn't mean, worded for them, along with the actor
+    /// each is about: for now, actors slowed down by the optional encumbrance rule. Unlike
+    /// [`State::validate`]'s errors, these don't stop the scenario from being simulated.
+    pub fn warnings(&self) -> Vec<(ActorId, String)> {
+        self.encumbrance_warnings()
+    }
+
+    /// Checks for anything a hand-edited state might get wrong that loading it doesn't catch:
+    /// ability scores out of bounds, weapon damage that can't be rolled, and homebrew or
+    /// environmental effects that don't compile.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for actor in self.actors.values() {
+            actor
+                .stats
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Invalid ability scores on {}: {}", actor.name, e))?;
+            actor
+                .homebrew
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Invalid homebrew effect on {}: {}", actor.name, e))?;
+            let phase_policies = actor
+                .phases
+                .iter()
+                .filter_map(|phase| phase.policy.as_ref());
+            for policy in std::iter::once(&actor.policy).chain(phase_policies) {
+                policy
+                    .validate()
+                    .map_err(|e| anyhow::anyhow!("Invalid policy on {}: {}", actor.name, e))?;
+            }
+        }
+        for (group, policy) in &self.group_policies {
+            policy
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Invalid policy for group {}: {}", group, e))?;
+        }
+        for item in self.items.values() {
+            if let ItemInner::Weapon(weapon) = &item.inner {
+                for roll in std::iter::once(&weapon.damage).chain(&weapon.critical_damage) {
+                    roll.validate().map_err(|e| {
+                        anyhow::anyhow!("Invalid damage roll on {}: {}", item.name, e)
+                    })?;
+                }
+            }
+        }
+        for environmental in &self.encounter.environment {
+            environmental.compile().map_err(|e| {
+                anyhow::anyhow!("Invalid environmental effect {}: {}", environmental.name, e)
+            })?;
+        }
+        if let Some(zones) = &self.encounter.zones {
+            zones.validate(self)?;
+        }
+        Ok(())
+    }
+
+    /// Pairs a rider with a mount, which must be at least one size larger than the rider.
+    pub fn mount_actor(&mut self, rider: ActorId, mount: ActorId) -> anyhow::Result<()> {
+        if rider == mount {
+            anyhow::bail!("An actor cannot mount itself");
+        }
+        let rider_size = self
+            .get_actor(rider)
+            .ok_or_else(|| anyhow::anyhow!("Rider actor not found"))?
+            .size;
+        let mount_actor = self
+            .get_actor(mount)
+            .ok_or_else(|| anyhow::anyhow!("Mount actor not found"))?;
+        if !mount_actor.size.can_carry_rider(rider_size) {
+            anyhow::bail!(
+                "{:?} mount is too small for a {:?} rider",
+                mount_actor.size,
+                rider_size
+            );
+        }
+        if let Some(other_rider) = self.rider_of(mount)
+            && other_rider != rider
+        {
+            anyhow::bail!("Mount already has a rider");
+        }
+        if mount_actor.mount.is_some() {
+            anyhow::bail!("A mount cannot itself be riding another actor");
+        }
+        if self.rider_of(rider).is_some() {
+            anyhow::bail!("An actor carrying a rider cannot ride another actor");
+        }
+
+        self.get_actor_mut(rider).unwrap().mount = Some(mount);
+        Ok(())
+    }
+
+    pub fn dismount_actor(&mut self, rider: ActorId) {
+        if let Some(actor) = self.get_actor_mut(rider) {
+            actor.mount = None;
+        }
+    }
+
+    pub fn rider_of(&self, mount: ActorId) -> Option<ActorId> {
+        self.actors
+            .values()
+            .find(|actor| actor.mount == Some(mount))
+            .map(|actor| actor.id)
+    }
+
+    /// Rebuilds the initiative order from each actor's initiative, highest first.
+    /// Mounts don't roll their own initiative; they act immediately after their rider.
+    pub fn recalculate_initiative_order(&mut self) {
+        let mut initiatives = self
+            .actors
+            .values()
+            .filter(|actor| self.rider_of(actor.id).is_none() && !self.reserves.contains(&actor.id))
+            .map(|actor| (actor.id, actor.initiative.unwrap_or(0)))
+            .collect::<Vec<(ActorId, i32)>>();
+        initiatives.sort_by_key(|b| std::cmp::Reverse(b.1)); // descending order
+
+        let mut order = Vec::with_capacity(self.actors.len());
+        for (id, _) in initiatives {
+            order.push(id);
+            if let Some(mount) = self.actors[&id].mount
+                && self.actors.contains_key(&mount)
+            {
+                order.push(mount);
+            }
+        }
+        self.initiative_order = order;
+    }
+
     pub fn set_actor_policy(&mut self, actor_id: ActorId, policy: Policy) {
         if let Some(actor) = self.actors.get_mut(&actor_id) {
             actor.policy = policy;
         }
     }
 
+    /// Sets the default policy for every actor in `group` that doesn't have its own.
+    pub fn set_group_policy(&mut self, group: u32, policy: Policy) {
+        self.group_policies.insert(group, policy);
+    }
+
+    /// The policy an actor acts on: its current phase's, if that sets one, then its own, or its
+    /// group's default if its own is empty.
+    pub fn policy_of<'a>(&'a self, actor: &'a Actor) -> &'a Policy {
+        let phase_policy = actor.phases[..actor.phase.min(actor.phases.len())]
+            .iter()
+            .rev()
+            .find_map(|phase| phase.policy.as_ref());
+        if let Some(policy) = phase_policy {
+            return policy;
+        }
+        match self.group_policies.get(&actor.group) {
+            Some(policy) if actor.policy.is_empty() => policy,
+            _ => &actor.policy,
+        }
+    }
+
+    /// Gives `actor` [`Policy::attacker`] if it has no policy to act on at all, neither its own nor
+    /// its group's. Loading never does this, so saved states round-trip as they are; front ends
+    /// opt in before simulating a scenario, since an actor that never acts can keep a combat from
+    /// ever ending.
+    pub fn fill_missing_policy(&mut self, actor: ActorId) {
+        let idle = self
+            .get_actor(actor)
+            .is_some_and(|actor| self.policy_of(actor).is_empty());
+        if idle {
+            self.set_actor_policy(actor, Policy::attacker());
+        }
+    }
+
+    /// [`State::fill_missing_policy`] for every actor.
+    pub fn fill_missing_policies(&mut self) {
+        let actors: Vec<_> = self.actors.keys().copied().collect();
+        for actor in actors {
+            self.fill_missing_policy(actor);
+        }
+    }
+
+    /// The actor whose turn it currently is, if combat is underway.
+    pub fn current_actor(&self) -> Option<ActorId> {
+        self.current_turn_index
+            .and_then(|index| self.initiative_order.get(index).copied())
+    }
+
     pub fn get_actor(&self, actor_id: ActorId) -> Option<&Actor> {
         self.actors.get(&actor_id)
     }
@@ -74,20 +700,35 @@ impl State {
         self.actors.get_mut(&actor_id)
     }
 
+    pub fn actors_with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Actor> + 'a {
+        self.actors.values().filter(move |actor| actor.has_tag(tag))
+    }
+
+    /// Whether every actor with the given tag is down. False if no actor has the tag.
+    pub fn all_tagged_down(&self, tag: &str) -> bool {
+        let mut tagged = self.actors_with_tag(tag).peekable();
+        tagged.peek().is_some() && tagged.all(|actor| !actor.is_alive())
+    }
+
     pub fn allies_of(&self, actor_id: ActorId) -> Option<Vec<ActorId>> {
         let actor = self.actors.get(&actor_id)?;
         let group_id = actor.group;
         let allies: Vec<ActorId> = self
             .actors
             .values()
-            .filter(|a| a.group == group_id && a.id != actor_id)
+            .filter(|a| a.group == group_id && a.id != actor_id && !self.reserves.contains(&a.id))
             .map(|a| a.id)
             .collect();
         Some(allies)
     }
 
     pub fn enemies_of(&self, actor_id: ActorId) -> Vec<ActorId> {
-        let mut enemies = BTreeSet::from_iter(self.actors.keys().cloned());
+        let mut enemies: BTreeSet<ActorId> = self
+            .actors
+            .keys()
+            .filter(|actor| !self.reserves.contains(actor))
+            .copied()
+            .collect();
         if let Some(allies) = self.allies_of(actor_id) {
             for ally in allies {
                 enemies.remove(&ally);
@@ -97,6 +738,96 @@ impl State {
         enemies.into_iter().collect()
     }
 
+    /// Whether an ally of `actor` that can still fight is in melee with `target`.
+    pub fn ally_engaged_with(&self, actor: ActorId, target: ActorId) -> bool {
+        self.allies_of(actor).is_some_and(|allies| {
+            allies.into_iter().any(|ally| {
+                self.engagements.get(&ally) == Some(&target)
+                    && self.get_actor(ally).is_some_and(|ally| ally.is_alive())
+            })
+        })
+    }
+
+    /// Whether `observer` can see `target`, given the light where the target stands and the
+    /// observer's senses. Always true unless the lighting rule is enabled.
+    pub fn can_see(&self, observer: &Actor, target: &Actor) -> bool {
+        !self.encounter.rules.lighting
+            || observer.vision.can_see(
+                self.encounter.lighting_at(target.id),
+                self.distance_between(observer.id, target.id),
+            )
+    }
+
+    /// Roll settings for a sight-based Perception check by `observer` to notice `target`.
+    /// Dim light imposes disadvantage, and darkness the observer can't see through does too.
+    pub fn perception_settings(
+        &self,
+        observer: &Actor,
+        target: &Actor,
+        settings: RollSettings,
+    ) -> RollSettings {
+        if !self.encounter.rules.lighting {
+            return settings;
+        }
+        let lighting = observer.vision.perceived_lighting(
+            self.encounter.lighting_at(target.id),
+            self.distance_between(observer.id, target.id),
+        );
+        let disadvantage = settings.advantage.is_disadvantage() || lighting != Lighting::Bright;
+        RollSettings {
+            advantage: Advantage::from_sources(settings.advantage.is_advantage(), disadvantage),
+            ..settings
+        }
+    }
+
+    /// `observer`'s passive Perception against `target`: 10 plus their Perception modifier,
+    /// with +5 for advantage and -5 for disadvantage from [`State::perception_settings`]. A
+    /// hiding creature's Stealth check has to beat it.
+    pub fn passive_perception(&self, observer: &Actor, target: &Actor) -> i32 {
+        let settings = self.perception_settings(observer, target, RollSettings::default());
+        let advantage = match settings.advantage {
+            Advantage::Advantage => 5,
+            Advantage::Disadvantage => -5,
+            _ => 0,
+        };
+        10 + observer.skill_modifier(Skill::Perception) + advantage
+    }
+
+    /// How far apart the sides start: [`EncounterSetup::engagement_distance`], unless the
+    /// [`stealth`](crate::simulation::encounter::OptionalRules::stealth) rule is on and some
+    /// group sneaks up unnoticed, which opens the combat in melee. Each member's passive Stealth,
+    /// 10 plus their Stealth modifier, has to beat every enemy's [`State::passive_perception`]
+    /// of them, as they stand now; [`Transition::BeginCombat`] first puts them at the engagement
+    /// distance.
+    ///
+    /// [`Transition::BeginCombat`]: crate::simulation::transition::Transition::BeginCombat
+    pub fn opening_distance(&self) -> Option<u32> {
+        let distance = self.encounter.engagement_distance?;
+        if !self.encounter.rules.stealth {
+            return Some(distance);
+        }
+        let fighting: Vec<&Actor> = self
+            .actors
+            .values()
+            .filter(|actor| self.is_fighting(actor))
+            .collect();
+        let unnoticed = |member: &Actor| {
+            let stealth = 10 + member.skill_modifier(Skill::Stealth);
+            fighting
+                .iter()
+                .filter(|observer| self.are_enemies(observer.id, member.id))
+                .all(|observer| stealth > self.passive_perception(observer, member))
+        };
+        let groups: BTreeSet<u32> = fighting.iter().map(|actor| actor.group).collect();
+        let sneaks_up = groups.into_iter().any(|group| {
+            fighting
+                .iter()
+                .filter(|actor| actor.group == group)
+                .all(|member| unnoticed(member))
+        });
+        Some(if sneaks_up { distance.min(5) } else { distance })
+    }
+
     pub fn are_allies(&self, actor1: ActorId, actor2: ActorId) -> bool {
         if let Some(group) = self.allies_of(actor1) {
             group.contains(&actor2)
@@ -110,41 +841,655 @@ impl State {
     }
 
     pub fn is_combat_over(&self) -> bool {
-        // combat is over when only one allied group remains alive
-        let mut living_groups = BTreeSet::new();
-        for actor in self.actors.values() {
-            if actor.is_alive() {
-                living_groups.insert(actor.group);
+        self.combat_outcome().is_some()
+    }
+
+    /// Resolves the advantage state of an attack roll from every applicable source.
+    pub fn attack_roll_settings(
+        &self,
+        attacker: &Actor,
+        target: &Actor,
+        melee: bool,
+        settings: RollSettings,
+    ) -> RollSettings {
+        let mut advantage = settings.advantage.is_advantage();
+        let mut disadvantage = settings.advantage.is_disadvantage();
+
+        if target.conditions.is_prone() {
+            if melee {
+                advantage = true;
+            } else {
+                disadvantage = true;
             }
         }
-        living_groups.len() <= 1
+        if attacker.conditions.is_prone() {
+            disadvantage = true;
+        }
+        if attacker.conditions.is_helped_against(target.id) {
+            advantage = true;
+        }
+        let rules = self.encounter.rules;
+        if (rules.pack_tactics || (rules.flanking && melee))
+            && self.ally_engaged_with(attacker.id, target.id)
+        {
+            advantage = true;
+        }
+        if !self.can_see(attacker, target) {
+            disadvantage = true;
+        }
+        if !self.can_see(target, attacker) {
+            advantage = true;
+        }
+        // ranged attacks suffer once the attacker is in melee with the target
+        if !melee
+            && self
+                .distance_between(attacker.id, target.id)
+                .is_some_and(|distance| distance <= 5)
+        {
+            disadvantage = true;
+        }
+
+        let advantage = self
+            .encounter
+            .advantage_override(attacker.id, target.id)
+            .unwrap_or_else(|| Advantage::from_sources(advantage, disadvantage));
+
+        RollSettings {
+            advantage,
+            ..settings
+        }
     }
 
     pub fn possible_targets(&self, actor_id: ActorId) -> Vec<ActorId> {
-        self.enemies_of(actor_id)
+        let mut targets = self.enemies_of(actor_id);
+        targets.retain(|target| {
+            !self.fallen.contains(target)
+                && !self
+                    .encounter
+                    .cover_between(actor_id, *target)
+                    .blocks_targeting()
+        });
+        targets
     }
 
-    pub fn possible_actions(&self, actor_id: ActorId) -> Vec<ActionType> {
-        if let Some(actor) = self.actors.get(&actor_id) {
-            let mut actions = vec![ActionType::Wait]; // can always wait
+    /// The farthest away the target of `action` can be, in feet, or `None` if distance doesn't
+    /// limit it.
+    pub fn action_reach(&self, action: &Action) -> Option<u32> {
+        match action {
+            Action::UnarmedStrike(_) | Action::Grapple(_) | Action::Shove(_) => Some(5),
+            Action::Attack(attack) => match self.items.get(&attack.weapon_used).map(|i| &i.inner) {
+                Some(ItemInner::Weapon(weapon)) => Some(weapon.reach()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 
-            if actor
-                .action_economy
-                .can_take_action(ActionEconomyUsage::Action)
-            {
-                actions.push(ActionType::Attack);
-                actions.push(ActionType::CastSpell);
-                actions.push(ActionType::UseItem);
-                actions.push(ActionType::Dash);
-                actions.push(ActionType::Disengage);
-                actions.push(ActionType::Dodge);
-                actions.push(ActionType::Help);
-                actions.push(ActionType::UnarmedStrike);
+    /// Whether `actor` can take `action` from where it stands now, without moving.
+    pub fn in_reach(&self, actor: ActorId, action: &Action) -> bool {
+        match (action.target(), self.action_reach(action)) {
+            (Some(target), Some(reach)) => self.positioning().in_reach(self, actor, target, reach),
+            _ => true,
+        }
+    }
+
+    /// How far apart two actors are in feet, or `None` if distance isn't being modeled.
+    pub fn distance_between(&self, from: ActorId, to: ActorId) -> Option<u32> {
+        self.positioning().distance(self, from, to)
+    }
+
+    /// Every concrete action `actor_id` could legally take with `usage` right now, with its
+    /// target, weapon, ally or ability filled in. Empty if the actor can't act at all. Attacks
+    /// come with default roll settings and no power attack, which are up to the policy.
+    pub fn possible_actions(&self, actor_id: ActorId, usage: ActionEconomyUsage) -> Vec<Action> {
+        let Some(actor) = self.actors.get(&actor_id) else {
+            return vec![];
+        };
+        let weapons: Vec<ItemId> = actor
+            .inventory
+            .items
+            .keys()
+            .filter(|item_id| {
+                matches!(
+                    self.items.get(item_id).map(|item| &item.inner),
+                    Some(ItemInner::Weapon(_))
+                )
+            })
+            .copied()
+            .collect();
+        let allies = self.allies_of(actor_id).unwrap_or_default();
+
+        let mut actions = vec![Action::Wait, Action::EscapeGrapple];
+        for target in self.possible_targets(actor_id) {
+            actions.push(Action::UnarmedStrike(UnarmedStrikeAction {
+                target,
+                attack_roll_settings: Default::default(),
+            }));
+            actions.extend(weapons.iter().map(|&weapon_used| {
+                Action::Attack(AttackAction {
+                    weapon_used,
+                    target,
+                    attack_roll_settings: Default::default(),
+                    power_attack: false,
+                })
+            }));
+            actions.push(Action::Grapple(GrappleAction { target }));
+            actions.push(Action::Shove(ShoveAction { target }));
+            actions.extend(
+                allies
+                    .iter()
+                    .map(|&ally| Action::Help(HelpAction { ally, target })),
+            );
+            actions.extend(actor.ready_abilities().map(|ability| {
+                Action::UseAbility(UseAbilityAction {
+                    actor: actor_id,
+                    ability,
+                    target,
+                })
+            }));
+        }
+        actions.retain(|action| self.is_legal(actor_id, action, usage));
+        actions
+    }
+
+    /// Whether `actor_id` can take `action` with `usage` right now: it's up and has that part of
+    /// its action economy left, the target is one it can pick and can reach, and whatever the
+    /// action needs (a weapon it carries, a grapple to escape, a ready ability) is there. Only the
+    /// main action comes with movement, so anything else has to be in reach already.
+    ///
+    /// Ammunition isn't tracked, so a ranged weapon always has a shot. Actions the integrator
+    /// can't carry out yet, like Dodge, are never legal.
+    pub fn is_legal(&self, actor_id: ActorId, action: &Action, usage: ActionEconomyUsage) -> bool {
+        let Some(actor) = self.actors.get(&actor_id) else {
+            return false;
+        };
+        if !actor.is_alive() || !actor.action_economy.can_take_action(usage) {
+            return false;
+        }
+
+        if let Some(target) = action.target()
+            && !matches!(action, Action::UseAbility(_) | Action::Help(_))
+        {
+            if !self.possible_targets(actor_id).contains(&target) {
+                return false;
+            }
+            // a target there's no way to is kept, so taking the action fails loudly rather than
+            // the actor waiting forever
+            let reachable = self.in_reach(actor_id, action)
+                || (usage == ActionEconomyUsage::Action
+                    && self.action_reach(action).is_some_and(|reach| {
+                        !matches!(
+                            self.positioning().approach(self, actor_id, target, reach),
+                            Ok(None)
+                        )
+                    }));
+            if !reachable {
+                return false;
             }
+        }
 
-            actions
-        } else {
-            vec![]
+        match action {
+            Action::Wait | Action::UnarmedStrike(_) => true,
+            Action::Attack(AttackAction { weapon_used, .. }) => {
+                actor.inventory.has_item(*weapon_used, 1)
+                    && matches!(
+                        self.items.get(weapon_used).map(|item| &item.inner),
+                        Some(ItemInner::Weapon(_))
+                    )
+            }
+            Action::Grapple(GrappleAction { target }) | Action::Shove(ShoveAction { target }) => {
+                self.get_actor(*target)
+                    .is_some_and(|target| actor.size.can_grapple_or_shove(target.size))
+            }
+            Action::Help(HelpAction { ally, target }) => {
+                *ally != actor_id
+                    && self.are_allies(actor_id, *ally)
+                    && self.get_actor(*ally).is_some_and(|ally| ally.is_alive())
+                    && self
+                        .get_actor(*target)
+                        .is_some_and(|target| target.is_alive())
+            }
+            Action::EscapeGrapple => actor.conditions.is_grappled(),
+            Action::UseAbility(UseAbilityAction {
+                actor: user,
+                ability,
+                target,
+            }) => {
+                *user == actor_id
+                    && actor.ability_ready(*ability)
+                    && self.possible_targets(actor_id).contains(target)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::items::{Armor, Weapon},
+        rules::{
+            actions::ActionType,
+            actor::ActorBuilder,
+            size::Size,
+            stats::{Stat, StatModifier},
+        },
+        simulation::{
+            integration::Integrator,
+            policy::PolicyBuilder,
+            roller::Roller,
+            state_tree::StateHash,
+            timed_effects::{Expiry, TimedChange, TimedEffect},
+            transition::Transition,
+        },
+        test_utils::brawl_policy,
+    };
+
+    #[test]
+    fn test_mount_size_restriction() {
+        let mut state = State::new();
+        let knight = state.add_actor(ActorBuilder::new("Knight").build());
+        let pony = state.add_actor(ActorBuilder::new("Pony").build());
+        let horse = state.add_actor(ActorBuilder::new("Horse").size(Size::Large).build());
+
+        assert!(state.mount_actor(knight, pony).is_err());
+        assert!(state.mount_actor(knight, horse).is_ok());
+        assert_eq!(state.rider_of(horse), Some(knight));
+
+        // one rider per mount, and no chains of riders
+        let squire = state.add_actor(ActorBuilder::new("Squire").build());
+        assert!(state.mount_actor(squire, horse).is_err());
+        let elephant = state.add_actor(ActorBuilder::new("Elephant").size(Size::Huge).build());
+        assert!(state.mount_actor(horse, elephant).is_err());
+        assert!(state.mount_actor(elephant, knight).is_err());
+    }
+
+    #[test]
+    fn test_mount_acts_after_rider() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let knight = state.add_actor(ActorBuilder::new("Knight").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        let horse = state.add_actor(ActorBuilder::new("Horse").size(Size::Large).build());
+        state.mount_actor(knight, horse)?;
+
+        Transition::InitiativeRoll {
+            actor: goblin,
+            roll: 15,
+        }
+        .apply(&mut state)?;
+        Transition::InitiativeRoll {
+            actor: knight,
+            roll: 12,
+        }
+        .apply(&mut state)?;
+
+        assert_eq!(state.initiative_order, vec![goblin, knight, horse]);
+        assert_eq!(state.get_actor(horse).unwrap().initiative, Some(12));
+
+        Transition::InitiativeRoll {
+            actor: knight,
+            roll: 18,
+        }
+        .apply(&mut state)?;
+        assert_eq!(state.initiative_order, vec![knight, horse, goblin]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_ignores_names() {
+        let mut a = State::new();
+        a.add_actor(ActorBuilder::new("Goblin").build());
+        a.add_item("Sword", ItemInner::Weapon(Weapon::test_sword()));
+        let mut b = State::new();
+        let goblin = b.add_actor(ActorBuilder::new("Goblin Boss").build());
+        b.add_item("Scimitar", ItemInner::Weapon(Weapon::test_sword()));
+        assert_eq!(StateHash::hash_state(&a), StateHash::hash_state(&b));
+
+        b.get_actor_mut(goblin).unwrap().health -= 1;
+        assert_ne!(StateHash::hash_state(&a), StateHash::hash_state(&b));
+    }
+
+    #[test]
+    fn test_hash_ignores_id_allocation() {
+        let mut a = State::new();
+        a.add_actor(ActorBuilder::new("Goblin").build());
+        let mut b = a.clone();
+        let summoned = b.add_actor(ActorBuilder::new("Summoned Wolf").build());
+        b.actors.remove(&summoned);
+
+        assert_ne!(a.ids, b.ids);
+        assert_eq!(StateHash::hash_state(&a), StateHash::hash_state(&b));
+    }
+
+    #[test]
+    fn test_clone_actor_items() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let sword = state.add_item("Longsword", ItemInner::Weapon(Weapon::test_sword()));
+        let mut hero = ActorBuilder::new("Hero").build();
+        hero.give_item(sword, 1);
+        hero.equipped_items.equip(sword);
+        let hero = state.add_actor(hero);
+        let horse = state.add_actor(ActorBuilder::new("Horse").size(Size::Large).build());
+        state.mount_actor(hero, horse)?;
+
+        let shared = state.clone_actor(hero, ItemCloning::ShareItems)?;
+        assert_eq!(state.items.len(), 1);
+        assert!(state.actors[&shared].inventory.has_item(sword, 1));
+        assert_eq!(state.actors[&shared].mount, None);
+        assert_eq!(state.rider_of(horse), Some(hero));
+
+        let copied = state.clone_actor(hero, ItemCloning::DeepCopyItems)?;
+        assert_eq!(state.items.len(), 2);
+        let copy = &state.actors[&copied];
+        let (&new_sword, _) = copy.inventory.items.iter().next().unwrap();
+        assert_ne!(new_sword, sword);
+        assert!(copy.equipped_items.items.contains(&new_sword));
+        assert_eq!(state.items[&new_sword].inner, state.items[&sword].inner);
+
+        assert!(
+            state
+                .clone_actor(ActorId(99), ItemCloning::ShareItems)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_actor_and_item() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let sword = state.add_item("Longsword", ItemInner::Weapon(Weapon::test_sword()));
+        let mut knight = ActorBuilder::new("Knight").build();
+        knight.give_item(sword, 1);
+        knight.equipped_items.equip(sword);
+        let knight = state.add_actor(knight);
+        let horse = state.add_actor(ActorBuilder::new("Horse").size(Size::Large).build());
+        let ogre = state.add_actor(ActorBuilder::new("Ogre").group(1).build());
+        state.mount_actor(knight, horse)?;
+        state.set_group_policy(1, PolicyBuilder::new().target_weight(horse, 5).build());
+        state
+            .encounter
+            .set_cover(ogre, horse, crate::rules::cover::Cover::Half);
+        state.encounter.objectives = vec![
+            Objective::Protect {
+                group: 0,
+                actor: horse,
+            },
+            Objective::Survive {
+                group: 0,
+                rounds: 3,
+            },
+        ];
+        state.recalculate_initiative_order();
+        state.current_turn_index = Some(2);
+        let helped = Condition::Helped {
+            by: horse,
+            against: ogre,
+        };
+        for (target, change, expiry) in [
+            (
+                knight,
+                TimedChange::from(helped),
+                Expiry::StartOfTurn(horse),
+            ),
+            (horse, Condition::Prone.into(), Expiry::StartOfTurn(horse)),
+            (
+                ogre,
+                StatModifier {
+                    stat: Stat::Strength,
+                    delta: -2,
+                }
+                .into(),
+                Expiry::StartOfTurnInRound(horse, 2),
+            ),
+            (knight, Condition::Prone.into(), Expiry::StartOfTurn(knight)),
+        ] {
+            let effect = TimedEffect::new(target, change, expiry, &state);
+            state.timed_effects.add(effect);
         }
+        let mut saddlebags = Inventory::default();
+        saddlebags.add_item(sword, 1);
+        state.loot.add_item(sword, 1);
+        state.dropped.insert(horse, saddlebags);
+
+        let removed = state.remove_actor(horse)?;
+        assert_eq!(removed.name, "Horse");
+        assert_eq!(state.actors[&knight].mount, None);
+        assert_eq!(state.initiative_order, [knight, ogre]);
+        assert_eq!(state.current_actor(), Some(ogre));
+        assert!(state.group_policies[&1].target_weights.is_empty());
+        assert!(state.encounter.cover[&ogre].is_empty());
+        assert_eq!(state.encounter.objectives.len(), 1);
+        assert_eq!(state.timed_effects.effects.len(), 1);
+        assert!(state.timed_effects.gives(knight, Condition::Prone));
+        assert!(!state.dropped.contains_key(&horse));
+        assert_eq!(state.loot.items[&sword], 1);
+        assert!(state.remove_actor(horse).is_err());
+
+        state.loot.add_item(sword, 1);
+        assert_eq!(state.remove_item(sword)?.name, "Longsword");
+        assert!(state.actors[&knight].inventory.items.is_empty());
+        assert!(state.actors[&knight].equipped_items.items.is_empty());
+        assert!(state.loot.items.is_empty());
+        assert!(state.remove_item(sword).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_for_targeting() {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        let boss = state.add_actor(ActorBuilder::new("Boss").group(1).tag("boss").build());
+        let minion = state.add_actor(ActorBuilder::new("Minion").group(1).tag("minion").build());
+
+        let policy = PolicyBuilder::new()
+            .tag_weight("boss", 5)
+            .tag_weight("minion", 2)
+            .target_weight(minion, 7)
+            .build();
+        assert_eq!(policy.target_weight(boss, &state), 5);
+        assert_eq!(policy.target_weight(minion, &state), 7);
+        assert_eq!(policy.target_weight(hero, &state), 1);
+
+        assert!(!state.all_tagged_down("boss"));
+        state.get_actor_mut(boss).unwrap().health = 0;
+        assert!(state.all_tagged_down("boss"));
+        assert!(!state.all_tagged_down("healer"));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let mut state = State::new();
+        let plate = state.add_item(
+            "Plate",
+            ItemInner::Armor(Armor::new(8).strength_requirement(15)),
+        );
+        let mut wizard = ActorBuilder::new("Wizard").stat(Stat::Strength, 8).build();
+        wizard.equipped_items.equip(plate);
+        let wizard = state.add_actor(wizard);
+        assert!(state.warnings().is_empty());
+
+        // a weak wizard in plate is allowed, but worth pointing out once the rule is on
+        state.encounter.rules.encumbrance = true;
+        assert!(state.validate().is_ok());
+        assert_eq!(
+            state.warnings(),
+            vec![(
+                wizard,
+                "Wizard lacks the Strength 15 to wear Plate, losing 10 ft of speed".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        assert!(state.validate().is_ok());
+
+        *state
+            .get_actor_mut(hero)
+            .unwrap()
+            .stats
+            .get_mut(Stat::Charisma) = 31;
+        let error = state.validate().unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Invalid ability scores on Hero: Charisma 31 is outside 1..=30"
+        );
+
+        *state
+            .get_actor_mut(hero)
+            .unwrap()
+            .stats
+            .get_mut(Stat::Charisma) = 10;
+        let mut sword = Weapon::test_sword();
+        sword.damage.die_size = 0;
+        state.add_item("Sword", ItemInner::Weapon(sword));
+        let error = state.validate().unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Invalid damage roll on Sword: Dice need at least one side"
+        );
+        state.items.clear();
+
+        // policies can't weigh anything below 0, whether an actor's own or its group's
+        state.set_group_policy(
+            1,
+            PolicyBuilder::new()
+                .action_weight(ActionType::Attack, -1)
+                .build(),
+        );
+        let error = state.validate().unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Invalid policy for group 1: Attack has a negative weight of -1"
+        );
+        state.set_actor_policy(hero, PolicyBuilder::new().target_weight(hero, -2).build());
+        let error = state.validate().unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!(
+                "Invalid policy on Hero: Actor {} has a negative target weight of -2",
+                hero.0
+            )
+        );
+
+        // homebrew effects load as written, and only fail to compile when validated
+        let mut json = serde_json::to_value(State::new()).unwrap();
+        let mut hero = serde_json::to_value(ActorBuilder::new("Hero").build()).unwrap();
+        hero["homebrew"] = serde_json::json!({ "on_hit": { "damage": "2d6 fire", "dc": 13 } });
+        hero["id"] = 0.into();
+        json["actors"] = serde_json::json!({ "0": hero });
+        let state = State::from_json_value(json).unwrap();
+        let error = state.validate().unwrap_err().to_string();
+        assert!(
+            error.starts_with("Invalid homebrew effect on Hero:"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_group_policies() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").max_health(200).build());
+        let goblins: Vec<ActorId> = (0..3)
+            .map(|_| state.add_actor(ActorBuilder::new("Goblin").group(1).build()))
+            .collect();
+        let brawl = brawl_policy();
+        let cower = PolicyBuilder::new()
+            .action_weight(ActionType::Dodge, 1)
+            .build();
+        state.set_group_policy(1, brawl.clone());
+        state.set_actor_policy(goblins[2], cower.clone());
+
+        let policy_of = |actor| state.policy_of(state.get_actor(actor).unwrap()).clone();
+        assert_eq!(policy_of(goblins[0]), brawl);
+        assert_eq!(policy_of(goblins[2]), cower);
+        assert!(policy_of(hero).is_empty());
+
+        // the inherited policy is the one acted on: the goblins punch the hero, who only waits
+        let results = Integrator::new(1, Roller::test_rng(), state.clone()).run()?;
+        let summary = crate::simulation::summary::RunSummary::new(&results);
+        assert!(summary.actors[0].mean_final_health < 200.0);
+
+        // and it's part of the combat state
+        let mut other = state.clone();
+        other.group_policies.clear();
+        assert_ne!(StateHash::hash_state(&state), StateHash::hash_state(&other));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_possible_actions() {
+        let mut state = State::new();
+        let sword = state.add_item("Sword", ItemInner::Weapon(Weapon::test_sword()));
+        let mut hero = ActorBuilder::new("Hero").build();
+        hero.give_item(sword, 1);
+        let hero = state.add_actor(hero);
+        let squire = state.add_actor(ActorBuilder::new("Squire").build());
+        let giant = state.add_actor(ActorBuilder::new("Giant").group(1).size(Size::Huge).build());
+        state.distance = Some(60);
+
+        let types = |state: &State, usage| {
+            state
+                .possible_actions(hero, usage)
+                .iter()
+                .map(Action::action_type)
+                .collect::<BTreeSet<_>>()
+        };
+        // too big to grapple or shove, and nothing to escape, but the hero can walk up and hit it
+        assert_eq!(
+            types(&state, ActionEconomyUsage::Action),
+            BTreeSet::from([
+                ActionType::Wait,
+                ActionType::UnarmedStrike,
+                ActionType::Attack,
+                ActionType::Help,
+            ])
+        );
+        assert!(state.is_legal(
+            hero,
+            &Action::Help(HelpAction {
+                ally: squire,
+                target: giant,
+            }),
+            ActionEconomyUsage::Action
+        ));
+        assert!(!state.is_legal(hero, &Action::Dodge, ActionEconomyUsage::Action));
+
+        // a bonus action doesn't come with movement
+        assert_eq!(
+            types(&state, ActionEconomyUsage::BonusAction),
+            BTreeSet::from([ActionType::Wait, ActionType::Help])
+        );
+        state.distance = Some(5);
+        assert!(types(&state, ActionEconomyUsage::BonusAction).contains(&ActionType::Attack));
+
+        // nor does the main action for a hero who can't move
+        state.distance = Some(60);
+        state.get_actor_mut(hero).unwrap().movement_speed = 0;
+        assert!(!types(&state, ActionEconomyUsage::Action).contains(&ActionType::UnarmedStrike));
+
+        // once the action is spent, or the hero is down, there's nothing left to do
+        state
+            .get_actor_mut(hero)
+            .unwrap()
+            .action_economy
+            .action_used = true;
+        assert!(
+            state
+                .possible_actions(hero, ActionEconomyUsage::Action)
+                .is_empty()
+        );
+        state.get_actor_mut(hero).unwrap().health = 0;
+        assert!(
+            state
+                .possible_actions(hero, ActionEconomyUsage::BonusAction)
+                .is_empty()
+        );
     }
 }
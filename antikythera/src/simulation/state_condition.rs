@@ -0,0 +1,703 @@
+//! A small expression language for the conditions queries ask about most often, so they can be
+//! written without a scripting runtime:
+//!
+//! ```text
+//! actor("Hero").alive && group(1).standing == 0
+//! !group(0).wiped || actor(3).health >= 5
+//! ```
+//!
+//! - `actor("Name")` or `actor(ID)` has the fields `alive` (above 0 HP), `dead`, `unconscious`,
+//!   `health`, `max_health` and `overkill`.
+//! - `group(N)` or `group("Name")` has the fields `standing` (how many are above 0 HP), `alive`
+//!   (how many aren't dead), `dead`, and `wiped` (nobody standing). Names are the ones given in
+//!   [`GroupInfo`](crate::simulation::groups::GroupInfo), ignoring case.
+//! - `round` is the round in progress, counting from 1. Once combat is over it's the round the
+//!   combat ended in, and before it starts it's 0.
+//! - Integers and `true`/`false` can be compared with `==`, `!=`, `<`, `<=`, `>` and `>=`, and
+//!   conditions combined with `&&`, `||`, `!` and parentheses.
+//! - `$name` is replaced with a parameter's value, as a number if it looks like one and as a
+//!   string otherwise, so one condition can be reused for different actors.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    rules::actor::ActorId,
+    simulation::{query::Query, state::State, state_tree::StateTree},
+    utils::Ratio,
+};
+
+/// How deep parentheses and `!` can nest, and how many operands `&&` and `||` can chain, before
+/// parsing gives up rather than risk the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Str(String),
+    LParen,
+    RParen,
+    Dot,
+    Not,
+    And,
+    Or,
+    Cmp(CmpOp),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ActorRef {
+    Name(String),
+    Id(ActorId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GroupRef {
+    Name(String),
+    Id(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ActorField {
+    Alive,
+    Dead,
+    Unconscious,
+    Health,
+    MaxHealth,
+    Overkill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GroupField {
+    Standing,
+    Alive,
+    Dead,
+    Wiped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Bool,
+    Int,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Bool(bool),
+    Int(i64),
+    Round,
+    Actor(ActorRef, ActorField),
+    Group(GroupRef, GroupField),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CmpOp, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Bool(bool),
+    Int(i64),
+}
+
+impl Value {
+    fn as_bool(self) -> bool {
+        matches!(self, Value::Bool(true))
+    }
+
+    fn as_int(self) -> i64 {
+        match self {
+            Value::Int(value) => value,
+            Value::Bool(value) => value as i64,
+        }
+    }
+}
+
+fn tokenize(source: &str, params: &BTreeMap<String, String>) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let word = |chars: &mut std::iter::Peekable<std::str::CharIndices>| {
+        let mut word = String::new();
+        while let Some((_, c)) = chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_') {
+            word.push(c);
+        }
+        word
+    };
+
+    while let Some(&(position, c)) = chars.peek() {
+        let two = |chars: &mut std::iter::Peekable<std::str::CharIndices>, next: char| {
+            chars.next();
+            chars.next_if(|(_, c)| *c == next).is_some()
+        };
+        let token =
+            match c {
+                c if c.is_whitespace() => {
+                    chars.next();
+                    continue;
+                }
+                '(' => {
+                    chars.next();
+                    Token::LParen
+                }
+                ')' => {
+                    chars.next();
+                    Token::RParen
+                }
+                '.' => {
+                    chars.next();
+                    Token::Dot
+                }
+                '&' if two(&mut chars, '&') => Token::And,
+                '|' if two(&mut chars, '|') => Token::Or,
+                '=' if two(&mut chars, '=') => Token::Cmp(CmpOp::Eq),
+                '!' if two(&mut chars, '=') => Token::Cmp(CmpOp::Ne),
+                '!' => Token::Not,
+                '<' if two(&mut chars, '=') => Token::Cmp(CmpOp::Le),
+                '<' => Token::Cmp(CmpOp::Lt),
+                '>' if two(&mut chars, '=') => Token::Cmp(CmpOp::Ge),
+                '>' => Token::Cmp(CmpOp::Gt),
+                '"' => {
+                    chars.next();
+                    let mut string = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, c)) => string.push(c),
+                            None => anyhow::bail!("Unterminated string starting at {}", position),
+                        }
+                    }
+                    Token::Str(string)
+                }
+                '$' => {
+                    chars.next();
+                    let name = word(&mut chars);
+                    let value = params
+                        .get(&name)
+                        .ok_or_else(|| anyhow::anyhow!("No value for parameter `${}`", name))?;
+                    match value.parse() {
+                        Ok(value) => Token::Int(value),
+                        Err(_) => Token::Str(value.clone()),
+                    }
+                }
+                c if c.is_ascii_digit() => {
+                    let digits = word(&mut chars);
+                    Token::Int(digits.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid number `{}` at {}", digits, position)
+                    })?)
+                }
+                c if c.is_alphabetic() || c == '_' => Token::Ident(word(&mut chars)),
+                // `&`, `|` and `=` only come in pairs
+                _ => anyhow::bail!("Unexpected `{}` at {}", c, position),
+            };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> anyhow::Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            found => anyhow::bail!("Expected {:?}, found {:?}", expected, found),
+        }
+    }
+
+    /// Counts one more level of nesting, failing past [`MAX_DEPTH`].
+    fn nest(&mut self) -> anyhow::Result<()> {
+        self.depth += 1;
+        anyhow::ensure!(
+            self.depth <= MAX_DEPTH,
+            "Condition is nested more than {} deep",
+            MAX_DEPTH
+        );
+        Ok(())
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let depth = self.depth;
+        let mut lhs = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            self.nest()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let depth = self.depth;
+        let mut lhs = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            self.nest()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            self.nest()?;
+            let expr = Expr::Not(Box::new(self.unary()?));
+            self.depth -= 1;
+            return Ok(expr);
+        }
+        let lhs = self.value()?;
+        if let Some(Token::Cmp(op)) = self.peek() {
+            let op = *op;
+            self.next();
+            let rhs = self.value()?;
+            return Ok(Expr::Compare(Box::new(lhs), op, Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn value(&mut self) -> anyhow::Result<Expr> {
+        match self.next() {
+            Some(Token::Int(value)) => Ok(Expr::Int(value)),
+            Some(Token::LParen) => {
+                self.nest()?;
+                let expr = self.or()?;
+                self.depth -= 1;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(Expr::Bool(true)),
+                "false" => Ok(Expr::Bool(false)),
+                "round" => Ok(Expr::Round),
+                "actor" => {
+                    self.expect(Token::LParen)?;
+                    let actor = match self.next() {
+                        Some(Token::Str(name)) => ActorRef::Name(name),
+                        Some(Token::Int(id)) => ActorRef::Id(ActorId(u32::try_from(id)?)),
+                        found => anyhow::bail!("Expected an actor name or ID, found {:?}", found),
+                    };
+                    self.expect(Token::RParen)?;
+                    let field = match self.field()?.as_str() {
+                        "alive" => ActorField::Alive,
+                        "dead" => ActorField::Dead,
+                        "unconscious" => ActorField::Unconscious,
+                        "health" => ActorField::Health,
+                        "max_health" => ActorField::MaxHealth,
+                        "overkill" => ActorField::Overkill,
+                        field => anyhow::bail!("Actors have no field `{}`", field),
+                    };
+                    Ok(Expr::Actor(actor, field))
+                }
+                "group" => {
+                    self.expect(Token::LParen)?;
+                    let group = match self.next() {
+                        Some(Token::Str(name)) => GroupRef::Name(name),
+                        Some(Token::Int(group)) => GroupRef::Id(u32::try_from(group)?),
+                        found => {
+                            anyhow::bail!("Expected a group name or number, found {:?}", found)
+                        }
+                    };
+                    self.expect(Token::RParen)?;
+                    let field = match self.field()?.as_str() {
+                        "standing" => GroupField::Standing,
+                        "alive" => GroupField::Alive,
+                        "dead" => GroupField::Dead,
+                        "wiped" => GroupField::Wiped,
+                        field => anyhow::bail!("Groups have no field `{}`", field),
+                    };
+                    Ok(Expr::Group(group, field))
+                }
+                ident => anyhow::bail!("Unknown name `{}`", ident),
+            },
+            found => anyhow::bail!("Expected a value, found {:?}", found),
+        }
+    }
+
+    fn field(&mut self) -> anyhow::Result<String> {
+        self.expect(Token::Dot)?;
+        match self.next() {
+            Some(Token::Ident(field)) => Ok(field),
+            found => anyhow::bail!("Expected a field name, found {:?}", found),
+        }
+    }
+}
+
+impl Expr {
+    fn check(&self) -> anyhow::Result<Type> {
+        let expect = |expr: &Expr, expected: Type, what: &str| {
+            let ty = expr.check()?;
+            anyhow::ensure!(ty == expected, "{} needs {:?} operands", what, expected);
+            Ok(())
+        };
+        Ok(match self {
+            Expr::Bool(_) => Type::Bool,
+            Expr::Int(_) | Expr::Round => Type::Int,
+            Expr::Actor(_, field) => match field {
+                ActorField::Alive | ActorField::Dead | ActorField::Unconscious => Type::Bool,
+                ActorField::Health | ActorField::MaxHealth | ActorField::Overkill => Type::Int,
+            },
+            Expr::Group(_, field) => match field {
+                GroupField::Wiped => Type::Bool,
+                GroupField::Standing | GroupField::Alive | GroupField::Dead => Type::Int,
+            },
+            Expr::Not(expr) => {
+                expect(expr, Type::Bool, "`!`")?;
+                Type::Bool
+            }
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+                expect(lhs, Type::Bool, "`&&` and `||`")?;
+                expect(rhs, Type::Bool, "`&&` and `||`")?;
+                Type::Bool
+            }
+            Expr::Compare(lhs, op, rhs) => {
+                let ty = lhs.check()?;
+                if matches!(op, CmpOp::Eq | CmpOp::Ne) {
+                    expect(rhs, ty, "`==` and `!=`")?;
+                } else {
+                    expect(lhs, Type::Int, "Ordering comparisons")?;
+                    expect(rhs, Type::Int, "Ordering comparisons")?;
+                }
+                Type::Bool
+            }
+        })
+    }
+
+    fn resolve(&mut self, state: &State) -> anyhow::Result<()> {
+        match self {
+            Expr::Actor(actor, _) => {
+                let id = match actor {
+                    ActorRef::Id(id) => *id,
+                    ActorRef::Name(name) => {
+                        let mut matches = state.actors.values().filter(|a| &a.name == name);
+                        let found = matches
+                            .next()
+                            .ok_or_else(|| anyhow::anyhow!("No actor named \"{}\"", name))?;
+                        anyhow::ensure!(
+                            matches.next().is_none(),
+                            "Several actors are named \"{}\"; refer to one by ID instead, like actor({})",
+                            name,
+                            found.id.0
+                        );
+                        found.id
+                    }
+                };
+                anyhow::ensure!(state.actors.contains_key(&id), "No actor with ID {}", id.0);
+                *actor = ActorRef::Id(id);
+            }
+            Expr::Not(expr) => expr.resolve(state)?,
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) | Expr::Compare(lhs, _, rhs) => {
+                lhs.resolve(state)?;
+                rhs.resolve(state)?;
+            }
+            Expr::Group(group, _) => {
+                if let GroupRef::Name(name) = group {
+                    let id = state
+                        .group_by_name(name)
+                        .ok_or_else(|| anyhow::anyhow!("No group named \"{}\"", name))?;
+                    *group = GroupRef::Id(id);
+                }
+            }
+            Expr::Bool(_) | Expr::Int(_) | Expr::Round => {}
+        }
+        Ok(())
+    }
+
+    fn eval(&self, state: &State) -> Value {
+        match self {
+            Expr::Bool(value) => Value::Bool(*value),
+            Expr::Int(value) => Value::Int(*value),
+            Expr::Round => Value::Int(match state.current_turn_index {
+                Some(_) => state.turn as i64 + 1,
+                None => state.final_round.unwrap_or(0) as i64,
+            }),
+            Expr::Actor(actor, field) => {
+                let actor = match actor {
+                    ActorRef::Id(id) => state.get_actor(*id),
+                    ActorRef::Name(name) => state.actors.values().find(|a| &a.name == name),
+                };
+                // actors that aren't there count as neither alive nor dead, with no HP
+                let Some(actor) = actor else {
+                    return match field {
+                        ActorField::Alive | ActorField::Dead | ActorField::Unconscious => {
+                            Value::Bool(false)
+                        }
+                        _ => Value::Int(0),
+                    };
+                };
+                match field {
+                    ActorField::Alive => Value::Bool(actor.is_alive()),
+                    ActorField::Dead => Value::Bool(actor.is_dead()),
+                    ActorField::Unconscious => Value::Bool(actor.is_unconscious()),
+                    ActorField::Health => Value::Int(actor.health as i64),
+                    ActorField::MaxHealth => Value::Int(actor.max_health as i64),
+                    ActorField::Overkill => Value::Int(actor.overkill as i64),
+                }
+            }
+            Expr::Group(group, field) => {
+                let group = match group {
+                    GroupRef::Id(id) => Some(*id),
+                    GroupRef::Name(name) => state.group_by_name(name),
+                };
+                let members = state.actors.values().filter(|a| Some(a.group) == group);
+                let count = |f: fn(&crate::rules::actor::Actor) -> bool| {
+                    members.clone().filter(|a| f(a)).count() as i64
+                };
+                match field {
+                    GroupField::Standing => Value::Int(count(|a| a.is_alive())),
+                    GroupField::Alive => Value::Int(count(|a| !a.is_dead())),
+                    GroupField::Dead => Value::Int(count(|a| a.is_dead())),
+                    GroupField::Wiped => Value::Bool(count(|a| a.is_alive()) == 0),
+                }
+            }
+            Expr::Not(expr) => Value::Bool(!expr.eval(state).as_bool()),
+            Expr::And(lhs, rhs) => {
+                Value::Bool(lhs.eval(state).as_bool() && rhs.eval(state).as_bool())
+            }
+            Expr::Or(lhs, rhs) => {
+                Value::Bool(lhs.eval(state).as_bool() || rhs.eval(state).as_bool())
+            }
+            Expr::Compare(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(state).as_int(), rhs.eval(state).as_int());
+                Value::Bool(match op {
+                    CmpOp::Eq => lhs == rhs,
+                    CmpOp::Ne => lhs != rhs,
+                    CmpOp::Lt => lhs < rhs,
+                    CmpOp::Le => lhs <= rhs,
+                    CmpOp::Gt => lhs > rhs,
+                    CmpOp::Ge => lhs >= rhs,
+                })
+            }
+        }
+    }
+}
+
+/// A parsed condition over a single state. See the [module docs](self) for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateCondition {
+    expr: Expr,
+}
+
+impl StateCondition {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        Self::parse_with_params(source, &BTreeMap::new())
+    }
+
+    /// Parses a condition, filling in each `$name` from `params`.
+    pub fn parse_with_params(
+        source: &str,
+        params: &BTreeMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source, params)?,
+            position: 0,
+            depth: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            anyhow::bail!("Unexpected {:?} after the end of the condition", token);
+        }
+        anyhow::ensure!(
+            expr.check()? == Type::Bool,
+            "A condition has to be true or false, not a number"
+        );
+        Ok(Self { expr })
+    }
+
+    /// Looks up the actors and groups the condition names in `state`, failing if any are missing or
+    /// ambiguous. Unresolved names are looked up in each state as it's checked.
+    pub fn resolve(&mut self, state: &State) -> anyhow::Result<()> {
+        self.expr.resolve(state)
+    }
+
+    pub fn matches(&self, state: &State) -> bool {
+        self.expr.eval(state).as_bool()
+    }
+}
+
+/// The probability of a state matching a [`StateCondition`], weighted by how often each state was
+/// reached.
+pub struct StateConditionProbability {
+    pub condition: StateCondition,
+    /// Only check the states combats ended in.
+    pub externals_only: bool,
+}
+
+impl StateConditionProbability {
+    pub fn new(condition: StateCondition) -> Self {
+        Self {
+            condition,
+            externals_only: true,
+        }
+    }
+
+    pub fn all_states(mut self) -> Self {
+        self.externals_only = false;
+        self
+    }
+}
+
+impl Query for StateConditionProbability {
+    type Output = Ratio;
+
+    /// The exact number of states matching the condition, out of every state checked.
+    fn query(&self, state_tree: &StateTree) -> anyhow::Result<Self::Output> {
+        state_tree.ensure_states_kept("State conditions")?;
+        let mut condition = self.condition.clone();
+        condition.resolve(state_tree.initial_state())?;
+
+        let mut ratio = Ratio::default();
+        state_tree.visit_states(self.externals_only, |state, hits| {
+            if condition.matches(state) {
+                ratio.numerator += hits;
+            }
+            ratio.denominator += hits;
+            true
+        });
+        Ok(ratio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::actor::Actor,
+        simulation::{
+            groups::GroupInfo, integration::Integrator, query::OutcomeConditionProbability,
+            roller::Roller,
+        },
+        test_utils::brawl_policy,
+    };
+
+    #[test]
+    fn test_condition_parsing() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(Actor::test_actor(0, "Hero"));
+        let mut goblin = Actor::test_actor(1, "Goblin");
+        goblin.group = 1;
+        let goblin = state.add_actor(goblin);
+
+        let check = |source: &str, state: &State| -> anyhow::Result<bool> {
+            let mut condition = StateCondition::parse(source)?;
+            condition.resolve(state)?;
+            Ok(condition.matches(state))
+        };
+        assert!(check(
+            r#"actor("Hero").alive && group(1).standing == 1"#,
+            &state
+        )?);
+        assert!(check("!group(0).wiped || false", &state)?);
+        assert!(check(&format!("actor({}).health >= 10", goblin.0), &state)?);
+        // `&&` binds tighter than `||`
+        assert!(check("true || false && false", &state)?);
+        assert!(!check("(true || false) && false", &state)?);
+        assert!(check("round == 0", &state)?);
+
+        state.get_actor_mut(hero).unwrap().health = 0;
+        assert!(check(
+            r#"actor("Hero").unconscious && group(0).wiped"#,
+            &state
+        )?);
+
+        let params = BTreeMap::from([("who".to_string(), "Goblin".to_string())]);
+        let condition = StateCondition::parse_with_params("actor($who).health > 5", &params)?;
+        assert!(condition.matches(&state));
+
+        for bad in [
+            "actor(\"Hero\").health",
+            "actor(\"Hero\").alive < 3",
+            "group(0).wiped &&",
+            "actor(\"Hero\").speed > 1",
+            "round = 1",
+            "actor($missing).alive",
+        ] {
+            assert!(
+                StateCondition::parse(bad).is_err(),
+                "{bad} should not parse"
+            );
+        }
+        let nested = format!(
+            "{}true{}",
+            "(".repeat(MAX_DEPTH + 1),
+            ")".repeat(MAX_DEPTH + 1)
+        );
+        let chained = "true && ".repeat(MAX_DEPTH + 1) + "true";
+        let negated = "!".repeat(MAX_DEPTH + 1) + "true";
+        for deep in [nested, chained, negated] {
+            assert!(StateCondition::parse(&deep).is_err());
+        }
+        assert!(check(&format!("{}true", "!".repeat(MAX_DEPTH)), &state)?);
+        let mut missing = StateCondition::parse(r#"actor("Nobody").alive"#)?;
+        assert!(missing.resolve(&state).is_err());
+
+        state.groups.insert(1, GroupInfo::new("Monsters"));
+        assert!(check(r#"group("monsters").standing == 1"#, &state)?);
+        let mut missing = StateCondition::parse(r#"group("Party").wiped"#)?;
+        assert!(missing.resolve(&state).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_condition_probability_matches_closure_query() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(Actor::test_actor(0, "Hero"));
+        let mut goblin = Actor::test_actor(1, "Goblin");
+        goblin.group = 1;
+        state.add_actor(goblin);
+        let policy = brawl_policy();
+        for actor in state.actors.values_mut() {
+            actor.policy = policy.clone();
+        }
+        let tree = Integrator::new(100, Roller::test_rng(), state)
+            .run()?
+            .state_tree;
+
+        let dsl = StateConditionProbability::new(StateCondition::parse(
+            r#"actor("Hero").alive && group(1).standing == 0"#,
+        )?)
+        .query(&tree)?;
+        let closure = OutcomeConditionProbability::new(move |state: &State| {
+            state.get_actor(hero).is_some_and(|hero| hero.is_alive())
+                && state
+                    .actors
+                    .values()
+                    .all(|actor| actor.group != 1 || !actor.is_alive())
+        })
+        .query(&tree)?;
+        assert_eq!(dsl, closure);
+        assert!(dsl.numerator > 0 && dsl.numerator < dsl.denominator);
+
+        // terminal states remember the round combat ended in
+        let ended = |source: &str| {
+            StateConditionProbability::new(StateCondition::parse(source)?).query(&tree)
+        };
+        assert_eq!(ended("round >= 1")?, Ratio::new(100, 100));
+        assert_eq!(ended("round == 0")?, Ratio::new(0, 100));
+        Ok(())
+    }
+}
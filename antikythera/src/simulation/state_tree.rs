@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, VecDeque},
     fmt::Debug,
     num::NonZeroU64,
 };
@@ -7,7 +7,13 @@ use std::{
 use rustc_hash::FxHashSet;
 use serde::{Deserialize, Serialize};
 
-use crate::simulation::{state::State, transition::Transition};
+use crate::{
+    rules::{actor::Actor, items::Item},
+    simulation::{
+        state::{HashableState, State},
+        transition::Transition,
+    },
+};
 
 pub type NodeIndex = u32;
 pub type EdgeIndex = u32;
@@ -39,7 +45,7 @@ impl StateHash {
     pub fn hash_state(state: &State) -> Self {
         use std::hash::{Hash, Hasher};
         let mut hasher = rustc_hash::FxHasher::default();
-        state.hash(&mut hasher);
+        HashableState(state).hash(&mut hasher);
         StateHash(hasher.finish())
     }
 }
@@ -71,6 +77,49 @@ pub struct Edge {
     pub hits: NonZeroU64,
 }
 
+/// All edges between a pair of nodes, one per distinct transition.
+///
+/// Transitions that record a fact without changing the state (such as a contest result) can
+/// connect the same pair of nodes as other transitions, so a pair may carry more than one edge.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct Edges(Vec<Edge>);
+
+impl Edges {
+    pub fn iter(&self) -> impl Iterator<Item = &Edge> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn total_hits(&self) -> u64 {
+        self.0.iter().map(|edge| edge.hits.get()).sum()
+    }
+}
+
+impl<'de> Deserialize<'de> for Edges {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Older results stored a single edge per node pair.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Many(Vec<Edge>),
+            One(Edge),
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Many(edges) => Edges(edges),
+            Repr::One(edge) => Edges(vec![edge]),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[repr(transparent)]
 pub struct EdgeKey(u64);
@@ -92,21 +141,92 @@ impl EdgeKey {
     }
 }
 
+/// How much of each combat a [`StateTree`] keeps. Keeping less makes runs faster and results
+/// smaller, at the cost of the queries that need what was left out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TreeMode {
+    /// Every state and transition.
+    #[default]
+    Full,
+    /// Only the states combats end in, without the transitions that led there. The run summary
+    /// and queries over terminal states still work; anything that looks at transitions, like
+    /// hit rates, fails.
+    TerminalOnly,
+    /// Nothing; combats are only seen by hooks and the recorded statistics, like the HP time
+    /// series, and queries over states fail. The fastest mode, for runs that only need those.
+    None,
+}
+
+/// Roughly how many bytes each part of a [`StateTree`] takes up. It's an estimate from the
+/// sizes of the tree's collections rather than an exact count of allocations, cheap enough to
+/// take between every few combats.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeMemoryUsage {
+    /// Node hits and each node's list of neighbors.
+    pub nodes: usize,
+    /// The transitions between nodes.
+    pub edges: usize,
+    /// The lookup from state hashes to nodes.
+    pub state_cache: usize,
+    /// The states kept in [`TreeMode::TerminalOnly`].
+    pub terminal_states: usize,
+}
+
+impl TreeMemoryUsage {
+    pub fn total(&self) -> usize {
+        self.nodes + self.edges + self.state_cache + self.terminal_states
+    }
+
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        let mb = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+        write!(
+            f,
+            "{:.1} MB ({:.1} MB nodes, {:.1} MB edges, {:.1} MB state cache, {:.1} MB terminal states)",
+            mb(self.total()),
+            mb(self.nodes),
+            mb(self.edges),
+            mb(self.state_cache),
+            mb(self.terminal_states)
+        )
+    }
+}
+
+/// A state some combat ended in, and how many combats ended there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalState {
+    pub state: State,
+    pub hits: NonZeroU64,
+    /// How many of the hits ended after each number of rounds. `state` holds one of them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rounds: BTreeMap<u64, u64>,
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct StateTree {
+    #[serde(default)]
+    mode: TreeMode,
     initial_state: State,
     root: NodeIndex,
     nodes: Vec<NonZeroU64>,
     total_node_hits: u64,
     total_edge_hits: u64,
     state_cache: HashMap<StateHash, NodeIndex, NoHashBuildHasher>,
-    edge_cache: BTreeMap<EdgeKey, Edge>,
+    edge_cache: BTreeMap<EdgeKey, Edges>,
     neighbors: Vec<Vec<NodeIndex>>,
+    /// Kept instead of nodes and edges in [`TreeMode::TerminalOnly`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    terminal_states: BTreeMap<StateHash, TerminalState>,
+    /// How many combats ending at each node of a [`TreeMode::Full`] tree lasted each number of
+    /// rounds. The round count is left out of the state hash, so combats that end alike share a
+    /// node however long they took.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    final_rounds: BTreeMap<NodeIndex, BTreeMap<u64, u64>>,
 }
 
 impl StateTree {
     pub fn new(initial_state: State) -> Self {
         let mut this = Self {
+            mode: TreeMode::Full,
             initial_state,
             root: 0,
             nodes: Vec::new(),
@@ -115,11 +235,85 @@ impl StateTree {
             state_cache: HashMap::default(),
             edge_cache: BTreeMap::default(),
             neighbors: Vec::new(),
+            terminal_states: BTreeMap::new(),
+            final_rounds: BTreeMap::new(),
         };
         this.root = this.add_node(StateHash::hash_state(&this.initial_state));
         this
     }
 
+    /// A tree that keeps only what `mode` asks for. The root is always there.
+    pub fn with_mode(initial_state: State, mode: TreeMode) -> Self {
+        let mut this = Self::new(initial_state);
+        this.mode = mode;
+        this
+    }
+
+    pub fn mode(&self) -> TreeMode {
+        self.mode
+    }
+
+    /// Fails if the tree kept none of the states its combats ended in, as with
+    /// [`TreeMode::None`], so that queries over them say so instead of finding nothing. `what`
+    /// names what needed them, for the error.
+    pub fn ensure_states_kept(&self, what: &str) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.mode != TreeMode::None,
+            "{} need the states combats ended in, but this run only kept {:?}",
+            what,
+            self.mode
+        );
+        Ok(())
+    }
+
+    /// Records a state a combat ended in, for [`TreeMode::TerminalOnly`] trees.
+    pub fn add_terminal_state(&mut self, state: &State) {
+        let rounds = BTreeMap::from([(state.final_round.unwrap_or(0), 1)]);
+        self.insert_terminal_state(
+            StateHash::hash_state(state),
+            state,
+            NonZeroU64::MIN,
+            &rounds,
+        );
+    }
+
+    fn insert_terminal_state(
+        &mut self,
+        state_hash: StateHash,
+        state: &State,
+        hits: NonZeroU64,
+        rounds: &BTreeMap<u64, u64>,
+    ) {
+        self.total_node_hits = self.total_node_hits.saturating_add(hits.get());
+        let terminal = self
+            .terminal_states
+            .entry(state_hash)
+            .and_modify(|existing| existing.hits = existing.hits.saturating_add(hits.get()))
+            .or_insert_with(|| TerminalState {
+                state: state.clone(),
+                hits,
+                rounds: BTreeMap::new(),
+            });
+        add_rounds(&mut terminal.rounds, rounds);
+    }
+
+    /// The states combats ended in, when only those were kept.
+    pub fn terminal_states(&self) -> impl Iterator<Item = &TerminalState> {
+        self.terminal_states.values()
+    }
+
+    /// How many distinct states combats ended in: the leaves of a full tree, or the terminal
+    /// states kept instead of one.
+    pub fn terminal_state_count(&self) -> usize {
+        match self.mode {
+            TreeMode::Full => (0..self.nodes.len() as NodeIndex)
+                .filter(|node| self.neighbors(*node).next().is_none())
+                .count(),
+            TreeMode::TerminalOnly => self.terminal_states.len(),
+            TreeMode::None => 0,
+        }
+    }
+
     pub fn add_state(&mut self, state: &State) -> NodeIndex {
         let state_hash = StateHash::hash_state(state);
         self.add_node(state_hash)
@@ -132,57 +326,75 @@ impl StateTree {
         transition: Transition,
     ) -> NodeIndex {
         let to = self.add_state(new_state);
+        if let (Transition::EndCombat, Some(round)) = (&transition, new_state.final_round) {
+            *self
+                .final_rounds
+                .entry(to)
+                .or_default()
+                .entry(round)
+                .or_default() += 1;
+        }
         self.add_edge(from, to, transition);
         to
     }
 
     pub fn add_node(&mut self, state_hash: StateHash) -> NodeIndex {
-        self.total_node_hits = self.total_node_hits.saturating_add(1);
+        self.insert_node(state_hash, NonZeroU64::MIN)
+    }
+
+    pub fn add_edge(
+        &mut self,
+        from: NodeIndex,
+        to: NodeIndex,
+        transition: Transition,
+    ) -> Option<EdgeKey> {
+        Some(self.insert_edge(from, to, transition, NonZeroU64::MIN))
+    }
+
+    fn insert_node(&mut self, state_hash: StateHash, hits: NonZeroU64) -> NodeIndex {
+        self.total_node_hits = self.total_node_hits.saturating_add(hits.get());
 
         // Check if the node already exists
         if let Some(&existing_index) = self.state_cache.get(&state_hash)
             && let Some(node_hits) = self.nodes.get_mut(existing_index as usize)
         {
             // Increment hits if it exists
-            *node_hits = node_hits.saturating_add(1);
+            *node_hits = node_hits.saturating_add(hits.get());
 
             existing_index
         } else {
             // Add the new node
             let node = self.nodes.len() as NodeIndex;
-            self.nodes.push(NonZeroU64::MIN); // Start with 1 hit
+            self.nodes.push(hits);
             self.state_cache.insert(state_hash, node);
 
             node
         }
     }
 
-    pub fn add_edge(
+    fn insert_edge(
         &mut self,
         from: NodeIndex,
         to: NodeIndex,
         transition: Transition,
-    ) -> Option<EdgeKey> {
-        // Check if the edge already exists
+        hits: NonZeroU64,
+    ) -> EdgeKey {
         let key = EdgeKey::new(from, to);
-        if let Some(existing_edge) = self.edge_cache.get_mut(&key) {
-            debug_assert_eq!(
-                existing_edge.transition, transition,
-                "Discontinuity in transition graph detected: existing transition does not match new transition for edge from {:?} to {:?}",
-                from, to
-            );
-            // Increment hits if it exists
-            existing_edge.hits = existing_edge.hits.saturating_add(1);
-            self.total_edge_hits = self.total_edge_hits.saturating_add(1);
-            Some(key)
+        self.total_edge_hits = self.total_edge_hits.saturating_add(hits.get());
+
+        // Check if the edge already exists
+        if let Some(edges) = self.edge_cache.get_mut(&key) {
+            if let Some(existing_edge) = edges.0.iter_mut().find(|e| e.transition == transition) {
+                // Increment hits if it exists
+                existing_edge.hits = existing_edge.hits.saturating_add(hits.get());
+            } else {
+                // Another transition between the same pair of states
+                edges.0.push(Edge { transition, hits });
+            }
         } else {
             // Add the new edge
-            let edge = Edge {
-                transition,
-                hits: NonZeroU64::MIN, // Start with 1 hit
-            };
-            self.edge_cache.insert(key, edge);
-            self.total_edge_hits = self.total_edge_hits.saturating_add(1);
+            self.edge_cache
+                .insert(key, Edges(vec![Edge { transition, hits }]));
 
             // Update neighbors
             if let Some(neighbors) = self.neighbors.get_mut(from as usize) {
@@ -192,9 +404,196 @@ impl StateTree {
                 self.neighbors.resize((from + 1) as usize, Vec::new());
                 self.neighbors[from as usize].push(to);
             }
+        }
+
+        key
+    }
+
+    /// Adds the hits of another tree over the same initial state into this one, so results from
+    /// separate runs can be combined. States and transitions seen by both trees are matched up
+    /// and their hits summed.
+    pub fn merge(&mut self, other: &StateTree) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.initial_state == other.initial_state,
+            "Cannot merge state trees with different initial states"
+        );
+        anyhow::ensure!(
+            self.mode == other.mode,
+            "Cannot merge a {:?} state tree into a {:?} one",
+            other.mode,
+            self.mode
+        );
+        for (&hash, terminal) in &other.terminal_states {
+            self.insert_terminal_state(hash, &terminal.state, terminal.hits, &terminal.rounds);
+        }
 
-            Some(key)
+        // Visit the other tree's nodes in index order so new nodes get deterministic indices
+        let mut hashes = vec![None; other.nodes.len()];
+        for (&hash, &index) in &other.state_cache {
+            hashes[index as usize] = Some(hash);
         }
+        let mut remap = Vec::with_capacity(other.nodes.len());
+        for (hash, &hits) in hashes.into_iter().zip(&other.nodes) {
+            let hash =
+                hash.ok_or_else(|| anyhow::anyhow!("State tree node is missing its hash"))?;
+            remap.push(self.insert_node(hash, hits));
+        }
+
+        for (key, edges) in &other.edge_cache {
+            let from = remap[key.source() as usize];
+            let to = remap[key.target() as usize];
+            for edge in edges.iter() {
+                self.insert_edge(from, to, edge.transition.clone(), edge.hits);
+            }
+        }
+        for (&node, rounds) in &other.final_rounds {
+            add_rounds(
+                self.final_rounds.entry(remap[node as usize]).or_default(),
+                rounds,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Drops every edge hit fewer than `min_hits` times and every node visited fewer than
+    /// `min_hits` times, along with anything no longer reachable from the root. The root is
+    /// always kept. Node indices are reassigned, and the totals only count what's left.
+    pub fn prune_below_hits(&mut self, min_hits: u64) {
+        let keep_edge = |edge: &Edge| edge.hits.get() >= min_hits;
+        let keep_node = |node: NodeIndex| self.nodes[node as usize].get() >= min_hits;
+
+        // Walk the kept part of the tree from the root, numbering nodes as they're reached
+        let mut remap: Vec<Option<NodeIndex>> = vec![None; self.nodes.len()];
+        let mut order = vec![self.root];
+        remap[self.root as usize] = Some(0);
+        let mut queue = VecDeque::from([self.root]);
+        while let Some(node) = queue.pop_front() {
+            for neighbor in self.neighbors(node) {
+                if remap[neighbor as usize].is_some() || !keep_node(neighbor) {
+                    continue;
+                }
+                let reached = self
+                    .get_edges(node, neighbor)
+                    .is_some_and(|edges| edges.iter().any(keep_edge));
+                if reached {
+                    remap[neighbor as usize] = Some(order.len() as NodeIndex);
+                    order.push(neighbor);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        let mut pruned = Self {
+            mode: self.mode,
+            initial_state: std::mem::take(&mut self.initial_state),
+            root: 0,
+            nodes: order
+                .iter()
+                .map(|&node| self.nodes[node as usize])
+                .collect(),
+            total_node_hits: 0,
+            total_edge_hits: 0,
+            state_cache: HashMap::default(),
+            edge_cache: BTreeMap::default(),
+            neighbors: Vec::new(),
+            terminal_states: std::mem::take(&mut self.terminal_states),
+            final_rounds: std::mem::take(&mut self.final_rounds)
+                .into_iter()
+                .filter_map(|(node, rounds)| Some((remap[node as usize]?, rounds)))
+                .collect(),
+        };
+        pruned
+            .terminal_states
+            .retain(|_, terminal| terminal.hits.get() >= min_hits);
+        pruned.total_node_hits = pruned.nodes.iter().map(|hits| hits.get()).sum::<u64>()
+            + pruned
+                .terminal_states
+                .values()
+                .map(|terminal| terminal.hits.get())
+                .sum::<u64>();
+        for (&hash, &index) in &self.state_cache {
+            if let Some(new_index) = remap[index as usize] {
+                pruned.state_cache.insert(hash, new_index);
+            }
+        }
+        for &node in &order {
+            let from = remap[node as usize].unwrap();
+            for neighbor in self.neighbors(node) {
+                let Some(to) = remap[neighbor as usize] else {
+                    continue;
+                };
+                if let Some(edges) = self.get_edges(node, neighbor) {
+                    for edge in edges.iter().filter(|edge| keep_edge(edge)) {
+                        pruned.insert_edge(from, to, edge.transition.clone(), edge.hits);
+                    }
+                }
+            }
+        }
+
+        *self = pruned;
+    }
+
+    /// Switches to keeping less of each combat, converting what's already been kept: a full
+    /// tree keeps the states its combats ended in, and dropping to [`TreeMode::None`] keeps
+    /// nothing. Combats have to be finished, as every leaf is taken to be where one ended.
+    pub fn reduce_to(&mut self, mode: TreeMode) -> anyhow::Result<()> {
+        let terminal_states = match (self.mode, mode) {
+            (from, to) if from == to => return Ok(()),
+            (TreeMode::Full, TreeMode::TerminalOnly) => {
+                // visited once per round count, which the new tree sums up again
+                let mut terminal_states = Vec::new();
+                self.visit_states(true, |state, hits| {
+                    terminal_states.push((state.clone(), hits));
+                    true
+                });
+                terminal_states
+            }
+            (_, TreeMode::None) => Vec::new(),
+            (from, to) => anyhow::bail!("Cannot turn a {:?} state tree into a {:?} one", from, to),
+        };
+
+        *self = Self::with_mode(std::mem::take(&mut self.initial_state), mode);
+        for (state, hits) in terminal_states {
+            let Some(hits) = NonZeroU64::new(hits) else {
+                continue;
+            };
+            let rounds = BTreeMap::from([(state.final_round.unwrap_or(0), hits.get())]);
+            self.insert_terminal_state(StateHash::hash_state(&state), &state, hits, &rounds);
+        }
+        Ok(())
+    }
+
+    /// Roughly how much memory the tree takes up. See [`TreeMemoryUsage`].
+    pub fn memory_usage(&self) -> TreeMemoryUsage {
+        use std::mem::size_of;
+
+        // a B-tree node holds up to 11 entries, usually about two thirds full
+        const BTREE_OVERHEAD: f64 = 1.5;
+        // hash maps keep a control byte per slot
+        let hash_slot = size_of::<StateHash>() + size_of::<NodeIndex>() + 1;
+        // each state's actors and items are stored apart from it
+        let state_size = size_of::<TerminalState>()
+            + self.initial_state.actors.len() * size_of::<Actor>()
+            + self.initial_state.items.len() * size_of::<Item>();
+
+        TreeMemoryUsage {
+            nodes: self.nodes.capacity() * size_of::<NonZeroU64>()
+                + self.neighbors.capacity() * size_of::<Vec<NodeIndex>>()
+                // every edge puts its target in its source's neighbors
+                + self.edge_cache.len() * size_of::<NodeIndex>(),
+            edges: (self.edge_cache.len() as f64
+                * (size_of::<EdgeKey>() + size_of::<Edges>() + size_of::<Edge>()) as f64
+                * BTREE_OVERHEAD) as usize,
+            state_cache: self.state_cache.capacity() * hash_slot,
+            terminal_states: (self.terminal_states.len() as f64
+                * (size_of::<StateHash>() + state_size) as f64
+                * BTREE_OVERHEAD) as usize,
+        }
+    }
+
+    pub fn initial_state(&self) -> &State {
+        &self.initial_state
     }
 
     pub fn root(&self) -> NodeIndex {
@@ -206,7 +605,15 @@ impl StateTree {
     }
 
     pub fn edge_count(&self) -> usize {
-        self.edge_cache.len()
+        self.edge_cache.values().map(Edges::len).sum()
+    }
+
+    pub fn total_node_hits(&self) -> u64 {
+        self.total_node_hits
+    }
+
+    pub fn total_edge_hits(&self) -> u64 {
+        self.total_edge_hits
     }
 
     pub fn neighbors<'a>(&'a self, node: NodeIndex) -> impl Iterator<Item = NodeIndex> + 'a {
@@ -220,24 +627,89 @@ impl StateTree {
         self.nodes.get(index as usize).copied()
     }
 
+    /// Returns the first edge recorded between two nodes. Any of them reaches the same state.
     pub fn get_edge(&self, from: NodeIndex, to: NodeIndex) -> Option<&Edge> {
+        self.get_edges(from, to).and_then(|edges| edges.0.first())
+    }
+
+    pub fn get_edges(&self, from: NodeIndex, to: NodeIndex) -> Option<&Edges> {
         let key = EdgeKey::new(from, to);
         self.edge_cache.get(&key)
     }
 
+    /// Iterates over every edge in the tree along with the nodes it connects.
+    pub fn edges(&self) -> impl Iterator<Item = (NodeIndex, NodeIndex, &Edge)> {
+        self.edge_cache
+            .iter()
+            .flat_map(|(key, edges)| edges.iter().map(|edge| (key.source(), key.target(), edge)))
+    }
+
+    /// Calls `visitor` with every state and its hits, or just the states combats ended in. A
+    /// visitor returning `false` skips the states reached through that one. A state combats
+    /// ended in after different numbers of rounds is visited once per round count, with
+    /// [`State::final_round`] set and the hits that ended after that many rounds.
+    /// [`TreeMode::TerminalOnly`] trees only have terminal states to visit either way, and
+    /// [`TreeMode::None`] trees have none.
     pub fn visit_states<F>(&self, externals_only: bool, mut visitor: F)
     where
         F: FnMut(&State, u64) -> bool,
     {
+        match self.mode {
+            TreeMode::Full => {}
+            TreeMode::TerminalOnly => {
+                for terminal in self.terminal_states.values() {
+                    visit_rounds(
+                        &terminal.state,
+                        terminal.hits.get(),
+                        &terminal.rounds,
+                        &mut visitor,
+                    );
+                }
+                return;
+            }
+            TreeMode::None => return,
+        }
         self.visit_states_recursive(
             externals_only,
             self.root,
             &self.initial_state,
             &mut FxHashSet::default(),
-            &mut visitor,
+            &mut |node, state, hits| match self.final_rounds.get(&node) {
+                Some(rounds) => visit_rounds(state, hits, rounds, &mut visitor),
+                None => visitor(state, hits),
+            },
         )
     }
 
+    /// Recomputes the hash of every node's state, for trees saved before the state hash
+    /// changed. Nodes that turn out to share a state keep their own hits, but only the first
+    /// is found by hash.
+    pub(crate) fn rebuild_state_cache(&mut self) {
+        let mut state_cache = HashMap::default();
+        self.visit_states_recursive(
+            false,
+            self.root,
+            &self.initial_state,
+            &mut FxHashSet::default(),
+            &mut |node, state, _| {
+                state_cache
+                    .entry(StateHash::hash_state(state))
+                    .or_insert(node);
+                true
+            },
+        );
+        self.state_cache = state_cache;
+        for terminal in std::mem::take(&mut self.terminal_states).into_values() {
+            self.total_node_hits -= terminal.hits.get();
+            self.insert_terminal_state(
+                StateHash::hash_state(&terminal.state),
+                &terminal.state,
+                terminal.hits,
+                &terminal.rounds,
+            );
+        }
+    }
+
     fn visit_states_recursive<F>(
         &self,
         externals_only: bool,
@@ -246,7 +718,7 @@ impl StateTree {
         visited: &mut FxHashSet<NodeIndex>,
         visitor: &mut F,
     ) where
-        F: FnMut(&State, u64) -> bool,
+        F: FnMut(NodeIndex, &State, u64) -> bool,
     {
         if !visited.insert(node) {
             return; // Already visited
@@ -261,7 +733,7 @@ impl StateTree {
         // Visit the state at the current node
         let keep_going = if should_visit {
             let hits = self.get_node_hits(node).map_or(0, |h| h.get());
-            visitor(state, hits)
+            visitor(node, state, hits)
         } else {
             true
         };
@@ -282,3 +754,197 @@ impl StateTree {
         }
     }
 }
+
+fn add_rounds(into: &mut BTreeMap<u64, u64>, rounds: &BTreeMap<u64, u64>) {
+    for (&round, &hits) in rounds {
+        let count = into.entry(round).or_default();
+        *count = count.saturating_add(hits);
+    }
+}
+
+/// Visits `state` once per round count combats ended after, or just once with all its hits if
+/// the round counts weren't kept, as in trees saved before they were.
+fn visit_rounds<F>(state: &State, hits: u64, rounds: &BTreeMap<u64, u64>, visitor: &mut F) -> bool
+where
+    F: FnMut(&State, u64) -> bool,
+{
+    if rounds.is_empty() {
+        return visitor(state, hits);
+    }
+    let mut keep_going = false;
+    for (&round, &round_hits) in rounds {
+        if state.final_round == Some(round) {
+            keep_going |= visitor(state, round_hits);
+        } else {
+            let mut state = state.clone();
+            state.final_round = Some(round);
+            keep_going |= visitor(&state, round_hits);
+        }
+    }
+    keep_going
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{ActorBuilder, Integrator, Roller, Stat, State};
+    use crate::test_utils::brawl_policy;
+
+    fn run(state: &State, seed: u64) -> anyhow::Result<StateTree> {
+        let mut integrator = Integrator::new(20, Roller::from_seed(seed), state.clone());
+        Ok(integrator.run()?.state_tree)
+    }
+
+    #[test]
+    fn test_merge_and_prune() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let policy = brawl_policy();
+        for (name, group) in [("Fighter", 0), ("Goblin", 1)] {
+            let mut actor = ActorBuilder::new(name)
+                .group(group)
+                .stat(Stat::Strength, 14)
+                .max_health(6)
+                .build();
+            actor.policy = policy.clone();
+            state.add_actor(actor);
+        }
+
+        let a = run(&state, 1)?;
+        let b = run(&state, 2)?;
+        let mut merged = a.clone();
+        merged.merge(&b)?;
+        assert_eq!(
+            merged.total_node_hits(),
+            a.total_node_hits() + b.total_node_hits()
+        );
+        assert_eq!(
+            merged.total_edge_hits(),
+            a.total_edge_hits() + b.total_edge_hits()
+        );
+        assert_eq!(
+            merged.get_node_hits(merged.root()).unwrap().get(),
+            a.get_node_hits(a.root()).unwrap().get() + b.get_node_hits(b.root()).unwrap().get()
+        );
+        assert!(merged.node_count() >= a.node_count().max(b.node_count()));
+        assert!(merged.node_count() <= a.node_count() + b.node_count());
+
+        // merging a tree into itself doubles every hit without adding anything
+        let mut doubled = a.clone();
+        doubled.merge(&a)?;
+        assert_eq!(doubled.node_count(), a.node_count());
+        assert_eq!(doubled.edge_count(), a.edge_count());
+        assert_eq!(doubled.total_node_hits(), a.total_node_hits() * 2);
+
+        let mut other_state = state.clone();
+        other_state.add_actor(ActorBuilder::new("Bystander").build());
+        assert!(merged.merge(&StateTree::new(other_state)).is_err());
+
+        let mut pruned = merged.clone();
+        pruned.prune_below_hits(2);
+        assert!(pruned.node_count() < merged.node_count());
+        assert_eq!(pruned.root(), 0);
+        assert_eq!(pruned.initial_state(), merged.initial_state());
+        for node in 0..pruned.node_count() as NodeIndex {
+            assert!(pruned.get_node_hits(node).unwrap().get() >= 2);
+        }
+        assert!(pruned.edges().all(|(_, _, edge)| edge.hits.get() >= 2));
+        let mut visited = 0;
+        pruned.visit_states(false, |_, _| {
+            visited += 1;
+            true
+        });
+        assert_eq!(visited, pruned.node_count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_terminal_only() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let policy = brawl_policy();
+        for (name, group) in [("Fighter", 0), ("Goblin", 1)] {
+            let mut actor = ActorBuilder::new(name).group(group).max_health(12).build();
+            actor.policy = policy.clone();
+            state.add_actor(actor);
+        }
+        let run_with = |mode| -> anyhow::Result<StateTree> {
+            let mut integrator = Integrator::new(200, Roller::from_seed(5), state.clone());
+            integrator.set_tree_mode(mode);
+            Ok(integrator.run()?.state_tree)
+        };
+        let full = run_with(TreeMode::Full)?;
+        let terminal = run_with(TreeMode::TerminalOnly)?;
+
+        // the same outcomes with the same hits, without anything in between
+        let outcomes = |tree: &StateTree| {
+            let mut outcomes = BTreeMap::new();
+            tree.visit_states(true, |state, hits| {
+                *outcomes.entry(StateHash::hash_state(state)).or_insert(0) += hits;
+                true
+            });
+            outcomes
+        };
+        assert_eq!(outcomes(&terminal), outcomes(&full));
+        assert_eq!(terminal.terminal_state_count(), full.terminal_state_count());
+        assert_eq!(terminal.edge_count(), 0);
+        assert!(serde_json::to_string(&terminal)?.len() < serde_json::to_string(&full)?.len() / 4);
+
+        let mut pruned = terminal.clone();
+        pruned.prune_below_hits(2);
+        assert!(pruned.terminal_states().all(|t| t.hits.get() >= 2));
+        assert!(pruned.terminal_state_count() < terminal.terminal_state_count());
+        assert_eq!(
+            pruned.total_node_hits(),
+            1 + pruned.terminal_states().map(|t| t.hits.get()).sum::<u64>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_counts() -> anyhow::Result<()> {
+        let (mut state, _, _) = crate::test_utils::duel(12, "Goblin", 12);
+        crate::test_utils::brawl(&mut state);
+        let run_with = |mode| -> anyhow::Result<StateTree> {
+            let mut integrator = Integrator::new(200, Roller::from_seed(11), state.clone());
+            integrator.set_tree_mode(mode);
+            Ok(integrator.run()?.state_tree)
+        };
+        let rounds = |tree: &StateTree| {
+            let mut rounds = BTreeMap::new();
+            tree.visit_states(true, |state, hits| {
+                *rounds
+                    .entry((StateHash::hash_state(state), state.final_round))
+                    .or_insert(0) += hits;
+                true
+            });
+            rounds
+        };
+
+        for mode in [TreeMode::Full, TreeMode::TerminalOnly] {
+            let tree = run_with(mode)?;
+            let counted = rounds(&tree);
+            // combats ending alike after different numbers of rounds share a terminal state,
+            // but are still told apart when visited
+            assert!(counted.len() > tree.terminal_state_count());
+            assert!(counted.keys().all(|(_, round)| round.is_some()));
+            assert_eq!(counted.values().sum::<u64>(), 200);
+
+            let mut doubled = tree.clone();
+            doubled.merge(&tree)?;
+            assert_eq!(doubled.terminal_state_count(), tree.terminal_state_count());
+            let doubled = rounds(&doubled);
+            assert!(counted.iter().all(|(key, hits)| doubled[key] == hits * 2));
+
+            let mut pruned = tree.clone();
+            pruned.prune_below_hits(1);
+            assert_eq!(rounds(&pruned), counted);
+        }
+
+        let mut reduced = run_with(TreeMode::Full)?;
+        reduced.reduce_to(TreeMode::TerminalOnly)?;
+        assert_eq!(rounds(&reduced), rounds(&run_with(TreeMode::TerminalOnly)?));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{actor::ActorId, damage::DamageType},
+    simulation::{
+        contribution::ContributionStats,
+        integration::IntegrationResults,
+        messages::{MessageTemplates, ReportMessage},
+        objectives::Outcome,
+        query::{OutcomeProbabilities, Query},
+        state_tree::StateTree,
+        transition::Transition,
+    },
+    utils::Ratio,
+};
+
+/// How a single actor fared across every simulated combat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorSummary {
+    pub actor: ActorId,
+    pub name: String,
+    pub group: u32,
+    /// The group's name, like "Party" or "Monsters", or "Group N" if it hasn't been given one.
+    #[serde(default)]
+    pub group_name: String,
+    /// Combats the actor ended not dead, whether conscious or not, out of combats run.
+    #[serde(default)]
+    pub survived: Ratio,
+    /// Combats the actor ended above 0 HP, out of combats run.
+    #[serde(default)]
+    pub standing: Ratio,
+    pub mean_final_health: f64,
+    pub max_health: i32,
+    /// Only present when the run recorded contributions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contribution: Option<ContributionSummary>,
+}
+
+/// An actor's share of what its group accomplished, from
+/// [`IntegrationResults::contributions`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContributionSummary {
+    pub mean_damage_dealt: f64,
+    pub damage_share: f64,
+    pub mean_healing_done: f64,
+    pub healing_share: f64,
+    pub kills: u64,
+    pub assists: u64,
+    /// Fraction of enemy deaths the actor got the kill or an assist on.
+    pub kill_participation: f64,
+    /// Mean typed damage per combat, before and after the targets' resistances.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub damage_by_type: BTreeMap<DamageType, TypedDamageSummary>,
+}
+
+/// One damage type's share of an actor's [`ContributionSummary`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TypedDamageSummary {
+    pub mean_rolled: f64,
+    pub mean_dealt: f64,
+}
+
+impl TypedDamageSummary {
+    /// Mean damage per combat that resistances and immunities stopped.
+    pub fn mean_resisted(&self) -> f64 {
+        (self.mean_rolled - self.mean_dealt).max(0.0)
+    }
+}
+
+/// One way combats ended, and how often.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutcomeSummary {
+    pub outcome: Outcome,
+    /// Like "Party wins (Party survives 5 rounds)".
+    pub description: String,
+    /// Combats that ended this way, out of combats run.
+    pub ratio: Ratio,
+}
+
+/// A flat, per-actor digest of an integration run, suitable for printing or exporting.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub combats_run: usize,
+    pub elapsed_secs: f64,
+    pub combats_per_second: f64,
+    /// Every way the combats ended, most likely first.
+    #[serde(default)]
+    pub outcomes: Vec<OutcomeSummary>,
+    pub actors: Vec<ActorSummary>,
+    /// Mean damage per combat from environmental effects. Only present when the run recorded
+    /// contributions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment_damage: Option<f64>,
+}
+
+impl RunSummary {
+    pub fn new(results: &IntegrationResults) -> Self {
+        Self::with_messages(results, &MessageTemplates::default())
+    }
+
+    /// Like [`Self::new`], but describes outcomes with the [`Transition::CombatDecided`]
+    /// template in `messages`, if it has one. Print it with [`Self::pretty_print_with_messages`]
+    /// to word the rest of the summary with `messages` too.
+    pub fn with_messages(results: &IntegrationResults, messages: &MessageTemplates) -> Self {
+        Self {
+            combats_run: results.combats_run,
+            elapsed_secs: results.elapsed_time.num_milliseconds() as f64 / 1000.0,
+            combats_per_second: results.combats_per_second(),
+            outcomes: summarize_outcomes(&results.state_tree, messages),
+            actors: summarize_actors(results),
+            environment_damage: results.contributions.as_ref().map(|contributions| {
+                contributions.environment.damage_dealt as f64 / contributions.combats.max(1) as f64
+            }),
+        }
+    }
+
+    /// CSV rows leave out contributions, so the columns stay the same whether or not they were
+    /// recorded.
+    pub const CSV_HEADER: &'static str =
+        "actor,name,group,survival_rate,standing_rate,mean_final_health,max_health";
+
+    /// Writes one CSV row per actor, without the header.
+    pub fn write_csv_rows(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        for actor in &self.actors {
+            // quote names so commas in them don't break the columns
+            writeln!(
+                f,
+                "{},\"{}\",{},{:.4},{:.4},{:.2},{}",
+                actor.actor.0,
+                actor.name.replace('"', "\"\""),
+                actor.group,
+                actor.survived.to_f64(),
+                actor.standing.to_f64(),
+                actor.mean_final_health,
+                actor.max_health
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        self.pretty_print_with_messages(f, &MessageTemplates::default())
+    }
+
+    /// Like [`Self::pretty_print`], but words each line with the templates in `messages`.
+    pub fn pretty_print_with_messages(
+        &self,
+        f: &mut impl std::fmt::Write,
+        messages: &MessageTemplates,
+    ) -> std::fmt::Result {
+        let percent = |ratio: Ratio| format!("{:.2}", ratio.to_f64() * 100.0);
+        writeln!(
+            f,
+            "{}",
+            messages.report(
+                ReportMessage::RunTotals,
+                &[
+                    ("combats", self.combats_run.to_string()),
+                    ("seconds", format!("{:.2}", self.elapsed_secs)),
+                    ("rate", format!("{:.2}", self.combats_per_second)),
+                ]
+            )
+        )?;
+        for outcome in &self.outcomes {
+            writeln!(
+                f,
+                "  {}",
+                messages.report(
+                    ReportMessage::Outcome,
+                    &[
+                        ("description", outcome.description.clone()),
+                        ("percent", percent(outcome.ratio)),
+                    ]
+                )
+            )?;
+        }
+        for actor in &self.actors {
+            writeln!(
+                f,
+                "  {}",
+                messages.report(
+                    ReportMessage::Actor,
+                    &[
+                        ("id", actor.actor.0.to_string()),
+                        ("name", actor.name.clone()),
+                        ("group", actor.group_name.clone()),
+                        ("survived", percent(actor.survived)),
+                        ("standing", percent(actor.standing)),
+                        ("health", format!("{:.1}", actor.mean_final_health)),
+                        ("max_health", actor.max_health.to_string()),
+                    ]
+                )
+            )?;
+            if let Some(contribution) = &actor.contribution {
+                writeln!(
+                    f,
+                    "      {}",
+                    messages.report(
+                        ReportMessage::Contribution,
+                        &[
+                            ("damage", format!("{:.1}", contribution.mean_damage_dealt)),
+                            (
+                                "damage_share",
+                                format!("{:.1}", contribution.damage_share * 100.0)
+                            ),
+                            ("healing", format!("{:.1}", contribution.mean_healing_done)),
+                            (
+                                "healing_share",
+                                format!("{:.1}", contribution.healing_share * 100.0)
+                            ),
+                            ("kills", contribution.kills.to_string()),
+                            ("assists", contribution.assists.to_string()),
+                            (
+                                "participation",
+                                format!("{:.1}", contribution.kill_participation * 100.0)
+                            ),
+                        ]
+                    )
+                )?;
+                for (damage_type, damage) in &contribution.damage_by_type {
+                    writeln!(
+                        f,
+                        "      {}",
+                        messages.report(
+                            ReportMessage::TypedDamage,
+                            &[
+                                ("damage_type", format!("{:?}", damage_type)),
+                                ("rolled", format!("{:.1}", damage.mean_rolled)),
+                                ("dealt", format!("{:.1}", damage.mean_dealt)),
+                                ("resisted", format!("{:.1}", damage.mean_resisted())),
+                            ]
+                        )
+                    )?;
+                }
+            }
+        }
+        if let Some(damage) = self.environment_damage.filter(|damage| *damage > 0.0) {
+            writeln!(
+                f,
+                "  {}",
+                messages.report(
+                    ReportMessage::Environment,
+                    &[("damage", format!("{:.1}", damage))]
+                )
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn summarize_outcomes(state_tree: &StateTree, messages: &MessageTemplates) -> Vec<OutcomeSummary> {
+    let outcomes = OutcomeProbabilities.query(state_tree).unwrap_or_default();
+    let mut outcomes: Vec<OutcomeSummary> = outcomes
+        .into_iter()
+        .map(|(outcome, ratio)| {
+            let mut description = String::new();
+            let decided = Transition::CombatDecided { outcome };
+            messages
+                .transition(&mut description, &decided, state_tree.initial_state())
+                .ok();
+            OutcomeSummary {
+                outcome,
+                description,
+                ratio,
+            }
+        })
+        .collect();
+    // every outcome is out of the same total, so the counts order them exactly
+    outcomes.sort_by_key(|outcome| std::cmp::Reverse(outcome.ratio.numerator));
+    outcomes
+}
+
+#[derive(Default)]
+struct Tally {
+    alive: u64,
+    standing: u64,
+    health: i64,
+}
+
+fn summarize_actors(results: &IntegrationResults) -> Vec<ActorSummary> {
+    let state_tree = &results.state_tree;
+    let mut tallies: BTreeMap<ActorId, Tally> = BTreeMap::new();
+    let mut total = 0u64;
+
+    state_tree.visit_states(true, |state, hits| {
+        total += hits;
+        for actor in state.actors.values() {
+            let tally = tallies.entry(actor.id).or_default();
+            if !actor.is_dead() {
+                tally.alive += hits;
+            }
+            if actor.is_alive() {
+                tally.standing += hits;
+            }
+            tally.health += actor.health as i64 * hits as i64;
+        }
+        true
+    });
+
+    state_tree
+        .initial_state()
+        .actors
+        .values()
+        .map(|actor| {
+            let tally = tallies.remove(&actor.id).unwrap_or_default();
+            ActorSummary {
+                actor: actor.id,
+                name: actor.name.clone(),
+                group: actor.group,
+                group_name: state_tree.initial_state().group_name(actor.group),
+                survived: Ratio::new(tally.alive, total),
+                standing: Ratio::new(tally.standing, total),
+                mean_final_health: if total > 0 {
+                    tally.health as f64 / total as f64
+                } else {
+                    actor.health as f64
+                },
+                max_health: actor.max_health,
+                contribution: results.contributions.as_ref().map(|contributions| {
+                    summarize_contribution(contributions, state_tree, actor.id)
+                }),
+            }
+        })
+        .collect()
+}
+
+fn summarize_contribution(
+    contributions: &ContributionStats,
+    state_tree: &StateTree,
+    actor: ActorId,
+) -> ContributionSummary {
+    let initial_state = state_tree.initial_state();
+    let tally = contributions
+        .actors
+        .get(&actor)
+        .cloned()
+        .unwrap_or_default();
+    let per_combat = |total: u64| total as f64 / contributions.combats.max(1) as f64;
+    ContributionSummary {
+        mean_damage_dealt: per_combat(tally.damage_dealt),
+        damage_share: contributions.damage_share(initial_state, actor),
+        mean_healing_done: per_combat(tally.healing_done),
+        healing_share: contributions.healing_share(initial_state, actor),
+        kills: tally.kills,
+        assists: tally.assists,
+        kill_participation: contributions.kill_participation(initial_state, actor),
+        damage_by_type: tally
+            .damage_by_type
+            .iter()
+            .map(|(&damage_type, damage)| {
+                let summary = TypedDamageSummary {
+                    mean_rolled: per_combat(damage.rolled),
+                    mean_dealt: per_combat(damage.dealt),
+                };
+                (damage_type, summary)
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::actor::ActorBuilder,
+        simulation::{integration::Integrator, roller::Roller, state::State},
+        test_utils::brawl_policy,
+    };
+
+    #[test]
+    fn test_run_summary() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let strong = state.add_actor(
+            ActorBuilder::new("Ogre, Big")
+                .group(0)
+                .max_health(60)
+                .build(),
+        );
+        let weak = state.add_actor(ActorBuilder::new("Rat").group(1).max_health(1).build());
+        let policy = brawl_policy();
+        state.set_actor_policy(strong, policy.clone());
+        state.set_actor_policy(weak, policy);
+
+        let mut integrator = Integrator::new(50, Roller::test_rng(), state);
+        integrator.record_contributions(true);
+        let summary = RunSummary::new(&integrator.run()?);
+
+        assert_eq!(summary.combats_run, 50);
+        assert_eq!(summary.actors.len(), 2);
+        let ended: u64 = summary.outcomes.iter().map(|o| o.ratio.numerator).sum();
+        assert_eq!(ended, 50);
+        assert!(
+            summary.outcomes[0]
+                .description
+                .ends_with("wins (last standing)")
+        );
+        let ogre = summary.actors.iter().find(|a| a.actor == strong).unwrap();
+        let rat = summary.actors.iter().find(|a| a.actor == weak).unwrap();
+        assert_eq!(ogre.survived.denominator, 50);
+        assert!(ogre.survived.numerator > rat.standing.numerator);
+        assert!(ogre.mean_final_health <= 60.0);
+        // alone in their groups, each deals all of their side's damage
+        let ogre_contribution = ogre.contribution.as_ref().unwrap();
+        assert_eq!(ogre_contribution.damage_share, 1.0);
+        assert!(ogre_contribution.kills > 0);
+        assert_eq!(ogre_contribution.kill_participation, 1.0);
+
+        let mut csv = String::new();
+        summary.write_csv_rows(&mut csv)?;
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.starts_with(&format!("{},\"Ogre, Big\",0,", strong.0)));
+
+        let mut messages = MessageTemplates::default();
+        messages.set_report(ReportMessage::RunTotals, "{combats} Kämpfe");
+        messages.set_report(ReportMessage::Actor, "{name}: {survived}% überlebt");
+        let mut text = String::new();
+        summary.pretty_print_with_messages(&mut text, &messages)?;
+        assert!(text.starts_with("50 Kämpfe\n"));
+        assert!(text.contains(&format!(
+            "  Rat: {:.2}% überlebt\n",
+            rat.survived.to_f64() * 100.0
+        )));
+
+        Ok(())
+    }
+}
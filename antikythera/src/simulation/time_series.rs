@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{rules::actor::ActorId, simulation::state::State};
+
+/// Summary of a single actor's HP distribution at the end of a single round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HpRoundSummary {
+    pub round: u64,
+    pub samples: u64,
+    pub mean: f64,
+    pub min: i32,
+    pub max: i32,
+    pub p10: i32,
+    pub p25: i32,
+    pub median: i32,
+    pub p75: i32,
+    pub p90: i32,
+}
+
+/// Records each actor's HP at the end of every round, aggregated across combats.
+///
+/// Samples are stored as per-round histograms, so percentiles are exact and memory usage
+/// is bounded by the range of HP values rather than the number of combats run.
+/// Only rounds that a combat actually reached contribute samples; check
+/// [`HpRoundSummary::samples`] when comparing late rounds against early ones.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HpTimeSeries {
+    /// actor -> round -> (hp -> number of combats)
+    pub histograms: BTreeMap<ActorId, Vec<BTreeMap<i32, u64>>>,
+}
+
+impl HpTimeSeries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_round(&mut self, state: &State, round: u64) {
+        let round = round as usize;
+        for (id, actor) in &state.actors {
+            let rounds = self.histograms.entry(*id).or_default();
+            if rounds.len() <= round {
+                rounds.resize(round + 1, BTreeMap::new());
+            }
+            *rounds[round].entry(actor.health).or_insert(0) += 1;
+        }
+    }
+
+    /// Adds another series' samples into this one.
+    pub fn merge(&mut self, other: &HpTimeSeries) {
+        for (id, other_rounds) in &other.histograms {
+            let rounds = self.histograms.entry(*id).or_default();
+            if rounds.len() < other_rounds.len() {
+                rounds.resize(other_rounds.len(), BTreeMap::new());
+            }
+            for (round, histogram) in rounds.iter_mut().zip(other_rounds) {
+                for (hp, count) in histogram {
+                    *round.entry(*hp).or_insert(0) += count;
+                }
+            }
+        }
+    }
+
+    pub fn rounds(&self, actor: ActorId) -> usize {
+        self.histograms.get(&actor).map_or(0, |rounds| rounds.len())
+    }
+
+    pub fn round_summary(&self, actor: ActorId, round: u64) -> Option<HpRoundSummary> {
+        let histogram = self.histograms.get(&actor)?.get(round as usize)?;
+        let samples: u64 = histogram.values().sum();
+        if samples == 0 {
+            return None;
+        }
+
+        let total: i64 = histogram
+            .iter()
+            .map(|(hp, count)| *hp as i64 * *count as i64)
+            .sum();
+
+        Some(HpRoundSummary {
+            round,
+            samples,
+            mean: total as f64 / samples as f64,
+            min: *histogram.keys().next()?,
+            max: *histogram.keys().next_back()?,
+            p10: Self::percentile(histogram, samples, 0.10),
+            p25: Self::percentile(histogram, samples, 0.25),
+            median: Self::percentile(histogram, samples, 0.50),
+            p75: Self::percentile(histogram, samples, 0.75),
+            p90: Self::percentile(histogram, samples, 0.90),
+        })
+    }
+
+    pub fn summary(&self, actor: ActorId) -> Vec<HpRoundSummary> {
+        (0..self.rounds(actor) as u64)
+            .filter_map(|round| self.round_summary(actor, round))
+            .collect()
+    }
+
+    /// Returns the first round at which the actor's median HP is below `hp`.
+    pub fn first_round_median_below(&self, actor: ActorId, hp: i32) -> Option<u64> {
+        self.summary(actor)
+            .into_iter()
+            .find(|summary| summary.median < hp)
+            .map(|summary| summary.round)
+    }
+
+    fn percentile(histogram: &BTreeMap<i32, u64>, samples: u64, p: f64) -> i32 {
+        let rank = ((samples as f64 * p).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (hp, count) in histogram {
+            seen += count;
+            if seen >= rank {
+                return *hp;
+            }
+        }
+        *histogram.keys().next_back().unwrap_or(&0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::actor::Actor;
+
+    #[test]
+    fn test_hp_time_series_summary() {
+        let mut state = State::new();
+        let hero = state.add_actor(Actor::test_actor(0, "Hero"));
+
+        let mut series = HpTimeSeries::new();
+        for hp in [10, 8, 6, 4, 2] {
+            state.get_actor_mut(hero).unwrap().health = hp;
+            series.record_round(&state, 0);
+        }
+        state.get_actor_mut(hero).unwrap().health = 1;
+        series.record_round(&state, 1);
+
+        assert_eq!(series.rounds(hero), 2);
+
+        let round0 = series.round_summary(hero, 0).unwrap();
+        assert_eq!(round0.samples, 5);
+        assert_eq!(round0.mean, 6.0);
+        assert_eq!(round0.min, 2);
+        assert_eq!(round0.max, 10);
+        assert_eq!(round0.median, 6);
+        assert_eq!(round0.p10, 2);
+        assert_eq!(round0.p90, 10);
+
+        assert_eq!(series.first_round_median_below(hero, 5), Some(1));
+        assert_eq!(series.first_round_median_below(hero, 1), None);
+    }
+}
@@ -0,0 +1,323 @@
+//! Conditions and stat changes that last until a turn boundary, like the advantage the Help
+//! action gives until the helper's next turn, being knocked prone until your own, or a strength
+//! penalty for a few rounds. Features apply them with [`Transition::TimedEffectApplied`], and the
+//! integrator expires them with [`Transition::TimedEffectExpired`] as turns begin and end, so
+//! none of them has to keep track of its own duration.
+//!
+//! [`Transition::TimedEffectApplied`]: crate::simulation::transition::Transition::TimedEffectApplied
+//! [`Transition::TimedEffectExpired`]: crate::simulation::transition::Transition::TimedEffectExpired
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{actor::ActorId, conditions::Condition, stats::StatModifier},
+    simulation::state::State,
+};
+
+/// When a [`TimedEffect`] wears off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Expiry {
+    /// At the start of the actor's next turn, whether or not it can act on it.
+    StartOfTurn(ActorId),
+    /// At the end of the actor's next turn. Applied during that actor's own turn, it lasts
+    /// through the turn after.
+    EndOfTurn(ActorId),
+    /// At the start of the actor's first turn in the given round or later, for effects that last
+    /// a number of rounds.
+    StartOfTurnInRound(ActorId, u64),
+}
+
+impl Expiry {
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write, state: &State) -> std::fmt::Result {
+        let actor = match self {
+            Expiry::StartOfTurn(actor) => {
+                write!(f, "the start of ")?;
+                actor
+            }
+            Expiry::EndOfTurn(actor) => {
+                write!(f, "the end of ")?;
+                actor
+            }
+            Expiry::StartOfTurnInRound(actor, round) => {
+                write!(f, "the start of ")?;
+                actor.pretty_print(f, state)?;
+                return write!(f, "'s turn in round {}", round + 1);
+            }
+        };
+        actor.pretty_print(f, state)?;
+        write!(f, "'s next turn")
+    }
+
+    /// The actor whose turn the effect waits for.
+    pub fn actor(&self) -> ActorId {
+        match *self {
+            Expiry::StartOfTurn(actor)
+            | Expiry::EndOfTurn(actor)
+            | Expiry::StartOfTurnInRound(actor, _) => actor,
+        }
+    }
+}
+
+/// What a [`TimedEffect`] does to its target while it lasts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum TimedChange {
+    Condition(Condition),
+    /// A [`StatModifier`] on the target's stats, taken off again when the effect wears off.
+    Stat(StatModifier),
+}
+
+impl TimedChange {
+    pub fn pretty_print(&self, f: &mut impl std::fmt::Write, state: &State) -> std::fmt::Result {
+        match self {
+            TimedChange::Condition(condition) => condition.pretty_print(f, state),
+            TimedChange::Stat(StatModifier { stat, delta }) => {
+                write!(f, "at {:+} {:?}", delta, stat)
+            }
+        }
+    }
+
+    fn involves(&self, actor: ActorId) -> bool {
+        match *self {
+            TimedChange::Condition(Condition::Grappled { by }) => by == actor,
+            TimedChange::Condition(Condition::Helped { by, against }) => {
+                by == actor || against == actor
+            }
+            TimedChange::Condition(Condition::Prone) | TimedChange::Stat(_) => false,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TimedChange {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Older states and results stored a bare condition.
+        #[derive(Deserialize)]
+        enum Change {
+            Condition(Condition),
+            Stat(StatModifier),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Change(Change),
+            Condition(Condition),
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Change(Change::Condition(condition)) | Repr::Condition(condition) => {
+                TimedChange::Condition(condition)
+            }
+            Repr::Change(Change::Stat(modifier)) => TimedChange::Stat(modifier),
+        })
+    }
+}
+
+impl From<Condition> for TimedChange {
+    fn from(condition: Condition) -> Self {
+        TimedChange::Condition(condition)
+    }
+}
+
+impl From<StatModifier> for TimedChange {
+    fn from(modifier: StatModifier) -> Self {
+        TimedChange::Stat(modifier)
+    }
+}
+
+/// A change to `target` that lasts until `expiry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct TimedEffect {
+    pub target: ActorId,
+    #[serde(alias = "condition")]
+    pub change: TimedChange,
+    pub expiry: Expiry,
+    /// The round and initiative slot the effect was applied in, to tell the end of the turn it
+    /// was applied in from the end of the next one.
+    pub applied_round: u64,
+    pub applied_turn: Option<usize>,
+}
+
+impl TimedEffect {
+    /// An effect applied now, as of `state`.
+    pub fn new(
+        target: ActorId,
+        change: impl Into<TimedChange>,
+        expiry: Expiry,
+        state: &State,
+    ) -> Self {
+        Self {
+            target,
+            change: change.into(),
+            expiry,
+            applied_round: state.turn,
+            applied_turn: state.current_turn_index,
+        }
+    }
+
+    /// Whether the effect wears off at `boundary`, reached in `state`.
+    pub fn expires_at(&self, boundary: Expiry, state: &State) -> bool {
+        match (boundary, self.expiry) {
+            (Expiry::StartOfTurn(actor), Expiry::StartOfTurnInRound(ours, round)) => {
+                actor == ours && state.turn >= round
+            }
+            (Expiry::StartOfTurn(_), _) => self.expiry == boundary,
+            (Expiry::EndOfTurn(_), _) => {
+                self.expiry == boundary
+                    && (self.applied_round, self.applied_turn)
+                        != (state.turn, state.current_turn_index)
+            }
+            (Expiry::StartOfTurnInRound(..), _) => false,
+        }
+    }
+}
+
+/// Every [`TimedEffect`] in play, in the order they were applied.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TimedEffects {
+    pub effects: Vec<TimedEffect>,
+}
+
+impl TimedEffects {
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TimedEffect> {
+        self.effects.iter()
+    }
+
+    pub fn add(&mut self, effect: TimedEffect) {
+        self.effects.push(effect);
+    }
+
+    pub fn remove(&mut self, effect: &TimedEffect) {
+        if let Some(index) = self.effects.iter().position(|ours| ours == effect) {
+            self.effects.remove(index);
+        }
+    }
+
+    /// Forgets every effect that gives `target` `condition`, for when the condition ends early,
+    /// like help used up on an attack.
+    pub fn remove_condition(&mut self, target: ActorId, condition: Condition) {
+        self.effects.retain(|effect| {
+            effect.target != target || effect.change != TimedChange::Condition(condition)
+        });
+    }
+
+    /// Forgets every effect on `actor`, waiting on its turns, or naming it in its condition, for
+    /// when it leaves the state.
+    pub fn forget_actor(&mut self, actor: ActorId) {
+        self.effects.retain(|effect| {
+            effect.target != actor
+                && effect.expiry.actor() != actor
+                && !effect.change.involves(actor)
+        });
+    }
+
+    /// Whether an effect still gives `target` `condition`.
+    pub fn gives(&self, target: ActorId, condition: Condition) -> bool {
+        self.effects.iter().any(|effect| {
+            effect.target == target && effect.change == TimedChange::Condition(condition)
+        })
+    }
+
+    /// The effects that wear off at `boundary`, reached in `state`.
+    pub fn expiring_at(&self, boundary: Expiry, state: &State) -> Vec<TimedEffect> {
+        self.effects
+            .iter()
+            .filter(|effect| effect.expires_at(boundary, state))
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rules::actor::ActorBuilder,
+        simulation::{
+            integration::{CombatContext, Integrator},
+            roller::Roller,
+            state_tree::StateTree,
+            transition::Transition,
+        },
+    };
+
+    #[test]
+    fn test_timed_effects() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = state.add_actor(ActorBuilder::new("Hero").build());
+        let goblin = state.add_actor(ActorBuilder::new("Goblin").group(1).build());
+        state.initiative_order = vec![hero, goblin];
+
+        let mut integrator = Integrator::new(1, Roller::test_rng(), state);
+        let mut state_tree = StateTree::new(integrator.initial_state.clone());
+        let mut ctx = CombatContext::new(&mut integrator, &mut state_tree);
+        ctx.transition(Transition::BeginCombat)?;
+
+        // on the hero's turn, one effect until the start of the goblin's turn and two until the
+        // end of the hero's
+        let effect = |condition, expiry: Expiry, ctx: &CombatContext| {
+            TimedEffect::new(goblin, condition, expiry, &ctx.state)
+        };
+        let prone = effect(Condition::Prone, Expiry::StartOfTurn(goblin), &ctx);
+        let grappled = effect(
+            Condition::Grappled { by: hero },
+            Expiry::EndOfTurn(hero),
+            &ctx,
+        );
+        for effect in [prone, grappled, grappled] {
+            ctx.transition(Transition::TimedEffectApplied { effect })?;
+        }
+        let conditions = |ctx: &CombatContext| {
+            ctx.state
+                .get_actor(goblin)
+                .unwrap()
+                .conditions
+                .iter()
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(conditions(&ctx).len(), 2);
+        assert_eq!(ctx.state.timed_effects.effects.len(), 3);
+
+        // the end of the turn the grapple started in doesn't count
+        assert!(
+            ctx.state
+                .timed_effects
+                .expiring_at(Expiry::EndOfTurn(hero), &ctx.state)
+                .is_empty()
+        );
+        assert_eq!(
+            ctx.state
+                .timed_effects
+                .expiring_at(Expiry::StartOfTurn(goblin), &ctx.state),
+            [prone]
+        );
+        ctx.transition(Transition::TimedEffectExpired { effect: prone })?;
+        assert_eq!(conditions(&ctx), [Condition::Grappled { by: hero }]);
+
+        // one of the two grapples wearing off leaves the other in place
+        ctx.transition(Transition::TimedEffectExpired { effect: grappled })?;
+        assert_eq!(conditions(&ctx), [Condition::Grappled { by: hero }]);
+        ctx.transition(Transition::AdvanceInitiative)?;
+        ctx.transition(Transition::AdvanceInitiative)?;
+        assert_eq!(
+            ctx.state
+                .timed_effects
+                .expiring_at(Expiry::EndOfTurn(hero), &ctx.state),
+            [grappled]
+        );
+
+        // ending the condition early forgets the effect
+        ctx.transition(Transition::ConditionRemoved {
+            target: goblin,
+            condition: Condition::Grappled { by: hero },
+        })?;
+        assert!(conditions(&ctx).is_empty());
+        assert!(ctx.state.timed_effects.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{rules::actor::ActorId, simulation::state::State};
+
+/// How many combats an event happened in on each round. Rounds count from 1.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundHistogram {
+    /// round -> number of combats
+    pub counts: BTreeMap<u64, u64>,
+}
+
+impl RoundHistogram {
+    pub fn record(&mut self, round: u64) {
+        *self.counts.entry(round).or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: &RoundHistogram) {
+        for (round, count) in &other.counts {
+            *self.counts.entry(*round).or_insert(0) += count;
+        }
+    }
+
+    /// Number of combats the event happened in at all.
+    pub fn occurrences(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Mean round of the event, over the combats it happened in.
+    pub fn mean(&self) -> Option<f64> {
+        let occurrences = self.occurrences();
+        let total: u64 = self.counts.iter().map(|(round, count)| round * count).sum();
+        (occurrences > 0).then(|| total as f64 / occurrences as f64)
+    }
+
+    /// The round the event happened on most often, and in how many combats. Ties go to the
+    /// earlier round.
+    pub fn most_common(&self) -> Option<(u64, u64)> {
+        self.counts
+            .iter()
+            .rev()
+            .max_by_key(|(_, count)| **count)
+            .map(|(round, count)| (*round, *count))
+    }
+
+    /// Fraction of `combats` in which the event happened on or before `round`.
+    pub fn fraction_by(&self, round: u64, combats: u64) -> f64 {
+        if combats == 0 {
+            return 0.0;
+        }
+        let count: u64 = self.counts.range(..=round).map(|(_, count)| count).sum();
+        count as f64 / combats as f64
+    }
+}
+
+/// The events of the combat in progress.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct CombatEvents {
+    starting_health: BTreeMap<ActorId, i32>,
+    first_blood: Option<u64>,
+    first_down: BTreeMap<ActorId, u64>,
+    death: BTreeMap<ActorId, u64>,
+}
+
+/// Records the round on which key events happen in each combat, aggregated across combats:
+/// first blood, each actor's first time dropping to 0 HP, each actor's death, and the end of
+/// combat.
+///
+/// Events that don't happen in a combat aren't recorded for it, so the distributions don't sum to
+/// [`EventTimeline::combats`] unless the event always happens.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventTimeline {
+    pub combats: u64,
+    /// The first time anyone loses HP.
+    pub first_blood: RoundHistogram,
+    pub first_down: BTreeMap<ActorId, RoundHistogram>,
+    pub death: BTreeMap<ActorId, RoundHistogram>,
+    pub combat_end: RoundHistogram,
+    #[serde(skip)]
+    current: CombatEvents,
+}
+
+impl EventTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin_combat(&mut self, state: &State) {
+        self.current = CombatEvents {
+            starting_health: state
+                .actors
+                .iter()
+                .map(|(id, actor)| (*id, actor.health))
+                .collect(),
+            ..Default::default()
+        };
+    }
+
+    /// Notes any events that `state` shows have happened by `round`.
+    pub fn observe(&mut self, state: &State, round: u64) {
+        let current = &mut self.current;
+        for (id, actor) in &state.actors {
+            if current.first_blood.is_none()
+                && current
+                    .starting_health
+                    .get(id)
+                    .is_some_and(|health| actor.health < *health)
+            {
+                current.first_blood = Some(round);
+            }
+            if actor.health <= 0 {
+                current.first_down.entry(*id).or_insert(round);
+            }
+            if actor.is_dead() {
+                current.death.entry(*id).or_insert(round);
+            }
+        }
+    }
+
+    pub fn end_combat(&mut self, round: u64) {
+        let current = std::mem::take(&mut self.current);
+        self.combats += 1;
+        self.combat_end.record(round);
+        if let Some(round) = current.first_blood {
+            self.first_blood.record(round);
+        }
+        for (id, round) in current.first_down {
+            self.first_down.entry(id).or_default().record(round);
+        }
+        for (id, round) in current.death {
+            self.death.entry(id).or_default().record(round);
+        }
+    }
+
+    /// Adds another timeline's combats into this one.
+    pub fn merge(&mut self, other: &EventTimeline) {
+        self.combats += other.combats;
+        self.first_blood.merge(&other.first_blood);
+        self.combat_end.merge(&other.combat_end);
+        for (id, histogram) in &other.first_down {
+            self.first_down.entry(*id).or_default().merge(histogram);
+        }
+        for (id, histogram) in &other.death {
+            self.death.entry(*id).or_default().merge(histogram);
+        }
+    }
+
+    /// Every recorded event with a readable name, in the order they're reported.
+    pub fn events(&self, initial_state: &State) -> Vec<(String, &RoundHistogram)> {
+        let name_of = |id: &ActorId| {
+            let mut name = String::new();
+            id.pretty_print(&mut name, initial_state).ok();
+            name
+        };
+        let mut events = vec![("First blood".to_string(), &self.first_blood)];
+        events.extend(
+            self.first_down
+                .iter()
+                .map(|(id, histogram)| (format!("{} goes down", name_of(id)), histogram)),
+        );
+        events.extend(
+            self.death
+                .iter()
+                .map(|(id, histogram)| (format!("{} dies", name_of(id)), histogram)),
+        );
+        events.push(("Combat ends".to_string(), &self.combat_end));
+        events
+    }
+
+    pub fn pretty_print(
+        &self,
+        f: &mut impl std::fmt::Write,
+        initial_state: &State,
+    ) -> std::fmt::Result {
+        let percent = |count: u64| 100.0 * count as f64 / self.combats.max(1) as f64;
+        for (name, histogram) in self.events(initial_state) {
+            let Some((round, count)) = histogram.most_common() else {
+                writeln!(f, "{}: never", name)?;
+                continue;
+            };
+            writeln!(
+                f,
+                "{} on round {} in {:.1}% of combats (mean round {:.2}, happens in {:.1}%)",
+                name,
+                round,
+                percent(count),
+                histogram.mean().unwrap_or_default(),
+                percent(histogram.occurrences())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{actor::Actor, death::DeathBehavior};
+
+    #[test]
+    fn test_event_timeline() {
+        let mut state = State::new();
+        let hero = state.add_actor(Actor::test_actor(0, "Hero"));
+        let mut goblin = Actor::test_actor(1, "Goblin");
+        goblin.death_behavior = DeathBehavior::InstantDeath;
+        let goblin = state.add_actor(goblin);
+
+        let mut timeline = EventTimeline::new();
+        for death_round in [2, 2, 3] {
+            let mut state = state.clone();
+            timeline.begin_combat(&state);
+            timeline.observe(&state, 1);
+            state.get_actor_mut(hero).unwrap().health -= 1;
+            timeline.observe(&state, 1);
+            state.get_actor_mut(goblin).unwrap().health = 0;
+            timeline.observe(&state, death_round);
+            // later rounds don't move events that already happened
+            timeline.observe(&state, death_round + 1);
+            timeline.end_combat(death_round + 1);
+        }
+
+        assert_eq!(timeline.combats, 3);
+        assert_eq!(timeline.first_blood.counts, BTreeMap::from([(1, 3)]));
+        assert_eq!(timeline.death[&goblin].most_common(), Some((2, 2)));
+        assert_eq!(timeline.first_down[&goblin], timeline.death[&goblin]);
+        assert!(!timeline.death.contains_key(&hero));
+        assert!(
+            (timeline.death[&goblin].fraction_by(2, timeline.combats) - 2.0 / 3.0).abs() < 1e-9
+        );
+        assert!((timeline.combat_end.mean().unwrap() - 10.0 / 3.0).abs() < 1e-9);
+
+        let mut merged = timeline.clone();
+        merged.merge(&timeline);
+        assert_eq!(merged.combats, 6);
+        assert_eq!(merged.death[&goblin].occurrences(), 6);
+
+        let mut report = String::new();
+        timeline.pretty_print(&mut report, &state).unwrap();
+        assert!(
+            report.contains("Goblin dies on round 2 in 66.7% of combats"),
+            "{report}"
+        );
+    }
+}
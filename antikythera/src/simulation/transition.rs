@@ -1,11 +1,25 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    rules::{actions::ActionEconomyUsage, actor::ActorId, stats::Stat},
-    simulation::state::State,
+    rules::{
+        actions::ActionEconomyUsage,
+        actor::ActorId,
+        conditions::{Condition, Contest},
+        damage::DamageType,
+        features::Feature,
+        rerolls::RerollResource,
+        saves::SavingThrow,
+        stats::{Stat, StatModifier},
+    },
+    simulation::{
+        objectives::Outcome,
+        positioning::ZoneId,
+        state::State,
+        timed_effects::{Expiry, TimedChange, TimedEffect},
+    },
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum TransitionType {
     Root,
     BeginCombat,
@@ -17,6 +31,30 @@ pub enum TransitionType {
     HealthModification,
     StatModification,
     ActionEconomyUsed,
+    ConditionApplied,
+    ConditionRemoved,
+    ContestResolved,
+    AttackResolved,
+    FeatureUsed,
+    RandomEffectRolled,
+    SavingThrowResolved,
+    StatModifiersExpired,
+    ActorKilled,
+    DistanceClosed,
+    AbilityUsed,
+    AbilityRecharged,
+    Engaged,
+    RerollUsed,
+    ZoneEntered,
+    ZoneApproached,
+    HazardEntered,
+    EnvironmentalEffect,
+    DamageTaken,
+    CombatDecided,
+    PhaseEntered,
+    TimedEffectApplied,
+    TimedEffectExpired,
+    Composite,
 }
 
 /// A transition represents a ***single***, atomic change from one simulation state to another.
@@ -29,7 +67,7 @@ pub enum TransitionType {
 ///
 /// Transitions should be deterministic and side-effect free.
 /// This means that transitions should not contain any random elements or references to external state.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Eq, Hash)]
 pub enum Transition {
     Root,
     BeginCombat,
@@ -49,15 +87,151 @@ pub enum Transition {
         target: ActorId,
         delta: i32, // positive for healing, negative for damage
     },
+    /// A change to one of the target's stats. Without a duration, the stat's base value
+    /// changes for good; with one, a modifier is added as a [`TimedEffect`] that wears off at
+    /// the start of the target's turn that many rounds later.
     StatModification {
         target: ActorId,
         stat: Stat,
         delta: i32,
+        #[serde(default)]
+        duration: Option<u32>,
     },
     ActionEconomyUsed {
         target: ActorId,
         action_type: ActionEconomyUsage,
     },
+    ConditionApplied {
+        target: ActorId,
+        condition: Condition,
+    },
+    ConditionRemoved {
+        target: ActorId,
+        condition: Condition,
+    },
+    /// The outcome of an opposed ability check. The resulting conditions (if any) follow as separate transitions.
+    ContestResolved {
+        initiator: ActorId,
+        target: ActorId,
+        contest: Contest,
+        success: bool,
+    },
+    /// The outcome of an attack roll. Any damage dealt follows as a separate transition.
+    AttackResolved {
+        attacker: ActorId,
+        target: ActorId,
+        hit: bool,
+        crit: bool,
+    },
+    FeatureUsed {
+        actor: ActorId,
+        feature: Feature,
+    },
+    /// An entry was picked from one of the actor's random effect tables, by index into
+    /// [`Actor::random_effects`](crate::rules::actor::Actor::random_effects) and that table's
+    /// entries. The entry's effects follow as separate transitions.
+    RandomEffectRolled {
+        actor: ActorId,
+        table: usize,
+        entry: usize,
+    },
+    /// The outcome of a saving throw forced by an effect. What the save decides follows as separate transitions.
+    SavingThrowResolved {
+        actor: ActorId,
+        save: SavingThrow,
+        dc: i32,
+        success: bool,
+    },
+    /// Only found in results saved before stat modifiers wore off as [`TimedEffect`]s, and does
+    /// nothing; it's kept so that those still load.
+    StatModifiersExpired {
+        target: ActorId,
+    },
+    /// A dead actor is taken out of targeting, and its inventory dropped into the loot pool if
+    /// `drop_loot` is set.
+    ActorKilled {
+        actor: ActorId,
+        drop_loot: bool,
+    },
+    /// An actor moves toward the other side, shrinking the distance between them by `feet`.
+    DistanceClosed {
+        actor: ActorId,
+        feet: u32,
+    },
+    /// An actor uses one of its [`Actor::abilities`](crate::rules::actor::Actor::abilities).
+    /// Abilities with a recharge are spent until they recharge.
+    AbilityUsed {
+        actor: ActorId,
+        ability: usize,
+    },
+    /// A spent ability's recharge roll succeeds, and it can be used again.
+    AbilityRecharged {
+        actor: ActorId,
+        ability: usize,
+    },
+    /// An actor moves into melee with `target`, leaving whoever it was engaged with before.
+    Engaged {
+        actor: ActorId,
+        target: ActorId,
+    },
+    /// An actor spends a reroll resource on a failed d20 roll. The new roll follows.
+    RerollUsed {
+        actor: ActorId,
+        resource: RerollResource,
+    },
+    /// An actor moves into an adjacent zone, carrying its rider or mount along.
+    ZoneEntered {
+        actor: ActorId,
+        zone: ZoneId,
+    },
+    /// An actor spends its move getting partway into an adjacent zone of difficult terrain, and
+    /// enters it with its next move.
+    ZoneApproached {
+        actor: ActorId,
+        zone: ZoneId,
+    },
+    /// An actor moving into a zone sets off its [`Hazard`](crate::simulation::environment::Hazard).
+    /// What it does to the actor follows, then the [`Transition::ZoneEntered`].
+    HazardEntered {
+        actor: ActorId,
+        zone: ZoneId,
+    },
+    /// One of the encounter's [`EncounterSetup::environment`](crate::simulation::encounter::EncounterSetup::environment)
+    /// effects fires, by index. What it does to each actor follows.
+    EnvironmentalEffect {
+        effect: usize,
+    },
+    /// Typed damage lands on an actor, which takes `dealt` of the `rolled` damage after its
+    /// resistances, immunities and vulnerabilities. The health change follows.
+    DamageTaken {
+        target: ActorId,
+        damage_type: DamageType,
+        rolled: i32,
+        dealt: i32,
+    },
+    /// The combat has been decided, and will end. See [`State::combat_outcome`].
+    CombatDecided {
+        outcome: Outcome,
+    },
+    /// The actor enters the boss phase at index `phase`, and any phase before it it hasn't yet,
+    /// spawning their reserves. See [`Phase`](crate::simulation::phases::Phase).
+    PhaseEntered {
+        actor: ActorId,
+        phase: usize,
+    },
+    /// A condition that lasts until a turn boundary, like help until the helper's next turn.
+    /// See [`TimedEffect`].
+    TimedEffectApplied {
+        effect: TimedEffect,
+    },
+    /// A [`TimedEffect`] wore off. Its condition goes with it, unless another effect still
+    /// gives it.
+    TimedEffectExpired {
+        effect: TimedEffect,
+    },
+    /// Everything one action did, applied in order, recorded as a single step when the
+    /// integrator batches actions. Never nested. See [`Transition::parts`].
+    Composite(Vec<Transition>),
 }
 
 impl Transition {
@@ -73,6 +247,30 @@ impl Transition {
             Transition::HealthModification { .. } => TransitionType::HealthModification,
             Transition::StatModification { .. } => TransitionType::StatModification,
             Transition::ActionEconomyUsed { .. } => TransitionType::ActionEconomyUsed,
+            Transition::ConditionApplied { .. } => TransitionType::ConditionApplied,
+            Transition::ConditionRemoved { .. } => TransitionType::ConditionRemoved,
+            Transition::ContestResolved { .. } => TransitionType::ContestResolved,
+            Transition::AttackResolved { .. } => TransitionType::AttackResolved,
+            Transition::FeatureUsed { .. } => TransitionType::FeatureUsed,
+            Transition::RandomEffectRolled { .. } => TransitionType::RandomEffectRolled,
+            Transition::SavingThrowResolved { .. } => TransitionType::SavingThrowResolved,
+            Transition::StatModifiersExpired { .. } => TransitionType::StatModifiersExpired,
+            Transition::ActorKilled { .. } => TransitionType::ActorKilled,
+            Transition::DistanceClosed { .. } => TransitionType::DistanceClosed,
+            Transition::AbilityUsed { .. } => TransitionType::AbilityUsed,
+            Transition::AbilityRecharged { .. } => TransitionType::AbilityRecharged,
+            Transition::Engaged { .. } => TransitionType::Engaged,
+            Transition::RerollUsed { .. } => TransitionType::RerollUsed,
+            Transition::ZoneEntered { .. } => TransitionType::ZoneEntered,
+            Transition::ZoneApproached { .. } => TransitionType::ZoneApproached,
+            Transition::HazardEntered { .. } => TransitionType::HazardEntered,
+            Transition::EnvironmentalEffect { .. } => TransitionType::EnvironmentalEffect,
+            Transition::DamageTaken { .. } => TransitionType::DamageTaken,
+            Transition::CombatDecided { .. } => TransitionType::CombatDecided,
+            Transition::PhaseEntered { .. } => TransitionType::PhaseEntered,
+            Transition::TimedEffectApplied { .. } => TransitionType::TimedEffectApplied,
+            Transition::TimedEffectExpired { .. } => TransitionType::TimedEffectExpired,
+            Transition::Composite(_) => TransitionType::Composite,
         }
     }
 
@@ -100,6 +298,47 @@ impl Transition {
                     "📉"
                 }
             }
+            Transition::ConditionApplied { .. } | Transition::TimedEffectApplied { .. } => "🔗",
+            Transition::ConditionRemoved { .. } | Transition::TimedEffectExpired { .. } => "🔓",
+            Transition::ContestResolved { success, .. } => {
+                if *success {
+                    "💪"
+                } else {
+                    "🛡️"
+                }
+            }
+            Transition::AttackResolved { hit, crit, .. } => match (hit, crit) {
+                (true, true) => "💥",
+                (true, false) => "🎯",
+                (false, _) => "💨",
+            },
+            Transition::FeatureUsed { .. } => "✨",
+            Transition::RandomEffectRolled { .. } => "🎰",
+            Transition::SavingThrowResolved { success, .. } => {
+                if *success {
+                    "🍀"
+                } else {
+                    "💢"
+                }
+            }
+            Transition::StatModifiersExpired { .. } => "⌛",
+            Transition::ActorKilled { .. } => "☠️",
+            Transition::DistanceClosed { .. } => "🏃",
+            Transition::AbilityUsed { .. } => "🐉",
+            Transition::AbilityRecharged { .. } => "🔋",
+            Transition::Engaged { .. } => "🤺",
+            Transition::RerollUsed { .. } => "🎟️",
+            Transition::ZoneEntered { .. } => "🧭",
+            Transition::ZoneApproached { .. } => "🥾",
+            Transition::HazardEntered { .. } => "🔥",
+            Transition::EnvironmentalEffect { .. } => "🌋",
+            Transition::DamageTaken { .. } => "🩸",
+            Transition::CombatDecided { .. } => "🏆",
+            Transition::PhaseEntered { .. } => "👹",
+            Transition::Composite(parts) => parts
+                .iter()
+                .find(|part| !part.is_quiet())
+                .map_or("📜", Transition::emoji),
         }
     }
 
@@ -108,19 +347,61 @@ impl Transition {
         match self {
             Transition::ActionEconomyUsed { .. } => true,
             Transition::AdvanceInitiative => true,
+            Transition::Composite(parts) => parts.iter().all(Transition::is_quiet),
             _ => false,
         }
     }
 
+    /// Gathers the transitions of one action into one, or `None` if there were none. A single
+    /// transition is kept as it is.
+    pub fn composite(mut parts: Vec<Transition>) -> Option<Transition> {
+        match parts.len() {
+            0 => None,
+            1 => parts.pop(),
+            _ => Some(Transition::Composite(parts)),
+        }
+    }
+
+    /// The transitions this one is made of: the parts of a [`Transition::Composite`], or just
+    /// itself. Anything looking for a particular kind of transition in a state tree should look
+    /// through these, in case actions were batched.
+    pub fn parts(&self) -> &[Transition] {
+        match self {
+            Transition::Composite(parts) => parts,
+            transition => std::slice::from_ref(transition),
+        }
+    }
+
     pub fn apply(&self, state: &mut State) -> anyhow::Result<()> {
         match self {
             Transition::Root => {}
             Transition::BeginCombat => {
                 state.current_turn_index = Some(0);
+                state.distance = state.encounter.engagement_distance;
+                state.actor_zones = match &state.encounter.zones {
+                    Some(zones) => state
+                        .actors
+                        .keys()
+                        .map(|&actor| (actor, zones.starting_zone(actor)))
+                        .collect(),
+                    None => Default::default(),
+                };
+                state.zone_progress.clear();
+                state.engagements.clear();
+                state.outcome = None;
+                state.final_round = None;
+                for actor in state.actors.values_mut() {
+                    actor.features_used.clear();
+                    actor.spent_abilities.clear();
+                    actor.phase = 0;
+                }
+                state.reserves = state.phase_reserves();
+                state.distance = state.opening_distance();
             }
             Transition::EndCombat => {
-                state.current_turn_index = None;
-
+                if state.current_turn_index.is_some() {
+                    state.final_round = Some(state.turn + 1);
+                }
                 state.turn = 0;
                 state.current_turn_index = None;
                 state.initiative_order.clear();
@@ -131,16 +412,14 @@ impl Transition {
             Transition::InitiativeRoll { actor, roll } => {
                 if let Some(actor) = state.actors.get_mut(actor) {
                     actor.initiative = Some(*roll);
+                    if let Some(mount) = actor.mount
+                        && let Some(mount) = state.actors.get_mut(&mount)
+                    {
+                        mount.initiative = Some(*roll);
+                    }
                 }
 
-                // recalculate initiative order
-                let mut initiatives = state
-                    .actors
-                    .iter()
-                    .map(|(id, actor)| (*id, actor.initiative.unwrap_or(0)))
-                    .collect::<Vec<(ActorId, i32)>>();
-                initiatives.sort_by(|a, b| b.1.cmp(&a.1)); // descending order
-                state.initiative_order = initiatives.into_iter().map(|(id, _)| id).collect();
+                state.recalculate_initiative_order();
             }
             Transition::BeginTurn { actor } => {
                 if let Some(actor) = state.actors.get_mut(actor) {
@@ -162,16 +441,35 @@ impl Transition {
             }
             Transition::HealthModification { target, delta } => {
                 if let Some(actor) = state.actors.get_mut(target) {
-                    actor.health += *delta;
+                    actor.modify_health(*delta);
                 }
             }
             Transition::StatModification {
                 target,
                 stat,
                 delta,
+                duration,
             } => {
-                if let Some(actor) = state.actors.get_mut(target) {
-                    *actor.stats.get_mut(*stat) += *delta as u32;
+                let modifier = StatModifier {
+                    stat: *stat,
+                    delta: *delta,
+                };
+                match duration {
+                    Some(rounds) => {
+                        let expiry =
+                            Expiry::StartOfTurnInRound(*target, state.turn + *rounds as u64);
+                        let effect = TimedEffect::new(*target, modifier, expiry, state);
+                        if let Some(actor) = state.actors.get_mut(target) {
+                            actor.stats.add_modifier(modifier);
+                            state.timed_effects.add(effect);
+                        }
+                    }
+                    None => {
+                        if let Some(actor) = state.actors.get_mut(target) {
+                            let value = actor.stats.base(*stat).saturating_add_signed(*delta);
+                            actor.stats.set(*stat, value);
+                        }
+                    }
                 }
             }
             Transition::ActionEconomyUsed {
@@ -182,6 +480,154 @@ impl Transition {
                     actor.action_economy.use_action(*action_type)?;
                 }
             }
+            Transition::ConditionApplied { target, condition } => {
+                if let Some(actor) = state.actors.get_mut(target) {
+                    actor.conditions.add(*condition);
+                }
+            }
+            Transition::ConditionRemoved { target, condition } => {
+                if let Some(actor) = state.actors.get_mut(target) {
+                    actor.conditions.remove(*condition);
+                }
+                state.timed_effects.remove_condition(*target, *condition);
+            }
+            Transition::TimedEffectApplied { effect } => {
+                if let Some(actor) = state.actors.get_mut(&effect.target) {
+                    match effect.change {
+                        TimedChange::Condition(condition) => actor.conditions.add(condition),
+                        TimedChange::Stat(modifier) => actor.stats.add_modifier(modifier),
+                    }
+                }
+                state.timed_effects.add(*effect);
+            }
+            Transition::TimedEffectExpired { effect } => {
+                state.timed_effects.remove(effect);
+                match effect.change {
+                    // another effect may still be giving the same condition
+                    TimedChange::Condition(condition) => {
+                        if !state.timed_effects.gives(effect.target, condition)
+                            && let Some(actor) = state.actors.get_mut(&effect.target)
+                        {
+                            actor.conditions.remove(condition);
+                        }
+                    }
+                    TimedChange::Stat(modifier) => {
+                        if let Some(actor) = state.actors.get_mut(&effect.target) {
+                            actor.stats.remove_modifier(&modifier);
+                        }
+                    }
+                }
+            }
+            Transition::ContestResolved { .. } => {}
+            Transition::AttackResolved { .. } => {}
+            Transition::RandomEffectRolled { .. } => {}
+            Transition::SavingThrowResolved { .. } => {}
+            Transition::StatModifiersExpired { .. } => {}
+            Transition::ActorKilled { actor, drop_loot } => {
+                state.fallen.insert(*actor);
+                if *drop_loot && let Some(actor) = state.actors.get_mut(actor) {
+                    let items = std::mem::take(&mut actor.inventory.items);
+                    actor.equipped_items.items.clear();
+                    let dropped = state.dropped.entry(actor.id).or_default();
+                    for (item, quantity) in items {
+                        state.loot.add_item(item, quantity);
+                        dropped.add_item(item, quantity);
+                    }
+                }
+            }
+            Transition::DistanceClosed { feet, .. } => {
+                if let Some(distance) = &mut state.distance {
+                    *distance = distance.saturating_sub(*feet);
+                }
+            }
+            Transition::AbilityUsed { actor, ability } => {
+                if let Some(actor) = state.actors.get_mut(actor)
+                    && actor
+                        .abilities
+                        .get(*ability)
+                        .is_some_and(|ability| ability.recharge.is_some())
+                {
+                    actor.spent_abilities.insert(*ability);
+                }
+            }
+            Transition::Engaged { actor, target } => {
+                state.engagements.insert(*actor, *target);
+            }
+            Transition::ZoneEntered { actor, zone } => {
+                let mount = state.get_actor(*actor).and_then(|actor| actor.mount);
+                let rider = state.rider_of(*actor);
+                for actor in [Some(*actor), mount, rider].into_iter().flatten() {
+                    state.actor_zones.insert(actor, *zone);
+                    state.zone_progress.remove(&actor);
+                }
+            }
+            Transition::ZoneApproached { actor, zone } => {
+                let mount = state.get_actor(*actor).and_then(|actor| actor.mount);
+                let rider = state.rider_of(*actor);
+                for actor in [Some(*actor), mount, rider].into_iter().flatten() {
+                    state.zone_progress.insert(actor, *zone);
+                }
+            }
+            Transition::HazardEntered { .. } => {}
+            Transition::EnvironmentalEffect { .. } | Transition::DamageTaken { .. } => {}
+            Transition::CombatDecided { outcome } => {
+                state.outcome = Some(*outcome);
+            }
+            Transition::Composite(parts) => {
+                for part in parts {
+                    part.apply(state)?;
+                }
+            }
+            Transition::PhaseEntered { actor, phase } => {
+                let current = state.current_actor();
+                if let Some(actor) = state.actors.get_mut(actor)
+                    && *phase >= actor.phase
+                {
+                    anyhow::ensure!(
+                        *phase < actor.phases.len(),
+                        "{} has no phase {}",
+                        actor.name,
+                        phase
+                    );
+                    let entered = actor.phase..*phase + 1;
+                    let spawned: Vec<ActorId> = actor.phases[entered]
+                        .iter()
+                        .flat_map(|phase| phase.spawn.iter().copied())
+                        .collect();
+                    actor.phase = *phase + 1;
+                    for spawned in spawned {
+                        state.reserves.remove(&spawned);
+                    }
+                    // reinforcements take their place in initiative without changing whose turn
+                    // it is
+                    if !state.initiative_order.is_empty() {
+                        state.recalculate_initiative_order();
+                        if let Some(current) = current {
+                            state.current_turn_index =
+                                state.initiative_order.iter().position(|&id| id == current);
+                        }
+                    }
+                }
+            }
+            Transition::RerollUsed { actor, resource } => {
+                if let Some(count) = state
+                    .actors
+                    .get_mut(actor)
+                    .and_then(|actor| actor.rerolls.get_mut(resource))
+                {
+                    *count = count.saturating_sub(1);
+                }
+            }
+            Transition::AbilityRecharged { actor, ability } => {
+                if let Some(actor) = state.actors.get_mut(actor) {
+                    actor.spent_abilities.remove(ability);
+                }
+            }
+            Transition::FeatureUsed { actor, feature } => {
+                if let Some(actor) = state.actors.get_mut(actor) {
+                    actor.features_used.insert(*feature);
+                }
+            }
         }
 
         Ok(())
@@ -218,13 +664,19 @@ impl Transition {
                 target,
                 stat,
                 delta,
+                duration,
             } => {
                 target.pretty_print(f, state)?;
                 write!(f, "'s {:?} is ", stat)?;
                 if *delta >= 0 {
-                    write!(f, "increased by {}", delta)
+                    write!(f, "increased by {}", delta)?;
                 } else {
-                    write!(f, "decreased by {}", delta.abs())
+                    write!(f, "decreased by {}", delta.abs())?;
+                }
+                match duration {
+                    Some(1) => write!(f, " for 1 round"),
+                    Some(rounds) => write!(f, " for {} rounds", rounds),
+                    None => Ok(()),
                 }
             }
             Transition::ActionEconomyUsed {
@@ -234,6 +686,221 @@ impl Transition {
                 target.pretty_print(f, state)?;
                 write!(f, " uses their {:?}", action_type)
             }
+            Transition::ConditionApplied { target, condition } => {
+                target.pretty_print(f, state)?;
+                write!(f, " is now ")?;
+                condition.pretty_print(f, state)
+            }
+            Transition::ConditionRemoved { target, condition } => {
+                target.pretty_print(f, state)?;
+                write!(f, " is no longer ")?;
+                condition.pretty_print(f, state)
+            }
+            Transition::TimedEffectApplied { effect } => {
+                effect.target.pretty_print(f, state)?;
+                write!(f, " is now ")?;
+                effect.change.pretty_print(f, state)?;
+                write!(f, " until ")?;
+                effect.expiry.pretty_print(f, state)
+            }
+            Transition::TimedEffectExpired { effect } => {
+                effect.target.pretty_print(f, state)?;
+                write!(f, " is no longer ")?;
+                effect.change.pretty_print(f, state)
+            }
+            Transition::ContestResolved {
+                initiator,
+                target,
+                contest,
+                success,
+            } => {
+                initiator.pretty_print(f, state)?;
+                if *success {
+                    write!(f, " wins a {:?} contest against ", contest)?;
+                } else {
+                    write!(f, " loses a {:?} contest against ", contest)?;
+                }
+                target.pretty_print(f, state)
+            }
+            Transition::AttackResolved {
+                attacker,
+                target,
+                hit,
+                crit,
+            } => {
+                attacker.pretty_print(f, state)?;
+                match (hit, crit) {
+                    (true, true) => write!(f, " critically hits ")?,
+                    (true, false) => write!(f, " hits ")?,
+                    (false, _) => write!(f, " misses ")?,
+                }
+                target.pretty_print(f, state)
+            }
+            Transition::FeatureUsed { actor, feature } => {
+                actor.pretty_print(f, state)?;
+                write!(f, " uses {:?}", feature)
+            }
+            Transition::RandomEffectRolled {
+                actor,
+                table,
+                entry,
+            } => {
+                actor.pretty_print(f, state)?;
+                let rolled = state
+                    .get_actor(*actor)
+                    .and_then(|actor| actor.random_effects.get(*table));
+                match rolled {
+                    Some(table) => {
+                        write!(f, " rolls on {}: ", table.name)?;
+                        match table.entries.get(*entry) {
+                            Some(entry) => write!(f, "{}", entry.name),
+                            None => write!(f, "<Entry {}>", entry),
+                        }
+                    }
+                    None => write!(f, " rolls on <Table {}>: <Entry {}>", table, entry),
+                }
+            }
+            Transition::SavingThrowResolved {
+                actor,
+                save,
+                dc,
+                success,
+            } => {
+                actor.pretty_print(f, state)?;
+                if *success {
+                    write!(f, " succeeds on a DC {} {:?} save", dc, save)
+                } else {
+                    write!(f, " fails a DC {} {:?} save", dc, save)
+                }
+            }
+            Transition::StatModifiersExpired { target } => {
+                target.pretty_print(f, state)?;
+                write!(f, "'s stat modifiers wear off")
+            }
+            Transition::ActorKilled { actor, drop_loot } => {
+                actor.pretty_print(f, state)?;
+                if *drop_loot {
+                    write!(f, " is killed and drops their gear")
+                } else {
+                    write!(f, " is killed")
+                }
+            }
+            Transition::DistanceClosed { actor, feet } => {
+                actor.pretty_print(f, state)?;
+                write!(f, " closes {feet} ft")
+            }
+            Transition::Engaged { actor, target } => {
+                actor.pretty_print(f, state)?;
+                write!(f, " engages ")?;
+                target.pretty_print(f, state)
+            }
+            Transition::ZoneEntered { actor, zone } => {
+                actor.pretty_print(f, state)?;
+                match state
+                    .encounter
+                    .zones
+                    .as_ref()
+                    .and_then(|zones| zones.name(*zone))
+                {
+                    Some(name) => write!(f, " moves to {name}"),
+                    None => write!(f, " moves to zone {}", zone.0),
+                }
+            }
+            Transition::ZoneApproached { actor, zone } => {
+                actor.pretty_print(f, state)?;
+                match state
+                    .encounter
+                    .zones
+                    .as_ref()
+                    .and_then(|zones| zones.name(*zone))
+                {
+                    Some(name) => write!(f, " struggles toward {name}"),
+                    None => write!(f, " struggles toward zone {}", zone.0),
+                }
+            }
+            Transition::HazardEntered { actor, zone } => {
+                actor.pretty_print(f, state)?;
+                match state
+                    .encounter
+                    .zones
+                    .as_ref()
+                    .and_then(|zones| zones.hazards.get(zone))
+                {
+                    Some(hazard) => write!(f, " runs into {}", hazard.name),
+                    None => write!(f, " runs into a hazard in zone {}", zone.0),
+                }
+            }
+            Transition::DamageTaken {
+                target,
+                damage_type,
+                rolled,
+                dealt,
+            } => {
+                target.pretty_print(f, state)?;
+                let damage_type = format!("{damage_type:?}").to_lowercase();
+                match dealt.cmp(rolled) {
+                    std::cmp::Ordering::Equal => write!(f, " takes {dealt} {damage_type} damage"),
+                    std::cmp::Ordering::Less => write!(
+                        f,
+                        " resists {} of {rolled} {damage_type} damage",
+                        rolled - dealt
+                    ),
+                    std::cmp::Ordering::Greater => {
+                        write!(f, " is vulnerable and takes {dealt} {damage_type} damage")
+                    }
+                }
+            }
+            Transition::CombatDecided { outcome } => outcome.pretty_print(f, state),
+            Transition::Composite(parts) => {
+                // one line, like a combat log entry, leaving out the bookkeeping
+                let mut parts = parts.iter().filter(|part| !part.is_quiet()).peekable();
+                if parts.peek().is_none() {
+                    return write!(f, "Nothing happens");
+                }
+                for (i, part) in parts.enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    part.pretty_print(f, state)?;
+                }
+                Ok(())
+            }
+            Transition::PhaseEntered { actor, phase } => {
+                actor.pretty_print(f, state)?;
+                match state
+                    .get_actor(*actor)
+                    .and_then(|actor| actor.phases.get(*phase))
+                {
+                    Some(phase) => write!(f, " enters phase: {}", phase.name),
+                    None => write!(f, " enters phase {}", phase + 1),
+                }
+            }
+            Transition::EnvironmentalEffect { effect } => {
+                match state.encounter.environment.get(*effect) {
+                    Some(effect) => write!(f, "{} takes effect", effect.name),
+                    None => write!(f, "Environmental effect {} takes effect", effect),
+                }
+            }
+            Transition::RerollUsed { actor, resource } => {
+                actor.pretty_print(f, state)?;
+                match resource {
+                    RerollResource::Inspiration => write!(f, " spends inspiration to reroll"),
+                    RerollResource::LuckPoint => write!(f, " spends a luck point to reroll"),
+                }
+            }
+            Transition::AbilityUsed { actor, ability }
+            | Transition::AbilityRecharged { actor, ability } => {
+                actor.pretty_print(f, state)?;
+                let name = state
+                    .get_actor(*actor)
+                    .and_then(|actor| actor.abilities.get(*ability))
+                    .map_or_else(|| format!("<Ability {}>", ability), |a| a.name.clone());
+                if matches!(self, Transition::AbilityUsed { .. }) {
+                    write!(f, " uses {}", name)
+                } else {
+                    write!(f, "'s {} recharges", name)
+                }
+            }
         }
     }
 }
@@ -0,0 +1,325 @@
+//! How much acting early in initiative matters: damage dealt and survival by initiative position,
+//! and how often each group wins when it acts first, each with a 95% confidence interval.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::actor::ActorId,
+    simulation::{environment::EnvironmentFiring, state::State, transition::Transition},
+    utils::{
+        Ratio,
+        stats::{Estimate, Moments, Z_95},
+    },
+};
+
+/// Every actor that acted from one initiative position.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionStats {
+    /// Actor-combats in this position.
+    pub count: u64,
+    pub damage_dealt: u64,
+    /// The sum of each actor-combat's damage squared, for the variance.
+    pub damage_sum_of_squares: f64,
+    /// Actor-combats that ended with the actor not dead.
+    pub survived: u64,
+}
+
+impl PositionStats {
+    fn record(&mut self, damage: u64, survived: bool) {
+        self.count += 1;
+        self.damage_dealt += damage;
+        self.damage_sum_of_squares += (damage as f64).powi(2);
+        self.survived += survived as u64;
+    }
+
+    fn merge(&mut self, other: &PositionStats) {
+        self.count += other.count;
+        self.damage_dealt += other.damage_dealt;
+        self.damage_sum_of_squares += other.damage_sum_of_squares;
+        self.survived += other.survived;
+    }
+
+    /// Damage each actor in this position dealt per combat.
+    pub fn damage(&self) -> Estimate {
+        Estimate::mean(&Moments {
+            count: self.count,
+            sum: self.damage_dealt as f64,
+            sum_of_squares: self.damage_sum_of_squares,
+        })
+    }
+
+    pub fn survival(&self) -> Estimate {
+        Estimate::proportion(Ratio::new(self.survived, self.count))
+    }
+}
+
+/// How often a group won when one of its actors took the first turn, and when one didn't.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirstMover {
+    pub first: Ratio,
+    pub later: Ratio,
+}
+
+impl FirstMover {
+    /// How much more often the group wins when it acts first, in win probability. Positive means
+    /// going first helps.
+    pub fn advantage(&self) -> Estimate {
+        let (first, later) = (self.first.to_f64(), self.later.to_f64());
+        let value = first - later;
+        if self.first.denominator == 0 || self.later.denominator == 0 {
+            return Estimate {
+                value,
+                low: -1.0,
+                high: 1.0,
+            };
+        }
+        let variance = first * (1.0 - first) / self.first.denominator as f64
+            + later * (1.0 - later) / self.later.denominator as f64;
+        let margin = Z_95 * variance.sqrt();
+        Estimate {
+            value,
+            low: (value - margin).max(-1.0),
+            high: (value + margin).min(1.0),
+        }
+    }
+}
+
+/// The turn order and damage dealt in the combat in progress.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct CombatTurnOrder {
+    order: Vec<ActorId>,
+    damage: BTreeMap<ActorId, u64>,
+    environment: EnvironmentFiring,
+}
+
+/// Damage and survival by initiative position, 0 being whoever acts first, and the win rate of
+/// each group with and without the first turn.
+///
+/// The turn order is read off each combat's initiative rolls. Like
+/// [`ContributionStats`](crate::simulation::contribution::ContributionStats), damage is credited to
+/// the actor whose turn it is, and damage from environmental effects isn't credited to anyone.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnOrderStats {
+    pub combats: u64,
+    /// Every actor's results, by the position it acted from.
+    pub positions: BTreeMap<usize, PositionStats>,
+    /// Each actor's results by position, for when actors on the same side differ a lot.
+    pub actors: BTreeMap<ActorId, BTreeMap<usize, PositionStats>>,
+    pub groups: BTreeMap<u32, FirstMover>,
+    #[serde(skip)]
+    current: CombatTurnOrder,
+}
+
+impl TurnOrderStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin_combat(&mut self) {
+        self.combats += 1;
+        self.current = CombatTurnOrder::default();
+    }
+
+    /// Follows the turn order and damage dealt, as of `transition` being applied to `state`.
+    pub fn observe(&mut self, state: &State, transition: &Transition) {
+        self.current.environment.observe(transition);
+        match transition {
+            Transition::InitiativeRoll { .. } => {
+                self.current.order = state.initiative_order.clone();
+            }
+            Transition::HealthModification { target, delta }
+                if *delta < 0 && !self.current.environment.is_firing() =>
+            {
+                let source = state
+                    .current_turn_index
+                    .and_then(|index| state.initiative_order.get(index));
+                if let Some(source) = source
+                    && source != target
+                {
+                    *self.current.damage.entry(*source).or_default() += delta.unsigned_abs() as u64;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Tallies the combat that ended in `state`.
+    pub fn end_combat(&mut self, state: &State) {
+        let current = std::mem::take(&mut self.current);
+        for (position, id) in current.order.iter().enumerate() {
+            let Some(actor) = state.get_actor(*id) else {
+                continue;
+            };
+            let damage = current.damage.get(id).copied().unwrap_or_default();
+            let survived = !actor.is_dead();
+            self.positions
+                .entry(position)
+                .or_default()
+                .record(damage, survived);
+            self.actors
+                .entry(*id)
+                .or_default()
+                .entry(position)
+                .or_default()
+                .record(damage, survived);
+        }
+
+        let Some(first) = current
+            .order
+            .first()
+            .and_then(|id| state.get_actor(*id))
+            .map(|actor| actor.group)
+        else {
+            return;
+        };
+        let winner = state.outcome.and_then(|outcome| outcome.winner);
+        for group in state.group_ids() {
+            let record = self.groups.entry(group).or_default();
+            let ratio = match group == first {
+                true => &mut record.first,
+                false => &mut record.later,
+            };
+            ratio.merge(&Ratio::new((winner == Some(group)) as u64, 1));
+        }
+    }
+
+    /// Adds another tally's combats into this one.
+    pub fn merge(&mut self, other: &TurnOrderStats) {
+        self.combats += other.combats;
+        for (position, stats) in &other.positions {
+            self.positions.entry(*position).or_default().merge(stats);
+        }
+        for (id, positions) in &other.actors {
+            let ours = self.actors.entry(*id).or_default();
+            for (position, stats) in positions {
+                ours.entry(*position).or_default().merge(stats);
+            }
+        }
+        for (group, other) in &other.groups {
+            let record = self.groups.entry(*group).or_default();
+            record.first.merge(&other.first);
+            record.later.merge(&other.later);
+        }
+    }
+
+    pub fn pretty_print(
+        &self,
+        f: &mut impl std::fmt::Write,
+        initial_state: &State,
+    ) -> std::fmt::Result {
+        writeln!(f, "Initiative over {} combats:", self.combats)?;
+        for (group, record) in &self.groups {
+            let advantage = record.advantage();
+            writeln!(
+                f,
+                "  {} wins {:.2}% going first ({}), {:.2}% otherwise ({}): {:+.2} points{}",
+                initial_state.group_name(*group),
+                record.first.to_f64() * 100.0,
+                record.first,
+                record.later.to_f64() * 100.0,
+                record.later,
+                advantage.value * 100.0,
+                match advantage.excludes_zero() {
+                    true => "",
+                    false => " (could be chance)",
+                }
+            )?;
+        }
+        for (position, stats) in &self.positions {
+            let survival = stats.survival();
+            writeln!(
+                f,
+                "  Position {}: damage {}, survival {:.2}% (95% CI {:.2}% to {:.2}%)",
+                position + 1,
+                stats.damage(),
+                survival.value * 100.0,
+                survival.low * 100.0,
+                survival.high * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        simulation::{integration::Integrator, roller::Roller},
+        test_utils::duel,
+    };
+
+    #[test]
+    fn test_estimates() {
+        let mut stats = PositionStats::default();
+        for damage in [4, 6, 8] {
+            stats.record(damage, true);
+        }
+        let damage = stats.damage();
+        assert_eq!(damage.value, 6.0);
+        // standard deviation 2, so a margin of 1.96 * 2 / sqrt(3)
+        assert!((damage.high - damage.value - 2.263).abs() < 1e-3);
+
+        // a single huge hit squares past u64 without overflowing
+        let mut huge = PositionStats::default();
+        huge.record(u64::MAX, false);
+        assert_eq!(huge.damage_sum_of_squares, (u64::MAX as f64).powi(2));
+
+        let mover = FirstMover {
+            first: Ratio::new(80, 100),
+            later: Ratio::new(40, 100),
+        };
+        assert!(mover.advantage().excludes_zero());
+        let close = FirstMover {
+            first: Ratio::new(6, 10),
+            later: Ratio::new(5, 10),
+        };
+        assert!(!close.advantage().excludes_zero());
+    }
+
+    #[test]
+    fn test_turn_order_stats() -> anyhow::Result<()> {
+        let (state, hero, _) = duel(6, "Goblin", 6);
+
+        let mut integrator = Integrator::new(60, Roller::test_rng(), state);
+        integrator.record_turn_order(true);
+        let results = integrator.run()?;
+        let stats = results.turn_order.as_ref().unwrap();
+        assert_eq!(stats.combats, 60);
+        assert_eq!(stats.positions[&0].count, 60);
+        assert_eq!(stats.positions[&1].count, 60);
+        let hero_positions = &stats.actors[&hero];
+        assert_eq!(hero_positions.values().map(|p| p.count).sum::<u64>(), 60);
+        let hero_record = stats.groups[&0];
+        assert_eq!(
+            hero_record.first.denominator + hero_record.later.denominator,
+            60
+        );
+        // somebody hits somebody every combat
+        assert!(
+            stats
+                .positions
+                .values()
+                .map(|p| p.damage_dealt)
+                .sum::<u64>()
+                > 0
+        );
+
+        let mut merged = results.clone();
+        merged.merge(&results)?;
+        let merged_stats = merged.turn_order.as_ref().unwrap();
+        assert_eq!(merged_stats.positions[&0].count, 120);
+        assert_eq!(
+            merged_stats.positions[&0].damage().value,
+            stats.positions[&0].damage().value
+        );
+
+        let mut report = String::new();
+        stats.pretty_print(&mut report, results.state_tree.initial_state())?;
+        assert!(report.contains("Position 1: damage"), "{}", report);
+
+        Ok(())
+    }
+}
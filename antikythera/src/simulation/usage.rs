@@ -0,0 +1,251 @@
+//! How often each actor used each of its actions, and on which rounds, for checking that policy
+//! weights produce the behavior they're meant to.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{
+        actions::{Action, ActionType},
+        actor::ActorId,
+        items::ItemId,
+        spells::SpellId,
+    },
+    simulation::{state::State, timeline::RoundHistogram},
+};
+
+/// An action, told apart finely enough to see which weapon, spell, item or ability was used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum UsedAction {
+    /// An action with nothing more specific to tell its uses apart, like dodging.
+    Action(ActionType),
+    Attack {
+        weapon: ItemId,
+    },
+    Spell {
+        spell: SpellId,
+    },
+    Item {
+        item: ItemId,
+    },
+    /// An index into the actor's abilities.
+    Ability {
+        index: usize,
+    },
+}
+
+impl UsedAction {
+    pub fn of(action: &Action) -> Self {
+        match action {
+            Action::Attack(attack) => UsedAction::Attack {
+                weapon: attack.weapon_used,
+            },
+            Action::CastSpell(cast) => UsedAction::Spell {
+                spell: cast.spell_used,
+            },
+            Action::UseItem(use_item) => UsedAction::Item {
+                item: use_item.item_used,
+            },
+            Action::UseAbility(ability) => UsedAction::Ability {
+                index: ability.ability,
+            },
+            action => UsedAction::Action(action.action_type()),
+        }
+    }
+
+    /// Names the action as `actor` would have used it, looking up abilities and items in `state`.
+    pub fn pretty_print(
+        &self,
+        f: &mut impl std::fmt::Write,
+        actor: ActorId,
+        state: &State,
+    ) -> std::fmt::Result {
+        match *self {
+            UsedAction::Action(action_type) => write!(f, "{:?}", action_type),
+            UsedAction::Attack { weapon } => {
+                write!(f, "Attack with ")?;
+                weapon.pretty_print(f, state)
+            }
+            UsedAction::Spell { spell } => write!(f, "Cast spell {}", spell.0),
+            UsedAction::Item { item } => {
+                write!(f, "Use ")?;
+                item.pretty_print(f, state)
+            }
+            UsedAction::Ability { index } => {
+                match state
+                    .get_actor(actor)
+                    .and_then(|actor| actor.abilities.get(index))
+                {
+                    Some(ability) => write!(f, "{}", ability.name),
+                    None => write!(f, "Ability {}", index),
+                }
+            }
+        }
+    }
+}
+
+/// Uses of one action by one actor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionCount {
+    pub action: UsedAction,
+    /// Uses on each round. Unlike most histograms, this counts every use, so an action used twice
+    /// in a round counts twice.
+    pub rounds: RoundHistogram,
+}
+
+/// Tallies which actions each actor actually took, across combats. Only actions that go ahead
+/// are counted: one an actor picks but can't carry out, like an attack on a target out of reach,
+/// isn't.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionUsage {
+    pub combats: u64,
+    /// Each actor's actions, most used first once the run is over.
+    pub actors: BTreeMap<ActorId, Vec<ActionCount>>,
+}
+
+impl ActionUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin_combat(&mut self) {
+        self.combats += 1;
+    }
+
+    pub fn record(&mut self, actor: ActorId, action: &Action, round: u64) {
+        self.count_mut(actor, UsedAction::of(action))
+            .rounds
+            .record(round);
+    }
+
+    fn count_mut(&mut self, actor: ActorId, action: UsedAction) -> &mut ActionCount {
+        let counts = self.actors.entry(actor).or_default();
+        let index = match counts.iter().position(|count| count.action == action) {
+            Some(index) => index,
+            None => {
+                counts.push(ActionCount {
+                    action,
+                    rounds: RoundHistogram::default(),
+                });
+                counts.len() - 1
+            }
+        };
+        &mut counts[index]
+    }
+
+    /// Puts each actor's most used actions first.
+    pub fn sort(&mut self) {
+        for counts in self.actors.values_mut() {
+            counts
+                .sort_by_key(|count| (std::cmp::Reverse(count.rounds.occurrences()), count.action));
+        }
+    }
+
+    /// Adds another run's tallies into these.
+    pub fn merge(&mut self, other: &ActionUsage) {
+        self.combats += other.combats;
+        for (actor, counts) in &other.actors {
+            for count in counts {
+                self.count_mut(*actor, count.action)
+                    .rounds
+                    .merge(&count.rounds);
+            }
+        }
+        self.sort();
+    }
+
+    /// Times `actor` used `action`, over every combat.
+    pub fn uses(&self, actor: ActorId, action: UsedAction) -> u64 {
+        self.actors
+            .get(&actor)
+            .and_then(|counts| counts.iter().find(|count| count.action == action))
+            .map_or(0, |count| count.rounds.occurrences())
+    }
+
+    /// Mean uses of `action` by `actor` per combat.
+    pub fn per_combat(&self, actor: ActorId, action: UsedAction) -> f64 {
+        self.uses(actor, action) as f64 / self.combats.max(1) as f64
+    }
+
+    pub fn pretty_print(
+        &self,
+        f: &mut impl std::fmt::Write,
+        initial_state: &State,
+    ) -> std::fmt::Result {
+        for (actor, counts) in &self.actors {
+            actor.pretty_print(f, initial_state)?;
+            writeln!(f, ":")?;
+            let total: u64 = counts.iter().map(|count| count.rounds.occurrences()).sum();
+            for count in counts {
+                let uses = count.rounds.occurrences();
+                write!(f, "  ")?;
+                count.action.pretty_print(f, *actor, initial_state)?;
+                write!(
+                    f,
+                    ": {:.2} per combat ({:.1}% of actions",
+                    uses as f64 / self.combats.max(1) as f64,
+                    100.0 * uses as f64 / total.max(1) as f64
+                )?;
+                if let Some((round, _)) = count.rounds.most_common() {
+                    write!(f, ", mostly on round {}", round)?;
+                }
+                writeln!(f, ")")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        simulation::{integration::Integrator, roller::Roller},
+        test_utils::duel,
+    };
+
+    #[test]
+    fn test_action_usage() -> anyhow::Result<()> {
+        let (state, hero, ogre) = duel(12, "Ogre", 12);
+
+        let mut integrator = Integrator::new(50, Roller::test_rng(), state.clone());
+        integrator.record_action_usage(true);
+        let results = integrator.run()?;
+        let usage = results.action_usage.as_ref().unwrap();
+        assert_eq!(usage.combats, 50);
+
+        // both sides only strike or do nothing, and someone has to strike to win
+        let strike = UsedAction::Action(ActionType::UnarmedStrike);
+        let wait = UsedAction::Action(ActionType::Wait);
+        for actor in [hero, ogre] {
+            assert!(
+                usage.actors[&actor]
+                    .iter()
+                    .all(|count| count.action == strike || count.action == wait)
+            );
+        }
+        assert!(usage.per_combat(hero, strike) + usage.per_combat(ogre, strike) >= 1.0);
+        assert_eq!(usage.uses(hero, UsedAction::Action(ActionType::Dodge)), 0);
+        let strikes = usage.actors[&hero]
+            .iter()
+            .find(|count| count.action == strike)
+            .unwrap();
+        assert!(strikes.rounds.counts[&1] > 0);
+
+        let mut merged = results.clone();
+        merged.merge(&results)?;
+        let merged_usage = merged.action_usage.as_ref().unwrap();
+        assert_eq!(merged_usage.combats, 100);
+        assert_eq!(
+            merged_usage.uses(hero, strike),
+            2 * usage.uses(hero, strike)
+        );
+
+        let mut report = String::new();
+        usage.pretty_print(&mut report, &state)?;
+        assert!(report.contains("UnarmedStrike"), "{}", report);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+//! How well each weapon did: attacks, hits, crits and damage, against each target and each armor
+//! class it was swung at, for comparing gear by what it actually does in combat.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{actor::ActorId, items::ItemId},
+    simulation::state::State,
+    utils::{Ratio, stats::Estimate},
+};
+
+/// Attacks made with a weapon and what came of them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttackTally {
+    pub attacks: u64,
+    pub hits: u64,
+    pub crits: u64,
+    /// Hit points taken off targets on hits, after their resistances. Damage past 0 HP isn't
+    /// counted.
+    pub damage_dealt: u64,
+}
+
+impl AttackTally {
+    fn record(&mut self, hit: bool, crit: bool, damage: u64) {
+        self.attacks += 1;
+        self.hits += hit as u64;
+        self.crits += crit as u64;
+        self.damage_dealt += damage;
+    }
+
+    fn merge(&mut self, other: &AttackTally) {
+        self.attacks += other.attacks;
+        self.hits += other.hits;
+        self.crits += other.crits;
+        self.damage_dealt += other.damage_dealt;
+    }
+
+    pub fn hit_rate(&self) -> Estimate {
+        Estimate::proportion(Ratio::new(self.hits, self.attacks))
+    }
+
+    pub fn crit_rate(&self) -> Estimate {
+        Estimate::proportion(Ratio::new(self.crits, self.attacks))
+    }
+
+    /// Mean damage per attack, counting misses as 0.
+    pub fn damage_per_attack(&self) -> f64 {
+        self.damage_dealt as f64 / self.attacks.max(1) as f64
+    }
+
+    pub fn damage_per_hit(&self) -> f64 {
+        self.damage_dealt as f64 / self.hits.max(1) as f64
+    }
+}
+
+/// Everything one weapon did, in total and broken down.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeaponRecord {
+    pub total: AttackTally,
+    pub targets: BTreeMap<ActorId, AttackTally>,
+    /// By the target's AC at the time of the attack, cover included.
+    pub armor_classes: BTreeMap<i32, AttackTally>,
+}
+
+impl WeaponRecord {
+    fn merge(&mut self, other: &WeaponRecord) {
+        self.total.merge(&other.total);
+        for (target, tally) in &other.targets {
+            self.targets.entry(*target).or_default().merge(tally);
+        }
+        for (armor_class, tally) in &other.armor_classes {
+            self.armor_classes
+                .entry(*armor_class)
+                .or_default()
+                .merge(tally);
+        }
+    }
+}
+
+/// Tallies every weapon attack across combats, by weapon item. Unarmed strikes aren't counted.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeaponStats {
+    pub weapons: BTreeMap<ItemId, WeaponRecord>,
+}
+
+impl WeaponStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an attack with `weapon` on `target`, which had `armor_class`, dealing `damage` if
+    /// it hit.
+    pub fn record(
+        &mut self,
+        weapon: ItemId,
+        target: ActorId,
+        armor_class: i32,
+        hit: bool,
+        crit: bool,
+        damage: u64,
+    ) {
+        let record = self.weapons.entry(weapon).or_default();
+        record.total.record(hit, crit, damage);
+        record
+            .targets
+            .entry(target)
+            .or_default()
+            .record(hit, crit, damage);
+        record
+            .armor_classes
+            .entry(armor_class)
+            .or_default()
+            .record(hit, crit, damage);
+    }
+
+    /// Adds another run's tallies into these.
+    pub fn merge(&mut self, other: &WeaponStats) {
+        for (weapon, record) in &other.weapons {
+            self.weapons.entry(*weapon).or_default().merge(record);
+        }
+    }
+
+    pub fn pretty_print(
+        &self,
+        f: &mut impl std::fmt::Write,
+        initial_state: &State,
+    ) -> std::fmt::Result {
+        let line = |f: &mut dyn std::fmt::Write, tally: &AttackTally| {
+            let hit_rate = tally.hit_rate();
+            writeln!(
+                f,
+                "{} attacks, {:.1}% hit (95% CI {:.1}% to {:.1}%), {:.1}% crit, {:.2} damage per attack",
+                tally.attacks,
+                hit_rate.value * 100.0,
+                hit_rate.low * 100.0,
+                hit_rate.high * 100.0,
+                tally.crit_rate().value * 100.0,
+                tally.damage_per_attack()
+            )
+        };
+        for (weapon, record) in &self.weapons {
+            weapon.pretty_print(f, initial_state)?;
+            write!(f, ": ")?;
+            line(f, &record.total)?;
+            for (target, tally) in &record.targets {
+                write!(f, "  vs ")?;
+                target.pretty_print(f, initial_state)?;
+                write!(f, ": ")?;
+                line(f, tally)?;
+            }
+            for (armor_class, tally) in &record.armor_classes {
+                write!(f, "  vs AC {}: ", armor_class)?;
+                line(f, tally)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        simulation::{integration::Integrator, roller::Roller},
+        test_utils::{add_armed, fighter, goblin, longsword},
+    };
+
+    #[test]
+    fn test_weapon_stats() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = add_armed(&mut state, fighter("Hero"), "Longsword", longsword());
+        let goblin = state.add_actor(goblin());
+        let sword = *state
+            .get_actor(hero)
+            .unwrap()
+            .inventory
+            .items
+            .keys()
+            .next()
+            .unwrap();
+
+        let mut integrator = Integrator::new(40, Roller::test_rng(), state);
+        integrator.record_weapon_stats(true);
+        let results = integrator.run()?;
+        let stats = results.weapon_stats.as_ref().unwrap();
+        assert_eq!(stats.weapons.len(), 1);
+        let record = &stats.weapons[&sword];
+        // the goblin dies at 0 HP, so the sword lands at least once a combat
+        assert!(record.total.hits >= 40);
+        assert!(record.total.attacks >= record.total.hits);
+        assert!(record.total.hits >= record.total.crits);
+        assert_eq!(record.targets[&goblin], record.total);
+        assert_eq!(record.armor_classes[&15], record.total);
+        // 1d8+3, or 2d8+3 on a crit
+        let per_hit = record.total.damage_per_hit();
+        assert!((4.0..=19.0).contains(&per_hit), "{}", per_hit);
+
+        let mut merged = results.clone();
+        merged.merge(&results)?;
+        let merged_record = &merged.weapon_stats.as_ref().unwrap().weapons[&sword];
+        assert_eq!(merged_record.total.attacks, 2 * record.total.attacks);
+        assert_eq!(merged_record.armor_classes[&15].hits, 2 * record.total.hits);
+
+        let mut report = String::new();
+        stats.pretty_print(&mut report, results.state_tree.initial_state())?;
+        assert!(report.contains("vs AC 15: "), "{}", report);
+        Ok(())
+    }
+}
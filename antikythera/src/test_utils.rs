@@ -0,0 +1,184 @@
+//! Helpers for writing simulation tests, enabled by the `test-utils` feature.
+//!
+//! Alongside these, the feature makes the canned fixtures used by this crate's own tests public:
+//! [`Actor::test_actor`], [`Weapon::test_sword`], [`Roller::test_rng`] and [`Roller::scripted`].
+
+use crate::{
+    rules::{
+        actions::ActionType,
+        actor::{Actor, ActorBuilder, ActorId},
+        death::DeathBehavior,
+        items::{ItemInner, Weapon, WeaponBuilder, WeaponProficiency, WeaponType},
+        stats::Stat,
+    },
+    simulation::{
+        integration::Integrator,
+        policy::{Policy, PolicyBuilder},
+        query::{OutcomeConditionProbability, Query},
+        roller::Roller,
+        state::State,
+        state_tree::StateTree,
+    },
+    utils::Ratio,
+};
+
+/// A longsword dealing 1d8+3, for a +3 STR wielder.
+pub fn longsword() -> Weapon {
+    WeaponBuilder::new(WeaponType::Longsword)
+        .damage("1d8+3")
+        .critical_damage("2d8+3")
+        .build()
+}
+
+/// A shortbow dealing 1d6+2 out to 80 feet, for a +2 DEX wielder.
+pub fn shortbow() -> Weapon {
+    WeaponBuilder::new(WeaponType::Shortbow)
+        .damage("1d6+2")
+        .critical_damage("2d6+2")
+        .range(80)
+        .build()
+}
+
+/// A level 1 fighter in group 0 who always attacks: 16 STR, 12 HP, AC 16.
+pub fn fighter(name: &str) -> Actor {
+    let mut actor = ActorBuilder::new(name)
+        .stat(Stat::Strength, 16)
+        .stat(Stat::Constitution, 14)
+        .max_health(12)
+        .weapon_proficiency(WeaponType::Longsword, WeaponProficiency::Proficient)
+        .weapon_proficiency(WeaponType::Shortbow, WeaponProficiency::Proficient)
+        .build();
+    actor.armor_class = 16;
+    actor.policy = attacker_policy();
+    actor
+}
+
+/// A goblin in group 1 who always attacks and dies at 0 HP: 14 DEX, 7 HP, AC 15.
+pub fn goblin() -> Actor {
+    let mut actor = ActorBuilder::new("Goblin")
+        .group(1)
+        .npc(true)
+        .stat(Stat::Strength, 8)
+        .stat(Stat::Dexterity, 14)
+        .max_health(7)
+        .death_behavior(DeathBehavior::InstantDeath)
+        .build();
+    actor.armor_class = 15;
+    actor.policy = attacker_policy();
+    actor
+}
+
+/// Attacks with a weapon when it has one, and falls back to unarmed strikes.
+fn attacker_policy() -> Policy {
+    PolicyBuilder::new()
+        .action_weight(ActionType::Attack, 100)
+        .action_weight(ActionType::UnarmedStrike, 1)
+        .build()
+}
+
+/// Only throws unarmed strikes, so even actors with no weapons or spells finish their combats.
+pub fn brawl_policy() -> Policy {
+    PolicyBuilder::new()
+        .action_weight(ActionType::UnarmedStrike, 1)
+        .build()
+}
+
+/// Gives groups 0 and 1 [`brawl_policy`], for every actor without a policy of its own.
+pub fn brawl(state: &mut State) {
+    state.set_group_policy(0, brawl_policy());
+    state.set_group_policy(1, brawl_policy());
+}
+
+/// A "Hero" in group 0 brawling `monster` in group 1, who dies at 0 HP. Returns the state and
+/// both of their IDs.
+pub fn duel(hero_health: i32, monster: &str, monster_health: i32) -> (State, ActorId, ActorId) {
+    let mut state = State::new();
+    let hero = state.add_actor(ActorBuilder::new("Hero").max_health(hero_health).build());
+    let monster = state.add_actor(
+        ActorBuilder::new(monster)
+            .group(1)
+            .max_health(monster_health)
+            .death_behavior(DeathBehavior::InstantDeath)
+            .build(),
+    );
+    brawl(&mut state);
+    (state, hero, monster)
+}
+
+/// Adds `actor` to the state holding `weapon`, and returns its ID.
+pub fn add_armed(state: &mut State, mut actor: Actor, name: &str, weapon: Weapon) -> ActorId {
+    let weapon = state.add_item(name, ItemInner::Weapon(weapon));
+    actor.give_item(weapon, 1);
+    state.add_actor(actor)
+}
+
+/// Runs `combats` combats of `state` with [`Roller::test_rng`] and returns the state tree.
+pub fn run_combats(state: State, combats: usize) -> anyhow::Result<StateTree> {
+    Ok(Integrator::new(combats, Roller::test_rng(), state)
+        .run()?
+        .state_tree)
+}
+
+/// Asserts that a terminal-state probability query is within `tolerance` of `expected`.
+#[track_caller]
+pub fn assert_terminal_probability(
+    state_tree: &StateTree,
+    query: impl Query<Output = Ratio>,
+    expected: f64,
+    tolerance: f64,
+) {
+    let actual = query
+        .query(state_tree)
+        .unwrap_or_else(|e| panic!("query failed: {e}"))
+        .to_f64();
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "terminal probability {actual:.4} isn't within {tolerance} of {expected}"
+    );
+}
+
+/// Asserts that no combat in the tree ended in a state matching `condition`.
+#[track_caller]
+pub fn assert_never(state_tree: &StateTree, condition: impl Fn(&State) -> bool + 'static) {
+    assert_terminal_probability(
+        state_tree,
+        OutcomeConditionProbability::new(condition),
+        0.0,
+        0.0,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripted_roller() -> anyhow::Result<()> {
+        let mut roller = Roller::scripted([20, 1, 4]);
+        assert!(roller.roll(&"1d20+5".into())?.is_critical_success());
+        assert_eq!(roller.roll(&"1d20".into())?.total, 1);
+        assert_eq!(roller.roll(&"1d6+1".into())?.total, 5);
+        // past the script, rolls are random again
+        assert!((1..=8).contains(&roller.d(8)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_assertion_helpers() -> anyhow::Result<()> {
+        let mut state = State::new();
+        let hero = add_armed(&mut state, fighter("Hero"), "Longsword", longsword());
+        add_armed(&mut state, goblin(), "Shortbow", shortbow());
+
+        let tree = run_combats(state, 200)?;
+        let hero_wins = OutcomeConditionProbability::new(move |state: &State| {
+            state.get_actor(hero).is_some_and(|hero| hero.is_alive())
+        });
+        assert_terminal_probability(&tree, hero_wins, 0.9, 0.1);
+        assert_never(&tree, move |state| {
+            state
+                .get_actor(hero)
+                .is_some_and(|hero| hero.overkill > 0 && hero.health > 0)
+        });
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+pub mod stats;
+
 use serde::{Deserialize, Serialize};
 
 /// A cell that protects its inner value from being directly mutated except
@@ -49,3 +51,65 @@ impl<T> std::ops::Deref for ProtectedCell<T> {
         &self.value
     }
 }
+
+/// An exact fraction of hit counts, like the share of combats that ended a certain way.
+///
+/// Results keep counts as integers all the way through merging and summarizing, so two runs
+/// that saw the same combats report the same ratio bit for bit, whatever order their shards were
+/// merged in. Convert with [`Ratio::to_f64`] only to present it.
+///
+/// ```rust
+/// # use antikythera::utils::Ratio;
+/// let mut wins = Ratio::new(3, 10);
+/// wins.merge(&Ratio::new(4, 10));
+/// assert_eq!(wins, Ratio::new(7, 20));
+/// assert_eq!(wins.to_string(), "7/20");
+/// assert_eq!(Ratio::new(5, 20).reduced(), Ratio::new(1, 4));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Ratio {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl Ratio {
+    pub fn new(numerator: u64, denominator: u64) -> Self {
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// The ratio as a float, or 0 if nothing was counted.
+    pub fn to_f64(&self) -> f64 {
+        if self.denominator > 0 {
+            self.numerator as f64 / self.denominator as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Adds another set of counts to this one, as when merging the shards of a run.
+    pub fn merge(&mut self, other: &Ratio) {
+        self.numerator += other.numerator;
+        self.denominator += other.denominator;
+    }
+
+    /// The same fraction in lowest terms.
+    pub fn reduced(&self) -> Ratio {
+        let (mut a, mut b) = (self.numerator, self.denominator);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        match a {
+            0 => *self,
+            gcd => Ratio::new(self.numerator / gcd, self.denominator / gcd),
+        }
+    }
+}
+
+impl std::fmt::Display for Ratio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
@@ -0,0 +1,148 @@
+//! Means, proportions and their confidence intervals, for reports that say how sure they are.
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::Ratio;
+
+/// The z-score for a two-sided 95% confidence interval.
+pub const Z_95: f64 = 1.96;
+
+/// Running totals for a mean and its variance, with each value weighted, say by how many combats
+/// ended that way.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Moments {
+    pub count: u64,
+    pub sum: f64,
+    pub sum_of_squares: f64,
+}
+
+impl Moments {
+    pub fn add(&mut self, value: f64, weight: u64) {
+        self.count += weight;
+        self.sum += value * weight as f64;
+        self.sum_of_squares += value * value * weight as f64;
+    }
+
+    /// The mean, or 0 if nothing was added.
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count.max(1) as f64
+    }
+
+    /// The sample variance, or 0 with fewer than two values.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        let n = self.count as f64;
+        ((self.sum_of_squares - self.sum * self.sum / n) / (n - 1.0)).max(0.0)
+    }
+
+    /// The variance of the mean itself, which shrinks as more values come in.
+    pub fn variance_of_mean(&self) -> f64 {
+        self.variance() / self.count.max(1) as f64
+    }
+}
+
+/// A number measured across combats, with its 95% confidence interval.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    pub value: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Estimate {
+    /// A proportion, with its Wilson score interval, which stays sensible near 0 and 1 and for
+    /// small samples.
+    pub fn proportion(ratio: Ratio) -> Self {
+        let n = ratio.denominator as f64;
+        if n == 0.0 {
+            return Self::default();
+        }
+        let p = ratio.to_f64();
+        let z2 = Z_95 * Z_95;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+        Self {
+            value: p,
+            low: (center - margin).max(0.0),
+            high: (center + margin).min(1.0),
+        }
+    }
+
+    /// A mean, with its normal-approximation interval.
+    pub fn mean(moments: &Moments) -> Self {
+        if moments.count == 0 {
+            return Self::default();
+        }
+        let mean = moments.mean();
+        let margin = Z_95 * moments.variance_of_mean().sqrt();
+        Self {
+            value: mean,
+            low: mean - margin,
+            high: mean + margin,
+        }
+    }
+
+    /// Whether the interval leaves out zero, so the difference it measures is unlikely to be chance.
+    pub fn excludes_zero(&self) -> bool {
+        self.low > 0.0 || self.high < 0.0
+    }
+}
+
+impl std::fmt::Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} (95% CI {:.2} to {:.2})",
+            self.value, self.low, self.high
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimates() {
+        let half = Estimate::proportion(Ratio::new(50, 100));
+        assert!((half.low - 0.404).abs() < 1e-3 && (half.high - 0.596).abs() < 1e-3);
+        let never = Estimate::proportion(Ratio::new(0, 10));
+        assert_eq!(never.low, 0.0);
+        assert!(never.high > 0.2 && never.high < 0.35);
+
+        let mut moments = Moments::default();
+        moments.add(4.0, 1);
+        moments.add(6.0, 1);
+        moments.add(8.0, 1);
+        let mean = Estimate::mean(&moments);
+        assert_eq!(mean.value, 6.0);
+        assert_eq!(moments.variance(), 4.0);
+        // standard deviation 2, so a margin of 1.96 * 2 / sqrt(3)
+        assert!((mean.high - mean.value - 2.263).abs() < 1e-3);
+
+        // weights count as that many copies of the value
+        let mut weighted = Moments::default();
+        weighted.add(4.0, 2);
+        let mut repeated = Moments::default();
+        repeated.add(4.0, 1);
+        repeated.add(4.0, 1);
+        assert_eq!(weighted, repeated);
+
+        let one = Moments {
+            count: 1,
+            sum: 5.0,
+            sum_of_squares: 25.0,
+        };
+        assert_eq!(
+            Estimate::mean(&one),
+            Estimate {
+                value: 5.0,
+                low: 5.0,
+                high: 5.0
+            }
+        );
+        assert_eq!(Estimate::mean(&Moments::default()), Estimate::default());
+    }
+}
@@ -0,0 +1,230 @@
+//! Golden-file regression tests of full combat traces.
+//!
+//! Each scenario is run for a few seeded combats, and the sequence of transitions is compared against
+//! `tests/golden/<scenario>.json`. Any change to the combat rules that alters a trace will fail here
+//! and has to be re-approved by regenerating the golden files:
+//!
+//! ```sh
+//! UPDATE_GOLDEN=1 cargo test -p antikythera --test golden
+//! ```
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use antikythera::prelude::*;
+
+const SEED: u64 = 0x5eed;
+const COMBATS: usize = 3;
+
+type Trace = Vec<Vec<Transition>>;
+
+/// Records every transition, one list per combat.
+struct TraceRecorder {
+    trace: Arc<Mutex<Trace>>,
+}
+
+impl Hook for TraceRecorder {
+    fn on_transition(&mut self, _state: &State, transition: &Transition) {
+        let mut trace = self.trace.lock().unwrap();
+        if matches!(transition, Transition::BeginCombat) {
+            trace.push(Vec::new());
+        }
+        if let Some(combat) = trace.last_mut() {
+            combat.push(transition.clone());
+        }
+    }
+}
+
+fn record_trace(state: State) -> anyhow::Result<Trace> {
+    let trace = Arc::new(Mutex::new(Vec::new()));
+    let mut integrator = Integrator::new(COMBATS, Roller::from_seed(SEED), state);
+    integrator.add_hook(TraceRecorder {
+        trace: trace.clone(),
+    });
+    integrator.run()?;
+    let trace = trace.lock().unwrap().clone();
+    Ok(trace)
+}
+
+fn check_golden(name: &str, state: State) -> anyhow::Result<()> {
+    let trace = record_trace(state)?;
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.json", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, serde_json::to_string_pretty(&trace)? + "\n")?;
+        return Ok(());
+    }
+
+    let source = std::fs::read_to_string(&path).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read golden file {} ({}); run with UPDATE_GOLDEN=1 to create it",
+            path.display(),
+            e
+        )
+    })?;
+    let expected: Trace = serde_json::from_str(&source)?;
+
+    for (combat, (expected, actual)) in expected.iter().zip(&trace).enumerate() {
+        if let Some(step) = expected.iter().zip(actual).position(|(e, a)| e != a) {
+            anyhow::bail!(
+                "{}: combat {} diverges at transition {}: expected {:?}, got {:?}\n\
+                 If this rule change is intentional, rerun with UPDATE_GOLDEN=1",
+                name,
+                combat,
+                step,
+                expected[step],
+                actual[step]
+            );
+        }
+        anyhow::ensure!(
+            expected.len() == actual.len(),
+            "{}: combat {} has {} transitions, expected {}\n\
+             If this rule change is intentional, rerun with UPDATE_GOLDEN=1",
+            name,
+            combat,
+            actual.len(),
+            expected.len()
+        );
+    }
+    anyhow::ensure!(
+        expected.len() == trace.len(),
+        "{}: ran {} combats, expected {}",
+        name,
+        trace.len(),
+        expected.len()
+    );
+
+    Ok(())
+}
+
+fn goblin(group: u32) -> Actor {
+    ActorBuilder::new("Goblin")
+        .group(group)
+        .stat(Stat::Strength, 8)
+        .stat(Stat::Dexterity, 14)
+        .stat(Stat::Constitution, 10)
+        .saving_throw_proficiency(SavingThrow::Dexterity, true)
+        .max_health(7)
+        .level(1)
+        .build()
+}
+
+fn attack_policy() -> Policy {
+    PolicyBuilder::new()
+        .action_weight(ActionType::Attack, 10)
+        .action_weight(ActionType::UnarmedStrike, 1)
+        .build()
+}
+
+#[test]
+fn golden_hero_vs_goblins() -> anyhow::Result<()> {
+    let mut state = State::new();
+    let sword = WeaponBuilder::new(WeaponType::Longsword)
+        .attack_bonus(1)
+        .damage("1d8+3")
+        .critical_damage("2d8+3")
+        .build();
+    let sword = state.add_item("Longsword", ItemInner::Weapon(sword));
+
+    let mut hero = ActorBuilder::new("Hero")
+        .group(0)
+        .stat(Stat::Strength, 16)
+        .stat(Stat::Dexterity, 12)
+        .stat(Stat::Constitution, 14)
+        .saving_throw_proficiency(SavingThrow::Constitution, true)
+        .weapon_proficiency(WeaponType::Longsword, WeaponProficiency::Proficient)
+        .max_health(12)
+        .level(1)
+        .build();
+    hero.give_item(sword, 1);
+    hero.policy = attack_policy();
+    state.add_actor(hero);
+
+    for _ in 0..2 {
+        let mut goblin = goblin(1);
+        goblin.give_item(sword, 1);
+        goblin.policy = attack_policy();
+        state.add_actor(goblin);
+    }
+
+    check_golden("hero_vs_goblins", state)
+}
+
+#[test]
+fn golden_grapplers() -> anyhow::Result<()> {
+    let mut state = State::new();
+    let policy = PolicyBuilder::new()
+        .action_weight(ActionType::UnarmedStrike, 4)
+        .action_weight(ActionType::Grapple, 2)
+        .action_weight(ActionType::Shove, 2)
+        .action_weight(ActionType::EscapeGrapple, 3)
+        .build();
+
+    for (name, group) in [("Wrestler", 0), ("Brawler", 1)] {
+        let mut actor = ActorBuilder::new(name)
+            .group(group)
+            .stat(Stat::Strength, 16)
+            .stat(Stat::Dexterity, 12)
+            .stat(Stat::Constitution, 14)
+            .skill_proficiency(Skill::Athletics, SkillProficiency::Proficient)
+            .max_health(15)
+            .level(2)
+            .build();
+        actor.policy = policy.clone();
+        state.add_actor(actor);
+    }
+
+    check_golden("grapplers", state)
+}
+
+#[test]
+fn golden_features() -> anyhow::Result<()> {
+    let mut state = State::new();
+    let greataxe = WeaponBuilder::new(WeaponType::Greataxe)
+        .attack_bonus(0)
+        .damage("1d12+3")
+        .critical_damage("2d12+3")
+        .build();
+    let greataxe = state.add_item("Greataxe", ItemInner::Weapon(greataxe));
+
+    let mut fighter = ActorBuilder::new("Fighter")
+        .group(0)
+        .stat(Stat::Strength, 16)
+        .stat(Stat::Constitution, 14)
+        .weapon_proficiency(WeaponType::Greataxe, WeaponProficiency::Proficient)
+        .feature(Feature::GreatWeaponFighting)
+        .feature(Feature::GreatWeaponMaster)
+        .max_health(28)
+        .level(3)
+        .build();
+    fighter.give_item(greataxe, 1);
+    fighter.policy = PolicyBuilder::new()
+        .action_weight(ActionType::Attack, 1)
+        .power_attack(PowerAttackHeuristic {
+            max_target_ac: Some(13),
+            with_advantage: true,
+        })
+        .build();
+    state.add_actor(fighter);
+
+    let mut orc = ActorBuilder::new("Half-Orc")
+        .group(1)
+        .stat(Stat::Strength, 16)
+        .stat(Stat::Constitution, 16)
+        .weapon_proficiency(WeaponType::Greataxe, WeaponProficiency::Proficient)
+        .feature(Feature::RelentlessEndurance)
+        .max_health(15)
+        .level(1)
+        .build();
+    orc.give_item(greataxe, 1);
+    orc.policy = attack_policy();
+    state.add_actor(orc);
+
+    check_golden("features", state)
+}